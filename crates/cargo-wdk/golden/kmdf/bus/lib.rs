@@ -0,0 +1,133 @@
+#![no_std]
+
+extern crate alloc;
+
+use wdk::{
+   string::UnicodeString,
+   wdf::{ChildDescription, ChildDeviceInit, ChildIdentification, ChildList},
+};
+use wdk_sys::{
+   call_unsafe_wdf_function_binding,
+   NTSTATUS,
+   PCUNICODE_STRING,
+   PDRIVER_OBJECT,
+   PWDFDEVICE_INIT,
+   STATUS_SUCCESS,
+   ULONG,
+   WDFDEVICE,
+   WDFDRIVER,
+   WDF_DRIVER_CONFIG,
+   WDF_NO_HANDLE,
+   WDF_NO_OBJECT_ATTRIBUTES,
+};
+
+#[cfg(not(test))]
+extern crate wdk_panic;
+
+#[cfg(not(test))]
+use wdk_alloc::WdkAllocator;
+
+#[cfg(not(test))]
+#[global_allocator]
+static GLOBAL_ALLOCATOR: WdkAllocator = WdkAllocator;
+
+/// Identifies a child device enumerated by this bus driver
+#[derive(Clone, Copy)]
+struct ChildId {
+   serial_number: u32,
+}
+
+impl ChildIdentification for ChildId {
+   fn create_device(&self, _child_list: &ChildList, child_init: ChildDeviceInit<'_>) -> NTSTATUS {
+      match create_child_device(self, child_init) {
+         Ok(()) => STATUS_SUCCESS,
+         Err(nt_status) => nt_status,
+      }
+   }
+}
+
+/// Assigns the IDs of `child` and creates its PDO
+fn create_child_device(
+   child: &ChildId,
+   mut child_init: ChildDeviceInit<'_>,
+) -> Result<(), NTSTATUS> {
+   // TODO: replace with the hardware ID that the INF of the child's function
+   // driver matches
+   let device_id = UnicodeString::try_from("RustBus\\Child")?;
+   let instance_id = UnicodeString::try_from(alloc::format!("{}", child.serial_number).as_str())?;
+   child_init.assign_device_id(device_id.as_unicode_str())?;
+   child_init.add_hardware_id(device_id.as_unicode_str())?;
+   child_init.assign_instance_id(instance_id.as_unicode_str())?;
+   child_init.create_device()?;
+   Ok(())
+}
+
+// SAFETY: "DriverEntry" is the required symbol name for Windows driver entry points.
+// No other function in this compilation unit exports this name, preventing symbol conflicts.
+#[unsafe(export_name = "DriverEntry")] // WDF expects a symbol with the name DriverEntry
+pub unsafe extern "system" fn driver_entry(
+   driver: PDRIVER_OBJECT,
+   registry_path: PCUNICODE_STRING,
+) -> NTSTATUS {
+   let mut driver_config = WDF_DRIVER_CONFIG {
+      Size: core::mem::size_of::<WDF_DRIVER_CONFIG>() as ULONG,
+      EvtDriverDeviceAdd: Some(evt_driver_device_add),
+      ..WDF_DRIVER_CONFIG::default()
+   };
+
+   // SAFETY: `driver` and `registry_path` are provided by `DriverEntry` and are
+   // never null, and `driver_config` is a valid `WDF_DRIVER_CONFIG`
+   unsafe {
+      call_unsafe_wdf_function_binding!(
+         WdfDriverCreate,
+         driver,
+         registry_path,
+         WDF_NO_OBJECT_ATTRIBUTES,
+         &mut driver_config,
+         WDF_NO_HANDLE.cast::<WDFDRIVER>(),
+      )
+   }
+}
+
+extern "C" fn evt_driver_device_add(
+   _driver: WDFDRIVER,
+   mut device_init: PWDFDEVICE_INIT,
+) -> NTSTATUS {
+   let mut child_list_config = ChildList::config::<ChildId>();
+   // SAFETY: `device_init` is provided by `EvtDriverDeviceAdd` and is never null
+   unsafe {
+      call_unsafe_wdf_function_binding!(
+         WdfFdoInitSetDefaultChildListConfig,
+         device_init,
+         &mut child_list_config,
+         WDF_NO_OBJECT_ATTRIBUTES,
+      );
+   }
+
+   let mut device: WDFDEVICE = WDF_NO_HANDLE.cast();
+   let nt_status;
+   // SAFETY: `device_init` is provided by `EvtDriverDeviceAdd` and is never null
+   unsafe {
+      nt_status = call_unsafe_wdf_function_binding!(
+         WdfDeviceCreate,
+         &mut device_init,
+         WDF_NO_OBJECT_ATTRIBUTES,
+         &mut device,
+      );
+   }
+   if !wdk::nt_success(nt_status) {
+      return nt_status;
+   }
+
+   // SAFETY: `device` was just created with a default child list configuration
+   let child_list = unsafe { ChildList::default_for_device(device) };
+
+   // TODO: replace with the children actually present on the bus
+   child_list.begin_scan();
+   let result = child_list.add_child(&mut ChildDescription::new(ChildId { serial_number: 1 }));
+   child_list.end_scan();
+   if let Err(nt_status) = result {
+      return nt_status;
+   }
+   nt_status
+}
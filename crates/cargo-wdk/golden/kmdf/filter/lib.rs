@@ -0,0 +1,75 @@
+#![no_std]
+
+use wdk_sys::{
+   call_unsafe_wdf_function_binding,
+   NTSTATUS,
+   PCUNICODE_STRING,
+   PDRIVER_OBJECT,
+   PWDFDEVICE_INIT,
+   ULONG,
+   WDFDEVICE,
+   WDFDRIVER,
+   WDF_DRIVER_CONFIG,
+   WDF_NO_HANDLE,
+   WDF_NO_OBJECT_ATTRIBUTES,
+};
+
+#[cfg(not(test))]
+extern crate wdk_panic;
+
+#[cfg(not(test))]
+use wdk_alloc::WdkAllocator;
+
+#[cfg(not(test))]
+#[global_allocator]
+static GLOBAL_ALLOCATOR: WdkAllocator = WdkAllocator;
+
+// SAFETY: "DriverEntry" is the required symbol name for Windows driver entry points.
+// No other function in this compilation unit exports this name, preventing symbol conflicts.
+#[unsafe(export_name = "DriverEntry")] // WDF expects a symbol with the name DriverEntry
+pub unsafe extern "system" fn driver_entry(
+   driver: PDRIVER_OBJECT,
+   registry_path: PCUNICODE_STRING,
+) -> NTSTATUS {
+   let mut driver_config = WDF_DRIVER_CONFIG {
+      Size: core::mem::size_of::<WDF_DRIVER_CONFIG>() as ULONG,
+      EvtDriverDeviceAdd: Some(evt_driver_device_add),
+      ..WDF_DRIVER_CONFIG::default()
+   };
+
+   // SAFETY: `driver` and `registry_path` are provided by `DriverEntry` and are
+   // never null, and `driver_config` is a valid `WDF_DRIVER_CONFIG`
+   unsafe {
+      call_unsafe_wdf_function_binding!(
+         WdfDriverCreate,
+         driver,
+         registry_path,
+         WDF_NO_OBJECT_ATTRIBUTES,
+         &mut driver_config,
+         WDF_NO_HANDLE.cast::<WDFDRIVER>(),
+      )
+   }
+}
+
+extern "C" fn evt_driver_device_add(
+   _driver: WDFDRIVER,
+   mut device_init: PWDFDEVICE_INIT,
+) -> NTSTATUS {
+   // Requests the driver does not handle are forwarded to the next lower driver
+   // in the device stack
+   // SAFETY: `device_init` is provided by `EvtDriverDeviceAdd` and is never null
+   unsafe {
+      call_unsafe_wdf_function_binding!(WdfFdoInitSetFilter, device_init);
+   }
+
+   let mut device: WDFDEVICE = WDF_NO_HANDLE.cast();
+   // SAFETY: `device_init` is provided by `EvtDriverDeviceAdd` and is never null
+   unsafe {
+      call_unsafe_wdf_function_binding!(
+         WdfDeviceCreate,
+         &mut device_init,
+         WDF_NO_OBJECT_ATTRIBUTES,
+         &mut device,
+      )
+   }
+}
@@ -0,0 +1,217 @@
+#![no_std]
+
+use wdk::{
+   ControlCode,
+   RequiredAccess,
+   TransferMethod,
+   wdf::{IoQueueDispatchType, Request},
+};
+use wdk_sys::{
+   _WDF_TRI_STATE,
+   call_unsafe_wdf_function_binding,
+   FILE_DEVICE_KEYBOARD,
+   NTSTATUS,
+   PCUNICODE_STRING,
+   PDRIVER_OBJECT,
+   PWDFDEVICE_INIT,
+   STATUS_NOT_SUPPORTED,
+   STATUS_SUCCESS,
+   ULONG,
+   WDFDEVICE,
+   WDFDRIVER,
+   WDFQUEUE,
+   WDFREQUEST,
+   WDF_DRIVER_CONFIG,
+   WDF_IO_QUEUE_CONFIG,
+   WDF_NO_HANDLE,
+   WDF_NO_OBJECT_ATTRIBUTES,
+};
+
+#[cfg(not(test))]
+extern crate wdk_panic;
+
+#[cfg(not(test))]
+use wdk_alloc::WdkAllocator;
+
+#[cfg(not(test))]
+#[global_allocator]
+static GLOBAL_ALLOCATOR: WdkAllocator = WdkAllocator;
+
+/// `HID_CTL_CODE` of `hidport.h`
+const fn hid_ctl_code(function: ULONG) -> ControlCode {
+   ControlCode::new(
+      FILE_DEVICE_KEYBOARD,
+      function,
+      TransferMethod::Neither,
+      RequiredAccess::Any,
+   )
+}
+
+const IOCTL_HID_GET_DEVICE_DESCRIPTOR: ControlCode = hid_ctl_code(0);
+const IOCTL_HID_GET_REPORT_DESCRIPTOR: ControlCode = hid_ctl_code(1);
+const IOCTL_HID_GET_DEVICE_ATTRIBUTES: ControlCode = hid_ctl_code(9);
+
+// TODO: replace with the vendor ID, product ID and version number of the device
+const VENDOR_ID: u16 = 0xDEED;
+const PRODUCT_ID: u16 = 0xFEED;
+const VERSION_NUMBER: u16 = 0x0101;
+
+/// Report descriptor of the device: a vendor-defined collection with a 1-byte
+/// input report
+// TODO: describe the reports of the device
+const REPORT_DESCRIPTOR: [u8; 21] = [
+   0x06, 0x00, 0xFF, // Usage Page (Vendor Defined 0xFF00)
+   0x09, 0x01,       // Usage (0x01)
+   0xA1, 0x01,       // Collection (Application)
+   0x15, 0x00,       //   Logical Minimum (0)
+   0x26, 0xFF, 0x00, //   Logical Maximum (255)
+   0x75, 0x08,       //   Report Size (8)
+   0x95, 0x01,       //   Report Count (1)
+   0x09, 0x01,       //   Usage (0x01)
+   0x81, 0x02,       //   Input (Data, Variable, Absolute)
+   0xC0,             // End Collection
+];
+
+/// `HID_DESCRIPTOR` of the device, which points the HID class driver to
+/// `REPORT_DESCRIPTOR`
+const HID_DESCRIPTOR: [u8; 9] = {
+   let report_descriptor_length = (REPORT_DESCRIPTOR.len() as u16).to_le_bytes();
+   [
+      0x09,       // bLength
+      0x21,       // bDescriptorType: HID
+      0x00, 0x01, // bcdHID: 1.00
+      0x00,       // bCountry
+      0x01,       // bNumDescriptors
+      0x22,       // bReportType: report
+      report_descriptor_length[0],
+      report_descriptor_length[1],
+   ]
+};
+
+/// `HID_DEVICE_ATTRIBUTES` of the device
+const HID_DEVICE_ATTRIBUTES: [u8; 32] = {
+   let mut attributes = [0; 32];
+   attributes[0] = 32; // Size
+   let vendor_id = VENDOR_ID.to_le_bytes();
+   attributes[4] = vendor_id[0];
+   attributes[5] = vendor_id[1];
+   let product_id = PRODUCT_ID.to_le_bytes();
+   attributes[6] = product_id[0];
+   attributes[7] = product_id[1];
+   let version_number = VERSION_NUMBER.to_le_bytes();
+   attributes[8] = version_number[0];
+   attributes[9] = version_number[1];
+   attributes
+};
+
+// SAFETY: "DriverEntry" is the required symbol name for Windows driver entry points.
+// No other function in this compilation unit exports this name, preventing symbol conflicts.
+#[unsafe(export_name = "DriverEntry")] // WDF expects a symbol with the name DriverEntry
+pub unsafe extern "system" fn driver_entry(
+   driver: PDRIVER_OBJECT,
+   registry_path: PCUNICODE_STRING,
+) -> NTSTATUS {
+   let mut driver_config = WDF_DRIVER_CONFIG {
+      Size: core::mem::size_of::<WDF_DRIVER_CONFIG>() as ULONG,
+      EvtDriverDeviceAdd: Some(evt_driver_device_add),
+      ..WDF_DRIVER_CONFIG::default()
+   };
+
+   // SAFETY: `driver` and `registry_path` are provided by `DriverEntry` and are
+   // never null, and `driver_config` is a valid `WDF_DRIVER_CONFIG`
+   unsafe {
+      call_unsafe_wdf_function_binding!(
+         WdfDriverCreate,
+         driver,
+         registry_path,
+         WDF_NO_OBJECT_ATTRIBUTES,
+         &mut driver_config,
+         WDF_NO_HANDLE.cast::<WDFDRIVER>(),
+      )
+   }
+}
+
+extern "C" fn evt_driver_device_add(
+   _driver: WDFDRIVER,
+   mut device_init: PWDFDEVICE_INIT,
+) -> NTSTATUS {
+   // The driver is a lower filter of the `mshidkmdf` pass-through driver, which
+   // forwards the requests of the HID class driver to it
+   // SAFETY: `device_init` is provided by `EvtDriverDeviceAdd` and is never null
+   unsafe {
+      call_unsafe_wdf_function_binding!(WdfFdoInitSetFilter, device_init);
+   }
+
+   let mut device: WDFDEVICE = WDF_NO_HANDLE.cast();
+   // SAFETY: `device_init` is provided by `EvtDriverDeviceAdd` and is never null
+   let nt_status = unsafe {
+      call_unsafe_wdf_function_binding!(
+         WdfDeviceCreate,
+         &mut device_init,
+         WDF_NO_OBJECT_ATTRIBUTES,
+         &mut device,
+      )
+   };
+   if !wdk::nt_success(nt_status) {
+      return nt_status;
+   }
+
+   let mut queue_config = WDF_IO_QUEUE_CONFIG {
+      Size: core::mem::size_of::<WDF_IO_QUEUE_CONFIG>() as ULONG,
+      PowerManaged: _WDF_TRI_STATE::WdfUseDefault,
+      DefaultQueue: u8::from(true),
+      EvtIoInternalDeviceControl: Some(evt_io_internal_device_control),
+      ..WDF_IO_QUEUE_CONFIG::default()
+   };
+   IoQueueDispatchType::Parallel {
+      presented_requests_limit: None,
+   }
+   .apply(&mut queue_config);
+
+   let mut queue: WDFQUEUE = WDF_NO_HANDLE.cast();
+   // SAFETY: `device` was just created, and `queue_config` is a valid
+   // `WDF_IO_QUEUE_CONFIG`
+   unsafe {
+      call_unsafe_wdf_function_binding!(
+         WdfIoQueueCreate,
+         device,
+         &mut queue_config,
+         WDF_NO_OBJECT_ATTRIBUTES,
+         &mut queue,
+      )
+   }
+}
+
+extern "C" fn evt_io_internal_device_control(
+   _queue: WDFQUEUE,
+   request: WDFREQUEST,
+   _output_buffer_length: usize,
+   _input_buffer_length: usize,
+   io_control_code: ULONG,
+) {
+   // SAFETY: `request` is provided by `EvtIoInternalDeviceControl`, and the driver
+   // owns it until it completes it
+   let mut request = unsafe { Request::from_raw(request) };
+   let result = match ControlCode::from_raw(io_control_code) {
+      IOCTL_HID_GET_DEVICE_DESCRIPTOR => copy_to_output_buffer(&mut request, &HID_DESCRIPTOR),
+      IOCTL_HID_GET_REPORT_DESCRIPTOR => copy_to_output_buffer(&mut request, &REPORT_DESCRIPTOR),
+      IOCTL_HID_GET_DEVICE_ATTRIBUTES => {
+         copy_to_output_buffer(&mut request, &HID_DEVICE_ATTRIBUTES)
+      }
+      // TODO: handle IOCTL_HID_READ_REPORT and the other HID requests the device
+      // supports
+      _ => Err(STATUS_NOT_SUPPORTED),
+   };
+   match result {
+      Ok(length) => request.complete_with_information(STATUS_SUCCESS, length),
+      Err(nt_status) => request.complete(nt_status),
+   }
+}
+
+/// Copies `data` to the output buffer of `request`, and returns the number of
+/// bytes copied
+fn copy_to_output_buffer(request: &mut Request, data: &[u8]) -> Result<usize, NTSTATUS> {
+   let output_buffer = request.retrieve_output_buffer(data.len())?;
+   output_buffer[..data.len()].copy_from_slice(data);
+   Ok(data.len())
+}
@@ -0,0 +1,136 @@
+//! Test client for the golden_driver driver.
+//!
+//! Opens every present device that exposes the device interface of the driver.
+//! The interface GUID defaults to the one generated for the driver, and can be
+//! overridden on the command line, ex.
+//! `cargo run -- {01234567-89AB-CDEF-0123-456789ABCDEF}`.
+
+use std::{env, process::ExitCode};
+
+use windows::{
+   core::{GUID, PCWSTR},
+   Win32::{
+      Devices::DeviceAndDriverInstallation::{
+         CM_Get_Device_Interface_ListW,
+         CM_Get_Device_Interface_List_SizeW,
+         CM_GET_DEVICE_INTERFACE_LIST_PRESENT,
+         CR_SUCCESS,
+      },
+      Foundation::{CloseHandle, GENERIC_READ, GENERIC_WRITE, HANDLE},
+      Storage::FileSystem::{
+         CreateFileW,
+         FILE_ATTRIBUTE_NORMAL,
+         FILE_SHARE_READ,
+         FILE_SHARE_WRITE,
+         OPEN_EXISTING,
+      },
+   },
+};
+
+/// Device interface GUID of the driver, {3F6C2A1E-5B7D-4C9A-8E0F-1A2B3C4D5E6F}
+const GUID_DEVINTERFACE: GUID = GUID::from_u128(0x3F6C2A1E_5B7D_4C9A_8E0F_1A2B3C4D5E6F);
+
+fn main() -> ExitCode {
+   let interface_guid = match env::args().nth(1) {
+      Some(arg) => match parse_guid(&arg) {
+         Some(interface_guid) => interface_guid,
+         None => {
+            eprintln!("Invalid interface GUID: {arg}");
+            return ExitCode::FAILURE;
+         }
+      },
+      None => GUID_DEVINTERFACE,
+   };
+
+   let Some(device_paths) = device_interface_paths(&interface_guid) else {
+      eprintln!("Failed to list the devices with interface {interface_guid:?}");
+      return ExitCode::FAILURE;
+   };
+   if device_paths.is_empty() {
+      eprintln!("No device with interface {interface_guid:?} is present. Is the driver installed?");
+      return ExitCode::FAILURE;
+   }
+
+   let mut exit_code = ExitCode::SUCCESS;
+   for device_path in device_paths {
+      let display_path = String::from_utf16_lossy(&device_path[..device_path.len() - 1]);
+      // SAFETY: `device_path` is a null-terminated wide string that outlives the call
+      let handle = unsafe {
+         CreateFileW(
+            PCWSTR(device_path.as_ptr()),
+            (GENERIC_READ | GENERIC_WRITE).0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            None,
+            OPEN_EXISTING,
+            FILE_ATTRIBUTE_NORMAL,
+            HANDLE::default(),
+         )
+      };
+      match handle {
+         Ok(handle) => {
+            println!("Opened {display_path}");
+            // SAFETY: `handle` was opened above and is not used afterwards
+            let _ = unsafe { CloseHandle(handle) };
+         }
+         Err(error) => {
+            eprintln!("Failed to open {display_path}: {error}");
+            exit_code = ExitCode::FAILURE;
+         }
+      }
+   }
+   exit_code
+}
+
+/// Parses a GUID in registry format, with or without braces
+fn parse_guid(guid: &str) -> Option<GUID> {
+   let digits: String = guid
+      .trim_start_matches('{')
+      .trim_end_matches('}')
+      .split('-')
+      .collect();
+   if digits.len() != 32 {
+      return None;
+   }
+   u128::from_str_radix(&digits, 16).ok().map(GUID::from_u128)
+}
+
+/// Returns the null-terminated paths of the present devices that expose
+/// `interface_guid`
+fn device_interface_paths(interface_guid: &GUID) -> Option<Vec<Vec<u16>>> {
+   let mut len = 0;
+   // SAFETY: `len` and `interface_guid` are valid for the duration of the call
+   let result = unsafe {
+      CM_Get_Device_Interface_List_SizeW(
+         &mut len,
+         interface_guid,
+         PCWSTR::null(),
+         CM_GET_DEVICE_INTERFACE_LIST_PRESENT,
+      )
+   };
+   if result != CR_SUCCESS {
+      return None;
+   }
+
+   let mut buffer = vec![0u16; len as usize];
+   // SAFETY: `interface_guid` and `buffer` are valid for the duration of the call
+   let result = unsafe {
+      CM_Get_Device_Interface_ListW(
+         interface_guid,
+         PCWSTR::null(),
+         &mut buffer,
+         CM_GET_DEVICE_INTERFACE_LIST_PRESENT,
+      )
+   };
+   if result != CR_SUCCESS {
+      return None;
+   }
+
+   // The list is a sequence of null-terminated paths, terminated by an empty one
+   Some(
+      buffer
+         .split_inclusive(|&c| c == 0)
+         .take_while(|path| path.len() > 1)
+         .map(<[u16]>::to_vec)
+         .collect(),
+   )
+}
@@ -0,0 +1,27 @@
+use wdk_sys::{
+   GUID,
+   PDRIVER_OBJECT,
+   NTSTATUS,
+   PCUNICODE_STRING,
+};
+
+/// Device interface GUID of the driver, {3F6C2A1E-5B7D-4C9A-8E0F-1A2B3C4D5E6F}. Register
+/// it for the device with `WdfDeviceCreateDeviceInterface` so that the test client in
+/// the `test-client` folder can open the device.
+pub const GUID_DEVINTERFACE: GUID = GUID {
+   Data1: (GUID_DEVINTERFACE_VALUE >> 96) as u32,
+   Data2: (GUID_DEVINTERFACE_VALUE >> 80) as u16,
+   Data3: (GUID_DEVINTERFACE_VALUE >> 64) as u16,
+   Data4: (GUID_DEVINTERFACE_VALUE as u64).to_be_bytes(),
+};
+const GUID_DEVINTERFACE_VALUE: u128 = 0x3F6C2A1E_5B7D_4C9A_8E0F_1A2B3C4D5E6F;
+
+// SAFETY: "DriverEntry" is the required symbol name for Windows driver entry points.
+// No other function in this compilation unit exports this name, preventing symbol conflicts.
+#[unsafe(export_name = "DriverEntry")] // WDF expects a symbol with the name DriverEntry
+pub unsafe extern "system" fn driver_entry(
+   _driver: PDRIVER_OBJECT,
+   _registry_path: PCUNICODE_STRING,
+) -> NTSTATUS {
+   0
+}
@@ -25,6 +25,7 @@ pub struct BuildTask<'a> {
     package_name: &'a str,
     profile: Option<&'a Profile>,
     target_arch: Option<CpuArchitecture>,
+    target_dir: Option<&'a Path>,
     verbosity_level: clap_verbosity_flag::Verbosity,
     manifest_path: PathBuf,
     command_exec: &'a CommandExec,
@@ -39,6 +40,8 @@ impl<'a> BuildTask<'a> {
     /// * `working_dir` - The working directory for the build
     /// * `profile` - An optional profile for the build
     /// * `target_arch` - The target architecture for the build
+    /// * `target_dir` - An optional directory to build in instead of the target
+    ///   directory configured for cargo
     /// * `verbosity_level` - The verbosity level for logging
     /// * `command_exec` - The command execution provider
     ///
@@ -52,6 +55,7 @@ pub fn new(
         working_dir: &'a Path,
         profile: Option<&'a Profile>,
         target_arch: Option<CpuArchitecture>,
+        target_dir: Option<&'a Path>,
         verbosity_level: clap_verbosity_flag::Verbosity,
         command_exec: &'a CommandExec,
     ) -> Self {
@@ -64,6 +68,7 @@ pub fn new(
             package_name,
             profile,
             target_arch,
+            target_dir,
             verbosity_level,
             manifest_path: working_dir.join("Cargo.toml"),
             command_exec,
@@ -108,6 +113,10 @@ pub fn run(
             args.push("--target".to_string());
             args.push(to_target_triple(target_arch));
         }
+        if let Some(target_dir) = self.target_dir {
+            args.push("--target-dir".to_string());
+            args.push(target_dir.to_string_lossy().to_string());
+        }
         if let Some(flag) = trace::get_cargo_verbose_flags(self.verbosity_level) {
             args.push(flag.to_string());
         }
@@ -157,6 +166,7 @@ fn new_succeeds_for_valid_args() {
             &working_dir,
             Some(&profile),
             target_arch,
+            None,
             verbosity_level,
             &command_exec,
         );
@@ -164,6 +174,7 @@ fn new_succeeds_for_valid_args() {
         assert_eq!(build_task.package_name, package_name);
         assert_eq!(build_task.profile, Some(&profile));
         assert_eq!(build_task.target_arch, target_arch);
+        assert_eq!(build_task.target_dir, None);
         assert_eq!(build_task.manifest_path, working_dir.join("Cargo.toml"));
         assert_eq!(
             std::ptr::from_ref(build_task.command_exec),
@@ -190,6 +201,7 @@ fn new_panics_when_working_dir_is_not_absolute() {
             &working_dir,
             profile.as_ref(),
             target_arch,
+            None,
             verbosity_level,
             &command_exec,
         );
@@ -202,6 +214,7 @@ fn run_invokes_cargo_build_with_expected_args_and_returns_output() {
         let manifest_path_string = manifest_path.to_string_lossy().to_string();
         let profile = Profile::Release;
         let target_arch = CpuArchitecture::Amd64;
+        let target_dir = PathBuf::from("D:/shared/target");
         let verbosity = clap_verbosity_flag::Verbosity::default();
         let expected_args = vec![
             "build".to_string(),
@@ -214,6 +227,8 @@ fn run_invokes_cargo_build_with_expected_args_and_returns_output() {
             "release".to_string(),
             "--target".to_string(),
             "x86_64-pc-windows-msvc".to_string(),
+            "--target-dir".to_string(),
+            target_dir.to_string_lossy().to_string(),
         ];
         let expected_working_dir = working_dir.clone();
         let mut expected_stdout = br#"{"reason":"build-finished","success":true}"#.to_vec();
@@ -246,6 +261,7 @@ fn run_invokes_cargo_build_with_expected_args_and_returns_output() {
             &working_dir,
             Some(&profile),
             Some(target_arch),
+            Some(&target_dir),
             verbosity,
             &mock,
         );
@@ -287,6 +303,7 @@ fn run_returns_error_when_cargo_command_fails() {
             &working_dir,
             None,
             None,
+            None,
             clap_verbosity_flag::Verbosity::default(),
             &mock,
         );
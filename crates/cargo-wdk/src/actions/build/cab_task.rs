@@ -0,0 +1,383 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+//! Module that creates a cabinet (.cab) file from a driver package.
+//! This module defines the `CabTask` struct and its associated methods for
+//! creating a cab file laid out for Partner Center attestation signing
+//! submissions. The cab contains the driver package under a folder named after
+//! the driver and is accompanied by a manifest listing the contained files. The
+//! cab is optionally signed, as Partner Center only accepts signed submissions.
+
+use std::path::{Path, PathBuf};
+
+use mockall_double::double;
+use tracing::{debug, info, warn};
+use wdk_build::{CpuArchitecture, metadata::PackageConfig};
+
+#[double]
+use crate::providers::{exec::CommandExec, fs::Fs};
+use crate::{
+    actions::build::{
+        error::CabTaskError,
+        package_task::{WDR_LOCAL_TEST_CERT, signtool_sign_args},
+    },
+    providers::error::FileError,
+};
+
+#[derive(Debug)]
+pub struct CabTaskParams<'a> {
+    pub package_name: &'a str,
+    /// Directory containing the driver package folder, which the cab folder is
+    /// created next to
+    pub target_dir: &'a Path,
+    pub target_arch: &'a CpuArchitecture,
+    pub package_config: &'a PackageConfig,
+    /// Whether to sign the cab file with the signing certificate of the driver
+    /// package
+    pub sign: bool,
+}
+
+/// Creates a cab file for attestation signing from a packaged driver
+pub struct CabTask<'a> {
+    package_name: String,
+    arch: &'a CpuArchitecture,
+    package_config: &'a PackageConfig,
+    sign: bool,
+
+    // src paths
+    src_package_folder: PathBuf,
+
+    // destination paths
+    dest_cab_folder: PathBuf,
+    dest_cab_file_path: PathBuf,
+    dest_ddf_file_path: PathBuf,
+    dest_manifest_file_path: PathBuf,
+
+    // Injected deps
+    command_exec: &'a CommandExec,
+    fs: &'a Fs,
+}
+
+impl<'a> CabTask<'a> {
+    /// Creates a new instance of `CabTask`.
+    ///
+    /// # Arguments
+    /// * `params` - Struct containing the parameters for the cab task.
+    /// * `command_exec` - The provider for command execution.
+    /// * `fs` - The provider for file system operations.
+    ///
+    /// # Returns
+    /// * `Self` - A new instance of `CabTask`.
+    ///
+    /// # Panics
+    /// * If `params.target_dir` is not absolute
+    pub fn new(params: &CabTaskParams<'a>, command_exec: &'a CommandExec, fs: &'a Fs) -> Self {
+        debug!("Cab task params: {params:?}");
+        assert!(
+            params.target_dir.is_absolute(),
+            "Target directory path must be absolute. Input path: {}",
+            params.target_dir.display()
+        );
+        let package_name = params.package_name.replace('-', "_");
+
+        let src_package_folder = params.target_dir.join(format!("{package_name}_package"));
+
+        let dest_cab_folder = params.target_dir.join(format!("{package_name}_cab"));
+        let dest_cab_file_path = dest_cab_folder.join(format!("{package_name}.cab"));
+        let dest_ddf_file_path = dest_cab_folder.join(format!("{package_name}.ddf"));
+        let dest_manifest_file_path = dest_cab_folder.join(format!("{package_name}.cab.json"));
+
+        Self {
+            package_name,
+            arch: params.target_arch,
+            package_config: params.package_config,
+            sign: params.sign,
+            src_package_folder,
+            dest_cab_folder,
+            dest_cab_file_path,
+            dest_ddf_file_path,
+            dest_manifest_file_path,
+            command_exec,
+            fs,
+        }
+    }
+
+    /// Entry point method to create the cab file.
+    ///
+    /// # Returns
+    /// * `Result<(), CabTaskError>` - A result indicating success or failure.
+    ///
+    /// # Errors
+    /// * `CabTaskError::MissingDriverPackage` - If the driver package folder
+    ///   does not exist.
+    /// * `CabTaskError::MakecabCommand` - If there is an error creating the cab
+    ///   file using makecab.
+    /// * `CabTaskError::CabSignCommand` - If there is an error signing the cab
+    ///   file using signtool.
+    /// * `CabTaskError::FileIo` - If there is an error reading the driver
+    ///   package or writing the directive and manifest files.
+    pub fn run(&self) -> Result<(), CabTaskError> {
+        if !self.fs.exists(&self.src_package_folder) {
+            return Err(CabTaskError::MissingDriverPackage(
+                self.src_package_folder.clone(),
+            ));
+        }
+        if !self.fs.exists(&self.dest_cab_folder) {
+            self.fs.create_dir(&self.dest_cab_folder)?;
+        }
+        let src_file_paths = self.package_file_paths()?;
+
+        info!("Creating cab file: {}", self.dest_cab_file_path.display());
+        let ddf = ddf_content(
+            &self.dest_cab_file_path,
+            &self.package_name,
+            &src_file_paths,
+        );
+        self.fs
+            .write_to_file(&self.dest_ddf_file_path, ddf.as_bytes())?;
+        let ddf_file_path = self.dest_ddf_file_path.to_string_lossy();
+        if let Err(e) = self
+            .command_exec
+            .run("makecab", &["/f", &ddf_file_path], None, None)
+        {
+            return Err(CabTaskError::MakecabCommand(e));
+        }
+
+        if self.sign {
+            self.run_signtool_sign()?;
+        }
+        self.write_manifest(&src_file_paths)?;
+        Ok(())
+    }
+
+    // Returns the paths of the files of the driver package to put in the cab.
+    // Test certificates are left out as they have no place in a submission
+    fn package_file_paths(&self) -> Result<Vec<PathBuf>, FileError> {
+        let mut src_file_paths = vec![];
+        for entry in self.fs.read_dir_entries(&self.src_package_folder)? {
+            if !self.fs.dir_file_type(&entry)?.is_file() {
+                continue;
+            }
+            let src_file_path = entry.path();
+            if src_file_path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("cer"))
+            {
+                debug!("Leaving {} out of the cab", src_file_path.display());
+                continue;
+            }
+            src_file_paths.push(src_file_path);
+        }
+        src_file_paths.sort();
+        Ok(src_file_paths)
+    }
+
+    fn run_signtool_sign(&self) -> Result<(), CabTaskError> {
+        info!("Signing cab file using signtool");
+        let is_local_test_cert = self.package_config.cert_path.is_none()
+            && self.package_config.cert_thumbprint.is_none()
+            && self
+                .package_config
+                .cert_name
+                .as_deref()
+                .is_none_or(|cert_name| cert_name == WDR_LOCAL_TEST_CERT);
+        if is_local_test_cert {
+            warn!(
+                "Signing cab file with the local test certificate. Partner Center requires the \
+                 cab to be signed with the EV certificate registered for your account"
+            );
+        }
+        let args = signtool_sign_args(&self.package_config, &self.dest_cab_file_path);
+        let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+        if let Err(e) = self.command_exec.run("signtool", &args, None, None) {
+            return Err(CabTaskError::CabSignCommand(e));
+        }
+        Ok(())
+    }
+
+    fn write_manifest(&self, src_file_paths: &[PathBuf]) -> Result<(), FileError> {
+        info!("Writing cab manifest");
+        let files = src_file_paths
+            .iter()
+            .map(|path| cab_file_path(&self.package_name, path))
+            .collect::<Vec<_>>();
+        let manifest = serde_json::json!({
+            "driver": self.package_name,
+            "architecture": self.arch.to_string(),
+            "cab": self.dest_cab_file_path.file_name().map(|name| name.to_string_lossy()),
+            "signed": self.sign,
+            "files": files,
+        });
+        let content = serde_json::to_string_pretty(&manifest)
+            .expect("Cab manifest is always serializable to JSON");
+        self.fs
+            .write_to_file(&self.dest_manifest_file_path, content.as_bytes())
+    }
+}
+
+/// Returns the path of a driver package file inside the cab. Partner Center
+/// expects the files of each driver under a folder of their own.
+fn cab_file_path(cab_folder_name: &str, src_file_path: &Path) -> String {
+    format!(
+        "{cab_folder_name}/{}",
+        src_file_path
+            .file_name()
+            .expect("Package entries always have a file name")
+            .to_string_lossy()
+    )
+}
+
+/// Returns the content of the makecab directive file that puts the source files
+/// under `cab_folder_name` in the cab file at `cab_file_path`
+fn ddf_content(cab_file_path: &Path, cab_folder_name: &str, src_file_paths: &[PathBuf]) -> String {
+    let cab_dir = cab_file_path
+        .parent()
+        .expect("Cab file path always has a parent folder");
+    let cab_file_name = cab_file_path
+        .file_name()
+        .expect("Cab file path always has a file name");
+    let mut content = format!(
+        r".OPTION EXPLICIT
+.Set CabinetFileCountThreshold=0
+.Set FolderFileCountThreshold=0
+.Set FolderSizeThreshold=0
+.Set MaxCabinetSize=0
+.Set MaxDiskFileCount=0
+.Set MaxDiskSize=0
+.Set CompressionType=MSZIP
+.Set Cabinet=on
+.Set Compress=on
+.Set UniqueFiles=on
+.Set InfFileName=NUL
+.Set RptFileName=NUL
+.Set CabinetNameTemplate={}
+.Set DiskDirectoryTemplate={}
+.Set DestinationDir={cab_folder_name}
+",
+        cab_file_name.to_string_lossy(),
+        cab_dir.display(),
+    );
+    content.extend(
+        src_file_paths
+            .iter()
+            .map(|src_file_path| format!("\"{}\"\n", src_file_path.display())),
+    );
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use wdk_build::CpuArchitecture;
+
+    use super::*;
+
+    #[test]
+    fn new_succeeds_for_valid_args() {
+        let target_dir = PathBuf::from("C:/absolute/path/to/target/dir");
+        let arch = CpuArchitecture::Amd64;
+        let package_config = PackageConfig::default();
+        let params = CabTaskParams {
+            package_name: "test-package",
+            target_dir: &target_dir,
+            target_arch: &arch,
+            package_config: &package_config,
+            sign: true,
+        };
+        let command_exec = CommandExec::default();
+        let fs = Fs::default();
+        let task = CabTask::new(&params, &command_exec, &fs);
+
+        let cab_folder = target_dir.join("test_package_cab");
+        assert_eq!(task.package_name, "test_package");
+        assert!(task.sign);
+        assert_eq!(
+            task.src_package_folder,
+            target_dir.join("test_package_package")
+        );
+        assert_eq!(task.dest_cab_folder, cab_folder);
+        assert_eq!(task.dest_cab_file_path, cab_folder.join("test_package.cab"));
+        assert_eq!(task.dest_ddf_file_path, cab_folder.join("test_package.ddf"));
+        assert_eq!(
+            task.dest_manifest_file_path,
+            cab_folder.join("test_package.cab.json")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Target directory path must be absolute. Input path: \
+                               relative/path/to/target/dir")]
+    fn new_panics_when_target_dir_is_not_absolute() {
+        let target_dir = PathBuf::from("relative/path/to/target/dir");
+        let arch = CpuArchitecture::Amd64;
+        let package_config = PackageConfig::default();
+        let params = CabTaskParams {
+            package_name: "test_package",
+            target_dir: &target_dir,
+            target_arch: &arch,
+            package_config: &package_config,
+            sign: false,
+        };
+        let command_exec = CommandExec::default();
+        let fs = Fs::default();
+        CabTask::new(&params, &command_exec, &fs);
+    }
+
+    #[test]
+    fn run_fails_when_driver_package_is_missing() {
+        let target_dir = PathBuf::from("C:/abs/driver/target/debug");
+        let arch = CpuArchitecture::Amd64;
+        let package_config = PackageConfig::default();
+        let params = CabTaskParams {
+            package_name: "driver",
+            target_dir: &target_dir,
+            target_arch: &arch,
+            package_config: &package_config,
+            sign: true,
+        };
+        let command_exec = CommandExec::default();
+        let mut fs = Fs::default();
+        let expected_package_folder = target_dir.join("driver_package");
+        fs.expect_exists()
+            .withf(move |path| path == expected_package_folder)
+            .once()
+            .return_const(false);
+
+        let result = CabTask::new(&params, &command_exec, &fs).run();
+        assert!(
+            matches!(result, Err(CabTaskError::MissingDriverPackage(_))),
+            "Expected MissingDriverPackage error, got: {result:?}"
+        );
+    }
+
+    #[test]
+    fn ddf_content_places_files_under_driver_folder() {
+        let cab_file_path = PathBuf::from("C:/target/debug/driver_cab/driver.cab");
+        let src_file_paths = [
+            PathBuf::from("C:/target/debug/driver_package/driver.cat"),
+            PathBuf::from("C:/target/debug/driver_package/driver.inf"),
+            PathBuf::from("C:/target/debug/driver_package/driver.sys"),
+        ];
+        let ddf = ddf_content(&cab_file_path, "driver", &src_file_paths);
+
+        assert!(ddf.starts_with(".OPTION EXPLICIT\n"));
+        assert!(ddf.contains(".Set CabinetNameTemplate=driver.cab\n"));
+        assert!(ddf.contains(".Set DiskDirectoryTemplate=C:/target/debug/driver_cab\n"));
+        assert!(ddf.contains(".Set DestinationDir=driver\n"));
+        assert!(ddf.ends_with(
+            "\"C:/target/debug/driver_package/driver.cat\"\n\"C:/target/debug/driver_package/\
+             driver.inf\"\n\"C:/target/debug/driver_package/driver.sys\"\n"
+        ));
+    }
+
+    #[test]
+    fn cab_file_path_is_relative_to_driver_folder() {
+        assert_eq!(
+            cab_file_path(
+                "driver",
+                Path::new("C:/target/debug/driver_package/driver.sys")
+            ),
+            "driver/driver.sys"
+        );
+    }
+}
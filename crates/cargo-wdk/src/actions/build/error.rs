@@ -6,7 +6,10 @@
 
 use thiserror::Error;
 
-use crate::providers::error::{CommandError, FileError};
+use crate::{
+    actions::FailureCategory,
+    providers::error::{CommandError, FileError},
+};
 
 /// Errors for the build action layer
 #[derive(Error, Debug)]
@@ -29,12 +32,20 @@ pub enum BuildActionError {
     NotAWorkspaceMember(PathBuf),
     #[error(transparent)]
     PackageTask(#[from] PackageTaskError),
+    #[error(transparent)]
+    HlkTask(#[from] HlkTaskError),
+    #[error(transparent)]
+    CabTask(#[from] CabTaskError),
+    #[error(transparent)]
+    NugetTask(#[from] NugetTaskError),
     #[error("No valid rust projects in the current working directory: {0}")]
     NoValidRustProjectsInTheDirectory(PathBuf),
     #[error("One or more packages failed to build in the emulated workspace: {0}")]
-    OneOrMoreRustProjectsFailedToBuild(PathBuf),
+    OneOrMoreRustProjectsFailedToBuild(PathBuf, FailureCategory),
     #[error("One or more workspace members failed to build in the workspace: {0}")]
-    OneOrMoreWorkspaceMembersFailedToBuild(PathBuf),
+    OneOrMoreWorkspaceMembersFailedToBuild(PathBuf, FailureCategory),
+    #[error("Build failed for one or more target architectures: {0}")]
+    OneOrMoreTargetArchitecturesFailedToBuild(String, FailureCategory),
     #[error("Unsupported target arch: {0}")]
     UnsupportedArchitecture(String),
     #[error("Failed to detect target arch")]
@@ -43,6 +54,34 @@ pub enum BuildActionError {
     CannotDetermineTargetDir(String),
 }
 
+impl BuildActionError {
+    /// Returns the [`FailureCategory`] of the error. For failures of multiple
+    /// packages, this is the category of the first failure.
+    #[must_use]
+    pub const fn category(&self) -> FailureCategory {
+        match self {
+            Self::WdkBuildConfig(_) | Self::UnsupportedArchitecture(_) => {
+                FailureCategory::EnvironmentMissing
+            }
+            Self::CargoMetadataParse(_)
+            | Self::WdkMetadataParse(_)
+            | Self::NotAWorkspaceMember(_)
+            | Self::NoValidRustProjectsInTheDirectory(_) => FailureCategory::MetadataInvalid,
+            Self::BuildTask(_) | Self::CannotDetectTargetArch => FailureCategory::Build,
+            Self::PackageTask(e) => e.category(),
+            Self::HlkTask(_) | Self::CannotDetermineTargetDir(_) => FailureCategory::Packaging,
+            Self::CabTask(e) => e.category(),
+            Self::NugetTask(_) => FailureCategory::EnvironmentMissing,
+            Self::OneOrMoreRustProjectsFailedToBuild(_, category)
+            | Self::OneOrMoreWorkspaceMembersFailedToBuild(_, category)
+            | Self::OneOrMoreTargetArchitecturesFailedToBuild(_, category) => *category,
+            Self::NotAbsolute(..) | Self::FileIo(_) | Self::CommandExecution(_) => {
+                FailureCategory::Other
+            }
+        }
+    }
+}
+
 /// Errors for the low level build task layer
 #[derive(Error, Debug)]
 pub enum BuildTaskError {
@@ -76,12 +115,49 @@ pub enum PackageTaskError {
     CertGenerationInStoreCommand(#[source] CommandError),
     #[error("Error while acquiring mutex for generating certificate. HRESULT: {0:#x}")]
     CertMutexError(i32),
+    #[error("Certificate {1} not found in certificate store {0}")]
+    CertNotFoundInStore(String, String),
+    #[error("Missing certificate file: {0}")]
+    MissingCertFile(PathBuf),
+    #[error(
+        "Production signing requires a certificate name, thumbprint or file to be configured, as \
+         the local test certificate is not generated"
+    )]
+    MissingProductionCert,
     #[error("Error signing driver binary using signtool")]
     DriverBinarySignCommand(#[source] CommandError),
     #[error("Error verifying signed driver binary using signtool")]
     DriverBinarySignVerificationCommand(#[source] CommandError),
     #[error("Error verifying inf file using infverif")]
     InfVerificationCommand(#[source] CommandError),
+    #[error("Infverif reported {0} warning(s) for inf file {1}, which are treated as errors")]
+    InfVerificationWarnings(usize, PathBuf),
+    #[error("Invalid driver date in package metadata: {0}, expected format is MM/DD/YYYY")]
+    InvalidDriverDate(String),
+    #[error("Invalid catalog file decoration in package metadata: {0}")]
+    InvalidCatalogFileDecoration(String),
+    #[error("Invalid [Version] directive in package metadata: {0}")]
+    InvalidVersionDirective(String),
+    #[error(
+        "A certificate file cannot be combined with a certificate store, name or thumbprint, and \
+         a certificate thumbprint cannot be combined with a certificate name"
+    )]
+    ConflictingCertOptions,
+    #[error("A CSP and a key container must be set together, along with a certificate file")]
+    IncompleteCspOptions,
+    #[error("Invalid certificate thumbprint: {0}, expected the 40 hex digits of a SHA1 hash")]
+    InvalidCertThumbprint(String),
+    #[error("Invalid digest algorithm: {0}, expected one of SHA1, SHA256, SHA384 or SHA512")]
+    InvalidDigestAlgorithm(String),
+    #[error("Missing [Version] section in inf file: {0}")]
+    MissingVersionSection(PathBuf),
+    #[error("Invalid hardware ID in package metadata: {0}")]
+    InvalidHardwareId(String),
+    #[error(
+        "Inf file {0} uses the $HARDWARE_IDS$ token, but no hardware IDs are set in the package \
+         metadata"
+    )]
+    MissingHardwareIds(PathBuf),
 
     // TODO: We can make this specific error instead of generic one
     #[error(transparent)]
@@ -89,3 +165,87 @@ pub enum PackageTaskError {
     #[error(transparent)]
     FileIo(#[from] FileError),
 }
+
+impl PackageTaskError {
+    /// Returns the [`FailureCategory`] of the error
+    #[must_use]
+    pub const fn category(&self) -> FailureCategory {
+        match self {
+            Self::CreateCertFileFromStoreCommand(_)
+            | Self::VerifyCertExistsInStoreCommand(_)
+            | Self::VerifyCertExistsInStoreInvalidCommandOutput(_)
+            | Self::CertGenerationInStoreCommand(_)
+            | Self::CertMutexError(_)
+            | Self::CertNotFoundInStore(..)
+            | Self::MissingCertFile(_)
+            | Self::MissingProductionCert
+            | Self::DriverBinarySignCommand(_)
+            | Self::DriverBinarySignVerificationCommand(_) => FailureCategory::Signing,
+            Self::WdkBuildConfig(_) => FailureCategory::EnvironmentMissing,
+            Self::InvalidDriverDate(_)
+            | Self::InvalidCatalogFileDecoration(_)
+            | Self::InvalidVersionDirective(_)
+            | Self::InvalidHardwareId(_)
+            | Self::MissingHardwareIds(_)
+            | Self::ConflictingCertOptions
+            | Self::IncompleteCspOptions
+            | Self::InvalidCertThumbprint(_)
+            | Self::InvalidDigestAlgorithm(_) => FailureCategory::MetadataInvalid,
+            Self::MissingInxSrcFile(_)
+            | Self::MissingVersionSection(_)
+            | Self::StampinfCommand(_)
+            | Self::Inf2CatCommand(_)
+            | Self::InfVerificationCommand(_)
+            | Self::InfVerificationWarnings(..)
+            | Self::FileIo(_) => FailureCategory::Packaging,
+        }
+    }
+}
+
+/// Errors for the low level HLK artifacts task layer
+#[derive(Error, Debug)]
+pub enum HlkTaskError {
+    #[error("Missing driver package folder: {0}, HLK artifacts require a packaged driver")]
+    MissingDriverPackage(PathBuf),
+    #[error(transparent)]
+    FileIo(#[from] FileError),
+}
+
+/// Errors for the low level cab file task layer
+#[derive(Error, Debug)]
+pub enum CabTaskError {
+    #[error("Missing driver package folder: {0}, cab file requires a packaged driver")]
+    MissingDriverPackage(PathBuf),
+    #[error("Error creating cab file using makecab")]
+    MakecabCommand(#[source] CommandError),
+    #[error("Error signing cab file using signtool")]
+    CabSignCommand(#[source] CommandError),
+    #[error(transparent)]
+    FileIo(#[from] FileError),
+}
+
+impl CabTaskError {
+    /// Returns the [`FailureCategory`] of the error
+    #[must_use]
+    pub const fn category(&self) -> FailureCategory {
+        match self {
+            Self::CabSignCommand(_) => FailureCategory::Signing,
+            Self::MissingDriverPackage(_) | Self::MakecabCommand(_) | Self::FileIo(_) => {
+                FailureCategory::Packaging
+            }
+        }
+    }
+}
+
+/// Errors for the low level `NuGet` WDK task layer
+#[derive(Error, Debug)]
+pub enum NugetTaskError {
+    #[error("Missing packages.config file listing the WDK NuGet packages: {0}")]
+    MissingPackagesConfig(PathBuf),
+    #[error("Error restoring WDK NuGet packages using nuget")]
+    RestoreCommand(#[source] CommandError),
+    #[error("No {1} package found in restored NuGet packages: {0}")]
+    MissingWdkPackage(PathBuf, String),
+    #[error(transparent)]
+    FileIo(#[from] FileError),
+}
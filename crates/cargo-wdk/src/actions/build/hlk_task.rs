@@ -0,0 +1,380 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+//! Module that prepares Hardware Lab Kit (HLK) artifacts for a driver package.
+//! This module defines the `HlkTask` struct and its associated methods for
+//! laying out an HLK-ready artifacts folder from an already created driver
+//! package. The folder contains the driver package, its symbols and a test
+//! metadata file describing the driver. Optionally, an HLK playlist skeleton
+//! keyed to the driver's device class is generated as well.
+
+use std::path::{Path, PathBuf};
+
+use mockall_double::double;
+use tracing::{debug, info};
+use wdk_build::{CpuArchitecture, DriverConfig};
+
+#[double]
+use crate::providers::fs::Fs;
+use crate::{actions::build::error::HlkTaskError, providers::error::FileError};
+
+const HLK_PACKAGE_DIR_NAME: &str = "package";
+const HLK_SYMBOLS_DIR_NAME: &str = "symbols";
+const HLK_METADATA_FILE_NAME: &str = "hlk_metadata.json";
+
+#[derive(Debug)]
+pub struct HlkTaskParams<'a> {
+    pub package_name: &'a str,
+    /// Directory containing the driver package folder, which the HLK artifacts
+    /// folder is created next to
+    pub target_dir: &'a Path,
+    pub target_arch: &'a CpuArchitecture,
+    pub driver_model: &'a DriverConfig,
+    pub generate_playlist: bool,
+}
+
+/// Device setup class of a driver as declared in the `[Version]` section of
+/// its INF file
+#[derive(Debug, Default, PartialEq, Eq)]
+struct DeviceClass {
+    name: Option<String>,
+    guid: Option<String>,
+}
+
+/// Lays out HLK artifacts for a packaged driver
+pub struct HlkTask<'a> {
+    package_name: String,
+    generate_playlist: bool,
+    arch: &'a CpuArchitecture,
+    driver_model: &'a DriverConfig,
+
+    // src paths
+    src_package_folder: PathBuf,
+    src_inf_file_path: PathBuf,
+
+    // destination paths
+    dest_root_hlk_folder: PathBuf,
+    dest_package_folder: PathBuf,
+    dest_symbols_folder: PathBuf,
+    dest_metadata_file_path: PathBuf,
+    dest_playlist_file_path: PathBuf,
+
+    // Injected deps
+    fs: &'a Fs,
+}
+
+impl<'a> HlkTask<'a> {
+    /// Creates a new instance of `HlkTask`.
+    ///
+    /// # Arguments
+    /// * `params` - Struct containing the parameters for the HLK task.
+    /// * `fs` - The provider for file system operations.
+    ///
+    /// # Returns
+    /// * `Self` - A new instance of `HlkTask`.
+    ///
+    /// # Panics
+    /// * If `params.target_dir` is not absolute
+    pub fn new(params: &HlkTaskParams<'a>, fs: &'a Fs) -> Self {
+        debug!("HLK task params: {params:?}");
+        assert!(
+            params.target_dir.is_absolute(),
+            "Target directory path must be absolute. Input path: {}",
+            params.target_dir.display()
+        );
+        let package_name = params.package_name.replace('-', "_");
+
+        let src_package_folder = params.target_dir.join(format!("{package_name}_package"));
+        let src_inf_file_path = src_package_folder.join(format!("{package_name}.inf"));
+
+        let dest_root_hlk_folder = params.target_dir.join(format!("{package_name}_hlk"));
+        let dest_package_folder = dest_root_hlk_folder.join(HLK_PACKAGE_DIR_NAME);
+        let dest_symbols_folder = dest_root_hlk_folder.join(HLK_SYMBOLS_DIR_NAME);
+        let dest_metadata_file_path = dest_root_hlk_folder.join(HLK_METADATA_FILE_NAME);
+        let dest_playlist_file_path =
+            dest_root_hlk_folder.join(format!("{package_name}.playlist.xml"));
+
+        Self {
+            package_name,
+            generate_playlist: params.generate_playlist,
+            arch: params.target_arch,
+            driver_model: params.driver_model,
+            src_package_folder,
+            src_inf_file_path,
+            dest_root_hlk_folder,
+            dest_package_folder,
+            dest_symbols_folder,
+            dest_metadata_file_path,
+            dest_playlist_file_path,
+            fs,
+        }
+    }
+
+    /// Entry point method to lay out the HLK artifacts folder.
+    ///
+    /// # Returns
+    /// * `Result<(), HlkTaskError>` - A result indicating success or failure.
+    ///
+    /// # Errors
+    /// * `HlkTaskError::MissingDriverPackage` - If the driver package folder
+    ///   does not exist.
+    /// * `HlkTaskError::FileIo` - If there is an error creating the HLK
+    ///   folders, copying the artifacts or writing the generated files.
+    pub fn run(&self) -> Result<(), HlkTaskError> {
+        if !self.fs.exists(&self.src_package_folder) {
+            return Err(HlkTaskError::MissingDriverPackage(
+                self.src_package_folder.clone(),
+            ));
+        }
+        info!(
+            "Creating HLK artifacts folder: {}",
+            self.dest_root_hlk_folder.display()
+        );
+        for dir in [
+            &self.dest_root_hlk_folder,
+            &self.dest_package_folder,
+            &self.dest_symbols_folder,
+        ] {
+            if !self.fs.exists(dir) {
+                self.fs.create_dir(dir)?;
+            }
+        }
+        self.copy_package_artifacts()?;
+
+        let inf_content = self.fs.read_file_to_string(&self.src_inf_file_path)?;
+        let device_class = parse_device_class(&inf_content);
+        debug!("Device class parsed from INF: {device_class:?}");
+        self.write_metadata(&device_class)?;
+        if self.generate_playlist {
+            self.write_playlist(&device_class)?;
+        }
+        Ok(())
+    }
+
+    fn copy_package_artifacts(&self) -> Result<(), FileError> {
+        for entry in self.fs.read_dir_entries(&self.src_package_folder)? {
+            if !self.fs.dir_file_type(&entry)?.is_file() {
+                continue;
+            }
+            let src_file_path = entry.path();
+            let is_symbol_file = src_file_path.extension().is_some_and(|ext| {
+                ext.eq_ignore_ascii_case("pdb") || ext.eq_ignore_ascii_case("map")
+            });
+            let dest_folder = if is_symbol_file {
+                &self.dest_symbols_folder
+            } else {
+                &self.dest_package_folder
+            };
+            let dest_file_path = dest_folder.join(entry.file_name());
+            debug!(
+                "Copying {} to {}",
+                src_file_path.display(),
+                dest_file_path.display()
+            );
+            self.fs.copy(&src_file_path, &dest_file_path)?;
+        }
+        Ok(())
+    }
+
+    fn write_metadata(&self, device_class: &DeviceClass) -> Result<(), FileError> {
+        info!("Writing HLK test metadata");
+        let driver_type = match self.driver_model {
+            DriverConfig::Kmdf(_) => "KMDF",
+            DriverConfig::Umdf(_) => "UMDF",
+            DriverConfig::Wdm => "WDM",
+        };
+        let metadata = serde_json::json!({
+            "driver": self.package_name,
+            "driver_type": driver_type,
+            "architecture": self.arch.to_string(),
+            "device_class": device_class.name,
+            "device_class_guid": device_class.guid,
+            "package_dir": HLK_PACKAGE_DIR_NAME,
+            "symbols_dir": HLK_SYMBOLS_DIR_NAME,
+        });
+        let content = serde_json::to_string_pretty(&metadata)
+            .expect("HLK metadata is always serializable to JSON");
+        self.fs
+            .write_to_file(&self.dest_metadata_file_path, content.as_bytes())
+    }
+
+    fn write_playlist(&self, device_class: &DeviceClass) -> Result<(), FileError> {
+        info!("Generating HLK playlist skeleton");
+        let class_name = device_class.name.as_deref().unwrap_or_default();
+        let class_guid = device_class.guid.as_deref().unwrap_or_default();
+        let content = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<!-- HLK playlist skeleton generated by cargo-wdk. Add the tests selected for certification -->
+<!-- of this driver in HLK Studio and replace this file with the exported playlist. -->
+<Playlist Version="1.0" Name="{name}">
+  <DeviceClass Name="{class_name}" Guid="{class_guid}" />
+  <Driver Name="{name}" Architecture="{arch}" Package="{package_dir}" Symbols="{symbols_dir}" />
+  <Tests>
+  </Tests>
+</Playlist>
+"#,
+            name = self.package_name,
+            arch = self.arch,
+            package_dir = HLK_PACKAGE_DIR_NAME,
+            symbols_dir = HLK_SYMBOLS_DIR_NAME,
+        );
+        self.fs
+            .write_to_file(&self.dest_playlist_file_path, content.as_bytes())
+    }
+}
+
+/// Reads the `Class` and `ClassGuid` entries from the `[Version]` section of
+/// the INF content. Entries that are missing or left empty are returned as
+/// `None`.
+fn parse_device_class(inf_content: &str) -> DeviceClass {
+    let mut device_class = DeviceClass::default();
+    let mut in_version_section = false;
+    for line in inf_content.lines() {
+        let line = line.split(';').next().unwrap_or_default().trim();
+        if line.starts_with('[') {
+            in_version_section = line.eq_ignore_ascii_case("[Version]");
+            continue;
+        }
+        if !in_version_section {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.trim();
+        if value.is_empty() {
+            continue;
+        }
+        match key.trim() {
+            k if k.eq_ignore_ascii_case("Class") => device_class.name = Some(value.to_string()),
+            k if k.eq_ignore_ascii_case("ClassGuid") => {
+                device_class.guid = Some(value.to_string());
+            }
+            _ => {}
+        }
+    }
+    device_class
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use wdk_build::{CpuArchitecture, KmdfConfig};
+
+    use super::*;
+
+    #[test]
+    fn new_succeeds_for_valid_args() {
+        let target_dir = PathBuf::from("C:/absolute/path/to/target/dir");
+        let arch = CpuArchitecture::Amd64;
+        let driver_model = DriverConfig::Kmdf(KmdfConfig::default());
+        let params = HlkTaskParams {
+            package_name: "test-package",
+            target_dir: &target_dir,
+            target_arch: &arch,
+            driver_model: &driver_model,
+            generate_playlist: true,
+        };
+        let fs = Fs::default();
+        let task = HlkTask::new(&params, &fs);
+
+        let hlk_root = target_dir.join("test_package_hlk");
+        assert_eq!(task.package_name, "test_package");
+        assert!(task.generate_playlist);
+        assert_eq!(
+            task.src_package_folder,
+            target_dir.join("test_package_package")
+        );
+        assert_eq!(
+            task.src_inf_file_path,
+            target_dir
+                .join("test_package_package")
+                .join("test_package.inf")
+        );
+        assert_eq!(task.dest_root_hlk_folder, hlk_root);
+        assert_eq!(task.dest_package_folder, hlk_root.join("package"));
+        assert_eq!(task.dest_symbols_folder, hlk_root.join("symbols"));
+        assert_eq!(
+            task.dest_metadata_file_path,
+            hlk_root.join("hlk_metadata.json")
+        );
+        assert_eq!(
+            task.dest_playlist_file_path,
+            hlk_root.join("test_package.playlist.xml")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Target directory path must be absolute. Input path: \
+                               relative/path/to/target/dir")]
+    fn new_panics_when_target_dir_is_not_absolute() {
+        let target_dir = PathBuf::from("relative/path/to/target/dir");
+        let arch = CpuArchitecture::Amd64;
+        let driver_model = DriverConfig::Wdm;
+        let params = HlkTaskParams {
+            package_name: "test_package",
+            target_dir: &target_dir,
+            target_arch: &arch,
+            driver_model: &driver_model,
+            generate_playlist: false,
+        };
+        let fs = Fs::default();
+        HlkTask::new(&params, &fs);
+    }
+
+    #[test]
+    fn run_fails_when_driver_package_is_missing() {
+        let target_dir = PathBuf::from("C:/abs/driver/target/debug");
+        let arch = CpuArchitecture::Amd64;
+        let driver_model = DriverConfig::Wdm;
+        let params = HlkTaskParams {
+            package_name: "driver",
+            target_dir: &target_dir,
+            target_arch: &arch,
+            driver_model: &driver_model,
+            generate_playlist: false,
+        };
+        let mut fs = Fs::default();
+        let expected_package_folder = target_dir.join("driver_package");
+        fs.expect_exists()
+            .withf(move |path| path == expected_package_folder)
+            .once()
+            .return_const(false);
+
+        let result = HlkTask::new(&params, &fs).run();
+        assert!(
+            matches!(result, Err(HlkTaskError::MissingDriverPackage(_))),
+            "Expected MissingDriverPackage error, got: {result:?}"
+        );
+    }
+
+    #[test]
+    fn parse_device_class_reads_version_section() {
+        let inf_content = r#"
+[Version]
+Signature   = "$WINDOWS NT$"
+Class       = Sample ; the device class
+ClassGuid   = {78A1C341-4539-11d3-B88D-00C04FAD5171}
+
+[Strings]
+Class = NotTheClass
+"#;
+        assert_eq!(
+            parse_device_class(inf_content),
+            DeviceClass {
+                name: Some("Sample".to_string()),
+                guid: Some("{78A1C341-4539-11d3-B88D-00C04FAD5171}".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_device_class_ignores_empty_entries() {
+        let inf_content = r"
+[Version]
+Class       = ; TODO: specify appropriate Class
+ClassGuid   = ; TODO: specify appropriate ClassGuid
+";
+        assert_eq!(parse_device_class(inf_content), DeviceClass::default());
+    }
+}
@@ -8,48 +8,209 @@
 //! the package phase.
 
 mod build_task;
+mod cab_task;
 mod error;
+mod hlk_task;
+mod nuget_task;
 mod package_task;
 #[cfg(test)]
 mod tests;
+mod verification_report;
 use std::{
+    collections::HashSet,
+    fmt::{self, Display},
     path::{Path, PathBuf, absolute},
+    rc::Rc,
     result::Result::Ok,
+    str::FromStr,
 };
 
 use anyhow::Result;
 use build_task::BuildTask;
-use cargo_metadata::{CrateType, Message, Metadata as CargoMetadata, Package, TargetKind};
-use error::BuildActionError;
+use cab_task::{CabTask, CabTaskParams};
+use cargo_metadata::{
+    CrateType,
+    DependencyKind,
+    Message,
+    Metadata as CargoMetadata,
+    Package,
+    TargetKind,
+};
+pub use error::BuildActionError;
+use hlk_task::{HlkTask, HlkTaskParams};
 use mockall_double::double;
+use nuget_task::NugetTask;
+pub(crate) use package_task::signtool_sign_args;
 use package_task::{PackageTask, PackageTaskParams};
 use tracing::{debug, error as err, info, trace, warn};
+use verification_report::VerificationReport;
 use wdk_build::{
     CpuArchitecture,
-    metadata::{TryFromCargoMetadataError, Wdk},
+    metadata::{PackageConfig, TryFromCargoMetadataError, Wdk},
 };
 
-use crate::actions::Profile;
+use crate::actions::{
+    Profile,
+    SigningOverrides,
+    WdkSource,
+    get_target_dir,
+    resolve_package_config,
+};
 #[double]
 use crate::providers::{exec::CommandExec, fs::Fs, metadata::Metadata, wdk_build::WdkBuild};
 
+/// HLK artifacts to emit alongside the driver package
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HlkArtifacts {
+    /// HLK-ready folder with the driver package, symbols and test metadata
+    Folder,
+    /// HLK-ready folder along with an HLK playlist skeleton
+    FolderWithPlaylist,
+}
+
+/// How the driver package is signed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SignMode {
+    /// Sign with the configured certificate, generating the local test
+    /// certificate if no certificate is configured and adding the certificate
+    /// to the driver package
+    #[default]
+    Test,
+    /// Sign with the configured certificate only. No test certificate is
+    /// generated and no certificate is added to the driver package
+    Production,
+    /// Leave the driver package unsigned, ex. to sign it in a later step or
+    /// through the Hardware Dev Center
+    None,
+}
+
+impl SignMode {
+    /// Returns whether the driver package is signed
+    #[must_use]
+    pub const fn signs(self) -> bool {
+        !matches!(self, Self::None)
+    }
+}
+
+impl FromStr for SignMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "test" => Ok(Self::Test),
+            "production" => Ok(Self::Production),
+            "none" => Ok(Self::None),
+            _ => Err(format!("'{s}' is not a valid sign mode")),
+        }
+    }
+}
+
+impl Display for SignMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Test => "test",
+            Self::Production => "production",
+            Self::None => "none",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Stages of the packaging process to run after the driver is built
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackageStages {
+    /// How to sign the driver binary and the cat file
+    pub sign_mode: SignMode,
+    /// Verify the INF file of the driver package with infverif
+    pub verify: bool,
+}
+
+impl Default for PackageStages {
+    fn default() -> Self {
+        Self {
+            sign_mode: SignMode::default(),
+            verify: true,
+        }
+    }
+}
+
 pub struct BuildActionParams<'a> {
     pub working_dir: &'a Path,
     pub profile: Option<&'a Profile>,
-    pub target_arch: Option<CpuArchitecture>,
+    /// Target architectures to build for. The host architecture is used when
+    /// it is empty
+    pub target_archs: Vec<CpuArchitecture>,
     pub verify_signature: bool,
     pub is_sample_class: bool,
+    pub hlk_artifacts: Option<HlkArtifacts>,
+    /// Create a cab file of the driver package for attestation signing
+    pub cab: bool,
+    pub package_only: bool,
+    /// Package the drivers even if their packages are up to date
+    pub force_repackage: bool,
+    /// Fail packaging when infverif reports warnings
+    pub treat_inf_warnings_as_errors: bool,
+    pub package_stages: PackageStages,
+    pub cert_store: Option<&'a str>,
+    pub cert_name: Option<&'a str>,
+    pub cert_path: Option<&'a Path>,
+    /// SHA1 thumbprint of the signing certificate in the certificate store
+    pub cert_thumbprint: Option<&'a str>,
+    /// Cryptographic service provider holding the private key of the
+    /// certificate in `cert_path`
+    pub csp: Option<&'a str>,
+    /// Key container of the private key in `csp`
+    pub key_container: Option<&'a str>,
+    /// Digest algorithm to sign with, ex. `SHA256`
+    pub digest_algorithm: Option<&'a str>,
+    pub wdk_source: WdkSource,
+    /// Path of the JSON file to write the results of verifying the driver
+    /// packages to
+    pub verification_report_path: Option<&'a Path>,
+    /// Path of the SARIF file to write the diagnostics of verifying the driver
+    /// packages to
+    pub sarif_path: Option<&'a Path>,
+    /// Directory to build in instead of the target directory configured for
+    /// cargo, ex. through `CARGO_TARGET_DIR`
+    pub target_dir: Option<&'a Path>,
+    /// Directory to create the driver packages in instead of the target
+    /// directory. Packages are laid out in it like the binaries are in the
+    /// target directory, ex. `<package_dir>\release`
+    pub package_dir: Option<&'a Path>,
     pub verbosity_level: clap_verbosity_flag::Verbosity,
 }
 
 /// Action that orchestrates the build and package of a driver project. Build is
 /// a pre-requisite for packaging.
+#[derive(Clone)]
 pub struct BuildAction<'a> {
     working_dir: PathBuf,
     profile: Option<&'a Profile>,
+    target_archs: Vec<CpuArchitecture>,
+    // Target architecture of the current build pass
     target_arch: Option<CpuArchitecture>,
     verify_signature: bool,
     is_sample_class: bool,
+    hlk_artifacts: Option<HlkArtifacts>,
+    cab: bool,
+    package_only: bool,
+    force_repackage: bool,
+    treat_inf_warnings_as_errors: bool,
+    package_stages: PackageStages,
+    cert_store: Option<&'a str>,
+    cert_name: Option<&'a str>,
+    cert_path: Option<PathBuf>,
+    cert_thumbprint: Option<&'a str>,
+    csp: Option<&'a str>,
+    key_container: Option<&'a str>,
+    digest_algorithm: Option<&'a str>,
+    wdk_source: WdkSource,
+    verification_report_path: Option<PathBuf>,
+    sarif_path: Option<PathBuf>,
+    target_dir: Option<PathBuf>,
+    package_dir: Option<PathBuf>,
+    // Shared by the build passes of all target architectures
+    verification_report: Rc<VerificationReport>,
     verbosity_level: clap_verbosity_flag::Verbosity,
 
     // Injected deps
@@ -64,7 +225,8 @@ impl<'a> BuildAction<'a> {
     ///
     /// # Arguments:
     /// * `params` - The `BuildActionParams` struct containing the parameters
-    ///   for the build action
+    ///   for the build action. Certificate parameters that are set take
+    ///   precedence over the `metadata.wdk.package` section of `Cargo.toml`
     /// * `wdk_build` - The WDK build provider instance
     /// * `command_exec` - The command execution provider instance
     /// * `fs` - The file system provider instance
@@ -75,7 +237,9 @@ impl<'a> BuildAction<'a> {
     ///   `BuildAction` on success, or an `anyhow::Error`.
     ///
     /// # Errors
-    /// * [`anyhow::Error`] -  If `params.working_dir` is not a syntactically
+    /// * [`anyhow::Error`] -  If `params.working_dir`, `params.cert_path`,
+    ///   `params.verification_report_path`, `params.sarif_path`,
+    ///   `params.target_dir` or `params.package_dir` is not a syntactically
     ///   valid path, e.g. it is empty
     pub fn new(
         params: &BuildActionParams<'a>,
@@ -85,12 +249,41 @@ pub fn new(
         metadata: &'a Metadata,
     ) -> Result<Self> {
         // TODO: validate params
+        let mut target_archs = Vec::with_capacity(params.target_archs.len());
+        for target_arch in &params.target_archs {
+            if !target_archs.contains(target_arch) {
+                target_archs.push(*target_arch);
+            }
+        }
         Ok(Self {
             working_dir: absolute(params.working_dir)?,
             profile: params.profile,
-            target_arch: params.target_arch,
+            target_arch: match target_archs.as_slice() {
+                [target_arch] => Some(*target_arch),
+                _ => None,
+            },
+            target_archs,
             verify_signature: params.verify_signature,
             is_sample_class: params.is_sample_class,
+            hlk_artifacts: params.hlk_artifacts,
+            cab: params.cab,
+            package_only: params.package_only,
+            force_repackage: params.force_repackage,
+            treat_inf_warnings_as_errors: params.treat_inf_warnings_as_errors,
+            package_stages: params.package_stages,
+            cert_store: params.cert_store,
+            cert_name: params.cert_name,
+            cert_path: params.cert_path.map(absolute).transpose()?,
+            cert_thumbprint: params.cert_thumbprint,
+            csp: params.csp,
+            key_container: params.key_container,
+            digest_algorithm: params.digest_algorithm,
+            wdk_source: params.wdk_source,
+            verification_report_path: params.verification_report_path.map(absolute).transpose()?,
+            sarif_path: params.sarif_path.map(absolute).transpose()?,
+            target_dir: params.target_dir.map(absolute).transpose()?,
+            package_dir: params.package_dir.map(absolute).transpose()?,
+            verification_report: Rc::default(),
             verbosity_level: params.verbosity_level,
             wdk_build,
             command_exec,
@@ -128,16 +321,137 @@ pub fn new(
     ///   more workspace members fail to build inside a workspace.
     /// * `BuildActionError::BuildTask` - If there is an error during the build
     ///   task process.
+    /// * `BuildActionError::HlkTask` - If there is an error while laying out
+    ///   the HLK artifacts.
+    /// * `BuildActionError::CabTask` - If there is an error while creating or
+    ///   signing the cab file.
+    /// * `BuildActionError::OneOrMoreTargetArchitecturesFailedToBuild` - If
+    ///   building for one or more of multiple target architectures fails.
+    /// * `BuildActionError::NugetTask` - If there is an error restoring or
+    ///   resolving the WDK `NuGet` packages.
+    /// * `BuildActionError::FileIo` - If there is an error writing the
+    ///   verification report or the SARIF file.
     pub fn run(&self) -> Result<(), BuildActionError> {
+        let result = self.run_for_target_archs();
+        // The reports are written even if the build fails so that the failed
+        // verifications can be inspected
+        let mut write_result = Ok(());
+        if let Some(verification_report_path) = &self.verification_report_path {
+            info!(
+                "Writing verification report to {}",
+                verification_report_path.display()
+            );
+            write_result = self.fs.write_to_file(
+                verification_report_path,
+                self.verification_report.to_json_string().as_bytes(),
+            );
+        }
+        if let Some(sarif_path) = &self.sarif_path {
+            info!("Writing SARIF file to {}", sarif_path.display());
+            let sarif_write_result = self.fs.write_to_file(
+                sarif_path,
+                self.verification_report.to_sarif_string().as_bytes(),
+            );
+            write_result = write_result.and(sarif_write_result);
+        }
+        result?;
+        write_result?;
+        Ok(())
+    }
+
+    // Runs the build for each of the target architectures
+    fn run_for_target_archs(&self) -> Result<(), BuildActionError> {
         debug!(
             "Initialized build for project at: {}",
             self.working_dir.display()
         );
+        let nuget_task = (self.wdk_source == WdkSource::Nuget)
+            .then(|| NugetTask::new(&self.working_dir, self.command_exec, self.fs));
+        if let Some(nuget_task) = &nuget_task {
+            nuget_task.restore()?;
+            self.use_nuget_wdk(nuget_task, self.target_archs.first().copied())?;
+        }
         let build_number = self.wdk_build.detect_wdk_build_number()?;
         debug!("WDK build number: {}", build_number);
         wdk_build::cargo_make::setup_path()?;
         debug!("PATH env variable is set with WDK bin and tools paths");
 
+        if self.target_archs.len() <= 1 {
+            return self.run_for_target_arch();
+        }
+
+        // Each target architecture is built in its own pass. Packages of each
+        // pass are emitted in the target directory of its target triple
+        let mut failed_target_archs = vec![];
+        let mut first_failure_category = None;
+        for target_arch in &self.target_archs {
+            info!("Building for target architecture {target_arch}");
+            let build_action = Self {
+                target_arch: Some(*target_arch),
+                ..self.clone()
+            };
+            // The WDK NuGet packages are specific to the target architecture
+            let result = nuget_task
+                .as_ref()
+                .map_or(Ok(()), |nuget_task| {
+                    self.use_nuget_wdk(nuget_task, Some(*target_arch))
+                })
+                .and_then(|()| build_action.run_for_target_arch());
+            if let Err(e) = result {
+                first_failure_category.get_or_insert_with(|| e.category());
+                failed_target_archs.push(target_arch.to_string());
+                err!(
+                    "Error building for target architecture: {target_arch}, error: {:?}",
+                    anyhow::Error::new(e)
+                );
+            }
+        }
+
+        if let Some(failure_category) = first_failure_category {
+            return Err(BuildActionError::OneOrMoreTargetArchitecturesFailedToBuild(
+                failed_target_archs.join(", "),
+                failure_category,
+            ));
+        }
+
+        info!(
+            "Build completed successfully for target architectures {}",
+            self.target_archs
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        Ok(())
+    }
+
+    // Makes the build use the WDK from the restored NuGet packages for the target
+    // architecture, or for the host architecture if none is specified
+    fn use_nuget_wdk(
+        &self,
+        nuget_task: &NugetTask,
+        target_arch: Option<CpuArchitecture>,
+    ) -> Result<(), BuildActionError> {
+        let target_arch = target_arch.unwrap_or_else(|| {
+            CpuArchitecture::try_from_cargo_str(std::env::consts::ARCH)
+                .expect("The rust standard library should always set env::consts::ARCH")
+        });
+        let wdk_paths = nuget_task.resolve_wdk_paths(target_arch)?;
+        debug!(
+            "Using WDK from NuGet packages at: {}",
+            wdk_paths.wdk_content_root.display()
+        );
+        self.wdk_build
+            .use_wdk_content_root(&wdk_paths.wdk_content_root);
+        if let Some(windows_sdk_bin_path) = &wdk_paths.windows_sdk_bin_path {
+            self.wdk_build
+                .use_windows_sdk_bin_path(windows_sdk_bin_path);
+        }
+        Ok(())
+    }
+
+    // Runs the build for the target architecture of the current pass
+    fn run_for_target_arch(&self) -> Result<(), BuildActionError> {
         // Standalone driver/driver workspace support
         if self.fs.exists(&self.working_dir.join("Cargo.toml")) {
             return self.run_from_workspace_root(&self.working_dir);
@@ -178,7 +492,7 @@ pub fn run(&self) -> Result<(), BuildActionError> {
 
         info!("Building packages in {}", self.working_dir.display());
 
-        let mut failed_atleast_one_project = false;
+        let mut first_failure_category = None;
         for dir in dirs {
             debug!("Checking dir entry: {}", dir.path().display());
             if !self.fs.dir_file_type(&dir)?.is_dir()
@@ -196,7 +510,7 @@ pub fn run(&self) -> Result<(), BuildActionError> {
 
             debug!("Building package(s) in dir {sub_dir}");
             if let Err(e) = self.run_from_workspace_root(&dir.path()) {
-                failed_atleast_one_project = true;
+                first_failure_category.get_or_insert_with(|| e.category());
                 err!(
                     "Error building project: {sub_dir}, error: {:?}",
                     anyhow::Error::new(e)
@@ -205,9 +519,10 @@ pub fn run(&self) -> Result<(), BuildActionError> {
         }
 
         debug!("Done building packages in {}", self.working_dir.display());
-        if failed_atleast_one_project {
+        if let Some(failure_category) = first_failure_category {
             return Err(BuildActionError::OneOrMoreRustProjectsFailedToBuild(
                 self.working_dir.clone(),
+                failure_category,
             ));
         }
 
@@ -234,8 +549,11 @@ fn run_from_workspace_root(&self, working_dir: &Path) -> Result<(), BuildActionE
                 "Running from standalone project or from a root of a workspace: {}",
                 working_dir.display()
             );
-            let mut failed_atleast_one_workspace_member = false;
-            for package in workspace_packages {
+            let mut first_failure_category = None;
+            // Members are packaged after the members they depend on, so that
+            // the import libraries of export drivers are packaged before the
+            // drivers linking against them
+            for package in Self::sort_by_workspace_dependencies(&workspace_packages) {
                 let package_root_path: PathBuf = package
                     .manifest_path
                     .parent()
@@ -249,8 +567,13 @@ fn run_from_workspace_root(&self, working_dir: &Path) -> Result<(), BuildActionE
                     package_root_path.display()
                 );
 
-                if let Err(e) = self.build_and_package(&package_root_path, &wdk_metadata, package) {
-                    failed_atleast_one_workspace_member = true;
+                if let Err(e) = self.build_and_package(
+                    &package_root_path,
+                    &wdk_metadata,
+                    package,
+                    cargo_metadata.target_directory.as_std_path(),
+                ) {
+                    first_failure_category.get_or_insert_with(|| e.category());
                     err!(
                         "Error building the workspace member project: {}, error: {:?}",
                         package_root_path.display(),
@@ -265,9 +588,10 @@ fn run_from_workspace_root(&self, working_dir: &Path) -> Result<(), BuildActionE
                 }
             }
 
-            if failed_atleast_one_workspace_member {
+            if let Some(failure_category) = first_failure_category {
                 return Err(BuildActionError::OneOrMoreWorkspaceMembersFailedToBuild(
                     working_dir.to_owned(),
+                    failure_category,
                 ));
             }
         } else {
@@ -291,7 +615,12 @@ fn run_from_workspace_root(&self, working_dir: &Path) -> Result<(), BuildActionE
             let package = package
                 .ok_or_else(|| BuildActionError::NotAWorkspaceMember(working_dir.to_owned()))?;
 
-            self.build_and_package(working_dir, &wdk_metadata, package)?;
+            self.build_and_package(
+                working_dir,
+                &wdk_metadata,
+                package,
+                cargo_metadata.target_directory.as_std_path(),
+            )?;
 
             if let Err(e) = wdk_metadata {
                 // Ignore NoWdkConfigurationsDetected but propagate any other error
@@ -320,25 +649,34 @@ fn get_cargo_metadata(&self, working_dir: &Path) -> Result<CargoMetadata, BuildA
         Ok(cargo_metadata)
     }
 
-    // Method to perform the build and package tasks on the given package
+    // Method to perform the build and package tasks on the given package. The
+    // build task is skipped in package only mode and the artifacts of a
+    // previous build in `target_directory`, or in the target directory of the
+    // action if set, are packaged instead
     fn build_and_package(
         &self,
         working_dir: &Path,
         wdk_metadata: &Result<Wdk, TryFromCargoMetadataError>,
         package: &Package,
+        target_directory: &Path,
     ) -> Result<(), BuildActionError> {
         let package_name = package.name.as_str();
-        info!("Building package {package_name}");
-
         let build_task = BuildTask::new(
             package_name,
             working_dir,
             self.profile,
             self.target_arch,
+            self.target_dir.as_deref(),
             self.verbosity_level,
             self.command_exec,
         );
-        let output_message_iter = build_task.run()?;
+        let output_message_iter = if self.package_only {
+            info!("Skipping build of package {package_name}");
+            None
+        } else {
+            info!("Building package {package_name}");
+            Some(build_task.run()?)
+        };
 
         let wdk_metadata = if let Ok(wdk_metadata) = wdk_metadata {
             debug!("Found wdk metadata in package: {}", package_name);
@@ -348,23 +686,18 @@ fn build_and_package(
             return Ok(());
         };
 
-        // Identifying non driver packages
-        if package.metadata.get("wdk").is_none() {
-            debug!("Packaging task skipped for non-driver package");
-            return Ok(());
-        }
-
-        if !package
-            .targets
-            .iter()
-            .any(|t| t.kind.contains(&TargetKind::CDyLib))
-        {
-            warn!("No cdylib target found. Skipping package task");
+        if !Self::is_driver_package(package) {
             return Ok(());
         }
 
         debug!("Creating the driver package in the target directory");
         let driver_model = wdk_metadata.driver_model.clone();
+        let export_driver = wdk_metadata.package.as_ref().is_some_and(|package_config| {
+            package_config
+                .export_drivers
+                .iter()
+                .any(|name| name == package_name)
+        });
         // Resolve the target architecture for the packaging task
         let target_arch = if let Some(arch) = self.target_arch {
             arch
@@ -372,22 +705,64 @@ fn build_and_package(
             self.get_target_arch_from_cargo_rustc(working_dir)?
         };
         debug!("Target architecture for package: {package_name} is: {target_arch}");
-        let target_dir = Self::get_target_dir_from_output(package, output_message_iter)?;
+        let target_dir = match output_message_iter {
+            Some(output_message_iter) => {
+                Self::get_target_dir_from_output(package, output_message_iter)?
+            }
+            None => get_target_dir(
+                self.target_dir.as_deref().unwrap_or(target_directory),
+                self.profile,
+                self.target_arch,
+            ),
+        };
         debug!(
             "Target directory for package: {} is: {}",
             package_name,
             target_dir.display()
         );
+        let package_dir = match &self.package_dir {
+            Some(package_dir) => {
+                let package_dir = get_target_dir(package_dir, self.profile, self.target_arch);
+                self.fs.create_dir_all(&package_dir)?;
+                package_dir
+            }
+            None => target_dir.clone(),
+        };
+        debug!(
+            "Package directory for package: {} is: {}",
+            package_name,
+            package_dir.display()
+        );
 
+        let package_version = package.version.to_string();
+        let package_config = resolve_package_config(
+            wdk_metadata
+                .package
+                .clone()
+                .unwrap_or_default()
+                .with_driver_package_settings(package)?,
+            &self.signing_overrides(),
+            working_dir,
+        );
         PackageTask::new(
             PackageTaskParams {
                 package_name,
+                package_version: &package_version,
                 working_dir,
                 target_dir: &target_dir,
+                package_dir: &package_dir,
                 target_arch: &target_arch,
                 verify_signature: self.verify_signature,
                 sample_class: self.is_sample_class,
-                driver_model,
+                stages: self.package_stages,
+                verification_report: (self.verification_report_path.is_some()
+                    || self.sarif_path.is_some())
+                .then_some(&*self.verification_report),
+                driver_model: driver_model.clone(),
+                package_config: package_config.clone(),
+                export_driver,
+                force_repackage: self.force_repackage,
+                treat_inf_warnings_as_errors: self.treat_inf_warnings_as_errors,
             },
             self.wdk_build,
             self.command_exec,
@@ -395,10 +770,123 @@ fn build_and_package(
         )
         .run()?;
 
+        // Export drivers are not installed on their own, so they have no HLK
+        // artifacts
+        if let Some(hlk_artifacts) = self.hlk_artifacts
+            && !export_driver
+        {
+            HlkTask::new(
+                &HlkTaskParams {
+                    package_name,
+                    target_dir: &package_dir,
+                    target_arch: &target_arch,
+                    driver_model: &driver_model,
+                    generate_playlist: hlk_artifacts == HlkArtifacts::FolderWithPlaylist,
+                },
+                self.fs,
+            )
+            .run()?;
+        }
+
+        // Export drivers are submitted along with the drivers that import them
+        if self.cab && !export_driver {
+            CabTask::new(
+                &CabTaskParams {
+                    package_name,
+                    target_dir: &package_dir,
+                    target_arch: &target_arch,
+                    package_config: &package_config,
+                    sign: self.package_stages.sign_mode.signs(),
+                },
+                self.command_exec,
+                self.fs,
+            )
+            .run()?;
+        }
+
         info!("Finished building {package_name}");
         Ok(())
     }
 
+    /// Returns whether the package builds a driver binary that can be packaged.
+    /// Libraries shared by drivers are linked into the drivers that depend on
+    /// them and are not packaged on their own.
+    fn is_driver_package(package: &Package) -> bool {
+        // Identifying non driver packages
+        if package.metadata.get("wdk").is_none() {
+            debug!("Packaging task skipped for non-driver package");
+            return false;
+        }
+
+        if package
+            .targets
+            .iter()
+            .any(|t| t.kind.contains(&TargetKind::CDyLib))
+        {
+            return true;
+        }
+        if package.targets.iter().any(|t| {
+            t.kind
+                .iter()
+                .any(|kind| matches!(kind, TargetKind::Lib | TargetKind::StaticLib))
+        }) {
+            info!(
+                "Package {} is a driver library. Skipping package task",
+                package.name
+            );
+        } else {
+            warn!("No cdylib target found. Skipping package task");
+        }
+        false
+    }
+
+    /// Orders the workspace packages so that every package comes after the
+    /// workspace packages it depends on. The original order is kept otherwise.
+    /// Development dependencies are ignored as they are not linked into the
+    /// package.
+    fn sort_by_workspace_dependencies<'p>(packages: &[&'p Package]) -> Vec<&'p Package> {
+        fn visit<'p>(
+            package: &'p Package,
+            packages: &[&'p Package],
+            visited: &mut HashSet<&'p str>,
+            sorted: &mut Vec<&'p Package>,
+        ) {
+            if !visited.insert(package.name.as_str()) {
+                return;
+            }
+            for dependency in package
+                .dependencies
+                .iter()
+                .filter(|d| d.path.is_some() && d.kind != DependencyKind::Development)
+            {
+                if let Some(dependency) = packages.iter().find(|p| p.name == dependency.name) {
+                    visit(dependency, packages, visited, sorted);
+                }
+            }
+            sorted.push(package);
+        }
+
+        let mut visited = HashSet::new();
+        let mut sorted = Vec::with_capacity(packages.len());
+        for package in packages {
+            visit(package, packages, &mut visited, &mut sorted);
+        }
+        sorted
+    }
+
+    /// Signing parameters of the action that override the package metadata
+    fn signing_overrides(&self) -> SigningOverrides<'_> {
+        SigningOverrides {
+            cert_store: self.cert_store,
+            cert_name: self.cert_name,
+            cert_path: self.cert_path.as_deref(),
+            cert_thumbprint: self.cert_thumbprint,
+            csp: self.csp,
+            key_container: self.key_container,
+            digest_algorithm: self.digest_algorithm,
+        }
+    }
+
     /// Determines the target directory (i.e. path where binaries are emitted)
     /// for a cdylib package by scanning the output of the
     /// `cargo build --message-format json` command.
@@ -0,0 +1,232 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+//! Module that provides the WDK from its `NuGet` distribution.
+//! This module defines the `NugetTask` struct and its associated methods for
+//! restoring the WDK `NuGet` packages listed in the `packages.config` file of a
+//! project and resolving the WDK content root and Windows SDK tools from the
+//! layout of the restored packages, so that drivers can be built and packaged
+//! without a system WDK install.
+
+use std::path::{Path, PathBuf};
+
+use mockall_double::double;
+use tracing::{debug, info};
+use wdk_build::CpuArchitecture;
+
+use crate::actions::build::error::NugetTaskError;
+#[double]
+use crate::providers::{exec::CommandExec, fs::Fs};
+
+const PACKAGES_CONFIG_FILE_NAME: &str = "packages.config";
+const PACKAGES_DIR_NAME: &str = "packages";
+const WDK_PACKAGE_ID_PREFIX: &str = "Microsoft.Windows.WDK.";
+const SDK_PACKAGE_ID_PREFIX: &str = "Microsoft.Windows.SDK.CPP.";
+/// Folder of the WDK and SDK `NuGet` packages containing the kit content
+const PACKAGE_CONTENT_DIR_NAME: &str = "c";
+
+/// Paths of the WDK provided by the restored `NuGet` packages
+#[derive(Debug, PartialEq, Eq)]
+pub struct NugetWdkPaths {
+    /// Root of the WDK content. Corresponds with `WDKContentRoot`
+    pub wdk_content_root: PathBuf,
+    /// Root of the Windows SDK tools. Corresponds with `WindowsSdkBinPath`
+    pub windows_sdk_bin_path: Option<PathBuf>,
+}
+
+/// Restores the WDK `NuGet` packages and resolves the WDK from their layout
+pub struct NugetTask<'a> {
+    packages_config_file_path: PathBuf,
+    packages_dir: PathBuf,
+
+    // Injected deps
+    command_exec: &'a CommandExec,
+    fs: &'a Fs,
+}
+
+impl<'a> NugetTask<'a> {
+    /// Creates a new instance of `NugetTask`.
+    ///
+    /// # Arguments
+    /// * `working_dir` - Directory containing the `packages.config` file. The
+    ///   packages are restored to the `packages` folder in it.
+    /// * `command_exec` - The provider for command execution.
+    /// * `fs` - The provider for file system operations.
+    ///
+    /// # Returns
+    /// * `Self` - A new instance of `NugetTask`.
+    ///
+    /// # Panics
+    /// * If `working_dir` is not absolute
+    pub fn new(working_dir: &Path, command_exec: &'a CommandExec, fs: &'a Fs) -> Self {
+        assert!(
+            working_dir.is_absolute(),
+            "Working directory path must be absolute. Input path: {}",
+            working_dir.display()
+        );
+        Self {
+            packages_config_file_path: working_dir.join(PACKAGES_CONFIG_FILE_NAME),
+            packages_dir: working_dir.join(PACKAGES_DIR_NAME),
+            command_exec,
+            fs,
+        }
+    }
+
+    /// Restores the `NuGet` packages listed in the `packages.config` file using
+    /// the `nuget` CLI.
+    ///
+    /// # Errors
+    /// * `NugetTaskError::MissingPackagesConfig` - If there is no
+    ///   `packages.config` file in the working directory.
+    /// * `NugetTaskError::RestoreCommand` - If there is an error restoring the
+    ///   packages.
+    pub fn restore(&self) -> Result<(), NugetTaskError> {
+        if !self.fs.exists(&self.packages_config_file_path) {
+            return Err(NugetTaskError::MissingPackagesConfig(
+                self.packages_config_file_path.clone(),
+            ));
+        }
+        info!(
+            "Restoring WDK NuGet packages from {}",
+            self.packages_config_file_path.display()
+        );
+        let packages_config_file_path = self.packages_config_file_path.to_string_lossy();
+        let packages_dir = self.packages_dir.to_string_lossy();
+        let args = [
+            "restore",
+            &packages_config_file_path,
+            "-PackagesDirectory",
+            &packages_dir,
+            "-NonInteractive",
+        ];
+        self.command_exec
+            .run("nuget", &args, None, None)
+            .map_err(NugetTaskError::RestoreCommand)?;
+        Ok(())
+    }
+
+    /// Resolves the WDK content root and the Windows SDK tools from the
+    /// restored packages for the given target architecture.
+    ///
+    /// # Errors
+    /// * `NugetTaskError::MissingWdkPackage` - If no WDK package is restored
+    ///   for the target architecture.
+    /// * `NugetTaskError::FileIo` - If the packages folder cannot be read.
+    pub fn resolve_wdk_paths(
+        &self,
+        target_arch: CpuArchitecture,
+    ) -> Result<NugetWdkPaths, NugetTaskError> {
+        let package_dir_names = self
+            .fs
+            .read_dir_entries(&self.packages_dir)?
+            .iter()
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        debug!("Restored NuGet packages: {package_dir_names:?}");
+
+        let wdk_package_id = format!("{WDK_PACKAGE_ID_PREFIX}{}", nuget_arch_name(target_arch));
+        let wdk_package_dir_name = find_package_dir_name(&package_dir_names, &wdk_package_id)
+            .ok_or_else(|| {
+                NugetTaskError::MissingWdkPackage(self.packages_dir.clone(), wdk_package_id)
+            })?;
+        let wdk_content_root = self
+            .packages_dir
+            .join(wdk_package_dir_name)
+            .join(PACKAGE_CONTENT_DIR_NAME);
+
+        // The architecture neutral SDK package contains the tools used for packaging
+        // and signing
+        let windows_sdk_bin_path = find_package_dir_name(&package_dir_names, SDK_PACKAGE_ID_PREFIX)
+            .map(|sdk_package_dir_name| {
+                self.packages_dir
+                    .join(sdk_package_dir_name)
+                    .join(PACKAGE_CONTENT_DIR_NAME)
+                    .join("bin")
+            });
+
+        Ok(NugetWdkPaths {
+            wdk_content_root,
+            windows_sdk_bin_path,
+        })
+    }
+}
+
+/// Returns the architecture name used in the ids of the WDK `NuGet` packages
+const fn nuget_arch_name(arch: CpuArchitecture) -> &'static str {
+    match arch {
+        CpuArchitecture::Amd64 => "x64",
+        CpuArchitecture::Arm64 => "ARM64",
+    }
+}
+
+/// Finds the folder of the latest restored version of a package. Packages
+/// restored from a `packages.config` file are placed in folders named
+/// `<package id>.<version>`.
+fn find_package_dir_name<'a>(package_dir_names: &'a [String], package_id: &str) -> Option<&'a str> {
+    let package_id = package_id.trim_end_matches('.').to_ascii_lowercase();
+    package_dir_names
+        .iter()
+        .filter_map(|dir_name| {
+            let version = dir_name
+                .to_ascii_lowercase()
+                .strip_prefix(&package_id)?
+                .strip_prefix('.')?
+                .split('.')
+                .map(str::parse::<u32>)
+                .collect::<Result<Vec<_>, _>>()
+                .ok()?;
+            Some((version, dir_name.as_str()))
+        })
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, dir_name)| dir_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_package_dir_name_matches_package_id_and_version() {
+        let package_dir_names = [
+            "Microsoft.Windows.SDK.CPP.10.0.26100.1",
+            "Microsoft.Windows.SDK.CPP.x64.10.0.26100.1",
+            "Microsoft.Windows.WDK.x64.10.0.26100.1",
+            "Microsoft.Windows.WDK.x64.10.0.26100.10",
+            "Microsoft.Windows.WDK.x64.10.0.26100.2",
+            "Microsoft.Windows.WDK.ARM64.10.0.26100.1",
+        ]
+        .map(ToString::to_string);
+
+        assert_eq!(
+            find_package_dir_name(&package_dir_names, "Microsoft.Windows.WDK.x64"),
+            Some("Microsoft.Windows.WDK.x64.10.0.26100.10")
+        );
+        assert_eq!(
+            find_package_dir_name(&package_dir_names, "Microsoft.Windows.WDK.arm64"),
+            Some("Microsoft.Windows.WDK.ARM64.10.0.26100.1")
+        );
+        assert_eq!(
+            find_package_dir_name(&package_dir_names, SDK_PACKAGE_ID_PREFIX),
+            Some("Microsoft.Windows.SDK.CPP.10.0.26100.1")
+        );
+        assert_eq!(
+            find_package_dir_name(&package_dir_names, "Microsoft.Windows.WDK.x86"),
+            None
+        );
+    }
+
+    #[test]
+    fn restore_fails_when_packages_config_is_missing() {
+        let working_dir = PathBuf::from("C:/absolute/path/to/working/dir");
+        let command_exec = CommandExec::default();
+        let mut fs = Fs::default();
+        fs.expect_exists()
+            .withf(|path| path.ends_with(PACKAGES_CONFIG_FILE_NAME))
+            .returning(|_| false);
+
+        let task = NugetTask::new(&working_dir, &command_exec, &fs);
+        assert!(matches!(
+            task.restore(),
+            Err(NugetTaskError::MissingPackagesConfig(_))
+        ));
+    }
+}
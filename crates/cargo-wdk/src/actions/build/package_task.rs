@@ -12,12 +12,13 @@
     marker::PhantomData,
     ops::RangeFrom,
     path::{Path, PathBuf},
+    process::Output,
     result::Result,
 };
 
 use mockall_double::double;
-use tracing::{debug, info, warn};
-use wdk_build::{CpuArchitecture, DriverConfig};
+use tracing::{debug, error as err, info, warn};
+use wdk_build::{CpuArchitecture, DriverConfig, metadata::PackageConfig};
 use windows::{
     Win32::{
         Foundation::{CloseHandle, GetLastError, HANDLE, WAIT_ABANDONED, WAIT_OBJECT_0},
@@ -28,31 +29,79 @@
 
 #[double]
 use crate::providers::{exec::CommandExec, fs::Fs, wdk_build::WdkBuild};
-use crate::{actions::build::error::PackageTaskError, providers::error::FileError};
+use crate::{
+    actions::build::{
+        PackageStages,
+        SignMode,
+        error::PackageTaskError,
+        verification_report::{
+            InfLineMap,
+            Severity,
+            VerificationReport,
+            VerificationResult,
+            VerificationTool,
+        },
+    },
+    providers::error::{CommandError, FileError},
+};
 
 // FIXME: This range is inclusive of 25798. Update with range end after /sample
 // flag is added to InfVerif CLI
 const MISSING_SAMPLE_FLAG_WDK_BUILD_NUMBER_RANGE: RangeFrom<u32> = 25798..;
-const WDR_TEST_CERT_STORE: &str = "WDRTestCertStore";
-const WDR_LOCAL_TEST_CERT: &str = "WDRLocalTestCert";
+pub(super) const WDR_TEST_CERT_STORE: &str = "WDRTestCertStore";
+pub(super) const WDR_LOCAL_TEST_CERT: &str = "WDRLocalTestCert";
+/// Digest algorithm signtool signs with unless another one is configured
+const DEFAULT_DIGEST_ALGORITHM: &str = "SHA256";
+/// Digest algorithms accepted by the `/fd` option of signtool
+const DIGEST_ALGORITHMS: [&str; 4] = ["SHA1", "SHA256", "SHA384", "SHA512"];
+const TIMESTAMP_SERVER_URL: &str = "http://timestamp.digicert.com";
 const STAMPINF_VERSION_ENV_VAR: &str = "STAMPINF_VERSION";
+/// `[Version]` directives that are managed by stampinf and cannot be set
+/// through the package metadata
+const RESERVED_VERSION_DIRECTIVES: [&str; 2] = ["DriverVer", "CatalogFile"];
+/// Tokens of the INF template that are substituted before stampinf runs
+const INF_DRIVER_NAME_TOKEN: &str = "$DRIVER_NAME$";
+const INF_DRIVER_VERSION_TOKEN: &str = "$DRIVER_VERSION$";
+const INF_HARDWARE_IDS_TOKEN: &str = "$HARDWARE_IDS$";
 
 #[derive(Debug)]
 pub struct PackageTaskParams<'a> {
     pub package_name: &'a str,
+    pub package_version: &'a str,
     pub working_dir: &'a Path,
+    /// Directory the driver binaries are built in
     pub target_dir: &'a Path,
+    /// Directory to create the driver package folder in. This is the target
+    /// directory, unless the package directory of the build is set
+    pub package_dir: &'a Path,
     pub target_arch: &'a CpuArchitecture,
     pub verify_signature: bool,
     pub sample_class: bool,
+    pub stages: PackageStages,
+    /// Report to record the results of infverif and signtool verify in
+    pub verification_report: Option<&'a VerificationReport>,
     pub driver_model: DriverConfig,
+    pub package_config: PackageConfig,
+    /// Whether the package is an export driver, which is packaged without an
+    /// INF file
+    pub export_driver: bool,
+    /// Whether to package the driver even if the package is up to date
+    pub force_repackage: bool,
+    /// Whether warnings reported by infverif fail the packaging of the driver
+    pub treat_inf_warnings_as_errors: bool,
 }
 
 /// Supports low level driver packaging operations
 pub struct PackageTask<'a> {
     package_name: String,
+    package_version: &'a str,
     verify_signature: bool,
     sample_class: bool,
+    stages: PackageStages,
+    verification_report: Option<&'a VerificationReport>,
+    export_driver: bool,
+    force_repackage: bool,
+    treat_inf_warnings_as_errors: bool,
 
     // src paths
     src_inx_file_path: PathBuf,
@@ -61,6 +110,8 @@ pub struct PackageTask<'a> {
     src_pdb_file_path: PathBuf,
     src_map_file_path: PathBuf,
     src_cert_file_path: PathBuf,
+    src_import_lib_file_path: PathBuf,
+    src_cargo_toml_file_path: PathBuf,
 
     // destination paths
     dest_root_package_folder: PathBuf,
@@ -70,10 +121,18 @@ pub struct PackageTask<'a> {
     dest_map_file_path: PathBuf,
     dest_cert_file_path: PathBuf,
     dest_cat_file_path: PathBuf,
+    dest_import_lib_file_path: PathBuf,
+
+    // Records the settings of the last successful packaging run. Its
+    // modification time tells when that run finished
+    package_stamp_file_path: PathBuf,
 
     arch: &'a CpuArchitecture,
     os_mapping: &'a str,
     driver_model: DriverConfig,
+    package_config: PackageConfig,
+    cert_store: String,
+    cert_name: String,
 
     // Injected deps
     wdk_build: &'a WdkBuild,
@@ -101,6 +160,7 @@ impl<'a> PackageTask<'a> {
     /// # Panics
     /// * If `params.working_dir` is not absolute
     /// * If `params.target_dir` is not absolute
+    /// * If `params.package_dir` is not absolute
     pub fn new(
         params: PackageTaskParams<'a>,
         wdk_build: &'a WdkBuild,
@@ -118,10 +178,19 @@ pub fn new(
             "Target directory path must be absolute. Input path: {}",
             params.target_dir.display()
         );
+        assert!(
+            params.package_dir.is_absolute(),
+            "Package directory path must be absolute. Input path: {}",
+            params.package_dir.display()
+        );
         let package_name = params.package_name.replace('-', "_");
         // src paths
         let src_driver_binary_extension = "dll";
-        let src_inx_file_path = params.working_dir.join(format!("{package_name}.inx"));
+        let src_inx_file_path = params
+            .package_config
+            .inf
+            .clone()
+            .unwrap_or_else(|| params.working_dir.join(format!("{package_name}.inx")));
 
         // all paths inside target directory
         let src_driver_binary_file_path = params
@@ -132,27 +201,60 @@ pub fn new(
             .target_dir
             .join("deps")
             .join(format!("{package_name}.map"));
-        let src_cert_file_path = params.target_dir.join(format!("{WDR_LOCAL_TEST_CERT}.cer"));
+        let cert_store = params
+            .package_config
+            .cert_store
+            .clone()
+            .unwrap_or_else(|| WDR_TEST_CERT_STORE.to_string());
+        let cert_name = params
+            .package_config
+            .cert_name
+            .clone()
+            .unwrap_or_else(|| WDR_LOCAL_TEST_CERT.to_string());
+        // Certificates selected by thumbprint are exported under their thumbprint, as
+        // they can share the name of other certificates in the store
+        let cert_file_stem = params
+            .package_config
+            .cert_thumbprint
+            .clone()
+            .unwrap_or_else(|| cert_name.clone());
+        let src_cert_file_path = params.target_dir.join(format!("{cert_file_stem}.cer"));
+        let src_import_lib_file_path = params
+            .target_dir
+            .join(format!("{package_name}.{src_driver_binary_extension}.lib"));
+        let src_cargo_toml_file_path = params.working_dir.join("Cargo.toml");
 
         // destination paths
-        let dest_driver_binary_extension = match params.driver_model {
-            DriverConfig::Kmdf(_) | DriverConfig::Wdm => "sys",
-            DriverConfig::Umdf(_) => "dll",
+        // Export drivers keep the .dll extension as it is the name their import
+        // library refers to
+        let dest_driver_binary_extension = if params.export_driver {
+            "dll"
+        } else {
+            match params.driver_model {
+                DriverConfig::Kmdf(_) | DriverConfig::Wdm => "sys",
+                DriverConfig::Umdf(_) => "dll",
+            }
         };
 
         let src_renamed_driver_binary_file_path = params
             .target_dir
             .join(format!("{package_name}.{dest_driver_binary_extension}"));
         let dest_root_package_folder: PathBuf =
-            params.target_dir.join(format!("{package_name}_package"));
+            params.package_dir.join(format!("{package_name}_package"));
         let dest_inf_file_path = dest_root_package_folder.join(format!("{package_name}.inf"));
         let dest_driver_binary_path =
             dest_root_package_folder.join(format!("{package_name}.{dest_driver_binary_extension}"));
         let dest_pdb_file_path = dest_root_package_folder.join(format!("{package_name}.pdb"));
         let dest_map_file_path = dest_root_package_folder.join(format!("{package_name}.map"));
-        let dest_cert_file_path =
-            dest_root_package_folder.join(format!("{WDR_LOCAL_TEST_CERT}.cer"));
+        let dest_cert_file_path = dest_root_package_folder.join(format!("{cert_file_stem}.cer"));
         let dest_cat_file_path = dest_root_package_folder.join(format!("{package_name}.cat"));
+        let dest_import_lib_file_path =
+            dest_root_package_folder.join(format!("{package_name}.lib"));
+        // The stamp is kept out of the package folder so that it is not shipped
+        // with the package
+        let package_stamp_file_path = params
+            .package_dir
+            .join(format!("{package_name}_package.stamp"));
 
         let os_mapping = match params.target_arch {
             CpuArchitecture::Amd64 => "10_x64",
@@ -161,14 +263,22 @@ pub fn new(
 
         Self {
             package_name,
+            package_version: params.package_version,
             verify_signature: params.verify_signature,
             sample_class: params.sample_class,
+            stages: params.stages,
+            verification_report: params.verification_report,
+            export_driver: params.export_driver,
+            force_repackage: params.force_repackage,
+            treat_inf_warnings_as_errors: params.treat_inf_warnings_as_errors,
             src_inx_file_path,
             src_driver_binary_file_path,
             src_renamed_driver_binary_file_path,
             src_pdb_file_path,
             src_map_file_path,
             src_cert_file_path,
+            src_import_lib_file_path,
+            src_cargo_toml_file_path,
             dest_root_package_folder,
             dest_inf_file_path,
             dest_driver_binary_path,
@@ -176,9 +286,14 @@ pub fn new(
             dest_map_file_path,
             dest_cert_file_path,
             dest_cat_file_path,
+            dest_import_lib_file_path,
+            package_stamp_file_path,
             arch: params.target_arch,
             os_mapping,
             driver_model: params.driver_model,
+            package_config: params.package_config,
+            cert_store,
+            cert_name,
             wdk_build,
             command_exec,
             fs,
@@ -186,6 +301,9 @@ pub fn new(
     }
 
     /// Entry point method to run the low level driver packaging operations.
+    /// Packaging is skipped if the package is up to date, i.e. none of its
+    /// source files changed since the last successful run with the same
+    /// settings, unless `force_repackage` is set.
     /// # Returns
     /// * `Result<(), PackageTaskError>` - A result indicating success or
     ///   failure.
@@ -204,8 +322,34 @@ pub fn new(
     ///   inf2cat command to generate the cat file.
     /// * `PackageTaskError::InfVerificationCommand` - If there is an error
     ///   verifying the inf file.
+    /// * `PackageTaskError::InfVerificationWarnings` - If infverif reports
+    ///   warnings and `treat_inf_warnings_as_errors` is set.
     /// * `PackageTaskError::MissingInxSrcFile` - If the .inx source file is
     ///   missing.
+    /// * `PackageTaskError::InvalidDriverDate`,
+    ///   `PackageTaskError::InvalidCatalogFileDecoration`,
+    ///   `PackageTaskError::InvalidVersionDirective`,
+    ///   `PackageTaskError::InvalidHardwareId` - If the package metadata is not
+    ///   valid.
+    /// * `PackageTaskError::MissingVersionSection` - If directives must be
+    ///   added to an INF file that has no `[Version]` section.
+    /// * `PackageTaskError::MissingHardwareIds` - If the INF template uses the
+    ///   `$HARDWARE_IDS$` token but no hardware IDs are set.
+    /// * `PackageTaskError::ConflictingCertOptions` - If a certificate file is
+    ///   configured along with a certificate store, name or thumbprint, or a
+    ///   certificate thumbprint along with a certificate name.
+    /// * `PackageTaskError::IncompleteCspOptions` - If only one of a CSP and a
+    ///   key container is configured, or they are configured without a
+    ///   certificate file.
+    /// * `PackageTaskError::InvalidCertThumbprint`,
+    ///   `PackageTaskError::InvalidDigestAlgorithm` - If the signing settings
+    ///   are not valid.
+    /// * `PackageTaskError::CertNotFoundInStore` - If a user provided
+    ///   certificate is not in the certificate store.
+    /// * `PackageTaskError::MissingCertFile` - If the configured certificate
+    ///   file does not exist.
+    /// * `PackageTaskError::MissingProductionCert` - If no certificate is
+    ///   configured in production sign mode.
     /// * `PackageTaskError::StampinfCommand` - If there is an error running the
     ///   stampinf command to generate the inf file from the .inx template file.
     /// * `PackageTaskError::VerifyCertExistsInStoreCommand` - If there is an
@@ -217,43 +361,205 @@ pub fn new(
     ///   the WDK build number.
     /// * `PackageTaskError::Io` - Wraps all possible IO errors.
     pub fn run(&self) -> Result<(), PackageTaskError> {
-        self.check_inx_exists()?;
-        debug!("Creating final package directory if it doesn't exist");
-        if !self.fs.exists(&self.dest_root_package_folder) {
-            self.fs.create_dir(&self.dest_root_package_folder)?;
+        let package_stamp = self.package_stamp();
+        if self.is_package_up_to_date(&package_stamp) {
+            info!(
+                "Package {} is up to date. Skipping package task",
+                self.package_name
+            );
+            return Ok(());
         }
-        info!(
-            "Copying files to target package folder: {}",
-            self.dest_root_package_folder.to_string_lossy()
-        );
+        if self.export_driver {
+            self.run_for_export_driver()?;
+        } else {
+            self.run_for_driver()?;
+        }
+        self.fs
+            .write_to_file(&self.package_stamp_file_path, package_stamp.as_bytes())?;
+        Ok(())
+    }
+
+    /// Packages a driver that is installed through an INF file
+    fn run_for_driver(&self) -> Result<(), PackageTaskError> {
+        self.check_inx_exists()?;
+        validate_package_config(&self.package_config)?;
+        self.create_package_folder()?;
         self.rename_driver_binary_extension()?;
         self.copy(
             &self.src_renamed_driver_binary_file_path,
             &self.dest_driver_binary_path,
         )?;
         self.copy(&self.src_pdb_file_path, &self.dest_pdb_file_path)?;
-        self.copy(&self.src_inx_file_path, &self.dest_inf_file_path)?;
+        let inf_line_map = self.write_inf_file()?;
         self.copy(&self.src_map_file_path, &self.dest_map_file_path)?;
         self.run_stampinf()?;
         self.run_inf2cat()?;
-        self.generate_certificate()?;
-        self.copy(&self.src_cert_file_path, &self.dest_cert_file_path)?;
-        self.run_signtool_sign(
+        if self.stages.sign_mode.signs() {
+            self.prepare_signing_certificate()?;
+            self.run_signtool_sign(&self.dest_driver_binary_path)?;
+            self.run_signtool_sign(&self.dest_cat_file_path)?;
+        } else {
+            info!("Skipping signing of driver binary and cat file");
+        }
+        if self.stages.verify {
+            self.run_infverif(inf_line_map)?;
+        } else {
+            info!("Skipping inf verification");
+        }
+        // Verify signatures only when --verify-signature flag = true is passed.
+        // Unsigned packages have no signatures to verify
+        if self.stages.sign_mode.signs() && self.verify_signature {
+            info!("Verifying signatures for driver binary and cat file using signtool");
+            self.run_signtool_verify(&self.dest_driver_binary_path)?;
+            self.run_signtool_verify(&self.dest_cat_file_path)?;
+        }
+        Ok(())
+    }
+
+    /// Packages an export driver. Export drivers are not installed through an
+    /// INF file, so the package only contains the signed driver binary, its
+    /// symbols and the import library other drivers link against.
+    fn run_for_export_driver(&self) -> Result<(), PackageTaskError> {
+        validate_package_config(&self.package_config)?;
+        self.create_package_folder()?;
+        self.copy(
+            &self.src_driver_binary_file_path,
             &self.dest_driver_binary_path,
-            WDR_TEST_CERT_STORE,
-            WDR_LOCAL_TEST_CERT,
         )?;
-        self.run_signtool_sign(
-            &self.dest_cat_file_path,
-            WDR_TEST_CERT_STORE,
-            WDR_LOCAL_TEST_CERT,
+        self.copy(&self.src_pdb_file_path, &self.dest_pdb_file_path)?;
+        self.copy(&self.src_map_file_path, &self.dest_map_file_path)?;
+        self.copy(
+            &self.src_import_lib_file_path,
+            &self.dest_import_lib_file_path,
         )?;
-        self.run_infverif()?;
-        // Verify signatures only when --verify-signature flag = true is passed
-        if self.verify_signature {
-            info!("Verifying signatures for driver binary and cat file using signtool");
+        if self.stages.sign_mode.signs() {
+            self.prepare_signing_certificate()?;
+            self.run_signtool_sign(&self.dest_driver_binary_path)?;
+        } else {
+            info!("Skipping signing of export driver binary");
+        }
+        if self.stages.sign_mode.signs() && self.verify_signature {
+            info!("Verifying signature for export driver binary using signtool");
             self.run_signtool_verify(&self.dest_driver_binary_path)?;
-            self.run_signtool_verify(&self.dest_cat_file_path)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the contents of the package stamp, which describe everything
+    /// that affects the package other than its source files
+    fn package_stamp(&self) -> String {
+        format!(
+            "cargo-wdk {}\narch: {}\ndriver model: {:?}\nstages: {:?}\nverify signature: \
+             {}\nsample class: {}\npackage config: {:?}\n{STAMPINF_VERSION_ENV_VAR}: {:?}\n",
+            env!("CARGO_PKG_VERSION"),
+            self.arch,
+            self.driver_model,
+            self.stages,
+            self.verify_signature,
+            self.sample_class,
+            self.package_config,
+            std::env::var(STAMPINF_VERSION_ENV_VAR).ok(),
+        )
+    }
+
+    /// Returns the source files the package is created from
+    fn package_src_file_paths(&self) -> Vec<&Path> {
+        let mut src_file_paths = vec![
+            self.src_cargo_toml_file_path.as_path(),
+            &self.src_driver_binary_file_path,
+            &self.src_pdb_file_path,
+            &self.src_map_file_path,
+        ];
+        if self.export_driver {
+            src_file_paths.push(&self.src_import_lib_file_path);
+        } else {
+            src_file_paths.push(&self.src_inx_file_path);
+        }
+        if let Some(cert_path) = &self.package_config.cert_path {
+            src_file_paths.push(cert_path);
+        }
+        src_file_paths
+    }
+
+    /// Checks whether the last successful packaging run used the same settings
+    /// and finished after all the source files of the package were last
+    /// modified. Packages are always recreated when a verification report is
+    /// requested, so that the report has results for every package.
+    fn is_package_up_to_date(&self, package_stamp: &str) -> bool {
+        if self.force_repackage {
+            debug!("Repackaging is forced");
+            return false;
+        }
+        if self.verification_report.is_some() {
+            debug!("Verification report is requested, the package is recreated");
+            return false;
+        }
+        match self.fs.read_file_to_string(&self.package_stamp_file_path) {
+            Ok(last_package_stamp) if last_package_stamp == package_stamp => {}
+            Ok(_) => {
+                debug!("Package settings changed since the last packaging run");
+                return false;
+            }
+            Err(e) => {
+                debug!("No previous packaging run found: {e}");
+                return false;
+            }
+        }
+        let last_packaged_at = match self.fs.modified(&self.package_stamp_file_path) {
+            Ok(last_packaged_at) => last_packaged_at,
+            Err(e) => {
+                debug!("Cannot determine when the package was last created: {e}");
+                return false;
+            }
+        };
+        self.package_src_file_paths()
+            .into_iter()
+            .all(|src_file_path| {
+                let is_unchanged = self
+                    .fs
+                    .modified(src_file_path)
+                    .is_ok_and(|modified| modified <= last_packaged_at);
+                if !is_unchanged {
+                    debug!(
+                        "{} changed since the last packaging run",
+                        src_file_path.display()
+                    );
+                }
+                is_unchanged
+            })
+    }
+
+    fn create_package_folder(&self) -> Result<(), FileError> {
+        debug!("Creating final package directory if it doesn't exist");
+        if !self.fs.exists(&self.dest_root_package_folder) {
+            self.fs.create_dir(&self.dest_root_package_folder)?;
+        }
+        info!(
+            "Copying files to target package folder: {}",
+            self.dest_root_package_folder.to_string_lossy()
+        );
+        Ok(())
+    }
+
+    /// Makes sure the certificate to sign the package with is available. In
+    /// test sign mode the default test certificate is generated if needed and
+    /// the certificate is added to the package folder. In production sign
+    /// mode a certificate must be configured, and it is left out of the
+    /// package folder
+    fn prepare_signing_certificate(&self) -> Result<(), PackageTaskError> {
+        if let Some(cert_path) = &self.package_config.cert_path {
+            if !self.fs.exists(cert_path) {
+                return Err(PackageTaskError::MissingCertFile(cert_path.clone()));
+            }
+        } else if self.stages.sign_mode == SignMode::Production {
+            if self.package_config.cert_name.is_none()
+                && self.package_config.cert_thumbprint.is_none()
+            {
+                return Err(PackageTaskError::MissingProductionCert);
+            }
+        } else {
+            self.generate_certificate()?;
+            self.copy(&self.src_cert_file_path, &self.dest_cert_file_path)?;
         }
         Ok(())
     }
@@ -288,6 +594,86 @@ fn copy(&self, src_file_path: &'a Path, dest_file_path: &'a Path) -> Result<u64,
         self.fs.copy(src_file_path, dest_file_path)
     }
 
+    /// Creates the INF file in the package folder from the INX template file.
+    /// The tokens of the template are substituted and the directives from the
+    /// package metadata are added to its `[Version]` section. Returns the map
+    /// of the lines of the INF file to the lines of the INX file
+    fn write_inf_file(&self) -> Result<InfLineMap, PackageTaskError> {
+        debug!(
+            "Creating inf file {} from {}",
+            self.dest_inf_file_path.to_string_lossy(),
+            self.src_inx_file_path.to_string_lossy()
+        );
+        let inx = self.fs.read_file_to_string(&self.src_inx_file_path)?;
+        let (inf, inf_line_map) = self.add_version_directives(self.substitute_inf_tokens(&inx)?)?;
+        self.fs
+            .write_to_file(&self.dest_inf_file_path, inf.as_bytes())?;
+        Ok(inf_line_map)
+    }
+
+    /// Substitutes the driver name, i.e. the file name of the driver binary
+    /// without extension, the crate version and the hardware IDs from the
+    /// package metadata for their tokens in the INF template
+    fn substitute_inf_tokens(&self, inx: &str) -> Result<String, PackageTaskError> {
+        if inx.contains(INF_HARDWARE_IDS_TOKEN) && self.package_config.hardware_ids.is_empty() {
+            return Err(PackageTaskError::MissingHardwareIds(
+                self.src_inx_file_path.clone(),
+            ));
+        }
+        Ok(inx
+            .replace(INF_DRIVER_NAME_TOKEN, &self.package_name)
+            .replace(INF_DRIVER_VERSION_TOKEN, self.package_version)
+            .replace(
+                INF_HARDWARE_IDS_TOKEN,
+                &self.package_config.hardware_ids.join(","),
+            ))
+    }
+
+    /// Adds the decorated `CatalogFile` directives and the additional
+    /// `[Version]` directives from the package metadata to the INF file, along
+    /// with the map of its lines to the lines of the INX file
+    fn add_version_directives(
+        &self,
+        inf: String,
+    ) -> Result<(String, InfLineMap), PackageTaskError> {
+        let mut directives = self
+            .package_config
+            .catalog_file_decorations
+            .iter()
+            .map(|decoration| format!("CatalogFile.{decoration} = {}.cat", self.package_name))
+            .collect::<Vec<_>>();
+        directives.extend(
+            self.package_config
+                .version_attributes
+                .iter()
+                .map(|(name, value)| format!("{name} = {value}")),
+        );
+        if directives.is_empty() {
+            return Ok((inf, InfLineMap::default()));
+        }
+
+        debug!("Adding directives to the [Version] section of the INF file");
+        let line_ending = if inf.contains("\r\n") { "\r\n" } else { "\n" };
+        let mut lines = inf.split(line_ending).collect::<Vec<_>>();
+        // Directives are inserted right after the `[Version]` section header
+        let insert_index = lines
+            .iter()
+            .position(|line| line.trim().eq_ignore_ascii_case("[Version]"))
+            .ok_or_else(|| {
+                PackageTaskError::MissingVersionSection(self.src_inx_file_path.clone())
+            })?
+            + 1;
+        lines.splice(
+            insert_index..insert_index,
+            directives.iter().map(String::as_str),
+        );
+        // `insert_index` is also the line number of the section header
+        Ok((
+            lines.join(line_ending),
+            InfLineMap::new(insert_index, directives.len()),
+        ))
+    }
+
     fn run_stampinf(&self) -> Result<(), PackageTaskError> {
         info!("Running stampinf");
         let wdf_version_flags = match self.driver_model {
@@ -318,7 +704,7 @@ fn run_stampinf(&self) -> Result<(), PackageTaskError> {
             "-f",
             &dest_inf_file_path,
             "-d",
-            "*",
+            self.package_config.driver_date.as_deref().unwrap_or("*"),
             "-a",
             &arch,
             "-c",
@@ -376,6 +762,13 @@ fn generate_certificate(&self) -> Result<(), PackageTaskError> {
         }
         if self.is_self_signed_certificate_in_store()? {
             self.create_cert_file_from_store()?;
+        } else if !self.is_default_cert() {
+            // Only the default test certificate is generated. Certificates
+            // provided by the user must already be in the store
+            return Err(PackageTaskError::CertNotFoundInStore(
+                self.cert_store.clone(),
+                self.cert_name.clone(),
+            ));
         } else {
             // This mutex prevents multiple instances of this app from racing to
             // create a cert in the store. It is not a correctness problem. We
@@ -400,14 +793,40 @@ fn generate_certificate(&self) -> Result<(), PackageTaskError> {
         Ok(())
     }
 
+    /// Returns whether the package is signed with the default test
+    /// certificate, which is generated when it does not exist
+    fn is_default_cert(&self) -> bool {
+        self.cert_store == WDR_TEST_CERT_STORE
+            && self.cert_name == WDR_LOCAL_TEST_CERT
+            && self.package_config.cert_thumbprint.is_none()
+    }
+
     fn is_self_signed_certificate_in_store(&self) -> Result<bool, PackageTaskError> {
-        debug!("Checking if self signed certificate exists in WDRTestCertStore store");
-        let args = ["-s", WDR_TEST_CERT_STORE];
+        let cert_thumbprint = self.package_config.cert_thumbprint.as_deref();
+        debug!(
+            "Checking if certificate {} exists in {} store",
+            cert_thumbprint.unwrap_or(self.cert_name.as_str()),
+            self.cert_store
+        );
+        let args = ["-s", self.cert_store.as_str()];
 
         match self.command_exec.run("certmgr.exe", &args, None, None) {
             Ok(output) if output.status.success() => String::from_utf8(output.stdout).map_or_else(
                 |e| Err(PackageTaskError::VerifyCertExistsInStoreInvalidCommandOutput(e)),
-                |stdout| Ok(stdout.contains(WDR_LOCAL_TEST_CERT)),
+                |stdout| {
+                    Ok(cert_thumbprint.map_or_else(
+                        || stdout.contains(self.cert_name.as_str()),
+                        // certmgr prints thumbprints in groups of hex digits separated by spaces
+                        |cert_thumbprint| {
+                            stdout
+                                .chars()
+                                .filter(|c| !c.is_whitespace())
+                                .collect::<String>()
+                                .to_ascii_uppercase()
+                                .contains(&cert_thumbprint.to_ascii_uppercase())
+                        },
+                    ))
+                },
             ),
             Ok(_) => Ok(false),
             Err(e) => Err(PackageTaskError::VerifyCertExistsInStoreCommand(e)),
@@ -425,9 +844,9 @@ fn create_self_signed_cert_in_store(&self) -> Result<(), PackageTaskError> {
             "-eku",
             "1.3.6.1.5.5.7.3.3",
             "-ss",
-            WDR_TEST_CERT_STORE, // FIXME: this should be a parameter
+            WDR_TEST_CERT_STORE,
             "-n",
-            &format!("CN={WDR_LOCAL_TEST_CERT}"), // FIXME: this should be a parameter
+            &format!("CN={WDR_LOCAL_TEST_CERT}"),
             &cert_path,
         ];
         if let Err(e) = self.command_exec.run("makecert", &args, None, None) {
@@ -437,15 +856,25 @@ fn create_self_signed_cert_in_store(&self) -> Result<(), PackageTaskError> {
     }
 
     fn create_cert_file_from_store(&self) -> Result<(), PackageTaskError> {
-        info!("Creating certificate file from WDRTestCertStore store using certmgr");
+        info!(
+            "Creating certificate file from {} store using certmgr",
+            self.cert_store
+        );
         let cert_path = self.src_cert_file_path.to_string_lossy();
+        let (cert_selector, cert_id) = self
+            .package_config
+            .cert_thumbprint
+            .as_ref()
+            .map_or(("-n", &self.cert_name), |cert_thumbprint| {
+                ("-sha1", cert_thumbprint)
+            });
         let args = [
             "-put",
             "-s",
-            WDR_TEST_CERT_STORE,
+            &self.cert_store,
             "-c",
-            "-n",
-            WDR_LOCAL_TEST_CERT,
+            cert_selector,
+            cert_id,
             &cert_path,
         ];
         if let Err(e) = self.command_exec.run("certmgr.exe", &args, None, None) {
@@ -454,21 +883,14 @@ fn create_cert_file_from_store(&self) -> Result<(), PackageTaskError> {
         Ok(())
     }
 
-    /// Signs the specified file using signtool command using certificate from
-    /// certificate store.
+    /// Signs the specified file using signtool command with the signing
+    /// identity configured in the package config. See
+    /// [`signtool_sign_args`].
     ///
     /// # Arguments
     ///
     /// * `file_path` - The path to the file to be signed.
-    /// * `cert_store` - The certificate store to use for signing.
-    /// * `cert_name` - The name of the certificate to use for signing. TODO:
-    ///   Add parameters for certificate store and name
-    fn run_signtool_sign(
-        &self,
-        file_path: &Path,
-        cert_store: &str,
-        cert_name: &str,
-    ) -> Result<(), PackageTaskError> {
+    fn run_signtool_sign(&self, file_path: &Path) -> Result<(), PackageTaskError> {
         info!(
             "Signing {} using signtool",
             file_path
@@ -476,20 +898,8 @@ fn run_signtool_sign(
                 .expect("Unable to read file name from the path")
                 .to_string_lossy()
         );
-        let driver_binary_file_path = file_path.to_string_lossy();
-        let args = [
-            "sign",
-            "/v",
-            "/s",
-            cert_store,
-            "/n",
-            cert_name,
-            "/t",
-            "http://timestamp.digicert.com",
-            "/fd",
-            "SHA256",
-            &driver_binary_file_path,
-        ];
+        let args = signtool_sign_args(&self.package_config, file_path);
+        let args = args.iter().map(String::as_str).collect::<Vec<_>>();
         if let Err(e) = self.command_exec.run("signtool", &args, None, None) {
             return Err(PackageTaskError::DriverBinarySignCommand(e));
         }
@@ -508,13 +918,17 @@ fn run_signtool_verify(&self, file_path: &Path) -> Result<(), PackageTaskError>
         let args = ["verify", "/v", "/pa", &driver_binary_file_path];
         // TODO: Differentiate between command exec failure and signature verification
         // failure
-        if let Err(e) = self.command_exec.run("signtool", &args, None, None) {
+        let result = self.command_exec.run("signtool", &args, None, None);
+        self.record_verification_result(VerificationTool::SigntoolVerify, file_path, &result);
+        if let Err(e) = result {
             return Err(PackageTaskError::DriverBinarySignVerificationCommand(e));
         }
         Ok(())
     }
 
-    fn run_infverif(&self) -> Result<(), PackageTaskError> {
+    /// Verifies the INF file of the package. Diagnostics are recorded for the
+    /// lines of the INX file that `inf_line_map` maps them to.
+    fn run_infverif(&self, inf_line_map: InfLineMap) -> Result<(), PackageTaskError> {
         let additional_args = if self.sample_class {
             let wdk_build_number = self.wdk_build.detect_wdk_build_number()?;
             if MISSING_SAMPLE_FLAG_WDK_BUILD_NUMBER_RANGE.contains(&wdk_build_number) {
@@ -523,6 +937,16 @@ fn run_infverif(&self) -> Result<(), PackageTaskError> {
                      /samples flag."
                 );
                 warn!("InfVerif skipped for samples class. WDK Build: {wdk_build_number}");
+                if let Some(verification_report) = self.verification_report {
+                    verification_report.record_note(
+                        VerificationTool::Infverif,
+                        format!(
+                            "InfVerif skipped for samples class of package {} ({}). WDK Build: \
+                             {wdk_build_number}",
+                            self.package_name, self.arch
+                        ),
+                    );
+                }
                 return Ok(());
             }
             "/msft"
@@ -547,12 +971,68 @@ fn run_infverif(&self) -> Result<(), PackageTaskError> {
         }
         args.push(&inf_path);
 
-        if let Err(e) = self.command_exec.run("infverif", &args, None, None) {
+        let result = self.command_exec.run("infverif", &args, None, None);
+        let verification_result = self
+            .verification_result(
+                VerificationTool::Infverif,
+                &self.dest_inf_file_path,
+                &result,
+            )
+            .with_source_file(&self.src_inx_file_path, inf_line_map);
+        for diagnostic in &verification_result.diagnostics {
+            match diagnostic.severity {
+                Severity::Warning => warn!("{diagnostic}"),
+                Severity::Error => err!("{diagnostic}"),
+            }
+        }
+        let warning_count = verification_result.warnings.len();
+        if let Some(verification_report) = self.verification_report {
+            verification_report.record(verification_result);
+        }
+        if let Err(e) = result {
             return Err(PackageTaskError::InfVerificationCommand(e));
         }
+        if self.treat_inf_warnings_as_errors && warning_count > 0 {
+            return Err(PackageTaskError::InfVerificationWarnings(
+                warning_count,
+                self.src_inx_file_path.clone(),
+            ));
+        }
 
         Ok(())
     }
+
+    /// Records the result of a verification tool in the verification report
+    fn record_verification_result(
+        &self,
+        tool: VerificationTool,
+        file_path: &Path,
+        result: &Result<Output, CommandError>,
+    ) {
+        if let Some(verification_report) = self.verification_report {
+            verification_report.record(self.verification_result(tool, file_path, result));
+        }
+    }
+
+    /// Parses the result of a verification tool run from the outcome of its
+    /// command
+    fn verification_result(
+        &self,
+        tool: VerificationTool,
+        file_path: &Path,
+        result: &Result<Output, CommandError>,
+    ) -> VerificationResult {
+        let stdout = result
+            .as_ref()
+            .map(|output| String::from_utf8_lossy(&output.stdout).to_string());
+        VerificationResult::new(
+            &self.package_name,
+            *self.arch,
+            tool,
+            file_path,
+            stdout.as_deref().map_err(|e| *e),
+        )
+    }
 }
 
 /// An RAII wrapper over a Win API named mutex
@@ -610,17 +1090,191 @@ fn drop(&mut self) {
     }
 }
 
+/// Validates the formats of the package metadata before any tool is invoked
+fn validate_package_config(package_config: &PackageConfig) -> Result<(), PackageTaskError> {
+    if let Some(driver_date) = &package_config.driver_date
+        && !is_valid_driver_date(driver_date)
+    {
+        return Err(PackageTaskError::InvalidDriverDate(driver_date.clone()));
+    }
+    if let Some(decoration) = package_config
+        .catalog_file_decorations
+        .iter()
+        .find(|decoration| !is_valid_directive_name(decoration))
+    {
+        return Err(PackageTaskError::InvalidCatalogFileDecoration(
+            decoration.clone(),
+        ));
+    }
+    if let Some((name, value)) = package_config
+        .version_attributes
+        .iter()
+        .find(|(name, value)| !is_valid_version_directive(name, value))
+    {
+        return Err(PackageTaskError::InvalidVersionDirective(format!(
+            "{name} = {value}"
+        )));
+    }
+    if let Some(hardware_id) = package_config
+        .hardware_ids
+        .iter()
+        .find(|hardware_id| !is_valid_hardware_id(hardware_id))
+    {
+        return Err(PackageTaskError::InvalidHardwareId(hardware_id.clone()));
+    }
+    if package_config.cert_path.is_some()
+        && (package_config.cert_store.is_some()
+            || package_config.cert_name.is_some()
+            || package_config.cert_thumbprint.is_some())
+    {
+        return Err(PackageTaskError::ConflictingCertOptions);
+    }
+    if package_config.cert_thumbprint.is_some() && package_config.cert_name.is_some() {
+        return Err(PackageTaskError::ConflictingCertOptions);
+    }
+    if let Some(cert_thumbprint) = package_config
+        .cert_thumbprint
+        .as_ref()
+        .filter(|cert_thumbprint| !is_valid_cert_thumbprint(cert_thumbprint))
+    {
+        return Err(PackageTaskError::InvalidCertThumbprint(
+            cert_thumbprint.clone(),
+        ));
+    }
+    if package_config.csp.is_some() != package_config.key_container.is_some()
+        || (package_config.csp.is_some() && package_config.cert_path.is_none())
+    {
+        return Err(PackageTaskError::IncompleteCspOptions);
+    }
+    if let Some(digest_algorithm) =
+        package_config
+            .digest_algorithm
+            .as_ref()
+            .filter(|digest_algorithm| {
+                !DIGEST_ALGORITHMS
+                    .iter()
+                    .any(|algorithm| algorithm.eq_ignore_ascii_case(digest_algorithm))
+            })
+    {
+        return Err(PackageTaskError::InvalidDigestAlgorithm(
+            digest_algorithm.clone(),
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that the thumbprint is a SHA1 hash, i.e. 40 hex digits
+fn is_valid_cert_thumbprint(cert_thumbprint: &str) -> bool {
+    cert_thumbprint.len() == 40 && cert_thumbprint.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Returns the arguments of the signtool command that signs `file_path` with
+/// the signing identity configured in `package_config`. The certificate is
+/// taken from the certificate file if one is configured, with its private key
+/// kept by the configured CSP if any, or from the certificate store otherwise,
+/// where it is selected by thumbprint or by name.
+pub(crate) fn signtool_sign_args(package_config: &PackageConfig, file_path: &Path) -> Vec<String> {
+    let mut args = vec!["sign".to_string(), "/v".to_string()];
+    if let Some(cert_path) = &package_config.cert_path {
+        args.extend(["/f".to_string(), cert_path.to_string_lossy().into_owned()]);
+        if let (Some(csp), Some(key_container)) =
+            (&package_config.csp, &package_config.key_container)
+        {
+            args.extend([
+                "/csp".to_string(),
+                csp.clone(),
+                "/kc".to_string(),
+                key_container.clone(),
+            ]);
+        }
+    } else {
+        let cert_store = package_config
+            .cert_store
+            .as_deref()
+            .unwrap_or(WDR_TEST_CERT_STORE);
+        args.extend(["/s".to_string(), cert_store.to_string()]);
+        match &package_config.cert_thumbprint {
+            Some(cert_thumbprint) => args.extend(["/sha1".to_string(), cert_thumbprint.clone()]),
+            None => args.extend([
+                "/n".to_string(),
+                package_config
+                    .cert_name
+                    .as_deref()
+                    .unwrap_or(WDR_LOCAL_TEST_CERT)
+                    .to_string(),
+            ]),
+        }
+    }
+    args.extend([
+        "/t".to_string(),
+        TIMESTAMP_SERVER_URL.to_string(),
+        "/fd".to_string(),
+        package_config
+            .digest_algorithm
+            .as_deref()
+            .unwrap_or(DEFAULT_DIGEST_ALGORITHM)
+            .to_string(),
+        file_path.to_string_lossy().into_owned(),
+    ]);
+    args
+}
+
+/// Checks that the date is in the `MM/DD/YYYY` format expected by stampinf
+fn is_valid_driver_date(date: &str) -> bool {
+    let parts = date.split('/').collect::<Vec<_>>();
+    let [month, day, year] = parts.as_slice() else {
+        return false;
+    };
+    let is_number_of_len =
+        |part: &str, len: usize| part.len() == len && part.bytes().all(|b| b.is_ascii_digit());
+    if !(is_number_of_len(month, 2) && is_number_of_len(day, 2) && is_number_of_len(year, 4)) {
+        return false;
+    }
+    matches!(month.parse::<u8>(), Ok(1..=12)) && matches!(day.parse::<u8>(), Ok(1..=31))
+}
+
+/// Checks that the name only consists of characters allowed in INF directive
+/// names and platform extensions
+fn is_valid_directive_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_')
+}
+
+/// Checks that the hardware ID is not empty and has no whitespace or commas,
+/// which are not allowed in device identification strings
+fn is_valid_hardware_id(hardware_id: &str) -> bool {
+    !hardware_id.is_empty() && !hardware_id.chars().any(|c| c.is_whitespace() || c == ',')
+}
+
+/// Checks that the directive has a valid name that is not managed by
+/// stampinf and a non-empty single line value
+fn is_valid_version_directive(name: &str, value: &str) -> bool {
+    let base_name = name.split('.').next().unwrap_or(name);
+    is_valid_directive_name(name)
+        && !RESERVED_VERSION_DIRECTIVES
+            .iter()
+            .any(|reserved| base_name.eq_ignore_ascii_case(reserved))
+        && !value.trim().is_empty()
+        && !value.contains(['\r', '\n'])
+}
+
 #[cfg(test)]
 mod tests {
     use std::{
         path::PathBuf,
         process::{ExitStatus, Output},
+        time::{Duration, SystemTime},
     };
 
+    use mockall::predicate::eq;
     use wdk_build::{CpuArchitecture, KmdfConfig};
 
     use super::*;
 
+    const CERT_THUMBPRINT: &str = "0123456789ABCDEF0123456789ABCDEF01234567";
+
     #[test]
     fn new_succeeds_for_valid_args() {
         let package_name = "test_package";
@@ -630,11 +1284,19 @@ fn new_succeeds_for_valid_args() {
 
         let package_task_params = PackageTaskParams {
             package_name,
+            package_version: "0.1.0",
             working_dir: &working_dir,
             target_dir: &target_dir,
+            package_dir: &target_dir,
             target_arch: &arch,
             driver_model: DriverConfig::Kmdf(KmdfConfig::default()),
             sample_class: false,
+            stages: PackageStages::default(),
+            verification_report: None,
+            package_config: PackageConfig::default(),
+            export_driver: false,
+            force_repackage: false,
+            treat_inf_warnings_as_errors: false,
             verify_signature: false,
         };
         let dest_root = target_dir.join(format!("{package_name}_package"));
@@ -682,6 +1344,85 @@ fn new_succeeds_for_valid_args() {
         assert!(matches!(task.driver_model, DriverConfig::Kmdf(_)));
     }
 
+    #[test]
+    fn new_creates_the_package_in_the_package_dir() {
+        let working_dir = PathBuf::from("D:/absolute/path/to/working/dir");
+        let target_dir = PathBuf::from("C:/shared/target/x86_64-pc-windows-msvc/debug");
+        let package_dir = PathBuf::from("D:/artifacts/x86_64-pc-windows-msvc/debug");
+        let arch = CpuArchitecture::Amd64;
+
+        let package_task_params = PackageTaskParams {
+            package_dir: &package_dir,
+            ..package_task_params(&working_dir, &target_dir, &arch, false)
+        };
+
+        let command_exec = CommandExec::default();
+        let wdk_build = WdkBuild::default();
+        let fs = Fs::default();
+        let task = PackageTask::new(package_task_params, &wdk_build, &command_exec, &fs);
+        assert_eq!(
+            task.src_driver_binary_file_path,
+            target_dir.join("driver.dll")
+        );
+        assert_eq!(task.src_pdb_file_path, target_dir.join("driver.pdb"));
+        assert_eq!(
+            task.dest_root_package_folder,
+            package_dir.join("driver_package")
+        );
+        assert_eq!(
+            task.dest_driver_binary_path,
+            package_dir.join("driver_package").join("driver.sys")
+        );
+        assert_eq!(
+            task.package_stamp_file_path,
+            package_dir.join("driver_package.stamp")
+        );
+    }
+
+    #[test]
+    fn new_keeps_dll_extension_for_export_drivers() {
+        let working_dir = PathBuf::from("D:/absolute/path/to/working/dir");
+        let target_dir = PathBuf::from("C:/absolute/path/to/target/dir");
+        let arch = CpuArchitecture::Amd64;
+
+        let package_task_params = PackageTaskParams {
+            package_name: "export-driver",
+            package_version: "0.1.0",
+            working_dir: &working_dir,
+            target_dir: &target_dir,
+            package_dir: &target_dir,
+            target_arch: &arch,
+            driver_model: DriverConfig::Wdm,
+            sample_class: false,
+            stages: PackageStages::default(),
+            verification_report: None,
+            package_config: PackageConfig::default(),
+            export_driver: true,
+            force_repackage: false,
+            treat_inf_warnings_as_errors: false,
+            verify_signature: false,
+        };
+        let dest_root = target_dir.join("export_driver_package");
+
+        let command_exec = CommandExec::default();
+        let wdk_build = WdkBuild::default();
+        let fs = Fs::default();
+        let task = PackageTask::new(package_task_params, &wdk_build, &command_exec, &fs);
+        assert!(task.export_driver);
+        assert_eq!(
+            task.dest_driver_binary_path,
+            dest_root.join("export_driver.dll")
+        );
+        assert_eq!(
+            task.src_import_lib_file_path,
+            target_dir.join("export_driver.dll.lib")
+        );
+        assert_eq!(
+            task.dest_import_lib_file_path,
+            dest_root.join("export_driver.lib")
+        );
+    }
+
     #[test]
     #[should_panic(expected = "Target directory path must be absolute. Input path: \
                                ../relative/path/to/target/dir")]
@@ -693,11 +1434,19 @@ fn new_panics_when_target_dir_is_not_absolute() {
 
         let package_task_params = PackageTaskParams {
             package_name,
+            package_version: "0.1.0",
             working_dir: &working_dir,
             target_dir: &target_dir,
+            package_dir: &target_dir,
             target_arch: &arch,
             driver_model: DriverConfig::Kmdf(KmdfConfig::default()),
             sample_class: false,
+            stages: PackageStages::default(),
+            verification_report: None,
+            package_config: PackageConfig::default(),
+            export_driver: false,
+            force_repackage: false,
+            treat_inf_warnings_as_errors: false,
             verify_signature: false,
         };
 
@@ -719,11 +1468,19 @@ fn new_panics_when_working_dir_is_not_absolute() {
 
         let package_task_params = PackageTaskParams {
             package_name,
+            package_version: "0.1.0",
             working_dir: &working_dir,
             target_dir: &target_dir,
+            package_dir: &target_dir,
             target_arch: &arch,
             driver_model: DriverConfig::Kmdf(KmdfConfig::default()),
             sample_class: false,
+            stages: PackageStages::default(),
+            verification_report: None,
+            package_config: PackageConfig::default(),
+            export_driver: false,
+            force_repackage: false,
+            treat_inf_warnings_as_errors: false,
             verify_signature: false,
         };
 
@@ -754,11 +1511,19 @@ fn stampinf_version_overrides_with_env_var() {
 
                     let params = PackageTaskParams {
                         package_name,
+                        package_version: "0.1.0",
                         working_dir: &working_dir,
                         target_dir: &target_dir,
+                        package_dir: &target_dir,
                         target_arch: &arch,
                         driver_model: DriverConfig::Kmdf(KmdfConfig::default()),
                         sample_class: false,
+                        stages: PackageStages::default(),
+                        verification_report: None,
+                        package_config: PackageConfig::default(),
+                        export_driver: false,
+                        force_repackage: false,
+                        treat_inf_warnings_as_errors: false,
                         verify_signature: false,
                     };
 
@@ -799,6 +1564,494 @@ fn stampinf_version_overrides_with_env_var() {
         }
     }
 
+    #[test]
+    fn driver_date_validation() {
+        for date in ["01/31/2025", "12/01/1999"] {
+            assert!(is_valid_driver_date(date), "{date} should be valid");
+        }
+        for date in [
+            "1/31/2025",
+            "13/01/2025",
+            "01/32/2025",
+            "01/00/2025",
+            "2025-01-31",
+            "*",
+        ] {
+            assert!(!is_valid_driver_date(date), "{date} should be invalid");
+        }
+    }
+
+    #[test]
+    fn version_directive_validation() {
+        assert!(is_valid_version_directive("PnpLockdown", "1"));
+        assert!(is_valid_version_directive("ClassVer", "2.0"));
+        assert!(!is_valid_version_directive(
+            "DriverVer",
+            "01/01/2025,1.0.0.0"
+        ));
+        assert!(!is_valid_version_directive("CatalogFile.NTamd64", "a.cat"));
+        assert!(!is_valid_version_directive("Pnp Lockdown", "1"));
+        assert!(!is_valid_version_directive("PnpLockdown", " "));
+        assert!(!is_valid_version_directive("PnpLockdown", "1\n[Strings]"));
+    }
+
+    #[test]
+    fn new_uses_inf_template_from_package_config() {
+        let working_dir = PathBuf::from("C:/abs/driver");
+        let target_dir = PathBuf::from("C:/abs/driver/target/debug");
+        let arch = CpuArchitecture::Amd64;
+        let inf = working_dir.join("inf").join("driver.inx");
+
+        let command_exec = CommandExec::default();
+        let wdk_build = WdkBuild::default();
+        let fs = Fs::default();
+        let task = PackageTask::new(
+            PackageTaskParams {
+                package_config: PackageConfig {
+                    inf: Some(inf.clone()),
+                    ..PackageConfig::default()
+                },
+                ..package_task_params(&working_dir, &target_dir, &arch, false)
+            },
+            &wdk_build,
+            &command_exec,
+            &fs,
+        );
+        assert_eq!(task.src_inx_file_path, inf);
+        assert_eq!(
+            task.dest_inf_file_path,
+            target_dir.join("driver_package").join("driver.inf")
+        );
+    }
+
+    #[test]
+    fn inf_tokens_substitution() {
+        let working_dir = PathBuf::from("C:/abs/driver");
+        let target_dir = PathBuf::from("C:/abs/driver/target/debug");
+        let arch = CpuArchitecture::Amd64;
+        let inx = "ServiceBinary = %13%\\$DRIVER_NAME$.sys\r\n; Version \
+                   $DRIVER_VERSION$\r\n%DeviceDesc% = Install, $HARDWARE_IDS$\r\n";
+
+        let command_exec = CommandExec::default();
+        let wdk_build = WdkBuild::default();
+        let fs = Fs::default();
+        let task = PackageTask::new(
+            PackageTaskParams {
+                package_config: PackageConfig {
+                    hardware_ids: vec![
+                        "Root\\SAMPLE_HW_ID".to_string(),
+                        "Root\\SAMPLE_COMPATIBLE_ID".to_string(),
+                    ],
+                    ..PackageConfig::default()
+                },
+                ..package_task_params(&working_dir, &target_dir, &arch, false)
+            },
+            &wdk_build,
+            &command_exec,
+            &fs,
+        );
+        assert_eq!(
+            task.substitute_inf_tokens(inx)
+                .expect("tokens should be substituted"),
+            "ServiceBinary = %13%\\driver.sys\r\n; Version 0.1.0\r\n%DeviceDesc% = Install, \
+             Root\\SAMPLE_HW_ID,Root\\SAMPLE_COMPATIBLE_ID\r\n"
+        );
+
+        let task = PackageTask::new(
+            package_task_params(&working_dir, &target_dir, &arch, false),
+            &wdk_build,
+            &command_exec,
+            &fs,
+        );
+        assert!(matches!(
+            task.substitute_inf_tokens(inx),
+            Err(PackageTaskError::MissingHardwareIds(_))
+        ));
+    }
+
+    #[test]
+    fn package_config_validation() {
+        assert!(validate_package_config(&PackageConfig::default()).is_ok());
+        assert!(matches!(
+            validate_package_config(&PackageConfig {
+                catalog_file_decorations: vec!["NT amd64".to_string()],
+                ..PackageConfig::default()
+            }),
+            Err(PackageTaskError::InvalidCatalogFileDecoration(_))
+        ));
+        assert!(matches!(
+            validate_package_config(&PackageConfig {
+                driver_date: Some("2025/01/31".to_string()),
+                ..PackageConfig::default()
+            }),
+            Err(PackageTaskError::InvalidDriverDate(_))
+        ));
+        assert!(matches!(
+            validate_package_config(&PackageConfig {
+                cert_name: Some("ContosoTestCert".to_string()),
+                cert_path: Some(PathBuf::from("C:/certs/contoso.pfx")),
+                ..PackageConfig::default()
+            }),
+            Err(PackageTaskError::ConflictingCertOptions)
+        ));
+        assert!(matches!(
+            validate_package_config(&PackageConfig {
+                hardware_ids: vec!["Root\\SAMPLE HW ID".to_string()],
+                ..PackageConfig::default()
+            }),
+            Err(PackageTaskError::InvalidHardwareId(_))
+        ));
+        assert!(matches!(
+            validate_package_config(&PackageConfig {
+                cert_name: Some("ContosoTestCert".to_string()),
+                cert_thumbprint: Some(CERT_THUMBPRINT.to_string()),
+                ..PackageConfig::default()
+            }),
+            Err(PackageTaskError::ConflictingCertOptions)
+        ));
+        assert!(matches!(
+            validate_package_config(&PackageConfig {
+                cert_thumbprint: Some("not a thumbprint".to_string()),
+                ..PackageConfig::default()
+            }),
+            Err(PackageTaskError::InvalidCertThumbprint(_))
+        ));
+        assert!(matches!(
+            validate_package_config(&PackageConfig {
+                cert_path: Some(PathBuf::from("C:/certs/contoso.cer")),
+                csp: Some("eToken Base Cryptographic Provider".to_string()),
+                ..PackageConfig::default()
+            }),
+            Err(PackageTaskError::IncompleteCspOptions)
+        ));
+        assert!(matches!(
+            validate_package_config(&PackageConfig {
+                csp: Some("eToken Base Cryptographic Provider".to_string()),
+                key_container: Some("contoso-ev".to_string()),
+                ..PackageConfig::default()
+            }),
+            Err(PackageTaskError::IncompleteCspOptions)
+        ));
+        assert!(matches!(
+            validate_package_config(&PackageConfig {
+                digest_algorithm: Some("MD5".to_string()),
+                ..PackageConfig::default()
+            }),
+            Err(PackageTaskError::InvalidDigestAlgorithm(_))
+        ));
+        assert!(
+            validate_package_config(&PackageConfig {
+                cert_store: Some("My".to_string()),
+                cert_thumbprint: Some(CERT_THUMBPRINT.to_string()),
+                digest_algorithm: Some("sha384".to_string()),
+                ..PackageConfig::default()
+            })
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn signtool_sign_args_select_signing_identity() {
+        let file_path = Path::new("C:/abs/driver_package/driver.sys");
+        let assert_args = |package_config: &PackageConfig, expected_args: &[&str]| {
+            assert_eq!(signtool_sign_args(package_config, file_path), expected_args);
+        };
+        let timestamp_args = ["/t", TIMESTAMP_SERVER_URL, "/fd"];
+        let file_path_arg = file_path.to_string_lossy().into_owned();
+
+        assert_args(
+            &PackageConfig::default(),
+            &[
+                &[
+                    "sign",
+                    "/v",
+                    "/s",
+                    WDR_TEST_CERT_STORE,
+                    "/n",
+                    WDR_LOCAL_TEST_CERT,
+                ][..],
+                &timestamp_args,
+                &["SHA256", file_path_arg.as_str()],
+            ]
+            .concat(),
+        );
+        assert_args(
+            &PackageConfig {
+                cert_store: Some("My".to_string()),
+                cert_thumbprint: Some(CERT_THUMBPRINT.to_string()),
+                digest_algorithm: Some("SHA384".to_string()),
+                ..PackageConfig::default()
+            },
+            &[
+                &["sign", "/v", "/s", "My", "/sha1", CERT_THUMBPRINT][..],
+                &timestamp_args,
+                &["SHA384", file_path_arg.as_str()],
+            ]
+            .concat(),
+        );
+        assert_args(
+            &PackageConfig {
+                cert_path: Some(PathBuf::from("C:/certs/contoso.cer")),
+                csp: Some("eToken Base Cryptographic Provider".to_string()),
+                key_container: Some("contoso-ev".to_string()),
+                ..PackageConfig::default()
+            },
+            &[
+                &[
+                    "sign",
+                    "/v",
+                    "/f",
+                    "C:/certs/contoso.cer",
+                    "/csp",
+                    "eToken Base Cryptographic Provider",
+                    "/kc",
+                    "contoso-ev",
+                ][..],
+                &timestamp_args,
+                &["SHA256", file_path_arg.as_str()],
+            ]
+            .concat(),
+        );
+    }
+
+    #[test]
+    fn new_names_cert_file_after_thumbprint() {
+        let working_dir = PathBuf::from("C:/abs/driver");
+        let target_dir = PathBuf::from("C:/abs/driver/target/debug");
+        let arch = CpuArchitecture::Amd64;
+
+        let command_exec = CommandExec::default();
+        let wdk_build = WdkBuild::default();
+        let fs = Fs::default();
+        let task = PackageTask::new(
+            PackageTaskParams {
+                package_config: PackageConfig {
+                    cert_thumbprint: Some(CERT_THUMBPRINT.to_string()),
+                    ..PackageConfig::default()
+                },
+                ..package_task_params(&working_dir, &target_dir, &arch, false)
+            },
+            &wdk_build,
+            &command_exec,
+            &fs,
+        );
+        assert!(!task.is_default_cert());
+        assert_eq!(
+            task.src_cert_file_path,
+            target_dir.join(format!("{CERT_THUMBPRINT}.cer"))
+        );
+    }
+
+    fn package_task_params<'a>(
+        working_dir: &'a Path,
+        target_dir: &'a Path,
+        arch: &'a CpuArchitecture,
+        force_repackage: bool,
+    ) -> PackageTaskParams<'a> {
+        PackageTaskParams {
+            package_name: "driver",
+            package_version: "0.1.0",
+            working_dir,
+            target_dir,
+            package_dir: target_dir,
+            target_arch: arch,
+            driver_model: DriverConfig::Kmdf(KmdfConfig::default()),
+            sample_class: false,
+            stages: PackageStages::default(),
+            verification_report: None,
+            package_config: PackageConfig::default(),
+            export_driver: false,
+            force_repackage,
+            treat_inf_warnings_as_errors: false,
+            verify_signature: false,
+        }
+    }
+
+    /// Sets up `fs` so that the package stamp matches `package_stamp` and
+    /// was written at `packaged_at`, and the source files of the package
+    /// were modified at `src_modified_at`
+    fn expect_package_stamp(
+        fs: &mut Fs,
+        target_dir: &Path,
+        package_stamp: String,
+        packaged_at: SystemTime,
+        src_modified_at: SystemTime,
+    ) {
+        let package_stamp_file_path = target_dir.join("driver_package.stamp");
+        fs.expect_read_file_to_string()
+            .with(eq(package_stamp_file_path.clone()))
+            .returning(move |_| Ok(package_stamp.clone()));
+        fs.expect_modified()
+            .with(eq(package_stamp_file_path.clone()))
+            .returning(move |_| Ok(packaged_at));
+        fs.expect_modified()
+            .withf(move |path: &Path| path != package_stamp_file_path)
+            .returning(move |_| Ok(src_modified_at));
+    }
+
+    #[test]
+    fn run_skips_packaging_when_package_is_up_to_date() {
+        let working_dir = PathBuf::from("C:/abs/driver");
+        let target_dir = PathBuf::from("C:/abs/driver/target/debug");
+        let arch = CpuArchitecture::Amd64;
+        let packaged_at = SystemTime::UNIX_EPOCH + Duration::from_secs(200);
+        let src_modified_at = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+
+        let wdk_build = WdkBuild::default();
+        let command_exec = CommandExec::default();
+        let result = crate::test_utils::with_env::<&str, &str, _, _>(&[], || {
+            let package_stamp = PackageTask::new(
+                package_task_params(&working_dir, &target_dir, &arch, false),
+                &wdk_build,
+                &command_exec,
+                &Fs::default(),
+            )
+            .package_stamp();
+
+            // No command or file write expectations are set, so packaging
+            // must be skipped
+            let mut fs = Fs::default();
+            expect_package_stamp(
+                &mut fs,
+                &target_dir,
+                package_stamp,
+                packaged_at,
+                src_modified_at,
+            );
+            PackageTask::new(
+                package_task_params(&working_dir, &target_dir, &arch, false),
+                &wdk_build,
+                &command_exec,
+                &fs,
+            )
+            .run()
+        });
+        assert!(result.is_ok(), "up to date package failed: {result:?}");
+    }
+
+    #[test]
+    fn package_is_not_up_to_date_when_a_src_file_changed_or_settings_changed() {
+        let working_dir = PathBuf::from("C:/abs/driver");
+        let target_dir = PathBuf::from("C:/abs/driver/target/debug");
+        let arch = CpuArchitecture::Amd64;
+        let packaged_at = SystemTime::UNIX_EPOCH + Duration::from_secs(200);
+        let src_modified_at = SystemTime::UNIX_EPOCH + Duration::from_secs(300);
+
+        let wdk_build = WdkBuild::default();
+        let command_exec = CommandExec::default();
+        let (src_changed, settings_changed) =
+            crate::test_utils::with_env::<&str, &str, _, _>(&[], || {
+                let package_stamp = PackageTask::new(
+                    package_task_params(&working_dir, &target_dir, &arch, false),
+                    &wdk_build,
+                    &command_exec,
+                    &Fs::default(),
+                )
+                .package_stamp();
+
+                let mut fs = Fs::default();
+                expect_package_stamp(
+                    &mut fs,
+                    &target_dir,
+                    package_stamp.clone(),
+                    packaged_at,
+                    src_modified_at,
+                );
+                let task = PackageTask::new(
+                    package_task_params(&working_dir, &target_dir, &arch, false),
+                    &wdk_build,
+                    &command_exec,
+                    &fs,
+                );
+                let src_changed = !task.is_package_up_to_date(&package_stamp);
+
+                let mut fs = Fs::default();
+                expect_package_stamp(
+                    &mut fs,
+                    &target_dir,
+                    package_stamp.replace("stages", "previous stages"),
+                    packaged_at,
+                    packaged_at,
+                );
+                let task = PackageTask::new(
+                    package_task_params(&working_dir, &target_dir, &arch, false),
+                    &wdk_build,
+                    &command_exec,
+                    &fs,
+                );
+                let settings_changed = !task.is_package_up_to_date(&package_stamp);
+                (src_changed, settings_changed)
+            });
+        assert!(src_changed, "package with a changed src file is up to date");
+        assert!(
+            settings_changed,
+            "package with changed settings is up to date"
+        );
+    }
+
+    #[test]
+    fn package_is_not_up_to_date_when_repackaging_is_forced() {
+        let working_dir = PathBuf::from("C:/abs/driver");
+        let target_dir = PathBuf::from("C:/abs/driver/target/debug");
+        let arch = CpuArchitecture::Amd64;
+
+        // No file system expectations are set, so the package stamp must not
+        // be read
+        let wdk_build = WdkBuild::default();
+        let command_exec = CommandExec::default();
+        let fs = Fs::default();
+        let task = PackageTask::new(
+            package_task_params(&working_dir, &target_dir, &arch, true),
+            &wdk_build,
+            &command_exec,
+            &fs,
+        );
+        assert!(!task.is_package_up_to_date(""));
+    }
+
+    #[test]
+    fn infverif_warnings_fail_packaging_when_treated_as_errors() {
+        let working_dir = PathBuf::from("C:/abs/driver");
+        let target_dir = PathBuf::from("C:/abs/driver/target/debug");
+        let arch = CpuArchitecture::Amd64;
+        let infverif_output = "INF is VALID\nWARNING(1205) in driver.inf, line 12: Section [Foo] \
+                               referenced from DelFiles and CopyFiles directive.\n";
+
+        let wdk_build = WdkBuild::default();
+        let fs = Fs::default();
+        let mut command_exec = CommandExec::default();
+        command_exec
+            .expect_run()
+            .withf(|command: &str, _, _, _| command == "infverif")
+            .times(2)
+            .returning(move |_, _, _, _| {
+                Ok(Output {
+                    status: ExitStatus::default(),
+                    stdout: infverif_output.as_bytes().to_vec(),
+                    stderr: vec![],
+                })
+            });
+
+        let run_infverif = |treat_inf_warnings_as_errors| {
+            PackageTask::new(
+                PackageTaskParams {
+                    treat_inf_warnings_as_errors,
+                    ..package_task_params(&working_dir, &target_dir, &arch, false)
+                },
+                &wdk_build,
+                &command_exec,
+                &fs,
+            )
+            .run_infverif(InfLineMap::new(2, 2))
+        };
+        assert!(run_infverif(false).is_ok());
+        assert!(matches!(
+            run_infverif(true),
+            Err(PackageTaskError::InfVerificationWarnings(1, path))
+                if path == working_dir.join("driver.inx")
+        ));
+    }
+
     mod named_mutex {
         use std::{
             ffi::CString,
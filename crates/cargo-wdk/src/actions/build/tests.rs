@@ -28,8 +28,16 @@
 };
 use crate::{
     actions::{
+        FailureCategory,
         Profile,
-        build::{BuildAction, BuildActionParams, error::BuildActionError},
+        WdkSource,
+        build::{
+            BuildAction,
+            BuildActionParams,
+            PackageStages,
+            SignMode,
+            error::{BuildActionError, PackageTaskError},
+        },
         to_target_triple,
     },
     providers::error::{CommandError, FileError},
@@ -77,6 +85,442 @@ pub fn given_a_driver_project_when_default_values_are_provided_then_it_builds_su
     );
 }
 
+#[test]
+pub fn given_a_driver_project_when_package_only_is_set_then_it_packages_without_building() {
+    // Input CLI args
+    let cwd = PathBuf::from("C:\\tmp");
+    let target_arch = CpuArchitecture::Amd64;
+    let sample_class = false;
+
+    // Driver project data
+    let driver_type = "KMDF";
+    let driver_name = "sample-kmdf";
+    let driver_version = "0.0.1";
+    let wdk_metadata = get_cargo_metadata_wdk_metadata(driver_type, 1, 33);
+    let (workspace_member, package) =
+        get_cargo_metadata_package(&cwd, driver_name, driver_version, Some(&wdk_metadata));
+
+    // No cargo build expectation is set, so the build task must not run
+    let test_build_action =
+        &TestBuildAction::new(cwd.clone(), None, Some(target_arch), sample_class)
+            .set_up_standalone_driver_project((workspace_member, package))
+            .expect_detect_wdk_build_number(25100u32)
+            .expect_root_manifest_exists(&cwd, true)
+            .expect_default_package_task_steps(driver_name, driver_type, target_arch, false);
+
+    let run_result = run_build_action_with_package_options(
+        &cwd,
+        &[target_arch],
+        true,
+        PackageStages::default(),
+        None,
+        test_build_action,
+    );
+    assert!(
+        run_result.is_ok(),
+        "package only build failed unexpectedly: {run_result:?}"
+    );
+}
+
+#[test]
+pub fn given_a_driver_project_when_signing_and_verification_are_skipped_then_it_builds_an_unsigned_package()
+ {
+    // Input CLI args
+    let cwd = PathBuf::from("C:\\tmp");
+    let target_arch = CpuArchitecture::Amd64;
+    let sample_class = false;
+
+    // Driver project data
+    let driver_type = "KMDF";
+    let driver_name = "sample-kmdf";
+    let driver_version = "0.0.1";
+    let wdk_metadata = get_cargo_metadata_wdk_metadata(driver_type, 1, 33);
+    let (workspace_member, package) =
+        get_cargo_metadata_package(&cwd, driver_name, driver_version, Some(&wdk_metadata));
+
+    let cargo_build_output =
+        create_cargo_build_output_json(driver_name, driver_version, &cwd, None, None);
+
+    // No certificate, signtool or infverif expectations are set, so those
+    // steps must not run
+    let test_build_action = &TestBuildAction::new(cwd.clone(), None, None, sample_class)
+        .set_up_standalone_driver_project((workspace_member, package))
+        .expect_default_build_task_steps(driver_name, Some(cargo_build_output))
+        .expect_probe_target_arch_using_cargo_rustc(&cwd, target_arch, None)
+        .expect_package_stamp_missing(driver_name, &cwd)
+        .expect_final_package_dir_exists(driver_name, &cwd, true)
+        .expect_inx_file_exists(driver_name, &cwd, true)
+        .expect_rename_driver_binary_dll_to_sys(driver_name, &cwd)
+        .expect_copy_driver_binary_sys_to_package_folder(driver_name, &cwd, true)
+        .expect_copy_pdb_file_to_package_folder(driver_name, &cwd, true)
+        .expect_write_inf_file_to_package_folder(driver_name, &cwd, true, &cwd)
+        .expect_copy_map_file_to_package_folder(driver_name, &cwd, true)
+        .expect_stampinf(driver_name, &cwd, target_arch, None)
+        .expect_inf2cat(driver_name, &cwd, target_arch, None)
+        .expect_package_stamp_written(driver_name, &cwd);
+
+    let run_result = run_build_action_with_package_options(
+        &cwd,
+        &[],
+        false,
+        PackageStages {
+            sign_mode: SignMode::None,
+            verify: false,
+        },
+        None,
+        test_build_action,
+    );
+    assert!(
+        run_result.is_ok(),
+        "build without signing and verification failed unexpectedly: {run_result:?}"
+    );
+}
+
+#[test]
+pub fn given_a_driver_project_when_production_signing_without_a_cert_then_it_fails_without_generating_one()
+ {
+    // Input CLI args
+    let cwd = PathBuf::from("C:\\tmp");
+    let target_arch = CpuArchitecture::Amd64;
+    let sample_class = false;
+
+    // Driver project data
+    let driver_type = "KMDF";
+    let driver_name = "sample-kmdf";
+    let driver_version = "0.0.1";
+    let wdk_metadata = get_cargo_metadata_wdk_metadata(driver_type, 1, 33);
+    let (workspace_member, package) =
+        get_cargo_metadata_package(&cwd, driver_name, driver_version, Some(&wdk_metadata));
+
+    let cargo_build_output =
+        create_cargo_build_output_json(driver_name, driver_version, &cwd, None, None);
+
+    // No certmgr or makecert expectations are set, so the test certificate
+    // must not be looked up or generated
+    let test_build_action = &TestBuildAction::new(cwd.clone(), None, None, sample_class)
+        .set_up_standalone_driver_project((workspace_member, package))
+        .expect_default_build_task_steps(driver_name, Some(cargo_build_output))
+        .expect_probe_target_arch_using_cargo_rustc(&cwd, target_arch, None)
+        .expect_package_stamp_missing(driver_name, &cwd)
+        .expect_final_package_dir_exists(driver_name, &cwd, true)
+        .expect_inx_file_exists(driver_name, &cwd, true)
+        .expect_rename_driver_binary_dll_to_sys(driver_name, &cwd)
+        .expect_copy_driver_binary_sys_to_package_folder(driver_name, &cwd, true)
+        .expect_copy_pdb_file_to_package_folder(driver_name, &cwd, true)
+        .expect_write_inf_file_to_package_folder(driver_name, &cwd, true, &cwd)
+        .expect_copy_map_file_to_package_folder(driver_name, &cwd, true)
+        .expect_stampinf(driver_name, &cwd, target_arch, None)
+        .expect_inf2cat(driver_name, &cwd, target_arch, None);
+
+    let run_result = run_build_action_with_package_options(
+        &cwd,
+        &[],
+        false,
+        PackageStages {
+            sign_mode: SignMode::Production,
+            verify: true,
+        },
+        None,
+        test_build_action,
+    );
+    assert!(matches!(
+        run_result,
+        Err(BuildActionError::PackageTask(
+            PackageTaskError::MissingProductionCert
+        ))
+    ));
+}
+
+#[test]
+pub fn given_a_driver_project_when_verification_report_is_set_then_it_writes_the_verification_results()
+ {
+    // Input CLI args
+    let cwd = PathBuf::from("C:\\tmp");
+    let target_arch = CpuArchitecture::Amd64;
+    let verify_signature = true;
+    let sample_class = false;
+    let verification_report_path = cwd.join("verification-report.json");
+
+    // Driver project data
+    let driver_type = "KMDF";
+    let driver_name = "sample-kmdf";
+    let driver_version = "0.0.1";
+    let wdk_metadata = get_cargo_metadata_wdk_metadata(driver_type, 1, 33);
+    let (workspace_member, package) =
+        get_cargo_metadata_package(&cwd, driver_name, driver_version, Some(&wdk_metadata));
+
+    let cargo_build_output =
+        create_cargo_build_output_json(driver_name, driver_version, &cwd, None, None);
+
+    let test_build_action = &TestBuildAction::new(cwd.clone(), None, None, sample_class)
+        .set_up_standalone_driver_project((workspace_member, package))
+        .expect_default_build_task_steps(driver_name, Some(cargo_build_output))
+        .expect_probe_target_arch_using_cargo_rustc(&cwd, target_arch, None)
+        // The package stamp is not checked, so that the package is recreated
+        // and its verification results are recorded
+        .expect_final_package_dir_exists(driver_name, &cwd, true)
+        .expect_inx_file_exists(driver_name, &cwd, true)
+        .expect_rename_driver_binary_dll_to_sys(driver_name, &cwd)
+        .expect_copy_driver_binary_sys_to_package_folder(driver_name, &cwd, true)
+        .expect_copy_pdb_file_to_package_folder(driver_name, &cwd, true)
+        .expect_write_inf_file_to_package_folder(driver_name, &cwd, true, &cwd)
+        .expect_copy_map_file_to_package_folder(driver_name, &cwd, true)
+        .expect_stampinf(driver_name, &cwd, target_arch, None)
+        .expect_inf2cat(driver_name, &cwd, target_arch, None)
+        .expect_self_signed_cert_file_exists(&cwd, false)
+        .expect_certmgr_exists_check(Some(get_certmgr_success_output()))
+        .expect_makecert(&cwd, None)
+        .expect_copy_self_signed_cert_file_to_package_folder(driver_name, &cwd, true)
+        .expect_signtool_sign_driver_binary_sys_file(driver_name, &cwd, None)
+        .expect_signtool_sign_cat_file(driver_name, &cwd, None)
+        .expect_infverif(driver_name, &cwd, driver_type, None)
+        .expect_signtool_verify_driver_binary_sys_file(driver_name, &cwd, None)
+        .expect_signtool_verify_cat_file(driver_name, &cwd, None)
+        .expect_package_stamp_written(driver_name, &cwd)
+        .expect_verification_report_written(&verification_report_path, |report| {
+            report["passed"] == true
+                && report["results"]
+                    .as_array()
+                    .is_some_and(|results| results.len() == 3)
+                && report["results"][0]["tool"] == "infverif"
+                && report["results"][1]["tool"] == "signtool verify"
+                && report["results"][2]["tool"] == "signtool verify"
+        });
+
+    let run_result = run_build_action_with_verification_report(
+        &cwd,
+        verify_signature,
+        &verification_report_path,
+        test_build_action,
+    );
+    assert!(
+        run_result.is_ok(),
+        "build with verification report failed unexpectedly: {run_result:?}"
+    );
+}
+
+#[test]
+pub fn given_a_driver_project_when_verification_report_is_set_and_infverif_fails_then_it_writes_the_failure()
+ {
+    // Input CLI args
+    let cwd = PathBuf::from("C:\\tmp");
+    let target_arch = CpuArchitecture::Amd64;
+    let verify_signature = false;
+    let sample_class = false;
+    let verification_report_path = cwd.join("verification-report.json");
+
+    // Driver project data
+    let driver_type = "KMDF";
+    let driver_name = "sample-kmdf";
+    let driver_version = "0.0.1";
+    let wdk_metadata = get_cargo_metadata_wdk_metadata(driver_type, 1, 33);
+    let (workspace_member, package) =
+        get_cargo_metadata_package(&cwd, driver_name, driver_version, Some(&wdk_metadata));
+
+    let cargo_build_output =
+        create_cargo_build_output_json(driver_name, driver_version, &cwd, None, None);
+    let infverif_output = Output {
+        status: ExitStatus::from_raw(1),
+        stdout: b"ERROR(1284) in sample_kmdf.inf, line 5: Class \"Sample\" is unrecognized."
+            .to_vec(),
+        stderr: vec![],
+    };
+
+    let test_build_action = &TestBuildAction::new(cwd.clone(), None, None, sample_class)
+        .set_up_standalone_driver_project((workspace_member, package))
+        .expect_default_build_task_steps(driver_name, Some(cargo_build_output))
+        .expect_probe_target_arch_using_cargo_rustc(&cwd, target_arch, None)
+        .expect_final_package_dir_exists(driver_name, &cwd, true)
+        .expect_inx_file_exists(driver_name, &cwd, true)
+        .expect_rename_driver_binary_dll_to_sys(driver_name, &cwd)
+        .expect_copy_driver_binary_sys_to_package_folder(driver_name, &cwd, true)
+        .expect_copy_pdb_file_to_package_folder(driver_name, &cwd, true)
+        .expect_write_inf_file_to_package_folder(driver_name, &cwd, true, &cwd)
+        .expect_copy_map_file_to_package_folder(driver_name, &cwd, true)
+        .expect_stampinf(driver_name, &cwd, target_arch, None)
+        .expect_inf2cat(driver_name, &cwd, target_arch, None)
+        .expect_self_signed_cert_file_exists(&cwd, false)
+        .expect_certmgr_exists_check(Some(get_certmgr_success_output()))
+        .expect_makecert(&cwd, None)
+        .expect_copy_self_signed_cert_file_to_package_folder(driver_name, &cwd, true)
+        .expect_signtool_sign_driver_binary_sys_file(driver_name, &cwd, None)
+        .expect_signtool_sign_cat_file(driver_name, &cwd, None)
+        .expect_infverif(driver_name, &cwd, driver_type, Some(infverif_output))
+        .expect_verification_report_written(&verification_report_path, |report| {
+            report["passed"] == false
+                && report["results"][0]["tool"] == "infverif"
+                && report["results"][0]["errors"][0]
+                    .as_str()
+                    .is_some_and(|error| error.starts_with("ERROR(1284)"))
+        });
+
+    let run_result = run_build_action_with_verification_report(
+        &cwd,
+        verify_signature,
+        &verification_report_path,
+        test_build_action,
+    );
+    assert!(matches!(
+        run_result,
+        Err(BuildActionError::PackageTask(
+            PackageTaskError::InfVerificationCommand(_)
+        ))
+    ));
+}
+
+#[test]
+pub fn given_a_driver_project_when_sarif_is_set_and_infverif_fails_then_it_writes_the_diagnostics_for_the_inx_file()
+ {
+    // Input CLI args
+    let cwd = PathBuf::from("C:\\tmp");
+    let target_arch = CpuArchitecture::Amd64;
+    let sample_class = false;
+    let sarif_path = cwd.join("infverif.sarif");
+
+    // Driver project data
+    let driver_type = "KMDF";
+    let driver_name = "sample-kmdf";
+    let driver_version = "0.0.1";
+    let wdk_metadata = get_cargo_metadata_wdk_metadata(driver_type, 1, 33);
+    let (workspace_member, package) =
+        get_cargo_metadata_package(&cwd, driver_name, driver_version, Some(&wdk_metadata));
+
+    let cargo_build_output =
+        create_cargo_build_output_json(driver_name, driver_version, &cwd, None, None);
+    let infverif_output = Output {
+        status: ExitStatus::from_raw(1),
+        stdout: b"ERROR(1284) in sample_kmdf.inf, line 5: Class \"Sample\" is unrecognized."
+            .to_vec(),
+        stderr: vec![],
+    };
+
+    let test_build_action = &TestBuildAction::new(cwd.clone(), None, None, sample_class)
+        .set_up_standalone_driver_project((workspace_member, package))
+        .expect_default_build_task_steps(driver_name, Some(cargo_build_output))
+        .expect_probe_target_arch_using_cargo_rustc(&cwd, target_arch, None)
+        .expect_final_package_dir_exists(driver_name, &cwd, true)
+        .expect_inx_file_exists(driver_name, &cwd, true)
+        .expect_rename_driver_binary_dll_to_sys(driver_name, &cwd)
+        .expect_copy_driver_binary_sys_to_package_folder(driver_name, &cwd, true)
+        .expect_copy_pdb_file_to_package_folder(driver_name, &cwd, true)
+        .expect_write_inf_file_to_package_folder(driver_name, &cwd, true, &cwd)
+        .expect_copy_map_file_to_package_folder(driver_name, &cwd, true)
+        .expect_stampinf(driver_name, &cwd, target_arch, None)
+        .expect_inf2cat(driver_name, &cwd, target_arch, None)
+        .expect_self_signed_cert_file_exists(&cwd, false)
+        .expect_certmgr_exists_check(Some(get_certmgr_success_output()))
+        .expect_makecert(&cwd, None)
+        .expect_copy_self_signed_cert_file_to_package_folder(driver_name, &cwd, true)
+        .expect_signtool_sign_driver_binary_sys_file(driver_name, &cwd, None)
+        .expect_signtool_sign_cat_file(driver_name, &cwd, None)
+        .expect_infverif(driver_name, &cwd, driver_type, Some(infverif_output))
+        .expect_verification_report_written(&sarif_path, |sarif| {
+            let result = &sarif["runs"][0]["results"][0];
+            let location = &result["locations"][0]["physicalLocation"];
+            sarif["version"] == "2.1.0"
+                && sarif["runs"][0]["tool"]["driver"]["name"] == "infverif"
+                && result["ruleId"] == "1284"
+                && result["level"] == "error"
+                && location["artifactLocation"]["uri"]
+                    .as_str()
+                    .is_some_and(|uri| uri.ends_with("/sample_kmdf.inx"))
+                && location["region"]["startLine"] == 5
+        });
+
+    let build_action = BuildAction::new(
+        &BuildActionParams {
+            working_dir: &cwd,
+            profile: None,
+            target_archs: vec![],
+            verify_signature: false,
+            is_sample_class: sample_class,
+            hlk_artifacts: None,
+            cab: false,
+            package_only: false,
+            force_repackage: false,
+            treat_inf_warnings_as_errors: false,
+            package_stages: PackageStages::default(),
+            cert_store: None,
+            cert_name: None,
+            cert_path: None,
+            cert_thumbprint: None,
+            csp: None,
+            key_container: None,
+            digest_algorithm: None,
+            wdk_source: WdkSource::Installed,
+            verification_report_path: None,
+            sarif_path: Some(&sarif_path),
+            target_dir: None,
+            package_dir: None,
+            verbosity_level: clap_verbosity_flag::Verbosity::new(1, 0),
+        },
+        test_build_action.mock_wdk_build_provider(),
+        test_build_action.mock_run_command(),
+        test_build_action.mock_fs_provider(),
+        test_build_action.mock_metadata_provider(),
+    );
+    let run_result = run_build_action(build_action);
+    assert!(matches!(
+        run_result,
+        Err(BuildActionError::PackageTask(
+            PackageTaskError::InfVerificationCommand(_)
+        ))
+    ));
+}
+
+#[test]
+pub fn given_a_driver_project_when_a_cert_file_is_provided_then_it_is_signed_with_the_cert_file() {
+    // Input CLI args
+    let cwd = PathBuf::from("C:\\tmp");
+    let target_arch = CpuArchitecture::Amd64;
+    let sample_class = false;
+    let cert_path = PathBuf::from("C:\\certs\\contoso.pfx");
+
+    // Driver project data
+    let driver_type = "KMDF";
+    let driver_name = "sample-kmdf";
+    let driver_version = "0.0.1";
+    let wdk_metadata = get_cargo_metadata_wdk_metadata(driver_type, 1, 33);
+    let (workspace_member, package) =
+        get_cargo_metadata_package(&cwd, driver_name, driver_version, Some(&wdk_metadata));
+
+    // No certificate generation expectations are set, so the self signed
+    // certificate must not be created
+    let test_build_action =
+        &TestBuildAction::new(cwd.clone(), None, Some(target_arch), sample_class)
+            .set_up_standalone_driver_project((workspace_member, package))
+            .expect_detect_wdk_build_number(25100u32)
+            .expect_root_manifest_exists(&cwd, true)
+            .expect_package_stamp_missing(driver_name, &cwd)
+            .expect_final_package_dir_exists(driver_name, &cwd, true)
+            .expect_inx_file_exists(driver_name, &cwd, true)
+            .expect_rename_driver_binary_dll_to_sys(driver_name, &cwd)
+            .expect_copy_driver_binary_sys_to_package_folder(driver_name, &cwd, true)
+            .expect_copy_pdb_file_to_package_folder(driver_name, &cwd, true)
+            .expect_write_inf_file_to_package_folder(driver_name, &cwd, true, &cwd)
+            .expect_copy_map_file_to_package_folder(driver_name, &cwd, true)
+            .expect_stampinf(driver_name, &cwd, target_arch, None)
+            .expect_inf2cat(driver_name, &cwd, target_arch, None)
+            .expect_cert_file_exists(&cert_path)
+            .expect_signtool_sign_with_cert_file(driver_name, &cwd, "sys", &cert_path)
+            .expect_signtool_sign_with_cert_file(driver_name, &cwd, "cat", &cert_path)
+            .expect_infverif(driver_name, &cwd, driver_type, None)
+            .expect_package_stamp_written(driver_name, &cwd);
+
+    let run_result = run_build_action_with_package_options(
+        &cwd,
+        &[target_arch],
+        true,
+        PackageStages::default(),
+        Some(&cert_path),
+        test_build_action,
+    );
+    assert!(
+        run_result.is_ok(),
+        "build with a cert file failed unexpectedly: {run_result:?}"
+    );
+}
+
 #[test]
 pub fn given_a_driver_project_when_profile_is_release_then_it_builds_successfully() {
     // Input CLI args
@@ -152,6 +596,54 @@ pub fn given_a_driver_project_when_target_arch_is_arm64_then_it_builds_successfu
     );
 }
 
+#[test]
+pub fn given_a_driver_project_when_multiple_target_archs_are_provided_then_it_builds_for_each_target_arch()
+ {
+    // Input CLI args
+    let cwd = PathBuf::from("C:\\tmp");
+    let target_archs = [CpuArchitecture::Amd64, CpuArchitecture::Arm64];
+    let sample_class = false;
+
+    // Driver project data
+    let driver_type = "KMDF";
+    let driver_name = "sample-kmdf";
+    let driver_version = "0.0.1";
+    let wdk_metadata = get_cargo_metadata_wdk_metadata(driver_type, 1, 33);
+    let (workspace_member, package) =
+        get_cargo_metadata_package(&cwd, driver_name, driver_version, Some(&wdk_metadata));
+
+    let mut test_build_action = TestBuildAction::new(cwd.clone(), None, None, sample_class)
+        .expect_detect_wdk_build_number(25100u32);
+    for target_arch in target_archs {
+        let cargo_build_output = create_cargo_build_output_json(
+            driver_name,
+            driver_version,
+            &cwd,
+            Some(&to_target_triple(target_arch)),
+            None,
+        );
+        test_build_action.target_arch = Some(target_arch);
+        test_build_action = test_build_action
+            .set_up_standalone_driver_project((workspace_member.clone(), package.clone()))
+            .expect_root_manifest_exists(&cwd, true)
+            .expect_cargo_build(driver_name, &cwd, Some(cargo_build_output))
+            .expect_default_package_task_steps(driver_name, driver_type, target_arch, false);
+    }
+
+    let run_result = run_build_action_with_package_options(
+        &cwd,
+        &target_archs,
+        false,
+        PackageStages::default(),
+        None,
+        &test_build_action,
+    );
+    assert!(
+        run_result.is_ok(),
+        "multi target arch build failed unexpectedly: {run_result:?}"
+    );
+}
+
 #[test]
 pub fn given_a_driver_project_when_profile_is_release_and_target_arch_is_arm64_then_it_builds_successfully()
  {
@@ -319,12 +811,13 @@ pub fn given_a_driver_project_when_self_signed_exists_then_it_should_skip_callin
         .set_up_standalone_driver_project((workspace_member, package))
         .expect_default_build_task_steps(driver_name, Some(cargo_build_output))
         .expect_probe_target_arch_using_cargo_rustc(&cwd, target_arch, None)
+        .expect_package_stamp_missing(driver_name, &cwd)
         .expect_final_package_dir_exists(driver_name, &cwd, true)
         .expect_inx_file_exists(driver_name, &cwd, true)
         .expect_rename_driver_binary_dll_to_sys(driver_name, &cwd)
         .expect_copy_driver_binary_sys_to_package_folder(driver_name, &cwd, true)
         .expect_copy_pdb_file_to_package_folder(driver_name, &cwd, true)
-        .expect_copy_inx_file_to_package_folder(driver_name, &cwd, true, &cwd)
+        .expect_write_inf_file_to_package_folder(driver_name, &cwd, true, &cwd)
         .expect_copy_map_file_to_package_folder(driver_name, &cwd, true)
         .expect_stampinf(driver_name, &cwd, target_arch, None)
         .expect_inf2cat(driver_name, &cwd, target_arch, None)
@@ -336,7 +829,8 @@ pub fn given_a_driver_project_when_self_signed_exists_then_it_should_skip_callin
         .expect_signtool_sign_cat_file(driver_name, &cwd, None)
         .expect_infverif(driver_name, &cwd, "KMDF", None)
         .expect_signtool_verify_driver_binary_sys_file(driver_name, &cwd, None)
-        .expect_signtool_verify_cat_file(driver_name, &cwd, None);
+        .expect_signtool_verify_cat_file(driver_name, &cwd, None)
+        .expect_package_stamp_written(driver_name, &cwd);
 
     assert_build_action_run_with_env_is_success(
         &cwd,
@@ -373,13 +867,14 @@ pub fn given_a_driver_project_when_final_package_dir_exists_then_it_should_skip_
         .set_up_standalone_driver_project((workspace_member, package))
         .expect_default_build_task_steps(driver_name, Some(cargo_build_output))
         .expect_probe_target_arch_using_cargo_rustc(&cwd, target_arch, None)
+        .expect_package_stamp_missing(driver_name, &cwd)
         .expect_final_package_dir_exists(driver_name, &cwd, false)
         .expect_dir_created(driver_name, &cwd, true)
         .expect_inx_file_exists(driver_name, &cwd, true)
         .expect_rename_driver_binary_dll_to_sys(driver_name, &cwd)
         .expect_copy_driver_binary_sys_to_package_folder(driver_name, &cwd, true)
         .expect_copy_pdb_file_to_package_folder(driver_name, &cwd, true)
-        .expect_copy_inx_file_to_package_folder(driver_name, &cwd, true, &cwd)
+        .expect_write_inf_file_to_package_folder(driver_name, &cwd, true, &cwd)
         .expect_copy_map_file_to_package_folder(driver_name, &cwd, true)
         .expect_stampinf(driver_name, &cwd, target_arch, None)
         .expect_inf2cat(driver_name, &cwd, target_arch, None)
@@ -389,7 +884,8 @@ pub fn given_a_driver_project_when_final_package_dir_exists_then_it_should_skip_
         .expect_copy_self_signed_cert_file_to_package_folder(driver_name, &cwd, true)
         .expect_signtool_sign_driver_binary_sys_file(driver_name, &cwd, None)
         .expect_signtool_sign_cat_file(driver_name, &cwd, None)
-        .expect_infverif(driver_name, &cwd, "KMDF", None);
+        .expect_infverif(driver_name, &cwd, "KMDF", None)
+        .expect_package_stamp_written(driver_name, &cwd);
 
     assert_build_action_run_with_env_is_success(
         &cwd,
@@ -425,6 +921,7 @@ pub fn given_a_driver_project_when_inx_file_do_not_exist_then_package_should_fai
         .set_up_standalone_driver_project((workspace_member, package))
         .expect_default_build_task_steps(driver_name, Some(cargo_build_output))
         .expect_probe_target_arch_using_cargo_rustc(&cwd, target_arch, None)
+        .expect_package_stamp_missing(driver_name, &cwd)
         .expect_inx_file_exists(driver_name, &cwd, false);
 
     let build_action = initialize_build_action(
@@ -440,7 +937,7 @@ pub fn given_a_driver_project_when_inx_file_do_not_exist_then_package_should_fai
 
     assert!(matches!(
         run_result.as_ref().expect_err("expected error"),
-        BuildActionError::OneOrMoreWorkspaceMembersFailedToBuild(_)
+        BuildActionError::OneOrMoreWorkspaceMembersFailedToBuild(_, FailureCategory::Packaging)
     ));
 }
 
@@ -473,6 +970,7 @@ pub fn given_a_driver_project_when_copy_of_an_artifact_fails_then_the_package_sh
         &TestBuildAction::new(cwd.clone(), profile, Some(target_arch), sample_class)
             .set_up_standalone_driver_project((workspace_member, package))
             .expect_default_build_task_steps(driver_name, Some(cargo_build_output))
+            .expect_package_stamp_missing(driver_name, &cwd)
             .expect_final_package_dir_exists(driver_name, &cwd, true)
             .expect_inx_file_exists(driver_name, &cwd, true)
             .expect_rename_driver_binary_dll_to_sys(driver_name, &cwd)
@@ -491,7 +989,7 @@ pub fn given_a_driver_project_when_copy_of_an_artifact_fails_then_the_package_sh
 
     assert!(matches!(
         run_result.as_ref().expect_err("expected error"),
-        BuildActionError::OneOrMoreWorkspaceMembersFailedToBuild(_)
+        BuildActionError::OneOrMoreWorkspaceMembersFailedToBuild(_, FailureCategory::Packaging)
     ));
 }
 
@@ -525,12 +1023,13 @@ pub fn given_a_driver_project_when_stampinf_command_execution_fails_then_package
         .set_up_standalone_driver_project((workspace_member, package))
         .expect_default_build_task_steps(driver_name, Some(cargo_build_output))
         .expect_probe_target_arch_using_cargo_rustc(&cwd, target_arch, None)
+        .expect_package_stamp_missing(driver_name, &cwd)
         .expect_final_package_dir_exists(driver_name, &cwd, true)
         .expect_inx_file_exists(driver_name, &cwd, true)
         .expect_rename_driver_binary_dll_to_sys(driver_name, &cwd)
         .expect_copy_driver_binary_sys_to_package_folder(driver_name, &cwd, true)
         .expect_copy_pdb_file_to_package_folder(driver_name, &cwd, true)
-        .expect_copy_inx_file_to_package_folder(driver_name, &cwd, true, &cwd)
+        .expect_write_inf_file_to_package_folder(driver_name, &cwd, true, &cwd)
         .expect_copy_map_file_to_package_folder(driver_name, &cwd, true)
         .expect_stampinf(
             driver_name,
@@ -552,7 +1051,7 @@ pub fn given_a_driver_project_when_stampinf_command_execution_fails_then_package
 
     assert!(matches!(
         run_result.as_ref().expect_err("expected error"),
-        BuildActionError::OneOrMoreWorkspaceMembersFailedToBuild(_)
+        BuildActionError::OneOrMoreWorkspaceMembersFailedToBuild(_, FailureCategory::Packaging)
     ));
 }
 
@@ -586,12 +1085,13 @@ pub fn given_a_driver_project_when_inf2cat_command_execution_fails_then_package_
         .set_up_standalone_driver_project((workspace_member, package))
         .expect_default_build_task_steps(driver_name, Some(cargo_build_output))
         .expect_probe_target_arch_using_cargo_rustc(&cwd, target_arch, None)
+        .expect_package_stamp_missing(driver_name, &cwd)
         .expect_final_package_dir_exists(driver_name, &cwd, true)
         .expect_inx_file_exists(driver_name, &cwd, true)
         .expect_rename_driver_binary_dll_to_sys(driver_name, &cwd)
         .expect_copy_driver_binary_sys_to_package_folder(driver_name, &cwd, true)
         .expect_copy_pdb_file_to_package_folder(driver_name, &cwd, true)
-        .expect_copy_inx_file_to_package_folder(driver_name, &cwd, true, &cwd)
+        .expect_write_inf_file_to_package_folder(driver_name, &cwd, true, &cwd)
         .expect_copy_map_file_to_package_folder(driver_name, &cwd, true)
         .expect_stampinf(driver_name, &cwd, target_arch, None)
         .expect_inf2cat(
@@ -614,7 +1114,7 @@ pub fn given_a_driver_project_when_inf2cat_command_execution_fails_then_package_
 
     assert!(matches!(
         run_result.as_ref().expect_err("expected error"),
-        BuildActionError::OneOrMoreWorkspaceMembersFailedToBuild(_)
+        BuildActionError::OneOrMoreWorkspaceMembersFailedToBuild(_, FailureCategory::Packaging)
     ));
 }
 
@@ -648,12 +1148,13 @@ pub fn given_a_driver_project_when_certmgr_command_execution_fails_then_package_
         .set_up_standalone_driver_project((workspace_member, package))
         .expect_default_build_task_steps(driver_name, Some(cargo_build_output))
         .expect_probe_target_arch_using_cargo_rustc(&cwd, target_arch, None)
+        .expect_package_stamp_missing(driver_name, &cwd)
         .expect_final_package_dir_exists(driver_name, &cwd, true)
         .expect_inx_file_exists(driver_name, &cwd, true)
         .expect_rename_driver_binary_dll_to_sys(driver_name, &cwd)
         .expect_copy_driver_binary_sys_to_package_folder(driver_name, &cwd, true)
         .expect_copy_pdb_file_to_package_folder(driver_name, &cwd, true)
-        .expect_copy_inx_file_to_package_folder(driver_name, &cwd, true, &cwd)
+        .expect_write_inf_file_to_package_folder(driver_name, &cwd, true, &cwd)
         .expect_copy_map_file_to_package_folder(driver_name, &cwd, true)
         .expect_stampinf(driver_name, &cwd, target_arch, None)
         .expect_inf2cat(driver_name, &cwd, target_arch, None)
@@ -673,7 +1174,7 @@ pub fn given_a_driver_project_when_certmgr_command_execution_fails_then_package_
 
     assert!(matches!(
         run_result.as_ref().expect_err("expected error"),
-        BuildActionError::OneOrMoreWorkspaceMembersFailedToBuild(_)
+        BuildActionError::OneOrMoreWorkspaceMembersFailedToBuild(_, FailureCategory::Signing)
     ));
 }
 
@@ -707,12 +1208,13 @@ pub fn given_a_driver_project_when_makecert_command_execution_fails_then_package
         .set_up_standalone_driver_project((workspace_member, package))
         .expect_default_build_task_steps(driver_name, Some(cargo_build_output))
         .expect_probe_target_arch_using_cargo_rustc(&cwd, target_arch, None)
+        .expect_package_stamp_missing(driver_name, &cwd)
         .expect_final_package_dir_exists(driver_name, &cwd, true)
         .expect_inx_file_exists(driver_name, &cwd, true)
         .expect_rename_driver_binary_dll_to_sys(driver_name, &cwd)
         .expect_copy_driver_binary_sys_to_package_folder(driver_name, &cwd, true)
         .expect_copy_pdb_file_to_package_folder(driver_name, &cwd, true)
-        .expect_copy_inx_file_to_package_folder(driver_name, &cwd, true, &cwd)
+        .expect_write_inf_file_to_package_folder(driver_name, &cwd, true, &cwd)
         .expect_copy_map_file_to_package_folder(driver_name, &cwd, true)
         .expect_stampinf(driver_name, &cwd, target_arch, None)
         .expect_inf2cat(driver_name, &cwd, target_arch, None)
@@ -733,7 +1235,7 @@ pub fn given_a_driver_project_when_makecert_command_execution_fails_then_package
 
     assert!(matches!(
         run_result.as_ref().expect_err("expected error"),
-        BuildActionError::OneOrMoreWorkspaceMembersFailedToBuild(_)
+        BuildActionError::OneOrMoreWorkspaceMembersFailedToBuild(_, FailureCategory::Signing)
     ));
 }
 
@@ -767,12 +1269,13 @@ pub fn given_a_driver_project_when_signtool_command_execution_fails_then_package
         .set_up_standalone_driver_project((workspace_member, package))
         .expect_default_build_task_steps(driver_name, Some(cargo_build_output))
         .expect_probe_target_arch_using_cargo_rustc(&cwd, target_arch, None)
+        .expect_package_stamp_missing(driver_name, &cwd)
         .expect_final_package_dir_exists(driver_name, &cwd, true)
         .expect_inx_file_exists(driver_name, &cwd, true)
         .expect_rename_driver_binary_dll_to_sys(driver_name, &cwd)
         .expect_copy_driver_binary_sys_to_package_folder(driver_name, &cwd, true)
         .expect_copy_pdb_file_to_package_folder(driver_name, &cwd, true)
-        .expect_copy_inx_file_to_package_folder(driver_name, &cwd, true, &cwd)
+        .expect_write_inf_file_to_package_folder(driver_name, &cwd, true, &cwd)
         .expect_copy_map_file_to_package_folder(driver_name, &cwd, true)
         .expect_stampinf(driver_name, &cwd, target_arch, None)
         .expect_inf2cat(driver_name, &cwd, target_arch, None)
@@ -795,7 +1298,7 @@ pub fn given_a_driver_project_when_signtool_command_execution_fails_then_package
 
     assert!(matches!(
         run_result.as_ref().expect_err("expected error"),
-        BuildActionError::OneOrMoreWorkspaceMembersFailedToBuild(_)
+        BuildActionError::OneOrMoreWorkspaceMembersFailedToBuild(_, FailureCategory::Signing)
     ));
 }
 
@@ -829,12 +1332,13 @@ pub fn given_a_driver_project_when_infverif_command_execution_fails_then_package
         .set_up_standalone_driver_project((workspace_member, package))
         .expect_default_build_task_steps(driver_name, Some(cargo_build_output))
         .expect_probe_target_arch_using_cargo_rustc(&cwd, target_arch, None)
+        .expect_package_stamp_missing(driver_name, &cwd)
         .expect_final_package_dir_exists(driver_name, &cwd, true)
         .expect_inx_file_exists(driver_name, &cwd, true)
         .expect_rename_driver_binary_dll_to_sys(driver_name, &cwd)
         .expect_copy_driver_binary_sys_to_package_folder(driver_name, &cwd, true)
         .expect_copy_pdb_file_to_package_folder(driver_name, &cwd, true)
-        .expect_copy_inx_file_to_package_folder(driver_name, &cwd, true, &cwd)
+        .expect_write_inf_file_to_package_folder(driver_name, &cwd, true, &cwd)
         .expect_copy_map_file_to_package_folder(driver_name, &cwd, true)
         .expect_stampinf(driver_name, &cwd, target_arch, None)
         .expect_inf2cat(driver_name, &cwd, target_arch, None)
@@ -859,7 +1363,7 @@ pub fn given_a_driver_project_when_infverif_command_execution_fails_then_package
 
     assert!(matches!(
         run_result.as_ref().expect_err("expected error"),
-        BuildActionError::OneOrMoreWorkspaceMembersFailedToBuild(_)
+        BuildActionError::OneOrMoreWorkspaceMembersFailedToBuild(_, FailureCategory::Packaging)
     ));
 }
 
@@ -974,7 +1478,7 @@ pub fn given_a_driver_project_when_target_arch_is_not_provided_and_probing_cargo
     let run_result = build_action.expect("Failed to init build action").run();
     assert!(matches!(
         run_result.as_ref().expect_err("expected error"),
-        BuildActionError::OneOrMoreWorkspaceMembersFailedToBuild(_)
+        BuildActionError::OneOrMoreWorkspaceMembersFailedToBuild(_, FailureCategory::Build)
     ));
 }
 
@@ -1152,12 +1656,13 @@ pub fn given_a_workspace_with_multiple_driver_and_non_driver_projects_when_cwd_i
         .expect_root_manifest_exists(&cwd, true)
         .expect_cargo_build(driver_name_1, &cwd, Some(cargo_build_output))
         .expect_probe_target_arch_using_cargo_rustc(&cwd, target_arch, None)
+        .expect_package_stamp_missing(driver_name_1, &workspace_root_dir)
         .expect_final_package_dir_exists(driver_name_1, &workspace_root_dir, true)
         .expect_inx_file_exists(driver_name_1, &cwd, true)
         .expect_rename_driver_binary_dll_to_sys(driver_name_1, &workspace_root_dir)
         .expect_copy_driver_binary_sys_to_package_folder(driver_name_1, &workspace_root_dir, true)
         .expect_copy_pdb_file_to_package_folder(driver_name_1, &workspace_root_dir, true)
-        .expect_copy_inx_file_to_package_folder(driver_name_1, &cwd, true, &workspace_root_dir)
+        .expect_write_inf_file_to_package_folder(driver_name_1, &cwd, true, &workspace_root_dir)
         .expect_copy_map_file_to_package_folder(driver_name_1, &workspace_root_dir, true)
         .expect_stampinf(driver_name_1, &workspace_root_dir, target_arch, None)
         .expect_inf2cat(driver_name_1, &workspace_root_dir, target_arch, None)
@@ -1173,7 +1678,8 @@ pub fn given_a_workspace_with_multiple_driver_and_non_driver_projects_when_cwd_i
         .expect_signtool_sign_cat_file(driver_name_1, &workspace_root_dir, None)
         .expect_signtool_verify_driver_binary_sys_file(driver_name_1, &workspace_root_dir, None)
         .expect_signtool_verify_cat_file(driver_name_1, &workspace_root_dir, None)
-        .expect_infverif(driver_name_1, &workspace_root_dir, "KMDF", None);
+        .expect_infverif(driver_name_1, &workspace_root_dir, "KMDF", None)
+        .expect_package_stamp_written(driver_name_1, &workspace_root_dir);
 
     assert_build_action_run_with_env_is_success(
         &cwd,
@@ -1562,6 +2068,105 @@ pub fn given_a_workspace_only_with_non_driver_projects_when_cwd_is_workspace_mem
     );
 }
 
+#[test]
+pub fn given_an_export_driver_project_when_signing_and_verification_are_skipped_then_it_packages_without_an_inf()
+ {
+    // Input CLI args
+    let cwd = PathBuf::from("C:\\tmp");
+    let target_arch = CpuArchitecture::Amd64;
+    let sample_class = false;
+
+    // Driver project data
+    let driver_name = "sample-export-driver";
+    let driver_version = "0.0.1";
+    let wdk_metadata = TestWdkMetadata(format!(
+        r#"
+        {{
+            "wdk": {{
+                "driver-model": {{
+                    "driver-type": "WDM"
+                }},
+                "package": {{
+                    "export-drivers": ["{driver_name}"]
+                }}
+            }}
+        }}
+    "#
+    ));
+    let (workspace_member, package) =
+        get_cargo_metadata_package(&cwd, driver_name, driver_version, Some(&wdk_metadata));
+
+    let cargo_build_output =
+        create_cargo_build_output_json(driver_name, driver_version, &cwd, None, None);
+
+    // No inx, rename, stampinf or inf2cat expectations are set, so those steps
+    // must not run
+    let test_build_action = &TestBuildAction::new(cwd.clone(), None, None, sample_class)
+        .set_up_standalone_driver_project((workspace_member, package))
+        .expect_default_build_task_steps(driver_name, Some(cargo_build_output))
+        .expect_probe_target_arch_using_cargo_rustc(&cwd, target_arch, None)
+        .expect_package_stamp_missing(driver_name, &cwd)
+        .expect_final_package_dir_exists(driver_name, &cwd, true)
+        .expect_copy_export_driver_files_to_package_folder(driver_name, &cwd)
+        .expect_copy_pdb_file_to_package_folder(driver_name, &cwd, true)
+        .expect_copy_map_file_to_package_folder(driver_name, &cwd, true)
+        .expect_package_stamp_written(driver_name, &cwd);
+
+    let run_result = run_build_action_with_package_options(
+        &cwd,
+        &[],
+        false,
+        PackageStages {
+            sign_mode: SignMode::None,
+            verify: false,
+        },
+        None,
+        test_build_action,
+    );
+    assert!(
+        run_result.is_ok(),
+        "export driver build failed unexpectedly: {run_result:?}"
+    );
+}
+
+#[test]
+pub fn given_workspace_members_depending_on_each_other_when_they_are_sorted_then_dependencies_come_first()
+ {
+    let workspace_root_dir = PathBuf::from("C:\\tmp");
+    let driver = "sample-driver";
+    let export_driver = "sample-export-driver";
+    let library = "sample-library";
+    let (workspace_member_1, package_1) =
+        get_cargo_metadata_package(&workspace_root_dir.join(driver), driver, "0.0.1", None);
+    let (workspace_member_2, package_2) = get_cargo_metadata_package(
+        &workspace_root_dir.join(export_driver),
+        export_driver,
+        "0.0.1",
+        None,
+    );
+    let (workspace_member_3, package_3) =
+        get_cargo_metadata_package(&workspace_root_dir.join(library), library, "0.0.1", None);
+    let package_1 = with_path_dependencies(
+        &package_1,
+        &[(export_driver, "normal"), (library, "dev")],
+        &workspace_root_dir,
+    );
+    let package_2 = with_path_dependencies(&package_2, &[(library, "normal")], &workspace_root_dir);
+    let cargo_metadata = serde_json::from_str::<CargoMetadata>(&get_cargo_metadata(
+        &workspace_root_dir,
+        vec![package_1, package_2, package_3],
+        &[workspace_member_1, workspace_member_2, workspace_member_3],
+        None,
+    ))
+    .expect("Failed to parse cargo metadata");
+
+    let sorted = BuildAction::sort_by_workspace_dependencies(&cargo_metadata.workspace_packages());
+    assert_eq!(
+        sorted.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(),
+        [library, export_driver, driver]
+    );
+}
+
 fn assert_build_action_run_is_success(
     cwd: &PathBuf,
     profile: Option<Profile>,
@@ -1598,9 +2203,27 @@ fn initialize_build_action<'a>(
         &BuildActionParams {
             working_dir: cwd,
             profile,
-            target_arch,
+            target_archs: target_arch.into_iter().collect(),
             verify_signature,
             is_sample_class: sample_class,
+            hlk_artifacts: None,
+            cab: false,
+            package_only: false,
+            force_repackage: false,
+            treat_inf_warnings_as_errors: false,
+            package_stages: PackageStages::default(),
+            cert_store: None,
+            cert_name: None,
+            cert_path: None,
+            cert_thumbprint: None,
+            csp: None,
+            key_container: None,
+            digest_algorithm: None,
+            wdk_source: WdkSource::Installed,
+            verification_report_path: None,
+            sarif_path: None,
+            target_dir: None,
+            package_dir: None,
             verbosity_level: clap_verbosity_flag::Verbosity::new(1, 0),
         },
         test_build_action.mock_wdk_build_provider(),
@@ -1610,6 +2233,90 @@ fn initialize_build_action<'a>(
     )
 }
 
+fn run_build_action_with_package_options(
+    cwd: &PathBuf,
+    target_archs: &[CpuArchitecture],
+    package_only: bool,
+    package_stages: PackageStages,
+    cert_path: Option<&Path>,
+    test_build_action: &TestBuildAction,
+) -> Result<(), BuildActionError> {
+    let build_action = BuildAction::new(
+        &BuildActionParams {
+            working_dir: cwd,
+            profile: None,
+            target_archs: target_archs.to_vec(),
+            verify_signature: false,
+            is_sample_class: false,
+            hlk_artifacts: None,
+            cab: false,
+            package_only,
+            force_repackage: false,
+            treat_inf_warnings_as_errors: false,
+            package_stages,
+            cert_store: None,
+            cert_name: None,
+            cert_path,
+            cert_thumbprint: None,
+            csp: None,
+            key_container: None,
+            digest_algorithm: None,
+            wdk_source: WdkSource::Installed,
+            verification_report_path: None,
+            sarif_path: None,
+            target_dir: None,
+            package_dir: None,
+            verbosity_level: clap_verbosity_flag::Verbosity::new(1, 0),
+        },
+        test_build_action.mock_wdk_build_provider(),
+        test_build_action.mock_run_command(),
+        test_build_action.mock_fs_provider(),
+        test_build_action.mock_metadata_provider(),
+    );
+    run_build_action(build_action)
+}
+
+fn run_build_action_with_verification_report(
+    cwd: &PathBuf,
+    verify_signature: bool,
+    verification_report_path: &Path,
+    test_build_action: &TestBuildAction,
+) -> Result<(), BuildActionError> {
+    let build_action = BuildAction::new(
+        &BuildActionParams {
+            working_dir: cwd,
+            profile: None,
+            target_archs: vec![],
+            verify_signature,
+            is_sample_class: false,
+            hlk_artifacts: None,
+            cab: false,
+            package_only: false,
+            force_repackage: false,
+            treat_inf_warnings_as_errors: false,
+            package_stages: PackageStages::default(),
+            cert_store: None,
+            cert_name: None,
+            cert_path: None,
+            cert_thumbprint: None,
+            csp: None,
+            key_container: None,
+            digest_algorithm: None,
+            wdk_source: WdkSource::Installed,
+            verification_report_path: Some(verification_report_path),
+            sarif_path: None,
+            target_dir: None,
+            package_dir: None,
+            verbosity_level: clap_verbosity_flag::Verbosity::new(1, 0),
+        },
+        test_build_action.mock_wdk_build_provider(),
+        test_build_action.mock_run_command(),
+        test_build_action.mock_fs_provider(),
+        test_build_action.mock_metadata_provider(),
+    );
+    run_build_action(build_action)
+}
+
 fn get_certmgr_success_output() -> Output {
     Output {
         status: ExitStatus::default(),
@@ -1802,12 +2509,13 @@ fn expect_default_package_task_steps(
         let cwd = self.cwd.clone();
         let expected_certmgr_output = get_certmgr_success_output();
         let expectations = self
+            .expect_package_stamp_missing(driver_name, &cwd)
             .expect_final_package_dir_exists(driver_name, &cwd, true)
             .expect_inx_file_exists(driver_name, &cwd, true)
             .expect_rename_driver_binary_dll_to_sys(driver_name, &cwd)
             .expect_copy_driver_binary_sys_to_package_folder(driver_name, &cwd, true)
             .expect_copy_pdb_file_to_package_folder(driver_name, &cwd, true)
-            .expect_copy_inx_file_to_package_folder(driver_name, &cwd, true, &cwd)
+            .expect_write_inf_file_to_package_folder(driver_name, &cwd, true, &cwd)
             .expect_copy_map_file_to_package_folder(driver_name, &cwd, true)
             .expect_stampinf(driver_name, &cwd, target_arch, None)
             .expect_inf2cat(driver_name, &cwd, target_arch, None)
@@ -1817,7 +2525,8 @@ fn expect_default_package_task_steps(
             .expect_copy_self_signed_cert_file_to_package_folder(driver_name, &cwd, true)
             .expect_signtool_sign_driver_binary_sys_file(driver_name, &cwd, None)
             .expect_signtool_sign_cat_file(driver_name, &cwd, None)
-            .expect_infverif(driver_name, &cwd, driver_type, None);
+            .expect_infverif(driver_name, &cwd, driver_type, None)
+            .expect_package_stamp_written(driver_name, &cwd);
         if !verify_signature {
             return expectations;
         }
@@ -1836,12 +2545,18 @@ fn expect_default_package_task_steps_for_workspace(
         let cwd = self.cwd.clone();
         let expected_certmgr_output = get_certmgr_success_output();
         let expectations = self
+            .expect_package_stamp_missing(driver_name, &cwd)
             .expect_final_package_dir_exists(driver_name, &cwd, true)
             .expect_inx_file_exists(driver_name, &cwd.join(driver_name), true)
             .expect_rename_driver_binary_dll_to_sys(driver_name, &cwd)
             .expect_copy_driver_binary_sys_to_package_folder(driver_name, &cwd, true)
             .expect_copy_pdb_file_to_package_folder(driver_name, &cwd, true)
-            .expect_copy_inx_file_to_package_folder(driver_name, &cwd.join(driver_name), true, &cwd)
+            .expect_write_inf_file_to_package_folder(
+                driver_name,
+                &cwd.join(driver_name),
+                true,
+                &cwd,
+            )
             .expect_copy_map_file_to_package_folder(driver_name, &cwd, true)
             .expect_stampinf(driver_name, &cwd, target_arch, None)
             .expect_inf2cat(driver_name, &cwd, target_arch, None)
@@ -1851,7 +2566,8 @@ fn expect_default_package_task_steps_for_workspace(
             .expect_copy_self_signed_cert_file_to_package_folder(driver_name, &cwd, true)
             .expect_signtool_sign_driver_binary_sys_file(driver_name, &cwd, None)
             .expect_signtool_sign_cat_file(driver_name, &cwd, None)
-            .expect_infverif(driver_name, &cwd, driver_type, None);
+            .expect_infverif(driver_name, &cwd, driver_type, None)
+            .expect_package_stamp_written(driver_name, &cwd);
         if !verify_signature {
             return expectations;
         }
@@ -1898,6 +2614,34 @@ fn expect_final_package_dir_exists(
         self
     }
 
+    fn expect_package_stamp_missing(mut self, driver_name: &str, cwd: &Path) -> Self {
+        let expected_driver_name_underscored = driver_name.replace('-', "_");
+        let expected_target_dir = self.setup_target_dir(cwd);
+        let expected_package_stamp_path =
+            expected_target_dir.join(format!("{expected_driver_name_underscored}_package.stamp"));
+        self.mock_fs_provider
+            .expect_read_file_to_string()
+            .with(eq(expected_package_stamp_path.clone()))
+            .once()
+            .returning(move |_| Err(FileError::NotFound(expected_package_stamp_path.clone())));
+        self
+    }
+
+    fn expect_package_stamp_written(mut self, driver_name: &str, cwd: &Path) -> Self {
+        let expected_driver_name_underscored = driver_name.replace('-', "_");
+        let expected_target_dir = self.setup_target_dir(cwd);
+        let expected_package_stamp_path =
+            expected_target_dir.join(format!("{expected_driver_name_underscored}_package.stamp"));
+        self.mock_fs_provider
+            .expect_write_to_file()
+            .withf(move |path: &Path, data: &[u8]| -> bool {
+                path == expected_package_stamp_path && !data.is_empty()
+            })
+            .once()
+            .returning(|_, _| Ok(()));
+        self
+    }
+
     fn expect_dir_created(mut self, driver_name: &str, cwd: &Path, created: bool) -> Self {
         let expected_driver_name_underscored = driver_name.replace('-', "_");
         let expected_target_dir = self.setup_target_dir(cwd);
@@ -2141,7 +2885,7 @@ fn expect_copy_pdb_file_to_package_folder(
         self
     }
 
-    fn expect_copy_inx_file_to_package_folder(
+    fn expect_write_inf_file_to_package_folder(
         mut self,
         driver_name: &str,
         driver_dir: &Path,
@@ -2152,28 +2896,31 @@ fn expect_copy_inx_file_to_package_folder(
         let expected_target_dir = self.setup_target_dir(workspace_root_dir);
         let expected_final_package_dir_path =
             expected_target_dir.join(format!("{expected_driver_name_underscored}_package"));
-        let mock_non_zero_bytes_copied_size = 1000u64;
 
-        // copy inx file to package directory
+        // create inf file in package directory from inx file
         let expected_src_driver_inx_path =
             driver_dir.join(format!("{expected_driver_name_underscored}.inx"));
         let expected_dest_driver_inf_path =
             expected_final_package_dir_path.join(format!("{expected_driver_name_underscored}.inf"));
         self.mock_fs_provider
-            .expect_copy()
-            .with(
-                eq(expected_src_driver_inx_path.clone()),
-                eq(expected_dest_driver_inf_path.clone()),
-            )
+            .expect_read_file_to_string()
+            .with(eq(expected_src_driver_inx_path))
+            .once()
+            .returning(|_| Ok("[Version]\r\nSignature = \"$WINDOWS NT$\"\r\n".to_string()));
+        self.mock_fs_provider
+            .expect_write_to_file()
+            .withf({
+                let expected_dest_driver_inf_path = expected_dest_driver_inf_path.clone();
+                move |path: &Path, _: &[u8]| path == expected_dest_driver_inf_path
+            })
             .once()
             .returning(move |_, _| {
                 if is_success {
-                    Ok(mock_non_zero_bytes_copied_size)
+                    Ok(())
                 } else {
-                    Err(FileError::CopyError(
-                        expected_src_driver_inx_path.clone(),
+                    Err(FileError::WriteError(
                         expected_dest_driver_inf_path.clone(),
-                        std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "copy error"),
+                        std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "write error"),
                     ))
                 }
             });
@@ -2219,6 +2966,40 @@ fn expect_copy_map_file_to_package_folder(
         self
     }
 
+    fn expect_copy_export_driver_files_to_package_folder(
+        mut self,
+        driver_name: &str,
+        driver_dir: &Path,
+    ) -> Self {
+        let expected_driver_name_underscored = driver_name.replace('-', "_");
+        let expected_target_dir = self.setup_target_dir(driver_dir);
+        let expected_final_package_dir_path =
+            expected_target_dir.join(format!("{expected_driver_name_underscored}_package"));
+
+        // Export drivers keep their .dll extension and ship their import
+        // library
+        for (src_file_name, dest_file_name) in [
+            (
+                format!("{expected_driver_name_underscored}.dll"),
+                format!("{expected_driver_name_underscored}.dll"),
+            ),
+            (
+                format!("{expected_driver_name_underscored}.dll.lib"),
+                format!("{expected_driver_name_underscored}.lib"),
+            ),
+        ] {
+            self.mock_fs_provider
+                .expect_copy()
+                .with(
+                    eq(expected_target_dir.join(src_file_name)),
+                    eq(expected_final_package_dir_path.join(dest_file_name)),
+                )
+                .once()
+                .returning(|_, _| Ok(1000u64));
+        }
+        self
+    }
+
     fn expect_copy_self_signed_cert_file_to_package_folder(
         mut self,
         driver_name: &str,
@@ -2532,6 +3313,79 @@ fn expect_makecert(mut self, driver_dir: &Path, override_output: Option<Output>)
         self
     }
 
+    fn expect_verification_report_written(
+        mut self,
+        verification_report_path: &Path,
+        is_expected_report: fn(&serde_json::Value) -> bool,
+    ) -> Self {
+        let expected_verification_report_path = verification_report_path.to_path_buf();
+        self.mock_fs_provider
+            .expect_write_to_file()
+            .withf(move |path: &Path, data: &[u8]| -> bool {
+                path == expected_verification_report_path
+                    && serde_json::from_slice(data)
+                        .is_ok_and(|report: serde_json::Value| is_expected_report(&report))
+            })
+            .once()
+            .returning(|_, _| Ok(()));
+        self
+    }
+
+    fn expect_cert_file_exists(mut self, cert_path: &Path) -> Self {
+        self.mock_fs_provider
+            .expect_exists()
+            .with(eq(cert_path.to_path_buf()))
+            .once()
+            .returning(|_| true);
+        self
+    }
+
+    fn expect_signtool_sign_with_cert_file(
+        mut self,
+        driver_name: &str,
+        driver_dir: &Path,
+        extension: &str,
+        cert_path: &Path,
+    ) -> Self {
+        let expected_driver_name_underscored = driver_name.replace('-', "_");
+        let expected_file_path = self
+            .setup_target_dir(driver_dir)
+            .join(format!("{expected_driver_name_underscored}_package"))
+            .join(format!("{expected_driver_name_underscored}.{extension}"));
+        let expected_signtool_args: Vec<String> = vec![
+            "sign".to_string(),
+            "/v".to_string(),
+            "/f".to_string(),
+            cert_path.to_string_lossy().to_string(),
+            "/t".to_string(),
+            "http://timestamp.digicert.com".to_string(),
+            "/fd".to_string(),
+            "SHA256".to_string(),
+            expected_file_path.to_string_lossy().to_string(),
+        ];
+
+        self.mock_run_command
+            .expect_run()
+            .withf(
+                move |command: &str,
+                      args: &[&str],
+                      _env_vars: &Option<&HashMap<&str, &str>>,
+                      _working_dir: &Option<&Path>|
+                      -> bool {
+                    command == "signtool" && args == expected_signtool_args
+                },
+            )
+            .once()
+            .returning(|_, _, _, _| {
+                Ok(Output {
+                    status: ExitStatus::default(),
+                    stdout: vec![],
+                    stderr: vec![],
+                })
+            });
+        self
+    }
+
     fn expect_signtool_sign_driver_binary_sys_file(
         mut self,
         driver_name: &str,
@@ -3039,6 +3893,49 @@ fn get_cargo_metadata_package(
     )
 }
 
+/// Adds path dependencies on other workspace members to a package. Each
+/// dependency is a pair of the member name and the dependency kind
+fn with_path_dependencies(
+    package: &TestMetadataPackage,
+    dependencies: &[(&str, &str)],
+    workspace_root_dir: &Path,
+) -> TestMetadataPackage {
+    let dependencies = dependencies
+        .iter()
+        .map(|(name, kind)| {
+            format!(
+                r#"
+                {{
+                    "name": "{name}",
+                    "source": null,
+                    "req": "*",
+                    "kind": {},
+                    "rename": null,
+                    "optional": false,
+                    "uses_default_features": true,
+                    "features": [],
+                    "target": null,
+                    "path": "{}"
+                }}"#,
+                if *kind == "normal" {
+                    "null".to_string()
+                } else {
+                    format!("\"{kind}\"")
+                },
+                workspace_root_dir
+                    .join(name)
+                    .to_string_lossy()
+                    .escape_default()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    TestMetadataPackage(package.0.replace(
+        r#""dependencies": [],"#,
+        &format!(r#""dependencies": [{dependencies}],"#),
+    ))
+}
+
 fn get_cargo_metadata_wdk_metadata(
     driver_type: &str,
     kmdf_version_major: u8,
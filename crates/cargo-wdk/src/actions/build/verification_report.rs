@@ -0,0 +1,670 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+//! Module that collects the results of verifying driver packages.
+//! This module defines the `VerificationReport` struct which records the
+//! warnings, errors and signature chains reported by infverif and signtool
+//! verify for each driver package, so that they can be written to a
+//! machine-readable JSON file that CI pipelines can gate on, or to a SARIF
+//! file that editors and CI can annotate the sources of the packages with.
+
+use std::{
+    cell::RefCell,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{Value, json};
+use wdk_build::CpuArchitecture;
+
+use crate::providers::error::CommandError;
+
+/// Tools whose results are recorded in the verification report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerificationTool {
+    Infverif,
+    SigntoolVerify,
+}
+
+impl fmt::Display for VerificationTool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Infverif => "infverif",
+            Self::SigntoolVerify => "signtool verify",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Information URIs of the verification tools, reported in the SARIF file
+const INFVERIF_INFORMATION_URI: &str =
+    "https://learn.microsoft.com/en-us/windows-hardware/drivers/devtest/infverif";
+const SIGNTOOL_INFORMATION_URI: &str =
+    "https://learn.microsoft.com/en-us/windows/win32/seccrypto/signtool";
+const SARIF_SCHEMA_URI: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SARIF_VERSION: &str = "2.1.0";
+
+/// Severity of a diagnostic reported by a verification tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// A warning or error reported by a verification tool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Code of the reported issue, e.g. 1205 for `WARNING(1205)`. Only
+    /// reported by infverif
+    pub code: Option<u32>,
+    pub message: String,
+    /// File the diagnostic is reported for
+    pub file_path: PathBuf,
+    /// Line of `file_path` the diagnostic is reported for, starting at 1
+    pub line: Option<usize>,
+}
+
+/// Formats the diagnostic like a rustc diagnostic, e.g.
+/// ```text
+/// warning[1205]: Section [Foo] referenced from DelFiles and CopyFiles directive.
+///   --> driver.inx:10
+/// ```
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.severity)?;
+        if let Some(code) = self.code {
+            write!(f, "[{code}]")?;
+        }
+        write!(f, ": {}\n  --> {}", self.message, self.file_path.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Diagnostic {
+    fn to_json(&self) -> Value {
+        json!({
+            "severity": self.severity.to_string(),
+            "code": self.code,
+            "message": self.message,
+            "file": self.file_path.to_string_lossy(),
+            "line": self.line,
+        })
+    }
+}
+
+/// Maps the lines of the INF file of a driver package to the lines of the
+/// INX file it is generated from. The two only differ by the directives that
+/// are added after the `[Version]` section header of the INF file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InfLineMap {
+    version_section_line: usize,
+    added_directives: usize,
+}
+
+impl InfLineMap {
+    /// Creates the map of an INF file with `added_directives` lines added
+    /// after the `[Version]` section header at `version_section_line`
+    pub const fn new(version_section_line: usize, added_directives: usize) -> Self {
+        Self {
+            version_section_line,
+            added_directives,
+        }
+    }
+
+    /// Returns the line of the INX file that `inf_line` is generated from.
+    /// Added directives are mapped to the `[Version]` section header.
+    pub const fn to_inx_line(self, inf_line: usize) -> usize {
+        if inf_line <= self.version_section_line {
+            inf_line
+        } else if inf_line <= self.version_section_line + self.added_directives {
+            self.version_section_line
+        } else {
+            inf_line - self.added_directives
+        }
+    }
+}
+
+/// Result of verifying a file of a driver package with a verification tool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationResult {
+    pub package_name: String,
+    pub target_arch: CpuArchitecture,
+    pub tool: VerificationTool,
+    pub file_path: PathBuf,
+    pub passed: bool,
+    pub warnings: Vec<String>,
+    pub errors: Vec<String>,
+    /// Warnings and errors of the tool, with the location they are reported
+    /// for
+    pub diagnostics: Vec<Diagnostic>,
+    /// Subjects of the signing certificate chain, from the leaf certificate
+    /// to the root. Only reported by signtool verify
+    pub signature_chain: Vec<String>,
+}
+
+impl VerificationResult {
+    /// Creates the result of a verification tool run from the outcome of its
+    /// command. The warnings, errors and signature chain are parsed from the
+    /// output of the tool.
+    pub fn new(
+        package_name: &str,
+        target_arch: CpuArchitecture,
+        tool: VerificationTool,
+        file_path: &Path,
+        command_result: Result<&str, &CommandError>,
+    ) -> Self {
+        let (passed, stdout) = match command_result {
+            Ok(stdout) => (true, stdout),
+            Err(CommandError::CommandFailed { stdout, .. }) => (false, stdout.as_str()),
+            Err(CommandError::IoError(..)) => (false, ""),
+        };
+        let (warning_prefix, error_prefix) = match tool {
+            VerificationTool::Infverif => ("WARNING", "ERROR"),
+            VerificationTool::SigntoolVerify => ("SignTool Warning:", "SignTool Error:"),
+        };
+        let mut warnings = vec![];
+        let mut errors = vec![];
+        let mut diagnostics = vec![];
+        for line in stdout.lines().map(str::trim) {
+            let (severity, rest) = if let Some(rest) = line.strip_prefix(warning_prefix) {
+                warnings.push(line.to_string());
+                (Severity::Warning, rest)
+            } else if let Some(rest) = line.strip_prefix(error_prefix) {
+                errors.push(line.to_string());
+                (Severity::Error, rest)
+            } else {
+                continue;
+            };
+            let (code, line_number, message) = match tool {
+                VerificationTool::Infverif => parse_infverif_diagnostic(rest),
+                VerificationTool::SigntoolVerify => (None, None, rest.trim().to_string()),
+            };
+            diagnostics.push(Diagnostic {
+                severity,
+                code,
+                message,
+                file_path: file_path.to_path_buf(),
+                line: line_number,
+            });
+        }
+        // Failures without any reported error, e.g. when the tool cannot be
+        // run, are recorded with the error of the command
+        if let Err(e) = command_result
+            && errors.is_empty()
+        {
+            errors.push(e.to_string());
+        }
+        let signature_chain = if tool == VerificationTool::SigntoolVerify {
+            parse_signature_chain(stdout)
+        } else {
+            vec![]
+        };
+
+        Self {
+            package_name: package_name.to_string(),
+            target_arch,
+            tool,
+            file_path: file_path.to_path_buf(),
+            passed,
+            warnings,
+            errors,
+            diagnostics,
+            signature_chain,
+        }
+    }
+
+    /// Reports the diagnostics of the result for `src_file_path`, the INX file
+    /// the verified INF file is generated from, so that they point at the
+    /// lines the driver author edits
+    #[must_use]
+    pub fn with_source_file(mut self, src_file_path: &Path, line_map: InfLineMap) -> Self {
+        for diagnostic in &mut self.diagnostics {
+            diagnostic.file_path = src_file_path.to_path_buf();
+            diagnostic.line = diagnostic.line.map(|line| line_map.to_inx_line(line));
+        }
+        self
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "package_name": self.package_name,
+            "target_arch": self.target_arch.to_string(),
+            "tool": self.tool.to_string(),
+            "file": self.file_path.to_string_lossy(),
+            "passed": self.passed,
+            "warnings": self.warnings,
+            "errors": self.errors,
+            "diagnostics": self.diagnostics.iter().map(Diagnostic::to_json).collect::<Vec<_>>(),
+            "signature_chain": self.signature_chain,
+        })
+    }
+
+    fn to_sarif_results(&self) -> impl Iterator<Item = Value> {
+        self.diagnostics.iter().map(|diagnostic| {
+            let mut region = json!({});
+            if let Some(line) = diagnostic.line {
+                region["startLine"] = json!(line);
+            }
+            let mut result = json!({
+                "level": diagnostic.severity.to_string(),
+                "message": { "text": diagnostic.message },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": file_uri(&diagnostic.file_path) },
+                        "region": region,
+                    },
+                }],
+                "properties": {
+                    "packageName": self.package_name,
+                    "targetArch": self.target_arch.to_string(),
+                },
+            });
+            if let Some(code) = diagnostic.code {
+                result["ruleId"] = json!(code.to_string());
+            }
+            result
+        })
+    }
+}
+
+/// Collects the verification results of all the driver packages of a build
+#[derive(Debug, Default)]
+pub struct VerificationReport {
+    results: RefCell<Vec<VerificationResult>>,
+    /// Notes about verifications that were not run, e.g. infverif for sample
+    /// class drivers on WDK builds whose infverif lacks the samples flag
+    notes: RefCell<Vec<(VerificationTool, String)>>,
+}
+
+impl VerificationReport {
+    /// Adds a verification result to the report
+    pub fn record(&self, result: VerificationResult) {
+        self.results.borrow_mut().push(result);
+    }
+
+    /// Adds a note about a verification tool to the report, e.g. when the tool
+    /// was skipped
+    pub fn record_note(&self, tool: VerificationTool, note: String) {
+        self.notes.borrow_mut().push((tool, note));
+    }
+
+    /// Returns the report as pretty printed JSON. The top level `passed` field
+    /// is false if any verification failed.
+    pub fn to_json_string(&self) -> String {
+        let results = self.results.borrow();
+        let notes = self
+            .notes
+            .borrow()
+            .iter()
+            .map(|(tool, note)| json!({ "tool": tool.to_string(), "note": note }))
+            .collect::<Vec<_>>();
+        let report = json!({
+            "passed": results.iter().all(|result| result.passed),
+            "results": results.iter().map(VerificationResult::to_json).collect::<Vec<_>>(),
+            "notes": notes,
+        });
+        serde_json::to_string_pretty(&report).expect("JSON values are always serializable")
+    }
+
+    /// Returns the diagnostics of the report as a pretty printed SARIF 2.1.0
+    /// log, with a run for each verification tool that has results or notes.
+    /// Diagnostics without a code have no `ruleId`.
+    pub fn to_sarif_string(&self) -> String {
+        let results = self.results.borrow();
+        let notes = self.notes.borrow();
+        let runs = [
+            (VerificationTool::Infverif, INFVERIF_INFORMATION_URI),
+            (VerificationTool::SigntoolVerify, SIGNTOOL_INFORMATION_URI),
+        ]
+        .into_iter()
+        .filter_map(|(tool, information_uri)| {
+            let tool_results = results
+                .iter()
+                .filter(|result| result.tool == tool)
+                .collect::<Vec<_>>();
+            let tool_notes = notes
+                .iter()
+                .filter(|(note_tool, _)| *note_tool == tool)
+                .map(|(_, note)| json!({ "level": "note", "message": { "text": note } }))
+                .collect::<Vec<_>>();
+            if tool_results.is_empty() && tool_notes.is_empty() {
+                return None;
+            }
+            let mut rule_ids = tool_results
+                .iter()
+                .flat_map(|result| &result.diagnostics)
+                .filter_map(|diagnostic| diagnostic.code)
+                .collect::<Vec<_>>();
+            rule_ids.sort_unstable();
+            rule_ids.dedup();
+            let rules = rule_ids
+                .iter()
+                .map(|id| json!({ "id": id.to_string() }))
+                .collect::<Vec<_>>();
+            let sarif_results = tool_results
+                .iter()
+                .flat_map(|result| result.to_sarif_results())
+                .collect::<Vec<_>>();
+            Some(json!({
+                "tool": {
+                    "driver": {
+                        "name": tool.to_string(),
+                        "informationUri": information_uri,
+                        "rules": rules,
+                    },
+                },
+                "invocations": [{
+                    "executionSuccessful": tool_results.iter().all(|result| result.passed),
+                    "toolExecutionNotifications": tool_notes,
+                }],
+                "results": sarif_results,
+            }))
+        })
+        .collect::<Vec<_>>();
+        let log = json!({
+            "$schema": SARIF_SCHEMA_URI,
+            "version": SARIF_VERSION,
+            "runs": runs,
+        });
+        serde_json::to_string_pretty(&log).expect("JSON values are always serializable")
+    }
+}
+
+/// Parses the code, line and message of an infverif diagnostic from the rest
+/// of its line after the `WARNING` or `ERROR` prefix, e.g.
+/// `(1205) in driver.inf, line 10: Section [Foo] referenced from ...`
+fn parse_infverif_diagnostic(rest: &str) -> (Option<u32>, Option<usize>, String) {
+    let (code, rest) = match rest.strip_prefix('(').and_then(|rest| rest.split_once(')')) {
+        Some((code, rest)) => (code.trim().parse().ok(), rest),
+        None => (None, rest),
+    };
+    // The file path may contain a drive letter, which is not followed by a
+    // space, so the location ends at the first `: `
+    let (location, message) = rest.split_once(": ").unwrap_or(("", rest));
+    let line = location
+        .rsplit_once(", line ")
+        .and_then(|(_, line)| line.trim().parse().ok());
+    let message = message.trim().trim_start_matches(':').trim().to_string();
+    (code, line, message)
+}
+
+/// Returns the `file` URI of `path`, as SARIF locations are URIs. Characters
+/// that are not allowed in a URI path are percent-encoded.
+fn file_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from(if path.starts_with('/') {
+        "file://"
+    } else {
+        "file:///"
+    });
+    for c in path.chars() {
+        match c {
+            ' ' => uri.push_str("%20"),
+            '%' => uri.push_str("%25"),
+            '#' => uri.push_str("%23"),
+            '?' => uri.push_str("%3F"),
+            _ => uri.push(c),
+        }
+    }
+    uri
+}
+
+/// Parses the subjects of the signing certificate chain from the verbose
+/// output of signtool verify. The chain is listed as `Issued to:` entries under
+/// `Signing Certificate Chain:`, before any timestamp certificate chain.
+fn parse_signature_chain(stdout: &str) -> Vec<String> {
+    stdout
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| !line.starts_with("Signing Certificate Chain:"))
+        .take_while(|line| !line.contains("timestamp") && !line.contains("Timestamp"))
+        .filter_map(|line| line.strip_prefix("Issued to:"))
+        .map(|subject| subject.trim().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIGNTOOL_VERIFY_OUTPUT: &str = r"Verifying: C:\driver_package\driver.sys
+
+Signature Index: 0 (Primary Signature)
+Hash of file (sha256): 0123456789ABCDEF
+
+Signing Certificate Chain:
+    Issued to: WDRLocalTestCert
+    Issued by: WDRLocalTestCert
+    Expires:   Sat Jan 01 00:59:59 2040
+    SHA1 hash: 0123456789ABCDEF0123456789ABCDEF01234567
+
+The signature is timestamped: Mon Jan 01 00:00:00 2024
+Timestamp Verified by:
+    Issued to: Timestamp Root
+    Issued by: Timestamp Root
+SignTool Error: A certificate chain processed, but terminated in a root
+        certificate which is not trusted by the trust provider.
+
+Number of files successfully Verified: 0
+Number of warnings: 0
+Number of errors: 1
+";
+
+    #[test]
+    fn signtool_verify_output_is_parsed() {
+        let error = CommandError::CommandFailed {
+            command: "signtool".to_string(),
+            args: vec![],
+            stdout: SIGNTOOL_VERIFY_OUTPUT.to_string(),
+        };
+        let result = VerificationResult::new(
+            "driver",
+            CpuArchitecture::Amd64,
+            VerificationTool::SigntoolVerify,
+            Path::new(r"C:\driver_package\driver.sys"),
+            Err(&error),
+        );
+        assert!(!result.passed);
+        assert!(result.warnings.is_empty());
+        assert_eq!(
+            result.errors,
+            vec![
+                "SignTool Error: A certificate chain processed, but terminated in a root"
+                    .to_string()
+            ]
+        );
+        assert_eq!(result.signature_chain, vec!["WDRLocalTestCert".to_string()]);
+    }
+
+    #[test]
+    fn infverif_output_is_parsed() {
+        let output = "INF is VALID\nWARNING(1205) in driver.inf, line 10: Section [Foo] \
+                      referenced from DelFiles and CopyFiles directive.\n";
+        let result = VerificationResult::new(
+            "driver",
+            CpuArchitecture::Arm64,
+            VerificationTool::Infverif,
+            Path::new(r"C:\driver_package\driver.inf"),
+            Ok(output),
+        );
+        assert!(result.passed);
+        assert_eq!(result.warnings.len(), 1);
+        assert!(result.errors.is_empty());
+        assert!(result.signature_chain.is_empty());
+        assert_eq!(
+            result.diagnostics,
+            vec![Diagnostic {
+                severity: Severity::Warning,
+                code: Some(1205),
+                message: "Section [Foo] referenced from DelFiles and CopyFiles directive."
+                    .to_string(),
+                file_path: PathBuf::from(r"C:\driver_package\driver.inf"),
+                line: Some(10),
+            }]
+        );
+    }
+
+    #[test]
+    fn infverif_diagnostics_are_mapped_to_the_inx_file() {
+        let output = "ERROR(1284) in C:\\driver_package\\driver.inf, line 4: Class \"Sample\" is \
+                      unrecognized.\nWARNING(1205) in driver.inf, line 12: Section [Foo] \
+                      referenced from DelFiles and CopyFiles directive.\nWARNING: No line.\n";
+        // Two directives were added after the `[Version]` header on line 2
+        let result = VerificationResult::new(
+            "driver",
+            CpuArchitecture::Amd64,
+            VerificationTool::Infverif,
+            Path::new(r"C:\driver_package\driver.inf"),
+            Ok(output),
+        )
+        .with_source_file(Path::new(r"C:\driver\driver.inx"), InfLineMap::new(2, 2));
+        let locations = result
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.file_path.clone(),
+                    diagnostic.code,
+                    diagnostic.line,
+                )
+            })
+            .collect::<Vec<_>>();
+        let inx_file_path = PathBuf::from(r"C:\driver\driver.inx");
+        assert_eq!(
+            locations,
+            vec![
+                (inx_file_path.clone(), Some(1284), Some(2)),
+                (inx_file_path.clone(), Some(1205), Some(10)),
+                (inx_file_path, None, None),
+            ]
+        );
+        assert_eq!(result.diagnostics[2].message, "No line.");
+    }
+
+    #[test]
+    fn diagnostics_are_displayed_like_rustc_diagnostics() {
+        let mut diagnostic = Diagnostic {
+            severity: Severity::Warning,
+            code: Some(1205),
+            message: "Section [Foo] referenced from DelFiles and CopyFiles directive.".to_string(),
+            file_path: PathBuf::from("driver.inx"),
+            line: Some(10),
+        };
+        assert_eq!(
+            diagnostic.to_string(),
+            "warning[1205]: Section [Foo] referenced from DelFiles and CopyFiles directive.\n  \
+             --> driver.inx:10"
+        );
+
+        diagnostic.severity = Severity::Error;
+        diagnostic.code = None;
+        diagnostic.line = None;
+        assert_eq!(
+            diagnostic.to_string(),
+            "error: Section [Foo] referenced from DelFiles and CopyFiles directive.\n  --> \
+             driver.inx"
+        );
+    }
+
+    #[test]
+    fn report_is_written_as_sarif() {
+        let report = VerificationReport::default();
+        let error = CommandError::CommandFailed {
+            command: "infverif".to_string(),
+            args: vec![],
+            stdout: "ERROR(1284) in driver.inf, line 5: Class \"Sample\" is unrecognized."
+                .to_string(),
+        };
+        report.record(
+            VerificationResult::new(
+                "driver",
+                CpuArchitecture::Amd64,
+                VerificationTool::Infverif,
+                Path::new(r"C:\driver_package\driver.inf"),
+                Err(&error),
+            )
+            .with_source_file(Path::new(r"C:\my driver\driver.inx"), InfLineMap::default()),
+        );
+        report.record_note(
+            VerificationTool::Infverif,
+            "InfVerif skipped for samples class".to_string(),
+        );
+        let sarif: Value =
+            serde_json::from_str(&report.to_sarif_string()).expect("SARIF should be valid JSON");
+        assert_eq!(sarif["version"], "2.1.0");
+        assert_eq!(sarif["runs"].as_array().map(Vec::len), Some(1));
+        let run = &sarif["runs"][0];
+        assert_eq!(run["tool"]["driver"]["name"], "infverif");
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "1284");
+        assert_eq!(run["invocations"][0]["executionSuccessful"], false);
+        assert_eq!(
+            run["invocations"][0]["toolExecutionNotifications"][0]["message"]["text"],
+            "InfVerif skipped for samples class"
+        );
+        let result = &run["results"][0];
+        assert_eq!(result["ruleId"], "1284");
+        assert_eq!(result["level"], "error");
+        assert_eq!(
+            result["message"]["text"],
+            "Class \"Sample\" is unrecognized."
+        );
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(
+            location["artifactLocation"]["uri"],
+            "file:///C:/my%20driver/driver.inx"
+        );
+        assert_eq!(location["region"]["startLine"], 5);
+    }
+
+    #[test]
+    fn report_fails_when_any_verification_fails() {
+        let report = VerificationReport::default();
+        report.record(VerificationResult::new(
+            "driver",
+            CpuArchitecture::Amd64,
+            VerificationTool::Infverif,
+            Path::new(r"C:\driver_package\driver.inf"),
+            Ok(""),
+        ));
+        let json: Value =
+            serde_json::from_str(&report.to_json_string()).expect("report should be valid JSON");
+        assert_eq!(json["passed"], true);
+
+        let error = CommandError::CommandFailed {
+            command: "infverif".to_string(),
+            args: vec![],
+            stdout: "ERROR(1284) in driver.inf, line 5: Class \"Sample\" is unrecognized."
+                .to_string(),
+        };
+        report.record(VerificationResult::new(
+            "driver",
+            CpuArchitecture::Amd64,
+            VerificationTool::Infverif,
+            Path::new(r"C:\driver_package\driver.inf"),
+            Err(&error),
+        ));
+        let json: Value =
+            serde_json::from_str(&report.to_json_string()).expect("report should be valid JSON");
+        assert_eq!(json["passed"], false);
+        assert_eq!(json["results"].as_array().map(Vec::len), Some(2));
+        assert_eq!(json["results"][1]["tool"], "infverif");
+        assert_eq!(json["results"][1]["target_arch"], "amd64");
+        assert_eq!(
+            json["results"][1]["errors"].as_array().map(Vec::len),
+            Some(1)
+        );
+    }
+}
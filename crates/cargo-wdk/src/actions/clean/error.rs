@@ -0,0 +1,48 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+//! This module defines error types for the clean action module.
+
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use crate::{
+    actions::FailureCategory,
+    providers::error::{CommandError, FileError},
+};
+
+/// Errors for the clean action layer
+#[derive(Debug, Error)]
+pub enum CleanActionError {
+    #[error(transparent)]
+    WdkBuildConfig(#[from] wdk_build::ConfigError),
+    #[error("Error Parsing Cargo.toml, not a valid rust project/workspace")]
+    CargoMetadataParse(#[from] cargo_metadata::Error),
+    #[error("Error Parsing WDK metadata from Cargo.toml, not a valid driver project/workspace")]
+    WdkMetadataParse(#[from] wdk_build::metadata::TryFromCargoMetadataError),
+    #[error("Not a workspace member, working directory: {0}")]
+    NotAWorkspaceMember(PathBuf),
+    #[error(transparent)]
+    FileIo(#[from] FileError),
+    #[error("Checking for existence of cert in store using certmgr")]
+    VerifyCertExistsInStoreCommand(#[source] CommandError),
+    #[error("Error removing test certificate from store using certmgr")]
+    RemoveCertCommand(#[source] CommandError),
+}
+
+impl CleanActionError {
+    /// Returns the [`FailureCategory`] of the error
+    #[must_use]
+    pub const fn category(&self) -> FailureCategory {
+        match self {
+            Self::WdkBuildConfig(_) => FailureCategory::EnvironmentMissing,
+            Self::CargoMetadataParse(_)
+            | Self::WdkMetadataParse(_)
+            | Self::NotAWorkspaceMember(_) => FailureCategory::MetadataInvalid,
+            Self::VerifyCertExistsInStoreCommand(_) | Self::RemoveCertCommand(_) => {
+                FailureCategory::Signing
+            }
+            Self::FileIo(_) => FailureCategory::Other,
+        }
+    }
+}
@@ -0,0 +1,463 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+//! This module contains the `CleanAction` struct and its associated methods
+//! for removing the driver packages and other artifacts created by
+//! `cargo wdk build`. Cleaning optionally also removes the local test
+//! certificate generated by `cargo wdk build` from its certificate store, so
+//! that the next build starts from a fresh build and signing state.
+
+mod error;
+
+use std::path::{Path, PathBuf, absolute};
+
+use anyhow::Result;
+use cargo_metadata::{Metadata as CargoMetadata, Package, TargetKind};
+pub use error::CleanActionError;
+use mockall_double::double;
+use tracing::{debug, info};
+use wdk_build::{
+    CpuArchitecture,
+    metadata::{PackageConfig, Wdk},
+};
+
+use crate::actions::{Profile, get_target_dir};
+#[double]
+use crate::providers::{exec::CommandExec, fs::Fs, metadata::Metadata};
+
+/// Name of the certificate store `cargo wdk build` generates the local test
+/// certificate in
+const WDR_TEST_CERT_STORE: &str = "WDRTestCertStore";
+/// Name of the local test certificate generated by `cargo wdk build`
+const WDR_LOCAL_TEST_CERT: &str = "WDRLocalTestCert";
+
+pub struct CleanActionParams<'a> {
+    pub working_dir: &'a Path,
+    pub profile: Option<&'a Profile>,
+    pub target_arch: Option<CpuArchitecture>,
+    pub remove_cert: bool,
+}
+
+/// Action that removes the artifacts `cargo wdk build` creates for the driver
+/// packages of a driver project or workspace, for the same profile and target
+/// architecture. The artifacts of `cargo build` itself are left to
+/// `cargo clean`.
+pub struct CleanAction<'a> {
+    working_dir: PathBuf,
+    profile: Option<&'a Profile>,
+    target_arch: Option<CpuArchitecture>,
+    remove_cert: bool,
+
+    // Injected deps
+    command_exec: &'a CommandExec,
+    fs: &'a Fs,
+    metadata: &'a Metadata,
+}
+
+impl<'a> CleanAction<'a> {
+    /// Creates a new instance of `CleanAction`.
+    ///
+    /// # Arguments:
+    /// * `params` - The `CleanActionParams` struct containing the parameters
+    ///   for the clean action
+    /// * `command_exec` - The command execution provider instance
+    /// * `fs` - The file system provider instance
+    /// * `metadata` - The metadata provider instance
+    ///
+    /// # Returns
+    /// * `Result<Self>` - A result containing either a new instance of
+    ///   `CleanAction` on success, or an `anyhow::Error`.
+    ///
+    /// # Errors
+    /// * [`anyhow::Error`] -  If `params.working_dir` is not a syntactically
+    ///   valid path, e.g. it is empty
+    pub fn new(
+        params: &CleanActionParams<'a>,
+        command_exec: &'a CommandExec,
+        fs: &'a Fs,
+        metadata: &'a Metadata,
+    ) -> Result<Self> {
+        Ok(Self {
+            working_dir: absolute(params.working_dir)?,
+            profile: params.profile,
+            target_arch: params.target_arch,
+            remove_cert: params.remove_cert,
+            command_exec,
+            fs,
+            metadata,
+        })
+    }
+
+    /// Entry point method to execute the clean action flow.
+    ///
+    /// # Returns
+    /// `Result<(), CleanActionError>`
+    ///
+    /// # Errors
+    /// * `CleanActionError::CargoMetadataParse` - If there is an error parsing
+    ///   the Cargo metadata.
+    /// * `CleanActionError::WdkMetadataParse` - If there is an error parsing
+    ///   the WDK metadata.
+    /// * `CleanActionError::NotAWorkspaceMember` - If the working directory is
+    ///   not a workspace member.
+    /// * `CleanActionError::FileIo` - If there is an error removing an
+    ///   artifact.
+    /// * `CleanActionError::WdkBuildConfig` - If the WDK tools required to
+    ///   remove the test certificate cannot be found.
+    /// * `CleanActionError::VerifyCertExistsInStoreCommand` - If there is an
+    ///   error checking whether the test certificate exists.
+    /// * `CleanActionError::RemoveCertCommand` - If there is an error removing
+    ///   the test certificate from its store.
+    pub fn run(&self) -> Result<(), CleanActionError> {
+        debug!(
+            "Initialized clean for project at: {}",
+            self.working_dir.display()
+        );
+        let cargo_metadata = self
+            .metadata
+            .get_cargo_metadata_at_path(&self.working_dir)?;
+        let wdk_metadata = Wdk::try_from(&cargo_metadata)?;
+        let cert_file_name = get_cert_file_name(wdk_metadata.package.as_ref());
+        let target_dir = get_target_dir(
+            cargo_metadata.target_directory.as_std_path(),
+            self.profile,
+            self.target_arch,
+        );
+
+        for package_name in self.get_driver_package_names(&cargo_metadata)? {
+            info!("Cleaning package {package_name}");
+            self.clean_package(&package_name, &target_dir)?;
+        }
+
+        if let Some(cert_file_name) = cert_file_name {
+            self.remove_file_if_exists(&target_dir.join(cert_file_name))?;
+        }
+
+        if self.remove_cert {
+            self.remove_test_cert_from_store()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the names of the driver packages to clean. These are all the
+    /// driver packages of the workspace when running from the workspace root,
+    /// or the package of the workspace member the action is run from.
+    fn get_driver_package_names(
+        &self,
+        cargo_metadata: &CargoMetadata,
+    ) -> Result<Vec<String>, CleanActionError> {
+        let is_driver_package = |package: &Package| {
+            package.metadata.get("wdk").is_some()
+                && package
+                    .targets
+                    .iter()
+                    .any(|t| t.kind.contains(&TargetKind::CDyLib))
+        };
+        let workspace_packages = cargo_metadata.workspace_packages();
+
+        let driver_packages = if cargo_metadata.workspace_root.as_std_path() == self.working_dir {
+            workspace_packages
+                .into_iter()
+                .filter(|package| is_driver_package(package))
+                .collect::<Vec<_>>()
+        } else {
+            let package = workspace_packages
+                .into_iter()
+                .find(|package| {
+                    package
+                        .manifest_path
+                        .parent()
+                        .is_some_and(|p| p.as_std_path() == self.working_dir)
+                })
+                .ok_or_else(|| CleanActionError::NotAWorkspaceMember(self.working_dir.clone()))?;
+            if is_driver_package(package) {
+                vec![package]
+            } else {
+                vec![]
+            }
+        };
+
+        Ok(driver_packages
+            .into_iter()
+            .map(|package| package.name.clone())
+            .collect())
+    }
+
+    /// Removes the driver package folder of a single package, along with its
+    /// stamp file and the HLK and cab folders created next to it
+    fn clean_package(&self, package_name: &str, target_dir: &Path) -> Result<(), CleanActionError> {
+        let package_name = package_name.replace('-', "_");
+        for dir_name in [
+            format!("{package_name}_package"),
+            format!("{package_name}_hlk"),
+            format!("{package_name}_cab"),
+        ] {
+            let dir_path = target_dir.join(dir_name);
+            if self.fs.exists(&dir_path) {
+                debug!("Removing {}", dir_path.display());
+                self.fs.remove_dir_all(&dir_path)?;
+            }
+        }
+        self.remove_file_if_exists(&target_dir.join(format!("{package_name}_package.stamp")))
+    }
+
+    /// Removes the file at `path`, if there is one
+    fn remove_file_if_exists(&self, path: &Path) -> Result<(), CleanActionError> {
+        if self.fs.exists(path) {
+            debug!("Removing {}", path.display());
+            self.fs.remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the local test certificate from its certificate store, if it
+    /// exists. Certificates provided by the user are never removed.
+    fn remove_test_cert_from_store(&self) -> Result<(), CleanActionError> {
+        // certmgr ships with the WDK tools
+        wdk_build::cargo_make::setup_path()?;
+
+        let is_cert_in_store =
+            match self
+                .command_exec
+                .run("certmgr.exe", &["-s", WDR_TEST_CERT_STORE], None, None)
+            {
+                Ok(output) => String::from_utf8_lossy(&output.stdout).contains(WDR_LOCAL_TEST_CERT),
+                Err(e) => return Err(CleanActionError::VerifyCertExistsInStoreCommand(e)),
+            };
+        if !is_cert_in_store {
+            info!("Certificate {WDR_LOCAL_TEST_CERT} not found in {WDR_TEST_CERT_STORE} store");
+            return Ok(());
+        }
+
+        info!("Removing certificate {WDR_LOCAL_TEST_CERT} from {WDR_TEST_CERT_STORE} store");
+        self.command_exec
+            .run(
+                "certmgr.exe",
+                &[
+                    "-del",
+                    "-c",
+                    "-n",
+                    WDR_LOCAL_TEST_CERT,
+                    "-s",
+                    WDR_TEST_CERT_STORE,
+                ],
+                None,
+                None,
+            )
+            .map_err(CleanActionError::RemoveCertCommand)?;
+        Ok(())
+    }
+}
+
+/// Returns the name of the certificate file `cargo wdk build` exports to the
+/// target directory. No certificate file is exported for packages signed with
+/// a certificate file.
+fn get_cert_file_name(package_config: Option<&PackageConfig>) -> Option<String> {
+    match package_config {
+        Some(PackageConfig {
+            cert_path: Some(_), ..
+        }) => None,
+        Some(PackageConfig {
+            cert_thumbprint: Some(cert_thumbprint),
+            ..
+        }) => Some(format!("{cert_thumbprint}.cer")),
+        Some(PackageConfig {
+            cert_name: Some(cert_name),
+            ..
+        }) => Some(format!("{cert_name}.cer")),
+        _ => Some(format!("{WDR_LOCAL_TEST_CERT}.cer")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::{
+        actions::{
+            Profile,
+            clean::{CleanAction, CleanActionError, CleanActionParams},
+        },
+        providers::{exec::MockCommandExec, fs::MockFs, metadata::MockMetadata},
+    };
+
+    const DRIVER_NAME: &str = "sample-kmdf";
+
+    #[test]
+    fn given_a_built_driver_when_cleaning_then_package_artifacts_and_cert_file_are_removed() {
+        let cwd = PathBuf::from(r"C:\tmp\sample-kmdf");
+        let target_dir = cwd.join("target").join("release");
+        let package_dir = target_dir.join("sample_kmdf_package");
+        let existing_files = [
+            target_dir.join("sample_kmdf_package.stamp"),
+            target_dir.join("WDRLocalTestCert.cer"),
+        ];
+
+        let mut mock_fs = MockFs::new();
+        let existing_paths = [
+            package_dir.clone(),
+            existing_files[0].clone(),
+            existing_files[1].clone(),
+        ];
+        mock_fs
+            .expect_exists()
+            .returning(move |path| existing_paths.iter().any(|p| p == path));
+        mock_fs
+            .expect_remove_dir_all()
+            .withf(move |path| path == package_dir)
+            .once()
+            .returning(|_| Ok(()));
+        for file_path in existing_files {
+            mock_fs
+                .expect_remove_file()
+                .withf(move |path| path == file_path)
+                .once()
+                .returning(|_| Ok(()));
+        }
+
+        let result = run_clean_action(
+            &cwd,
+            Some(&Profile::Release),
+            false,
+            &MockCommandExec::new(),
+            &mock_fs,
+        );
+
+        assert!(result.is_ok(), "clean failed unexpectedly: {result:?}");
+    }
+
+    #[test]
+    fn given_nothing_was_built_when_cleaning_then_nothing_is_removed() {
+        let cwd = PathBuf::from(r"C:\tmp\sample-kmdf");
+
+        let mut mock_fs = MockFs::new();
+        mock_fs.expect_exists().returning(|_| false);
+        mock_fs.expect_remove_file().never();
+        mock_fs.expect_remove_dir_all().never();
+
+        let result = run_clean_action(&cwd, None, false, &MockCommandExec::new(), &mock_fs);
+
+        assert!(result.is_ok(), "clean failed unexpectedly: {result:?}");
+    }
+
+    #[test]
+    fn given_a_non_member_directory_when_cleaning_then_not_a_workspace_member_is_returned() {
+        let cwd = PathBuf::from(r"C:\tmp\sample-kmdf");
+        let cargo_metadata = get_cargo_metadata(&cwd);
+        let mut mock_metadata = MockMetadata::new();
+        mock_metadata
+            .expect_get_cargo_metadata_at_path()
+            .returning(move |_| {
+                Ok(serde_json::from_str(&cargo_metadata).expect("invalid cargo metadata"))
+            });
+
+        let result = CleanAction::new(
+            &CleanActionParams {
+                working_dir: &cwd.join("src"),
+                profile: None,
+                target_arch: None,
+                remove_cert: false,
+            },
+            &MockCommandExec::new(),
+            &MockFs::new(),
+            &mock_metadata,
+        )
+        .expect("Failed to init clean action")
+        .run();
+
+        assert!(matches!(
+            result,
+            Err(CleanActionError::NotAWorkspaceMember(_))
+        ));
+    }
+
+    fn run_clean_action(
+        cwd: &Path,
+        profile: Option<&Profile>,
+        remove_cert: bool,
+        mock_exec: &MockCommandExec,
+        mock_fs: &MockFs,
+    ) -> Result<(), CleanActionError> {
+        let cargo_metadata = get_cargo_metadata(cwd);
+        let mut mock_metadata = MockMetadata::new();
+        mock_metadata
+            .expect_get_cargo_metadata_at_path()
+            .returning(move |_| {
+                Ok(serde_json::from_str(&cargo_metadata).expect("invalid cargo metadata"))
+            });
+
+        CleanAction::new(
+            &CleanActionParams {
+                working_dir: cwd,
+                profile,
+                target_arch: None,
+                remove_cert,
+            },
+            mock_exec,
+            mock_fs,
+            &mock_metadata,
+        )
+        .expect("Failed to init clean action")
+        .run()
+    }
+
+    fn get_cargo_metadata(root_dir: &Path) -> String {
+        let normalized_root = root_dir.to_string_lossy().replace('\\', "/");
+        let package_id = format!("path+file:///{normalized_root}#{DRIVER_NAME}@0.0.1");
+        format!(
+            r#"
+            {{
+                "target_directory": "{target_directory}",
+                "workspace_root": "{workspace_root}",
+                "packages": [
+                    {{
+                        "name": "{DRIVER_NAME}",
+                        "version": "0.0.1",
+                        "id": "{package_id}",
+                        "dependencies": [],
+                        "targets": [
+                            {{
+                                "kind": ["cdylib"],
+                                "crate_types": ["cdylib"],
+                                "name": "{DRIVER_NAME}",
+                                "src_path": "{src_path}",
+                                "edition": "2021",
+                                "doc": true,
+                                "doctest": false,
+                                "test": true
+                            }}
+                        ],
+                        "features": {{}},
+                        "manifest_path": "{manifest_path}",
+                        "authors": [],
+                        "categories": [],
+                        "keywords": [],
+                        "edition": "2021",
+                        "metadata": {{
+                            "wdk": {{
+                                "driver-model": {{
+                                    "driver-type": "KMDF",
+                                    "kmdf-version-major": 1,
+                                    "target-kmdf-version-minor": 33
+                                }}
+                            }}
+                        }}
+                    }}
+                ],
+                "workspace_members": ["{package_id}"],
+                "metadata": null,
+                "version": 1
+            }}"#,
+            target_directory = root_dir.join("target").to_string_lossy().escape_default(),
+            workspace_root = root_dir.to_string_lossy().escape_default(),
+            src_path = root_dir
+                .join("src")
+                .join("lib.rs")
+                .to_string_lossy()
+                .escape_default(),
+            manifest_path = root_dir
+                .join("Cargo.toml")
+                .to_string_lossy()
+                .escape_default(),
+        )
+    }
+}
@@ -0,0 +1,60 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+//! This module defines error types for the deploy action module.
+
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use crate::{
+    actions::FailureCategory,
+    providers::error::{CommandError, FileError},
+};
+
+/// Errors for the deploy action layer
+#[derive(Debug, Error)]
+pub enum DeployActionError {
+    #[error(transparent)]
+    WdkBuildConfig(#[from] wdk_build::ConfigError),
+    #[error("Error Parsing Cargo.toml, not a valid rust project/workspace")]
+    CargoMetadataParse(#[from] cargo_metadata::Error),
+    #[error("Error Parsing WDK metadata from Cargo.toml, not a valid driver project/workspace")]
+    WdkMetadataParse(#[from] wdk_build::metadata::TryFromCargoMetadataError),
+    #[error("Not a workspace member, working directory: {0}")]
+    NotAWorkspaceMember(PathBuf),
+    #[error("No driver packages found to deploy in: {0}")]
+    NoDriverPackages(PathBuf),
+    #[error("Missing driver package folder: {0}, run `cargo wdk build` before deploying")]
+    MissingDriverPackage(PathBuf),
+    #[error(transparent)]
+    FileIo(#[from] FileError),
+    #[error("Error enabling test signing using bcdedit")]
+    EnableTestSigningCommand(#[source] CommandError),
+    #[error("Error adding test certificate to the certificate stores using certutil")]
+    TrustTestCertCommand(#[source] CommandError),
+    #[error("Error installing driver package")]
+    DriverInstallCommand(#[source] CommandError),
+    #[error("Error restarting the test machine")]
+    RebootCommand(#[source] CommandError),
+}
+
+impl DeployActionError {
+    /// Returns the [`FailureCategory`] of the error
+    #[must_use]
+    pub const fn category(&self) -> FailureCategory {
+        match self {
+            Self::WdkBuildConfig(_) => FailureCategory::EnvironmentMissing,
+            Self::CargoMetadataParse(_)
+            | Self::WdkMetadataParse(_)
+            | Self::NotAWorkspaceMember(_)
+            | Self::NoDriverPackages(_) => FailureCategory::MetadataInvalid,
+            Self::MissingDriverPackage(_) => FailureCategory::Packaging,
+            Self::EnableTestSigningCommand(_) | Self::TrustTestCertCommand(_) => {
+                FailureCategory::Signing
+            }
+            Self::FileIo(_) | Self::DriverInstallCommand(_) | Self::RebootCommand(_) => {
+                FailureCategory::Other
+            }
+        }
+    }
+}
@@ -0,0 +1,792 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+//! This module contains the `DeployAction` struct and its associated methods
+//! for deploying driver packages built by `cargo wdk build` to a test machine.
+//! The test machine is either the local machine or a remote machine reachable
+//! through its administrative share and `PowerShell` remoting. Deploying
+//! optionally enables test signing on the test machine, then installs the
+//! driver package using `pnputil` or, for root enumerated devices, `devcon`,
+//! and optionally restarts the test machine.
+
+mod error;
+
+use std::{
+    path::{Path, PathBuf, absolute},
+    process::Output,
+};
+
+use anyhow::Result;
+use cargo_metadata::{Metadata as CargoMetadata, Package, TargetKind};
+pub use error::DeployActionError;
+use mockall_double::double;
+use tracing::{debug, info, warn};
+use wdk_build::{
+    CpuArchitecture,
+    DriverConfig,
+    metadata::{DeployConfig, PackageConfig, Wdk},
+};
+
+#[double]
+use crate::providers::{exec::CommandExec, fs::Fs, metadata::Metadata};
+use crate::{
+    actions::{Profile, get_target_dir},
+    providers::error::CommandError,
+};
+
+/// Administrative share on a remote test machine that driver packages are
+/// copied to
+const REMOTE_DEPLOY_SHARE: &str = r"C$\DriverTest";
+/// Path of [`REMOTE_DEPLOY_SHARE`] on the remote test machine
+const REMOTE_DEPLOY_DIR: &str = r"C:\DriverTest";
+/// Name of the test certificate that `cargo wdk build` signs driver packages
+/// with
+const WDR_LOCAL_TEST_CERT: &str = "WDRLocalTestCert";
+
+pub struct DeployActionParams<'a> {
+    pub working_dir: &'a Path,
+    pub profile: Option<&'a Profile>,
+    pub target_arch: Option<CpuArchitecture>,
+    pub target_machine: Option<&'a str>,
+    pub hardware_id: Option<&'a str>,
+    pub enable_test_signing: bool,
+    pub reboot: bool,
+}
+
+/// Action that deploys the driver packages of a driver project or workspace to
+/// a test machine. The driver packages must have been built with
+/// `cargo wdk build` using the same profile and target architecture.
+pub struct DeployAction<'a> {
+    working_dir: PathBuf,
+    profile: Option<&'a Profile>,
+    target_arch: Option<CpuArchitecture>,
+    target_machine: Option<&'a str>,
+    hardware_id: Option<&'a str>,
+    enable_test_signing: bool,
+    reboot: bool,
+
+    // Injected deps
+    command_exec: &'a CommandExec,
+    fs: &'a Fs,
+    metadata: &'a Metadata,
+}
+
+impl<'a> DeployAction<'a> {
+    /// Creates a new instance of `DeployAction`.
+    ///
+    /// # Arguments:
+    /// * `params` - The `DeployActionParams` struct containing the parameters
+    ///   for the deploy action. Parameters that are set take precedence over
+    ///   the `metadata.wdk.deploy` section of `Cargo.toml`
+    /// * `command_exec` - The command execution provider instance
+    /// * `fs` - The file system provider instance
+    /// * `metadata` - The metadata provider instance
+    ///
+    /// # Returns
+    /// * `Result<Self>` - A result containing either a new instance of
+    ///   `DeployAction` on success, or an `anyhow::Error`.
+    ///
+    /// # Errors
+    /// * [`anyhow::Error`] -  If `params.working_dir` is not a syntactically
+    ///   valid path, e.g. it is empty
+    pub fn new(
+        params: &DeployActionParams<'a>,
+        command_exec: &'a CommandExec,
+        fs: &'a Fs,
+        metadata: &'a Metadata,
+    ) -> Result<Self> {
+        Ok(Self {
+            working_dir: absolute(params.working_dir)?,
+            profile: params.profile,
+            target_arch: params.target_arch,
+            target_machine: params.target_machine,
+            hardware_id: params.hardware_id,
+            enable_test_signing: params.enable_test_signing,
+            reboot: params.reboot,
+            command_exec,
+            fs,
+            metadata,
+        })
+    }
+
+    /// Entry point method to execute the deploy action flow.
+    ///
+    /// # Returns
+    /// `Result<(), DeployActionError>`
+    ///
+    /// # Errors
+    /// * `DeployActionError::CargoMetadataParse` - If there is an error parsing
+    ///   the Cargo metadata.
+    /// * `DeployActionError::WdkMetadataParse` - If there is an error parsing
+    ///   the WDK metadata.
+    /// * `DeployActionError::NotAWorkspaceMember` - If the working directory is
+    ///   not a workspace member.
+    /// * `DeployActionError::NoDriverPackages` - If there are no driver
+    ///   packages to deploy.
+    /// * `DeployActionError::MissingDriverPackage` - If a driver package has
+    ///   not been built.
+    /// * `DeployActionError::FileIo` - If there is an error copying a driver
+    ///   package to the remote test machine.
+    /// * `DeployActionError::EnableTestSigningCommand` - If there is an error
+    ///   enabling test signing.
+    /// * `DeployActionError::TrustTestCertCommand` - If there is an error
+    ///   adding the test certificate to the certificate stores.
+    /// * `DeployActionError::WdkBuildConfig` - If the WDK tools required to
+    ///   install the driver package cannot be found.
+    /// * `DeployActionError::DriverInstallCommand` - If there is an error
+    ///   installing a driver package.
+    /// * `DeployActionError::RebootCommand` - If there is an error restarting
+    ///   the test machine.
+    pub fn run(&self) -> Result<(), DeployActionError> {
+        debug!(
+            "Initialized deploy for project at: {}",
+            self.working_dir.display()
+        );
+        let cargo_metadata = self
+            .metadata
+            .get_cargo_metadata_at_path(&self.working_dir)?;
+        let wdk_metadata = Wdk::try_from(&cargo_metadata)?;
+        let deploy_config = self.resolve_deploy_config(wdk_metadata.deploy.unwrap_or_default());
+        let cert_file_name = get_cert_file_name(wdk_metadata.package.as_ref());
+        let target_machine = deploy_config.target_machine.as_deref();
+        let target_machine_name = target_machine.unwrap_or("the local machine");
+        let package_names = self.get_driver_package_names(&cargo_metadata)?;
+        let target_dir = get_target_dir(
+            cargo_metadata.target_directory.as_std_path(),
+            self.profile,
+            self.target_arch,
+        );
+
+        if deploy_config.enable_test_signing {
+            info!("Enabling test signing on {target_machine_name}");
+            self.run_on_target_machine(target_machine, "bcdedit", &["/set", "testsigning", "on"])
+                .map_err(DeployActionError::EnableTestSigningCommand)?;
+        }
+
+        for package_name in package_names {
+            info!("Deploying package {package_name} to {target_machine_name}");
+            self.deploy_package(
+                &package_name,
+                &target_dir,
+                &wdk_metadata.driver_model,
+                &deploy_config,
+                cert_file_name.as_deref(),
+            )?;
+            info!("Finished deploying {package_name}");
+        }
+
+        if deploy_config.reboot {
+            info!("Restarting {target_machine_name}");
+            self.run_on_target_machine(target_machine, "shutdown", &["/r", "/t", "0"])
+                .map_err(DeployActionError::RebootCommand)?;
+        } else if deploy_config.enable_test_signing {
+            warn!(
+                "Test signing was enabled on {target_machine_name}. It must be restarted before \
+                 test signed drivers can load"
+            );
+        }
+        Ok(())
+    }
+
+    /// Merges the parameters of the action with the deploy metadata. Parameters
+    /// that are set take precedence over the metadata.
+    fn resolve_deploy_config(&self, deploy_config: DeployConfig) -> DeployConfig {
+        DeployConfig {
+            target_machine: self
+                .target_machine
+                .map(ToString::to_string)
+                .or(deploy_config.target_machine),
+            hardware_id: self
+                .hardware_id
+                .map(ToString::to_string)
+                .or(deploy_config.hardware_id),
+            enable_test_signing: self.enable_test_signing || deploy_config.enable_test_signing,
+            reboot: self.reboot || deploy_config.reboot,
+        }
+    }
+
+    /// Returns the names of the driver packages to deploy. These are all the
+    /// driver packages of the workspace when running from the workspace root,
+    /// or the package of the workspace member the action is run from.
+    fn get_driver_package_names(
+        &self,
+        cargo_metadata: &CargoMetadata,
+    ) -> Result<Vec<String>, DeployActionError> {
+        let is_driver_package = |package: &Package| {
+            package.metadata.get("wdk").is_some()
+                && package
+                    .targets
+                    .iter()
+                    .any(|t| t.kind.contains(&TargetKind::CDyLib))
+        };
+        let workspace_packages = cargo_metadata.workspace_packages();
+
+        let driver_packages = if cargo_metadata.workspace_root.as_std_path() == self.working_dir {
+            workspace_packages
+                .into_iter()
+                .filter(|package| is_driver_package(package))
+                .collect::<Vec<_>>()
+        } else {
+            let package = workspace_packages
+                .into_iter()
+                .find(|package| {
+                    package
+                        .manifest_path
+                        .parent()
+                        .is_some_and(|p| p.as_std_path() == self.working_dir)
+                })
+                .ok_or_else(|| DeployActionError::NotAWorkspaceMember(self.working_dir.clone()))?;
+            if is_driver_package(package) {
+                vec![package]
+            } else {
+                vec![]
+            }
+        };
+
+        if driver_packages.is_empty() {
+            return Err(DeployActionError::NoDriverPackages(
+                self.working_dir.clone(),
+            ));
+        }
+        Ok(driver_packages
+            .into_iter()
+            .map(|package| package.name.clone())
+            .collect())
+    }
+
+    /// Deploys a single driver package to the test machine
+    fn deploy_package(
+        &self,
+        package_name: &str,
+        target_dir: &Path,
+        driver_model: &DriverConfig,
+        deploy_config: &DeployConfig,
+        cert_file_name: Option<&str>,
+    ) -> Result<(), DeployActionError> {
+        let package_name = package_name.replace('-', "_");
+        let package_dir = target_dir.join(format!("{package_name}_package"));
+        if !self.fs.exists(&package_dir) {
+            return Err(DeployActionError::MissingDriverPackage(package_dir));
+        }
+
+        let target_machine = deploy_config.target_machine.as_deref();
+        let install_dir = match target_machine {
+            Some(machine) => self.copy_package_to_remote_machine(
+                machine,
+                &package_name,
+                &package_dir,
+                driver_model,
+                cert_file_name,
+            )?,
+            None => package_dir,
+        };
+
+        match (deploy_config.enable_test_signing, cert_file_name) {
+            (true, Some(cert_file_name)) => {
+                debug!("Adding the test certificate to the certificate stores");
+                let cert_file_path = install_dir.join(cert_file_name);
+                let cert_file_path = cert_file_path.to_string_lossy();
+                for store in ["root", "TrustedPublisher"] {
+                    self.run_on_target_machine(
+                        target_machine,
+                        "certutil",
+                        &["-addstore", "-f", store, &cert_file_path],
+                    )
+                    .map_err(DeployActionError::TrustTestCertCommand)?;
+                }
+            }
+            (true, None) => warn!(
+                "Driver package {package_name} is signed with a certificate file and contains no \
+                 certificate to trust. It must be trusted on the test machine manually"
+            ),
+            (false, _) => {}
+        }
+
+        let inf_file_path = install_dir.join(format!("{package_name}.inf"));
+        let inf_file_path = inf_file_path.to_string_lossy();
+        if let Some(hardware_id) = deploy_config.hardware_id.as_deref() {
+            debug!("Installing driver package on a root enumerated device using devcon");
+            if target_machine.is_none() {
+                // devcon ships with the WDK tools
+                wdk_build::cargo_make::setup_path()?;
+            }
+            self.run_on_target_machine(
+                target_machine,
+                "devcon",
+                &["install", &inf_file_path, hardware_id],
+            )
+        } else {
+            debug!("Installing driver package using pnputil");
+            self.run_on_target_machine(
+                target_machine,
+                "pnputil",
+                &["/add-driver", &inf_file_path, "/install"],
+            )
+        }
+        .map_err(DeployActionError::DriverInstallCommand)?;
+        Ok(())
+    }
+
+    /// Copies the files needed to install a driver package to the
+    /// administrative share of a remote test machine.
+    ///
+    /// # Returns
+    /// * `PathBuf` - The path of the copied driver package on the remote test
+    ///   machine
+    fn copy_package_to_remote_machine(
+        &self,
+        machine: &str,
+        package_name: &str,
+        package_dir: &Path,
+        driver_model: &DriverConfig,
+        cert_file_name: Option<&str>,
+    ) -> Result<PathBuf, DeployActionError> {
+        let package_dir_name = format!("{package_name}_package");
+        let remote_share_dir =
+            PathBuf::from(format!(r"\\{machine}\{REMOTE_DEPLOY_SHARE}")).join(&package_dir_name);
+        debug!(
+            "Copying driver package to remote machine at: {}",
+            remote_share_dir.display()
+        );
+        self.fs.create_dir_all(&remote_share_dir)?;
+
+        let driver_binary_extension = match driver_model {
+            DriverConfig::Kmdf(_) | DriverConfig::Wdm => "sys",
+            DriverConfig::Umdf(_) => "dll",
+        };
+        let file_names = [
+            format!("{package_name}.inf"),
+            format!("{package_name}.{driver_binary_extension}"),
+            format!("{package_name}.cat"),
+        ]
+        .into_iter()
+        .chain(cert_file_name.map(ToString::to_string));
+        for file_name in file_names {
+            self.fs.copy(
+                &package_dir.join(&file_name),
+                &remote_share_dir.join(&file_name),
+            )?;
+        }
+        Ok(PathBuf::from(REMOTE_DEPLOY_DIR).join(package_dir_name))
+    }
+
+    /// Runs a command on the test machine. Commands for a remote test machine
+    /// are run through `PowerShell` remoting, and the exit code of the remote
+    /// command is propagated.
+    fn run_on_target_machine(
+        &self,
+        target_machine: Option<&str>,
+        command: &str,
+        args: &[&str],
+    ) -> Result<Output, CommandError> {
+        let Some(machine) = target_machine else {
+            return self.command_exec.run(command, args, None, None);
+        };
+
+        let quote = |arg: &str| format!("'{}'", arg.replace('\'', "''"));
+        let quoted_args = args
+            .iter()
+            .map(|arg| quote(arg))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let script = format!(
+            "$exitCode = Invoke-Command -ComputerName {} -ScriptBlock {{ & {} {quoted_args} | \
+             Out-Host; $LASTEXITCODE }}; exit $exitCode",
+            quote(machine),
+            quote(command),
+        );
+        self.command_exec.run(
+            "powershell",
+            &["-NoProfile", "-NonInteractive", "-Command", &script],
+            None,
+            None,
+        )
+    }
+}
+
+/// Returns the name of the certificate file `cargo wdk build` adds to driver
+/// packages. Packages signed with a certificate file do not contain one.
+fn get_cert_file_name(package_config: Option<&PackageConfig>) -> Option<String> {
+    match package_config {
+        Some(PackageConfig {
+            cert_path: Some(_), ..
+        }) => None,
+        Some(PackageConfig {
+            cert_thumbprint: Some(cert_thumbprint),
+            ..
+        }) => Some(format!("{cert_thumbprint}.cer")),
+        Some(PackageConfig {
+            cert_name: Some(cert_name),
+            ..
+        }) => Some(format!("{cert_name}.cer")),
+        _ => Some(format!("{WDR_LOCAL_TEST_CERT}.cer")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        os::windows::process::ExitStatusExt,
+        path::{Path, PathBuf},
+        process::{ExitStatus, Output},
+    };
+
+    use mockall::Sequence;
+
+    use crate::{
+        actions::{
+            Profile,
+            deploy::{DeployAction, DeployActionError, DeployActionParams},
+        },
+        providers::{
+            error::CommandError,
+            exec::MockCommandExec,
+            fs::MockFs,
+            metadata::MockMetadata,
+        },
+    };
+
+    const DRIVER_NAME: &str = "sample-kmdf";
+
+    #[test]
+    fn given_a_built_driver_when_deploying_locally_then_pnputil_installs_the_package() {
+        let cwd = PathBuf::from(r"C:\tmp\sample-kmdf");
+        let package_dir = cwd
+            .join("target")
+            .join("release")
+            .join("sample_kmdf_package");
+        let inf_file_path = package_dir.join("sample_kmdf.inf");
+
+        let mut mock_exec = MockCommandExec::new();
+        mock_exec
+            .expect_run()
+            .withf(move |command, args, _, _| {
+                command == "pnputil"
+                    && args
+                        == [
+                            "/add-driver",
+                            inf_file_path.to_string_lossy().as_ref(),
+                            "/install",
+                        ]
+            })
+            .once()
+            .returning(|_, _, _, _| Ok(success_output()));
+
+        let result = run_deploy_action(
+            &cwd,
+            Some(&Profile::Release),
+            None,
+            None,
+            &mock_exec,
+            &mock_fs_with_package_dir(package_dir, true),
+        );
+
+        assert!(result.is_ok(), "deploy failed unexpectedly: {result:?}");
+    }
+
+    #[test]
+    fn given_test_signing_is_enabled_when_deploying_locally_then_test_cert_is_trusted() {
+        let cwd = PathBuf::from(r"C:\tmp\sample-kmdf");
+        let package_dir = cwd.join("target").join("debug").join("sample_kmdf_package");
+        let cert_file_path = package_dir.join("WDRLocalTestCert.cer");
+
+        let mut mock_exec = MockCommandExec::new();
+        mock_exec
+            .expect_run()
+            .withf(|command, args, _, _| {
+                command == "bcdedit" && args == ["/set", "testsigning", "on"]
+            })
+            .once()
+            .returning(|_, _, _, _| Ok(success_output()));
+        for store in ["root", "TrustedPublisher"] {
+            let cert_file_path = cert_file_path.clone();
+            mock_exec
+                .expect_run()
+                .withf(move |command, args, _, _| {
+                    command == "certutil"
+                        && args
+                            == [
+                                "-addstore",
+                                "-f",
+                                store,
+                                cert_file_path.to_string_lossy().as_ref(),
+                            ]
+                })
+                .once()
+                .returning(|_, _, _, _| Ok(success_output()));
+        }
+        mock_exec
+            .expect_run()
+            .withf(|command, _, _, _| command == "pnputil")
+            .once()
+            .returning(|_, _, _, _| Ok(success_output()));
+
+        let result = run_deploy_action(
+            &cwd,
+            None,
+            None,
+            Some(true),
+            &mock_exec,
+            &mock_fs_with_package_dir(package_dir, true),
+        );
+
+        assert!(result.is_ok(), "deploy failed unexpectedly: {result:?}");
+    }
+
+    #[test]
+    fn given_a_target_machine_in_metadata_when_deploying_then_package_is_installed_remotely() {
+        let cwd = PathBuf::from(r"C:\tmp\sample-kmdf");
+        let package_dir = cwd.join("target").join("debug").join("sample_kmdf_package");
+        let remote_share_dir = PathBuf::from(r"\\test-machine\C$\DriverTest\sample_kmdf_package");
+
+        let mut mock_fs = mock_fs_with_package_dir(package_dir.clone(), true);
+        let expected_remote_share_dir = remote_share_dir.clone();
+        mock_fs
+            .expect_create_dir_all()
+            .withf(move |path| path == expected_remote_share_dir)
+            .once()
+            .returning(|_| Ok(()));
+        for file_name in [
+            "sample_kmdf.inf",
+            "sample_kmdf.sys",
+            "sample_kmdf.cat",
+            "WDRLocalTestCert.cer",
+        ] {
+            let src = package_dir.join(file_name);
+            let dest = remote_share_dir.join(file_name);
+            mock_fs
+                .expect_copy()
+                .withf(move |s, d| s == src && d == dest)
+                .once()
+                .returning(|_, _| Ok(0));
+        }
+
+        let mut mock_exec = MockCommandExec::new();
+        mock_exec
+            .expect_run()
+            .withf(|command, args, _, _| {
+                command == "powershell"
+                    && args.last().is_some_and(|script| {
+                        script.contains("Invoke-Command -ComputerName 'test-machine'")
+                            && script.contains(
+                                r"& 'pnputil' '/add-driver' 'C:\DriverTest\sample_kmdf_package\sample_kmdf.inf' '/install'",
+                            )
+                    })
+            })
+            .once()
+            .returning(|_, _, _, _| Ok(success_output()));
+
+        let result = run_deploy_action(
+            &cwd,
+            None,
+            Some(r#"{ "target-machine": "test-machine" }"#),
+            None,
+            &mock_exec,
+            &mock_fs,
+        );
+
+        assert!(result.is_ok(), "deploy failed unexpectedly: {result:?}");
+    }
+
+    #[test]
+    fn given_reboot_in_metadata_when_deploying_then_target_machine_is_restarted_after_install() {
+        let cwd = PathBuf::from(r"C:\tmp\sample-kmdf");
+        let package_dir = cwd.join("target").join("debug").join("sample_kmdf_package");
+
+        let mut sequence = Sequence::new();
+        let mut mock_exec = MockCommandExec::new();
+        mock_exec
+            .expect_run()
+            .withf(|command, _, _, _| command == "pnputil")
+            .once()
+            .in_sequence(&mut sequence)
+            .returning(|_, _, _, _| Ok(success_output()));
+        mock_exec
+            .expect_run()
+            .withf(|command, args, _, _| command == "shutdown" && args == ["/r", "/t", "0"])
+            .once()
+            .in_sequence(&mut sequence)
+            .returning(|_, _, _, _| Ok(success_output()));
+
+        let result = run_deploy_action(
+            &cwd,
+            None,
+            Some(r#"{ "reboot": true }"#),
+            None,
+            &mock_exec,
+            &mock_fs_with_package_dir(package_dir, true),
+        );
+
+        assert!(result.is_ok(), "deploy failed unexpectedly: {result:?}");
+    }
+
+    #[test]
+    fn given_a_driver_that_is_not_built_when_deploying_then_missing_driver_package_is_returned() {
+        let cwd = PathBuf::from(r"C:\tmp\sample-kmdf");
+        let package_dir = cwd.join("target").join("debug").join("sample_kmdf_package");
+
+        let result = run_deploy_action(
+            &cwd,
+            None,
+            None,
+            None,
+            &MockCommandExec::new(),
+            &mock_fs_with_package_dir(package_dir, false),
+        );
+
+        assert!(matches!(
+            result,
+            Err(DeployActionError::MissingDriverPackage(_))
+        ));
+    }
+
+    #[test]
+    fn given_pnputil_fails_when_deploying_then_driver_install_command_error_is_returned() {
+        let cwd = PathBuf::from(r"C:\tmp\sample-kmdf");
+        let package_dir = cwd.join("target").join("debug").join("sample_kmdf_package");
+
+        let mut mock_exec = MockCommandExec::new();
+        mock_exec
+            .expect_run()
+            .once()
+            .returning(|command, args, _, _| {
+                Err(CommandError::from_output(
+                    command,
+                    args,
+                    &Output {
+                        status: ExitStatus::from_raw(1),
+                        stdout: vec![],
+                        stderr: vec![],
+                    },
+                ))
+            });
+
+        let result = run_deploy_action(
+            &cwd,
+            None,
+            None,
+            None,
+            &mock_exec,
+            &mock_fs_with_package_dir(package_dir, true),
+        );
+
+        assert!(matches!(
+            result,
+            Err(DeployActionError::DriverInstallCommand(_))
+        ));
+    }
+
+    fn run_deploy_action(
+        cwd: &Path,
+        profile: Option<&Profile>,
+        deploy_metadata: Option<&str>,
+        enable_test_signing: Option<bool>,
+        mock_exec: &MockCommandExec,
+        mock_fs: &MockFs,
+    ) -> Result<(), DeployActionError> {
+        let cargo_metadata = get_cargo_metadata(cwd, deploy_metadata);
+        let mut mock_metadata = MockMetadata::new();
+        mock_metadata
+            .expect_get_cargo_metadata_at_path()
+            .returning(move |_| {
+                Ok(serde_json::from_str(&cargo_metadata).expect("invalid cargo metadata"))
+            });
+
+        DeployAction::new(
+            &DeployActionParams {
+                working_dir: cwd,
+                profile,
+                target_arch: None,
+                target_machine: None,
+                hardware_id: None,
+                enable_test_signing: enable_test_signing.unwrap_or_default(),
+                reboot: false,
+            },
+            mock_exec,
+            mock_fs,
+            &mock_metadata,
+        )
+        .expect("Failed to init deploy action")
+        .run()
+    }
+
+    fn mock_fs_with_package_dir(package_dir: PathBuf, exists: bool) -> MockFs {
+        let mut mock_fs = MockFs::new();
+        mock_fs
+            .expect_exists()
+            .withf(move |path| path == package_dir)
+            .returning(move |_| exists);
+        mock_fs
+    }
+
+    fn success_output() -> Output {
+        Output {
+            status: ExitStatus::default(),
+            stdout: vec![],
+            stderr: vec![],
+        }
+    }
+
+    fn get_cargo_metadata(root_dir: &Path, deploy_metadata: Option<&str>) -> String {
+        let normalized_root = root_dir.to_string_lossy().replace('\\', "/");
+        let package_id = format!("path+file:///{normalized_root}#{DRIVER_NAME}@0.0.1");
+        let deploy_section = deploy_metadata
+            .map(|deploy_metadata| format!(r#", "deploy": {deploy_metadata}"#))
+            .unwrap_or_default();
+        format!(
+            r#"
+            {{
+                "target_directory": "{target_directory}",
+                "workspace_root": "{workspace_root}",
+                "packages": [
+                    {{
+                        "name": "{DRIVER_NAME}",
+                        "version": "0.0.1",
+                        "id": "{package_id}",
+                        "dependencies": [],
+                        "targets": [
+                            {{
+                                "kind": ["cdylib"],
+                                "crate_types": ["cdylib"],
+                                "name": "{DRIVER_NAME}",
+                                "src_path": "{src_path}",
+                                "edition": "2021",
+                                "doc": true,
+                                "doctest": false,
+                                "test": true
+                            }}
+                        ],
+                        "features": {{}},
+                        "manifest_path": "{manifest_path}",
+                        "authors": [],
+                        "categories": [],
+                        "keywords": [],
+                        "edition": "2021",
+                        "metadata": {{
+                            "wdk": {{
+                                "driver-model": {{
+                                    "driver-type": "KMDF",
+                                    "kmdf-version-major": 1,
+                                    "target-kmdf-version-minor": 33
+                                }}{deploy_section}
+                            }}
+                        }}
+                    }}
+                ],
+                "workspace_members": ["{package_id}"],
+                "metadata": null,
+                "version": 1
+            }}"#,
+            target_directory = root_dir.join("target").to_string_lossy().escape_default(),
+            workspace_root = root_dir.to_string_lossy().escape_default(),
+            src_path = root_dir
+                .join("src")
+                .join("lib.rs")
+                .to_string_lossy()
+                .escape_default(),
+            manifest_path = root_dir
+                .join("Cargo.toml")
+                .to_string_lossy()
+                .escape_default(),
+        )
+    }
+}
@@ -6,19 +6,34 @@
 //! business logic of the cargo-wdk utility are:
 //! * `new` - New action module
 //! * `build` - Build action module
+//! * `deploy` - Deploy action module
+//! * `clean` - Clean action module
+//! * `sign` - Sign action module
+//! * `self_test` - Self test action module
 pub mod build;
+pub mod clean;
+pub mod deploy;
 pub mod new;
+pub mod self_test;
+pub mod sign;
 
 use std::{
     fmt::{self, Display},
+    path::{Path, PathBuf},
     str::FromStr,
 };
 
-use wdk_build::CpuArchitecture;
+use wdk_build::{CpuArchitecture, metadata::PackageConfig};
 
 pub const KMDF_STR: &str = "kmdf";
 pub const UMDF_STR: &str = "umdf";
 pub const WDM_STR: &str = "wdm";
+pub const FUNCTION_STR: &str = "function";
+pub const FILTER_STR: &str = "filter";
+pub const BUS_STR: &str = "bus";
+pub const HID_MINIDRIVER_STR: &str = "hid-minidriver";
+pub const INSTALLED_STR: &str = "installed";
+pub const NUGET_STR: &str = "nuget";
 /// `x86_64/Amd64` target triple name
 const X86_64_TARGET_TRIPLE_NAME: &str = "x86_64-pc-windows-msvc";
 /// `aarch64/Arm64` target triple name
@@ -59,6 +74,121 @@ pub fn to_target_triple(cpu_arch: CpuArchitecture) -> String {
     }
 }
 
+/// Enum of sources of the WDK used to build and package drivers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WdkSource {
+    /// WDK installed on the system or provided by an eWDK environment
+    Installed,
+    /// WDK restored from `NuGet` packages listed in `packages.config`
+    Nuget,
+}
+
+impl FromStr for WdkSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            INSTALLED_STR => Ok(Self::Installed),
+            NUGET_STR => Ok(Self::Nuget),
+            _ => Err(format!("'{s}' is not a valid WDK source")),
+        }
+    }
+}
+
+impl Display for WdkSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Installed => INSTALLED_STR,
+            Self::Nuget => NUGET_STR,
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Returns the directory cargo emits the artifacts of a build to, given the
+/// `target_directory` from cargo metadata, the profile and the target
+/// architecture of the build.
+#[must_use]
+pub fn get_target_dir(
+    target_directory: &Path,
+    profile: Option<&Profile>,
+    target_arch: Option<CpuArchitecture>,
+) -> PathBuf {
+    let mut target_dir = target_directory.to_path_buf();
+    if let Some(target_arch) = target_arch {
+        target_dir.push(to_target_triple(target_arch));
+    }
+    target_dir.push(match profile {
+        Some(Profile::Release) => "release",
+        Some(Profile::Dev) | None => "debug",
+    });
+    target_dir
+}
+
+/// Signing parameters of `cargo wdk build` and `cargo wdk sign` that override
+/// the signing settings of the package metadata.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SigningOverrides<'a> {
+    pub cert_store: Option<&'a str>,
+    pub cert_name: Option<&'a str>,
+    pub cert_path: Option<&'a Path>,
+    pub cert_thumbprint: Option<&'a str>,
+    pub csp: Option<&'a str>,
+    pub key_container: Option<&'a str>,
+    pub digest_algorithm: Option<&'a str>,
+}
+
+impl SigningOverrides<'_> {
+    /// Returns whether any parameter selecting the certificate is set
+    const fn selects_cert(&self) -> bool {
+        self.cert_store.is_some()
+            || self.cert_name.is_some()
+            || self.cert_path.is_some()
+            || self.cert_thumbprint.is_some()
+            || self.csp.is_some()
+            || self.key_container.is_some()
+    }
+}
+
+/// Merges the signing parameters of an action with the package metadata of a
+/// driver package. If any parameter selecting the certificate is set, the
+/// certificate settings of the metadata are ignored so that they cannot
+/// conflict with each other. The digest algorithm parameter overrides the one
+/// of the metadata on its own. Relative paths in the metadata are resolved
+/// against `manifest_dir`, the directory of the package.
+#[must_use]
+pub fn resolve_package_config(
+    package_config: PackageConfig,
+    overrides: &SigningOverrides<'_>,
+    manifest_dir: &Path,
+) -> PackageConfig {
+    let package_config = PackageConfig {
+        inf: package_config.inf.map(|inf| manifest_dir.join(inf)),
+        digest_algorithm: overrides
+            .digest_algorithm
+            .map(ToString::to_string)
+            .or(package_config.digest_algorithm),
+        ..package_config
+    };
+    if overrides.selects_cert() {
+        return PackageConfig {
+            cert_store: overrides.cert_store.map(ToString::to_string),
+            cert_name: overrides.cert_name.map(ToString::to_string),
+            cert_thumbprint: overrides.cert_thumbprint.map(ToString::to_string),
+            cert_path: overrides.cert_path.map(Path::to_path_buf),
+            csp: overrides.csp.map(ToString::to_string),
+            key_container: overrides.key_container.map(ToString::to_string),
+            ..package_config
+        };
+    }
+    PackageConfig {
+        cert_path: package_config
+            .cert_path
+            .map(|cert_path| manifest_dir.join(cert_path)),
+        ..package_config
+    }
+}
+
 /// Enum of driver types.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DriverType {
@@ -90,3 +220,125 @@ fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{s}")
     }
 }
+
+/// Enum of driver project templates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriverTemplate {
+    Function,
+    Filter,
+    Bus,
+    HidMinidriver,
+}
+
+impl FromStr for DriverTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            FUNCTION_STR => Ok(Self::Function),
+            FILTER_STR => Ok(Self::Filter),
+            BUS_STR => Ok(Self::Bus),
+            HID_MINIDRIVER_STR => Ok(Self::HidMinidriver),
+            _ => Err(format!("'{s}' is not a valid driver template")),
+        }
+    }
+}
+
+impl Display for DriverTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Function => FUNCTION_STR,
+            Self::Filter => FILTER_STR,
+            Self::Bus => BUS_STR,
+            Self::HidMinidriver => HID_MINIDRIVER_STR,
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Broad category of a failed action. Each category maps to a stable process
+/// exit code so that CI pipelines can branch on the kind of failure (ex. retry
+/// only signing failures).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureCategory {
+    /// Failure that does not fall in any of the other categories
+    Other,
+    /// A cargo command building or creating a driver project failed (`cargo
+    /// build`, `cargo new`, or `cargo check` during the self test)
+    Build,
+    /// Creating the driver package failed (stampinf, inf2cat, infverif, file
+    /// copies)
+    Packaging,
+    /// Generating the test certificate or signing/verifying the driver failed
+    Signing,
+    /// The WDK or one of its tools could not be found
+    EnvironmentMissing,
+    /// Cargo or WDK metadata of the project is invalid
+    MetadataInvalid,
+}
+
+impl FailureCategory {
+    /// Returns the process exit code for the category. Exit code 2 is skipped
+    /// since it is used by clap for argument parsing errors.
+    #[must_use]
+    pub const fn exit_code(self) -> u8 {
+        match self {
+            Self::Other => 1,
+            Self::Build => 3,
+            Self::Packaging => 4,
+            Self::Signing => 5,
+            Self::EnvironmentMissing => 6,
+            Self::MetadataInvalid => 7,
+        }
+    }
+
+    /// Returns the category of an error returned by an action. Errors not
+    /// originating from an action are categorized as
+    /// [`FailureCategory::Other`].
+    #[must_use]
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        error
+            .downcast_ref::<build::BuildActionError>()
+            .map(build::BuildActionError::category)
+            .or_else(|| {
+                error
+                    .downcast_ref::<deploy::DeployActionError>()
+                    .map(deploy::DeployActionError::category)
+            })
+            .or_else(|| {
+                error
+                    .downcast_ref::<clean::CleanActionError>()
+                    .map(clean::CleanActionError::category)
+            })
+            .or_else(|| {
+                error
+                    .downcast_ref::<sign::SignActionError>()
+                    .map(sign::SignActionError::category)
+            })
+            .or_else(|| {
+                error
+                    .downcast_ref::<new::NewActionError>()
+                    .map(new::NewActionError::category)
+            })
+            .or_else(|| {
+                error
+                    .downcast_ref::<self_test::SelfTestActionError>()
+                    .map(self_test::SelfTestActionError::category)
+            })
+            .unwrap_or(Self::Other)
+    }
+}
+
+impl Display for FailureCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Other => "other",
+            Self::Build => "build",
+            Self::Packaging => "packaging",
+            Self::Signing => "signing",
+            Self::EnvironmentMissing => "environment_missing",
+            Self::MetadataInvalid => "metadata_invalid",
+        };
+        write!(f, "{s}")
+    }
+}
@@ -3,7 +3,10 @@
 //! This module defines error types for new action module.
 use thiserror::Error;
 
-use crate::providers::error::{CommandError, FileError};
+use crate::{
+    actions::FailureCategory,
+    providers::error::{CommandError, FileError},
+};
 
 /// Errors for the new action layer
 #[derive(Debug, Error)]
@@ -12,6 +15,8 @@ pub enum NewActionError {
     CargoNewCommand(#[from] CommandError),
     #[error(transparent)]
     FileSystem(#[from] FileError),
+    #[error("The {0} template is not available for {1} drivers")]
+    UnsupportedTemplate(String, String),
     #[error("Template file not found: {0}")]
     TemplateNotFound(String),
     #[error("Unable to derive driver crate name from the provided path: {0}")]
@@ -19,3 +24,18 @@ pub enum NewActionError {
     #[error(transparent)]
     Io(#[from] std::io::Error),
 }
+
+impl NewActionError {
+    /// Returns the [`FailureCategory`] of the error
+    #[must_use]
+    pub const fn category(&self) -> FailureCategory {
+        match self {
+            Self::CargoNewCommand(_) => FailureCategory::Build,
+            Self::FileSystem(_)
+            | Self::UnsupportedTemplate(..)
+            | Self::TemplateNotFound(_)
+            | Self::InvalidDriverCrateName(_)
+            | Self::Io(_) => FailureCategory::Other,
+        }
+    }
+}
@@ -0,0 +1,116 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+//! This module defines the `InterfaceGuid` type, the device interface GUID
+//! that `cargo wdk new` embeds in a new driver project and its test client.
+
+use std::{
+    fmt::{self, Display},
+    hash::{BuildHasher, Hasher, RandomState},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Mask of the version bits, the high nibble of the third group of a GUID
+const VERSION_MASK: u128 = 0xF << 76;
+/// Version 4 (random) GUID
+const VERSION_4: u128 = 0x4 << 76;
+/// Mask of the variant bits, the two high bits of the fourth group of a GUID
+const VARIANT_MASK: u128 = 0b11 << 62;
+/// RFC 4122 variant
+const VARIANT_RFC_4122: u128 = 0b10 << 62;
+
+/// Device interface GUID of a driver project, stored in the big-endian order
+/// it is written in, ex. `{6B4E9A0C-1D2F-4A3B-8C5D-7E6F8091A2B3}`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterfaceGuid(u128);
+
+impl InterfaceGuid {
+    /// Creates an `InterfaceGuid` from its value
+    pub const fn from_u128(value: u128) -> Self {
+        Self(value)
+    }
+
+    /// Generates a new random (version 4) GUID.
+    ///
+    /// The random bits come from the randomly keyed hasher of the standard
+    /// library, which is seeded by the operating system. This is not suitable
+    /// for cryptographic use, but unique enough for a device interface.
+    pub fn generate() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let random_u64 = |seed: u128| {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u128(seed);
+            u128::from(hasher.finish())
+        };
+        let value = (random_u64(nanos) << 64) | random_u64(!nanos);
+        Self((value & !VERSION_MASK & !VARIANT_MASK) | VERSION_4 | VARIANT_RFC_4122)
+    }
+
+    /// Returns the value of the GUID
+    pub const fn as_u128(self) -> u128 {
+        self.0
+    }
+
+    /// Returns the GUID as a hexadecimal Rust integer literal, grouped like
+    /// its registry format, ex. `0x6B4E9A0C_1D2F_4A3B_8C5D_7E6F8091A2B3`
+    pub fn to_u128_literal(self) -> String {
+        let [data1, data2, data3, data4, data5] = self.groups();
+        format!("0x{data1:08X}_{data2:04X}_{data3:04X}_{data4:04X}_{data5:012X}")
+    }
+
+    fn groups(self) -> [u128; 5] {
+        [
+            self.0 >> 96,
+            (self.0 >> 80) & 0xFFFF,
+            (self.0 >> 64) & 0xFFFF,
+            (self.0 >> 48) & 0xFFFF,
+            self.0 & 0xFFFF_FFFF_FFFF,
+        ]
+    }
+}
+
+/// Formats the GUID in its registry format, ex.
+/// `{6B4E9A0C-1D2F-4A3B-8C5D-7E6F8091A2B3}`
+impl Display for InterfaceGuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [data1, data2, data3, data4, data5] = self.groups();
+        write!(
+            f,
+            "{{{data1:08X}-{data2:04X}-{data3:04X}-{data4:04X}-{data5:012X}}}"
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUID: InterfaceGuid = InterfaceGuid::from_u128(0x0B4E_9A0C_1D2F_4A3B_8C5D_7E6F_8091_A2B3);
+
+    #[test]
+    fn display_formats_guid_in_registry_format() {
+        assert_eq!(GUID.to_string(), "{0B4E9A0C-1D2F-4A3B-8C5D-7E6F8091A2B3}");
+    }
+
+    #[test]
+    fn to_u128_literal_groups_digits_like_registry_format() {
+        assert_eq!(
+            GUID.to_u128_literal(),
+            "0x0B4E9A0C_1D2F_4A3B_8C5D_7E6F8091A2B3"
+        );
+    }
+
+    #[test]
+    fn generated_guids_are_version_4_and_unique() {
+        let first = InterfaceGuid::generate();
+        let second = InterfaceGuid::generate();
+
+        for guid in [first, second] {
+            assert_eq!(guid.as_u128() & VERSION_MASK, VERSION_4);
+            assert_eq!(guid.as_u128() & VARIANT_MASK, VARIANT_RFC_4122);
+        }
+        assert_ne!(first, second);
+    }
+}
@@ -7,27 +7,46 @@
 //! and uses the pre-defined templates to setup the new project with the
 //! necessary files and configurations.
 mod error;
+mod interface_guid;
 
 use std::path::{Path, PathBuf};
 
 use clap_verbosity_flag::Verbosity;
-use error::NewActionError;
+pub use error::NewActionError;
 use include_dir::{Dir, include_dir};
+pub use interface_guid::InterfaceGuid;
 use mockall_double::double;
 use tracing::{debug, info};
 
 #[double]
 use crate::providers::{exec::CommandExec, fs::Fs};
-use crate::{actions::DriverType, trace};
+use crate::{
+    actions::{DriverTemplate, DriverType},
+    trace,
+};
 
 /// Directory containing the templates to be bundled with the utility
-static TEMPLATES_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/templates");
+pub static TEMPLATES_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/templates");
+/// Placeholder in the `.inx` templates that is substituted with the driver
+/// crate name
+pub const DRIVER_NAME_PLACEHOLDER: &str = "##driver_name_placeholder##";
+/// Placeholder in the templates that is substituted with the device interface
+/// GUID of the driver in registry format
+pub const INTERFACE_GUID_PLACEHOLDER: &str = "##interface_guid_placeholder##";
+/// Placeholder in the templates that is substituted with the device interface
+/// GUID of the driver as a `u128` literal
+pub const INTERFACE_GUID_VALUE_PLACEHOLDER: &str = "##interface_guid_value_placeholder##";
+/// Directory of the generated project that holds the usermode test client
+/// crate
+pub const TEST_CLIENT_DIR_NAME: &str = "test-client";
 
 /// `NewAction` struct and its methods orchestrates the creation of new driver
-/// project based on the specified driver type.
+/// project based on the specified driver type and template.
 pub struct NewAction<'a> {
     path: &'a Path,
     driver_type: DriverType,
+    template: DriverTemplate,
+    interface_guid: InterfaceGuid,
     verbosity_level: Verbosity,
     command_exec: &'a CommandExec,
     fs: &'a Fs,
@@ -41,6 +60,9 @@ impl<'a> NewAction<'a> {
     /// * `path` - The path to the new driver project. The last part of the path
     ///   is used as the package name.
     /// * `driver_type` - The type of the driver project to be created.
+    /// * `template` - The template the driver project is created from.
+    /// * `interface_guid` - The device interface GUID embedded in the driver
+    ///   and its test client.
     /// * `verbosity_level` - The verbosity level for logging.
     /// * `command_exec` - The provider for command execution.
     /// * `fs` - The provider for file system operations.
@@ -51,6 +73,8 @@ impl<'a> NewAction<'a> {
     pub const fn new(
         path: &'a Path,
         driver_type: DriverType,
+        template: DriverTemplate,
+        interface_guid: InterfaceGuid,
         verbosity_level: Verbosity,
         command_exec: &'a CommandExec,
         fs: &'a Fs,
@@ -58,6 +82,8 @@ pub const fn new(
         Self {
             path,
             driver_type,
+            template,
+            interface_guid,
             verbosity_level,
             command_exec,
             fs,
@@ -73,24 +99,38 @@ pub const fn new(
     ///
     /// # Errors
     ///
+    /// * `NewActionError::UnsupportedTemplate` - If the template is not
+    ///   available for the driver type.
     /// * `NewActionError::CargoNewCommand` - If there is an error running the
     ///   `cargo new` command.
     /// * `NewActionError::TemplateNotFound` - If a template file matching the
     ///   driver type is not found
+    /// * `NewActionError::InvalidDriverCrateName` - If the driver crate name
+    ///   cannot be derived from the path.
     /// * `NewActionError::FileSystem` - If there is an error with file system
     ///   operations.
     pub fn run(&self) -> Result<(), NewActionError> {
         info!(
-            "Trying to create new {} driver package at: {}",
+            "Trying to create new {} {} driver package at: {}",
             self.driver_type,
+            self.template,
             self.path.display()
         );
+        if !is_template_supported(self.driver_type, self.template) {
+            return Err(NewActionError::UnsupportedTemplate(
+                self.template.to_string(),
+                self.driver_type.to_string(),
+            ));
+        }
         self.run_cargo_new()?;
         self.copy_lib_rs_template()?;
         self.update_cargo_toml()?;
         self.create_inx_file()?;
         self.copy_build_rs_template()?;
         self.copy_cargo_config()?;
+        if self.template == DriverTemplate::Function {
+            self.create_test_client()?;
+        }
         info!(
             "New {} driver crate created successfully at: {}",
             self.driver_type,
@@ -142,13 +182,17 @@ pub fn copy_lib_rs_template(&self) -> Result<(), NewActionError> {
             "Copying lib.rs template for driver type: {}",
             self.driver_type.to_string()
         );
-        let template_path = PathBuf::from(&self.driver_type.to_string()).join("lib.rs.tmp");
+        let template_path = template_dir(self.driver_type, self.template).join("lib.rs.tmp");
         let template_file = TEMPLATES_DIR.get_file(&template_path).ok_or_else(|| {
             NewActionError::TemplateNotFound(template_path.to_string_lossy().into_owned())
         })?;
+        let lib_rs_content = substitute_interface_guid(
+            &String::from_utf8_lossy(template_file.contents()),
+            self.interface_guid,
+        );
         let lib_rs_path = self.path.join("src").join("lib.rs");
         self.fs
-            .write_to_file(&lib_rs_path, template_file.contents())?;
+            .write_to_file(&lib_rs_path, lib_rs_content.as_bytes())?;
         Ok(())
     }
 
@@ -230,26 +274,16 @@ pub fn update_cargo_toml(&self) -> Result<(), NewActionError> {
     /// * `NewActionError::FileSystem` - If there is an error writing .inx
     ///   template content to the destination .inx file.
     pub fn create_inx_file(&self) -> Result<(), NewActionError> {
-        let driver_crate_name = self
-            .path
-            .file_name()
-            .ok_or_else(|| {
-                NewActionError::InvalidDriverCrateName(self.path.to_string_lossy().into_owned())
-            })?
-            .to_string_lossy()
-            .to_string();
-        debug!("Creating .inx file for: {}", driver_crate_name);
-        let underscored_driver_crate_name = driver_crate_name.replace('-', "_");
+        let underscored_driver_crate_name = self.underscored_driver_crate_name()?;
+        debug!("Creating .inx file for: {}", underscored_driver_crate_name);
         let inx_template_path =
-            PathBuf::from(&self.driver_type.to_string()).join("driver_name.inx.tmp");
+            template_dir(self.driver_type, self.template).join("driver_name.inx.tmp");
         let inx_template_file = TEMPLATES_DIR.get_file(&inx_template_path).ok_or_else(|| {
             NewActionError::TemplateNotFound(inx_template_path.to_string_lossy().into_owned())
         })?;
         let inx_content = String::from_utf8_lossy(inx_template_file.contents()).to_string();
-        let substituted_inx_content = inx_content.replace(
-            "##driver_name_placeholder##",
-            &underscored_driver_crate_name,
-        );
+        let substituted_inx_content =
+            inx_content.replace(DRIVER_NAME_PLACEHOLDER, &underscored_driver_crate_name);
         let inx_output_path = self
             .path
             .join(format!("{underscored_driver_crate_name}.inx"));
@@ -288,6 +322,98 @@ pub fn copy_cargo_config(&self) -> Result<(), NewActionError> {
             .write_to_file(&cargo_config_path, cargo_config_template_file.contents())?;
         Ok(())
     }
+
+    /// Creates the usermode test client crate of the driver project in its
+    /// `test-client` directory. The client opens the devices that expose the
+    /// device interface GUID of the driver.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), NewActionError>` - A result indicating success or failure
+    ///   of the operation.
+    ///
+    /// # Errors
+    ///
+    /// * `NewActionError::InvalidDriverCrateName` - If the driver crate name
+    ///   cannot be derived from the path.
+    /// * `NewActionError::TemplateNotFound` - If a test client template file is
+    ///   not bundled with the utility.
+    /// * `NewActionError::FileSystem` - If there is an error creating the test
+    ///   client directory or writing its files.
+    pub fn create_test_client(&self) -> Result<(), NewActionError> {
+        let underscored_driver_crate_name = self.underscored_driver_crate_name()?;
+        debug!(
+            "Creating test client with interface GUID {} for: {}",
+            self.interface_guid, underscored_driver_crate_name
+        );
+        let test_client_dir = self.path.join(TEST_CLIENT_DIR_NAME);
+        self.fs.create_dir_all(&test_client_dir.join("src"))?;
+        let test_client_files = [
+            ("Cargo.toml.tmp", test_client_dir.join("Cargo.toml")),
+            ("main.rs.tmp", test_client_dir.join("src").join("main.rs")),
+        ];
+        for (template_file_name, output_path) in test_client_files {
+            let template_path = PathBuf::from(TEST_CLIENT_DIR_NAME).join(template_file_name);
+            let template_file = TEMPLATES_DIR.get_file(&template_path).ok_or_else(|| {
+                NewActionError::TemplateNotFound(template_path.to_string_lossy().into_owned())
+            })?;
+            let content = substitute_interface_guid(
+                &String::from_utf8_lossy(template_file.contents()),
+                self.interface_guid,
+            )
+            .replace(DRIVER_NAME_PLACEHOLDER, &underscored_driver_crate_name);
+            self.fs.write_to_file(&output_path, content.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Returns the driver crate name, i.e. the last component of the path,
+    /// with hyphens replaced by underscores
+    fn underscored_driver_crate_name(&self) -> Result<String, NewActionError> {
+        let driver_crate_name = self.path.file_name().ok_or_else(|| {
+            NewActionError::InvalidDriverCrateName(self.path.to_string_lossy().into_owned())
+        })?;
+        Ok(driver_crate_name.to_string_lossy().replace('-', "_"))
+    }
+}
+
+/// Substitutes the interface GUID placeholders in `content` with
+/// `interface_guid`
+pub fn substitute_interface_guid(content: &str, interface_guid: InterfaceGuid) -> String {
+    content
+        .replace(INTERFACE_GUID_PLACEHOLDER, &interface_guid.to_string())
+        .replace(
+            INTERFACE_GUID_VALUE_PLACEHOLDER,
+            &interface_guid.to_u128_literal(),
+        )
+}
+
+/// Checks whether the template is available for the driver type. Filter
+/// templates exist for KMDF and UMDF drivers, and bus templates only for KMDF
+/// drivers since child device enumeration is not supported by UMDF. HID
+/// minidriver templates only exist for KMDF drivers, which run under the
+/// `mshidkmdf` pass-through driver.
+pub const fn is_template_supported(driver_type: DriverType, template: DriverTemplate) -> bool {
+    match template {
+        DriverTemplate::Function => true,
+        DriverTemplate::Filter => matches!(driver_type, DriverType::Kmdf | DriverType::Umdf),
+        DriverTemplate::Bus | DriverTemplate::HidMinidriver => {
+            matches!(driver_type, DriverType::Kmdf)
+        }
+    }
+}
+
+/// Returns the directory in the bundled templates that holds the
+/// template-specific files. Function driver templates live directly under the
+/// driver type directory, other templates in a subdirectory of it.
+pub fn template_dir(driver_type: DriverType, template: DriverTemplate) -> PathBuf {
+    let driver_type_dir = PathBuf::from(driver_type.to_string());
+    match template {
+        DriverTemplate::Function => driver_type_dir,
+        DriverTemplate::Filter | DriverTemplate::Bus | DriverTemplate::HidMinidriver => {
+            driver_type_dir.join(template.to_string())
+        }
+    }
 }
 
 #[cfg(test)]
@@ -306,8 +432,9 @@ mod tests {
 
     use crate::{
         actions::{
+            DriverTemplate,
             DriverType,
-            new::{NewAction, NewActionError},
+            new::{InterfaceGuid, NewAction, NewActionError, TEST_CLIENT_DIR_NAME},
         },
         providers::{
             error::{CommandError, FileError},
@@ -330,6 +457,7 @@ fn new_project_created_successfully() {
             set_up_and_assert(
                 Path::new("test_driver"),
                 DriverType::Kmdf,
+                DriverTemplate::Function,
                 verbosity_level,
                 |test_setup| test_setup.set_expectations_with(None, expected_flag),
                 |result| {
@@ -339,11 +467,67 @@ fn new_project_created_successfully() {
         }
     }
 
+    #[test]
+    fn new_project_created_successfully_from_template() {
+        let cases = [
+            (DriverType::Kmdf, DriverTemplate::Filter),
+            (DriverType::Kmdf, DriverTemplate::Bus),
+            (DriverType::Kmdf, DriverTemplate::HidMinidriver),
+            (DriverType::Umdf, DriverTemplate::Filter),
+        ];
+
+        for (driver_type, template) in cases {
+            set_up_and_assert(
+                Path::new("test_driver"),
+                driver_type,
+                template,
+                Verbosity::default(),
+                |test_setup| test_setup.set_expectations_with(None, None),
+                |result| {
+                    assert!(
+                        result.is_ok(),
+                        "Expected {driver_type} {template} driver project to be created"
+                    );
+                },
+            );
+        }
+    }
+
+    #[test]
+    fn when_template_is_unsupported_then_run_returns_unsupported_template_error() {
+        let cases = [
+            (DriverType::Umdf, DriverTemplate::Bus),
+            (DriverType::Umdf, DriverTemplate::HidMinidriver),
+            (DriverType::Wdm, DriverTemplate::Filter),
+            (DriverType::Wdm, DriverTemplate::Bus),
+            (DriverType::Wdm, DriverTemplate::HidMinidriver),
+        ];
+
+        // No mock expectations are set since nothing should run before the
+        // template is validated
+        for (driver_type, template) in cases {
+            set_up_and_assert(
+                Path::new("test_driver_unsupported_template"),
+                driver_type,
+                template,
+                Verbosity::default(),
+                |test_setup| test_setup,
+                |result| {
+                    assert!(
+                        matches!(result, Err(NewActionError::UnsupportedTemplate(_, _))),
+                        "Expected UnsupportedTemplate error for {driver_type} {template}"
+                    );
+                },
+            );
+        }
+    }
+
     #[test]
     fn when_cargo_new_fails_then_run_returns_cargo_new_command_error() {
         set_up_and_assert(
             Path::new("test_driver_fail_cargo_new"),
             DriverType::Kmdf,
+            DriverTemplate::Function,
             Verbosity::default(),
             |test_setup| {
                 // Set up mocks with failure at cargo new step
@@ -370,6 +554,7 @@ fn when_copy_lib_rs_template_fails_then_run_returns_filesystem_error() {
         set_up_and_assert(
             Path::new("test_driver_fail_lib_copy"),
             DriverType::Kmdf,
+            DriverTemplate::Function,
             Verbosity::default(),
             |test_setup| {
                 // Set up mocks with failure at copy lib rs template to driver project step
@@ -429,6 +614,7 @@ fn when_update_cargo_toml_fails_then_run_returns_filesystem_error() {
             set_up_and_assert(
                 Path::new("test_driver_fail_cargo_toml_update"),
                 DriverType::Kmdf,
+                DriverTemplate::Function,
                 Verbosity::default(),
                 |test_setup| {
                     test_setup.set_expectations_with(
@@ -452,6 +638,7 @@ fn when_create_inx_file_fails_then_run_returns_filesystem_error() {
         set_up_and_assert(
             Path::new("test_driver_fail_create_inx_file"),
             DriverType::Kmdf,
+            DriverTemplate::Function,
             Verbosity::default(),
             |test_setup| {
                 // Set up mocks with failure at creating inx file step
@@ -475,6 +662,7 @@ fn when_invalid_path_provided_then_run_returns_invalid_driver_crate_name() {
             // Use an empty path component so that calling file_name() on it returns None
             Path::new(""),
             DriverType::Kmdf,
+            DriverTemplate::Function,
             Verbosity::default(),
             |test_setup| {
                 // Set up mocks with failure at parsing driver crate name step
@@ -497,6 +685,7 @@ fn when_copy_build_rs_template_fails_then_run_returns_filesystem_error() {
         set_up_and_assert(
             Path::new("test_driver_fail_build_rs"),
             DriverType::Kmdf,
+            DriverTemplate::Function,
             Verbosity::default(),
             |test_setup| {
                 // Set up mocks with failure at copy build rs template to driver project step
@@ -519,6 +708,7 @@ fn when_copy_cargo_config_fails_then_run_returns_filesystem_error() {
         set_up_and_assert(
             Path::new("test_driver_fail_cargo_config"),
             DriverType::Kmdf,
+            DriverTemplate::Function,
             Verbosity::default(),
             |test_setup| {
                 // Set up mocks with failure at copy cargo config to driver project step
@@ -536,6 +726,29 @@ fn when_copy_cargo_config_fails_then_run_returns_filesystem_error() {
         );
     }
 
+    #[test]
+    fn when_create_test_client_fails_then_run_returns_filesystem_error() {
+        set_up_and_assert(
+            Path::new("test_driver_fail_test_client"),
+            DriverType::Kmdf,
+            DriverTemplate::Function,
+            Verbosity::default(),
+            |test_setup| {
+                // Set up mocks with failure at creating the test client step
+                test_setup.set_expectations_with(Some(FailureStep::CreateTestClient), None)
+            },
+            |result| {
+                assert!(
+                    matches!(
+                        result,
+                        Err(NewActionError::FileSystem(FileError::WriteError(_, _)))
+                    ),
+                    "Expected FileSystem WriteError from create_test_client step"
+                );
+            },
+        );
+    }
+
     /// Helper function to set up mock expectations and assert on the result.
     ///
     /// This function takes a closure to configure the test setup (e.g., mock
@@ -546,16 +759,19 @@ fn when_copy_cargo_config_fails_then_run_returns_filesystem_error() {
     fn set_up_and_assert(
         path: &Path,
         driver_type: DriverType,
+        template: DriverTemplate,
         verbosity_level: Verbosity,
         set_expectations_fn: impl FnOnce(TestSetup) -> TestSetup,
         assert_fn: impl FnOnce(Result<(), NewActionError>),
     ) {
-        let test_setup = TestSetup::new(path);
+        let test_setup = TestSetup::new(path, template);
         let test_setup = set_expectations_fn(test_setup);
 
         let result = NewAction::new(
             path,
             driver_type,
+            template,
+            TEST_INTERFACE_GUID,
             verbosity_level,
             &test_setup.mock_exec,
             &test_setup.mock_fs,
@@ -565,6 +781,9 @@ fn set_up_and_assert(
         assert_fn(result);
     }
 
+    const TEST_INTERFACE_GUID: InterfaceGuid =
+        InterfaceGuid::from_u128(0x0B4E_9A0C_1D2F_4A3B_8C5D_7E6F_8091_A2B3);
+
     /// Enum representing different steps where failures can be injected during
     /// tests. Used to configure mock expectations for specific failure
     /// scenarios.
@@ -575,6 +794,7 @@ enum FailureStep {
         CreateInxFile,
         CopyBuildRsTemplate,
         CopyCargoConfig,
+        CreateTestClient,
     }
 
     /// Test helper struct that provides a fluent API for configuring mock
@@ -596,7 +816,7 @@ enum FailureStep {
     /// # Example
     ///
     /// ```ignore
-    /// let test_setup = TestSetup::new(Path::new("test_driver"))
+    /// let test_setup = TestSetup::new(Path::new("test_driver"), DriverTemplate::Function)
     ///     .set_expectations_with(Some(FailureStep::CopyLibRsTemplate), None);
     ///
     /// // Now use test_setup.mock_exec and test_setup.mock_fs in your test
@@ -604,6 +824,8 @@ enum FailureStep {
     struct TestSetup<'a> {
         /// The path to the driver project being created in the test.
         path: &'a Path,
+        /// The template the driver project is created from in the test.
+        template: DriverTemplate,
         /// Mock implementation of the command execution provider.
         mock_exec: MockCommandExec,
         /// Mock implementation of the file system provider.
@@ -611,9 +833,10 @@ struct TestSetup<'a> {
     }
 
     impl<'a> TestSetup<'a> {
-        fn new(path: &'a Path) -> Self {
+        fn new(path: &'a Path, template: DriverTemplate) -> Self {
             Self {
                 path,
+                template,
                 mock_exec: MockCommandExec::new(),
                 mock_fs: MockFs::new(),
             }
@@ -661,8 +884,16 @@ fn set_expectations_with(
             if matches!(failure_step, Some(FailureStep::CopyCargoConfig)) {
                 return self.expect_copy_cargo_config(false);
             }
+            self = self.expect_copy_cargo_config(true);
+
+            if self.template != DriverTemplate::Function {
+                return self;
+            }
+            if matches!(failure_step, Some(FailureStep::CreateTestClient)) {
+                return self.expect_create_test_client(false);
+            }
 
-            self.expect_copy_cargo_config(true)
+            self.expect_create_test_client(true)
         }
 
         fn expect_cargo_new(
@@ -707,7 +938,10 @@ fn expect_copy_lib_rs_template(mut self, is_copy_success: bool) -> Self {
             let expected_lib_rs_path = lib_rs_path.clone();
             self.mock_fs
                 .expect_write_to_file()
-                .withf(move |path, _| path == expected_lib_rs_path)
+                .withf(move |path, content| {
+                    path == expected_lib_rs_path
+                        && !String::from_utf8_lossy(content).contains("##interface_guid")
+                })
                 .returning(move |_, _| {
                     if !is_copy_success {
                         return Err(FileError::WriteError(
@@ -851,5 +1085,52 @@ fn expect_copy_cargo_config(mut self, is_copy_success: bool) -> Self {
                 });
             self
         }
+
+        fn expect_create_test_client(mut self, is_create_success: bool) -> Self {
+            let test_client_dir = self.path.join(TEST_CLIENT_DIR_NAME);
+            let test_client_src_dir = test_client_dir.join("src");
+            self.mock_fs
+                .expect_create_dir_all()
+                .withf(move |path| path == test_client_src_dir)
+                .returning(move |_| Ok(()));
+
+            let underscored_driver_crate_name = self
+                .path
+                .file_name()
+                .expect("Path must not be empty or terminate in '..' when creating test client")
+                .to_string_lossy()
+                .replace('-', "_");
+            let expected_package_name =
+                format!("name = \"{underscored_driver_crate_name}_test_client\"");
+            let cargo_toml_path = test_client_dir.join("Cargo.toml");
+            self.mock_fs
+                .expect_write_to_file()
+                .withf(move |path, content| {
+                    path == cargo_toml_path
+                        && String::from_utf8_lossy(content).contains(&expected_package_name)
+                })
+                .returning(|_, _| Ok(()));
+
+            let main_rs_path = test_client_dir.join("src").join("main.rs");
+            let expected_main_rs_path = main_rs_path.clone();
+            let expected_guid_literal = TEST_INTERFACE_GUID.to_u128_literal();
+            self.mock_fs
+                .expect_write_to_file()
+                .withf(move |path, content| {
+                    path == expected_main_rs_path
+                        && String::from_utf8_lossy(content).contains(&expected_guid_literal)
+                })
+                .returning(move |_, _| {
+                    if is_create_success {
+                        Ok(())
+                    } else {
+                        Err(FileError::WriteError(
+                            main_rs_path.clone(),
+                            Error::other("Write error"),
+                        ))
+                    }
+                });
+            self
+        }
     }
 }
@@ -0,0 +1,51 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+//! This module defines error types for the self test action module.
+
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use crate::{
+    actions::{FailureCategory, new::NewActionError},
+    providers::error::{CommandError, FileError},
+};
+
+/// Errors for the self test action layer
+#[derive(Debug, Error)]
+pub enum SelfTestActionError {
+    #[error("Self test directory already exists: {0}, remove it or choose another path")]
+    SelfTestDirExists(PathBuf),
+    #[error(transparent)]
+    NewAction(#[from] NewActionError),
+    #[error("Golden file not found: {0}")]
+    GoldenFileNotFound(PathBuf),
+    #[error("Generated file {0} does not match golden file {1}")]
+    GoldenFileMismatch(PathBuf, PathBuf),
+    #[error("Generated Cargo.toml {0} does not declare the {1} driver model")]
+    InvalidCargoToml(PathBuf, String),
+    #[error("Error running cargo check for target {0}")]
+    CompileCheckCommand(String, #[source] CommandError),
+    #[error(transparent)]
+    FileIo(#[from] FileError),
+    #[error("Self test failed for one or more templates: {0}")]
+    OneOrMoreTemplatesFailed(String, FailureCategory),
+}
+
+impl SelfTestActionError {
+    /// Returns the [`FailureCategory`] of the error. For failures of multiple
+    /// templates, this is the category of the first failure.
+    #[must_use]
+    pub const fn category(&self) -> FailureCategory {
+        match self {
+            Self::NewAction(e) => e.category(),
+            Self::CompileCheckCommand(..) => FailureCategory::Build,
+            Self::InvalidCargoToml(..) => FailureCategory::MetadataInvalid,
+            Self::OneOrMoreTemplatesFailed(_, category) => *category,
+            Self::SelfTestDirExists(_)
+            | Self::GoldenFileNotFound(_)
+            | Self::GoldenFileMismatch(..)
+            | Self::FileIo(_) => FailureCategory::Other,
+        }
+    }
+}
@@ -0,0 +1,454 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+//! This module contains the `SelfTestAction` struct and its associated methods
+//! for validating the driver project templates bundled with `cargo wdk new`.
+//! For every supported driver type and template combination it creates a new
+//! driver project, compares the generated files against the golden files
+//! bundled with the utility and checks that the project compiles for each
+//! target architecture, so that template changes cannot silently produce
+//! broken projects.
+
+mod error;
+
+use std::path::{Path, PathBuf, absolute};
+
+use anyhow::Result;
+use clap_verbosity_flag::Verbosity;
+pub use error::SelfTestActionError;
+use include_dir::{Dir, include_dir};
+use mockall_double::double;
+use tracing::{debug, error as err, info};
+use wdk_build::CpuArchitecture;
+
+#[double]
+use crate::providers::{exec::CommandExec, fs::Fs};
+use crate::{
+    actions::{
+        DriverTemplate,
+        DriverType,
+        new::{
+            InterfaceGuid,
+            NewAction,
+            TEST_CLIENT_DIR_NAME,
+            is_template_supported,
+            template_dir,
+        },
+        to_target_triple,
+    },
+    trace,
+};
+
+/// Directory containing the golden files of the generated driver projects.
+/// Its layout mirrors the templates directory
+static GOLDEN_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/golden");
+/// Name of the driver crates generated by the self test. The golden `.inx`
+/// files are generated for this name
+const GOLDEN_DRIVER_NAME: &str = "golden-driver";
+/// Device interface GUID of the driver crates generated by the self test. The
+/// golden `lib.rs` and test client files are generated for this GUID
+const GOLDEN_INTERFACE_GUID: InterfaceGuid =
+    InterfaceGuid::from_u128(0x3F6C_2A1E_5B7D_4C9A_8E0F_1A2B_3C4D_5E6F);
+/// Name of the directory the self test creates driver projects in
+const SELF_TEST_DIR_NAME: &str = "cargo-wdk-self-test";
+const DRIVER_TYPES: [DriverType; 3] = [DriverType::Kmdf, DriverType::Umdf, DriverType::Wdm];
+const DRIVER_TEMPLATES: [DriverTemplate; 4] = [
+    DriverTemplate::Function,
+    DriverTemplate::Filter,
+    DriverTemplate::Bus,
+    DriverTemplate::HidMinidriver,
+];
+
+pub struct SelfTestActionParams<'a> {
+    pub working_dir: &'a Path,
+    /// Target architectures to check compilation for. The host architecture
+    /// is used when it is empty
+    pub target_archs: Vec<CpuArchitecture>,
+    pub compile: bool,
+    pub verbosity_level: Verbosity,
+}
+
+/// Action that validates the driver project templates bundled with the
+/// utility against golden files and compile checks
+pub struct SelfTestAction<'a> {
+    working_dir: PathBuf,
+    target_archs: Vec<CpuArchitecture>,
+    compile: bool,
+    verbosity_level: Verbosity,
+
+    // Injected deps
+    command_exec: &'a CommandExec,
+    fs: &'a Fs,
+}
+
+impl<'a> SelfTestAction<'a> {
+    /// Creates a new instance of `SelfTestAction`.
+    ///
+    /// # Arguments:
+    /// * `params` - The `SelfTestActionParams` struct containing the parameters
+    ///   for the self test action
+    /// * `command_exec` - The command execution provider instance
+    /// * `fs` - The file system provider instance
+    ///
+    /// # Returns
+    /// * `Result<Self>` - A result containing either a new instance of
+    ///   `SelfTestAction` on success, or an `anyhow::Error`.
+    ///
+    /// # Errors
+    /// * [`anyhow::Error`] -  If `params.working_dir` is not a syntactically
+    ///   valid path, e.g. it is empty
+    pub fn new(
+        params: &SelfTestActionParams<'a>,
+        command_exec: &'a CommandExec,
+        fs: &'a Fs,
+    ) -> Result<Self> {
+        let target_archs = if params.target_archs.is_empty() {
+            vec![
+                CpuArchitecture::try_from_cargo_str(std::env::consts::ARCH)
+                    .expect("The rust standard library should always set env::consts::ARCH"),
+            ]
+        } else {
+            params.target_archs.clone()
+        };
+        Ok(Self {
+            working_dir: absolute(params.working_dir)?,
+            target_archs,
+            compile: params.compile,
+            verbosity_level: params.verbosity_level,
+            command_exec,
+            fs,
+        })
+    }
+
+    /// Entry point method to execute the self test action flow. The driver
+    /// projects are created in a `cargo-wdk-self-test` directory in the
+    /// working directory, which is left in place for inspection.
+    ///
+    /// # Returns
+    /// `Result<(), SelfTestActionError>`
+    ///
+    /// # Errors
+    /// * `SelfTestActionError::SelfTestDirExists` - If the self test directory
+    ///   already exists.
+    /// * `SelfTestActionError::FileIo` - If there is an error creating the self
+    ///   test directory.
+    /// * `SelfTestActionError::OneOrMoreTemplatesFailed` - If the validation of
+    ///   one or more templates fails.
+    pub fn run(&self) -> Result<(), SelfTestActionError> {
+        let self_test_dir = self.working_dir.join(SELF_TEST_DIR_NAME);
+        if self.fs.exists(&self_test_dir) {
+            return Err(SelfTestActionError::SelfTestDirExists(self_test_dir));
+        }
+        info!("Running self test in {}", self_test_dir.display());
+
+        let mut failed_templates = vec![];
+        let mut first_failure_category = None;
+        for driver_type in DRIVER_TYPES {
+            for template in DRIVER_TEMPLATES {
+                if !is_template_supported(driver_type, template) {
+                    continue;
+                }
+                let template_name = format!("{driver_type}-{template}");
+                info!("Validating {driver_type} {template} template");
+                let project_dir = self_test_dir.join(&template_name).join(GOLDEN_DRIVER_NAME);
+                if let Err(e) = self.run_for_template(driver_type, template, &project_dir) {
+                    first_failure_category.get_or_insert_with(|| e.category());
+                    err!(
+                        "Error validating {driver_type} {template} template, error: {:?}",
+                        anyhow::Error::new(e)
+                    );
+                    failed_templates.push(template_name);
+                }
+            }
+        }
+
+        if let Some(failure_category) = first_failure_category {
+            return Err(SelfTestActionError::OneOrMoreTemplatesFailed(
+                failed_templates.join(", "),
+                failure_category,
+            ));
+        }
+        info!("Self test completed successfully");
+        Ok(())
+    }
+
+    fn run_for_template(
+        &self,
+        driver_type: DriverType,
+        template: DriverTemplate,
+        project_dir: &Path,
+    ) -> Result<(), SelfTestActionError> {
+        if let Some(template_dir) = project_dir.parent() {
+            self.fs.create_dir_all(template_dir)?;
+        }
+        NewAction::new(
+            project_dir,
+            driver_type,
+            template,
+            GOLDEN_INTERFACE_GUID,
+            self.verbosity_level,
+            self.command_exec,
+            self.fs,
+        )
+        .run()?;
+        self.validate_golden_files(driver_type, template, project_dir)?;
+        self.validate_cargo_toml(driver_type, project_dir)?;
+        if self.compile {
+            for target_arch in &self.target_archs {
+                self.run_cargo_check(*target_arch, project_dir)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Compares the files generated from the templates with their golden
+    /// files
+    fn validate_golden_files(
+        &self,
+        driver_type: DriverType,
+        template: DriverTemplate,
+        project_dir: &Path,
+    ) -> Result<(), SelfTestActionError> {
+        let golden_template_dir = template_dir(driver_type, template);
+        let inx_file_name = format!("{}.inx", GOLDEN_DRIVER_NAME.replace('-', "_"));
+        let mut golden_files = vec![
+            (PathBuf::from("build.rs"), project_dir.join("build.rs")),
+            (
+                PathBuf::from("config.toml"),
+                project_dir.join(".cargo").join("config.toml"),
+            ),
+            (
+                golden_template_dir.join("lib.rs"),
+                project_dir.join("src").join("lib.rs"),
+            ),
+            (
+                golden_template_dir.join(&inx_file_name),
+                project_dir.join(&inx_file_name),
+            ),
+        ];
+        if template == DriverTemplate::Function {
+            let golden_test_client_dir = PathBuf::from(TEST_CLIENT_DIR_NAME);
+            let test_client_dir = project_dir.join(TEST_CLIENT_DIR_NAME);
+            golden_files.extend([
+                (
+                    golden_test_client_dir.join("Cargo.toml"),
+                    test_client_dir.join("Cargo.toml"),
+                ),
+                (
+                    golden_test_client_dir.join("main.rs"),
+                    test_client_dir.join("src").join("main.rs"),
+                ),
+            ]);
+        }
+        for (golden_file_path, generated_file_path) in golden_files {
+            debug!(
+                "Comparing {} with golden file {}",
+                generated_file_path.display(),
+                golden_file_path.display()
+            );
+            let golden_file = GOLDEN_DIR
+                .get_file(&golden_file_path)
+                .ok_or_else(|| SelfTestActionError::GoldenFileNotFound(golden_file_path.clone()))?;
+            let generated_content = self.fs.read_file_to_string(&generated_file_path)?;
+            if generated_content.as_bytes() != golden_file.contents() {
+                return Err(SelfTestActionError::GoldenFileMismatch(
+                    generated_file_path,
+                    golden_file_path,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the generated `Cargo.toml` declares the driver model of the
+    /// driver type. The rest of the file is generated by `cargo new` and
+    /// depends on the installed cargo, so it has no golden file
+    fn validate_cargo_toml(
+        &self,
+        driver_type: DriverType,
+        project_dir: &Path,
+    ) -> Result<(), SelfTestActionError> {
+        let cargo_toml_path = project_dir.join("Cargo.toml");
+        let driver_model = driver_type.to_string().to_uppercase();
+        let cargo_toml_content = self.fs.read_file_to_string(&cargo_toml_path)?;
+        if !cargo_toml_content.contains("[package.metadata.wdk.driver-model]")
+            || !cargo_toml_content.contains(&format!("driver-type = \"{driver_model}\""))
+        {
+            return Err(SelfTestActionError::InvalidCargoToml(
+                cargo_toml_path,
+                driver_model,
+            ));
+        }
+        Ok(())
+    }
+
+    fn run_cargo_check(
+        &self,
+        target_arch: CpuArchitecture,
+        project_dir: &Path,
+    ) -> Result<(), SelfTestActionError> {
+        let target_triple = to_target_triple(target_arch);
+        info!("Checking generated project compiles for target {target_triple}");
+        let mut args = vec!["check", "--target", &target_triple];
+        if let Some(flag) = trace::get_cargo_verbose_flags(self.verbosity_level) {
+            args.push(flag);
+        }
+        self.command_exec
+            .run("cargo", &args, None, Some(project_dir))
+            .map_err(|e| SelfTestActionError::CompileCheckCommand(target_triple.clone(), e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use clap_verbosity_flag::Verbosity;
+
+    use super::*;
+    use crate::{
+        actions::{
+            FailureCategory,
+            new::{DRIVER_NAME_PLACEHOLDER, TEMPLATES_DIR, substitute_interface_guid},
+        },
+        providers::{exec::MockCommandExec, fs::MockFs},
+    };
+
+    fn template_file_content(template_file_path: &Path) -> Vec<u8> {
+        TEMPLATES_DIR
+            .get_file(template_file_path)
+            .unwrap_or_else(|| panic!("missing template {}", template_file_path.display()))
+            .contents()
+            .to_vec()
+    }
+
+    fn rendered_template_file_content(template_file_path: &Path) -> Vec<u8> {
+        let content = String::from_utf8(template_file_content(template_file_path))
+            .expect("template should be valid UTF-8");
+        substitute_interface_guid(&content, GOLDEN_INTERFACE_GUID)
+            .replace(DRIVER_NAME_PLACEHOLDER, "golden_driver")
+            .into_bytes()
+    }
+
+    fn golden_file_content(golden_file_path: &Path) -> Vec<u8> {
+        GOLDEN_DIR
+            .get_file(golden_file_path)
+            .unwrap_or_else(|| panic!("missing golden file {}", golden_file_path.display()))
+            .contents()
+            .to_vec()
+    }
+
+    // Golden files must be updated along with the templates
+    #[test]
+    fn golden_files_match_templates() {
+        assert_eq!(
+            golden_file_content(Path::new("build.rs")),
+            template_file_content(Path::new("build.rs.tmp"))
+        );
+        assert_eq!(
+            golden_file_content(Path::new("config.toml")),
+            template_file_content(Path::new("config.toml.tmp"))
+        );
+        for driver_type in DRIVER_TYPES {
+            for template in DRIVER_TEMPLATES {
+                if !is_template_supported(driver_type, template) {
+                    continue;
+                }
+                let template_dir = template_dir(driver_type, template);
+                assert_eq!(
+                    golden_file_content(&template_dir.join("lib.rs")),
+                    rendered_template_file_content(&template_dir.join("lib.rs.tmp")),
+                    "lib.rs of {driver_type} {template} template does not match golden file"
+                );
+                assert_eq!(
+                    golden_file_content(&template_dir.join("golden_driver.inx")),
+                    rendered_template_file_content(&template_dir.join("driver_name.inx.tmp")),
+                    ".inx of {driver_type} {template} template does not match golden file"
+                );
+            }
+        }
+        let test_client_dir = Path::new(TEST_CLIENT_DIR_NAME);
+        for (golden_file_name, template_file_name) in
+            [("Cargo.toml", "Cargo.toml.tmp"), ("main.rs", "main.rs.tmp")]
+        {
+            assert_eq!(
+                golden_file_content(&test_client_dir.join(golden_file_name)),
+                rendered_template_file_content(&test_client_dir.join(template_file_name)),
+                "{golden_file_name} of test client template does not match golden file"
+            );
+        }
+    }
+
+    #[test]
+    fn when_self_test_dir_exists_then_run_returns_self_test_dir_exists_error() {
+        let working_dir = PathBuf::from("C:\\tmp");
+        let command_exec = MockCommandExec::default();
+        let mut fs = MockFs::default();
+        fs.expect_exists()
+            .withf(|path| path.ends_with(SELF_TEST_DIR_NAME))
+            .returning(|_| true);
+
+        let self_test_action = SelfTestAction::new(
+            &SelfTestActionParams {
+                working_dir: &working_dir,
+                target_archs: vec![CpuArchitecture::Amd64],
+                compile: true,
+                verbosity_level: Verbosity::default(),
+            },
+            &command_exec,
+            &fs,
+        )
+        .expect("Failed to init self test action");
+        assert!(matches!(
+            self_test_action.run(),
+            Err(SelfTestActionError::SelfTestDirExists(_))
+        ));
+    }
+
+    #[test]
+    fn when_generated_files_do_not_match_golden_files_then_run_fails_for_every_template() {
+        let working_dir = PathBuf::from("C:\\tmp");
+        let mut command_exec = MockCommandExec::default();
+        command_exec
+            .expect_run()
+            .withf(|command, args, _, _| command == "cargo" && args.first() == Some(&"new"))
+            .times(7)
+            .returning(|_, _, _, _| {
+                Ok(std::process::Output {
+                    status: std::process::ExitStatus::default(),
+                    stdout: vec![],
+                    stderr: vec![],
+                })
+            });
+        let mut fs = MockFs::default();
+        fs.expect_exists().returning(|_| false);
+        fs.expect_create_dir_all().returning(|_| Ok(()));
+        fs.expect_read_file_to_string()
+            .returning(|_| Ok("[dependencies]\n".to_string()));
+        fs.expect_write_to_file().returning(|_, _| Ok(()));
+        fs.expect_append_to_file().returning(|_, _| Ok(()));
+
+        let self_test_action = SelfTestAction::new(
+            &SelfTestActionParams {
+                working_dir: &working_dir,
+                target_archs: vec![CpuArchitecture::Amd64],
+                compile: true,
+                verbosity_level: Verbosity::default(),
+            },
+            &command_exec,
+            &fs,
+        )
+        .expect("Failed to init self test action");
+        let Err(SelfTestActionError::OneOrMoreTemplatesFailed(failed_templates, category)) =
+            self_test_action.run()
+        else {
+            panic!("self test should fail when generated files do not match golden files");
+        };
+        assert_eq!(
+            failed_templates,
+            "kmdf-function, kmdf-filter, kmdf-bus, kmdf-hid-minidriver, umdf-function, \
+             umdf-filter, wdm-function"
+        );
+        assert_eq!(category, FailureCategory::Other);
+    }
+}
@@ -0,0 +1,62 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+//! This module defines error types for the sign action module.
+
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use crate::{
+    actions::FailureCategory,
+    providers::error::{CommandError, FileError},
+};
+
+/// Errors for the sign action layer
+#[derive(Debug, Error)]
+pub enum SignActionError {
+    #[error(transparent)]
+    WdkBuildConfig(#[from] wdk_build::ConfigError),
+    #[error("Error Parsing Cargo.toml, not a valid rust project/workspace")]
+    CargoMetadataParse(#[from] cargo_metadata::Error),
+    #[error("Error Parsing WDK metadata from Cargo.toml, not a valid driver project/workspace")]
+    WdkMetadataParse(#[from] wdk_build::metadata::TryFromCargoMetadataError),
+    #[error("Not a workspace member, working directory: {0}")]
+    NotAWorkspaceMember(PathBuf),
+    #[error("No driver packages found to sign in: {0}")]
+    NoDriverPackages(PathBuf),
+    #[error("Missing driver package folder: {0}, run `cargo wdk build` before signing")]
+    MissingDriverPackage(PathBuf),
+    #[error("Missing driver binary in driver package: {0}")]
+    MissingDriverBinary(PathBuf),
+    #[error("Missing certificate file: {0}")]
+    MissingCertFile(PathBuf),
+    #[error(transparent)]
+    FileIo(#[from] FileError),
+    #[error("Error generating cat file using inf2cat")]
+    Inf2CatCommand(#[source] CommandError),
+    #[error("Error signing driver package file using signtool")]
+    SignCommand(#[source] CommandError),
+    #[error("Error verifying signed driver package file using signtool")]
+    SignVerificationCommand(#[source] CommandError),
+}
+
+impl SignActionError {
+    /// Returns the [`FailureCategory`] of the error
+    #[must_use]
+    pub const fn category(&self) -> FailureCategory {
+        match self {
+            Self::WdkBuildConfig(_) => FailureCategory::EnvironmentMissing,
+            Self::CargoMetadataParse(_)
+            | Self::WdkMetadataParse(_)
+            | Self::NotAWorkspaceMember(_)
+            | Self::NoDriverPackages(_) => FailureCategory::MetadataInvalid,
+            Self::MissingDriverPackage(_)
+            | Self::MissingDriverBinary(_)
+            | Self::FileIo(_)
+            | Self::Inf2CatCommand(_) => FailureCategory::Packaging,
+            Self::MissingCertFile(_) | Self::SignCommand(_) | Self::SignVerificationCommand(_) => {
+                FailureCategory::Signing
+            }
+        }
+    }
+}
@@ -0,0 +1,715 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+//! This module contains the `SignAction` struct and its associated methods
+//! for signing the driver packages created by `cargo wdk build` in a separate
+//! step, ex. with a release certificate in a pipeline that builds the driver
+//! packages once. The driver binary and the cat file of each driver package
+//! are signed, or re-signed, with the configured certificate. The cat file is
+//! generated again with `inf2cat` if the driver binary was replaced after the
+//! package was created.
+
+mod error;
+
+use std::path::{Path, PathBuf, absolute};
+
+use anyhow::Result;
+use cargo_metadata::{Metadata as CargoMetadata, Package, TargetKind};
+pub use error::SignActionError;
+use mockall_double::double;
+use tracing::{debug, info};
+use wdk_build::{
+    CpuArchitecture,
+    DriverConfig,
+    metadata::{PackageConfig, Wdk},
+};
+
+use crate::actions::{
+    Profile,
+    SigningOverrides,
+    build::signtool_sign_args,
+    get_target_dir,
+    resolve_package_config,
+};
+#[double]
+use crate::providers::{exec::CommandExec, fs::Fs, metadata::Metadata};
+
+/// Name of the test certificate that `cargo wdk build` signs driver packages
+/// with when no certificate is configured
+const WDR_LOCAL_TEST_CERT: &str = "WDRLocalTestCert";
+
+pub struct SignActionParams<'a> {
+    pub working_dir: &'a Path,
+    pub profile: Option<&'a Profile>,
+    pub target_arch: Option<CpuArchitecture>,
+    /// Directory the driver packages were created in, if it is not the target
+    /// directory
+    pub package_dir: Option<&'a Path>,
+    pub cert_store: Option<&'a str>,
+    pub cert_name: Option<&'a str>,
+    pub cert_path: Option<&'a Path>,
+    /// SHA1 thumbprint of the signing certificate in the certificate store
+    pub cert_thumbprint: Option<&'a str>,
+    /// Cryptographic service provider holding the private key of the
+    /// certificate in `cert_path`
+    pub csp: Option<&'a str>,
+    /// Key container of the private key in the CSP
+    pub key_container: Option<&'a str>,
+    pub digest_algorithm: Option<&'a str>,
+    pub verify_signature: bool,
+}
+
+/// Action that signs the driver packages of a driver project or workspace.
+/// The driver packages must have been created with `cargo wdk build` using
+/// the same profile and target architecture.
+pub struct SignAction<'a> {
+    working_dir: PathBuf,
+    profile: Option<&'a Profile>,
+    target_arch: Option<CpuArchitecture>,
+    package_dir: Option<PathBuf>,
+    cert_store: Option<&'a str>,
+    cert_name: Option<&'a str>,
+    cert_path: Option<PathBuf>,
+    cert_thumbprint: Option<&'a str>,
+    csp: Option<&'a str>,
+    key_container: Option<&'a str>,
+    digest_algorithm: Option<&'a str>,
+    verify_signature: bool,
+
+    // Injected deps
+    command_exec: &'a CommandExec,
+    fs: &'a Fs,
+    metadata: &'a Metadata,
+}
+
+impl<'a> SignAction<'a> {
+    /// Creates a new instance of `SignAction`.
+    ///
+    /// # Arguments:
+    /// * `params` - The `SignActionParams` struct containing the parameters for
+    ///   the sign action. Signing parameters that are set take precedence over
+    ///   the `metadata.wdk.package` section of `Cargo.toml`
+    /// * `command_exec` - The command execution provider instance
+    /// * `fs` - The file system provider instance
+    /// * `metadata` - The metadata provider instance
+    ///
+    /// # Returns
+    /// * `Result<Self>` - A result containing either a new instance of
+    ///   `SignAction` on success, or an `anyhow::Error`.
+    ///
+    /// # Errors
+    /// * [`anyhow::Error`] -  If `params.working_dir`, `params.package_dir` or
+    ///   `params.cert_path` is not a syntactically valid path, e.g. it is empty
+    pub fn new(
+        params: &SignActionParams<'a>,
+        command_exec: &'a CommandExec,
+        fs: &'a Fs,
+        metadata: &'a Metadata,
+    ) -> Result<Self> {
+        Ok(Self {
+            working_dir: absolute(params.working_dir)?,
+            profile: params.profile,
+            target_arch: params.target_arch,
+            package_dir: params.package_dir.map(absolute).transpose()?,
+            cert_store: params.cert_store,
+            cert_name: params.cert_name,
+            cert_path: params.cert_path.map(absolute).transpose()?,
+            cert_thumbprint: params.cert_thumbprint,
+            csp: params.csp,
+            key_container: params.key_container,
+            digest_algorithm: params.digest_algorithm,
+            verify_signature: params.verify_signature,
+            command_exec,
+            fs,
+            metadata,
+        })
+    }
+
+    /// Entry point method to execute the sign action flow.
+    ///
+    /// # Returns
+    /// `Result<(), SignActionError>`
+    ///
+    /// # Errors
+    /// * `SignActionError::CargoMetadataParse` - If there is an error parsing
+    ///   the Cargo metadata.
+    /// * `SignActionError::WdkMetadataParse` - If there is an error parsing the
+    ///   WDK metadata.
+    /// * `SignActionError::NotAWorkspaceMember` - If the working directory is
+    ///   not a workspace member.
+    /// * `SignActionError::NoDriverPackages` - If there are no driver packages
+    ///   to sign.
+    /// * `SignActionError::WdkBuildConfig` - If the WDK tools cannot be found.
+    /// * `SignActionError::MissingDriverPackage` - If a driver package has not
+    ///   been created.
+    /// * `SignActionError::MissingDriverBinary` - If a driver package does not
+    ///   contain its driver binary.
+    /// * `SignActionError::MissingCertFile` - If the configured certificate
+    ///   file does not exist.
+    /// * `SignActionError::Inf2CatCommand` - If there is an error generating
+    ///   the cat file of a driver package again.
+    /// * `SignActionError::SignCommand` - If there is an error signing a file
+    ///   of a driver package.
+    /// * `SignActionError::SignVerificationCommand` - If there is an error
+    ///   verifying the signature of a file of a driver package.
+    /// * `SignActionError::FileIo` - If there is an error removing the test
+    ///   certificate from a driver package.
+    pub fn run(&self) -> Result<(), SignActionError> {
+        debug!(
+            "Initialized sign for project at: {}",
+            self.working_dir.display()
+        );
+        let cargo_metadata = self
+            .metadata
+            .get_cargo_metadata_at_path(&self.working_dir)?;
+        let wdk_metadata = Wdk::try_from(&cargo_metadata)?;
+        let packages = self.get_driver_packages(&cargo_metadata)?;
+        let package_root_dir = get_target_dir(
+            self.package_dir
+                .as_deref()
+                .unwrap_or_else(|| cargo_metadata.target_directory.as_std_path()),
+            self.profile,
+            self.target_arch,
+        );
+        let target_arch = self.target_arch.unwrap_or_else(|| {
+            CpuArchitecture::try_from_cargo_str(std::env::consts::ARCH)
+                .expect("The rust standard library should always set env::consts::ARCH")
+        });
+
+        // inf2cat and signtool ship with the WDK tools
+        wdk_build::cargo_make::setup_path()?;
+        debug!("PATH env variable is set with WDK bin and tools paths");
+
+        for package in packages {
+            info!("Signing package {}", package.name.as_str());
+            self.sign_package(package, &wdk_metadata, &package_root_dir, target_arch)?;
+            info!("Finished signing {}", package.name.as_str());
+        }
+        Ok(())
+    }
+
+    /// Returns the driver packages to sign. These are all the driver packages
+    /// of the workspace when running from the workspace root, or the package
+    /// of the workspace member the action is run from.
+    fn get_driver_packages<'m>(
+        &self,
+        cargo_metadata: &'m CargoMetadata,
+    ) -> Result<Vec<&'m Package>, SignActionError> {
+        let is_driver_package = |package: &Package| {
+            package.metadata.get("wdk").is_some()
+                && package
+                    .targets
+                    .iter()
+                    .any(|t| t.kind.contains(&TargetKind::CDyLib))
+        };
+        let workspace_packages = cargo_metadata.workspace_packages();
+
+        let driver_packages = if cargo_metadata.workspace_root.as_std_path() == self.working_dir {
+            workspace_packages
+                .into_iter()
+                .filter(|package| is_driver_package(package))
+                .collect::<Vec<_>>()
+        } else {
+            let package = workspace_packages
+                .into_iter()
+                .find(|package| {
+                    package
+                        .manifest_path
+                        .parent()
+                        .is_some_and(|p| p.as_std_path() == self.working_dir)
+                })
+                .ok_or_else(|| SignActionError::NotAWorkspaceMember(self.working_dir.clone()))?;
+            if is_driver_package(package) {
+                vec![package]
+            } else {
+                vec![]
+            }
+        };
+
+        if driver_packages.is_empty() {
+            return Err(SignActionError::NoDriverPackages(self.working_dir.clone()));
+        }
+        Ok(driver_packages)
+    }
+
+    /// Signs a single driver package. Export drivers are packaged without an
+    /// INF file, so only their driver binary is signed.
+    fn sign_package(
+        &self,
+        package: &Package,
+        wdk_metadata: &Wdk,
+        package_root_dir: &Path,
+        target_arch: CpuArchitecture,
+    ) -> Result<(), SignActionError> {
+        let package_config = wdk_metadata
+            .package
+            .clone()
+            .unwrap_or_default()
+            .with_driver_package_settings(package)?;
+        let export_driver = package_config
+            .export_drivers
+            .iter()
+            .any(|name| name == package.name.as_str());
+        let package_name = package.name.as_str().replace('-', "_");
+        let package_dir = package_root_dir.join(format!("{package_name}_package"));
+        if !self.fs.exists(&package_dir) {
+            return Err(SignActionError::MissingDriverPackage(package_dir));
+        }
+
+        let manifest_dir = package
+            .manifest_path
+            .parent()
+            .map_or_else(|| self.working_dir.clone(), |dir| dir.as_std_path().into());
+        let package_config =
+            resolve_package_config(package_config, &self.signing_overrides(), &manifest_dir);
+        if let Some(cert_path) = &package_config.cert_path
+            && !self.fs.exists(cert_path)
+        {
+            return Err(SignActionError::MissingCertFile(cert_path.clone()));
+        }
+
+        // Export drivers keep the .dll extension as it is the name their import
+        // library refers to
+        let driver_binary_extension = if export_driver {
+            "dll"
+        } else {
+            match wdk_metadata.driver_model {
+                DriverConfig::Kmdf(_) | DriverConfig::Wdm => "sys",
+                DriverConfig::Umdf(_) => "dll",
+            }
+        };
+        let driver_binary_path =
+            package_dir.join(format!("{package_name}.{driver_binary_extension}"));
+        if !self.fs.exists(&driver_binary_path) {
+            return Err(SignActionError::MissingDriverBinary(driver_binary_path));
+        }
+
+        if export_driver {
+            self.run_signtool_sign(&package_config, &driver_binary_path)?;
+            if self.verify_signature {
+                self.run_signtool_verify(&driver_binary_path)?;
+            }
+            return Ok(());
+        }
+
+        let cat_file_path = package_dir.join(format!("{package_name}.cat"));
+        if self.is_cat_file_outdated(&driver_binary_path, &cat_file_path) {
+            self.run_inf2cat(&package_dir, target_arch)?;
+        } else {
+            debug!("Cat file is up to date with the driver binary");
+        }
+        self.run_signtool_sign(&package_config, &driver_binary_path)?;
+        self.run_signtool_sign(&package_config, &cat_file_path)?;
+
+        // The test certificate added to the package by `cargo wdk build` does
+        // not match the signatures of a package signed with another certificate
+        let test_cert_file_path = package_dir.join(format!("{WDR_LOCAL_TEST_CERT}.cer"));
+        if is_cert_configured(&package_config) && self.fs.exists(&test_cert_file_path) {
+            info!("Removing test certificate from package {package_name}");
+            self.fs.remove_file(&test_cert_file_path)?;
+        }
+
+        if self.verify_signature {
+            self.run_signtool_verify(&driver_binary_path)?;
+            self.run_signtool_verify(&cat_file_path)?;
+        }
+        Ok(())
+    }
+
+    /// Signing parameters of the action that override the package metadata
+    fn signing_overrides(&self) -> SigningOverrides<'_> {
+        SigningOverrides {
+            cert_store: self.cert_store,
+            cert_name: self.cert_name,
+            cert_path: self.cert_path.as_deref(),
+            cert_thumbprint: self.cert_thumbprint,
+            csp: self.csp,
+            key_container: self.key_container,
+            digest_algorithm: self.digest_algorithm,
+        }
+    }
+
+    /// Returns whether the cat file must be generated again because it is
+    /// missing or the driver binary was replaced after it was generated. The
+    /// hashes in the cat file do not cover the signature of the driver binary,
+    /// so signing the driver binary again does not make the cat file outdated.
+    fn is_cat_file_outdated(&self, driver_binary_path: &Path, cat_file_path: &Path) -> bool {
+        if !self.fs.exists(cat_file_path) {
+            debug!("Cat file {} is missing", cat_file_path.display());
+            return true;
+        }
+        match (
+            self.fs.modified(driver_binary_path),
+            self.fs.modified(cat_file_path),
+        ) {
+            (Ok(driver_binary_modified), Ok(cat_file_modified)) => {
+                driver_binary_modified > cat_file_modified
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                debug!("Cannot determine whether the cat file is up to date: {e}");
+                true
+            }
+        }
+    }
+
+    fn run_inf2cat(
+        &self,
+        package_dir: &Path,
+        target_arch: CpuArchitecture,
+    ) -> Result<(), SignActionError> {
+        info!("Running inf2cat as the driver binary changed since the cat file was generated");
+        let os_mapping = match target_arch {
+            CpuArchitecture::Amd64 => "10_x64",
+            CpuArchitecture::Arm64 => "Server10_arm64",
+        };
+        let args = [
+            &format!(
+                "/driver:{}",
+                package_dir.to_string_lossy().trim_start_matches("\\\\?\\")
+            ),
+            &format!("/os:{os_mapping}"),
+            "/uselocaltime",
+        ];
+        self.command_exec
+            .run("inf2cat", &args, None, None)
+            .map_err(SignActionError::Inf2CatCommand)?;
+        Ok(())
+    }
+
+    fn run_signtool_sign(
+        &self,
+        package_config: &PackageConfig,
+        file_path: &Path,
+    ) -> Result<(), SignActionError> {
+        info!("Signing {} using signtool", file_path.display());
+        let args = signtool_sign_args(package_config, file_path);
+        let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+        self.command_exec
+            .run("signtool", &args, None, None)
+            .map_err(SignActionError::SignCommand)?;
+        Ok(())
+    }
+
+    fn run_signtool_verify(&self, file_path: &Path) -> Result<(), SignActionError> {
+        info!("Verifying {} using signtool", file_path.display());
+        let file_path = file_path.to_string_lossy();
+        self.command_exec
+            .run("signtool", &["verify", "/v", "/pa", &file_path], None, None)
+            .map_err(SignActionError::SignVerificationCommand)?;
+        Ok(())
+    }
+}
+
+/// Returns whether a certificate other than the test certificate is
+/// configured to sign with
+const fn is_cert_configured(package_config: &PackageConfig) -> bool {
+    package_config.cert_path.is_some()
+        || package_config.cert_name.is_some()
+        || package_config.cert_thumbprint.is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        os::windows::process::ExitStatusExt,
+        path::{Path, PathBuf},
+        process::{ExitStatus, Output},
+        time::{Duration, SystemTime},
+    };
+
+    use mockall::Sequence;
+
+    use crate::{
+        actions::{
+            Profile,
+            sign::{SignAction, SignActionError, SignActionParams},
+        },
+        providers::{
+            error::CommandError,
+            exec::MockCommandExec,
+            fs::MockFs,
+            metadata::MockMetadata,
+        },
+    };
+
+    const DRIVER_NAME: &str = "sample-kmdf";
+    const CERT_NAME: &str = "Contoso Release";
+
+    #[test]
+    fn given_a_package_with_an_up_to_date_cat_file_when_signing_then_driver_binary_and_cat_file_are_signed()
+     {
+        let cwd = PathBuf::from(r"C:\tmp\sample-kmdf");
+        let package_dir = cwd
+            .join("target")
+            .join("release")
+            .join("sample_kmdf_package");
+        let packaged_at = SystemTime::UNIX_EPOCH + Duration::from_secs(200);
+
+        let mut mock_exec = MockCommandExec::new();
+        let mut sequence = Sequence::new();
+        for file_name in ["sample_kmdf.sys", "sample_kmdf.cat"] {
+            expect_signtool_sign(&mut mock_exec, &mut sequence, package_dir.join(file_name));
+        }
+        let mut mock_fs = mock_fs_with_package(
+            &package_dir,
+            packaged_at - Duration::from_secs(100),
+            packaged_at,
+        );
+        let test_cert_file_path = package_dir.join("WDRLocalTestCert.cer");
+        mock_fs
+            .expect_exists()
+            .withf(move |path| path == test_cert_file_path)
+            .returning(|_| true);
+        mock_fs
+            .expect_remove_file()
+            .withf(|path| path.ends_with("WDRLocalTestCert.cer"))
+            .once()
+            .returning(|_| Ok(()));
+
+        let result = run_sign_action(&cwd, Some(&Profile::Release), &mock_exec, &mock_fs);
+
+        assert!(result.is_ok(), "sign failed unexpectedly: {result:?}");
+    }
+
+    #[test]
+    fn given_a_driver_binary_newer_than_the_cat_file_when_signing_then_cat_file_is_generated_again()
+    {
+        let cwd = PathBuf::from(r"C:\tmp\sample-kmdf");
+        let package_dir = cwd
+            .join("target")
+            .join("release")
+            .join("sample_kmdf_package");
+        let packaged_at = SystemTime::UNIX_EPOCH + Duration::from_secs(200);
+
+        let mut mock_exec = MockCommandExec::new();
+        let mut sequence = Sequence::new();
+        let expected_driver_arg = format!("/driver:{}", package_dir.to_string_lossy());
+        mock_exec
+            .expect_run()
+            .withf(move |command, args, _, _| {
+                command == "inf2cat" && args.first() == Some(&expected_driver_arg.as_str())
+            })
+            .once()
+            .in_sequence(&mut sequence)
+            .returning(|_, _, _, _| Ok(success_output()));
+        for file_name in ["sample_kmdf.sys", "sample_kmdf.cat"] {
+            expect_signtool_sign(&mut mock_exec, &mut sequence, package_dir.join(file_name));
+        }
+        let mut mock_fs = mock_fs_with_package(
+            &package_dir,
+            packaged_at + Duration::from_secs(100),
+            packaged_at,
+        );
+        mock_fs
+            .expect_exists()
+            .withf(|path| path.ends_with("WDRLocalTestCert.cer"))
+            .returning(|_| false);
+
+        let result = run_sign_action(&cwd, Some(&Profile::Release), &mock_exec, &mock_fs);
+
+        assert!(result.is_ok(), "sign failed unexpectedly: {result:?}");
+    }
+
+    #[test]
+    fn given_a_driver_that_is_not_packaged_when_signing_then_missing_driver_package_is_returned() {
+        let cwd = PathBuf::from(r"C:\tmp\sample-kmdf");
+        let package_dir = cwd.join("target").join("debug").join("sample_kmdf_package");
+
+        let mut mock_fs = MockFs::new();
+        mock_fs
+            .expect_exists()
+            .withf(move |path| path == package_dir)
+            .returning(|_| false);
+
+        let result = run_sign_action(&cwd, None, &MockCommandExec::new(), &mock_fs);
+
+        assert!(matches!(
+            result,
+            Err(SignActionError::MissingDriverPackage(_))
+        ));
+    }
+
+    #[test]
+    fn given_signtool_fails_when_signing_then_sign_command_error_is_returned() {
+        let cwd = PathBuf::from(r"C:\tmp\sample-kmdf");
+        let package_dir = cwd.join("target").join("debug").join("sample_kmdf_package");
+        let packaged_at = SystemTime::UNIX_EPOCH + Duration::from_secs(200);
+
+        let mut mock_exec = MockCommandExec::new();
+        mock_exec
+            .expect_run()
+            .withf(|command, _, _, _| command == "signtool")
+            .once()
+            .returning(|_, _, _, _| {
+                let output = Output {
+                    status: ExitStatus::from_raw(1),
+                    stdout: vec![],
+                    stderr: vec![],
+                };
+                Err(CommandError::from_output("signtool", &[], &output))
+            });
+        let mock_fs = mock_fs_with_package(
+            &package_dir,
+            packaged_at - Duration::from_secs(100),
+            packaged_at,
+        );
+
+        let result = run_sign_action(&cwd, None, &mock_exec, &mock_fs);
+
+        assert!(matches!(result, Err(SignActionError::SignCommand(_))));
+    }
+
+    fn run_sign_action(
+        cwd: &Path,
+        profile: Option<&Profile>,
+        mock_exec: &MockCommandExec,
+        mock_fs: &MockFs,
+    ) -> Result<(), SignActionError> {
+        let cargo_metadata = get_cargo_metadata(cwd);
+        let mut mock_metadata = MockMetadata::new();
+        mock_metadata
+            .expect_get_cargo_metadata_at_path()
+            .returning(move |_| {
+                Ok(serde_json::from_str(&cargo_metadata).expect("invalid cargo metadata"))
+            });
+
+        SignAction::new(
+            &SignActionParams {
+                working_dir: cwd,
+                profile,
+                target_arch: None,
+                package_dir: None,
+                cert_store: Some("My"),
+                cert_name: Some(CERT_NAME),
+                cert_path: None,
+                cert_thumbprint: None,
+                csp: None,
+                key_container: None,
+                digest_algorithm: None,
+                verify_signature: false,
+            },
+            mock_exec,
+            mock_fs,
+            &mock_metadata,
+        )
+        .expect("Failed to init sign action")
+        .run()
+    }
+
+    fn expect_signtool_sign(
+        mock_exec: &mut MockCommandExec,
+        sequence: &mut Sequence,
+        file_path: PathBuf,
+    ) {
+        mock_exec
+            .expect_run()
+            .withf(move |command, args, _, _| {
+                command == "signtool"
+                    && args.windows(2).any(|w| w == ["/s", "My"])
+                    && args.windows(2).any(|w| w == ["/n", CERT_NAME])
+                    && args.last() == Some(&file_path.to_string_lossy().as_ref())
+            })
+            .once()
+            .in_sequence(sequence)
+            .returning(|_, _, _, _| Ok(success_output()));
+    }
+
+    /// Mocks the file system with a driver package containing a driver binary
+    /// and a cat file last modified at the given times
+    fn mock_fs_with_package(
+        package_dir: &Path,
+        driver_binary_modified: SystemTime,
+        cat_file_modified: SystemTime,
+    ) -> MockFs {
+        let mut mock_fs = MockFs::new();
+        for path in [
+            package_dir.to_path_buf(),
+            package_dir.join("sample_kmdf.sys"),
+            package_dir.join("sample_kmdf.cat"),
+        ] {
+            mock_fs
+                .expect_exists()
+                .withf(move |p| p == path)
+                .returning(|_| true);
+        }
+        for (file_name, modified) in [
+            ("sample_kmdf.sys", driver_binary_modified),
+            ("sample_kmdf.cat", cat_file_modified),
+        ] {
+            let path = package_dir.join(file_name);
+            mock_fs
+                .expect_modified()
+                .withf(move |p| p == path)
+                .returning(move |_| Ok(modified));
+        }
+        mock_fs
+    }
+
+    fn success_output() -> Output {
+        Output {
+            status: ExitStatus::default(),
+            stdout: vec![],
+            stderr: vec![],
+        }
+    }
+
+    fn get_cargo_metadata(root_dir: &Path) -> String {
+        let normalized_root = root_dir.to_string_lossy().replace('\\', "/");
+        let package_id = format!("path+file:///{normalized_root}#{DRIVER_NAME}@0.0.1");
+        format!(
+            r#"
+            {{
+                "target_directory": "{target_directory}",
+                "workspace_root": "{workspace_root}",
+                "packages": [
+                    {{
+                        "name": "{DRIVER_NAME}",
+                        "version": "0.0.1",
+                        "id": "{package_id}",
+                        "dependencies": [],
+                        "targets": [
+                            {{
+                                "kind": ["cdylib"],
+                                "crate_types": ["cdylib"],
+                                "name": "{DRIVER_NAME}",
+                                "src_path": "{src_path}",
+                                "edition": "2021",
+                                "doc": true,
+                                "doctest": false,
+                                "test": true
+                            }}
+                        ],
+                        "features": {{}},
+                        "manifest_path": "{manifest_path}",
+                        "authors": [],
+                        "categories": [],
+                        "keywords": [],
+                        "edition": "2021",
+                        "metadata": {{
+                            "wdk": {{
+                                "driver-model": {{
+                                    "driver-type": "KMDF",
+                                    "kmdf-version-major": 1,
+                                    "target-kmdf-version-minor": 33
+                                }}
+                            }}
+                        }}
+                    }}
+                ],
+                "workspace_members": ["{package_id}"],
+                "metadata": null,
+                "version": 1
+            }}"#,
+            target_directory = root_dir.join("target").to_string_lossy().escape_default(),
+            workspace_root = root_dir.to_string_lossy().escape_default(),
+            src_path = root_dir
+                .join("src")
+                .join("lib.rs")
+                .to_string_lossy()
+                .escape_default(),
+            manifest_path = root_dir
+                .join("Cargo.toml")
+                .to_string_lossy()
+                .escape_default(),
+        )
+    }
+}
@@ -3,7 +3,11 @@
 //! This module defines the top-level CLI layer, its argument types and
 //! structures used for parsing and validating arguments for various
 //! subcommands.
-use std::path::{Path, PathBuf};
+use std::{
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use anyhow::{Ok, Result};
 use clap::{ArgGroup, Args, Parser, Subcommand};
@@ -12,13 +16,19 @@
 use wdk_build::CpuArchitecture;
 
 use crate::actions::{
+    DriverTemplate,
     DriverType,
     KMDF_STR,
     Profile,
     UMDF_STR,
     WDM_STR,
-    build::{BuildAction, BuildActionParams},
-    new::NewAction,
+    WdkSource,
+    build::{BuildAction, BuildActionParams, HlkArtifacts, PackageStages, SignMode},
+    clean::{CleanAction, CleanActionParams},
+    deploy::{DeployAction, DeployActionParams},
+    new::{InterfaceGuid, NewAction},
+    self_test::{SelfTestAction, SelfTestActionParams},
+    sign::{SignAction, SignActionParams},
 };
 #[double]
 use crate::providers::{exec::CommandExec, fs::Fs, metadata::Metadata, wdk_build::WdkBuild};
@@ -26,6 +36,7 @@
 const ABOUT_STRING: &str = "cargo-wdk is a cargo extension that can be used to create and build \
                             Windows Rust driver projects.";
 const CARGO_WDK_BIN_NAME: &str = "cargo wdk";
+const DRIVER_TYPE_ARG_ID: &str = "driver_type_name";
 
 /// Arguments for the `new` subcommand
 #[derive(Debug, Args)]
@@ -33,7 +44,7 @@
     group(
         ArgGroup::new("driver_type")
             .required(true)
-            .args([KMDF_STR, UMDF_STR, WDM_STR])
+            .args([KMDF_STR, UMDF_STR, WDM_STR, DRIVER_TYPE_ARG_ID])
     ),
 )]
 pub struct NewArgs {
@@ -49,6 +60,14 @@ pub struct NewArgs {
     #[arg(long)]
     pub wdm: bool,
 
+    /// Create a driver crate of the specified driver type
+    #[arg(long = "driver-type", value_name = "DRIVER_TYPE", ignore_case = true)]
+    pub driver_type_name: Option<DriverType>,
+
+    /// Template to create the driver crate from
+    #[arg(long, ignore_case = true, default_value_t = DriverTemplate::Function)]
+    pub template: DriverTemplate,
+
     /// Path at which the new driver crate should be created
     #[arg(required = true)]
     pub path: Option<PathBuf>,
@@ -56,7 +75,8 @@ pub struct NewArgs {
 
 impl NewArgs {
     /// Returns the variant of `DriverType` based on which of the `driver_type`
-    /// flags, `--kmdf`, `--umdf` or `--wdm` was passed to the `new` command.
+    /// flags, `--kmdf`, `--umdf`, `--wdm` or `--driver-type` was passed to the
+    /// `new` command.
     ///
     /// # Returns
     ///
@@ -64,7 +84,9 @@ impl NewArgs {
     const fn driver_type(&self) -> DriverType {
         // `ArgGroup` setting on `NewArgs` ensures
         // exactly one of these flags is set
-        if self.kmdf {
+        if let Some(driver_type) = self.driver_type_name {
+            driver_type
+        } else if self.kmdf {
             DriverType::Kmdf
         } else if self.umdf {
             DriverType::Umdf
@@ -76,22 +98,310 @@ const fn driver_type(&self) -> DriverType {
 
 /// Arguments for the `build` subcommand
 #[derive(Debug, Args)]
+#[allow(clippy::struct_excessive_bools)] // Each bool maps to an independent CLI flag
 pub struct BuildArgs {
     /// Build artifacts with the specified profile
     #[arg(long, ignore_case = true)]
     pub profile: Option<Profile>,
 
-    /// Build for the target architecture
-    #[arg(long, ignore_case = true)]
-    pub target_arch: Option<CpuArchitecture>,
+    /// Build for the target architectures. Multiple architectures can be
+    /// separated by commas
+    #[arg(long, ignore_case = true, value_delimiter = ',')]
+    pub target_arch: Vec<CpuArchitecture>,
+
+    /// Build for all supported target architectures
+    #[arg(long, conflicts_with = "target_arch")]
+    pub all_archs: bool,
 
     /// Verify the signature
-    #[arg(long)]
+    #[arg(long, conflicts_with = "no_sign")]
     pub verify_signature: bool,
 
     /// Build sample class driver project
     #[arg(long)]
     pub sample: bool,
+
+    /// Emit an HLK artifacts folder next to the driver package
+    #[arg(long)]
+    pub hlk: bool,
+
+    /// Generate an HLK playlist skeleton in the HLK artifacts folder
+    #[arg(long, requires = "hlk")]
+    pub hlk_playlist: bool,
+
+    /// Create a cab file of the driver package for Partner Center attestation
+    /// signing, along with a manifest of its files
+    #[arg(long)]
+    pub cab: bool,
+
+    /// Skip signing the driver package. Same as --sign-mode none
+    #[arg(long, conflicts_with = "sign_mode")]
+    pub no_sign: bool,
+
+    /// How to sign the driver package: `test` generates the local test
+    /// certificate if no certificate is configured, `production` only signs
+    /// with the configured certificate and `none` leaves the package unsigned
+    /// [default: test]
+    #[arg(long, ignore_case = true)]
+    pub sign_mode: Option<SignMode>,
+
+    /// Skip verifying the INF file of the driver package with infverif
+    #[arg(long)]
+    pub no_verify: bool,
+
+    /// Package the artifacts of a previous build without running cargo build
+    #[arg(long)]
+    pub package_only: bool,
+
+    /// Package the drivers even if their packages are up to date. Packages are
+    /// otherwise only recreated when their source files or packaging settings
+    /// change
+    #[arg(long)]
+    pub force_repackage: bool,
+
+    /// Fail the build when infverif reports warnings for the INF file of a
+    /// driver package
+    #[arg(long, conflicts_with = "no_verify")]
+    pub treat_inf_warnings_as_errors: bool,
+
+    /// Name of the certificate store containing the signing certificate
+    #[arg(long, conflicts_with = "no_sign")]
+    pub cert_store: Option<String>,
+
+    /// Name of the signing certificate
+    #[arg(long, conflicts_with = "no_sign")]
+    pub cert_name: Option<String>,
+
+    /// SHA1 thumbprint of the signing certificate in the certificate store
+    #[arg(long, conflicts_with_all = ["no_sign", "cert_name"])]
+    pub cert_thumbprint: Option<String>,
+
+    /// Path to a PFX file containing the signing certificate, or to the
+    /// certificate file of a key kept by a CSP
+    #[arg(long, conflicts_with_all = ["no_sign", "cert_store", "cert_name", "cert_thumbprint"])]
+    pub cert_path: Option<PathBuf>,
+
+    /// Cryptographic service provider holding the private key of the
+    /// certificate in --cert-path, ex. the provider of an HSM or token
+    #[arg(long, requires_all = ["cert_path", "key_container"])]
+    pub csp: Option<String>,
+
+    /// Key container of the private key in the CSP
+    #[arg(long, requires = "csp")]
+    pub key_container: Option<String>,
+
+    /// Digest algorithm to sign with, ex. SHA256 or SHA384 [default: SHA256]
+    #[arg(long, conflicts_with = "no_sign")]
+    pub digest_algorithm: Option<String>,
+
+    /// Source of the WDK to build and package with
+    #[arg(long, ignore_case = true, default_value_t = WdkSource::Installed)]
+    pub wdk_source: WdkSource,
+
+    /// Write the results of infverif and signtool verify to a JSON file
+    #[arg(long, value_name = "PATH")]
+    pub verification_report: Option<PathBuf>,
+
+    /// Write the warnings and errors of infverif and signtool verify to a SARIF
+    /// file, with infverif diagnostics located in the .inx file
+    #[arg(long, value_name = "PATH")]
+    pub sarif: Option<PathBuf>,
+
+    /// Directory for all generated artifacts, overriding `CARGO_TARGET_DIR`
+    /// and `build.target-dir`
+    #[arg(long, value_name = "PATH")]
+    pub target_dir: Option<PathBuf>,
+
+    /// Directory to create the driver packages in instead of the target
+    /// directory
+    #[arg(long, value_name = "PATH")]
+    pub package_dir: Option<PathBuf>,
+}
+
+impl BuildArgs {
+    /// Returns the variant of `HlkArtifacts` based on the `--hlk` and
+    /// `--hlk-playlist` flags passed to the `build` command.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<HlkArtifacts>` - `None` when no HLK artifacts are requested
+    const fn hlk_artifacts(&self) -> Option<HlkArtifacts> {
+        // `requires` setting on `hlk_playlist` ensures
+        // `--hlk` is always set along with `--hlk-playlist`
+        match (self.hlk, self.hlk_playlist) {
+            (true, true) => Some(HlkArtifacts::FolderWithPlaylist),
+            (true, false) => Some(HlkArtifacts::Folder),
+            (false, _) => None,
+        }
+    }
+
+    /// Returns the target architectures to build for based on the
+    /// `--target-arch` and `--all-archs` flags passed to the `build` command.
+    fn target_archs(&self) -> Vec<CpuArchitecture> {
+        if self.all_archs {
+            vec![CpuArchitecture::Amd64, CpuArchitecture::Arm64]
+        } else {
+            self.target_arch.clone()
+        }
+    }
+
+    /// Returns the `PackageStages` to run based on the `--no-sign`,
+    /// `--sign-mode` and `--no-verify` flags passed to the `build` command.
+    fn package_stages(&self) -> PackageStages {
+        PackageStages {
+            sign_mode: if self.no_sign {
+                SignMode::None
+            } else {
+                self.sign_mode.unwrap_or_default()
+            },
+            verify: !self.no_verify,
+        }
+    }
+
+    /// Checks that no signing flag is passed along with `--sign-mode none`.
+    /// Clap only rejects the signing flags along with `--no-sign`, as its
+    /// conflicts cannot depend on the value of `--sign-mode`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the first signing flag passed when the driver
+    /// package is not signed.
+    fn check_signing_args(&self) -> Result<()> {
+        if self.package_stages().sign_mode.signs() {
+            return Ok(());
+        }
+        let signing_args = [
+            ("--verify-signature", self.verify_signature),
+            ("--cert-store", self.cert_store.is_some()),
+            ("--cert-name", self.cert_name.is_some()),
+            ("--cert-thumbprint", self.cert_thumbprint.is_some()),
+            ("--cert-path", self.cert_path.is_some()),
+            ("--digest-algorithm", self.digest_algorithm.is_some()),
+        ];
+        if let Some((arg, _)) = signing_args.iter().find(|(_, is_set)| *is_set) {
+            return Err(anyhow::anyhow!(
+                "{arg} cannot be used when the driver package is not signed"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Arguments for the `deploy` subcommand
+#[derive(Debug, Args)]
+pub struct DeployArgs {
+    /// Deploy artifacts built with the specified profile
+    #[arg(long, ignore_case = true)]
+    pub profile: Option<Profile>,
+
+    /// Deploy artifacts built for the target architecture
+    #[arg(long, ignore_case = true)]
+    pub target_arch: Option<CpuArchitecture>,
+
+    /// Name of the remote test machine to deploy to. Deploys to the local
+    /// machine if not specified
+    #[arg(long)]
+    pub target_machine: Option<String>,
+
+    /// Hardware ID of a root enumerated device to create for the driver
+    #[arg(long)]
+    pub hardware_id: Option<String>,
+
+    /// Enable test signing on the test machine and trust the test certificate
+    #[arg(long)]
+    pub enable_test_signing: bool,
+
+    /// Restart the test machine after installing the driver packages
+    #[arg(long)]
+    pub reboot: bool,
+}
+
+/// Arguments for the `sign` subcommand
+#[derive(Debug, Args)]
+pub struct SignArgs {
+    /// Sign driver packages built with the specified profile
+    #[arg(long, ignore_case = true)]
+    pub profile: Option<Profile>,
+
+    /// Sign driver packages built for the target architecture
+    #[arg(long, ignore_case = true)]
+    pub target_arch: Option<CpuArchitecture>,
+
+    /// Directory the driver packages were created in with `build
+    /// --package-dir`, instead of the target directory
+    #[arg(long, value_name = "PATH")]
+    pub package_dir: Option<PathBuf>,
+
+    /// Name of the certificate store containing the signing certificate
+    #[arg(long)]
+    pub cert_store: Option<String>,
+
+    /// Name of the signing certificate
+    #[arg(long)]
+    pub cert_name: Option<String>,
+
+    /// SHA1 thumbprint of the signing certificate in the certificate store
+    #[arg(long, conflicts_with = "cert_name")]
+    pub cert_thumbprint: Option<String>,
+
+    /// Path to a PFX file containing the signing certificate, or to the
+    /// certificate file of a key kept by a CSP
+    #[arg(long, conflicts_with_all = ["cert_store", "cert_name", "cert_thumbprint"])]
+    pub cert_path: Option<PathBuf>,
+
+    /// Cryptographic service provider holding the private key of the
+    /// certificate in --cert-path, ex. the provider of an HSM or token
+    #[arg(long, requires_all = ["cert_path", "key_container"])]
+    pub csp: Option<String>,
+
+    /// Key container of the private key in the CSP
+    #[arg(long, requires = "csp")]
+    pub key_container: Option<String>,
+
+    /// Digest algorithm to sign with, ex. SHA256 or SHA384 [default: SHA256]
+    #[arg(long)]
+    pub digest_algorithm: Option<String>,
+
+    /// Verify the signatures after signing
+    #[arg(long)]
+    pub verify_signature: bool,
+}
+
+/// Arguments for the `clean` subcommand
+#[derive(Debug, Args)]
+pub struct CleanArgs {
+    /// Clean artifacts built with the specified profile
+    #[arg(long, ignore_case = true)]
+    pub profile: Option<Profile>,
+
+    /// Clean artifacts built for the target architecture
+    #[arg(long, ignore_case = true)]
+    pub target_arch: Option<CpuArchitecture>,
+
+    /// Also remove the WDRLocalTestCert test certificate from the
+    /// WDRTestCertStore certificate store
+    #[arg(long)]
+    pub remove_cert: bool,
+}
+
+/// Arguments for the `self-test` subcommand
+#[derive(Debug, Args)]
+pub struct SelfTestArgs {
+    /// Directory to create the test driver projects in. Defaults to the
+    /// temporary directory
+    #[arg(long)]
+    pub path: Option<PathBuf>,
+
+    /// Check that the generated projects compile for the target architectures.
+    /// Multiple architectures can be separated by commas. Defaults to the host
+    /// architecture
+    #[arg(long, ignore_case = true, value_delimiter = ',')]
+    pub target_arch: Vec<CpuArchitecture>,
+
+    /// Only compare the generated projects with the golden files, without
+    /// compiling them
+    #[arg(long)]
+    pub no_compile: bool,
 }
 
 /// Subcommands
@@ -101,6 +411,58 @@ pub enum Subcmd {
     New(NewArgs),
     #[clap(name = "build", about = "Build the Windows Driver Kit project")]
     Build(BuildArgs),
+    #[clap(
+        name = "deploy",
+        about = "Deploy the Windows Driver Kit project to a test machine"
+    )]
+    Deploy(DeployArgs),
+    #[clap(
+        name = "sign",
+        about = "Sign the driver packages created by build with the configured certificate"
+    )]
+    Sign(SignArgs),
+    #[clap(
+        name = "clean",
+        about = "Remove the driver packages and test certificate created by build"
+    )]
+    Clean(CleanArgs),
+    #[clap(
+        name = "self-test",
+        about = "Validate the driver project templates of cargo wdk new"
+    )]
+    SelfTest(SelfTestArgs),
+}
+
+/// Format of the final result message of a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// Failures are reported as log messages only
+    Human,
+    /// The result, including the failure category, is also printed to stdout
+    /// as a single line of JSON
+    Json,
+}
+
+impl FromStr for MessageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "human" => std::result::Result::Ok(Self::Human),
+            "json" => std::result::Result::Ok(Self::Json),
+            _ => Err(format!("'{s}' is not a valid message format")),
+        }
+    }
+}
+
+impl Display for MessageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Human => "human",
+            Self::Json => "json",
+        };
+        write!(f, "{s}")
+    }
 }
 
 /// Top level command line interface for cargo wdk
@@ -119,6 +481,9 @@ pub struct Cli {
     pub cargo_command: String,
     #[clap(subcommand)]
     pub sub_cmd: Subcmd,
+    /// Format of the result message
+    #[clap(long, global = true, ignore_case = true, default_value_t = MessageFormat::Human)]
+    pub message_format: MessageFormat,
     #[command(flatten)]
     #[clap(next_help_heading = "Verbosity")]
     pub verbose: Verbosity,
@@ -153,6 +518,8 @@ pub fn run(self) -> Result<()> {
                 NewAction::new(
                     cli_args.path.as_ref().unwrap_or(&std::env::current_dir()?),
                     cli_args.driver_type(),
+                    cli_args.template,
+                    InterfaceGuid::generate(),
                     self.verbose,
                     &command_exec,
                     &fs,
@@ -161,13 +528,32 @@ pub fn run(self) -> Result<()> {
                 Ok(())
             }
             Subcmd::Build(cli_args) => {
+                cli_args.check_signing_args()?;
                 BuildAction::new(
                     &BuildActionParams {
                         working_dir: Path::new("."), // Using current dir as working dir
                         profile: cli_args.profile.as_ref(),
-                        target_arch: cli_args.target_arch,
+                        target_archs: cli_args.target_archs(),
                         verify_signature: cli_args.verify_signature,
                         is_sample_class: cli_args.sample,
+                        hlk_artifacts: cli_args.hlk_artifacts(),
+                        cab: cli_args.cab,
+                        package_only: cli_args.package_only,
+                        force_repackage: cli_args.force_repackage,
+                        treat_inf_warnings_as_errors: cli_args.treat_inf_warnings_as_errors,
+                        package_stages: cli_args.package_stages(),
+                        cert_store: cli_args.cert_store.as_deref(),
+                        cert_name: cli_args.cert_name.as_deref(),
+                        cert_path: cli_args.cert_path.as_deref(),
+                        cert_thumbprint: cli_args.cert_thumbprint.as_deref(),
+                        csp: cli_args.csp.as_deref(),
+                        key_container: cli_args.key_container.as_deref(),
+                        digest_algorithm: cli_args.digest_algorithm.as_deref(),
+                        wdk_source: cli_args.wdk_source,
+                        verification_report_path: cli_args.verification_report.as_deref(),
+                        sarif_path: cli_args.sarif.as_deref(),
+                        target_dir: cli_args.target_dir.as_deref(),
+                        package_dir: cli_args.package_dir.as_deref(),
                         verbosity_level: self.verbose,
                     },
                     &wdk_build,
@@ -178,23 +564,134 @@ pub fn run(self) -> Result<()> {
                 .run()?;
                 Ok(())
             }
+            Subcmd::Deploy(cli_args) => {
+                DeployAction::new(
+                    &DeployActionParams {
+                        working_dir: Path::new("."), // Using current dir as working dir
+                        profile: cli_args.profile.as_ref(),
+                        target_arch: cli_args.target_arch,
+                        target_machine: cli_args.target_machine.as_deref(),
+                        hardware_id: cli_args.hardware_id.as_deref(),
+                        enable_test_signing: cli_args.enable_test_signing,
+                        reboot: cli_args.reboot,
+                    },
+                    &command_exec,
+                    &fs,
+                    &metadata,
+                )?
+                .run()?;
+                Ok(())
+            }
+            Subcmd::Sign(cli_args) => {
+                SignAction::new(
+                    &SignActionParams {
+                        working_dir: Path::new("."), // Using current dir as working dir
+                        profile: cli_args.profile.as_ref(),
+                        target_arch: cli_args.target_arch,
+                        package_dir: cli_args.package_dir.as_deref(),
+                        cert_store: cli_args.cert_store.as_deref(),
+                        cert_name: cli_args.cert_name.as_deref(),
+                        cert_path: cli_args.cert_path.as_deref(),
+                        cert_thumbprint: cli_args.cert_thumbprint.as_deref(),
+                        csp: cli_args.csp.as_deref(),
+                        key_container: cli_args.key_container.as_deref(),
+                        digest_algorithm: cli_args.digest_algorithm.as_deref(),
+                        verify_signature: cli_args.verify_signature,
+                    },
+                    &command_exec,
+                    &fs,
+                    &metadata,
+                )?
+                .run()?;
+                Ok(())
+            }
+            Subcmd::Clean(cli_args) => {
+                CleanAction::new(
+                    &CleanActionParams {
+                        working_dir: Path::new("."), // Using current dir as working dir
+                        profile: cli_args.profile.as_ref(),
+                        target_arch: cli_args.target_arch,
+                        remove_cert: cli_args.remove_cert,
+                    },
+                    &command_exec,
+                    &fs,
+                    &metadata,
+                )?
+                .run()?;
+                Ok(())
+            }
+            Subcmd::SelfTest(cli_args) => {
+                SelfTestAction::new(
+                    &SelfTestActionParams {
+                        working_dir: &cli_args.path.unwrap_or_else(std::env::temp_dir),
+                        target_archs: cli_args.target_arch,
+                        compile: !cli_args.no_compile,
+                        verbosity_level: self.verbose,
+                    },
+                    &command_exec,
+                    &fs,
+                )?
+                .run()?;
+                Ok(())
+            }
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use wdk_build::CpuArchitecture;
+
     use crate::{
-        actions::DriverType,
-        cli::{Cli, NewArgs},
+        actions::{
+            DriverTemplate,
+            DriverType,
+            WdkSource,
+            build::{HlkArtifacts, PackageStages, SignMode},
+        },
+        cli::{BuildArgs, Cli, MessageFormat, NewArgs},
     };
 
+    /// Build arguments as parsed from `cargo wdk build` without any flag
+    fn default_build_args() -> BuildArgs {
+        BuildArgs {
+            profile: None,
+            target_arch: vec![],
+            all_archs: false,
+            verify_signature: false,
+            sample: false,
+            hlk: false,
+            hlk_playlist: false,
+            cab: false,
+            no_sign: false,
+            sign_mode: None,
+            no_verify: false,
+            package_only: false,
+            force_repackage: false,
+            treat_inf_warnings_as_errors: false,
+            cert_store: None,
+            cert_name: None,
+            cert_path: None,
+            cert_thumbprint: None,
+            csp: None,
+            key_container: None,
+            digest_algorithm: None,
+            wdk_source: WdkSource::Installed,
+            verification_report: None,
+            sarif: None,
+            target_dir: None,
+            package_dir: None,
+        }
+    }
+
     #[test]
     fn new_args_driver_type_kmdf() {
         let args = NewArgs {
             kmdf: true,
             umdf: false,
             wdm: false,
+            driver_type_name: None,
+            template: DriverTemplate::Function,
             path: None,
         };
         assert_eq!(args.driver_type(), DriverType::Kmdf);
@@ -206,6 +703,8 @@ fn new_args_driver_type_umdf() {
             kmdf: false,
             umdf: true,
             wdm: false,
+            driver_type_name: None,
+            template: DriverTemplate::Function,
             path: None,
         };
         assert_eq!(args.driver_type(), DriverType::Umdf);
@@ -217,11 +716,146 @@ fn new_args_driver_type_wdm() {
             kmdf: false,
             umdf: false,
             wdm: true,
+            driver_type_name: None,
+            template: DriverTemplate::Function,
             path: None,
         };
         assert_eq!(args.driver_type(), DriverType::Wdm);
     }
 
+    #[test]
+    fn new_args_driver_type_from_driver_type_flag() {
+        let args = NewArgs {
+            kmdf: false,
+            umdf: false,
+            wdm: false,
+            driver_type_name: Some(DriverType::Umdf),
+            template: DriverTemplate::Filter,
+            path: None,
+        };
+        assert_eq!(args.driver_type(), DriverType::Umdf);
+    }
+
+    #[test]
+    fn build_args_hlk_artifacts() {
+        let cases = [
+            (false, false, None),
+            (true, false, Some(HlkArtifacts::Folder)),
+            (true, true, Some(HlkArtifacts::FolderWithPlaylist)),
+        ];
+        for (hlk, hlk_playlist, expected) in cases {
+            let args = BuildArgs {
+                hlk,
+                hlk_playlist,
+                ..default_build_args()
+            };
+            assert_eq!(args.hlk_artifacts(), expected);
+        }
+    }
+
+    #[test]
+    fn build_args_target_archs() {
+        let args = BuildArgs {
+            target_arch: vec![CpuArchitecture::Arm64],
+            ..default_build_args()
+        };
+        assert_eq!(args.target_archs(), vec![CpuArchitecture::Arm64]);
+
+        let args = BuildArgs {
+            all_archs: true,
+            ..default_build_args()
+        };
+        assert_eq!(
+            args.target_archs(),
+            vec![CpuArchitecture::Amd64, CpuArchitecture::Arm64]
+        );
+    }
+
+    #[test]
+    fn build_args_package_stages() {
+        let cases = [
+            (false, None, false, PackageStages::default()),
+            (
+                true,
+                None,
+                false,
+                PackageStages {
+                    sign_mode: SignMode::None,
+                    verify: true,
+                },
+            ),
+            (
+                false,
+                None,
+                true,
+                PackageStages {
+                    sign_mode: SignMode::Test,
+                    verify: false,
+                },
+            ),
+            (
+                false,
+                Some(SignMode::Production),
+                false,
+                PackageStages {
+                    sign_mode: SignMode::Production,
+                    verify: true,
+                },
+            ),
+            (
+                false,
+                Some(SignMode::None),
+                false,
+                PackageStages {
+                    sign_mode: SignMode::None,
+                    verify: true,
+                },
+            ),
+        ];
+        for (no_sign, sign_mode, no_verify, expected) in cases {
+            let args = BuildArgs {
+                no_sign,
+                sign_mode,
+                no_verify,
+                ..default_build_args()
+            };
+            assert_eq!(args.package_stages(), expected);
+        }
+    }
+
+    #[test]
+    fn build_args_signing_args_are_rejected_without_signing() {
+        let args = BuildArgs {
+            sign_mode: Some(SignMode::None),
+            cert_name: Some("WDRLocalTestCert".to_string()),
+            ..default_build_args()
+        };
+        assert_eq!(
+            args.check_signing_args().unwrap_err().to_string(),
+            "--cert-name cannot be used when the driver package is not signed"
+        );
+
+        let args = BuildArgs {
+            sign_mode: Some(SignMode::None),
+            verify_signature: true,
+            ..default_build_args()
+        };
+        assert!(args.check_signing_args().is_err());
+
+        let args = BuildArgs {
+            sign_mode: Some(SignMode::None),
+            ..default_build_args()
+        };
+        assert!(args.check_signing_args().is_ok());
+
+        let args = BuildArgs {
+            sign_mode: Some(SignMode::Production),
+            cert_name: Some("WDRLocalTestCert".to_string()),
+            ..default_build_args()
+        };
+        assert!(args.check_signing_args().is_ok());
+    }
+
     #[test]
     fn verbatim_path_is_rejected() {
         use std::path::PathBuf;
@@ -232,8 +866,11 @@ fn verbatim_path_is_rejected() {
                 kmdf: true,
                 umdf: false,
                 wdm: false,
+                driver_type_name: None,
+                template: DriverTemplate::Function,
                 path: Some(PathBuf::from(r"\\?\C:\some\path")),
             }),
+            message_format: MessageFormat::Human,
             verbose: clap_verbosity_flag::Verbosity::default(),
         };
 
@@ -11,9 +11,11 @@
 mod providers;
 mod trace;
 
-use anyhow::{Ok, Result};
+use std::process::ExitCode;
+
+use actions::FailureCategory;
 use clap::Parser;
-use cli::Cli;
+use cli::{Cli, MessageFormat};
 use tracing::error;
 
 #[cfg(test)]
@@ -22,20 +24,42 @@
 /// Main function for the [`cargo-wdk`][crate] CLI application.
 ///
 /// The main function parses the CLI input, sets up tracing and executes the
-/// command. If an error occurs during execution, it logs the error and exits
-/// with a non-zero status code.
+/// command. If an error occurs during execution, it logs the error along with
+/// its causes and exits with the exit code of the [`FailureCategory`] of the
+/// error. With
+/// `--message-format json`, the result is also printed to stdout as a single
+/// JSON object so that CI pipelines can branch on the failure category.
 ///
 /// # Returns
 ///
-/// `Result<()>`, which is `Ok` on success or an `anyhow::Error` on failure.
-///
-/// # Errors
-///
-/// This function will return an error if tracing initialization fails or if the
-/// CLI command execution fails.
-fn main() -> Result<()> {
+/// `ExitCode`, which is `ExitCode::SUCCESS` on success or the exit code of the
+/// failure category on failure.
+fn main() -> ExitCode {
     let cli: Cli = Cli::parse();
     trace::init_tracing(cli.verbose);
-    cli.run().inspect_err(|e| error!("{}", e))?;
-    Ok(())
+    let message_format = cli.message_format;
+    match cli.run() {
+        Ok(()) => {
+            if message_format == MessageFormat::Json {
+                println!("{}", serde_json::json!({ "success": true, "exit_code": 0 }));
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            error!("{e:?}");
+            let category = FailureCategory::from_error(&e);
+            if message_format == MessageFormat::Json {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "success": false,
+                        "exit_code": category.exit_code(),
+                        "category": category.to_string(),
+                        "message": format!("{e:#}"),
+                    })
+                );
+            }
+            ExitCode::from(category.exit_code())
+        }
+    }
 }
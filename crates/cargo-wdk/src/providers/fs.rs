@@ -19,11 +19,15 @@
         copy,
         create_dir,
         create_dir_all,
+        metadata,
         read_dir,
+        remove_dir_all,
+        remove_file,
         rename,
     },
     io::{Read, Write},
     path::Path,
+    time::SystemTime,
 };
 
 use mockall::automock;
@@ -68,6 +72,20 @@ pub fn rename(&self, src: &Path, dest: &Path) -> Result<(), FileError> {
         rename(src, dest).map_err(|e| FileError::RenameError(src.to_owned(), dest.to_owned(), e))
     }
 
+    pub fn remove_dir_all(&self, path: &Path) -> Result<(), FileError> {
+        remove_dir_all(path).map_err(|e| FileError::RemoveError(path.to_owned(), e))
+    }
+
+    pub fn remove_file(&self, path: &Path) -> Result<(), FileError> {
+        remove_file(path).map_err(|e| FileError::RemoveError(path.to_owned(), e))
+    }
+
+    pub fn modified(&self, path: &Path) -> Result<SystemTime, FileError> {
+        metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|e| FileError::MetadataError(path.to_owned(), e))
+    }
+
     pub fn read_file_to_string(&self, path: &Path) -> Result<String, FileError> {
         if !path.exists() {
             return Err(FileError::NotFound(path.to_owned()));
@@ -67,11 +67,15 @@ pub enum FileError {
         CreateDirError(PathBuf, #[source] io::Error),
         #[error("Failed to rename file from {0} to {1}")]
         RenameError(PathBuf, PathBuf, #[source] io::Error),
+        #[error("Failed to remove {0}")]
+        RemoveError(PathBuf, #[source] io::Error),
         #[error("Failed to get file type for directory entry {0:#?}")]
         DirFileTypeError(PathBuf, #[source] io::Error),
         #[error("Failed to read directory {0}")]
         ReadDirError(PathBuf, #[source] io::Error),
         #[error("Failed to read directory entries for {0}")]
         ReadDirEntriesError(PathBuf, #[source] io::Error),
+        #[error("Failed to read metadata of file {0}")]
+        MetadataError(PathBuf, #[source] io::Error),
     }
 }
@@ -1,7 +1,8 @@
 // Copyright (c) Microsoft Corporation
 // License: MIT OR Apache-2.0
 //! This module provides a wrapper around the `wdk-build` crate methods,
-//! focusing on the functionality required for detecting the WDK build number.
+//! focusing on the functionality required for detecting the WDK build number
+//! and selecting the WDK to use.
 //! It leverages the `mockall` crate to enable mocking of the `WdkBuild` struct
 //! for improved testability in unit tests.
 
@@ -9,6 +10,8 @@
 // The intellisense confusion seems to come from automock
 #![allow(dead_code)]
 #![allow(clippy::unused_self)]
+use std::{ffi::OsStr, path::Path};
+
 use mockall::automock;
 
 /// Provides limited access to wdk-build crate methods
@@ -20,4 +23,46 @@ impl WdkBuild {
     pub fn detect_wdk_build_number(&self) -> Result<u32, wdk_build::ConfigError> {
         wdk_build::detect_wdk_build_number()
     }
+
+    /// Makes WDK detection of this process, and of the commands it runs, use
+    /// the WDK at `wdk_content_root` instead of the installed WDK
+    pub fn use_wdk_content_root(&self, wdk_content_root: &Path) {
+        set_var("WDKContentRoot", wdk_content_root);
+    }
+
+    /// Makes this process, and the commands it runs, use the Windows SDK tools
+    /// at `windows_sdk_bin_path` instead of the installed Windows SDK
+    pub fn use_windows_sdk_bin_path(&self, windows_sdk_bin_path: &Path) {
+        set_var("WindowsSdkBinPath", windows_sdk_bin_path);
+    }
+}
+
+/// Safely sets an environment variable. Will not compile if crate is not
+/// targeted for Windows.
+///
+/// This function provides a safe wrapper around [`std::env::set_var`] that
+/// became unsafe in Rust 2024 edition.
+#[cfg(target_os = "windows")]
+fn set_var<K, V>(key: K, value: V)
+where
+    K: AsRef<OsStr>,
+    V: AsRef<OsStr>,
+{
+    // SAFETY: this function is only conditionally compiled for windows targets, and
+    // env::set_var is always safe for windows targets
+    unsafe {
+        std::env::set_var(key, value);
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn set_var<K, V>(_key: K, _value: V)
+where
+    K: AsRef<OsStr>,
+    V: AsRef<OsStr>,
+{
+    compile_error!(
+        "windows-drivers-rs is designed to be run on a Windows host machine in a WDK environment. \
+         Please build using a Windows target."
+    );
 }
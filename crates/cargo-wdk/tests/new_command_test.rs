@@ -27,7 +27,7 @@ fn if_no_driver_type_given_command_fails() {
     test_command_invocation(&[], true, false, |stdout, stderr| {
         assert!(stdout.is_empty());
         assert!(stderr.contains("error: the following required arguments were not provided:"));
-        assert!(stderr.contains("<--kmdf|--umdf|--wdm>"));
+        assert!(stderr.contains("<--kmdf|--umdf|--wdm|--driver-type <DRIVER_TYPE>>"));
     });
 }
 
@@ -39,12 +39,38 @@ fn if_multiple_driver_types_given_command_fails() {
     });
 }
 
+#[test]
+fn if_driver_type_flag_given_with_driver_type_option_command_fails() {
+    test_command_invocation(
+        &["--kmdf", "--driver-type", "umdf"],
+        true,
+        false,
+        |stdout, stderr| {
+            assert!(stdout.is_empty());
+            assert!(stderr.contains("error: the argument '--kmdf' cannot be used with"));
+        },
+    );
+}
+
+#[test]
+fn if_template_unsupported_for_driver_type_command_fails() {
+    test_command_invocation(
+        &["--driver-type", "wdm", "--template", "filter"],
+        true,
+        false,
+        |stdout, stderr| {
+            assert!(stdout.is_empty());
+            assert!(stderr.contains("The filter template is not available for wdm drivers"));
+        },
+    );
+}
+
 #[test]
 fn if_missing_required_arguments_command_fails() {
     test_command_invocation(&[], false, false, |stdout, stderr| {
         assert!(stdout.is_empty());
         assert!(stderr.contains("error: the following required arguments were not provided:"));
-        assert!(stderr.contains("<--kmdf|--umdf|--wdm>"));
+        assert!(stderr.contains("<--kmdf|--umdf|--wdm|--driver-type <DRIVER_TYPE>>"));
         assert!(stderr.contains("<PATH>"));
     });
 }
@@ -53,7 +79,10 @@ fn if_missing_required_arguments_command_fails() {
 fn help_works() {
     test_command_invocation(&["--help"], false, true, |stdout, stderr| {
         assert!(stdout.contains("Create a new Windows Driver Kit project"));
-        assert!(stdout.contains("Usage: cargo wdk new [OPTIONS] <--kmdf|--umdf|--wdm> <PATH>"));
+        assert!(stdout.contains(
+            "Usage: cargo wdk new [OPTIONS] <--kmdf|--umdf|--wdm|--driver-type <DRIVER_TYPE>> \
+             <PATH>"
+        ));
         assert!(stderr.is_empty());
     });
 }
@@ -157,6 +186,24 @@ fn verify_project_creation(driver_type: &str, tmp_dir: &TempDir) -> PathBuf {
         .child(driver_name_path.join(".cargo").join("config.toml"))
         .assert(predicates::str::contains("target-feature=+crt-static"));
 
+    // assert the test client opens the device interface of the driver
+    let test_client_path = driver_name_path.join("test-client");
+    tmp_dir
+        .child(test_client_path.join("Cargo.toml"))
+        .assert(predicates::str::contains(format!(
+            "name = \"{driver_name_underscored}_test_client\""
+        )));
+    let interface_guid_regex = regex::Regex::new(r"GUID of the driver, (\{[0-9A-F-]{36}\})")
+        .expect("interface GUID regex should be valid");
+    let lib_rs = std::fs::read_to_string(tmp_dir.join(driver_name_path.join("src").join("lib.rs")))
+        .expect("lib.rs should be readable");
+    let interface_guid = &interface_guid_regex
+        .captures(&lib_rs)
+        .expect("lib.rs should contain the interface GUID")[1];
+    tmp_dir
+        .child(test_client_path.join("src").join("main.rs"))
+        .assert(predicates::str::contains(interface_guid));
+
     driver_path
 }
 
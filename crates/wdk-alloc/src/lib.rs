@@ -19,9 +19,30 @@
 //! #[global_allocator]
 //! static GLOBAL_ALLOCATOR: WdkAllocator = WdkAllocator;
 //! ```
+//!
+//! To tell the allocations of a driver apart from those of other drivers, ex.
+//! when tracking pool allocations with Driver Verifier or `!poolused`, use a
+//! `TaggedAllocator` with a pool tag of the driver instead:
+//! ```rust, no_run
+//! #[cfg(all(
+//!     any(driver_model__driver_type = "WDM", driver_model__driver_type = "KMDF"),
+//!     not(test)
+//! ))]
+//! #[global_allocator]
+//! static GLOBAL_ALLOCATOR: wdk_alloc::TaggedAllocator = wdk_alloc::TaggedAllocator::new(*b"Smpl");
+//! ```
+//!
+//! `Box`, `Vec` and `String` are re-exported for convenience. Like all types
+//! of the `alloc` crate, they allocate through the global allocator.
 
 #![no_std]
 
+#[cfg(any(driver_model__driver_type = "WDM", driver_model__driver_type = "KMDF"))]
+extern crate alloc;
+
+#[cfg(any(driver_model__driver_type = "WDM", driver_model__driver_type = "KMDF"))]
+pub use alloc::{boxed::Box, string::String, vec::Vec};
+
 #[cfg(any(driver_model__driver_type = "WDM", driver_model__driver_type = "KMDF"))]
 pub use kernel_mode::*;
 
@@ -45,6 +66,35 @@ mod kernel_mode {
     /// <= `DISPATCH_LEVEL`
     pub struct WdkAllocator;
 
+    /// Allocator implementation to use with `#[global_allocator]` to allow use
+    /// of [`core::alloc`], which tags all allocations with a pool tag chosen by
+    /// the driver. Allocations are made from non-executable non-paged pool.
+    ///
+    /// # Safety
+    /// This allocator is only safe to use for allocations happening at `IRQL`
+    /// <= `DISPATCH_LEVEL`
+    pub struct TaggedAllocator {
+        tag: ULONG,
+    }
+
+    impl TaggedAllocator {
+        /// Creates an allocator that tags its allocations with `tag`. The tag
+        /// is given in the order it is displayed by tooling (ie. Windbg), ex.
+        /// `*b"Smpl"`.
+        #[must_use]
+        pub const fn new(tag: [u8; 4]) -> Self {
+            Self {
+                tag: u32::from_ne_bytes(tag),
+            }
+        }
+
+        /// Returns the pool tag of the allocations, as passed to the kernel
+        #[must_use]
+        pub const fn tag(&self) -> ULONG {
+            self.tag
+        }
+    }
+
     // The value of memory tags are stored in little-endian order, so it is
     // convenient to reverse the order for readability in tooling (ie. Windbg)
     const RUST_TAG: ULONG = u32::from_ne_bytes(*b"rust");
@@ -56,23 +106,54 @@ mod kernel_mode {
     //            supported)
     unsafe impl GlobalAlloc for WdkAllocator {
         unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-            let ptr =
-                // SAFETY: `ExAllocatePool2` is safe to call from any `IRQL` <= `DISPATCH_LEVEL` since its allocating from `POOL_FLAG_NON_PAGED`
-                unsafe {
-                    ExAllocatePool2(POOL_FLAG_NON_PAGED, layout.size() as SIZE_T, RUST_TAG)
-                };
-            if ptr.is_null() {
-                return core::ptr::null_mut();
-            }
-            ptr.cast()
+            allocate(layout, RUST_TAG)
         }
 
         unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
-            // SAFETY: `ExFreePool` is safe to call from any `IRQL` <= `DISPATCH_LEVEL`
-            // since its freeing memory allocated from `POOL_FLAG_NON_PAGED` in `alloc`
+            // SAFETY: `ptr` was allocated by `alloc`, as required by the caller
+            unsafe { free(ptr) }
+        }
+    }
+
+    // SAFETY: This is safe because the tagged allocator:
+    //         1. can never unwind since it can never panic
+    //         2. has implementations of alloc and dealloc that maintain layout
+    //            constraints (FIXME: Alignment of the layout is currently not
+    //            supported)
+    unsafe impl GlobalAlloc for TaggedAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            allocate(layout, self.tag)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
+            // SAFETY: `ptr` was allocated by `alloc`, as required by the caller
+            unsafe { free(ptr) }
+        }
+    }
+
+    /// Allocates `layout.size()` bytes of non-paged pool tagged with `tag`.
+    /// `POOL_FLAG_NON_PAGED` allocations are non-executable.
+    fn allocate(layout: Layout, tag: ULONG) -> *mut u8 {
+        let ptr =
+            // SAFETY: `ExAllocatePool2` is safe to call from any `IRQL` <= `DISPATCH_LEVEL` since its allocating from `POOL_FLAG_NON_PAGED`
             unsafe {
-                ExFreePool(ptr.cast());
-            }
+                ExAllocatePool2(POOL_FLAG_NON_PAGED, layout.size() as SIZE_T, tag)
+            };
+        if ptr.is_null() {
+            return core::ptr::null_mut();
+        }
+        ptr.cast()
+    }
+
+    /// Frees memory allocated by [`allocate`]
+    ///
+    /// # Safety
+    /// `ptr` must have been returned by [`allocate`] and not freed yet
+    unsafe fn free(ptr: *mut u8) {
+        // SAFETY: `ExFreePool` is safe to call from any `IRQL` <= `DISPATCH_LEVEL`
+        // since its freeing memory allocated from `POOL_FLAG_NON_PAGED` in `allocate`
+        unsafe {
+            ExFreePool(ptr.cast());
         }
     }
 }
@@ -515,6 +515,8 @@ fn emit_cfg_settings(&self) -> Result<(), ConfigError> {
         let serialized_wdk_metadata_map =
             metadata::to_map::<std::collections::BTreeMap<_, _>>(&metadata::Wdk {
                 driver_model: self.driver_config.clone(),
+                deploy: None,
+                package: None,
             })?;
 
         for cfg_key in EXPORTED_CFG_SETTINGS.iter().map(|(key, _)| *key) {
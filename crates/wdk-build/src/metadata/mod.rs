@@ -18,7 +18,11 @@
 mod error;
 mod map;
 
-use std::collections::HashSet;
+use std::{
+    collections::{BTreeMap, HashSet},
+    hash::{Hash, Hasher},
+    path::PathBuf,
+};
 
 use camino::Utf8PathBuf;
 use cargo_metadata::Metadata;
@@ -40,6 +44,223 @@
 pub struct Wdk {
     /// Metadata corresponding to the `Driver Model` property page in the WDK
     pub driver_model: DriverConfig,
+    /// Metadata used by `cargo wdk deploy` to install the driver package on a
+    /// test machine
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deploy: Option<DeployConfig>,
+    /// Metadata used by `cargo wdk build` to create the driver package. It is
+    /// not consumed by the build itself, so it is never serialized
+    #[serde(default, skip_serializing)]
+    pub package: Option<PackageConfig>,
+}
+
+/// Metadata specified in the `metadata.wdk.deploy` section of the `Cargo.toml`.
+///
+/// This corresponds with the settings in the `Driver Install` property pages
+/// for WDK projects in Visual Studio
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(
+    deny_unknown_fields,
+    rename_all(serialize = "SCREAMING_SNAKE_CASE", deserialize = "kebab-case")
+)]
+pub struct DeployConfig {
+    /// Name of the test machine to deploy the driver package to. The driver
+    /// package is deployed to the local machine if this is not set
+    pub target_machine: Option<String>,
+    /// Hardware ID of a root enumerated device to create for the driver. If
+    /// this is not set, the driver package is only added to the driver store
+    /// of the test machine and installed on matching devices
+    pub hardware_id: Option<String>,
+    /// Enable test signing on the test machine before installing the driver
+    /// package
+    #[serde(default)]
+    pub enable_test_signing: bool,
+    /// Restart the test machine after installing the driver package
+    #[serde(default)]
+    pub reboot: bool,
+}
+
+/// Metadata specified in the `metadata.wdk.package` section of the
+/// `Cargo.toml`.
+///
+/// This corresponds with the settings in the `StampInf` and `Inf2Cat` property
+/// pages for WDK projects in Visual Studio.
+///
+/// `inf` and `hardware_ids` describe a single driver, so they are not part of
+/// the configuration shared by the dependency graph: they are ignored when
+/// comparing configurations, and are read from the `Cargo.toml` of each driver
+/// package with [`PackageConfig::with_driver_package_settings()`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(
+    deny_unknown_fields,
+    rename_all(serialize = "SCREAMING_SNAKE_CASE", deserialize = "kebab-case")
+)]
+pub struct PackageConfig {
+    /// Date stamped into the `DriverVer` directive of the INF file, in
+    /// `MM/DD/YYYY` format. The current date is used if this is not set
+    pub driver_date: Option<String>,
+    /// Platform extensions (ex. `NTamd64`) for which decorated
+    /// `CatalogFile.<extension>` directives are added to the `[Version]`
+    /// section of the INF file
+    #[serde(default)]
+    pub catalog_file_decorations: Vec<String>,
+    /// Additional directives added to the `[Version]` section of the INF file
+    #[serde(default)]
+    pub version_attributes: BTreeMap<String, String>,
+    /// Name of the certificate store containing the certificate to sign the
+    /// driver package with. Defaults to `WDRTestCertStore`
+    pub cert_store: Option<String>,
+    /// Name of the certificate to sign the driver package with. Defaults to
+    /// `WDRLocalTestCert`
+    pub cert_name: Option<String>,
+    /// SHA1 thumbprint of the certificate to sign the driver package with,
+    /// which selects the certificate in `cert_store` unambiguously. Cannot be
+    /// combined with `cert_name`
+    pub cert_thumbprint: Option<String>,
+    /// Path to a PFX file containing the certificate to sign the driver
+    /// package with, or to the certificate file of a key kept by `csp`.
+    /// Relative paths are resolved against the package directory. Cannot be
+    /// combined with `cert_store`, `cert_name` or `cert_thumbprint`
+    pub cert_path: Option<PathBuf>,
+    /// Name of the cryptographic service provider holding the private key of
+    /// the certificate in `cert_path`, ex. the provider of a hardware security
+    /// module storing an EV certificate. Must be set along with
+    /// `key_container`
+    pub csp: Option<String>,
+    /// Name of the key container of the private key in `csp`
+    pub key_container: Option<String>,
+    /// Digest algorithm used to sign the driver package, ex. `SHA256` or
+    /// `SHA384`. Defaults to `SHA256`
+    pub digest_algorithm: Option<String>,
+    /// Names of the workspace packages that build export drivers, i.e.
+    /// kernel-mode DLLs exporting functions to other drivers. Export drivers
+    /// are packaged without an INF file and keep their `.dll` extension, so
+    /// that the import library consumed by other drivers refers to them by
+    /// their actual file name
+    #[serde(default)]
+    pub export_drivers: Vec<String>,
+    /// Path to the INX or INF template file the INF file of the driver
+    /// package is created from. Relative paths are resolved against the
+    /// package directory. Defaults to `<package_name>.inx` in the package
+    /// directory
+    pub inf: Option<PathBuf>,
+    /// Hardware IDs of the devices the driver is installed on. They are
+    /// substituted, separated by commas, for the `$HARDWARE_IDS$` token in the
+    /// INF template
+    #[serde(default)]
+    pub hardware_ids: Vec<String>,
+}
+
+impl PackageConfig {
+    /// Replace `inf` and `hardware_ids` with the ones set in the
+    /// `metadata.wdk.package` section of the `Cargo.toml` of `package`, so
+    /// that each driver of a workspace is packaged with its own INF template
+    /// and hardware IDs. They are cleared if `package` does not set them,
+    /// even if another package or the workspace does.
+    ///
+    /// # Errors
+    ///
+    /// This function will return
+    /// [`TryFromCargoMetadataError::WdkMetadataDeserialization`] if the
+    /// settings of `package` cannot be deserialized.
+    pub fn with_driver_package_settings(
+        self,
+        package: &cargo_metadata::Package,
+    ) -> std::result::Result<Self, TryFromCargoMetadataError> {
+        let settings = match &package.metadata["wdk"]["package"] {
+            serde_json::Value::Null => DriverPackageSettings::default(),
+            package_metadata => {
+                DriverPackageSettings::deserialize(package_metadata).map_err(|err| {
+                    TryFromCargoMetadataError::WdkMetadataDeserialization {
+                        metadata_source: format!(
+                            "{} for {} package",
+                            stringify!(package.metadata["wdk"]["package"]),
+                            package.name
+                        ),
+                        error_source: err,
+                    }
+                })?
+            }
+        };
+        Ok(Self {
+            inf: settings.inf,
+            hardware_ids: settings.hardware_ids,
+            ..self
+        })
+    }
+
+    /// Fields compared and hashed to detect distinct configurations, i.e. all
+    /// but the settings of individual drivers
+    #[allow(clippy::type_complexity)]
+    fn shared_settings(
+        &self,
+    ) -> (
+        &Option<String>,
+        &Vec<String>,
+        &BTreeMap<String, String>,
+        &Option<String>,
+        &Option<String>,
+        &Option<String>,
+        &Option<PathBuf>,
+        &Option<String>,
+        &Option<String>,
+        &Option<String>,
+        &Vec<String>,
+    ) {
+        // Destructured so that new fields are not left out by accident
+        let Self {
+            driver_date,
+            catalog_file_decorations,
+            version_attributes,
+            cert_store,
+            cert_name,
+            cert_thumbprint,
+            cert_path,
+            csp,
+            key_container,
+            digest_algorithm,
+            export_drivers,
+            inf: _,
+            hardware_ids: _,
+        } = self;
+        (
+            driver_date,
+            catalog_file_decorations,
+            version_attributes,
+            cert_store,
+            cert_name,
+            cert_thumbprint,
+            cert_path,
+            csp,
+            key_container,
+            digest_algorithm,
+            export_drivers,
+        )
+    }
+}
+
+impl PartialEq for PackageConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.shared_settings() == other.shared_settings()
+    }
+}
+
+impl Eq for PackageConfig {}
+
+impl Hash for PackageConfig {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.shared_settings().hash(state);
+    }
+}
+
+/// Settings of a single driver in the `metadata.wdk.package` section of the
+/// `Cargo.toml` of its package
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct DriverPackageSettings {
+    inf: Option<PathBuf>,
+    #[serde(default)]
+    hardware_ids: Vec<String>,
 }
 
 /// Errors that could result from trying to construct a
@@ -178,7 +399,7 @@ pub(crate) fn iter_manifest_paths(metadata: Metadata) -> impl IntoIterator<Item
 mod tests {
     use std::path::{Path, PathBuf};
 
-    use super::Wdk;
+    use super::{DeployConfig, PackageConfig, Wdk};
     use crate::{DriverConfig, KmdfConfig, metadata::TryFromCargoMetadataError};
 
     const TEST_ROOT_DIR: &str = "C:\\tmp";
@@ -248,6 +469,142 @@ fn no_wdk_configuration() {
         });
     }
 
+    #[test]
+    fn wdk_metadata_with_deploy_configuration() {
+        let cwd = PathBuf::from(TEST_ROOT_DIR);
+        let wdk_metadata = TestWdkMetadata(
+            r#"
+                {
+                    "wdk": {
+                        "driver-model": {
+                            "driver-type": "KMDF",
+                            "kmdf-version-major": 1,
+                            "target-kmdf-version-minor": 33
+                        },
+                        "deploy": {
+                            "target-machine": "test-machine",
+                            "enable-test-signing": true
+                        }
+                    }
+                }
+            "#
+            .to_string(),
+        );
+
+        let (workspace_member, package) =
+            create_cargo_metadata_package(&cwd, "sample-kmdf", "0.0.1", Some(wdk_metadata));
+
+        set_up_and_assert(&cwd, &[package], &[workspace_member], None, |wdk| {
+            assert_eq!(
+                wdk.unwrap().deploy,
+                Some(DeployConfig {
+                    target_machine: Some("test-machine".to_string()),
+                    hardware_id: None,
+                    enable_test_signing: true,
+                    reboot: false,
+                })
+            );
+        });
+    }
+
+    #[test]
+    fn wdk_metadata_with_package_configuration() {
+        let cwd = PathBuf::from(TEST_ROOT_DIR);
+        let wdk_metadata = TestWdkMetadata(
+            r#"
+                {
+                    "wdk": {
+                        "driver-model": {
+                            "driver-type": "KMDF",
+                            "kmdf-version-major": 1,
+                            "target-kmdf-version-minor": 33
+                        },
+                        "package": {
+                            "driver-date": "01/31/2025",
+                            "catalog-file-decorations": ["NTamd64"],
+                            "version-attributes": {
+                                "PnpLockdown": "1"
+                            },
+                            "cert-name": "ContosoTestCert",
+                            "digest-algorithm": "SHA384",
+                            "export-drivers": ["sample-export-driver"],
+                            "inf": "inf/sample_kmdf.inx",
+                            "hardware-ids": ["Root\\SAMPLE_KMDF_HW_ID"]
+                        }
+                    }
+                }
+            "#
+            .to_string(),
+        );
+
+        let (workspace_member, package) =
+            create_cargo_metadata_package(&cwd, "sample-kmdf", "0.0.1", Some(wdk_metadata));
+
+        set_up_and_assert(&cwd, &[package], &[workspace_member], None, |wdk| {
+            assert_eq!(
+                wdk.unwrap().package,
+                Some(PackageConfig {
+                    driver_date: Some("01/31/2025".to_string()),
+                    catalog_file_decorations: vec!["NTamd64".to_string()],
+                    version_attributes: [("PnpLockdown".to_string(), "1".to_string())].into(),
+                    cert_name: Some("ContosoTestCert".to_string()),
+                    digest_algorithm: Some("SHA384".to_string()),
+                    export_drivers: vec!["sample-export-driver".to_string()],
+                    ..PackageConfig::default()
+                })
+            );
+        });
+    }
+
+    #[test]
+    fn driver_package_settings_are_read_per_package() {
+        let cwd = PathBuf::from(TEST_ROOT_DIR);
+        let (member_id1, package1) = create_cargo_metadata_package(
+            &cwd,
+            "sample-kmdf-1",
+            "0.0.1",
+            Some(create_driver_package_wdk_metadata(
+                "inf/sample_kmdf_1.inx",
+                "Root\\SAMPLE_KMDF_1",
+            )),
+        );
+        let (member_id2, package2) = create_cargo_metadata_package(
+            &cwd,
+            "sample-kmdf-2",
+            "0.0.1",
+            Some(create_driver_package_wdk_metadata(
+                "inf/sample_kmdf_2.inx",
+                "Root\\SAMPLE_KMDF_2",
+            )),
+        );
+        let metadata =
+            create_cargo_metadata(&cwd, &[package1, package2], &[member_id1, member_id2], None);
+
+        let package_config = Wdk::try_from(&metadata)
+            .expect("drivers differing only in their INF settings should share a configuration")
+            .package
+            .expect("package configuration should be set");
+        for (package, inf, hardware_id) in [
+            (
+                &metadata.packages[0],
+                "inf/sample_kmdf_1.inx",
+                "Root\\SAMPLE_KMDF_1",
+            ),
+            (
+                &metadata.packages[1],
+                "inf/sample_kmdf_2.inx",
+                "Root\\SAMPLE_KMDF_2",
+            ),
+        ] {
+            let driver_config = package_config
+                .clone()
+                .with_driver_package_settings(package)
+                .unwrap();
+            assert_eq!(driver_config.inf, Some(PathBuf::from(inf)));
+            assert_eq!(driver_config.hardware_ids, vec![hardware_id.to_string()]);
+        }
+    }
+
     #[test]
     fn invalid_wdk_metadata() {
         let cwd = PathBuf::from(TEST_ROOT_DIR);
@@ -429,6 +786,29 @@ fn create_cargo_metadata_package(
         )
     }
 
+    /// Construct KMDF metadata wrapped in `TestWdkMetadata`, with the given INF
+    /// template and hardware ID in its package section
+    fn create_driver_package_wdk_metadata(inf: &str, hardware_id: &str) -> TestWdkMetadata {
+        TestWdkMetadata(format!(
+            r#"
+                {{
+                    "wdk": {{
+                        "driver-model": {{
+                            "driver-type": "KMDF",
+                            "kmdf-version-major": 1,
+                            "target-kmdf-version-minor": 33
+                        }},
+                        "package": {{
+                            "inf": "{inf}",
+                            "hardware-ids": ["{}"]
+                        }}
+                    }}
+                }}
+            "#,
+            hardware_id.escape_default()
+        ))
+    }
+
     /// Construct WDK metadata wrapped in `TestWdkMetadata` for the given driver
     /// type and versions. The JSON matches the format expected by
     /// `cargo_metadata::Metadata.workspace_metadata`.
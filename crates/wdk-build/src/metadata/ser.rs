@@ -42,6 +42,8 @@
 ///         target_kmdf_version_minor: 23,
 ///         minimum_kmdf_version_minor: None,
 ///     }),
+///     deploy: None,
+///     package: None,
 /// };
 ///
 /// let output = to_map::<BTreeMap<_, _>>(&wdk_metadata).unwrap();
@@ -89,6 +91,8 @@ pub fn to_map<M>(value: &impl Serialize) -> Result<M>
 ///         target_kmdf_version_minor: 33,
 ///         minimum_kmdf_version_minor: Some(31),
 ///     }),
+///     deploy: None,
+///     package: None,
 /// };
 ///
 /// let output = to_map_with_prefix::<BTreeMap<_, _>>("WDK_BUILD_METADATA", &wdk_metadata).unwrap();
@@ -597,6 +601,8 @@ fn test_kmdf() {
                 target_kmdf_version_minor: 23,
                 minimum_kmdf_version_minor: Some(21),
             }),
+            deploy: None,
+            package: None,
         };
 
         let output = to_map::<BTreeMap<_, _>>(&wdk_metadata).unwrap();
@@ -615,6 +621,8 @@ fn test_kmdf_no_minimum() {
                 target_kmdf_version_minor: 23,
                 minimum_kmdf_version_minor: None,
             }),
+            deploy: None,
+            package: None,
         };
 
         let output = to_map::<BTreeMap<_, _>>(&wdk_metadata).unwrap();
@@ -635,6 +643,8 @@ fn test_kmdf_with_prefix() {
                 target_kmdf_version_minor: 33,
                 minimum_kmdf_version_minor: Some(31),
             }),
+            deploy: None,
+            package: None,
         };
 
         let output =
@@ -666,6 +676,8 @@ fn test_kmdf_with_hashmap() {
                 target_kmdf_version_minor: 33,
                 minimum_kmdf_version_minor: Some(31),
             }),
+            deploy: None,
+            package: None,
         };
 
         let output = to_map::<HashMap<_, _>>(&wdk_metadata).unwrap();
@@ -684,6 +696,8 @@ fn test_umdf() {
                 target_umdf_version_minor: 23,
                 minimum_umdf_version_minor: Some(21),
             }),
+            deploy: None,
+            package: None,
         };
 
         let output = to_map::<BTreeMap<_, _>>(&wdk_metadata).unwrap();
@@ -702,6 +716,8 @@ fn test_umdf_no_minimum() {
                 target_umdf_version_minor: 23,
                 minimum_umdf_version_minor: None,
             }),
+            deploy: None,
+            package: None,
         };
 
         let output = to_map::<BTreeMap<_, _>>(&wdk_metadata).unwrap();
@@ -718,6 +734,8 @@ fn test_umdf_no_minimum() {
     fn test_wdm() {
         let wdk_metadata = metadata::Wdk {
             driver_model: DriverConfig::Wdm,
+            deploy: None,
+            package: None,
         };
 
         let output = to_map::<BTreeMap<_, _>>(&wdk_metadata).unwrap();
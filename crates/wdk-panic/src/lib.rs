@@ -2,29 +2,107 @@
 // License: MIT OR Apache-2.0
 
 //! Default Panic Handlers for programs built with the WDK (Windows Drivers Kit)
+//!
+//! In kernel mode, a panic prints its message and location to the debugger
+//! and then bug checks with [`RUST_PANIC_BUGCHECK_CODE`], so that the crash
+//! can be diagnosed from the resulting dump. The bug check parameters are:
+//!
+//! 1. The address of the NUL terminated panic message, which is kept on the
+//!    stack of the panicking thread
+//! 2. The address of the file name of the panic location (not NUL terminated)
+//! 3. The line of the panic location
+//! 4. The column of the panic location
 
 #![no_std]
 
-#[cfg(not(test))]
-use core::panic::PanicInfo;
+#[cfg(any(driver_model__driver_type = "WDM", driver_model__driver_type = "KMDF"))]
+pub use kernel_mode::*;
 
-#[cfg(all(
-    debug_assertions,
-    // Disable inclusion of panic handlers when compiling tests for wdk crate
-    not(test)
-))]
-#[panic_handler]
-const fn panic(_info: &PanicInfo) -> ! {
-    loop {}
+#[cfg(any(driver_model__driver_type = "WDM", driver_model__driver_type = "KMDF"))]
+mod kernel_mode {
+    use wdk_sys::ULONG;
+
+    /// Bug check code of a driver panic. It reads as `RUST` when displayed in
+    /// hex by the debugger.
+    pub const RUST_PANIC_BUGCHECK_CODE: ULONG = u32::from_be_bytes(*b"RUST");
 }
 
 #[cfg(all(
-    not(debug_assertions),
+    any(driver_model__driver_type = "WDM", driver_model__driver_type = "KMDF"),
     // Disable inclusion of panic handlers when compiling tests for wdk crate
     not(test)
 ))]
-#[panic_handler]
-const fn panic(_info: &PanicInfo) -> ! {
-    loop {}
-    // FIXME: Should this trigger Bugcheck via KeBugCheckEx?
+mod panic_handler {
+    use core::{fmt::Write, panic::PanicInfo};
+
+    use wdk_sys::{
+        ULONG_PTR,
+        ntddk::{DbgPrint, KeBugCheckEx},
+    };
+
+    use crate::RUST_PANIC_BUGCHECK_CODE;
+
+    /// Max size that can be transmitted by `DbgPrint` in single call:
+    /// <https://learn.microsoft.com/en-us/windows-hardware/drivers/debugger/reading-and-filtering-debugging-messages#dbgprint-buffer-and-the-debugger>
+    const PANIC_MESSAGE_MAX_SIZE: usize = 512;
+
+    /// Stack-based buffer the panic message is formatted into, so that
+    /// panicking does not need heap allocations. Messages that do not fit are
+    /// truncated, and the last byte is always kept for the NUL terminator.
+    struct PanicMessageBuffer {
+        buffer: [u8; PANIC_MESSAGE_MAX_SIZE],
+        used: usize,
+    }
+
+    impl Write for PanicMessageBuffer {
+        fn write_str(&mut self, s: &str) -> core::fmt::Result {
+            let remaining_buffer = &mut self.buffer[self.used..PANIC_MESSAGE_MAX_SIZE - 1];
+            let chunk_size = s.len().min(remaining_buffer.len());
+            remaining_buffer[..chunk_size].copy_from_slice(&s.as_bytes()[..chunk_size]);
+            self.used += chunk_size;
+            Ok(())
+        }
+    }
+
+    #[panic_handler]
+    #[allow(
+        unreachable_code,
+        reason = "`KeBugCheckEx` does not return, which depending on the WDK headers is not \
+                  reflected in its binding"
+    )]
+    fn panic(info: &PanicInfo) -> ! {
+        let mut message = PanicMessageBuffer {
+            // buffer is initialized to all null, so it is always NUL terminated
+            buffer: [0; PANIC_MESSAGE_MAX_SIZE],
+            used: 0,
+        };
+        // A failing `Display` implementation only cuts the message short, which is
+        // still worth reporting
+        let _ = write!(message, "{info}");
+
+        // SAFETY: `message.buffer` is NUL terminated and is passed as the argument of
+        // a `%s` format specifier
+        unsafe {
+            DbgPrint(c"%s\n".as_ptr(), message.buffer.as_ptr());
+        }
+
+        let (file, line, column) = info
+            .location()
+            .map_or((core::ptr::null(), 0, 0), |location| {
+                (location.file().as_ptr(), location.line(), location.column())
+            });
+
+        // SAFETY: Bug checking is allowed at any IRQL. The message stays on the stack
+        // of this thread, where it is captured by the dump.
+        unsafe {
+            KeBugCheckEx(
+                RUST_PANIC_BUGCHECK_CODE,
+                message.buffer.as_ptr() as ULONG_PTR,
+                file as ULONG_PTR,
+                ULONG_PTR::from(line),
+                ULONG_PTR::from(column),
+            );
+        }
+        loop {}
+    }
 }
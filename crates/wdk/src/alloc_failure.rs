@@ -0,0 +1,150 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Handling of allocation failures in the allocating APIs of this crate.
+//!
+//! The infallible allocating APIs, ex. [`crate::Guid::to_string_braced()`],
+//! call [`alloc::alloc::handle_alloc_error`] when an allocation fails, which
+//! panics in `no_std` drivers, at whatever `IRQL` the allocation happened.
+//! Each of them has a `try_` variant, ex.
+//! [`crate::Guid::try_to_string_braced()`], that returns
+//! `STATUS_INSUFFICIENT_RESOURCES` instead. Before returning the error, the
+//! `try_` variants apply the [`AllocFailurePolicy`] of the driver, set with
+//! [`set_alloc_failure_policy()`], so that drivers can log or count the
+//! failures in one place, or stop the system while the state that led to the
+//! failure can still be inspected.
+
+use alloc::string::String;
+use core::{
+    fmt::{self, Write},
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+#[cfg(any(driver_model__driver_type = "WDM", driver_model__driver_type = "KMDF"))]
+use wdk_sys::ULONG;
+use wdk_sys::{NTSTATUS, STATUS_INSUFFICIENT_RESOURCES, STATUS_INVALID_PARAMETER};
+
+/// What the `try_` variants of the allocating APIs of this crate do when an
+/// allocation fails, before they return `STATUS_INSUFFICIENT_RESOURCES`
+#[derive(Clone, Copy, Debug)]
+pub enum AllocFailurePolicy {
+    /// Only return the error. This is the default policy.
+    ReturnError,
+    /// Call the hook with the number of bytes that could not be allocated,
+    /// then return the error. The hook is called at the `IRQL` of the failed
+    /// allocation, so it must not block or allocate.
+    Notify(fn(usize)),
+    /// Stop the system with [`crate::fatal_error!`] and the bug check code
+    #[cfg(any(driver_model__driver_type = "WDM", driver_model__driver_type = "KMDF"))]
+    FatalError(ULONG),
+}
+
+/// Policy set with [`set_alloc_failure_policy()`], or null for
+/// [`AllocFailurePolicy::ReturnError`]
+static ALLOC_FAILURE_POLICY: AtomicPtr<AllocFailurePolicy> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Set the [`AllocFailurePolicy`] of the driver, typically from `DriverEntry`
+pub fn set_alloc_failure_policy(policy: &'static AllocFailurePolicy) {
+    ALLOC_FAILURE_POLICY.store(core::ptr::from_ref(policy).cast_mut(), Ordering::Release);
+}
+
+/// Get the [`AllocFailurePolicy`] of the driver
+#[must_use]
+pub fn alloc_failure_policy() -> &'static AllocFailurePolicy {
+    // SAFETY: `ALLOC_FAILURE_POLICY` is either null or set from a `'static`
+    // reference by `set_alloc_failure_policy`, and is never written through
+    unsafe { ALLOC_FAILURE_POLICY.load(Ordering::Acquire).as_ref() }
+        .unwrap_or(&AllocFailurePolicy::ReturnError)
+}
+
+/// Apply the [`AllocFailurePolicy`] of the driver to the failed allocation of
+/// `requested_bytes`, and get the error to return for it
+pub(crate) fn alloc_failed(requested_bytes: usize) -> NTSTATUS {
+    match alloc_failure_policy() {
+        AllocFailurePolicy::ReturnError => {}
+        AllocFailurePolicy::Notify(hook) => hook(requested_bytes),
+        #[cfg(any(driver_model__driver_type = "WDM", driver_model__driver_type = "KMDF"))]
+        AllocFailurePolicy::FatalError(code) => {
+            crate::fatal_error!(*code, "failed to allocate {requested_bytes} bytes")
+        }
+    }
+    STATUS_INSUFFICIENT_RESOURCES
+}
+
+/// Format `args` into a new [`String`], like [`alloc::format!`], but without
+/// panicking when the string cannot be allocated
+///
+/// # Errors
+///
+/// This function will return `STATUS_INSUFFICIENT_RESOURCES` if the string
+/// could not be allocated, after applying the [`AllocFailurePolicy`] of the
+/// driver, or `STATUS_INVALID_PARAMETER` if a formatting trait implementation
+/// returned an error.
+pub fn try_format(args: fmt::Arguments<'_>) -> Result<String, NTSTATUS> {
+    let mut writer = TryStringWriter {
+        string: String::new(),
+        failed_bytes: None,
+    };
+    match (fmt::write(&mut writer, args), writer.failed_bytes) {
+        (Ok(()), _) => Ok(writer.string),
+        (Err(_), Some(failed_bytes)) => Err(alloc_failed(failed_bytes)),
+        (Err(_), None) => Err(STATUS_INVALID_PARAMETER),
+    }
+}
+
+/// Writer that grows its string fallibly, and records the size of the first
+/// allocation that failed
+struct TryStringWriter {
+    string: String,
+    failed_bytes: Option<usize>,
+}
+
+impl Write for TryStringWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.string.try_reserve(s.len()).is_err() {
+            self.failed_bytes = Some(s.len());
+            return Err(fmt::Error);
+        }
+        self.string.push_str(s);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FailingDisplay;
+
+    impl fmt::Display for FailingDisplay {
+        fn fmt(&self, _f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            Err(fmt::Error)
+        }
+    }
+
+    #[test]
+    fn try_format_formats_like_format() {
+        assert_eq!(
+            try_format(format_args!("{}-{:04X}", "queue", 0x2A)).as_deref(),
+            Ok("queue-002A")
+        );
+        assert_eq!(try_format(format_args!("")).as_deref(), Ok(""));
+    }
+
+    #[test]
+    fn try_format_fails_on_formatting_errors() {
+        assert_eq!(
+            try_format(format_args!("{FailingDisplay}")),
+            Err(STATUS_INVALID_PARAMETER)
+        );
+    }
+
+    #[test]
+    fn default_policy_returns_the_error() {
+        assert!(matches!(
+            alloc_failure_policy(),
+            AllocFailurePolicy::ReturnError
+        ));
+        assert_eq!(alloc_failed(16), STATUS_INSUFFICIENT_RESOURCES);
+    }
+}
@@ -0,0 +1,728 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Manifest-free [TraceLogging] events, written through an ETW provider
+//! registered with `EtwRegister`.
+//!
+//! TraceLogging events describe their own fields, so they can be decoded by
+//! tools like WPA or `tracelog` without a manifest or TMF files. Declare the
+//! provider as a `static`, register it when the driver loads and write events
+//! with [`etw_event!`](crate::etw_event):
+//!
+//! ```ignore
+//! use wdk::{
+//!     Guid,
+//!     TraceControlGuid,
+//!     etw::{EtwProvider, Level},
+//! };
+//!
+//! static PROVIDER: EtwProvider = EtwProvider::new(
+//!     "MyCompany.MyDriver",
+//!     TraceControlGuid::new(Guid::from_u128(0x3F6C_2A1E_5B7D_4C9A_8E0F_1A2B_3C4D_5E6F)),
+//! );
+//!
+//! PROVIDER.register()?;
+//! wdk::etw_event!(PROVIDER, "DeviceAdded", Level::Information, 0x1, instance = 3u32);
+//! PROVIDER.unregister();
+//! ```
+//!
+//! For WPP-style debug traces, [`trace!`](crate::trace) writes a formatted
+//! message with a level and flag bits, which take the place of WPP trace
+//! flags, to a provider. Unlike WPP messages, trace messages are decoded
+//! without TMF files:
+//!
+//! ```ignore
+//! const FLAG_PNP: u64 = 0x2;
+//!
+//! wdk::trace!(PROVIDER, Level::Verbose, FLAG_PNP, "device {} started with {:#x}", 3, status);
+//! ```
+//!
+//! [TraceLogging]: https://learn.microsoft.com/en-us/windows/win32/tracelogging/trace-logging-about
+
+use core::{
+    fmt,
+    marker::PhantomData,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use wdk_sys::{
+    _EVENT_INFO_CLASS,
+    EVENT_DATA_DESCRIPTOR,
+    EVENT_DESCRIPTOR,
+    GUID,
+    NTSTATUS,
+    REGHANDLE,
+    STATUS_INVALID_PARAMETER,
+    ULONG,
+    ntddk::{EtwProviderEnabled, EtwRegister, EtwSetInformation, EtwUnregister, EtwWriteTransfer},
+};
+
+use crate::{TraceControlGuid, nt_success};
+
+/// Maximum number of fields of an [`EtwEvent`]. Fields beyond this are
+/// dropped.
+pub const MAX_EVENT_FIELDS: usize = 16;
+/// Maximum size of the message of a [`trace!`](crate::trace) event, in bytes.
+/// Longer messages are truncated.
+pub const MAX_TRACE_MESSAGE_SIZE: usize = 256;
+/// Maximum size of the metadata of an [`EtwEvent`], which holds the names and
+/// types of the event and its fields
+const MAX_EVENT_METADATA_SIZE: usize = 512;
+/// Maximum size of the metadata of an [`EtwProvider`], which holds its name
+const MAX_PROVIDER_METADATA_SIZE: usize = 128;
+/// Channel that marks events as TraceLogging events
+const TRACELOGGING_CHANNEL: u8 = 11;
+/// Type of the data descriptor carrying the metadata of an event
+const EVENT_METADATA_DESCRIPTOR_TYPE: u8 = 1;
+/// Type of the data descriptor carrying the metadata of the provider
+const PROVIDER_METADATA_DESCRIPTOR_TYPE: u8 = 2;
+/// Flag of a field input type, set when it is followed by an output type
+const OUT_TYPE_CHAIN_FLAG: u8 = 0x80;
+
+/// Level of an ETW event. Consumers enable events up to a level, ex.
+/// [`Level::Warning`] enables critical, error and warning events.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Level {
+    /// Abnormal exit or termination
+    Critical = 1,
+    /// Severe error
+    Error = 2,
+    /// Warning, ex. an allocation failure that is retried
+    Warning = 3,
+    /// Non-error event, ex. a device was added
+    Information = 4,
+    /// Detailed trace event
+    Verbose = 5,
+}
+
+/// ETW provider of TraceLogging events.
+///
+/// Providers are meant to be declared as a `static` with
+/// [`EtwProvider::new()`], registered with [`EtwProvider::register()`] when
+/// the driver loads, and unregistered with [`EtwProvider::unregister()`]
+/// before it unloads. Events written while the provider is not registered are
+/// dropped.
+pub struct EtwProvider {
+    guid: GUID,
+    metadata: [u8; MAX_PROVIDER_METADATA_SIZE],
+    metadata_size: u16,
+    /// Registration handle, or 0 if the provider is not registered
+    reg_handle: AtomicU64,
+}
+
+impl EtwProvider {
+    /// Construct a provider named `name`, ex. `MyCompany.MyDriver`, with the
+    /// control GUID `guid`. Consumers enable the provider by its GUID.
+    ///
+    /// # Panics
+    ///
+    /// Panics, at compile time when used in a `static`, if `name` is longer
+    /// than 124 bytes or contains a NUL character.
+    #[must_use]
+    pub const fn new(name: &str, guid: TraceControlGuid) -> Self {
+        let name = name.as_bytes();
+        // The metadata is the size of the metadata, followed by the NUL-terminated name
+        let metadata_size = 2 + name.len() + 1;
+        assert!(
+            metadata_size <= MAX_PROVIDER_METADATA_SIZE,
+            "the name of the provider is too long"
+        );
+
+        let mut metadata = [0; MAX_PROVIDER_METADATA_SIZE];
+        #[allow(clippy::cast_possible_truncation)] // checked above
+        let metadata_size = metadata_size as u16;
+        let size_bytes = metadata_size.to_le_bytes();
+        metadata[0] = size_bytes[0];
+        metadata[1] = size_bytes[1];
+        let mut i = 0;
+        while i < name.len() {
+            assert!(name[i] != 0, "the name of the provider contains a NUL");
+            metadata[2 + i] = name[i];
+            i += 1;
+        }
+
+        Self {
+            guid: guid.guid().to_raw(),
+            metadata,
+            metadata_size,
+            reg_handle: AtomicU64::new(0),
+        }
+    }
+
+    /// Register the provider, so that consumers can enable it. Must be called
+    /// at `IRQL` = `PASSIVE_LEVEL`, and the provider must be unregistered
+    /// before the driver unloads.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the provider could not be
+    /// registered, ex. `STATUS_INVALID_PARAMETER` if it is already registered.
+    pub fn register(&self) -> Result<(), NTSTATUS> {
+        let mut reg_handle: REGHANDLE = 0;
+
+        let nt_status;
+        // SAFETY: `guid` and `reg_handle` are valid for the duration of the call, and
+        // no enable callback is passed
+        unsafe {
+            nt_status = EtwRegister(
+                &raw const self.guid,
+                None,
+                core::ptr::null_mut(),
+                &raw mut reg_handle,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        // Failing to set the provider name only makes its events show up under the
+        // provider ID, so the status is ignored like the TraceLogging headers do
+        // SAFETY: `reg_handle` was just registered, and the metadata is only read
+        // during the call
+        unsafe {
+            EtwSetInformation(
+                reg_handle,
+                _EVENT_INFO_CLASS::EventProviderSetTraits,
+                self.metadata.as_ptr().cast_mut().cast(),
+                ULONG::from(self.metadata_size),
+            );
+        }
+
+        if self
+            .reg_handle
+            .compare_exchange(0, reg_handle, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            // SAFETY: `reg_handle` was just registered and is not shared
+            unsafe {
+                EtwUnregister(reg_handle);
+            }
+            return Err(STATUS_INVALID_PARAMETER);
+        }
+        Ok(())
+    }
+
+    /// Unregister the provider, if it is registered. Must be called at `IRQL`
+    /// = `PASSIVE_LEVEL`, and not concurrently with writing events.
+    pub fn unregister(&self) {
+        let reg_handle = self.reg_handle.swap(0, Ordering::AcqRel);
+        if reg_handle == 0 {
+            return;
+        }
+        // SAFETY: `reg_handle` was registered by `register` and is released only
+        // once, since it was swapped out
+        unsafe {
+            EtwUnregister(reg_handle);
+        }
+    }
+
+    /// Returns `true` if a consumer enabled events of `level` and `keyword`.
+    /// Use this to skip gathering the fields of events that nobody consumes.
+    /// Can be called at any `IRQL`.
+    #[must_use]
+    pub fn is_enabled(&self, level: Level, keyword: u64) -> bool {
+        let reg_handle = self.reg_handle.load(Ordering::Acquire);
+        if reg_handle == 0 {
+            return false;
+        }
+        let enabled;
+        // SAFETY: `reg_handle` is registered until `unregister`, which must not be
+        // called concurrently
+        unsafe {
+            enabled = EtwProviderEnabled(reg_handle, level as u8, keyword);
+        }
+        enabled != 0
+    }
+}
+
+/// Value that can be written as a field of an [`EtwEvent`]
+pub trait EtwField {
+    /// TraceLogging input type of the field, which describes its encoding
+    const IN_TYPE: u8;
+    /// TraceLogging output type of the field, which describes how to display
+    /// it, or 0 for the default of the input type
+    const OUT_TYPE: u8 = 0;
+    /// `true` if the field is prefixed with its size as a `u16`, ex. strings
+    const COUNTED: bool = false;
+
+    /// Get the bytes of the field, excluding the size prefix of counted fields
+    fn as_bytes(&self) -> &[u8];
+}
+
+macro_rules! impl_etw_field_for_int {
+    ($($int:ty => $in_type:literal),* $(,)?) => {
+        $(
+            impl EtwField for $int {
+                const IN_TYPE: u8 = $in_type;
+
+                fn as_bytes(&self) -> &[u8] {
+                    // SAFETY: Integers have no padding, so all of their bytes are
+                    // initialized
+                    unsafe {
+                        core::slice::from_raw_parts(
+                            core::ptr::from_ref(self).cast(),
+                            core::mem::size_of::<$int>(),
+                        )
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_etw_field_for_int! {
+    i8 => 3,
+    u8 => 4,
+    i16 => 5,
+    u16 => 6,
+    i32 => 7,
+    u32 => 8,
+    i64 => 9,
+    u64 => 10,
+}
+
+impl EtwField for bool {
+    // Encoded as a `u8`, displayed as a boolean
+    const IN_TYPE: u8 = 4;
+    const OUT_TYPE: u8 = 3;
+
+    fn as_bytes(&self) -> &[u8] {
+        if *self { &[1] } else { &[0] }
+    }
+}
+
+impl EtwField for str {
+    // Encoded as a counted string, displayed as UTF-8
+    const COUNTED: bool = true;
+    const IN_TYPE: u8 = 23;
+    const OUT_TYPE: u8 = 35;
+
+    fn as_bytes(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl EtwField for [u8] {
+    // Encoded as counted binary data
+    const COUNTED: bool = true;
+    const IN_TYPE: u8 = 14;
+
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl<T: EtwField + ?Sized> EtwField for &T {
+    const COUNTED: bool = T::COUNTED;
+    const IN_TYPE: u8 = T::IN_TYPE;
+    const OUT_TYPE: u8 = T::OUT_TYPE;
+
+    fn as_bytes(&self) -> &[u8] {
+        T::as_bytes(self)
+    }
+}
+
+/// Data of a field of an [`EtwEvent`]
+#[derive(Clone, Copy)]
+struct EventField {
+    data: *const u8,
+    /// Size of `data`, which is also the size prefix of counted fields
+    size: u16,
+    counted: bool,
+}
+
+/// TraceLogging event, built on the stack field by field and written with
+/// [`EtwEvent::write()`].
+///
+/// Usually built by [`etw_event!`](crate::etw_event). The event borrows the
+/// values of its fields until it is written. Fields that exceed
+/// [`MAX_EVENT_FIELDS`] or the size of the metadata buffer of the event are
+/// dropped, as are counted fields longer than `u16::MAX` bytes.
+pub struct EtwEvent<'a> {
+    metadata: [u8; MAX_EVENT_METADATA_SIZE],
+    metadata_size: usize,
+    fields: [EventField; MAX_EVENT_FIELDS],
+    field_count: usize,
+    _marker: PhantomData<&'a [u8]>,
+}
+
+impl<'a> EtwEvent<'a> {
+    /// Construct an event named `name`, without fields
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        let mut event = Self {
+            metadata: [0; MAX_EVENT_METADATA_SIZE],
+            // The metadata starts with its size and a byte of tags
+            metadata_size: 3,
+            fields: [EventField {
+                data: core::ptr::null(),
+                size: 0,
+                counted: false,
+            }; MAX_EVENT_FIELDS],
+            field_count: 0,
+            _marker: PhantomData,
+        };
+        event.push_metadata(&[name.as_bytes(), &[0]]);
+        event
+    }
+
+    /// Add a field named `name` with `value` to the event
+    pub fn field<T: EtwField + ?Sized>(&mut self, name: &str, value: &'a T) -> &mut Self {
+        let data = value.as_bytes();
+        let Ok(size) = u16::try_from(data.len()) else {
+            return self;
+        };
+        if self.field_count == MAX_EVENT_FIELDS {
+            return self;
+        }
+
+        let types = [T::IN_TYPE | OUT_TYPE_CHAIN_FLAG, T::OUT_TYPE];
+        let types = if T::OUT_TYPE == 0 {
+            &[T::IN_TYPE][..]
+        } else {
+            &types[..]
+        };
+        if self.push_metadata(&[name.as_bytes(), &[0], types]) {
+            self.fields[self.field_count] = EventField {
+                data: data.as_ptr(),
+                size,
+                counted: T::COUNTED,
+            };
+            self.field_count += 1;
+        }
+        self
+    }
+
+    /// Write the event with `level` and `keyword` to `provider`. Consumers
+    /// filter events by level and keyword, ex. a driver can use a keyword bit
+    /// per component. The event is dropped if the provider is not registered
+    /// or no consumer enabled it. Must be called at `IRQL` <= `HIGH_LEVEL`.
+    pub fn write(&self, provider: &EtwProvider, level: Level, keyword: u64) {
+        let reg_handle = provider.reg_handle.load(Ordering::Acquire);
+        if reg_handle == 0 {
+            return;
+        }
+
+        let event_descriptor = EVENT_DESCRIPTOR {
+            Id: 0,
+            Version: 0,
+            Channel: TRACELOGGING_CHANNEL,
+            Level: level as u8,
+            Opcode: 0,
+            Task: 0,
+            Keyword: keyword,
+        };
+
+        let mut data_descriptors = [EVENT_DATA_DESCRIPTOR::default(); 2 + 2 * MAX_EVENT_FIELDS];
+        data_descriptors[0] = data_descriptor(
+            provider.metadata.as_ptr(),
+            usize::from(provider.metadata_size),
+            PROVIDER_METADATA_DESCRIPTOR_TYPE,
+        );
+        data_descriptors[1] = data_descriptor(
+            self.metadata.as_ptr(),
+            self.metadata_size,
+            EVENT_METADATA_DESCRIPTOR_TYPE,
+        );
+        let mut data_descriptor_count = 2;
+        for field in &self.fields[..self.field_count] {
+            if field.counted {
+                data_descriptors[data_descriptor_count] = data_descriptor(
+                    core::ptr::from_ref(&field.size).cast(),
+                    core::mem::size_of::<u16>(),
+                    0,
+                );
+                data_descriptor_count += 1;
+            }
+            data_descriptors[data_descriptor_count] =
+                data_descriptor(field.data, usize::from(field.size), 0);
+            data_descriptor_count += 1;
+        }
+
+        // There are at most 2 + 2 * MAX_EVENT_FIELDS descriptors
+        #[allow(clippy::cast_possible_truncation)]
+        let data_descriptor_count = data_descriptor_count as ULONG;
+        // SAFETY: `reg_handle` is registered until `unregister`, which must not be
+        // called concurrently. The descriptors point to the metadata and to the
+        // values of the fields, which are borrowed by `self` and outlive the call.
+        unsafe {
+            EtwWriteTransfer(
+                reg_handle,
+                &raw const event_descriptor,
+                core::ptr::null(),
+                core::ptr::null(),
+                data_descriptor_count,
+                data_descriptors.as_mut_ptr(),
+            );
+        }
+    }
+
+    /// Append `parts` to the metadata, if they fit, and update its size.
+    /// Returns `true` if the parts were appended.
+    fn push_metadata(&mut self, parts: &[&[u8]]) -> bool {
+        let size = parts.iter().map(|part| part.len()).sum::<usize>();
+        if self.metadata_size + size > MAX_EVENT_METADATA_SIZE {
+            return false;
+        }
+        for part in parts {
+            self.metadata[self.metadata_size..self.metadata_size + part.len()]
+                .copy_from_slice(part);
+            self.metadata_size += part.len();
+        }
+        // MAX_EVENT_METADATA_SIZE fits in a u16
+        #[allow(clippy::cast_possible_truncation)]
+        let metadata_size = self.metadata_size as u16;
+        self.metadata[..2].copy_from_slice(&metadata_size.to_le_bytes());
+        true
+    }
+}
+
+/// Construct a data descriptor of `size` bytes at `data`, of `descriptor_type`
+fn data_descriptor(data: *const u8, size: usize, descriptor_type: u8) -> EVENT_DATA_DESCRIPTOR {
+    // Callers pass sizes of at most u16::MAX
+    #[allow(clippy::cast_possible_truncation)]
+    let size = size as ULONG;
+    let mut descriptor = EVENT_DATA_DESCRIPTOR {
+        Ptr: data as u64,
+        Size: size,
+        ..EVENT_DATA_DESCRIPTOR::default()
+    };
+    // The type is the low byte of `Reserved`
+    descriptor.__bindgen_anon_1.Reserved = ULONG::from(descriptor_type);
+    descriptor
+}
+
+/// Message of a [`trace!`](crate::trace) event, formatted on the stack with
+/// [`core::fmt::Write`]. Messages longer than [`MAX_TRACE_MESSAGE_SIZE`] are
+/// truncated at a character boundary.
+pub struct TraceMessage {
+    buffer: [u8; MAX_TRACE_MESSAGE_SIZE],
+    len: usize,
+    /// Set once the message is truncated, so that shorter text formatted
+    /// after the cut is not appended
+    truncated: bool,
+}
+
+impl TraceMessage {
+    /// Construct an empty message
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            buffer: [0; MAX_TRACE_MESSAGE_SIZE],
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    /// Get the message formatted so far
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        // Only whole characters are written to the buffer
+        core::str::from_utf8(&self.buffer[..self.len]).unwrap_or_default()
+    }
+}
+
+impl Default for TraceMessage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Write for TraceMessage {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.truncated {
+            return Ok(());
+        }
+        let mut len = s.len().min(MAX_TRACE_MESSAGE_SIZE - self.len);
+        self.truncated = len < s.len();
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.buffer[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.len += len;
+        // Truncating is not an error, so that the rest of the arguments are not
+        // formatted in vain but the message is still written
+        Ok(())
+    }
+}
+
+/// Writes a TraceLogging event to an [`EtwProvider`](crate::etw::EtwProvider).
+///
+/// The arguments are the provider, the name of the event, its
+/// [`Level`](crate::etw::Level) and keyword, followed by its fields as
+/// `name = value` pairs. Values can be integers, `bool`, `&str` or `&[u8]`,
+/// see [`EtwField`](crate::etw::EtwField). The values are only evaluated if a
+/// consumer enabled the level and keyword of the event.
+///
+/// # Examples
+///
+/// ```ignore
+/// wdk::etw_event!(
+///     PROVIDER,
+///     "RequestCompleted",
+///     Level::Verbose,
+///     KEYWORD_IO,
+///     status = nt_status,
+///     bytes = information as u64,
+///     path = "\\Device\\MyDevice",
+/// );
+/// ```
+#[macro_export]
+macro_rules! etw_event {
+    ($provider:expr, $name:literal, $level:expr, $keyword:expr $(, $field:ident = $value:expr)* $(,)?) => {{
+        let provider: &$crate::etw::EtwProvider = &$provider;
+        let level: $crate::etw::Level = $level;
+        let keyword: u64 = $keyword;
+        if provider.is_enabled(level, keyword) {
+            $(let $field = $value;)*
+            let mut event = $crate::etw::EtwEvent::new($name);
+            $(event.field(stringify!($field), &$field);)*
+            event.write(provider, level, keyword);
+        }
+    }};
+}
+
+/// Writes a WPP-style trace message to an
+/// [`EtwProvider`](crate::etw::EtwProvider).
+///
+/// The arguments are the provider, the [`Level`](crate::etw::Level) of the
+/// message and its flag bits, which consumers enable like WPP trace flags,
+/// followed by a format string and its arguments as for
+/// [`format_args!`](core::format_args). The message is written as a `Trace`
+/// event with `message`, `file` and `line` fields, so that it can be traced
+/// back to its call site. The arguments are only formatted if a consumer
+/// enabled the level and flags of the message, and the message is truncated
+/// to [`MAX_TRACE_MESSAGE_SIZE`](crate::etw::MAX_TRACE_MESSAGE_SIZE) bytes.
+/// Must be called at `IRQL` <= `HIGH_LEVEL`, as long as the arguments can be
+/// formatted at the current `IRQL`.
+///
+/// # Examples
+///
+/// ```ignore
+/// wdk::trace!(PROVIDER, Level::Error, FLAG_IO, "read failed: {:#010x}", nt_status);
+/// ```
+#[macro_export]
+macro_rules! trace {
+    ($provider:expr, $level:expr, $flags:expr, $($arg:tt)+) => {{
+        let provider: &$crate::etw::EtwProvider = &$provider;
+        let level: $crate::etw::Level = $level;
+        let flags: u64 = $flags;
+        if provider.is_enabled(level, flags) {
+            $crate::etw::_trace(
+                provider,
+                level,
+                flags,
+                ::core::format_args!($($arg)+),
+                ::core::file!(),
+                ::core::line!(),
+            );
+        }
+    }};
+}
+
+/// Internal implementation of [`trace!`](crate::trace), which writes `args`
+/// as a `Trace` event. This function is an implementation detail and should
+/// never be called directly, but must be public to be useable by the macros.
+#[doc(hidden)]
+pub fn _trace(
+    provider: &EtwProvider,
+    level: Level,
+    flags: u64,
+    args: fmt::Arguments,
+    file: &str,
+    line: u32,
+) {
+    let mut message = TraceMessage::new();
+    // Formatting into a `TraceMessage` never fails
+    let _ = fmt::write(&mut message, args);
+    let mut event = EtwEvent::new("Trace");
+    event
+        .field("message", message.as_str())
+        .field("file", file)
+        .field("line", &line);
+    event.write(provider, level, flags);
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Write;
+
+    use super::*;
+
+    #[test]
+    fn event_metadata_describes_name_and_fields() {
+        let status = 5i32;
+        let path = "abc";
+        let mut event = EtwEvent::new("Ev");
+        event.field("s", &status).field("p", &path);
+
+        let expected: &[u8] = &[
+            13,
+            0, // size
+            0, // tags
+            b'E',
+            b'v',
+            0, // name
+            b's',
+            0,
+            7, // i32 field
+            b'p',
+            0,
+            23 | OUT_TYPE_CHAIN_FLAG,
+            35, // UTF-8 counted string field
+        ];
+        assert_eq!(&event.metadata[..event.metadata_size], expected);
+        assert_eq!(event.field_count, 2);
+        assert!(event.fields[1].counted);
+        assert_eq!(event.fields[1].size, 3);
+    }
+
+    #[test]
+    fn fields_beyond_max_are_dropped() {
+        let value = 1u8;
+        let mut event = EtwEvent::new("Ev");
+        for _ in 0..=MAX_EVENT_FIELDS {
+            event.field("f", &value);
+        }
+
+        assert_eq!(event.field_count, MAX_EVENT_FIELDS);
+    }
+
+    #[test]
+    fn provider_metadata_holds_size_and_name() {
+        let provider = EtwProvider::new("A.B", TraceControlGuid::new(crate::Guid::NIL));
+
+        assert_eq!(provider.metadata_size, 6);
+        assert_eq!(&provider.metadata[..6], &[6, 0, b'A', b'.', b'B', 0]);
+    }
+
+    #[test]
+    fn trace_message_is_formatted() {
+        let mut message = TraceMessage::new();
+        write!(message, "device {} failed with {:#x}", 3, 0xC000_0001u32)
+            .expect("writing to a trace message never fails");
+
+        assert_eq!(message.as_str(), "device 3 failed with 0xc0000001");
+    }
+
+    #[test]
+    fn trace_message_is_truncated_at_char_boundary() {
+        let mut message = TraceMessage::new();
+        for _ in 0..MAX_TRACE_MESSAGE_SIZE - 1 {
+            message
+                .write_char('a')
+                .expect("writing to a trace message never fails");
+        }
+        // The 2-byte character does not fit, so the message ends before it
+        message
+            .write_str("\u{e9}")
+            .expect("writing to a trace message never fails");
+        message
+            .write_char('b')
+            .expect("writing to a trace message never fails");
+
+        assert_eq!(message.as_str().len(), MAX_TRACE_MESSAGE_SIZE - 1);
+        assert!(message.as_str().bytes().all(|b| b == b'a'));
+    }
+}
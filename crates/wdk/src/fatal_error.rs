@@ -0,0 +1,168 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::fmt::{self, Write};
+
+#[cfg(driver_model__driver_type = "KMDF")]
+use wdk_sys::call_unsafe_wdf_function_binding;
+#[cfg(driver_model__driver_type = "WDM")]
+use wdk_sys::ntddk::KeBugCheckEx;
+use wdk_sys::{
+    ULONG,
+    ULONG_PTR,
+    ntddk::{DbgPrint, KdRefreshDebuggerNotPresent},
+};
+
+/// Max size that can be transmitted by `DbgPrint` in single call:
+/// <https://learn.microsoft.com/en-us/windows-hardware/drivers/debugger/reading-and-filtering-debugging-messages#dbgprint-buffer-and-the-debugger>
+const FATAL_ERROR_MESSAGE_MAX_SIZE: usize = 512;
+
+/// Stops the system with a bug check, to handle an unrecoverable invariant
+/// violation of the driver.
+///
+/// The first argument is the bug check code, followed by an optional message
+/// with the same syntax as [`core::format!`]. This:
+///
+/// 1. Prints the message, along with the file and line of the call, to the
+///    debugger.
+/// 2. Breaks into the kernel debugger, if one is attached, so that the state of
+///    the driver can be inspected before the system stops.
+/// 3. Bug checks with the code. The parameters of the bug check are the address
+///    of the NUL-terminated message, the address of the file name, the line and
+///    zero. The message stays on the stack of the thread, so it is captured in
+///    the crash dump. KMDF drivers bug check through `WdfVerifierKeBugCheck`,
+///    so that the in-flight recorder log of the framework is captured along
+///    with it.
+///
+/// Messages longer than 511 bytes are truncated. This can be called at any
+/// `IRQL`, but the message is only printed at `IRQL` <= `DIRQL`, see
+/// [`wdk_sys::ntddk::DbgPrint`].
+///
+/// # Examples
+///
+/// ```ignore
+/// const QUEUE_STATE_CORRUPTED: u32 = 0xE000_0001;
+///
+/// if pending_requests > MAX_PENDING_REQUESTS {
+///     wdk::fatal_error!(QUEUE_STATE_CORRUPTED, "{pending_requests} requests pending");
+/// }
+/// ```
+#[macro_export]
+macro_rules! fatal_error {
+    ($code:expr $(,)?) => {
+        $crate::_fatal_error($code, format_args!(""), concat!(file!(), "\0"), line!())
+    };
+
+    ($code:expr, $($arg:tt)+) => {
+        $crate::_fatal_error($code, format_args!($($arg)+), concat!(file!(), "\0"), line!())
+    };
+}
+
+/// Internal implementation of the [`fatal_error!`] macro. `file` must be
+/// NUL-terminated.
+#[doc(hidden)]
+#[allow(
+    unreachable_code,
+    reason = "the bug check does not return, which depending on the WDK headers is not reflected \
+              in its binding"
+)]
+pub fn _fatal_error(code: ULONG, message: fmt::Arguments, file: &'static str, line: u32) -> ! {
+    let mut message_buffer = FatalErrorMessageBuffer {
+        // buffer is initialized to all null, so it is always NUL terminated
+        buffer: [0; FATAL_ERROR_MESSAGE_MAX_SIZE],
+        used: 0,
+    };
+    // A failing `Display` implementation only cuts the message short, which is
+    // still worth reporting
+    let _ = message_buffer.write_fmt(message);
+
+    // SAFETY: `message_buffer.buffer` and `file` are NUL terminated and are passed
+    // as the arguments of `%s` format specifiers
+    unsafe {
+        DbgPrint(
+            c"Fatal error 0x%08X at %s:%u: %s\n".as_ptr(),
+            code,
+            file.as_ptr(),
+            line,
+            message_buffer.buffer.as_ptr(),
+        );
+    }
+
+    let debugger_not_present;
+    // SAFETY: Checking for the kernel debugger is allowed at any IRQL
+    unsafe {
+        debugger_not_present = KdRefreshDebuggerNotPresent();
+    }
+    if debugger_not_present == 0 {
+        crate::dbg_break();
+    }
+
+    let parameters = [
+        message_buffer.buffer.as_ptr() as ULONG_PTR,
+        file.as_ptr() as ULONG_PTR,
+        ULONG_PTR::from(line),
+        0,
+    ];
+    cfg_if::cfg_if! {
+        if #[cfg(driver_model__driver_type = "KMDF")] {
+            // SAFETY: Bug checking is allowed at any IRQL. The message stays on the
+            // stack of this thread, where it is captured by the dump.
+            unsafe {
+                call_unsafe_wdf_function_binding!(
+                    WdfVerifierKeBugCheck,
+                    code,
+                    parameters[0],
+                    parameters[1],
+                    parameters[2],
+                    parameters[3],
+                );
+            }
+        } else {
+            // SAFETY: Bug checking is allowed at any IRQL. The message stays on the
+            // stack of this thread, where it is captured by the dump.
+            unsafe {
+                KeBugCheckEx(code, parameters[0], parameters[1], parameters[2], parameters[3]);
+            }
+        }
+    }
+    loop {}
+}
+
+/// Stack-based buffer the message of a fatal error is formatted into, so that
+/// reporting it does not need heap allocations. Messages that do not fit are
+/// truncated, and the last byte is always kept for the NUL terminator.
+struct FatalErrorMessageBuffer {
+    buffer: [u8; FATAL_ERROR_MESSAGE_MAX_SIZE],
+    used: usize,
+}
+
+impl Write for FatalErrorMessageBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let remaining_buffer = &mut self.buffer[self.used..FATAL_ERROR_MESSAGE_MAX_SIZE - 1];
+        let chunk_size = s.len().min(remaining_buffer.len());
+        remaining_buffer[..chunk_size].copy_from_slice(&s.as_bytes()[..chunk_size]);
+        self.used += chunk_size;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_that_exceeds_buffer_is_truncated_and_nul_terminated() {
+        let mut message_buffer = FatalErrorMessageBuffer {
+            buffer: [0; FATAL_ERROR_MESSAGE_MAX_SIZE],
+            used: 0,
+        };
+        for _ in 0..FATAL_ERROR_MESSAGE_MAX_SIZE + 10 {
+            message_buffer
+                .write_str("a")
+                .expect("writing should not fail");
+        }
+
+        assert_eq!(message_buffer.used, FATAL_ERROR_MESSAGE_MAX_SIZE - 1);
+        assert_eq!(message_buffer.buffer[FATAL_ERROR_MESSAGE_MAX_SIZE - 1], 0);
+    }
+}
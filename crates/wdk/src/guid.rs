@@ -0,0 +1,384 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Typed wrapper over [`GUID`] values, with parsing and formatting in the
+//! registry format, ex. `{6B4E9A0C-1D2F-4A3B-8C5D-7E6F8091A2B3}`, and newtypes
+//! of the GUIDs that identify different kinds of things, so that passing one
+//! kind where another is expected is a type error.
+
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+use core::fmt;
+
+#[cfg(any(driver_model__driver_type = "WDM", driver_model__driver_type = "KMDF"))]
+use wdk_sys::ntddk::ExUuidCreate;
+use wdk_sys::{GUID, NTSTATUS, STATUS_INVALID_PARAMETER};
+
+/// Length of a GUID in the registry format, without braces
+const GUID_STRING_LEN: usize = 36;
+/// Offsets of the dashes of a GUID in the registry format, without braces
+const DASH_OFFSETS: [usize; 4] = [8, 13, 18, 23];
+
+/// A typed [`GUID`] value.
+///
+/// Formatted with [`Display`](fmt::Display) in the registry format without
+/// braces, ex. `6B4E9A0C-1D2F-4A3B-8C5D-7E6F8091A2B3`, or with braces using the
+/// alternate flag `{:#}`. [`Debug`](fmt::Debug) always uses braces.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Guid {
+    data1: u32,
+    data2: u16,
+    data3: u16,
+    data4: [u8; 8],
+}
+
+impl Guid {
+    /// Length of a GUID in the registry format with braces, ex.
+    /// `{6B4E9A0C-1D2F-4A3B-8C5D-7E6F8091A2B3}`
+    pub const BRACED_LEN: usize = GUID_STRING_LEN + 2;
+    /// The nil GUID, `00000000-0000-0000-0000-000000000000`
+    pub const NIL: Self = Self::from_u128(0);
+
+    /// Construct a [`Guid`] from its fields, in the order of the groups of the
+    /// registry format
+    #[must_use]
+    pub const fn from_fields(data1: u32, data2: u16, data3: u16, data4: [u8; 8]) -> Self {
+        Self {
+            data1,
+            data2,
+            data3,
+            data4,
+        }
+    }
+
+    /// Construct a [`Guid`] from its value, in the order it is written in, ex.
+    /// `0x6B4E9A0C_1D2F_4A3B_8C5D_7E6F8091A2B3`
+    #[must_use]
+    pub const fn from_u128(value: u128) -> Self {
+        let bytes = value.to_be_bytes();
+        Self {
+            data1: u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            data2: u16::from_be_bytes([bytes[4], bytes[5]]),
+            data3: u16::from_be_bytes([bytes[6], bytes[7]]),
+            data4: [
+                bytes[8], bytes[9], bytes[10], bytes[11], bytes[12], bytes[13], bytes[14],
+                bytes[15],
+            ],
+        }
+    }
+
+    /// Get the value of the GUID, in the order it is written in
+    #[must_use]
+    pub const fn as_u128(&self) -> u128 {
+        let data1 = self.data1.to_be_bytes();
+        let data2 = self.data2.to_be_bytes();
+        let data3 = self.data3.to_be_bytes();
+        let data4 = self.data4;
+        u128::from_be_bytes([
+            data1[0], data1[1], data1[2], data1[3], data2[0], data2[1], data3[0], data3[1],
+            data4[0], data4[1], data4[2], data4[3], data4[4], data4[5], data4[6], data4[7],
+        ])
+    }
+
+    /// Parse a GUID in the registry format, with or without braces, ex.
+    /// `{6B4E9A0C-1D2F-4A3B-8C5D-7E6F8091A2B3}`. Hexadecimal digits can be
+    /// upper or lower case.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_INVALID_PARAMETER` if `s` is not a
+    /// GUID in the registry format.
+    // `u128::from` cannot be used in const functions
+    #[allow(clippy::cast_lossless)]
+    pub const fn parse(s: &str) -> Result<Self, NTSTATUS> {
+        let mut bytes = s.as_bytes();
+        if let [b'{', inner @ .., b'}'] = bytes {
+            bytes = inner;
+        }
+        if bytes.len() != GUID_STRING_LEN {
+            return Err(STATUS_INVALID_PARAMETER);
+        }
+
+        let mut value: u128 = 0;
+        let mut i = 0;
+        while i < GUID_STRING_LEN {
+            let byte = bytes[i];
+            if i == DASH_OFFSETS[0]
+                || i == DASH_OFFSETS[1]
+                || i == DASH_OFFSETS[2]
+                || i == DASH_OFFSETS[3]
+            {
+                if byte != b'-' {
+                    return Err(STATUS_INVALID_PARAMETER);
+                }
+            } else {
+                let digit = match byte {
+                    b'0'..=b'9' => byte - b'0',
+                    b'a'..=b'f' => byte - b'a' + 10,
+                    b'A'..=b'F' => byte - b'A' + 10,
+                    _ => return Err(STATUS_INVALID_PARAMETER),
+                };
+                value = (value << 4) | digit as u128;
+            }
+            i += 1;
+        }
+        Ok(Self::from_u128(value))
+    }
+
+    /// Get the GUID as a [`GUID`] of the bindings, in `const` contexts where
+    /// [`From`] cannot be used
+    pub(crate) const fn to_raw(self) -> GUID {
+        GUID {
+            Data1: self.data1,
+            Data2: self.data2,
+            Data3: self.data3,
+            Data4: self.data4,
+        }
+    }
+
+    /// Generate a new random GUID, ex. for a container ID that is unique per
+    /// boot. Must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the system could not generate a
+    /// GUID, ex. `STATUS_RETRY` if it could not be generated yet.
+    #[cfg(any(driver_model__driver_type = "WDM", driver_model__driver_type = "KMDF"))]
+    pub fn new_random() -> Result<Self, NTSTATUS> {
+        let mut guid = GUID::default();
+
+        let nt_status;
+        // SAFETY: `guid` is valid for writes for the duration of the call
+        unsafe {
+            nt_status = ExUuidCreate(&raw mut guid);
+        }
+        crate::nt_success(nt_status)
+            .then_some(Self::from(guid))
+            .ok_or(nt_status)
+    }
+
+    /// Write the GUID in the registry format with braces into `buffer`, ex.
+    /// `{6B4E9A0C-1D2F-4A3B-8C5D-7E6F8091A2B3}`, and get it as a string. Unlike
+    /// [`Guid::to_string_braced()`], this does not allocate.
+    #[must_use]
+    pub fn to_str_braced<'a>(&self, buffer: &'a mut [u8; Self::BRACED_LEN]) -> &'a str {
+        const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+        buffer[0] = b'{';
+        buffer[Self::BRACED_LEN - 1] = b'}';
+        // Write the digits from the last one, as the value is shifted out from its
+        // least significant end
+        let mut value = self.as_u128();
+        for offset in (0..GUID_STRING_LEN).rev() {
+            buffer[offset + 1] = if DASH_OFFSETS.contains(&offset) {
+                b'-'
+            } else {
+                // The digit is masked to 4 bits, so it always fits
+                #[allow(clippy::cast_possible_truncation)]
+                let digit = HEX_DIGITS[(value & 0xF) as usize];
+                value >>= 4;
+                digit
+            };
+        }
+        let buffer: &'a [u8] = buffer;
+        // SAFETY: `buffer` was filled with ASCII characters above
+        unsafe { core::str::from_utf8_unchecked(buffer) }
+    }
+
+    /// Get the GUID in the registry format with braces, ex.
+    /// `{6B4E9A0C-1D2F-4A3B-8C5D-7E6F8091A2B3}`
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn to_string_braced(&self) -> String {
+        alloc::format!("{self:#}")
+    }
+
+    /// Get the GUID in the registry format with braces, like
+    /// [`Guid::to_string_braced()`], but without panicking when the string
+    /// cannot be allocated
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_INSUFFICIENT_RESOURCES` if the string
+    /// could not be allocated, after applying the
+    /// [`crate::alloc_failure::AllocFailurePolicy`] of the driver.
+    #[cfg(feature = "alloc")]
+    pub fn try_to_string_braced(&self) -> Result<String, NTSTATUS> {
+        crate::alloc_failure::try_format(format_args!("{self:#}"))
+    }
+}
+
+/// Declare a newtype of [`Guid`] for the GUIDs of one kind of thing
+macro_rules! declare_guid_newtype {
+    ($(#[$attribute:meta])* $name:ident) => {
+        $(#[$attribute])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        #[repr(transparent)]
+        pub struct $name(Guid);
+
+        impl $name {
+            #[doc = concat!("Construct a [`", stringify!($name), "`] from its [`Guid`]")]
+            #[must_use]
+            pub const fn new(guid: Guid) -> Self {
+                Self(guid)
+            }
+
+            /// Get the underlying [`Guid`]
+            #[must_use]
+            pub const fn guid(self) -> Guid {
+                self.0
+            }
+        }
+
+        impl From<$name> for GUID {
+            fn from(guid: $name) -> Self {
+                guid.0.into()
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Display::fmt(&self.0, f)
+            }
+        }
+    };
+}
+
+declare_guid_newtype!(
+    /// GUID of a device interface class, ex. `GUID_DEVINTERFACE_HID`, that
+    /// applications and drivers use to find the devices exposing the
+    /// interface. Accepted by [`Device::create_interface()`].
+    ///
+    /// [`Device::create_interface()`]: crate::wdf::Device::create_interface
+    InterfaceGuid
+);
+
+declare_guid_newtype!(
+    /// GUID of an ETW provider, i.e. its control GUID, that trace consumers
+    /// enable the provider by. Accepted by [`EtwProvider::new()`].
+    ///
+    /// [`EtwProvider::new()`]: crate::etw::EtwProvider::new
+    TraceControlGuid
+);
+
+declare_guid_newtype!(
+    /// GUID of a device setup class, ex. `GUID_DEVCLASS_SYSTEM`, that groups
+    /// devices installed and configured the same way. Accepted by
+    /// `wdf::set_device_class()` in KMDF drivers.
+    DeviceClassGuid
+);
+
+impl From<GUID> for Guid {
+    fn from(guid: GUID) -> Self {
+        Self {
+            data1: guid.Data1,
+            data2: guid.Data2,
+            data3: guid.Data3,
+            data4: guid.Data4,
+        }
+    }
+}
+
+impl From<Guid> for GUID {
+    fn from(guid: Guid) -> Self {
+        guid.to_raw()
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [d0, d1, d2, d3, d4, d5, d6, d7] = self.data4;
+        let (open, close) = if f.alternate() { ("{", "}") } else { ("", "") };
+        write!(
+            f,
+            "{open}{:08X}-{:04X}-{:04X}-{d0:02X}{d1:02X}-",
+            self.data1, self.data2, self.data3,
+        )?;
+        write!(f, "{d2:02X}{d3:02X}{d4:02X}{d5:02X}{d6:02X}{d7:02X}{close}")
+    }
+}
+
+impl fmt::Debug for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:#}")
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use alloc::{format, string::ToString};
+
+    use super::*;
+
+    const GUID_VALUE: u128 = 0x6B4E_9A0C_1D2F_4A3B_8C5D_7E6F_8091_A2B3;
+
+    #[test]
+    fn from_fields_matches_from_u128() {
+        let guid = Guid::from_fields(
+            0x6B4E_9A0C,
+            0x1D2F,
+            0x4A3B,
+            [0x8C, 0x5D, 0x7E, 0x6F, 0x80, 0x91, 0xA2, 0xB3],
+        );
+        assert_eq!(guid, Guid::from_u128(GUID_VALUE));
+        assert_eq!(guid.as_u128(), GUID_VALUE);
+    }
+
+    #[test]
+    fn formats_in_registry_format() {
+        let guid = Guid::from_u128(GUID_VALUE);
+        assert_eq!(format!("{guid}"), "6B4E9A0C-1D2F-4A3B-8C5D-7E6F8091A2B3");
+        assert_eq!(
+            guid.to_string_braced(),
+            "{6B4E9A0C-1D2F-4A3B-8C5D-7E6F8091A2B3}"
+        );
+        assert_eq!(
+            guid.try_to_string_braced().as_deref(),
+            Ok("{6B4E9A0C-1D2F-4A3B-8C5D-7E6F8091A2B3}")
+        );
+        assert_eq!(
+            guid.to_str_braced(&mut [0; Guid::BRACED_LEN]),
+            "{6B4E9A0C-1D2F-4A3B-8C5D-7E6F8091A2B3}"
+        );
+        assert_eq!(
+            format!("{guid:?}"),
+            "{6B4E9A0C-1D2F-4A3B-8C5D-7E6F8091A2B3}"
+        );
+    }
+
+    #[test]
+    fn parse_round_trips_with_and_without_braces() {
+        let guid = Guid::from_u128(GUID_VALUE);
+        assert_eq!(Guid::parse(&guid.to_string()), Ok(guid));
+        assert_eq!(Guid::parse(&guid.to_string_braced()), Ok(guid));
+        assert_eq!(
+            Guid::parse("6b4e9a0c-1d2f-4a3b-8c5d-7e6f8091a2b3"),
+            Ok(guid)
+        );
+    }
+
+    #[test]
+    fn newtypes_keep_their_guid() {
+        let guid = Guid::from_u128(GUID_VALUE);
+        let interface = InterfaceGuid::new(guid);
+        assert_eq!(interface.guid(), guid);
+        assert_eq!(GUID::from(interface).Data1, 0x6B4E_9A0C);
+        assert_eq!(
+            TraceControlGuid::new(guid).to_string(),
+            "6B4E9A0C-1D2F-4A3B-8C5D-7E6F8091A2B3"
+        );
+    }
+
+    #[test]
+    fn parse_rejects_malformed_guids() {
+        for s in [
+            "",
+            "{6B4E9A0C-1D2F-4A3B-8C5D-7E6F8091A2B3",
+            "6B4E9A0C-1D2F-4A3B-8C5D-7E6F8091A2B",
+            "6B4E9A0C-1D2F-4A3B-8C5D_7E6F8091A2B3",
+            "6B4E9A0C-1D2F-4A3B-8C5D-7E6F8091A2BG",
+        ] {
+            assert_eq!(Guid::parse(s), Err(STATUS_INVALID_PARAMETER));
+        }
+    }
+}
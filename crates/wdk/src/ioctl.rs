@@ -0,0 +1,248 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Typed I/O control codes, the equivalent of the `CTL_CODE` macro, so that
+//! drivers can define and dispatch on device control codes without packing and
+//! unpacking their fields by hand.
+
+use core::fmt;
+
+use wdk_sys::ULONG;
+
+/// Largest device type of a [`ControlCode`]. Values from `0x8000` are reserved
+/// for device types defined by vendors.
+const MAX_DEVICE_TYPE: ULONG = 0xFFFF;
+/// Largest function code of a [`ControlCode`]. Values from `0x800` are
+/// reserved for function codes defined by vendors.
+const MAX_FUNCTION: ULONG = 0xFFF;
+
+/// How the I/O manager passes the buffers of a device control request to the
+/// driver, ex. `METHOD_BUFFERED`.
+///
+/// The framework retrieves the buffers of [`TransferMethod::Buffered`]
+/// requests, which are copies in system memory, as slices with
+/// [`Request::retrieve_input_buffer()`](crate::wdf::Request::retrieve_input_buffer)
+/// and [`Request::retrieve_output_buffer()`](crate::wdf::Request::retrieve_output_buffer).
+/// The buffers of [`TransferMethod::InDirect`] and
+/// [`TransferMethod::OutDirect`] requests are the pages of the application,
+/// mapped for the driver, so they are only copied to and from, with
+/// [`Request::retrieve_direct_input_buffer()`](crate::wdf::Request::retrieve_direct_input_buffer)
+/// and [`Request::retrieve_direct_output_buffer()`](crate::wdf::Request::retrieve_direct_output_buffer).
+/// The buffers of [`TransferMethod::Neither`] requests are user-mode addresses,
+/// which are only valid in the context of the sending thread, so they cannot be
+/// retrieved that way. They are probed and locked in that context instead, with
+/// [`Request::probe_and_lock_user_buffer_for_read()`](crate::wdf::Request::probe_and_lock_user_buffer_for_read)
+/// and [`Request::probe_and_lock_user_buffer_for_write()`](crate::wdf::Request::probe_and_lock_user_buffer_for_write).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum TransferMethod {
+    /// `METHOD_BUFFERED`: the input and output buffers are copied through a
+    /// single system buffer
+    Buffered = 0,
+    /// `METHOD_IN_DIRECT`: the input buffer is copied through a system buffer,
+    /// and the output buffer is locked in memory and read by the driver
+    InDirect = 1,
+    /// `METHOD_OUT_DIRECT`: the input buffer is copied through a system buffer,
+    /// and the output buffer is locked in memory and written by the driver
+    OutDirect = 2,
+    /// `METHOD_NEITHER`: the driver receives the user-mode addresses of the
+    /// buffers
+    Neither = 3,
+}
+
+/// Access rights the handle a device control request is sent through must have
+/// been opened with, ex. `FILE_READ_ACCESS`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum RequiredAccess {
+    /// `FILE_ANY_ACCESS`: any handle to the device
+    Any = 0,
+    /// `FILE_READ_ACCESS`: a handle opened for reading
+    Read = 1,
+    /// `FILE_WRITE_ACCESS`: a handle opened for writing
+    Write = 2,
+    /// `FILE_READ_ACCESS | FILE_WRITE_ACCESS`: a handle opened for reading and
+    /// writing
+    ReadWrite = 3,
+}
+
+/// An I/O control code, as sent by `DeviceIoControl` and delivered in
+/// [`RequestParameters::DeviceControl`](crate::wdf::RequestParameters::DeviceControl).
+///
+/// A control code packs a device type, a function code, a [`TransferMethod`]
+/// and the [`RequiredAccess`] of the request. Control codes built with
+/// [`ControlCode::new()`] in constants can be matched on directly:
+///
+/// ```ignore
+/// const IOCTL_ECHO: ControlCode = ControlCode::new(
+///     FILE_DEVICE_UNKNOWN,
+///     0x800,
+///     TransferMethod::Buffered,
+///     RequiredAccess::Any,
+/// );
+///
+/// match request.params() {
+///     RequestParameters::DeviceControl { code: IOCTL_ECHO, .. } => { /* ... */ }
+///     _ => request.complete(STATUS_INVALID_DEVICE_REQUEST),
+/// }
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct ControlCode(ULONG);
+
+impl ControlCode {
+    /// Build a control code from its fields, like the `CTL_CODE` macro
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `device_type` is larger than `0xFFFF` or `function` is
+    /// larger than `0xFFF`. When called in a constant, this fails the build
+    /// instead.
+    #[must_use]
+    pub const fn new(
+        device_type: ULONG,
+        function: ULONG,
+        method: TransferMethod,
+        access: RequiredAccess,
+    ) -> Self {
+        assert!(
+            device_type <= MAX_DEVICE_TYPE,
+            "device type of a control code must fit in 16 bits"
+        );
+        assert!(
+            function <= MAX_FUNCTION,
+            "function code of a control code must fit in 12 bits"
+        );
+
+        Self((device_type << 16) | ((access as ULONG) << 14) | (function << 2) | method as ULONG)
+    }
+
+    /// Wrap a raw control code, ex. the one passed to an `EvtIoDeviceControl`
+    /// callback
+    #[must_use]
+    pub const fn from_raw(code: ULONG) -> Self {
+        Self(code)
+    }
+
+    /// Get the raw control code, ex. to pass to `DeviceIoControl`
+    #[must_use]
+    pub const fn as_raw(self) -> ULONG {
+        self.0
+    }
+
+    /// Get the device type of the control code, ex. `FILE_DEVICE_UNKNOWN`
+    #[must_use]
+    pub const fn device_type(self) -> ULONG {
+        self.0 >> 16
+    }
+
+    /// Get the function code of the control code
+    #[must_use]
+    pub const fn function(self) -> ULONG {
+        (self.0 >> 2) & MAX_FUNCTION
+    }
+
+    /// Get the transfer method of the control code
+    #[must_use]
+    pub const fn method(self) -> TransferMethod {
+        match self.0 & 0b11 {
+            0 => TransferMethod::Buffered,
+            1 => TransferMethod::InDirect,
+            2 => TransferMethod::OutDirect,
+            _ => TransferMethod::Neither,
+        }
+    }
+
+    /// Get the access rights required by the control code
+    #[must_use]
+    pub const fn access(self) -> RequiredAccess {
+        match (self.0 >> 14) & 0b11 {
+            0 => RequiredAccess::Any,
+            1 => RequiredAccess::Read,
+            2 => RequiredAccess::Write,
+            _ => RequiredAccess::ReadWrite,
+        }
+    }
+}
+
+impl From<ULONG> for ControlCode {
+    fn from(code: ULONG) -> Self {
+        Self::from_raw(code)
+    }
+}
+
+impl From<ControlCode> for ULONG {
+    fn from(code: ControlCode) -> Self {
+        code.as_raw()
+    }
+}
+
+impl fmt::Debug for ControlCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ControlCode")
+            .field("code", &format_args!("{:#010X}", self.0))
+            .field("device_type", &format_args!("{:#X}", self.device_type()))
+            .field("function", &format_args!("{:#X}", self.function()))
+            .field("method", &self.method())
+            .field("access", &self.access())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `FILE_DEVICE_UNKNOWN`
+    const DEVICE_TYPE: ULONG = 0x22;
+
+    #[test]
+    fn new_matches_ctl_code() {
+        // CTL_CODE(FILE_DEVICE_UNKNOWN, 0x800, METHOD_BUFFERED, FILE_ANY_ACCESS)
+        let code = ControlCode::new(
+            DEVICE_TYPE,
+            0x800,
+            TransferMethod::Buffered,
+            RequiredAccess::Any,
+        );
+        assert_eq!(code.as_raw(), 0x0022_2000);
+
+        // CTL_CODE(FILE_DEVICE_UNKNOWN, 0x801, METHOD_OUT_DIRECT, FILE_READ_ACCESS |
+        // FILE_WRITE_ACCESS)
+        let code = ControlCode::new(
+            DEVICE_TYPE,
+            0x801,
+            TransferMethod::OutDirect,
+            RequiredAccess::ReadWrite,
+        );
+        assert_eq!(code.as_raw(), 0x0022_E006);
+    }
+
+    #[test]
+    fn fields_round_trip() {
+        let code = ControlCode::from_raw(
+            ControlCode::new(
+                0x8001,
+                0xABC,
+                TransferMethod::InDirect,
+                RequiredAccess::Write,
+            )
+            .as_raw(),
+        );
+        assert_eq!(code.device_type(), 0x8001);
+        assert_eq!(code.function(), 0xABC);
+        assert_eq!(code.method(), TransferMethod::InDirect);
+        assert_eq!(code.access(), RequiredAccess::Write);
+    }
+
+    #[test]
+    #[should_panic(expected = "function code of a control code must fit in 12 bits")]
+    fn new_rejects_large_function() {
+        let _ = ControlCode::new(
+            DEVICE_TYPE,
+            0x1000,
+            TransferMethod::Buffered,
+            RequiredAccess::Any,
+        );
+    }
+}
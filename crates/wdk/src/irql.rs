@@ -0,0 +1,65 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::marker::PhantomData;
+
+#[cfg(any(driver_model__driver_type = "WDM", driver_model__driver_type = "KMDF"))]
+use wdk_sys::{PASSIVE_LEVEL, ntddk::KeGetCurrentIrql};
+
+/// Proof that the current thread runs at `IRQL` = `PASSIVE_LEVEL`.
+///
+/// APIs that must be called at `PASSIVE_LEVEL`, ex. opening a `RegistryKey`,
+/// waiting for a `WaitLock` or waiting for the requests of an `IoQueue` to
+/// complete, take a [`PassiveLevel`], so that calling them from code that may
+/// run at `DISPATCH_LEVEL`, ex. a timer or DPC callback, fails to compile.
+///
+/// Callbacks that the framework always invokes at `PASSIVE_LEVEL` are passed
+/// one, ex. `WorkItemCallback::run`. Other code gets one by checking the
+/// current `IRQL` with [`PassiveLevel::check()`], or, when the framework
+/// documents that it invokes a callback at `PASSIVE_LEVEL`, ex.
+/// `EvtDriverDeviceAdd`, with [`PassiveLevel::new_unchecked()`].
+///
+/// The token is neither `Send` nor `Sync`, so it cannot be stored in the
+/// context of an object whose callbacks run at a higher `IRQL`. It does not
+/// track the `IRQL` being raised while it is alive, so it must not be used
+/// while a spin lock is held.
+#[derive(Clone, Copy, Debug)]
+pub struct PassiveLevel {
+    _not_send: PhantomData<*const ()>,
+}
+
+impl PassiveLevel {
+    /// Get a [`PassiveLevel`] without checking the current `IRQL`
+    ///
+    /// # Safety
+    ///
+    /// The caller must run at `IRQL` = `PASSIVE_LEVEL`, and must not raise the
+    /// `IRQL` while the returned token or its copies are used.
+    #[must_use]
+    pub const unsafe fn new_unchecked() -> Self {
+        Self {
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Get a [`PassiveLevel`] if the current thread runs at `IRQL` =
+    /// `PASSIVE_LEVEL`, or `None` otherwise. Can be called at any `IRQL`.
+    #[must_use]
+    pub fn check() -> Option<Self> {
+        #[cfg(any(driver_model__driver_type = "WDM", driver_model__driver_type = "KMDF"))]
+        {
+            let irql;
+            // SAFETY: `KeGetCurrentIrql` can be called at any IRQL, and has no other
+            // requirements
+            unsafe {
+                irql = KeGetCurrentIrql();
+            }
+            if u32::from(irql) != PASSIVE_LEVEL {
+                return None;
+            }
+        }
+        // SAFETY: The `IRQL` was checked above, and user-mode drivers always run at
+        // `PASSIVE_LEVEL`
+        Some(unsafe { Self::new_unchecked() })
+    }
+}
@@ -0,0 +1,320 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Leveled logging that is safe to use from hot paths and at elevated `IRQL`.
+//!
+//! [`println!`](crate::println) writes every message to the debugger, which
+//! floods it when called from DPCs, timers or I/O paths, and slows down the
+//! system while it does. A [`Logger`] filters messages by
+//! [`Level`], limits the rate of messages written to the debugger, and routes
+//! messages logged at `IRQL` >= `DISPATCH_LEVEL` to an
+//! [`EtwProvider`], as WPP and the In-flight Recorder do, when one is
+//! attached. Declare the logger as a `static` and log with
+//! [`log!`](crate::log):
+//!
+//! ```ignore
+//! use wdk::{etw::Level, log::Logger};
+//!
+//! static LOGGER: Logger = Logger::new(Level::Information)
+//!     .with_provider(&PROVIDER, FLAG_LOG)
+//!     .with_rate_limit(50);
+//!
+//! wdk::log!(LOGGER, Level::Warning, "read failed: {:#010x}", nt_status);
+//! ```
+
+use core::{
+    fmt,
+    sync::atomic::{AtomicU8, AtomicU32, AtomicU64, Ordering},
+};
+
+use wdk_sys::{DISPATCH_LEVEL, ULONG64, ntddk::KeGetCurrentIrql};
+
+use crate::{
+    etw::{EtwProvider, Level},
+    time::interrupt_time,
+};
+
+/// Length of the window that the rate limit of a [`Logger`] applies to, in
+/// 100-nanosecond intervals of interrupt time
+const RATE_LIMIT_WINDOW: ULONG64 = 10_000_000;
+
+/// Logger of leveled messages, see the [module documentation](self).
+///
+/// Messages above the maximum level of the logger are discarded before their
+/// arguments are formatted. Messages logged at `IRQL` >= `DISPATCH_LEVEL` are
+/// written as [`trace!`](crate::trace) events to the provider of the logger,
+/// if it has one. Other messages are printed to the debugger, at most as many
+/// per second as the rate limit of the logger. Messages over the limit are
+/// dropped, and counted in [`Logger::suppressed()`] and in a summary printed
+/// once messages are admitted again.
+pub struct Logger {
+    max_level: AtomicU8,
+    provider: Option<&'static EtwProvider>,
+    flags: u64,
+    rate_limiter: RateLimiter,
+}
+
+impl Logger {
+    /// Construct a logger of messages up to `max_level`, printed to the
+    /// debugger without a rate limit
+    #[must_use]
+    pub const fn new(max_level: Level) -> Self {
+        Self {
+            max_level: AtomicU8::new(max_level as u8),
+            provider: None,
+            flags: 0,
+            rate_limiter: RateLimiter::new(0),
+        }
+    }
+
+    /// Write messages logged at `IRQL` >= `DISPATCH_LEVEL` to `provider` with
+    /// the flag bits `flags`, instead of printing them to the debugger. The
+    /// messages are dropped while the provider is not registered or no
+    /// consumer enabled them.
+    #[must_use]
+    pub const fn with_provider(mut self, provider: &'static EtwProvider, flags: u64) -> Self {
+        self.provider = Some(provider);
+        self.flags = flags;
+        self
+    }
+
+    /// Print at most `messages_per_second` messages per second to the
+    /// debugger, or any number of messages if it is 0. The limit is
+    /// approximate, as concurrent messages can race at the start of a second.
+    #[must_use]
+    pub const fn with_rate_limit(mut self, messages_per_second: u32) -> Self {
+        self.rate_limiter = RateLimiter::new(messages_per_second);
+        self
+    }
+
+    /// Get the maximum level of messages that are logged
+    #[must_use]
+    pub fn max_level(&self) -> Level {
+        match self.max_level.load(Ordering::Relaxed) {
+            1 => Level::Critical,
+            2 => Level::Error,
+            3 => Level::Warning,
+            4 => Level::Information,
+            _ => Level::Verbose,
+        }
+    }
+
+    /// Set the maximum level of messages that are logged, ex. from a value
+    /// read from the registry when the driver loads. Can be called at any
+    /// `IRQL`.
+    pub fn set_max_level(&self, max_level: Level) {
+        self.max_level.store(max_level as u8, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if messages of `level` are logged. Can be called at any
+    /// `IRQL`.
+    #[must_use]
+    pub fn is_enabled(&self, level: Level) -> bool {
+        level as u8 <= self.max_level.load(Ordering::Relaxed)
+    }
+
+    /// Get the number of messages that were dropped because they exceeded the
+    /// rate limit
+    #[must_use]
+    pub fn suppressed(&self) -> u64 {
+        self.rate_limiter.suppressed.load(Ordering::Relaxed)
+    }
+
+    /// Internal implementation of [`log!`](crate::log). This function is an
+    /// implementation detail and should never be called directly, but must be
+    /// public to be useable by the macro.
+    #[doc(hidden)]
+    pub fn _log(&self, level: Level, args: fmt::Arguments, file: &str, line: u32) {
+        if !self.is_enabled(level) {
+            return;
+        }
+
+        // SAFETY: `KeGetCurrentIrql` can be called at any IRQL, and has no other
+        // requirements
+        let irql = unsafe { KeGetCurrentIrql() };
+        if let Some(provider) = self.provider {
+            if u32::from(irql) >= DISPATCH_LEVEL {
+                if provider.is_enabled(level, self.flags) {
+                    crate::etw::_trace(provider, level, self.flags, args, file, line);
+                }
+                return;
+            }
+        }
+
+        match self.rate_limiter.admit(interrupt_time()) {
+            Admission::Allow { suppressed: 0 } => {}
+            Admission::Allow { suppressed } => {
+                crate::_print(format_args!(
+                    "{suppressed} log messages were suppressed by the rate limit\n"
+                ));
+            }
+            Admission::Suppress => return,
+        }
+        crate::_print(format_args!(
+            "[{}] {file}:{line}: {args}\n",
+            level_name(level)
+        ));
+    }
+}
+
+/// Logs a message to a [`Logger`](crate::log::Logger).
+///
+/// The arguments are the logger, the [`Level`](crate::etw::Level) of the
+/// message, followed by a format string and its arguments as for
+/// [`format_args!`](core::format_args). The arguments are only formatted if
+/// the logger logs messages of the level. Must be called at `IRQL` <=
+/// `DIRQL`, or at any `IRQL` if the logger has a provider, as long as the
+/// arguments can be formatted at the current `IRQL`.
+///
+/// # Examples
+///
+/// ```ignore
+/// wdk::log!(LOGGER, Level::Verbose, "timer fired with {} pending requests", pending);
+/// ```
+#[macro_export]
+macro_rules! log {
+    ($logger:expr, $level:expr, $($arg:tt)+) => {{
+        let logger: &$crate::log::Logger = &$logger;
+        let level: $crate::etw::Level = $level;
+        if logger.is_enabled(level) {
+            logger._log(
+                level,
+                ::core::format_args!($($arg)+),
+                ::core::file!(),
+                ::core::line!(),
+            );
+        }
+    }};
+}
+
+/// Outcome of [`RateLimiter::admit()`]
+#[derive(Debug, PartialEq, Eq)]
+enum Admission {
+    /// The message is admitted, after `suppressed` messages were dropped in
+    /// the previous window
+    Allow { suppressed: u32 },
+    /// The message exceeds the rate limit
+    Suppress,
+}
+
+/// Lock-free limiter of the number of messages per [`RATE_LIMIT_WINDOW`]
+struct RateLimiter {
+    /// Maximum number of messages per window, or 0 for no limit
+    limit: u32,
+    /// Interrupt time at the start of the current window
+    window_start: AtomicU64,
+    /// Number of messages in the current window
+    count: AtomicU32,
+    /// Number of messages dropped since the last admitted message
+    pending_suppressed: AtomicU32,
+    /// Total number of messages dropped
+    suppressed: AtomicU64,
+}
+
+impl RateLimiter {
+    const fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            window_start: AtomicU64::new(0),
+            count: AtomicU32::new(0),
+            pending_suppressed: AtomicU32::new(0),
+            suppressed: AtomicU64::new(0),
+        }
+    }
+
+    /// Decide whether a message at interrupt time `now` is admitted
+    fn admit(&self, now: ULONG64) -> Admission {
+        if self.limit == 0 {
+            return Admission::Allow { suppressed: 0 };
+        }
+
+        let window_start = self.window_start.load(Ordering::Relaxed);
+        // Only the processor that moves the window resets the count, so that
+        // messages are not admitted twice in a window
+        if now.wrapping_sub(window_start) >= RATE_LIMIT_WINDOW
+            && self
+                .window_start
+                .compare_exchange(window_start, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.count.store(1, Ordering::Relaxed);
+            return Admission::Allow {
+                suppressed: self.pending_suppressed.swap(0, Ordering::Relaxed),
+            };
+        }
+
+        if self.count.fetch_add(1, Ordering::Relaxed) < self.limit {
+            Admission::Allow { suppressed: 0 }
+        } else {
+            self.pending_suppressed.fetch_add(1, Ordering::Relaxed);
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+            Admission::Suppress
+        }
+    }
+}
+
+/// Get the name of `level` that prefixes messages printed to the debugger
+const fn level_name(level: Level) -> &'static str {
+    match level {
+        Level::Critical => "CRITICAL",
+        Level::Error => "ERROR",
+        Level::Warning => "WARNING",
+        Level::Information => "INFO",
+        Level::Verbose => "VERBOSE",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_above_max_level_are_disabled() {
+        let logger = Logger::new(Level::Warning);
+        assert!(logger.is_enabled(Level::Error));
+        assert!(logger.is_enabled(Level::Warning));
+        assert!(!logger.is_enabled(Level::Information));
+
+        logger.set_max_level(Level::Verbose);
+        assert_eq!(logger.max_level(), Level::Verbose);
+        assert!(logger.is_enabled(Level::Verbose));
+    }
+
+    #[test]
+    fn rate_limit_suppresses_messages_until_next_window() {
+        let rate_limiter = RateLimiter::new(2);
+        let start = RATE_LIMIT_WINDOW;
+
+        assert_eq!(
+            rate_limiter.admit(start),
+            Admission::Allow { suppressed: 0 }
+        );
+        assert_eq!(
+            rate_limiter.admit(start + 1),
+            Admission::Allow { suppressed: 0 }
+        );
+        assert_eq!(rate_limiter.admit(start + 2), Admission::Suppress);
+        assert_eq!(rate_limiter.admit(start + 3), Admission::Suppress);
+        assert_eq!(rate_limiter.suppressed.load(Ordering::Relaxed), 2);
+
+        // The first message of the next window reports the suppressed ones
+        let next_window = start + RATE_LIMIT_WINDOW;
+        assert_eq!(
+            rate_limiter.admit(next_window),
+            Admission::Allow { suppressed: 2 }
+        );
+        assert_eq!(
+            rate_limiter.admit(next_window + 1),
+            Admission::Allow { suppressed: 0 }
+        );
+        assert_eq!(rate_limiter.admit(next_window + 2), Admission::Suppress);
+    }
+
+    #[test]
+    fn no_rate_limit_admits_all_messages() {
+        let rate_limiter = RateLimiter::new(0);
+        for _ in 0..100 {
+            assert_eq!(rate_limiter.admit(0), Admission::Allow { suppressed: 0 });
+        }
+    }
+}
@@ -0,0 +1,224 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Access to the memory-mapped registers of a device.
+//!
+//! The physical address ranges of the registers of a device, ex. the BARs of a
+//! PCI device, are assigned by the PnP manager, and reported to KMDF drivers as
+//! the memory resources passed to their `EvtDevicePrepareHardware` callback.
+//! [`MappedMemory`] maps such a range into system space, and accesses its
+//! registers with volatile reads and writes.
+
+use core::ptr::NonNull;
+
+use wdk_sys::{
+    NTSTATUS,
+    PAGE_NOCACHE,
+    PAGE_READWRITE,
+    PHYSICAL_ADDRESS,
+    STATUS_INSUFFICIENT_RESOURCES,
+    STATUS_INVALID_PARAMETER,
+    ntddk::{MmMapIoSpaceEx, MmUnmapIoSpace},
+};
+
+/// Range of memory-mapped registers, mapped into system space.
+///
+/// Registers are accessed by their offset from the start of the range, with
+/// volatile reads and writes of their width. Every access is checked to be
+/// within the range and aligned to its width. The range is unmapped when the
+/// [`MappedMemory`] is dropped, ex. when it is dropped from the context of the
+/// device in `EvtDeviceReleaseHardware`.
+pub struct MappedMemory {
+    base: NonNull<u8>,
+    length: usize,
+}
+
+// SAFETY: The mapping is not tied to the thread that created it, and can be
+// unmapped from any thread
+unsafe impl Send for MappedMemory {}
+// SAFETY: Registers are only accessed with volatile reads and writes, whose
+// ordering with respect to the device is the responsibility of the driver, as
+// with any other hardware access
+unsafe impl Sync for MappedMemory {}
+
+impl MappedMemory {
+    /// Map the `length` bytes of physical address space that start at
+    /// `physical_address` into system space, uncached. Must be called at
+    /// `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the range could not be mapped:
+    /// * `STATUS_INVALID_PARAMETER` if `length` is 0
+    /// * `STATUS_INSUFFICIENT_RESOURCES` if `MmMapIoSpaceEx` fails
+    ///
+    /// # Safety
+    ///
+    /// The range must be a range of device registers assigned to the device of
+    /// the driver, ex. the start and the length of a memory resource of the
+    /// translated resources of the device. Mapping other ranges, ex. of RAM,
+    /// aliases memory that Rust code assumes it has exclusive access to.
+    pub unsafe fn map(physical_address: u64, length: usize) -> Result<Self, NTSTATUS> {
+        if length == 0 {
+            return Err(STATUS_INVALID_PARAMETER);
+        }
+
+        let physical_address = PHYSICAL_ADDRESS {
+            QuadPart: i64::from_ne_bytes(physical_address.to_ne_bytes()),
+        };
+
+        // SAFETY: The caller guarantees that the range is a range of registers of the
+        // device
+        let base =
+            unsafe { MmMapIoSpaceEx(physical_address, length, PAGE_READWRITE | PAGE_NOCACHE) };
+        NonNull::new(base.cast())
+            .map(|base| Self { base, length })
+            .ok_or(STATUS_INSUFFICIENT_RESOURCES)
+    }
+
+    /// Get the length of the mapped range, in bytes
+    #[must_use]
+    pub const fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Read the 8-bit register at `offset`
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the register is not within the mapped range.
+    #[must_use]
+    pub fn read8(&self, offset: usize) -> u8 {
+        self.read(offset)
+    }
+
+    /// Read the 16-bit register at `offset`
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the register is not within the mapped range, or if
+    /// `offset` is not a multiple of 2.
+    #[must_use]
+    pub fn read16(&self, offset: usize) -> u16 {
+        self.read(offset)
+    }
+
+    /// Read the 32-bit register at `offset`
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the register is not within the mapped range, or if
+    /// `offset` is not a multiple of 4.
+    #[must_use]
+    pub fn read32(&self, offset: usize) -> u32 {
+        self.read(offset)
+    }
+
+    /// Read the 64-bit register at `offset`
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the register is not within the mapped range, or if
+    /// `offset` is not a multiple of 8.
+    #[must_use]
+    pub fn read64(&self, offset: usize) -> u64 {
+        self.read(offset)
+    }
+
+    /// Write `value` to the 8-bit register at `offset`
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the register is not within the mapped range.
+    pub fn write8(&self, offset: usize, value: u8) {
+        self.write(offset, value);
+    }
+
+    /// Write `value` to the 16-bit register at `offset`
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the register is not within the mapped range, or if
+    /// `offset` is not a multiple of 2.
+    pub fn write16(&self, offset: usize, value: u16) {
+        self.write(offset, value);
+    }
+
+    /// Write `value` to the 32-bit register at `offset`
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the register is not within the mapped range, or if
+    /// `offset` is not a multiple of 4.
+    pub fn write32(&self, offset: usize, value: u32) {
+        self.write(offset, value);
+    }
+
+    /// Write `value` to the 64-bit register at `offset`
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the register is not within the mapped range, or if
+    /// `offset` is not a multiple of 8.
+    pub fn write64(&self, offset: usize, value: u64) {
+        self.write(offset, value);
+    }
+
+    fn read<T>(&self, offset: usize) -> T {
+        let register = self.register::<T>(offset);
+        // SAFETY: `register` is an aligned register within the mapped range
+        unsafe { register.read_volatile() }
+    }
+
+    fn write<T>(&self, offset: usize, value: T) {
+        let register = self.register::<T>(offset);
+        // SAFETY: `register` is an aligned register within the mapped range
+        unsafe { register.write_volatile(value) }
+    }
+
+    /// Get a pointer to the register of type `T` at `offset`
+    fn register<T>(&self, offset: usize) -> *mut T {
+        let size = core::mem::size_of::<T>();
+        assert!(
+            register_in_range(self.length, offset, size),
+            "register access out of the mapped range"
+        );
+        assert!(
+            offset % size == 0,
+            "register access must be aligned to the width of the register"
+        );
+
+        // SAFETY: `offset` is within the mapped range
+        unsafe { self.base.as_ptr().add(offset) }.cast()
+    }
+}
+
+impl Drop for MappedMemory {
+    fn drop(&mut self) {
+        // SAFETY: The range was mapped by `MappedMemory::map` with this length, and is
+        // not accessed after this
+        unsafe {
+            MmUnmapIoSpace(self.base.as_ptr().cast(), self.length);
+        }
+    }
+}
+
+/// Returns `true` if a register of `size` bytes at `offset` is within a range
+/// of `length` bytes
+fn register_in_range(length: usize, offset: usize, size: usize) -> bool {
+    offset.checked_add(size).is_some_and(|end| end <= length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn register_in_range_checks_bounds() {
+        assert!(register_in_range(0x100, 0, 4));
+        assert!(register_in_range(0x100, 0xFC, 4));
+        assert!(!register_in_range(0x100, 0xFD, 4));
+        assert!(!register_in_range(0x100, 0x100, 1));
+        assert!(!register_in_range(0x100, usize::MAX, 8));
+    }
+}
@@ -0,0 +1,315 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Typed wrapper over [`NTSTATUS`] values and an error type for failed WDK API
+//! calls.
+
+use core::fmt;
+
+use wdk_sys::{NT_ERROR, NT_INFORMATION, NT_SUCCESS, NT_WARNING, NTSTATUS};
+
+/// A typed [`NTSTATUS`] value.
+///
+/// Common status codes are available as associated constants, ex.
+/// [`NtStatus::SUCCESS`] and [`NtStatus::CANCELLED`]. Any other status code,
+/// including all the `STATUS_*` constants of [`wdk_sys`], can be converted
+/// from and into an [`NTSTATUS`] with [`From`].
+///
+/// See [Using NTSTATUS values](https://learn.microsoft.com/en-us/windows-hardware/drivers/kernel/using-ntstatus-values) for details.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct NtStatus(NTSTATUS);
+
+macro_rules! nt_status_codes {
+    ($($name:ident => $status:ident),* $(,)?) => {
+        impl NtStatus {
+            $(
+                #[doc = concat!("[`", stringify!($status), "`](wdk_sys::", stringify!($status), ")")]
+                pub const $name: Self = Self(wdk_sys::$status);
+            )*
+
+            /// Returns the name of the `STATUS_*` constant of the status code,
+            /// if it is one of the associated constants of [`NtStatus`]
+            #[must_use]
+            pub const fn name(self) -> Option<&'static str> {
+                match self.0 {
+                    $(wdk_sys::$status => Some(stringify!($status)),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+nt_status_codes! {
+    // Success and informational codes
+    SUCCESS => STATUS_SUCCESS,
+    ABANDONED => STATUS_ABANDONED,
+    USER_APC => STATUS_USER_APC,
+    ALERTED => STATUS_ALERTED,
+    TIMEOUT => STATUS_TIMEOUT,
+    PENDING => STATUS_PENDING,
+    REPARSE => STATUS_REPARSE,
+    MORE_ENTRIES => STATUS_MORE_ENTRIES,
+    NOT_ALL_ASSIGNED => STATUS_NOT_ALL_ASSIGNED,
+    SOME_NOT_MAPPED => STATUS_SOME_NOT_MAPPED,
+    OPLOCK_BREAK_IN_PROGRESS => STATUS_OPLOCK_BREAK_IN_PROGRESS,
+    NOTIFY_CLEANUP => STATUS_NOTIFY_CLEANUP,
+    NOTIFY_ENUM_DIR => STATUS_NOTIFY_ENUM_DIR,
+    BUFFER_ALL_ZEROS => STATUS_BUFFER_ALL_ZEROS,
+    OBJECT_NAME_EXISTS => STATUS_OBJECT_NAME_EXISTS,
+    // Warning codes
+    GUARD_PAGE_VIOLATION => STATUS_GUARD_PAGE_VIOLATION,
+    DATATYPE_MISALIGNMENT => STATUS_DATATYPE_MISALIGNMENT,
+    BREAKPOINT => STATUS_BREAKPOINT,
+    BUFFER_OVERFLOW => STATUS_BUFFER_OVERFLOW,
+    NO_MORE_FILES => STATUS_NO_MORE_FILES,
+    DEVICE_BUSY => STATUS_DEVICE_BUSY,
+    VERIFY_REQUIRED => STATUS_VERIFY_REQUIRED,
+    MEDIA_CHANGED => STATUS_MEDIA_CHANGED,
+    NO_MORE_ENTRIES => STATUS_NO_MORE_ENTRIES,
+    NO_DATA_DETECTED => STATUS_NO_DATA_DETECTED,
+    // Error codes
+    UNSUCCESSFUL => STATUS_UNSUCCESSFUL,
+    NOT_IMPLEMENTED => STATUS_NOT_IMPLEMENTED,
+    INVALID_INFO_CLASS => STATUS_INVALID_INFO_CLASS,
+    INFO_LENGTH_MISMATCH => STATUS_INFO_LENGTH_MISMATCH,
+    ACCESS_VIOLATION => STATUS_ACCESS_VIOLATION,
+    INVALID_HANDLE => STATUS_INVALID_HANDLE,
+    INVALID_PARAMETER => STATUS_INVALID_PARAMETER,
+    NO_SUCH_DEVICE => STATUS_NO_SUCH_DEVICE,
+    NO_SUCH_FILE => STATUS_NO_SUCH_FILE,
+    INVALID_DEVICE_REQUEST => STATUS_INVALID_DEVICE_REQUEST,
+    END_OF_FILE => STATUS_END_OF_FILE,
+    WRONG_VOLUME => STATUS_WRONG_VOLUME,
+    NO_MEDIA_IN_DEVICE => STATUS_NO_MEDIA_IN_DEVICE,
+    UNRECOGNIZED_MEDIA => STATUS_UNRECOGNIZED_MEDIA,
+    NONEXISTENT_SECTOR => STATUS_NONEXISTENT_SECTOR,
+    MORE_PROCESSING_REQUIRED => STATUS_MORE_PROCESSING_REQUIRED,
+    NO_MEMORY => STATUS_NO_MEMORY,
+    ILLEGAL_INSTRUCTION => STATUS_ILLEGAL_INSTRUCTION,
+    ALREADY_COMMITTED => STATUS_ALREADY_COMMITTED,
+    ACCESS_DENIED => STATUS_ACCESS_DENIED,
+    BUFFER_TOO_SMALL => STATUS_BUFFER_TOO_SMALL,
+    OBJECT_TYPE_MISMATCH => STATUS_OBJECT_TYPE_MISMATCH,
+    OBJECT_NAME_INVALID => STATUS_OBJECT_NAME_INVALID,
+    OBJECT_NAME_NOT_FOUND => STATUS_OBJECT_NAME_NOT_FOUND,
+    OBJECT_NAME_COLLISION => STATUS_OBJECT_NAME_COLLISION,
+    PORT_DISCONNECTED => STATUS_PORT_DISCONNECTED,
+    OBJECT_PATH_INVALID => STATUS_OBJECT_PATH_INVALID,
+    OBJECT_PATH_NOT_FOUND => STATUS_OBJECT_PATH_NOT_FOUND,
+    OBJECT_PATH_SYNTAX_BAD => STATUS_OBJECT_PATH_SYNTAX_BAD,
+    DATA_ERROR => STATUS_DATA_ERROR,
+    CRC_ERROR => STATUS_CRC_ERROR,
+    SHARING_VIOLATION => STATUS_SHARING_VIOLATION,
+    DELETE_PENDING => STATUS_DELETE_PENDING,
+    INVALID_LOCK_SEQUENCE => STATUS_INVALID_LOCK_SEQUENCE,
+    REVISION_MISMATCH => STATUS_REVISION_MISMATCH,
+    PROCEDURE_NOT_FOUND => STATUS_PROCEDURE_NOT_FOUND,
+    INTEGER_OVERFLOW => STATUS_INTEGER_OVERFLOW,
+    PRIVILEGE_NOT_HELD => STATUS_PRIVILEGE_NOT_HELD,
+    MEDIA_WRITE_PROTECTED => STATUS_MEDIA_WRITE_PROTECTED,
+    DEVICE_NOT_READY => STATUS_DEVICE_NOT_READY,
+    ILLEGAL_FUNCTION => STATUS_ILLEGAL_FUNCTION,
+    INSUFFICIENT_RESOURCES => STATUS_INSUFFICIENT_RESOURCES,
+    DISK_FULL => STATUS_DISK_FULL,
+    FILE_IS_A_DIRECTORY => STATUS_FILE_IS_A_DIRECTORY,
+    NOT_SUPPORTED => STATUS_NOT_SUPPORTED,
+    BAD_DEVICE_TYPE => STATUS_BAD_DEVICE_TYPE,
+    CANT_WAIT => STATUS_CANT_WAIT,
+    INTERNAL_ERROR => STATUS_INTERNAL_ERROR,
+    UNEXPECTED_IO_ERROR => STATUS_UNEXPECTED_IO_ERROR,
+    STACK_OVERFLOW => STATUS_STACK_OVERFLOW,
+    FILE_CLOSED => STATUS_FILE_CLOSED,
+    DEVICE_NOT_CONNECTED => STATUS_DEVICE_NOT_CONNECTED,
+    DEVICE_POWER_FAILURE => STATUS_DEVICE_POWER_FAILURE,
+    DEVICE_CONFIGURATION_ERROR => STATUS_DEVICE_CONFIGURATION_ERROR,
+    DRIVER_INTERNAL_ERROR => STATUS_DRIVER_INTERNAL_ERROR,
+    IO_TIMEOUT => STATUS_IO_TIMEOUT,
+    RETRY => STATUS_RETRY,
+    DEVICE_DOES_NOT_EXIST => STATUS_DEVICE_DOES_NOT_EXIST,
+    INVALID_DEVICE_STATE => STATUS_INVALID_DEVICE_STATE,
+    IO_DEVICE_ERROR => STATUS_IO_DEVICE_ERROR,
+    DEVICE_PROTOCOL_ERROR => STATUS_DEVICE_PROTOCOL_ERROR,
+    CANCELLED => STATUS_CANCELLED,
+    INVALID_USER_BUFFER => STATUS_INVALID_USER_BUFFER,
+    INVALID_ADDRESS => STATUS_INVALID_ADDRESS,
+    REQUEST_ABORTED => STATUS_REQUEST_ABORTED,
+    NOT_FOUND => STATUS_NOT_FOUND,
+    INSUFFICIENT_POWER => STATUS_INSUFFICIENT_POWER,
+    POWER_STATE_INVALID => STATUS_POWER_STATE_INVALID,
+    DEVICE_REMOVED => STATUS_DEVICE_REMOVED,
+    DEVICE_ENUMERATION_ERROR => STATUS_DEVICE_ENUMERATION_ERROR,
+    INVALID_BUFFER_SIZE => STATUS_INVALID_BUFFER_SIZE,
+    DEVICE_HARDWARE_ERROR => STATUS_DEVICE_HARDWARE_ERROR,
+}
+
+impl NtStatus {
+    /// Returns the raw [`NTSTATUS`] value
+    #[must_use]
+    pub const fn as_raw(self) -> NTSTATUS {
+        self.0
+    }
+
+    /// Returns `true` if the status is a success type (0 − 0x3FFFFFFF) or an
+    /// informational type (0x40000000 − 0x7FFFFFFF)
+    #[must_use]
+    pub const fn is_success(self) -> bool {
+        NT_SUCCESS(self.0)
+    }
+
+    /// Returns `true` if the status is an informational type (0x40000000 −
+    /// 0x7FFFFFFF)
+    #[must_use]
+    pub const fn is_information(self) -> bool {
+        NT_INFORMATION(self.0)
+    }
+
+    /// Returns `true` if the status is a warning type (0x80000000 −
+    /// 0xBFFFFFFF)
+    #[must_use]
+    pub const fn is_warning(self) -> bool {
+        NT_WARNING(self.0)
+    }
+
+    /// Returns `true` if the status is an error type (0xC0000000 - 0xFFFFFFFF)
+    #[must_use]
+    pub const fn is_error(self) -> bool {
+        NT_ERROR(self.0)
+    }
+
+    /// Converts the status returned by the WDK API `api` into a [`Result`].
+    /// Success and informational statuses are returned as `Ok`, so that
+    /// callers can still tell apart, ex., [`NtStatus::PENDING`] from
+    /// [`NtStatus::SUCCESS`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`NtError`] carrying the status and `api` if the status is a
+    /// warning or an error type.
+    pub const fn into_result(self, api: &'static str) -> Result<Self, NtError> {
+        if self.is_success() {
+            Ok(self)
+        } else {
+            Err(NtError::new(self, api))
+        }
+    }
+}
+
+impl From<NTSTATUS> for NtStatus {
+    fn from(nt_status: NTSTATUS) -> Self {
+        Self(nt_status)
+    }
+}
+
+impl From<NtStatus> for NTSTATUS {
+    fn from(nt_status: NtStatus) -> Self {
+        nt_status.0
+    }
+}
+
+impl fmt::Debug for NtStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NtStatus({self})")
+    }
+}
+
+impl fmt::Display for NtStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{name} ({:#010X})", self.0),
+            None => write!(f, "{:#010X}", self.0),
+        }
+    }
+}
+
+/// Error returned when a WDK API call fails. It carries the [`NtStatus`] of the
+/// failure along with the name of the API that returned it, for better logs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NtError {
+    status: NtStatus,
+    api: &'static str,
+}
+
+impl NtError {
+    /// Creates a new [`NtError`] for the failure `status` returned by the WDK
+    /// API `api`
+    #[must_use]
+    pub const fn new(status: NtStatus, api: &'static str) -> Self {
+        Self { status, api }
+    }
+
+    /// Returns the status of the failure
+    #[must_use]
+    pub const fn status(&self) -> NtStatus {
+        self.status
+    }
+
+    /// Returns the name of the API that failed
+    #[must_use]
+    pub const fn api(&self) -> &'static str {
+        self.api
+    }
+}
+
+impl From<NtError> for NTSTATUS {
+    fn from(error: NtError) -> Self {
+        error.status.0
+    }
+}
+
+impl fmt::Display for NtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed with {}", self.api, self.status)
+    }
+}
+
+impl core::error::Error for NtError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_types() {
+        assert!(NtStatus::SUCCESS.is_success());
+        assert!(NtStatus::PENDING.is_success());
+        assert!(!NtStatus::PENDING.is_information());
+        assert!(NtStatus::OBJECT_NAME_EXISTS.is_information());
+        assert!(NtStatus::BUFFER_OVERFLOW.is_warning());
+        assert!(!NtStatus::BUFFER_OVERFLOW.is_success());
+        assert!(NtStatus::CANCELLED.is_error());
+        assert!(!NtStatus::CANCELLED.is_warning());
+    }
+
+    #[test]
+    fn conversions_round_trip() {
+        let nt_status: NTSTATUS = NtStatus::CANCELLED.into();
+        assert_eq!(nt_status, wdk_sys::STATUS_CANCELLED);
+        assert_eq!(NtStatus::from(nt_status), NtStatus::CANCELLED);
+        assert_eq!(NtStatus::from(nt_status).name(), Some("STATUS_CANCELLED"));
+    }
+
+    #[test]
+    fn display_names_known_codes() {
+        assert_eq!(
+            NtStatus::DEVICE_NOT_READY.to_string(),
+            "STATUS_DEVICE_NOT_READY (0xC00000A3)"
+        );
+        assert_eq!(NtStatus::from(0x2000_0001).to_string(), "0x20000001");
+        assert_eq!(NtStatus::from(0x2000_0001).name(), None);
+    }
+
+    #[test]
+    fn into_result_carries_api_name() {
+        assert_eq!(
+            NtStatus::PENDING.into_result("WdfRequestSend"),
+            Ok(NtStatus::PENDING)
+        );
+        let error = NtStatus::INSUFFICIENT_RESOURCES
+            .into_result("WdfTimerCreate")
+            .expect_err("error statuses should convert to an error");
+        assert_eq!(error.status(), NtStatus::INSUFFICIENT_RESOURCES);
+        assert_eq!(error.api(), "WdfTimerCreate");
+    }
+}
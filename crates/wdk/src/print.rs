@@ -52,7 +52,9 @@ macro_rules! print {
 the debugger's use of IPIs (Inter-Process Interrupts).
 
 [`wdk_sys::ntddk::DbgPrint`]'s 512 byte limit does not apply to this macro, as it will
-automatically buffer and chunk the output if it exceeds that limit.
+automatically buffer and chunk the output if it exceeds that limit. Every message is
+written to the debugger, so use [`log!`](crate::log) with level filtering and a rate limit
+in DPCs, timers and other hot paths.
 "
 )]
 #[cfg_attr(
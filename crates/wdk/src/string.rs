@@ -0,0 +1,440 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Safe wrappers over [`UNICODE_STRING`], the counted UTF-16 strings used by
+//! WDK APIs for device names, symbolic links and registry paths. String
+//! literals can be converted at compile time with [`unicode_str!`].
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::fmt;
+
+use wdk_sys::{NTSTATUS, STATUS_NAME_TOO_LONG, UNICODE_STRING, USHORT};
+
+#[cfg(feature = "alloc")]
+use crate::alloc_failure::alloc_failed;
+
+/// Maximum number of UTF-16 code units in a [`UNICODE_STRING`], whose length is
+/// stored in bytes in a [`USHORT`]
+const MAX_LEN: usize = USHORT::MAX as usize / core::mem::size_of::<u16>();
+
+/// Borrowed UTF-16 string that can be passed to WDK APIs as a
+/// [`UNICODE_STRING`].
+///
+/// A [`UnicodeStr`] is not NUL terminated, and is displayed lossily, with
+/// unpaired surrogates replaced by [`char::REPLACEMENT_CHARACTER`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct UnicodeStr<'a> {
+    buffer: &'a [u16],
+}
+impl<'a> UnicodeStr<'a> {
+    /// Construct a [`UnicodeStr`] over the UTF-16 code units of `buffer`
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_NAME_TOO_LONG` if `buffer` does not
+    /// fit in a [`UNICODE_STRING`].
+    pub const fn from_slice(buffer: &'a [u16]) -> Result<Self, NTSTATUS> {
+        if buffer.len() > MAX_LEN {
+            return Err(STATUS_NAME_TOO_LONG);
+        }
+        Ok(Self { buffer })
+    }
+
+    /// Construct a [`UnicodeStr`] over the buffer of `unicode_string`, ex. the
+    /// registry path passed to `DriverEntry`
+    ///
+    /// # Safety
+    ///
+    /// `unicode_string` must point to a valid [`UNICODE_STRING`] whose buffer
+    /// holds `Length` bytes of UTF-16 code units, and the buffer must not be
+    /// mutated or freed for `'a`.
+    #[must_use]
+    pub const unsafe fn from_raw(unicode_string: *const UNICODE_STRING) -> Self {
+        // SAFETY: The caller guarantees that `unicode_string` points to a valid
+        // `UNICODE_STRING`
+        let unicode_string = unsafe { &*unicode_string };
+        let len = unicode_string.Length as usize / core::mem::size_of::<u16>();
+        if len == 0 {
+            return Self { buffer: &[] };
+        }
+        // SAFETY: The caller guarantees that `Buffer` holds `Length` bytes of UTF-16
+        // code units that stay valid for `'a`
+        let buffer = unsafe { core::slice::from_raw_parts(unicode_string.Buffer, len) };
+        Self { buffer }
+    }
+
+    /// Get the UTF-16 code units of the string
+    #[must_use]
+    pub const fn as_slice(&self) -> &'a [u16] {
+        self.buffer
+    }
+
+    /// Get the number of UTF-16 code units of the string
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns `true` if the string has no UTF-16 code units
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Get a [`UNICODE_STRING`] that points to the buffer of the string, to
+    /// pass to WDK APIs that take a `PCUNICODE_STRING`. The buffer must not be
+    /// written through the returned [`UNICODE_STRING`].
+    #[must_use]
+    pub const fn as_raw(&self) -> UNICODE_STRING {
+        // The length was checked against `MAX_LEN` on construction, so it always fits
+        #[allow(clippy::cast_possible_truncation)]
+        let length = core::mem::size_of_val(self.buffer) as USHORT;
+        UNICODE_STRING {
+            Length: length,
+            MaximumLength: length,
+            Buffer: self.buffer.as_ptr().cast_mut(),
+        }
+    }
+
+    /// Iterate over the characters of the string. Unpaired surrogates are
+    /// returned as errors.
+    pub fn chars(&self) -> impl Iterator<Item = Result<char, core::char::DecodeUtf16Error>> + 'a {
+        char::decode_utf16(self.buffer.iter().copied())
+    }
+
+    /// Returns `true` if the string equals `other`, ignoring the case of ASCII
+    /// letters, ex. to compare device or file names, which Windows treats
+    /// case-insensitively
+    #[must_use]
+    pub fn eq_ignore_ascii_case(&self, other: &str) -> bool {
+        let to_ascii_lowercase = |c: u16| {
+            if (u16::from(b'A')..=u16::from(b'Z')).contains(&c) {
+                c + u16::from(b'a' - b'A')
+            } else {
+                c
+            }
+        };
+        self.buffer
+            .iter()
+            .copied()
+            .map(to_ascii_lowercase)
+            .eq(other.encode_utf16().map(to_ascii_lowercase))
+    }
+}
+
+impl PartialEq<str> for UnicodeStr<'_> {
+    fn eq(&self, other: &str) -> bool {
+        self.buffer.iter().copied().eq(other.encode_utf16())
+    }
+}
+
+impl PartialEq<&str> for UnicodeStr<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        *self == **other
+    }
+}
+
+impl fmt::Display for UnicodeStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.chars() {
+            fmt::Write::write_char(f, c.unwrap_or(char::REPLACEMENT_CHARACTER))?;
+        }
+        Ok(())
+    }
+}
+
+/// Owned UTF-16 string that can be passed to WDK APIs as a [`UNICODE_STRING`].
+///
+/// A [`UnicodeString`] is built from a `&str` with [`TryFrom`], and borrowed
+/// as a [`UnicodeStr`] with [`UnicodeString::as_unicode_str()`].
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct UnicodeString {
+    buffer: Vec<u16>,
+}
+#[cfg(feature = "alloc")]
+impl UnicodeString {
+    /// Borrow the string as a [`UnicodeStr`]
+    #[must_use]
+    pub fn as_unicode_str(&self) -> UnicodeStr<'_> {
+        UnicodeStr {
+            buffer: &self.buffer,
+        }
+    }
+
+    /// Get a [`UNICODE_STRING`] that points to the buffer of the string, to
+    /// pass to WDK APIs that take a `PCUNICODE_STRING`. The buffer must not be
+    /// written through the returned [`UNICODE_STRING`], which must not outlive
+    /// `self`.
+    #[must_use]
+    pub fn as_raw(&self) -> UNICODE_STRING {
+        self.as_unicode_str().as_raw()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl TryFrom<&str> for UnicodeString {
+    type Error = NTSTATUS;
+
+    /// Encode `string` in UTF-16. Fails with `STATUS_NAME_TOO_LONG` if it does
+    /// not fit in a [`UNICODE_STRING`], or with
+    /// `STATUS_INSUFFICIENT_RESOURCES` if its buffer could not be allocated,
+    /// see [`crate::alloc_failure`].
+    fn try_from(string: &str) -> Result<Self, Self::Error> {
+        let len = string.encode_utf16().count();
+        if len > MAX_LEN {
+            return Err(STATUS_NAME_TOO_LONG);
+        }
+        let mut buffer = Vec::new();
+        buffer
+            .try_reserve_exact(len)
+            .map_err(|_| alloc_failed(len * core::mem::size_of::<u16>()))?;
+        buffer.extend(string.encode_utf16());
+        Ok(Self { buffer })
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl UnicodeString {
+    /// Copy `unicode_str` into a new [`UnicodeString`], like [`From`], but
+    /// without panicking when its buffer cannot be allocated
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_INSUFFICIENT_RESOURCES` if the buffer
+    /// could not be allocated, after applying the
+    /// [`crate::alloc_failure::AllocFailurePolicy`] of the driver.
+    pub fn try_from_unicode_str(unicode_str: UnicodeStr<'_>) -> Result<Self, NTSTATUS> {
+        let mut buffer = Vec::new();
+        buffer
+            .try_reserve_exact(unicode_str.len())
+            .map_err(|_| alloc_failed(core::mem::size_of_val(unicode_str.buffer)))?;
+        buffer.extend_from_slice(unicode_str.buffer);
+        Ok(Self { buffer })
+    }
+}
+
+/// Panics if the buffer cannot be allocated, see
+/// [`UnicodeString::try_from_unicode_str()`] for a fallible alternative
+#[cfg(feature = "alloc")]
+impl From<UnicodeStr<'_>> for UnicodeString {
+    fn from(unicode_str: UnicodeStr<'_>) -> Self {
+        Self {
+            buffer: unicode_str.buffer.to_vec(),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Display for UnicodeString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.as_unicode_str(), f)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl PartialEq<str> for UnicodeString {
+    fn eq(&self, other: &str) -> bool {
+        self.as_unicode_str() == *other
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl PartialEq<&str> for UnicodeString {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_unicode_str() == **other
+    }
+}
+
+/// Construct a [`UnicodeStr`] from a string literal, encoded in UTF-16 at
+/// compile time, so that it can be passed to WDK APIs without allocating.
+///
+/// Fails to compile if the literal does not fit in a [`UNICODE_STRING`].
+///
+/// # Examples
+///
+/// ```ignore
+/// device.create_symbolic_link(wdk::unicode_str!(r"\DosDevices\Sample"), irql)?;
+/// ```
+#[macro_export]
+macro_rules! unicode_str {
+    ($string:literal) => {{
+        const UTF16: &[u16] =
+            &$crate::string::encode_utf16::<{ $crate::string::utf16_len($string) }>($string);
+        const UNICODE_STR: $crate::string::UnicodeStr<'static> =
+            match $crate::string::UnicodeStr::from_slice(UTF16) {
+                Ok(unicode_str) => unicode_str,
+                Err(_) => panic!("string literal does not fit in a UNICODE_STRING"),
+            };
+        UNICODE_STR
+    }};
+}
+
+/// Get the number of UTF-16 code units of `string`, for [`unicode_str!`]
+#[doc(hidden)]
+#[must_use]
+pub const fn utf16_len(string: &str) -> usize {
+    let bytes = string.as_bytes();
+    let mut len = 0;
+    let mut index = 0;
+    while index < bytes.len() {
+        let (_, width) = decode_utf8(bytes, index);
+        // Characters encoded in 4 bytes are outside of the basic multilingual plane,
+        // so they are encoded as surrogate pairs
+        len += if width == 4 { 2 } else { 1 };
+        index += width;
+    }
+    len
+}
+
+/// Encode `string` in `N` UTF-16 code units, for [`unicode_str!`]. `N` must be
+/// [`utf16_len()`] of `string`.
+#[doc(hidden)]
+#[must_use]
+#[allow(clippy::cast_possible_truncation)]
+pub const fn encode_utf16<const N: usize>(string: &str) -> [u16; N] {
+    let bytes = string.as_bytes();
+    let mut buffer = [0; N];
+    let mut len = 0;
+    let mut index = 0;
+    while index < bytes.len() {
+        let (c, width) = decode_utf8(bytes, index);
+        // Code points below 0x10000 are a single code unit, and the others are split
+        // in two halves of 10 bits
+        if c < 0x1_0000 {
+            buffer[len] = c as u16;
+            len += 1;
+        } else {
+            let c = c - 0x1_0000;
+            buffer[len] = 0xD800 | (c >> 10) as u16;
+            buffer[len + 1] = 0xDC00 | (c & 0x3FF) as u16;
+            len += 2;
+        }
+        index += width;
+    }
+    assert!(len == N, "the buffer length does not match the string");
+    buffer
+}
+
+/// Decode the character starting at `bytes[index]` of a valid UTF-8 string.
+/// Returns its code point and the number of bytes it is encoded in.
+#[allow(clippy::cast_lossless)]
+const fn decode_utf8(bytes: &[u8], index: usize) -> (u32, usize) {
+    const fn continuation(byte: u8) -> u32 {
+        (byte & 0x3F) as u32
+    }
+
+    let first = bytes[index];
+    if first < 0x80 {
+        (first as u32, 1)
+    } else if first < 0xE0 {
+        (
+            ((first & 0x1F) as u32) << 6 | continuation(bytes[index + 1]),
+            2,
+        )
+    } else if first < 0xF0 {
+        (
+            ((first & 0x0F) as u32) << 12
+                | continuation(bytes[index + 1]) << 6
+                | continuation(bytes[index + 2]),
+            3,
+        )
+    } else {
+        (
+            ((first & 0x07) as u32) << 18
+                | continuation(bytes[index + 1]) << 12
+                | continuation(bytes[index + 2]) << 6
+                | continuation(bytes[index + 3]),
+            4,
+        )
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use alloc::{string::ToString, vec};
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let unicode_string = UnicodeString::try_from(r"\Device\Sample").unwrap();
+        let unicode_str = unicode_string.as_unicode_str();
+        assert_eq!(unicode_str.len(), 14);
+        assert_eq!(unicode_str.to_string(), r"\Device\Sample");
+
+        let raw = unicode_string.as_raw();
+        assert_eq!(raw.Length, 28);
+        assert_eq!(raw.MaximumLength, 28);
+
+        // SAFETY: `raw` points to the buffer of `unicode_string`, which outlives the
+        // borrowed string
+        let from_raw = unsafe { UnicodeStr::from_raw(&raw const raw) };
+        assert_eq!(from_raw, unicode_str);
+    }
+
+    #[test]
+    fn empty() {
+        let unicode_string = UnicodeString::try_from("").unwrap();
+        assert!(unicode_string.as_unicode_str().is_empty());
+        assert_eq!(unicode_string.as_raw().Length, 0);
+
+        let raw = UNICODE_STRING {
+            Length: 0,
+            MaximumLength: 0,
+            Buffer: core::ptr::null_mut(),
+        };
+        // SAFETY: An empty `UNICODE_STRING` does not need a buffer
+        let from_raw = unsafe { UnicodeStr::from_raw(&raw const raw) };
+        assert!(from_raw.is_empty());
+    }
+
+    #[test]
+    fn too_long() {
+        let buffer = vec![u16::from(b'a'); MAX_LEN + 1];
+        assert_eq!(
+            UnicodeStr::from_slice(&buffer[..MAX_LEN]).map(|s| s.len()),
+            Ok(MAX_LEN)
+        );
+        assert_eq!(UnicodeStr::from_slice(&buffer), Err(STATUS_NAME_TOO_LONG));
+    }
+
+    #[test]
+    fn try_from_unicode_str_copies_the_buffer() {
+        let buffer = [u16::from(b'a'), 0xD800, u16::from(b'b')];
+        let unicode_str = UnicodeStr::from_slice(&buffer).unwrap();
+        let unicode_string = UnicodeString::try_from_unicode_str(unicode_str).unwrap();
+        assert_eq!(unicode_string.as_unicode_str(), unicode_str);
+        assert_eq!(unicode_string, UnicodeString::from(unicode_str));
+    }
+
+    #[test]
+    fn unicode_str_encodes_literals_at_compile_time() {
+        let unicode_str = crate::unicode_str!(r"\Device\Sample");
+        assert_eq!(unicode_str, r"\Device\Sample");
+        assert_eq!(unicode_str.as_raw().Length, 28);
+
+        let unicode_str = crate::unicode_str!("é€😀");
+        assert_eq!(unicode_str.as_slice(), [0xE9, 0x20AC, 0xD83D, 0xDE00]);
+        assert_eq!(unicode_str.to_string(), "é€😀");
+
+        assert!(crate::unicode_str!("").is_empty());
+    }
+
+    #[test]
+    fn comparisons_with_str() {
+        let unicode_string = UnicodeString::try_from("COM1").unwrap();
+        assert_eq!(unicode_string, "COM1");
+        assert_ne!(unicode_string, "COM10");
+        assert!(unicode_string.as_unicode_str().eq_ignore_ascii_case("com1"));
+        assert!(!unicode_string.as_unicode_str().eq_ignore_ascii_case("com2"));
+    }
+
+    #[test]
+    fn display_replaces_unpaired_surrogates() {
+        let buffer = [u16::from(b'a'), 0xD800, u16::from(b'b')];
+        let unicode_str = UnicodeStr::from_slice(&buffer).unwrap();
+        assert_eq!(unicode_str.to_string(), "a\u{FFFD}b");
+    }
+}
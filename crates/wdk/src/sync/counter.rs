@@ -0,0 +1,92 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use wdk_sys::ntddk::KeGetCurrentProcessorIndex;
+
+/// Default number of slots of a [`PerCpuCounter`]
+pub const PER_CPU_COUNTER_DEFAULT_SLOTS: usize = 64;
+
+/// Counter slot, aligned to a cache line so that processors updating
+/// neighbouring slots do not contend for the same line
+#[repr(align(64))]
+struct Slot(AtomicU64);
+
+/// Statistics counter that processors can update concurrently without taking
+/// a lock.
+///
+/// Use per-CPU counters for statistics that are updated on hot paths, ex. the
+/// number of requests served or bytes transferred, and read rarely, ex. when
+/// they are reported through an I/O control request or WMI. Each processor
+/// adds to its own slot with an interlocked operation, and
+/// [`PerCpuCounter::snapshot()`] sums the slots. Processors beyond `SLOTS`
+/// share slots, which stays correct but can contend.
+///
+/// A counter holds no kernel objects, so it can be a `static`, or be kept in
+/// the context of a device. All methods can be called at any `IRQL`.
+pub struct PerCpuCounter<const SLOTS: usize = PER_CPU_COUNTER_DEFAULT_SLOTS> {
+    slots: [Slot; SLOTS],
+}
+
+impl<const SLOTS: usize> PerCpuCounter<SLOTS> {
+    /// Construct a counter with a value of 0
+    #[must_use]
+    pub const fn new() -> Self {
+        const { assert!(SLOTS > 0, "a counter needs at least one slot") }
+        Self {
+            slots: [const { Slot(AtomicU64::new(0)) }; SLOTS],
+        }
+    }
+
+    /// Add 1 to the counter
+    pub fn increment(&self) {
+        self.add(1);
+    }
+
+    /// Add `value` to the counter. The counter wraps around on overflow.
+    pub fn add(&self, value: u64) {
+        self.slot().0.fetch_add(value, Ordering::Relaxed);
+    }
+
+    /// Get the value of the counter. Updates that happen concurrently may or
+    /// may not be included, so the value is exact only while the counter is
+    /// not updated.
+    #[must_use]
+    pub fn snapshot(&self) -> u64 {
+        self.slots.iter().fold(0, |sum, slot| {
+            sum.wrapping_add(slot.0.load(Ordering::Relaxed))
+        })
+    }
+
+    /// Get the value of the counter and reset it to 0. Each update is counted
+    /// either in the returned value or in the value after the reset.
+    pub fn take(&self) -> u64 {
+        self.slots.iter().fold(0, |sum, slot| {
+            sum.wrapping_add(slot.0.swap(0, Ordering::Relaxed))
+        })
+    }
+
+    /// Get the slot of the current processor. The thread may move to another
+    /// processor right after, which only costs some contention.
+    fn slot(&self) -> &Slot {
+        // SAFETY: `KeGetCurrentProcessorIndex` can be called at any IRQL, and has no
+        // other requirements
+        let index = unsafe { KeGetCurrentProcessorIndex() } as usize;
+        &self.slots[index % SLOTS]
+    }
+}
+
+impl<const SLOTS: usize> Default for PerCpuCounter<SLOTS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SLOTS: usize> core::fmt::Debug for PerCpuCounter<SLOTS> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("PerCpuCounter")
+            .field(&self.snapshot())
+            .finish()
+    }
+}
@@ -0,0 +1,116 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use wdk_sys::{
+    _EVENT_TYPE,
+    EVENT_TYPE,
+    KEVENT,
+    NTSTATUS,
+    ntddk::{KeClearEvent, KeInitializeEvent, KeReadStateEvent, KeSetEvent},
+};
+
+use super::{DispatcherObject, NO_INCREMENT};
+
+/// Type of a [`KernelEvent`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventType {
+    /// The event stays signaled, releasing every waiting thread, until it is
+    /// explicitly cleared
+    Notification,
+    /// The event releases a single waiting thread and is then automatically
+    /// reset to not signaled
+    Synchronization,
+}
+
+impl From<EventType> for EVENT_TYPE {
+    fn from(event_type: EventType) -> Self {
+        match event_type {
+            EventType::Notification => _EVENT_TYPE::NotificationEvent,
+            EventType::Synchronization => _EVENT_TYPE::SynchronizationEvent,
+        }
+    }
+}
+
+/// Kernel Event.
+///
+/// Use kernel events to signal from one thread to others that a condition has
+/// occurred, ex. from a work item to a thread waiting in an I/O callback.
+/// Setting and clearing the event is allowed at `IRQL` <= `DISPATCH_LEVEL`,
+/// while waiting for it is restricted by [`KernelEvent::wait()`].
+pub struct KernelEvent {
+    event: DispatcherObject<KEVENT>,
+}
+
+// SAFETY: Kernel events are designed to be signaled and waited on from any
+// thread
+unsafe impl Send for KernelEvent {}
+// SAFETY: Kernel events are designed to be signaled and waited on from any
+// thread
+unsafe impl Sync for KernelEvent {}
+
+impl KernelEvent {
+    /// Try to construct a kernel event of `event_type`, which is initially
+    /// signaled if `signaled` is `true`. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_INSUFFICIENT_RESOURCES` if the
+    /// event could not be allocated.
+    pub fn try_new(event_type: EventType, signaled: bool) -> Result<Self, NTSTATUS> {
+        let event = DispatcherObject::<KEVENT>::try_new()?;
+        // SAFETY: `event` points to a non-paged allocation large enough for a
+        // `KEVENT`, which is not used before it is initialized here
+        unsafe {
+            KeInitializeEvent(event.as_ptr(), event_type.into(), u8::from(signaled));
+        }
+        Ok(Self { event })
+    }
+
+    /// Set the event to signaled
+    pub fn set(&self) {
+        // SAFETY: `event` is a private member of `KernelEvent` that was initialized by
+        // `KeInitializeEvent`, and stays valid for as long as `self`
+        unsafe {
+            KeSetEvent(self.event.as_ptr(), NO_INCREMENT, 0);
+        }
+    }
+
+    /// Set the event to not signaled
+    pub fn clear(&self) {
+        // SAFETY: `event` is a private member of `KernelEvent` that was initialized by
+        // `KeInitializeEvent`, and stays valid for as long as `self`
+        unsafe {
+            KeClearEvent(self.event.as_ptr());
+        }
+    }
+
+    /// Returns `true` if the event is signaled
+    #[must_use]
+    pub fn is_signaled(&self) -> bool {
+        let state;
+        // SAFETY: `event` is a private member of `KernelEvent` that was initialized by
+        // `KeInitializeEvent`, and stays valid for as long as `self`
+        unsafe {
+            state = KeReadStateEvent(self.event.as_ptr());
+        }
+        state != 0
+    }
+
+    /// Wait for the event to be signaled, for at most `timeout`. The timeout is
+    /// in 100-nanosecond units, and is relative to the current time if
+    /// negative. With a `timeout` of `None` this waits indefinitely, and with a
+    /// `timeout` of `Some(0)` this returns immediately if the event is not
+    /// signaled.
+    ///
+    /// Must be called at `IRQL` <= `APC_LEVEL`, unless `timeout` is `Some(0)`,
+    /// in which case it can be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_TIMEOUT` if the event was not
+    /// signaled before `timeout` elapsed.
+    pub fn wait(&self, timeout: Option<i64>) -> Result<(), NTSTATUS> {
+        self.event.wait(timeout)
+    }
+}
@@ -0,0 +1,121 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Safe abstractions over kernel dispatcher objects, ex. events and
+//! semaphores, and lock-free synchronization primitives, ex. per-CPU counters
+
+use core::ptr::NonNull;
+
+pub use counter::*;
+pub use event::*;
+pub use semaphore::*;
+use wdk_sys::{
+    _KWAIT_REASON,
+    _MODE,
+    KPRIORITY,
+    KPROCESSOR_MODE,
+    LARGE_INTEGER,
+    NTSTATUS,
+    POOL_FLAG_NON_PAGED,
+    SIZE_T,
+    STATUS_INSUFFICIENT_RESOURCES,
+    STATUS_TIMEOUT,
+    ULONG,
+    ntddk::{ExAllocatePool2, ExFreePool, KeWaitForSingleObject},
+};
+
+use crate::nt_success;
+
+mod counter;
+mod event;
+mod semaphore;
+
+// The value of memory tags are stored in little-endian order, so it is
+// convenient to reverse the order for readability in tooling (ie. Windbg)
+const SYNC_TAG: ULONG = u32::from_ne_bytes(*b"rsyn");
+
+/// Priority boost given to threads released by signaling a dispatcher object,
+/// which matches `IO_NO_INCREMENT`
+const NO_INCREMENT: KPRIORITY = 0;
+
+/// Non-paged pool allocation of a dispatcher object.
+///
+/// Dispatcher objects must stay at the same address once they are initialized,
+/// as the kernel links waiting threads to them, so they are allocated from
+/// non-paged pool instead of being stored inline. The allocation is freed when
+/// dropped.
+struct DispatcherObject<T> {
+    object: NonNull<T>,
+}
+// SAFETY: Dispatcher objects are designed to be signaled and waited on from any
+// thread, and the allocation can be freed from any thread
+unsafe impl<T> Send for DispatcherObject<T> {}
+// SAFETY: Dispatcher objects are designed to be signaled and waited on from any
+// thread, and the kernel synchronizes all accesses to them
+unsafe impl<T> Sync for DispatcherObject<T> {}
+
+impl<T> DispatcherObject<T> {
+    /// Allocate a zeroed dispatcher object. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    fn try_new() -> Result<Self, NTSTATUS> {
+        // SAFETY: `ExAllocatePool2` is safe to call from any `IRQL` <= `DISPATCH_LEVEL`
+        // since its allocating from `POOL_FLAG_NON_PAGED`
+        let object = unsafe {
+            ExAllocatePool2(
+                POOL_FLAG_NON_PAGED,
+                core::mem::size_of::<T>() as SIZE_T,
+                SYNC_TAG,
+            )
+        };
+        NonNull::new(object.cast())
+            .map(|object| Self { object })
+            .ok_or(STATUS_INSUFFICIENT_RESOURCES)
+    }
+
+    const fn as_ptr(&self) -> *mut T {
+        self.object.as_ptr()
+    }
+
+    /// Wait for the dispatcher object to be signaled, for at most `timeout`.
+    /// The timeout is in 100-nanosecond units, and is relative to the current
+    /// time if negative. With a `timeout` of `None` this waits indefinitely.
+    fn wait(&self, timeout: Option<i64>) -> Result<(), NTSTATUS> {
+        let mut timeout = timeout.map(|timeout| LARGE_INTEGER { QuadPart: timeout });
+
+        // `KernelMode` is 0, so it always fits in a `KPROCESSOR_MODE`
+        #[allow(clippy::cast_possible_truncation)]
+        let wait_mode = _MODE::KernelMode as KPROCESSOR_MODE;
+
+        let nt_status;
+        // SAFETY: `object` is an initialized dispatcher object that stays valid for as
+        // long as `self`. The timeout pointer is either null or points to a value that
+        // outlives the call.
+        unsafe {
+            nt_status = KeWaitForSingleObject(
+                self.as_ptr().cast(),
+                _KWAIT_REASON::Executive,
+                wait_mode,
+                0,
+                timeout
+                    .as_mut()
+                    .map_or(core::ptr::null_mut(), core::ptr::from_mut),
+            );
+        }
+        // STATUS_TIMEOUT is a success status, so it has to be checked explicitly
+        if nt_status == STATUS_TIMEOUT {
+            return Err(nt_status);
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+}
+
+impl<T> Drop for DispatcherObject<T> {
+    fn drop(&mut self) {
+        // SAFETY: `ExFreePool` is safe to call from any `IRQL` <= `DISPATCH_LEVEL`
+        // since its freeing memory allocated from `POOL_FLAG_NON_PAGED` in `try_new`.
+        // No thread can be waiting on the object, since waiting borrows it.
+        unsafe {
+            ExFreePool(self.as_ptr().cast());
+        }
+    }
+}
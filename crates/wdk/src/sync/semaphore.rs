@@ -0,0 +1,102 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use wdk_sys::{
+    KSEMAPHORE,
+    LONG,
+    NTSTATUS,
+    STATUS_INVALID_PARAMETER,
+    ntddk::{KeInitializeSemaphore, KeReadStateSemaphore, KeReleaseSemaphore},
+};
+
+use super::{DispatcherObject, NO_INCREMENT};
+
+/// Kernel Semaphore.
+///
+/// Use kernel semaphores to limit the number of threads that access a resource
+/// at the same time. Each [`Semaphore::acquire()`] takes one of the
+/// semaphore's available counts, which is given back when the returned
+/// [`SemaphoreGuard`] is dropped.
+///
+/// Counts are only given back through guards, so the count of the semaphore
+/// can never exceed its limit, which the kernel would raise an exception for.
+pub struct Semaphore {
+    semaphore: DispatcherObject<KSEMAPHORE>,
+}
+
+impl Semaphore {
+    /// Try to construct a semaphore with `count` available counts, out of at
+    /// most `limit`. Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_INVALID_PARAMETER` if `limit` is not
+    /// positive or `count` is not within `0..=limit`, and
+    /// `STATUS_INSUFFICIENT_RESOURCES` if the semaphore could not be
+    /// allocated.
+    pub fn try_new(count: LONG, limit: LONG) -> Result<Self, NTSTATUS> {
+        if limit <= 0 || !(0..=limit).contains(&count) {
+            return Err(STATUS_INVALID_PARAMETER);
+        }
+
+        let semaphore = DispatcherObject::<KSEMAPHORE>::try_new()?;
+        // SAFETY: `semaphore` points to a non-paged allocation large enough for a
+        // `KSEMAPHORE`, which is not used before it is initialized here
+        unsafe {
+            KeInitializeSemaphore(semaphore.as_ptr(), count, limit);
+        }
+        Ok(Self { semaphore })
+    }
+
+    /// Get the number of counts that are currently available
+    #[must_use]
+    pub fn available_count(&self) -> LONG {
+        let count;
+        // SAFETY: `semaphore` is a private member of `Semaphore` that was initialized
+        // by `KeInitializeSemaphore`, and stays valid for as long as `self`
+        unsafe {
+            count = KeReadStateSemaphore(self.semaphore.as_ptr());
+        }
+        count
+    }
+
+    /// Acquire one count of the semaphore, waiting at most `timeout` for one
+    /// to become available. The timeout is in 100-nanosecond units, and is
+    /// relative to the current time if negative. With a `timeout` of `None`
+    /// this waits indefinitely, and with a `timeout` of `Some(0)` this returns
+    /// immediately if no count is available.
+    ///
+    /// Must be called at `IRQL` <= `APC_LEVEL`, unless `timeout` is `Some(0)`,
+    /// in which case it can be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_TIMEOUT` if no count became available
+    /// before `timeout` elapsed.
+    pub fn acquire(&self, timeout: Option<i64>) -> Result<SemaphoreGuard<'_>, NTSTATUS> {
+        self.semaphore.wait(timeout)?;
+        Ok(SemaphoreGuard { semaphore: self })
+    }
+
+    fn release(&self) {
+        // SAFETY: `semaphore` is a private member of `Semaphore` that was initialized
+        // by `KeInitializeSemaphore`, and stays valid for as long as `self`. The count
+        // being released was taken by `acquire`, so it cannot exceed the limit.
+        unsafe {
+            KeReleaseSemaphore(self.semaphore.as_ptr(), NO_INCREMENT, 1, 0);
+        }
+    }
+}
+
+/// RAII guard of a count acquired from a [`Semaphore`]. The count is given back
+/// when the guard is dropped, at `IRQL` <= `DISPATCH_LEVEL`.
+#[must_use = "the semaphore count is released as soon as the guard is dropped"]
+pub struct SemaphoreGuard<'a> {
+    semaphore: &'a Semaphore,
+}
+
+impl Drop for SemaphoreGuard<'_> {
+    fn drop(&mut self) {
+        self.semaphore.release();
+    }
+}
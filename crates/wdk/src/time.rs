@@ -0,0 +1,12 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use wdk_sys::{ULONG64, ntddk::KeQueryInterruptTimePrecise};
+
+/// Get the current interrupt time, in 100-nanosecond intervals
+pub fn interrupt_time() -> ULONG64 {
+    let mut qpc_time_stamp: ULONG64 = 0;
+    // SAFETY: `qpc_time_stamp` is a valid location for the performance counter
+    // value that is returned along with the interrupt time
+    unsafe { KeQueryInterruptTimePrecise(&raw mut qpc_time_stamp) }
+}
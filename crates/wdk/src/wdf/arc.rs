@@ -0,0 +1,332 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{
+    marker::PhantomData,
+    ops::Deref,
+    ptr::NonNull,
+    sync::atomic::{AtomicUsize, Ordering, fence},
+};
+
+use wdk_sys::{
+    NTSTATUS,
+    STATUS_INSUFFICIENT_RESOURCES,
+    WDFOBJECT,
+    call_unsafe_wdf_function_binding,
+};
+
+use crate::{
+    nt_success,
+    wdf::context::{
+        declare_context_type,
+        dereference_object,
+        get_context,
+        object_attributes,
+        reference_object,
+    },
+};
+
+/// Largest alignment of the values an [`Arc`] can hold. The framework aligns
+/// the context space of its objects to at least this.
+pub const ARC_MAX_ALIGN: usize = 8;
+
+/// Thread-safe reference-counted pointer backed by a framework object.
+///
+/// The value is stored in the context space of a general framework object
+/// created by [`Arc::try_new()`]. [`Arc`] counts the strong references to the
+/// value, and drops it and deletes the object when the last one is dropped.
+/// [`Weak`] references, created with [`Arc::downgrade()`], hold a framework
+/// reference on the object instead, so that its memory stays valid until the
+/// last of them is dropped, but do not keep the value alive.
+///
+/// The strong count is the only reference count [`Arc`] keeps itself: it lives
+/// in the context created with the object, as the framework cannot take a
+/// reference only if the value is still alive. Every other reference, including
+/// those of [`Weak`], is a framework reference held by a [`RefCountedHandle`].
+///
+/// Use [`Weak`] for the back references of cyclic structures, ex. a queue
+/// context holding an `Arc<Timer>` whose context refers back to the queue, so
+/// that dropping the last strong reference from outside the cycle frees it.
+///
+/// The object is parented to the driver object. Values that are still shared
+/// when the driver object is deleted are not dropped.
+pub struct Arc<T: Send + Sync> {
+    wdf_object: WDFOBJECT,
+    inner: NonNull<ArcInner<T>>,
+    _marker: PhantomData<ArcInner<T>>,
+}
+
+// SAFETY: `Arc` only gives out shared references to `T`, and drops it on the
+// thread that drops the last strong reference, so `T` must be `Send + Sync`
+unsafe impl<T: Send + Sync> Send for Arc<T> {}
+// SAFETY: `Arc` only gives out shared references to `T`, and drops it on the
+// thread that drops the last strong reference, so `T` must be `Send + Sync`
+unsafe impl<T: Send + Sync> Sync for Arc<T> {}
+
+impl<T: Send + Sync> Arc<T> {
+    /// Try to move `value` into a new framework object. Must be called at
+    /// `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the framework fails to create the
+    /// object. The value is dropped in that case.
+    pub fn try_new(value: T) -> Result<Self, NTSTATUS> {
+        const {
+            assert!(
+                core::mem::align_of::<ArcInner<T>>() <= ARC_MAX_ALIGN,
+                "the alignment of the value exceeds ARC_MAX_ALIGN"
+            );
+        }
+        let mut attributes = object_attributes(&ARC_HEADER_TYPE_INFO);
+        attributes.ContextSizeOverride = core::mem::size_of::<ArcInner<T>>();
+        let mut wdf_object: WDFOBJECT = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `attributes` describes the context of the object and outlives the
+        // call. The framework zero-initializes the context, including its
+        // `ArcHeader`.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfObjectCreate,
+                &raw mut attributes,
+                &raw mut wdf_object,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        // SAFETY: `wdf_object` was just created with a context described by
+        // `ARC_HEADER_TYPE_INFO`, which stays valid while the object is referenced
+        let Some(header) = (unsafe { get_context::<ArcHeader>(wdf_object, &ARC_HEADER_TYPE_INFO) })
+        else {
+            // SAFETY: `wdf_object` was just created and is not shared yet
+            unsafe {
+                call_unsafe_wdf_function_binding!(WdfObjectDelete, wdf_object);
+            }
+            return Err(STATUS_INSUFFICIENT_RESOURCES);
+        };
+        let inner = NonNull::from(header).cast::<ArcInner<T>>();
+        // SAFETY: The context was allocated with the size of `ArcInner<T>`, and is
+        // aligned for it as checked above
+        let value_ptr = unsafe { &raw mut (*inner.as_ptr()).value };
+        // SAFETY: `value_ptr` is valid for writes, and the value is not initialized
+        // yet, so nothing is overwritten
+        unsafe {
+            value_ptr.write(value);
+        }
+        header.strong.store(1, Ordering::Release);
+        Ok(Self {
+            wdf_object,
+            inner,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Try to move `value` into a new framework object. This is an alias for
+    /// [`Arc::try_new()`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the framework fails to create the
+    /// object. The value is dropped in that case.
+    pub fn create(value: T) -> Result<Self, NTSTATUS> {
+        Self::try_new(value)
+    }
+
+    /// Create a [`Weak`] reference to the value of `this`
+    #[must_use]
+    pub fn downgrade(this: &Self) -> Weak<T> {
+        Weak {
+            // SAFETY: `this` holds a strong reference, so the object is not deleted
+            handle: unsafe { RefCountedHandle::new(this.wdf_object) },
+            inner: this.inner,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Get the number of strong references to the value of `this`
+    #[must_use]
+    pub fn strong_count(this: &Self) -> usize {
+        this.header().strong.load(Ordering::Acquire)
+    }
+
+    /// Returns `true` if `this` and `other` point to the same value
+    #[must_use]
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        this.wdf_object == other.wdf_object
+    }
+
+    fn header(&self) -> &ArcHeader {
+        // SAFETY: The context of the object stays valid while `self` holds a strong
+        // reference
+        unsafe { &(*self.inner.as_ptr()).header }
+    }
+}
+
+impl<T: Send + Sync> Clone for Arc<T> {
+    fn clone(&self) -> Self {
+        self.header().strong.fetch_add(1, Ordering::Relaxed);
+        Self {
+            wdf_object: self.wdf_object,
+            inner: self.inner,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Send + Sync> Deref for Arc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The value is initialized and not dropped while `self` holds a strong
+        // reference
+        unsafe { &(*self.inner.as_ptr()).value }
+    }
+}
+
+impl<T: Send + Sync> Drop for Arc<T> {
+    fn drop(&mut self) {
+        if self.header().strong.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+        // Synchronize with the other strong references releasing the value
+        fence(Ordering::Acquire);
+        // SAFETY: The context of the object stays valid while `self` holds a strong
+        // reference
+        let value_ptr = unsafe { &raw mut (*self.inner.as_ptr()).value };
+        // SAFETY: This was the last strong reference, so the value is not accessed
+        // anymore. Weak references cannot upgrade once the count reached zero.
+        unsafe {
+            core::ptr::drop_in_place(value_ptr);
+        }
+        // SAFETY: The object was created by `Arc::try_new()`. The framework frees its
+        // memory once the weak references release their framework references.
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfObjectDelete, self.wdf_object);
+        }
+    }
+}
+
+/// Weak reference to the value of an [`Arc`], created with
+/// [`Arc::downgrade()`].
+///
+/// A [`Weak`] does not keep the value alive, but keeps the memory of the
+/// framework object holding it valid, so that [`Weak::upgrade()`] can safely
+/// check whether the value is still alive.
+pub struct Weak<T: Send + Sync> {
+    handle: RefCountedHandle,
+    inner: NonNull<ArcInner<T>>,
+    _marker: PhantomData<ArcInner<T>>,
+}
+
+// SAFETY: `Weak` only gives access to `T` through `Arc`, which is `Send`
+unsafe impl<T: Send + Sync> Send for Weak<T> {}
+// SAFETY: `Weak` only gives access to `T` through `Arc`, which is `Sync`
+unsafe impl<T: Send + Sync> Sync for Weak<T> {}
+
+impl<T: Send + Sync> Weak<T> {
+    /// Try to get a strong reference to the value. Returns `None` if the value
+    /// was already dropped.
+    #[must_use]
+    pub fn upgrade(&self) -> Option<Arc<T>> {
+        // SAFETY: The framework reference held by `self` keeps the context of the
+        // object valid, even after the object is deleted
+        let header = unsafe { &(*self.inner.as_ptr()).header };
+        header
+            .strong
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |strong| {
+                (strong != 0).then_some(strong + 1)
+            })
+            .ok()
+            .map(|_| Arc {
+                wdf_object: self.handle.as_raw(),
+                inner: self.inner,
+                _marker: PhantomData,
+            })
+    }
+}
+
+impl<T: Send + Sync> Clone for Weak<T> {
+    fn clone(&self) -> Self {
+        Self {
+            handle: self.handle.clone(),
+            inner: self.inner,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Framework reference on an object.
+///
+/// The framework counts the references on each of its objects, and only frees
+/// the memory and the contexts of a deleted object once its last reference is
+/// released. A [`RefCountedHandle`] takes a reference when it is created or
+/// cloned, and releases it when it is dropped, so that the object it refers to
+/// stays valid meanwhile. It does not keep the object from being deleted.
+pub struct RefCountedHandle {
+    wdf_object: WDFOBJECT,
+}
+
+// SAFETY: The framework references and dereferences objects atomically, from
+// any thread
+unsafe impl Send for RefCountedHandle {}
+// SAFETY: `RefCountedHandle` only gives out the raw handle through a shared
+// reference
+unsafe impl Sync for RefCountedHandle {}
+
+impl RefCountedHandle {
+    /// Take a framework reference on `wdf_object`. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    ///
+    /// # Safety
+    ///
+    /// `wdf_object` must be a valid handle to a framework object
+    #[must_use]
+    pub unsafe fn new(wdf_object: WDFOBJECT) -> Self {
+        // SAFETY: The caller guarantees that `wdf_object` is valid
+        unsafe {
+            reference_object(wdf_object);
+        }
+        Self { wdf_object }
+    }
+
+    /// Get the raw handle of the referenced object
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFOBJECT {
+        self.wdf_object
+    }
+}
+
+impl Clone for RefCountedHandle {
+    fn clone(&self) -> Self {
+        // SAFETY: `self` holds a framework reference, so the object is valid
+        unsafe { Self::new(self.wdf_object) }
+    }
+}
+
+impl Drop for RefCountedHandle {
+    fn drop(&mut self) {
+        // SAFETY: `self` holds a framework reference, which is released here
+        unsafe {
+            dereference_object(self.wdf_object);
+        }
+    }
+}
+
+/// Strong reference count of an [`Arc`], at the start of the context of its
+/// framework object
+#[repr(C)]
+struct ArcHeader {
+    strong: AtomicUsize,
+}
+
+/// Context of the framework object of an [`Arc`]
+#[repr(C)]
+struct ArcInner<T> {
+    header: ArcHeader,
+    value: T,
+}
+
+declare_context_type!(ARC_HEADER_TYPE_INFO, ArcHeader);
@@ -0,0 +1,512 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{marker::PhantomData, mem::MaybeUninit};
+
+use wdk_sys::{
+    LCID,
+    NTSTATUS,
+    PWDF_CHILD_IDENTIFICATION_DESCRIPTION_HEADER,
+    PWDFDEVICE_INIT,
+    ULONG,
+    WDF_CHILD_IDENTIFICATION_DESCRIPTION_HEADER,
+    WDF_CHILD_LIST_CONFIG,
+    WDF_NO_OBJECT_ATTRIBUTES,
+    WDF_OBJECT_ATTRIBUTES,
+    WDFCHILDLIST,
+    WDFDEVICE,
+    call_unsafe_wdf_function_binding,
+};
+
+use crate::{nt_success, string::UnicodeStr, wdf::Device};
+
+/// Driver-defined identification of a child device reported on a
+/// [`ChildList`].
+///
+/// The framework copies identification descriptions bitwise and compares them
+/// when a child is reported more than once, so implementors must be plain
+/// [`Copy`] data without padding bytes that uniquely identifies a child (ex. a
+/// serial number or a port number).
+pub trait ChildIdentification: Copy {
+    /// Creates the PDO for a newly reported child. This is invoked from the
+    /// framework's `EvtChildListCreateDevice` callback with the
+    /// identification that was passed to [`ChildList::add_child`].
+    ///
+    /// The implementation is expected to assign the IDs of the child with
+    /// `child_init`, and then create the PDO with
+    /// [`ChildDeviceInit::create_device()`]. Full documentation is available in the [EvtChildListCreateDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfchildlist/nc-wdfchildlist-evt_wdf_child_list_create_device)
+    fn create_device(&self, child_list: &ChildList, child_init: ChildDeviceInit<'_>) -> NTSTATUS;
+}
+
+/// Identification description of a child device, as stored by the framework.
+///
+/// Wraps a [`ChildIdentification`] behind the
+/// [`WDF_CHILD_IDENTIFICATION_DESCRIPTION_HEADER`] that the framework requires
+/// at the start of every identification description.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ChildDescription<T: ChildIdentification> {
+    header: WDF_CHILD_IDENTIFICATION_DESCRIPTION_HEADER,
+    identification: T,
+}
+impl<T: ChildIdentification> ChildDescription<T> {
+    /// Size of the identification description reported to the framework
+    const SIZE: ULONG = {
+        let size = core::mem::size_of::<Self>();
+
+        // Manually assert there is not truncation since clippy doesn't work for
+        // compile-time constants
+        assert!(size <= ULONG::MAX as usize);
+
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        let size = size as ULONG;
+        size
+    };
+
+    /// Construct a child description for `identification`
+    #[must_use]
+    pub const fn new(identification: T) -> Self {
+        // The framework compares descriptions byte by byte, so the padding between
+        // the header and the identification is zeroed rather than left uninitialized
+        let mut description = MaybeUninit::<Self>::zeroed();
+        let description_ptr = description.as_mut_ptr();
+        // SAFETY: `description_ptr` points to the allocation of `description`, so the
+        // field is in bounds
+        let header_ptr = unsafe { &raw mut (*description_ptr).header };
+        // SAFETY: `header_ptr` is valid for writes and aligned, as a field of `Self`
+        unsafe {
+            header_ptr.write(WDF_CHILD_IDENTIFICATION_DESCRIPTION_HEADER {
+                IdentificationDescriptionSize: Self::SIZE,
+            });
+        }
+        // SAFETY: `description_ptr` points to the allocation of `description`, so the
+        // field is in bounds
+        let identification_ptr = unsafe { &raw mut (*description_ptr).identification };
+        // SAFETY: `identification_ptr` is valid for writes and aligned, as a field of
+        // `Self`
+        unsafe {
+            identification_ptr.write(identification);
+        }
+        // SAFETY: Both fields were written above, and the padding is zeroed
+        unsafe { description.assume_init() }
+    }
+
+    /// Get the identification of the child
+    #[must_use]
+    pub const fn identification(&self) -> &T {
+        &self.identification
+    }
+}
+
+/// WDF Child List.
+///
+/// Bus drivers use child lists to report the child devices they enumerate.
+/// Each child is identified by a [`ChildDescription`] of a driver-defined
+/// [`ChildIdentification`] type. When a new child is reported, the framework
+/// calls [`ChildIdentification::create_device`] to create its PDO.
+///
+/// A driver typically configures the default child list of its FDO with the
+/// [`WDF_CHILD_LIST_CONFIG`] returned by [`ChildList::config()`] before
+/// creating the device, and then retrieves it with
+/// [`ChildList::default_for_device()`].
+pub struct ChildList {
+    wdf_child_list: WDFCHILDLIST,
+}
+impl ChildList {
+    /// Build a [`WDF_CHILD_LIST_CONFIG`] for child lists whose children are
+    /// identified by `T`. The returned config routes the framework's
+    /// `EvtChildListCreateDevice` callback to
+    /// [`ChildIdentification::create_device`].
+    #[must_use]
+    pub fn config<T: ChildIdentification>() -> WDF_CHILD_LIST_CONFIG {
+        let wdf_child_list_config_size: ULONG;
+
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            const WDF_CHILD_LIST_CONFIG_SIZE: usize = core::mem::size_of::<WDF_CHILD_LIST_CONFIG>();
+
+            // Manually assert there is not truncation since clippy doesn't work for
+            // compile-time constants
+            const { assert!(WDF_CHILD_LIST_CONFIG_SIZE <= ULONG::MAX as usize) }
+
+            wdf_child_list_config_size = WDF_CHILD_LIST_CONFIG_SIZE as ULONG;
+        }
+
+        WDF_CHILD_LIST_CONFIG {
+            Size: wdf_child_list_config_size,
+            IdentificationDescriptionSize: ChildDescription::<T>::SIZE,
+            EvtChildListCreateDevice: Some(evt_child_list_create_device::<T>),
+            ..WDF_CHILD_LIST_CONFIG::default()
+        }
+    }
+
+    /// Try to construct a WDF Child List object for `device`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a child
+    /// list. The error variant will contain a [`NTSTATUS`] of the failure.
+    /// Full error documentation is available in the [WDFChildList Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfchildlist/nf-wdfchildlist-wdfchildlistcreate#return-value)
+    pub fn try_new(
+        device: WDFDEVICE,
+        config: &mut WDF_CHILD_LIST_CONFIG,
+        attributes: &mut WDF_OBJECT_ATTRIBUTES,
+    ) -> Result<Self, NTSTATUS> {
+        let mut child_list = Self {
+            wdf_child_list: core::ptr::null_mut(),
+        };
+
+        let nt_status;
+        // SAFETY: The resulting ffi object is stored in a private member and not
+        // accessible outside of this module, and this module guarantees that it is
+        // always in a valid state.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfChildListCreate,
+                device,
+                config,
+                attributes,
+                &mut child_list.wdf_child_list as *mut WDFCHILDLIST,
+            );
+        }
+        nt_success(nt_status).then_some(child_list).ok_or(nt_status)
+    }
+
+    /// Get the default child list of `device`. The default child list must
+    /// have been configured with `WdfFdoInitSetDefaultChildListConfig` before
+    /// `device` was created.
+    ///
+    /// # Safety
+    ///
+    /// `device` must be a valid handle to a framework device object that was
+    /// created with a default child list configuration.
+    #[must_use]
+    pub unsafe fn default_for_device(device: WDFDEVICE) -> Self {
+        let wdf_child_list;
+        // SAFETY: The caller guarantees that `device` is a valid FDO handle with a
+        // default child list.
+        unsafe {
+            wdf_child_list = call_unsafe_wdf_function_binding!(WdfFdoGetDefaultChildList, device);
+        }
+        Self { wdf_child_list }
+    }
+
+    /// Start a scan of the child list. Children reported between
+    /// [`ChildList::begin_scan()`] and [`ChildList::end_scan()`] are marked
+    /// present, and every child not reported is marked missing.
+    pub fn begin_scan(&self) {
+        // SAFETY: `wdf_child_list` is a private member of `ChildList`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfChildListBeginScan, self.wdf_child_list);
+        }
+    }
+
+    /// End a scan of the child list started by [`ChildList::begin_scan()`]
+    pub fn end_scan(&self) {
+        // SAFETY: `wdf_child_list` is a private member of `ChildList`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfChildListEndScan, self.wdf_child_list);
+        }
+    }
+
+    /// Report a child as present. If the child is new, the framework creates
+    /// its PDO through [`ChildIdentification::create_device`].
+    ///
+    /// `T` must be the identification type that the child list was configured
+    /// with through [`ChildList::config()`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to add the child. The
+    /// error variant will contain a [`NTSTATUS`] of the failure. Full error
+    /// documentation is available in the [WDFChildList Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfchildlist/nf-wdfchildlist-wdfchildlistaddorupdatechilddescriptionaspresent#return-value)
+    pub fn add_child<T: ChildIdentification>(
+        &self,
+        description: &mut ChildDescription<T>,
+    ) -> Result<(), NTSTATUS> {
+        let nt_status;
+        // SAFETY: `wdf_child_list` is a private member of `ChildList`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state. `description` starts with a header that matches its size.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfChildListAddOrUpdateChildDescriptionAsPresent,
+                self.wdf_child_list,
+                &raw mut description.header,
+                core::ptr::null_mut(),
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Report a child as missing. The framework removes the PDO of the child.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to update the child.
+    /// The error variant will contain a [`NTSTATUS`] of the failure. Full
+    /// error documentation is available in the [WDFChildList Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfchildlist/nf-wdfchildlist-wdfchildlistupdatechilddescriptionasmissing#return-value)
+    pub fn mark_child_missing<T: ChildIdentification>(
+        &self,
+        description: &mut ChildDescription<T>,
+    ) -> Result<(), NTSTATUS> {
+        let nt_status;
+        // SAFETY: `wdf_child_list` is a private member of `ChildList`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state. `description` starts with a header that matches its size.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfChildListUpdateChildDescriptionAsMissing,
+                self.wdf_child_list,
+                &raw mut description.header,
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Report every child on the list as present
+    pub fn update_all_as_present(&self) {
+        // SAFETY: `wdf_child_list` is a private member of `ChildList`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfChildListUpdateAllChildDescriptionsAsPresent,
+                self.wdf_child_list
+            );
+        }
+    }
+}
+
+/// Initialization of the PDO of a child device, as passed to
+/// [`ChildIdentification::create_device`].
+///
+/// The PnP manager matches the child with its function driver by the IDs
+/// assigned here, ex. with [`ChildDeviceInit::assign_device_id()`] and
+/// [`ChildDeviceInit::add_hardware_id()`]. IDs are passed as [`UnicodeStr`]s,
+/// which are built from Rust strings with
+/// [`crate::string::UnicodeString::try_from()`]. The framework copies every ID,
+/// so they only need to live until the call returns. The initialization is
+/// only valid during the callback it is passed to, and is consumed by
+/// [`ChildDeviceInit::create_device()`].
+pub struct ChildDeviceInit<'a> {
+    device_init: PWDFDEVICE_INIT,
+    _callback: PhantomData<&'a mut ()>,
+}
+impl ChildDeviceInit<'_> {
+    /// Get the raw initialization of the PDO, ex. to set its PnP and power
+    /// callbacks before it is created
+    #[must_use]
+    pub const fn as_raw(&self) -> PWDFDEVICE_INIT {
+        self.device_init
+    }
+
+    /// Assign the device ID of the child, ex. `MyBus\MyChild`. Must be called
+    /// at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the ID could not be assigned,
+    /// ex. `STATUS_INSUFFICIENT_RESOURCES` if the framework could not copy it.
+    /// Full error documentation is available in the [WdfPdoInitAssignDeviceID Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfpdo/nf-wdfpdo-wdfpdoinitassigndeviceid#return-value)
+    pub fn assign_device_id(&mut self, device_id: UnicodeStr<'_>) -> Result<(), NTSTATUS> {
+        let device_id = device_id.as_raw();
+        let nt_status;
+        // SAFETY: `device_init` is the initialization passed to the running
+        // `EvtChildListCreateDevice` callback. The framework copies the ID, which
+        // outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfPdoInitAssignDeviceID,
+                self.device_init,
+                &raw const device_id,
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Assign the instance ID of the child, which tells it apart from the
+    /// other children with the same device ID, ex. its serial number. Must be
+    /// called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the ID could not be assigned,
+    /// ex. `STATUS_INSUFFICIENT_RESOURCES` if the framework could not copy it.
+    /// Full error documentation is available in the [WdfPdoInitAssignInstanceID Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfpdo/nf-wdfpdo-wdfpdoinitassigninstanceid#return-value)
+    pub fn assign_instance_id(&mut self, instance_id: UnicodeStr<'_>) -> Result<(), NTSTATUS> {
+        let instance_id = instance_id.as_raw();
+        let nt_status;
+        // SAFETY: `device_init` is the initialization passed to the running
+        // `EvtChildListCreateDevice` callback. The framework copies the ID, which
+        // outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfPdoInitAssignInstanceID,
+                self.device_init,
+                &raw const instance_id,
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Add a hardware ID to the child. The first hardware ID added is the most
+    /// specific one. Must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the ID could not be added, ex.
+    /// `STATUS_INSUFFICIENT_RESOURCES` if the framework could not copy it.
+    /// Full error documentation is available in the [WdfPdoInitAddHardwareID Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfpdo/nf-wdfpdo-wdfpdoinitaddhardwareid#return-value)
+    pub fn add_hardware_id(&mut self, hardware_id: UnicodeStr<'_>) -> Result<(), NTSTATUS> {
+        let hardware_id = hardware_id.as_raw();
+        let nt_status;
+        // SAFETY: `device_init` is the initialization passed to the running
+        // `EvtChildListCreateDevice` callback. The framework copies the ID, which
+        // outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfPdoInitAddHardwareID,
+                self.device_init,
+                &raw const hardware_id,
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Add a compatible ID to the child, which the PnP manager falls back to
+    /// when no driver matches its hardware IDs. Must be called at `IRQL` =
+    /// `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the ID could not be added, ex.
+    /// `STATUS_INSUFFICIENT_RESOURCES` if the framework could not copy it.
+    /// Full error documentation is available in the [WdfPdoInitAddCompatibleID Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfpdo/nf-wdfpdo-wdfpdoinitaddcompatibleid#return-value)
+    pub fn add_compatible_id(&mut self, compatible_id: UnicodeStr<'_>) -> Result<(), NTSTATUS> {
+        let compatible_id = compatible_id.as_raw();
+        let nt_status;
+        // SAFETY: `device_init` is the initialization passed to the running
+        // `EvtChildListCreateDevice` callback. The framework copies the ID, which
+        // outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfPdoInitAddCompatibleID,
+                self.device_init,
+                &raw const compatible_id,
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Add the description and the location of the child, as displayed by the
+    /// Device Manager, for the locale `locale_id`, ex. `0x409` for English.
+    /// The locale is made the default one with
+    /// [`ChildDeviceInit::set_default_locale()`]. Must be called at `IRQL` =
+    /// `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the text could not be added, ex.
+    /// `STATUS_INSUFFICIENT_RESOURCES` if the framework could not copy it.
+    /// Full error documentation is available in the [WdfPdoInitAddDeviceText Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfpdo/nf-wdfpdo-wdfpdoinitadddevicetext#return-value)
+    pub fn add_device_text(
+        &mut self,
+        description: UnicodeStr<'_>,
+        location: UnicodeStr<'_>,
+        locale_id: LCID,
+    ) -> Result<(), NTSTATUS> {
+        let description = description.as_raw();
+        let location = location.as_raw();
+        let nt_status;
+        // SAFETY: `device_init` is the initialization passed to the running
+        // `EvtChildListCreateDevice` callback. The framework copies the text, which
+        // outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfPdoInitAddDeviceText,
+                self.device_init,
+                &raw const description,
+                &raw const location,
+                locale_id,
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Set the locale whose device text is displayed when the locale of the
+    /// system has none. Must be called at `IRQL` = `PASSIVE_LEVEL`.
+    pub fn set_default_locale(&mut self, locale_id: LCID) {
+        // SAFETY: `device_init` is the initialization passed to the running
+        // `EvtChildListCreateDevice` callback
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfPdoInitSetDefaultLocale,
+                self.device_init,
+                locale_id,
+            );
+        }
+    }
+
+    /// Create the PDO of the child. Must be called at `IRQL` =
+    /// `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to create the device.
+    /// The error variant will contain a [`NTSTATUS`] of the failure. Full error
+    /// documentation is available in the [WdfDeviceCreate Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdevicecreate#return-value)
+    pub fn create_device(self) -> Result<Device, NTSTATUS> {
+        let mut device_init = self.device_init;
+        let mut device: WDFDEVICE = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `device_init` is the initialization passed to the running
+        // `EvtChildListCreateDevice` callback, which is consumed by this call
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfDeviceCreate,
+                &raw mut device_init,
+                WDF_NO_OBJECT_ATTRIBUTES,
+                &raw mut device,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        // SAFETY: The device was just created, and is deleted by the framework when
+        // the child is removed
+        Ok(unsafe { Device::from_raw(device) })
+    }
+}
+
+/// `EvtChildListCreateDevice` callback that forwards to
+/// [`ChildIdentification::create_device`]
+unsafe extern "C" fn evt_child_list_create_device<T: ChildIdentification>(
+    wdf_child_list: WDFCHILDLIST,
+    identification_description: PWDF_CHILD_IDENTIFICATION_DESCRIPTION_HEADER,
+    child_init: PWDFDEVICE_INIT,
+) -> NTSTATUS {
+    let child_list = ChildList { wdf_child_list };
+    // SAFETY: The child list was configured through `ChildList::config::<T>()`, so
+    // every identification description the framework hands back is a
+    // `ChildDescription<T>` which starts with this header.
+    let description = unsafe {
+        &*identification_description
+            .cast::<ChildDescription<T>>()
+            .cast_const()
+    };
+    let child_init = ChildDeviceInit {
+        device_init: child_init,
+        _callback: PhantomData,
+    };
+    description
+        .identification
+        .create_device(&child_list, child_init)
+}
@@ -0,0 +1,203 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::marker::PhantomData;
+
+use wdk_sys::{
+    NTSTATUS,
+    ULONG,
+    WDF_OBJECT_ATTRIBUTES,
+    WDFCOLLECTION,
+    WDFDEVICE,
+    WDFKEY,
+    WDFMEMORY,
+    WDFOBJECT,
+    WDFQUEUE,
+    WDFREQUEST,
+    WDFSPINLOCK,
+    WDFSTRING,
+    WDFTIMER,
+    WDFWAITLOCK,
+    WDFWORKITEM,
+    call_unsafe_wdf_function_binding,
+};
+
+use crate::nt_success;
+
+/// Handle to a framework object that can be stored in a [`Collection`].
+///
+/// # Safety
+///
+/// Implementors must be handles to framework objects, so that they can be
+/// passed to WDF as a [`WDFOBJECT`] and converted back from the [`WDFOBJECT`]
+/// returned by WDF.
+pub unsafe trait WdfObjectHandle: Copy + PartialEq {
+    /// Convert the handle into a generic [`WDFOBJECT`] handle
+    fn into_wdf_object(self) -> WDFOBJECT;
+
+    /// Convert a generic [`WDFOBJECT`] handle, that is known to be a handle of
+    /// this type, back into this type
+    fn from_wdf_object(object: WDFOBJECT) -> Self;
+}
+
+macro_rules! impl_wdf_object_handle {
+    ($($handle:ty),* $(,)?) => {
+        $(
+            // SAFETY: `$handle` is a framework object handle
+            unsafe impl WdfObjectHandle for $handle {
+                fn into_wdf_object(self) -> WDFOBJECT {
+                    self.cast()
+                }
+
+                fn from_wdf_object(object: WDFOBJECT) -> Self {
+                    object.cast()
+                }
+            }
+        )*
+    };
+}
+
+impl_wdf_object_handle!(
+    WDFCOLLECTION,
+    WDFDEVICE,
+    WDFKEY,
+    WDFMEMORY,
+    WDFQUEUE,
+    WDFREQUEST,
+    WDFSPINLOCK,
+    WDFSTRING,
+    WDFTIMER,
+    WDFWAITLOCK,
+    WDFWORKITEM,
+);
+
+/// WDF Collection.
+///
+/// A collection is a list of framework objects of type `T`, ex. the
+/// [`WDFREQUEST`]s a driver is currently processing. Adding an object to a
+/// collection takes a reference on it, so that it is not deleted while it is in
+/// the collection. The collection is deleted along with its parent object,
+/// which is set through the `ParentObject` of the attributes passed to
+/// [`Collection::try_new()`], ex. a device or a queue.
+///
+/// The framework does not synchronize access to collections. Drivers accessing
+/// a collection from more than one callback must protect it with a lock, ex. a
+/// [`SpinLock`].
+///
+/// [`SpinLock`]: crate::wdf::SpinLock
+pub struct Collection<T: WdfObjectHandle> {
+    wdf_collection: WDFCOLLECTION,
+    _item: PhantomData<T>,
+}
+impl<T: WdfObjectHandle> Collection<T> {
+    /// Try to construct a WDF Collection object
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a
+    /// collection. The error variant will contain a [`NTSTATUS`] of the
+    /// failure. Full error documentation is available in the [WDFCollection Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfcollection/nf-wdfcollection-wdfcollectioncreate#return-value)
+    pub fn try_new(attributes: &mut WDF_OBJECT_ATTRIBUTES) -> Result<Self, NTSTATUS> {
+        let mut collection = Self {
+            wdf_collection: core::ptr::null_mut(),
+            _item: PhantomData,
+        };
+
+        let nt_status;
+        // SAFETY: The resulting ffi object is stored in a private member and not
+        // accessible outside of this module, and this module guarantees that it is
+        // always in a valid state.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfCollectionCreate,
+                attributes,
+                &mut collection.wdf_collection as *mut WDFCOLLECTION,
+            );
+        }
+        nt_success(nt_status).then_some(collection).ok_or(nt_status)
+    }
+
+    /// Add `item` to the end of the collection
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to add the item. The
+    /// error variant will contain a [`NTSTATUS`] of the failure. Full error
+    /// documentation is available in the [WDFCollection Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfcollection/nf-wdfcollection-wdfcollectionadd#return-value)
+    pub fn add(&self, item: T) -> Result<(), NTSTATUS> {
+        let nt_status;
+        // SAFETY: `wdf_collection` is a private member of `Collection`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfCollectionAdd,
+                self.wdf_collection,
+                item.into_wdf_object(),
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Remove the first occurrence of `item` from the collection. Returns
+    /// `false` if `item` is not in the collection.
+    pub fn remove(&self, item: T) -> bool {
+        let Some(index) = (0..self.len()).find(|&index| self.get(index) == Some(item)) else {
+            return false;
+        };
+        self.remove_at(index);
+        true
+    }
+
+    /// Remove the item at `index` from the collection. Does nothing if `index`
+    /// is out of bounds, as WDF bug checks on invalid indices.
+    pub fn remove_at(&self, index: ULONG) {
+        if index >= self.len() {
+            return;
+        }
+        // SAFETY: `wdf_collection` is a private member of `Collection`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state. `index` was checked to be in bounds.
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfCollectionRemoveItem, self.wdf_collection, index);
+        }
+    }
+
+    /// Get the item at `index`, or `None` if `index` is out of bounds
+    #[must_use]
+    pub fn get(&self, index: ULONG) -> Option<T> {
+        let object: WDFOBJECT;
+        // SAFETY: `wdf_collection` is a private member of `Collection`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            object =
+                call_unsafe_wdf_function_binding!(WdfCollectionGetItem, self.wdf_collection, index);
+        }
+        (!object.is_null()).then(|| T::from_wdf_object(object))
+    }
+
+    /// Get the number of items in the collection
+    #[must_use]
+    pub fn len(&self) -> ULONG {
+        let count;
+        // SAFETY: `wdf_collection` is a private member of `Collection`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            count = call_unsafe_wdf_function_binding!(WdfCollectionGetCount, self.wdf_collection);
+        }
+        count
+    }
+
+    /// Returns `true` if the collection has no items
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over the items of the collection, from first to last
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        (0..self.len()).filter_map(|index| self.get(index))
+    }
+}
@@ -0,0 +1,150 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Deprecated forms of the WDF APIs whose signatures changed.
+//!
+//! Each item keeps the previous signature of an API, implemented over the
+//! current one, so that drivers keep building while they migrate. Items are
+//! kept for one minor release after the change, and then removed. The
+//! `Migration` notes of the changelog describe how to replace each of them.
+//!
+//! Some changes have no shim, as the previous signature cannot be implemented
+//! soundly: [`Request`] is no longer `Clone` or `Copy`, and
+//! [`Request::mark_cancelable()`] is `unsafe`, with
+//! [`Request::into_cancelable()`] as its safe replacement.
+
+// The items of this module are deprecated, and implemented in terms of each
+// other
+#![allow(deprecated)]
+
+use wdk_sys::{NTSTATUS, WDF_OBJECT_ATTRIBUTES};
+
+use super::Request;
+#[cfg(driver_model__driver_type = "KMDF")]
+use super::{IoTarget, RequestCompletionCallback};
+
+/// WDF Wait Lock that protects no data, as [`super::WaitLock`] was before it
+/// owned the data it protects
+#[deprecated(
+    since = "0.5.0",
+    note = "use `wdf::WaitLock<T>`, which owns the data it protects, or `wdf::WaitLock<()>`"
+)]
+pub struct WaitLock(super::WaitLock<()>);
+
+/// RAII guard of an acquired [`WaitLock`]
+#[deprecated(since = "0.5.0", note = "use `wdf::WaitLockGuard<'a, T>`")]
+pub type WaitLockGuard<'a> = super::WaitLockGuard<'a, ()>;
+
+impl WaitLock {
+    /// Try to construct a WDF Wait Lock object. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a wait
+    /// lock. The error variant will contain a [`NTSTATUS`] of the failure. Full
+    /// error documentation is available in the [WdfWaitLockCreate Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfsync/nf-wdfsync-wdfwaitlockcreate#return-value)
+    pub fn try_new(attributes: &mut WDF_OBJECT_ATTRIBUTES) -> Result<Self, NTSTATUS> {
+        super::WaitLock::try_new(attributes, ()).map(Self)
+    }
+
+    /// Try to construct a WDF Wait Lock object. This is an alias for
+    /// [`WaitLock::try_new()`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a wait
+    /// lock. The error variant will contain a [`NTSTATUS`] of the failure. Full
+    /// error documentation is available in the [WdfWaitLockCreate Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfsync/nf-wdfsync-wdfwaitlockcreate#return-value)
+    pub fn create(attributes: &mut WDF_OBJECT_ATTRIBUTES) -> Result<Self, NTSTATUS> {
+        Self::try_new(attributes)
+    }
+
+    /// Acquire the wait lock, waiting for at most `timeout`, in 100-nanosecond
+    /// units, or indefinitely if `timeout` is `None`. See
+    /// [`super::WaitLock::acquire()`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_TIMEOUT` if the lock could not be
+    /// acquired before `timeout` elapsed.
+    pub fn acquire(&self, timeout: Option<i64>) -> Result<WaitLockGuard<'_>, NTSTATUS> {
+        self.0.acquire(timeout)
+    }
+}
+
+/// Return `request` to the queue it was delivered from, completing it with the
+/// error status if that fails, as the previous signature did not hand the
+/// request back
+///
+/// # Errors
+///
+/// This function will return the error of [`Request::requeue()`]. The request
+/// is completed with it.
+#[deprecated(
+    since = "0.5.0",
+    note = "use `Request::requeue()`, which hands the request back on failure"
+)]
+pub fn requeue(request: Request) -> Result<(), NTSTATUS> {
+    request.requeue().map_err(complete_with_error)
+}
+
+/// Acknowledge that the driver stopped processing `request` when its queue is
+/// stopping, and requeue it if `requeue` is `true`. Returns the request unless
+/// it was requeued, as the driver still owns it then.
+#[deprecated(
+    since = "0.5.0",
+    note = "use `Request::stop_acknowledge()`, or `Request::stop_acknowledge_and_requeue()` to \
+            requeue the request"
+)]
+pub fn stop_acknowledge(request: Request, requeue: bool) -> Option<Request> {
+    if requeue {
+        request.stop_acknowledge_and_requeue();
+        return None;
+    }
+    request.stop_acknowledge();
+    Some(request)
+}
+
+/// Send `request` to `target`, completing it with the error status if it
+/// could not be sent, as the previous signature did not hand the request back
+///
+/// # Errors
+///
+/// This function will return the error of [`IoTarget::send()`]. The request
+/// is completed with it.
+#[cfg(driver_model__driver_type = "KMDF")]
+#[deprecated(
+    since = "0.5.0",
+    note = "use `IoTarget::send()`, which hands the request back on failure"
+)]
+pub fn send<T: RequestCompletionCallback>(
+    target: &IoTarget,
+    request: Request,
+) -> Result<(), NTSTATUS> {
+    target.send::<T>(request).map_err(complete_with_error)
+}
+
+/// Send `request` to `target` without being notified of its completion,
+/// completing it with the error status if it could not be sent, as the
+/// previous signature did not hand the request back
+///
+/// # Errors
+///
+/// This function will return the error of [`IoTarget::send_and_forget()`].
+/// The request is completed with it.
+#[cfg(driver_model__driver_type = "KMDF")]
+#[deprecated(
+    since = "0.5.0",
+    note = "use `IoTarget::send_and_forget()`, which hands the request back on failure"
+)]
+pub fn send_and_forget(target: &IoTarget, request: Request) -> Result<(), NTSTATUS> {
+    target.send_and_forget(request).map_err(complete_with_error)
+}
+
+/// Complete a request handed back by a failed hand-off with the error, and
+/// return the error
+fn complete_with_error((request, nt_status): (Request, NTSTATUS)) -> NTSTATUS {
+    request.complete(nt_status);
+    nt_status
+}
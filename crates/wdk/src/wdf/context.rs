@@ -0,0 +1,590 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Context spaces that the wrappers allocate on the framework objects they
+//! wrap, to keep state that lives as long as the object does.
+
+use core::{
+    any::TypeId,
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    ptr::NonNull,
+    sync::atomic::{AtomicPtr, AtomicU8, Ordering},
+};
+
+use wdk_sys::{
+    _WDF_EXECUTION_LEVEL,
+    _WDF_SYNCHRONIZATION_SCOPE,
+    NTSTATUS,
+    STATUS_INSUFFICIENT_RESOURCES,
+    STATUS_OBJECT_NAME_COLLISION,
+    STATUS_SUCCESS,
+    ULONG,
+    WDF_OBJECT_ATTRIBUTES,
+    WDF_OBJECT_CONTEXT_TYPE_INFO,
+    WDFOBJECT,
+    call_unsafe_wdf_function_binding,
+};
+
+use crate::nt_success;
+
+/// Largest alignment of the values stored with [`set_value_context()`]. The
+/// framework aligns the context space of its objects to at least this.
+pub const VALUE_CONTEXT_MAX_ALIGN: usize = 8;
+
+/// States of the value stored with [`set_value_context()`]
+const VALUE_EMPTY: u8 = 0;
+const VALUE_INITIALIZING: u8 = 1;
+const VALUE_READY: u8 = 2;
+
+/// Framework type information of a context type, declared with
+/// [`declare_context_type!`]
+#[repr(transparent)]
+pub struct ContextTypeInfo(WDF_OBJECT_CONTEXT_TYPE_INFO);
+
+// SAFETY: The type information is immutable and only points to itself and to a
+// static string, so it can be shared between threads.
+unsafe impl Sync for ContextTypeInfo {}
+
+impl ContextTypeInfo {
+    /// Describe the context type `T`. `unique_type` must be the address of the
+    /// returned type information, as the framework identifies context types by
+    /// it.
+    pub const fn new<T>(name: &'static core::ffi::CStr, unique_type: *const Self) -> Self {
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        const SIZE: ULONG = core::mem::size_of::<WDF_OBJECT_CONTEXT_TYPE_INFO>() as ULONG;
+
+        Self(WDF_OBJECT_CONTEXT_TYPE_INFO {
+            Size: SIZE,
+            ContextName: name.as_ptr().cast_mut().cast(),
+            ContextSize: core::mem::size_of::<T>(),
+            UniqueType: unique_type.cast(),
+            EvtDriverGetUniqueContextType: None,
+        })
+    }
+}
+
+/// Declare a static `$type_info` describing the context type `$context`
+macro_rules! declare_context_type {
+    ($type_info:ident, $context:ty) => {
+        static $type_info: $crate::wdf::context::ContextTypeInfo =
+            $crate::wdf::context::ContextTypeInfo::new::<$context>(
+                match core::ffi::CStr::from_bytes_with_nul(
+                    concat!(stringify!($context), "\0").as_bytes(),
+                ) {
+                    Ok(name) => name,
+                    Err(_) => panic!("context type name contains a NUL character"),
+                },
+                &raw const $type_info,
+            );
+    };
+}
+pub(crate) use declare_context_type;
+
+/// Get the context of type `T` of `object`, allocating it on first use. The
+/// framework returns the existing context if another thread allocated it
+/// first.
+///
+/// # Errors
+///
+/// This function will return an error if the framework fails to allocate the
+/// context.
+///
+/// # Safety
+///
+/// `object` must be a valid handle to a framework object, that stays valid for
+/// as long as the returned context is used. `type_info` must describe `T`, and
+/// all-zero bytes must be a valid `T`, as the framework zero-initializes
+/// contexts.
+pub unsafe fn allocate_context<'a, T>(
+    object: WDFOBJECT,
+    type_info: &'static ContextTypeInfo,
+) -> Result<&'a T, NTSTATUS> {
+    // SAFETY: The caller upholds the requirements of `allocate_context`, and
+    // `object_attributes` describes the context type without callbacks
+    unsafe { allocate_context_with_attributes(object, &mut object_attributes(type_info)) }
+}
+
+/// Get the context described by `attributes` of `object`, allocating it on
+/// first use, like [`allocate_context()`]. The cleanup and destroy callbacks
+/// in `attributes` are invoked for the context when `object` is deleted, and
+/// the size of the context can be overridden with `ContextSizeOverride`.
+///
+/// # Errors
+///
+/// This function will return an error if the framework fails to allocate the
+/// context.
+///
+/// # Safety
+///
+/// `object` must be a valid handle to a framework object, that stays valid for
+/// as long as the returned context is used. `attributes` must describe a
+/// context of at least the size of `T`, and all-zero bytes must be a valid
+/// `T`, as the framework zero-initializes contexts.
+pub unsafe fn allocate_context_with_attributes<'a, T>(
+    object: WDFOBJECT,
+    attributes: &mut WDF_OBJECT_ATTRIBUTES,
+) -> Result<&'a T, NTSTATUS> {
+    let mut context = core::ptr::null_mut();
+
+    let nt_status;
+    // SAFETY: The caller guarantees that `object` is valid. `attributes` describes
+    // the context type and outlives the call.
+    unsafe {
+        nt_status = call_unsafe_wdf_function_binding!(
+            WdfObjectAllocateContext,
+            object,
+            attributes,
+            &raw mut context,
+        );
+    }
+    if !nt_success(nt_status) {
+        return Err(nt_status);
+    }
+    // SAFETY: On success, the framework returns the zero-initialized context of
+    // `object`, which the caller guarantees to be a valid `T` that outlives `'a`
+    unsafe { context.cast::<T>().as_ref() }.ok_or(STATUS_INSUFFICIENT_RESOURCES)
+}
+
+/// Take a framework reference on `wdf_object`
+///
+/// # Safety
+///
+/// `wdf_object` must be a valid handle to a framework object
+pub unsafe fn reference_object(wdf_object: WDFOBJECT) {
+    // SAFETY: The caller guarantees that `wdf_object` is valid
+    unsafe {
+        call_unsafe_wdf_function_binding!(
+            WdfObjectReferenceActual,
+            wdf_object,
+            core::ptr::null_mut(),
+            0,
+            core::ptr::null_mut(),
+        );
+    }
+}
+
+/// Release a framework reference on `wdf_object`
+///
+/// # Safety
+///
+/// The caller must hold a reference on `wdf_object` taken with
+/// [`reference_object()`]
+pub unsafe fn dereference_object(wdf_object: WDFOBJECT) {
+    // SAFETY: The caller holds a reference, so `wdf_object` is valid
+    unsafe {
+        call_unsafe_wdf_function_binding!(
+            WdfObjectDereferenceActual,
+            wdf_object,
+            core::ptr::null_mut(),
+            0,
+            core::ptr::null_mut(),
+        );
+    }
+}
+
+/// Get object attributes that describe a context of the type described by
+/// `type_info`, with the execution level and synchronization scope inherited
+/// from the parent object
+pub fn object_attributes(type_info: &'static ContextTypeInfo) -> WDF_OBJECT_ATTRIBUTES {
+    WDF_OBJECT_ATTRIBUTES {
+        ContextTypeInfo: &raw const type_info.0,
+        ..inherited_object_attributes()
+    }
+}
+
+/// Get object attributes without context, with the execution level and
+/// synchronization scope inherited from the parent object
+pub fn inherited_object_attributes() -> WDF_OBJECT_ATTRIBUTES {
+    let wdf_object_attributes_size: ULONG;
+    // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+    #[allow(clippy::cast_possible_truncation)]
+    {
+        const WDF_OBJECT_ATTRIBUTES_SIZE: usize = core::mem::size_of::<WDF_OBJECT_ATTRIBUTES>();
+        const { assert!(WDF_OBJECT_ATTRIBUTES_SIZE <= ULONG::MAX as usize) }
+        wdf_object_attributes_size = WDF_OBJECT_ATTRIBUTES_SIZE as ULONG;
+    }
+    WDF_OBJECT_ATTRIBUTES {
+        Size: wdf_object_attributes_size,
+        ExecutionLevel: _WDF_EXECUTION_LEVEL::WdfExecutionLevelInheritFromParent,
+        SynchronizationScope: _WDF_SYNCHRONIZATION_SCOPE::WdfSynchronizationScopeInheritFromParent,
+        ..WDF_OBJECT_ATTRIBUTES::default()
+    }
+}
+
+/// Get the context of type `T` of `object`, or `None` if it was not allocated
+/// with [`allocate_context()`] yet
+///
+/// # Safety
+///
+/// `object` must be a valid handle to a framework object, that stays valid for
+/// as long as the returned context is used. `type_info` must describe `T`.
+pub unsafe fn get_context<'a, T>(
+    object: WDFOBJECT,
+    type_info: &'static ContextTypeInfo,
+) -> Option<&'a T> {
+    let context;
+    // SAFETY: The caller guarantees that `object` is valid
+    unsafe {
+        context = call_unsafe_wdf_function_binding!(
+            WdfObjectGetTypedContextWorker,
+            object,
+            &raw const type_info.0,
+        );
+    }
+    // SAFETY: The framework returns either null, if `object` has no context of this
+    // type, or its context, which the caller guarantees to be a valid `T` that
+    // outlives `'a`
+    unsafe { context.cast::<T>().as_ref() }
+}
+
+/// Move `value` into the context space of `object`, so that it can be
+/// retrieved with [`value_context()`], and return a reference to it. The value
+/// is dropped when the framework deletes `object`. An object holds a single
+/// value.
+///
+/// # Errors
+///
+/// This function will return an error if the value could not be stored.
+/// `value` is dropped in that case:
+/// * `STATUS_OBJECT_NAME_COLLISION` if `object` already holds a value, or its
+///   context space was allocated for a value of another type than `T`
+/// * `STATUS_INSUFFICIENT_RESOURCES` if the context space could not be
+///   allocated
+///
+/// # Safety
+///
+/// `object` must be a valid handle to a framework object, that stays valid for
+/// as long as the returned value is used.
+pub unsafe fn set_value_context<'a, T: Send + Sync + 'static>(
+    object: WDFOBJECT,
+    value: T,
+) -> Result<&'a T, NTSTATUS> {
+    // SAFETY: The caller guarantees that `object` is valid
+    let header = unsafe { allocate_value_context_header::<T>(object) }?;
+    if header
+        .state
+        .compare_exchange(
+            VALUE_EMPTY,
+            VALUE_INITIALIZING,
+            Ordering::Acquire,
+            Ordering::Relaxed,
+        )
+        .is_err()
+    {
+        return Err(STATUS_OBJECT_NAME_COLLISION);
+    }
+    // SAFETY: The state was switched from empty to initializing above, and the
+    // context space was allocated for a `ValueContext<T>`, as checked by
+    // `allocate_value_context_header`
+    Ok(unsafe { initialize_value(header, value) })
+}
+
+/// Get the value of `object` stored with [`set_value_context()`], or move the
+/// value returned by `init` into the context space of `object` if it holds
+/// none yet. `init` is invoked at most once, and only if `object` holds no
+/// value, so that concurrent callers all get the same value. A caller racing
+/// with another one that is initializing the value waits for it.
+///
+/// # Errors
+///
+/// This function will return an error if the value could not be stored:
+/// * `STATUS_OBJECT_NAME_COLLISION` if the context space of `object` was
+///   allocated for a value of another type than `T`
+/// * `STATUS_INSUFFICIENT_RESOURCES` if the context space could not be
+///   allocated
+///
+/// # Safety
+///
+/// `object` must be a valid handle to a framework object, that stays valid for
+/// as long as the returned value is used.
+pub unsafe fn value_context_or_init<'a, T: Send + Sync + 'static>(
+    object: WDFOBJECT,
+    init: impl FnOnce() -> T,
+) -> Result<&'a T, NTSTATUS> {
+    // SAFETY: The caller guarantees that `object` is valid
+    let header = unsafe { allocate_value_context_header::<T>(object) }?;
+    loop {
+        match header.state.compare_exchange_weak(
+            VALUE_EMPTY,
+            VALUE_INITIALIZING,
+            Ordering::Acquire,
+            Ordering::Acquire,
+        ) {
+            // SAFETY: The state was switched from empty to initializing, and the
+            // context space was allocated for a `ValueContext<T>`, as checked by
+            // `allocate_value_context_header`
+            Ok(_) => return Ok(unsafe { initialize_value(header, init()) }),
+            Err(VALUE_READY) => break,
+            Err(_) => core::hint::spin_loop(),
+        }
+    }
+    // SAFETY: The context space was allocated for a `ValueContext<T>`, as checked
+    // by `allocate_value_context_header`
+    let value_ptr = unsafe { ValueContext::<T>::value_ptr(header) };
+    // SAFETY: The value was initialized by another caller, and is only dropped
+    // when `object` is deleted
+    Ok(unsafe { &*value_ptr })
+}
+
+/// Get object attributes that allocate the context space of a value of type
+/// `T` when the object is created. The value must then be stored with
+/// [`initialize_value_context()`] before the object is used. The value is
+/// dropped when the framework deletes the object.
+pub fn value_context_attributes<T: Send + Sync + 'static>() -> WDF_OBJECT_ATTRIBUTES {
+    const {
+        assert!(
+            core::mem::align_of::<ValueContext<T>>() <= VALUE_CONTEXT_MAX_ALIGN,
+            "the alignment of the context exceeds the alignment of framework context spaces"
+        );
+    }
+    let mut attributes = object_attributes(&VALUE_CONTEXT_HEADER_TYPE_INFO);
+    attributes.ContextSizeOverride = ValueLayout::of::<T>().size;
+    attributes.EvtDestroyCallback = Some(evt_value_context_destroy::<T>);
+    attributes
+}
+
+/// Move `value` into the context space that was allocated for it when
+/// `object` was created with [`value_context_attributes()`], and return a
+/// reference to it
+///
+/// # Errors
+///
+/// This function will return `STATUS_INSUFFICIENT_RESOURCES` if `object` has
+/// no value context space. `value` is dropped in that case.
+///
+/// # Safety
+///
+/// `object` must be a valid handle to a framework object, that was just
+/// created with the attributes returned by [`value_context_attributes()`] for
+/// `T`, and is not used by anything else yet. It must stay valid for as long
+/// as the returned value is used.
+pub unsafe fn initialize_value_context<'a, T: Send + Sync + 'static>(
+    object: WDFOBJECT,
+    value: T,
+) -> Result<&'a T, NTSTATUS> {
+    // SAFETY: The caller guarantees that `object` is valid.
+    // `VALUE_CONTEXT_HEADER_TYPE_INFO` describes `ValueContextHeader`.
+    let header: &ValueContextHeader =
+        unsafe { get_context(object, &VALUE_CONTEXT_HEADER_TYPE_INFO) }
+            .ok_or(STATUS_INSUFFICIENT_RESOURCES)?;
+    header.layout.store(
+        core::ptr::from_ref(ValueLayout::of::<T>()).cast_mut(),
+        Ordering::Release,
+    );
+    header.state.store(VALUE_INITIALIZING, Ordering::Relaxed);
+    // SAFETY: The caller guarantees that the context space was allocated for a
+    // `ValueContext<T>` and that nothing else uses it, so the state can be switched
+    // to initializing without a race
+    Ok(unsafe { initialize_value(header, value) })
+}
+
+/// Get the header of the value context space of `object`, allocating it with
+/// the size of `ValueContext<T>` on first use. The layout of the context space
+/// is recorded in the header by the caller that allocates it, so that callers
+/// for another type than `T` get an error before they write to it.
+///
+/// # Errors
+///
+/// This function will return an error if the header could not be allocated:
+/// * `STATUS_OBJECT_NAME_COLLISION` if the context space was allocated for a
+///   value of another type than `T`
+/// * `STATUS_INSUFFICIENT_RESOURCES` if the context space could not be
+///   allocated
+///
+/// # Safety
+///
+/// `object` must be a valid handle to a framework object, that stays valid for
+/// as long as the returned header is used.
+unsafe fn allocate_value_context_header<'a, T: Send + Sync + 'static>(
+    object: WDFOBJECT,
+) -> Result<&'a ValueContextHeader, NTSTATUS> {
+    let mut attributes = value_context_attributes::<T>();
+    let mut context = core::ptr::null_mut();
+
+    let nt_status;
+    // SAFETY: The caller guarantees that `object` is valid. `attributes` describes
+    // a context space starting with a `ValueContextHeader`, which is valid when
+    // all-zero, i.e. empty, and outlives the call.
+    unsafe {
+        nt_status = call_unsafe_wdf_function_binding!(
+            WdfObjectAllocateContext,
+            object,
+            &raw mut attributes,
+            &raw mut context,
+        );
+    }
+    if !nt_success(nt_status) {
+        return Err(nt_status);
+    }
+    // SAFETY: On success, the framework returns the context space of `object`,
+    // which starts with a `ValueContextHeader` and outlives `'a`
+    let header = unsafe { context.cast::<ValueContextHeader>().as_ref() }
+        .ok_or(STATUS_INSUFFICIENT_RESOURCES)?;
+
+    if nt_status == STATUS_SUCCESS {
+        // The framework allocated the context space for this call, with the size of
+        // `ValueContext<T>`
+        header.layout.store(
+            core::ptr::from_ref(ValueLayout::of::<T>()).cast_mut(),
+            Ordering::Release,
+        );
+        return Ok(header);
+    }
+    // The framework returned the context space another caller allocated, which
+    // records its layout right after allocating it
+    let recorded = loop {
+        let recorded = header.layout.load(Ordering::Acquire);
+        if !recorded.is_null() {
+            break recorded;
+        }
+        core::hint::spin_loop();
+    };
+    // SAFETY: The header only records pointers to the static layouts returned by
+    // `ValueLayout::of`
+    if !unsafe { &*recorded }.is_of::<T>() {
+        return Err(STATUS_OBJECT_NAME_COLLISION);
+    }
+    Ok(header)
+}
+
+/// Move `value` into the context space starting with `header`, and mark it
+/// ready
+///
+/// # Safety
+///
+/// The caller must have switched the state of `header` from empty to
+/// initializing, and the context space must have been allocated with the size
+/// of `ValueContext<T>`.
+unsafe fn initialize_value<'a, T: 'static>(header: &'a ValueContextHeader, value: T) -> &'a T {
+    // SAFETY: The caller guarantees that the context space holds a
+    // `ValueContext<T>`, which is aligned for it as checked by
+    // `value_context_attributes`
+    let value_ptr = unsafe { ValueContext::<T>::value_ptr(header) };
+    // SAFETY: The state is initializing, so no other thread accesses the value
+    unsafe {
+        value_ptr.write(value);
+    }
+    header.state.store(VALUE_READY, Ordering::Release);
+    // SAFETY: The value was initialized above, and is only dropped when the object
+    // is deleted
+    unsafe { &*value_ptr }
+}
+
+/// Get the value of `object` stored with [`set_value_context()`], or `None` if
+/// `object` holds no value of type `T`
+///
+/// # Safety
+///
+/// `object` must be a valid handle to a framework object, that stays valid for
+/// as long as the returned value is used.
+pub unsafe fn value_context<'a, T: Send + Sync + 'static>(object: WDFOBJECT) -> Option<&'a T> {
+    // SAFETY: The caller guarantees that `object` is valid.
+    // `VALUE_CONTEXT_HEADER_TYPE_INFO` describes `ValueContextHeader`.
+    let header: &ValueContextHeader =
+        unsafe { get_context(object, &VALUE_CONTEXT_HEADER_TYPE_INFO) }?;
+    if !header.holds::<T>() {
+        return None;
+    }
+    // SAFETY: The context space holds a `ValueContext<T>`, as checked above
+    let value_ptr = unsafe { ValueContext::<T>::value_ptr(header) };
+    // SAFETY: The value was initialized by `set_value_context`, and is only dropped
+    // when `object` is deleted
+    Some(unsafe { &*value_ptr })
+}
+
+/// Size and type of the value a value context space was allocated for
+struct ValueLayout {
+    /// Size of the context space, i.e. of `ValueContext<T>`
+    size: usize,
+    type_id: fn() -> TypeId,
+}
+impl ValueLayout {
+    /// Get the layout of the context space of a value of type `T`
+    fn of<T: 'static>() -> &'static Self {
+        const {
+            &Self {
+                size: core::mem::size_of::<ValueContext<T>>(),
+                type_id: TypeId::of::<T>,
+            }
+        }
+    }
+
+    /// Check whether this is the layout of the context space of a value of
+    /// type `T`
+    fn is_of<T: 'static>(&self) -> bool {
+        self.size == core::mem::size_of::<ValueContext<T>>()
+            && (self.type_id)() == TypeId::of::<T>()
+    }
+}
+
+/// State of the value stored with [`set_value_context()`], and layout of the
+/// context space, at its start
+#[repr(C)]
+struct ValueContextHeader {
+    state: AtomicU8,
+    /// Layout the context space was allocated with, or null until the caller
+    /// that allocated it records it
+    layout: AtomicPtr<ValueLayout>,
+}
+impl ValueContextHeader {
+    /// Check whether the context space holds an initialized `T`
+    fn holds<T: 'static>(&self) -> bool {
+        if self.state.load(Ordering::Acquire) != VALUE_READY {
+            return false;
+        }
+        let layout = self.layout.load(Ordering::Acquire);
+        // SAFETY: The state is ready, so the layout was recorded, and the header only
+        // records pointers to the static layouts returned by `ValueLayout::of`
+        unsafe { layout.as_ref() }.is_some_and(ValueLayout::is_of::<T>)
+    }
+}
+
+/// Context space of an object holding a value stored with
+/// [`set_value_context()`]
+#[repr(C)]
+struct ValueContext<T> {
+    header: ValueContextHeader,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+impl<T> ValueContext<T> {
+    /// Get a pointer to the value of the context space starting with `header`
+    ///
+    /// # Safety
+    ///
+    /// The context space must have been allocated with the size of
+    /// `ValueContext<T>`, and be aligned for it
+    unsafe fn value_ptr(header: &ValueContextHeader) -> *mut T {
+        let inner = NonNull::from(header).cast::<Self>();
+        // SAFETY: The caller guarantees that the context space holds a
+        // `ValueContext<T>`, so the field is in bounds
+        let value = unsafe { &raw const (*inner.as_ptr()).value };
+        UnsafeCell::raw_get(value).cast()
+    }
+}
+
+declare_context_type!(VALUE_CONTEXT_HEADER_TYPE_INFO, ValueContextHeader);
+
+/// `EvtDestroyCallback` of the context space of an object, that drops the
+/// value stored with [`set_value_context()`]
+unsafe extern "C" fn evt_value_context_destroy<T: 'static>(wdf_object: WDFOBJECT) {
+    // SAFETY: The framework passes the object the context space belongs to, which
+    // is valid until the callback returns. `VALUE_CONTEXT_HEADER_TYPE_INFO`
+    // describes `ValueContextHeader`.
+    let Some(header) =
+        (unsafe { get_context::<ValueContextHeader>(wdf_object, &VALUE_CONTEXT_HEADER_TYPE_INFO) })
+    else {
+        return;
+    };
+    if !header.holds::<T>() {
+        return;
+    }
+    // SAFETY: The context space holds a `ValueContext<T>`, as checked above
+    let value_ptr = unsafe { ValueContext::<T>::value_ptr(header) };
+    // SAFETY: The value was initialized by `set_value_context`. No callback of the
+    // object runs anymore, so it is dropped only here.
+    unsafe {
+        value_ptr.drop_in_place();
+    }
+}
@@ -0,0 +1,324 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{
+    ffi::c_void,
+    sync::atomic::{AtomicPtr, AtomicU32, AtomicUsize, Ordering},
+};
+
+#[cfg(driver_model__driver_type = "KMDF")]
+use wdk_sys::{GUID, WDFDEVICE_INIT};
+use wdk_sys::{
+    NTSTATUS,
+    STATUS_INSUFFICIENT_RESOURCES,
+    STATUS_OBJECT_NAME_COLLISION,
+    WDFDEVICE,
+    WDFQUEUE,
+    call_unsafe_wdf_function_binding,
+};
+
+#[cfg(driver_model__driver_type = "KMDF")]
+use crate::{DeviceClassGuid, wdf::Request};
+use crate::{
+    PassiveLevel,
+    nt_success,
+    string::UnicodeStr,
+    wdf::{
+        IoQueue,
+        context::{
+            VALUE_CONTEXT_MAX_ALIGN,
+            allocate_context,
+            declare_context_type,
+            get_context,
+            set_value_context,
+            value_context,
+            value_context_or_init,
+        },
+    },
+};
+
+/// Maximum number of queues that can be registered with a [`Device`] through
+/// [`Device::register_queue()`]
+pub const MAX_REGISTERED_QUEUES: usize = 16;
+
+/// Largest alignment of the contexts a [`Device`] can hold. The framework
+/// aligns the context space of its objects to at least this.
+pub const DEVICE_CONTEXT_MAX_ALIGN: usize = VALUE_CONTEXT_MAX_ALIGN;
+
+/// WDF Device.
+///
+/// A handle to a framework device object, ex. the one created by
+/// `WdfDeviceCreate` in `EvtDriverDeviceAdd`. The device object is owned by
+/// the framework, which deletes it when the device is removed.
+///
+/// Besides its default queue, which is returned by
+/// [`Device::get_default_queue()`], a device can have any number of other
+/// queues. Drivers that route requests between queues register them with a tag
+/// of their choice through [`Device::register_queue()`], and look them up later
+/// with [`Device::queue_by_tag()`].
+///
+/// A driver serves every device it is loaded for with the same callbacks, so
+/// the state of each device is kept with the device rather than in statics:
+/// [`Device::set_context()`] moves it into the context space of the device
+/// object in `EvtDriverDeviceAdd`, and the callbacks of the queues and timers
+/// of the device get it back with [`Device::context()`].
+#[derive(Clone, Copy)]
+pub struct Device {
+    wdf_device: WDFDEVICE,
+}
+impl Device {
+    /// Wrap a handle to a framework device object
+    ///
+    /// # Safety
+    ///
+    /// `device` must be a valid handle to a framework device object, that stays
+    /// valid for as long as the returned [`Device`] is used.
+    #[must_use]
+    pub const unsafe fn from_raw(device: WDFDEVICE) -> Self {
+        Self { wdf_device: device }
+    }
+
+    /// Get the raw handle of the framework device object
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFDEVICE {
+        self.wdf_device
+    }
+
+    /// Create a symbolic link to the device, ex. `\DosDevices\MyDevice`, so
+    /// that applications can open the device by that name. The framework
+    /// deletes the symbolic link when the device is removed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the symbolic link could not be
+    /// created, ex. `STATUS_OBJECT_NAME_COLLISION` if `symbolic_link_name` is
+    /// already in use.
+    pub fn create_symbolic_link(
+        &self,
+        symbolic_link_name: UnicodeStr<'_>,
+        _irql: PassiveLevel,
+    ) -> Result<(), NTSTATUS> {
+        let symbolic_link_name = symbolic_link_name.as_raw();
+
+        let nt_status;
+        // SAFETY: `wdf_device` is a private member of `Device`, which the caller of
+        // `from_raw` guaranteed to be valid. The framework copies the name, which
+        // outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfDeviceCreateSymbolicLink,
+                self.wdf_device,
+                &raw const symbolic_link_name,
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Add `request` to the I/O queues of the device, so that it is delivered
+    /// to the queue that handles its type, as if it had not gone through
+    /// [`IoInCallerContextCallback::in_caller_context()`](crate::wdf::IoInCallerContextCallback::in_caller_context).
+    /// The driver no longer owns the request once it is enqueued, so it is
+    /// consumed. Must be called from
+    /// [`IoInCallerContextCallback::in_caller_context()`](crate::wdf::IoInCallerContextCallback::in_caller_context),
+    /// at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request could not be enqueued,
+    /// ex. `STATUS_WDF_BUSY` if the device is not accepting requests. The
+    /// driver still owns the request in that case, so it is returned along
+    /// with the error.
+    #[cfg(driver_model__driver_type = "KMDF")]
+    pub fn enqueue_request(&self, request: Request) -> Result<(), (Request, NTSTATUS)> {
+        let nt_status;
+        // SAFETY: `wdf_device` is a private member of `Device`, which the caller of
+        // `from_raw` guaranteed to be valid. The caller of `Request::from_raw`
+        // guaranteed that the request is valid and not completed yet.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfDeviceEnqueueRequest,
+                self.wdf_device,
+                request.as_raw(),
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err((request, nt_status));
+        }
+        Ok(())
+    }
+
+    /// Get the default queue of the device, i.e. the queue created with
+    /// `DefaultQueue` set in its [`wdk_sys::WDF_IO_QUEUE_CONFIG`]. Returns
+    /// `None` if the device has no default queue.
+    #[must_use]
+    pub fn get_default_queue(&self) -> Option<IoQueue> {
+        let wdf_queue: WDFQUEUE;
+        // SAFETY: `wdf_device` is a private member of `Device`, which the caller of
+        // `from_raw` guaranteed to be valid.
+        unsafe {
+            wdf_queue =
+                call_unsafe_wdf_function_binding!(WdfDeviceGetDefaultQueue, self.wdf_device);
+        }
+        // SAFETY: The framework returned a handle to the default queue of the device,
+        // which is valid for as long as the device is.
+        (!wdf_queue.is_null()).then(|| unsafe { IoQueue::from_raw(wdf_queue) })
+    }
+
+    /// Register `queue` with the device under `tag`, so that it can be looked
+    /// up with [`Device::queue_by_tag()`]. The tag is chosen by the driver, ex.
+    /// the I/O control code the queue handles. `queue` must belong to the
+    /// device. Up to [`MAX_REGISTERED_QUEUES`] queues can be registered.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the queue could not be registered:
+    /// * `STATUS_OBJECT_NAME_COLLISION` if a queue is already registered under
+    ///   `tag`
+    /// * `STATUS_INSUFFICIENT_RESOURCES` if [`MAX_REGISTERED_QUEUES`] queues
+    ///   are already registered, or the registry could not be allocated
+    pub fn register_queue(&self, tag: u32, queue: &IoQueue) -> Result<(), NTSTATUS> {
+        let registry = self.allocate_queue_registry()?;
+        if registry.find(tag).is_some() {
+            return Err(STATUS_OBJECT_NAME_COLLISION);
+        }
+        registry
+            .insert(tag, queue.as_raw())
+            .then_some(())
+            .ok_or(STATUS_INSUFFICIENT_RESOURCES)
+    }
+
+    /// Look up the queue registered with the device under `tag` through
+    /// [`Device::register_queue()`]. Returns `None` if no queue is registered
+    /// under `tag`.
+    #[must_use]
+    pub fn queue_by_tag(&self, tag: u32) -> Option<IoQueue> {
+        // SAFETY: `wdf_device` is a private member of `Device`, which the caller of
+        // `from_raw` guaranteed to be valid. `QUEUE_REGISTRY_TYPE_INFO` describes
+        // `QueueRegistry`.
+        let registry = unsafe {
+            get_context::<QueueRegistry>(self.wdf_device.cast(), &QUEUE_REGISTRY_TYPE_INFO)
+        }?;
+        // SAFETY: Registered queues belong to the device, so they are valid for as
+        // long as the device is.
+        registry
+            .find(tag)
+            .map(|wdf_queue| unsafe { IoQueue::from_raw(wdf_queue) })
+    }
+
+    /// Move `context` into the context space of the device, so that it can be
+    /// retrieved with [`Device::context()`] by the callbacks of the device, ex.
+    /// of its queues and timers, and return a reference to it. The context is
+    /// dropped when the framework deletes the device object, after the
+    /// callbacks of the children of the device returned. A device holds a
+    /// single context. Must be called at `IRQL` <= `DISPATCH_LEVEL`,
+    /// typically from `EvtDriverDeviceAdd`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the context could not be set.
+    /// `context` is dropped in that case:
+    /// * `STATUS_OBJECT_NAME_COLLISION` if the device already holds a context
+    /// * `STATUS_INSUFFICIENT_RESOURCES` if the context space could not be
+    ///   allocated
+    pub fn set_context<T: Send + Sync + 'static>(&self, context: T) -> Result<&T, NTSTATUS> {
+        // SAFETY: `wdf_device` is a private member of `Device`, which the caller of
+        // `from_raw` guaranteed to be valid
+        unsafe { set_value_context(self.wdf_device.cast(), context) }
+    }
+
+    /// Get the context of the device set with [`Device::set_context()`], or
+    /// `None` if the device holds no context of type `T`
+    #[must_use]
+    pub fn context<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        // SAFETY: `wdf_device` is a private member of `Device`, which the caller of
+        // `from_raw` guaranteed to be valid
+        unsafe { value_context(self.wdf_device.cast()) }
+    }
+
+    /// Get the context of the device set with [`Device::set_context()`], or
+    /// move the context returned by `init` into the device if it holds none
+    /// yet. `init` is invoked at most once, so that concurrent callers all get
+    /// the same context.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the context could not be set:
+    /// * `STATUS_OBJECT_NAME_COLLISION` if the device already holds a context
+    ///   of another type than `T`
+    /// * `STATUS_INSUFFICIENT_RESOURCES` if the context space could not be
+    ///   allocated
+    pub fn context_or_init<T: Send + Sync + 'static>(
+        &self,
+        init: impl FnOnce() -> T,
+    ) -> Result<&T, NTSTATUS> {
+        // SAFETY: `wdf_device` is a private member of `Device`, which the caller of
+        // `from_raw` guaranteed to be valid
+        unsafe { value_context_or_init(self.wdf_device.cast(), init) }
+    }
+
+    // Returns the queue registry of the device, allocating it on first use
+    fn allocate_queue_registry(&self) -> Result<&QueueRegistry, NTSTATUS> {
+        // SAFETY: `wdf_device` is a private member of `Device`, which the caller of
+        // `from_raw` guaranteed to be valid. `QUEUE_REGISTRY_TYPE_INFO` describes
+        // `QueueRegistry`, which is valid when all-zero, i.e. empty.
+        unsafe { allocate_context(self.wdf_device.cast(), &QUEUE_REGISTRY_TYPE_INFO) }
+    }
+}
+
+/// Set the device setup class of the device that is created with
+/// `device_init`, which overrides the class of its INF file, ex. for a
+/// device that a bus driver reports in raw mode. Must be called before the
+/// device is created with `WdfDeviceCreate`, at `IRQL` = `PASSIVE_LEVEL`.
+///
+/// # Safety
+///
+/// `device_init` must point to a valid `WDFDEVICE_INIT` structure, that was
+/// not passed to `WdfDeviceCreate` yet.
+#[cfg(driver_model__driver_type = "KMDF")]
+pub unsafe fn set_device_class(device_init: *mut WDFDEVICE_INIT, device_class: DeviceClassGuid) {
+    let device_class = GUID::from(device_class);
+    // SAFETY: The caller guarantees that `device_init` is valid. The framework
+    // copies the class, which outlives the call.
+    unsafe {
+        call_unsafe_wdf_function_binding!(
+            WdfDeviceInitSetDeviceClass,
+            device_init,
+            &raw const device_class,
+        );
+    }
+}
+
+/// Queues registered with a device, stored in a context of the device object.
+///
+/// Entries are only ever added. A slot is claimed by bumping `len`, and its
+/// queue handle is published after its tag, so readers skip the slots that are
+/// claimed but not published yet.
+#[repr(C)]
+struct QueueRegistry {
+    len: AtomicUsize,
+    tags: [AtomicU32; MAX_REGISTERED_QUEUES],
+    queues: [AtomicPtr<c_void>; MAX_REGISTERED_QUEUES],
+}
+impl QueueRegistry {
+    fn insert(&self, tag: u32, wdf_queue: WDFQUEUE) -> bool {
+        let index = self.len.fetch_add(1, Ordering::Relaxed);
+        if index >= MAX_REGISTERED_QUEUES {
+            self.len.fetch_sub(1, Ordering::Relaxed);
+            return false;
+        }
+        self.tags[index].store(tag, Ordering::Relaxed);
+        self.queues[index].store(wdf_queue.cast(), Ordering::Release);
+        true
+    }
+
+    fn find(&self, tag: u32) -> Option<WDFQUEUE> {
+        let len = self.len.load(Ordering::Relaxed).min(MAX_REGISTERED_QUEUES);
+        (0..len).find_map(|index| {
+            let wdf_queue = self.queues[index].load(Ordering::Acquire);
+            (!wdf_queue.is_null() && self.tags[index].load(Ordering::Relaxed) == tag)
+                .then_some(wdf_queue.cast())
+        })
+    }
+}
+
+declare_context_type!(QUEUE_REGISTRY_TYPE_INFO, QueueRegistry);
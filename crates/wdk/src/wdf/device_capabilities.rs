@@ -0,0 +1,486 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use wdk_sys::{
+    _DEVICE_POWER_STATE,
+    _SYSTEM_POWER_STATE,
+    _WDF_TRI_STATE,
+    DEVICE_POWER_STATE,
+    SYSTEM_POWER_STATE,
+    ULONG,
+    WDF_DEVICE_PNP_CAPABILITIES,
+    WDF_DEVICE_POWER_CAPABILITIES,
+    WDF_DEVICE_STATE,
+    call_unsafe_wdf_function_binding,
+};
+
+use super::power_policy::to_tri_state;
+use crate::wdf::Device;
+
+/// Device power state, from fully on (`D0`) to off (`D3`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DevicePowerState {
+    /// `PowerDeviceD0`, the working state
+    D0,
+    /// `PowerDeviceD1`
+    D1,
+    /// `PowerDeviceD2`
+    D2,
+    /// `PowerDeviceD3`, the device is off
+    D3,
+}
+
+impl From<DevicePowerState> for DEVICE_POWER_STATE {
+    fn from(state: DevicePowerState) -> Self {
+        match state {
+            DevicePowerState::D0 => _DEVICE_POWER_STATE::PowerDeviceD0,
+            DevicePowerState::D1 => _DEVICE_POWER_STATE::PowerDeviceD1,
+            DevicePowerState::D2 => _DEVICE_POWER_STATE::PowerDeviceD2,
+            DevicePowerState::D3 => _DEVICE_POWER_STATE::PowerDeviceD3,
+        }
+    }
+}
+
+/// System power state, from working (`S0`) to off (`S5`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SystemPowerState {
+    /// `PowerSystemWorking`
+    S0,
+    /// `PowerSystemSleeping1`
+    S1,
+    /// `PowerSystemSleeping2`
+    S2,
+    /// `PowerSystemSleeping3`
+    S3,
+    /// `PowerSystemHibernate`
+    S4,
+    /// `PowerSystemShutdown`
+    S5,
+}
+
+impl From<SystemPowerState> for SYSTEM_POWER_STATE {
+    fn from(state: SystemPowerState) -> Self {
+        match state {
+            SystemPowerState::S0 => _SYSTEM_POWER_STATE::PowerSystemWorking,
+            SystemPowerState::S1 => _SYSTEM_POWER_STATE::PowerSystemSleeping1,
+            SystemPowerState::S2 => _SYSTEM_POWER_STATE::PowerSystemSleeping2,
+            SystemPowerState::S3 => _SYSTEM_POWER_STATE::PowerSystemSleeping3,
+            SystemPowerState::S4 => _SYSTEM_POWER_STATE::PowerSystemHibernate,
+            SystemPowerState::S5 => _SYSTEM_POWER_STATE::PowerSystemShutdown,
+        }
+    }
+}
+
+/// Plug and play state of a device, as reported to the plug and play manager.
+///
+/// Built with [`DeviceState::new()`] and the builder methods, and applied with
+/// [`Device::set_device_state()`]. Unset states keep their current values.
+#[derive(Clone, Copy)]
+pub struct DeviceState {
+    state: WDF_DEVICE_STATE,
+}
+impl DeviceState {
+    /// Construct a device state that leaves every state unchanged
+    #[must_use]
+    pub fn new() -> Self {
+        let state_size: ULONG;
+
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            const STATE_SIZE: usize = core::mem::size_of::<WDF_DEVICE_STATE>();
+
+            // Manually assert there is not truncation since clippy doesn't work for
+            // compile-time constants
+            const { assert!(STATE_SIZE <= ULONG::MAX as usize) }
+
+            state_size = STATE_SIZE as ULONG;
+        }
+
+        Self {
+            state: WDF_DEVICE_STATE {
+                Size: state_size,
+                Disabled: _WDF_TRI_STATE::WdfUseDefault,
+                DontDisplayInUI: _WDF_TRI_STATE::WdfUseDefault,
+                Failed: _WDF_TRI_STATE::WdfUseDefault,
+                NotDisableable: _WDF_TRI_STATE::WdfUseDefault,
+                Removed: _WDF_TRI_STATE::WdfUseDefault,
+                ResourcesChanged: _WDF_TRI_STATE::WdfUseDefault,
+            },
+        }
+    }
+
+    /// Report the device as disabled
+    #[must_use]
+    pub const fn disabled(mut self, disabled: bool) -> Self {
+        self.state.Disabled = to_tri_state(disabled);
+        self
+    }
+
+    /// Hide the device in Device Manager
+    #[must_use]
+    pub const fn dont_display_in_ui(mut self, dont_display_in_ui: bool) -> Self {
+        self.state.DontDisplayInUI = to_tri_state(dont_display_in_ui);
+        self
+    }
+
+    /// Report the device as failed, so that the plug and play manager stops
+    /// and removes it
+    #[must_use]
+    pub const fn failed(mut self, failed: bool) -> Self {
+        self.state.Failed = to_tri_state(failed);
+        self
+    }
+
+    /// Prevent users from disabling the device in Device Manager
+    #[must_use]
+    pub const fn not_disableable(mut self, not_disableable: bool) -> Self {
+        self.state.NotDisableable = to_tri_state(not_disableable);
+        self
+    }
+
+    /// Report the device as removed, ex. when the driver detects that the
+    /// hardware is gone
+    #[must_use]
+    pub const fn removed(mut self, removed: bool) -> Self {
+        self.state.Removed = to_tri_state(removed);
+        self
+    }
+
+    /// Ask the plug and play manager to stop the device and assign it new
+    /// hardware resources
+    #[must_use]
+    pub const fn resources_changed(mut self, resources_changed: bool) -> Self {
+        self.state.ResourcesChanged = to_tri_state(resources_changed);
+        self
+    }
+}
+
+impl Default for DeviceState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Plug and play capabilities of a device, ex. whether it can be removed
+/// without warning.
+///
+/// Built with [`PnpCapabilities::new()`] and the builder methods, and applied
+/// with [`Device::set_pnp_capabilities()`], usually in `EvtDriverDeviceAdd`.
+/// Unset capabilities keep the values reported by the bus driver.
+#[derive(Clone, Copy)]
+pub struct PnpCapabilities {
+    capabilities: WDF_DEVICE_PNP_CAPABILITIES,
+}
+impl PnpCapabilities {
+    /// Construct plug and play capabilities that leave every capability
+    /// unchanged
+    #[must_use]
+    pub fn new() -> Self {
+        let capabilities_size: ULONG;
+
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            const CAPABILITIES_SIZE: usize = core::mem::size_of::<WDF_DEVICE_PNP_CAPABILITIES>();
+
+            // Manually assert there is not truncation since clippy doesn't work for
+            // compile-time constants
+            const { assert!(CAPABILITIES_SIZE <= ULONG::MAX as usize) }
+
+            capabilities_size = CAPABILITIES_SIZE as ULONG;
+        }
+
+        Self {
+            capabilities: WDF_DEVICE_PNP_CAPABILITIES {
+                Size: capabilities_size,
+                LockSupported: _WDF_TRI_STATE::WdfUseDefault,
+                EjectSupported: _WDF_TRI_STATE::WdfUseDefault,
+                Removable: _WDF_TRI_STATE::WdfUseDefault,
+                DockDevice: _WDF_TRI_STATE::WdfUseDefault,
+                UniqueID: _WDF_TRI_STATE::WdfUseDefault,
+                SilentInstall: _WDF_TRI_STATE::WdfUseDefault,
+                SurpriseRemovalOK: _WDF_TRI_STATE::WdfUseDefault,
+                HardwareDisabled: _WDF_TRI_STATE::WdfUseDefault,
+                NoDisplayInUI: _WDF_TRI_STATE::WdfUseDefault,
+                Address: ULONG::MAX,
+                UINumber: ULONG::MAX,
+            },
+        }
+    }
+
+    /// Declare that the device can be removed without warning, ex. a USB
+    /// device that is unplugged, so that the system does not warn the user
+    /// about it
+    #[must_use]
+    pub const fn surprise_removal_ok(mut self, surprise_removal_ok: bool) -> Self {
+        self.capabilities.SurpriseRemovalOK = to_tri_state(surprise_removal_ok);
+        self
+    }
+
+    /// Declare that the device can be removed from its parent while the
+    /// system is running, so that it is listed under "Safely Remove Hardware"
+    #[must_use]
+    pub const fn removable(mut self, removable: bool) -> Self {
+        self.capabilities.Removable = to_tri_state(removable);
+        self
+    }
+
+    /// Declare that the device can be ejected by software
+    #[must_use]
+    pub const fn eject_supported(mut self, eject_supported: bool) -> Self {
+        self.capabilities.EjectSupported = to_tri_state(eject_supported);
+        self
+    }
+
+    /// Declare that the device can be locked in its slot to prevent ejection
+    #[must_use]
+    pub const fn lock_supported(mut self, lock_supported: bool) -> Self {
+        self.capabilities.LockSupported = to_tri_state(lock_supported);
+        self
+    }
+
+    /// Declare that the device is a docking station
+    #[must_use]
+    pub const fn dock_device(mut self, dock_device: bool) -> Self {
+        self.capabilities.DockDevice = to_tri_state(dock_device);
+        self
+    }
+
+    /// Declare that the instance ID of the device is unique in the system,
+    /// rather than only on its bus
+    #[must_use]
+    pub const fn unique_id(mut self, unique_id: bool) -> Self {
+        self.capabilities.UniqueID = to_tri_state(unique_id);
+        self
+    }
+
+    /// Suppress the "found new hardware" notifications for the device
+    #[must_use]
+    pub const fn silent_install(mut self, silent_install: bool) -> Self {
+        self.capabilities.SilentInstall = to_tri_state(silent_install);
+        self
+    }
+
+    /// Declare that the hardware of the device is disabled
+    #[must_use]
+    pub const fn hardware_disabled(mut self, hardware_disabled: bool) -> Self {
+        self.capabilities.HardwareDisabled = to_tri_state(hardware_disabled);
+        self
+    }
+
+    /// Hide the device in Device Manager
+    #[must_use]
+    pub const fn no_display_in_ui(mut self, no_display_in_ui: bool) -> Self {
+        self.capabilities.NoDisplayInUI = to_tri_state(no_display_in_ui);
+        self
+    }
+
+    /// Set the address of the device on its bus
+    #[must_use]
+    pub const fn address(mut self, address: ULONG) -> Self {
+        self.capabilities.Address = address;
+        self
+    }
+
+    /// Set the number of the device shown to users, ex. the number of its slot
+    #[must_use]
+    pub const fn ui_number(mut self, ui_number: ULONG) -> Self {
+        self.capabilities.UINumber = ui_number;
+        self
+    }
+}
+
+impl Default for PnpCapabilities {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Power capabilities of a device, ex. the device power states it supports.
+///
+/// Built with [`PowerCapabilities::new()`] and the builder methods, and
+/// applied with [`Device::set_power_capabilities()`], usually in
+/// `EvtDriverDeviceAdd`. Unset capabilities keep the values reported by the
+/// bus driver.
+#[derive(Clone, Copy)]
+pub struct PowerCapabilities {
+    capabilities: WDF_DEVICE_POWER_CAPABILITIES,
+}
+impl PowerCapabilities {
+    /// Construct power capabilities that leave every capability unchanged
+    #[must_use]
+    pub fn new() -> Self {
+        let capabilities_size: ULONG;
+
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            const CAPABILITIES_SIZE: usize = core::mem::size_of::<WDF_DEVICE_POWER_CAPABILITIES>();
+
+            // Manually assert there is not truncation since clippy doesn't work for
+            // compile-time constants
+            const { assert!(CAPABILITIES_SIZE <= ULONG::MAX as usize) }
+
+            capabilities_size = CAPABILITIES_SIZE as ULONG;
+        }
+
+        let mut capabilities = WDF_DEVICE_POWER_CAPABILITIES {
+            Size: capabilities_size,
+            DeviceD1: _WDF_TRI_STATE::WdfUseDefault,
+            DeviceD2: _WDF_TRI_STATE::WdfUseDefault,
+            WakeFromD0: _WDF_TRI_STATE::WdfUseDefault,
+            WakeFromD1: _WDF_TRI_STATE::WdfUseDefault,
+            WakeFromD2: _WDF_TRI_STATE::WdfUseDefault,
+            WakeFromD3: _WDF_TRI_STATE::WdfUseDefault,
+            DeviceWake: _DEVICE_POWER_STATE::PowerDeviceMaximum,
+            SystemWake: _SYSTEM_POWER_STATE::PowerSystemMaximum,
+            D1Latency: ULONG::MAX,
+            D2Latency: ULONG::MAX,
+            D3Latency: ULONG::MAX,
+            IdealDxStateForSx: _DEVICE_POWER_STATE::PowerDeviceMaximum,
+            ..WDF_DEVICE_POWER_CAPABILITIES::default()
+        };
+        capabilities
+            .DeviceState
+            .fill(_DEVICE_POWER_STATE::PowerDeviceMaximum);
+        Self { capabilities }
+    }
+
+    /// Declare whether the device supports the `D1` power state
+    #[must_use]
+    pub const fn device_d1(mut self, supported: bool) -> Self {
+        self.capabilities.DeviceD1 = to_tri_state(supported);
+        self
+    }
+
+    /// Declare whether the device supports the `D2` power state
+    #[must_use]
+    pub const fn device_d2(mut self, supported: bool) -> Self {
+        self.capabilities.DeviceD2 = to_tri_state(supported);
+        self
+    }
+
+    /// Declare whether the device can wake the system from `device_state`
+    #[must_use]
+    pub const fn wake_from(mut self, device_state: DevicePowerState, can_wake: bool) -> Self {
+        let can_wake = to_tri_state(can_wake);
+        match device_state {
+            DevicePowerState::D0 => self.capabilities.WakeFromD0 = can_wake,
+            DevicePowerState::D1 => self.capabilities.WakeFromD1 = can_wake,
+            DevicePowerState::D2 => self.capabilities.WakeFromD2 = can_wake,
+            DevicePowerState::D3 => self.capabilities.WakeFromD3 = can_wake,
+        }
+        self
+    }
+
+    /// Set the device power state the device is in when the system is in
+    /// `system_state`
+    #[must_use]
+    pub fn device_state(
+        mut self,
+        system_state: SystemPowerState,
+        device_state: DevicePowerState,
+    ) -> Self {
+        // `SYSTEM_POWER_STATE` values index `DeviceState`, which has an entry for each
+        // of them
+        #[allow(clippy::cast_sign_loss)]
+        let index = SYSTEM_POWER_STATE::from(system_state) as usize;
+        self.capabilities.DeviceState[index] = device_state.into();
+        self
+    }
+
+    /// Set the lowest device power state the device can wake the system from
+    #[must_use]
+    pub fn device_wake(mut self, device_state: DevicePowerState) -> Self {
+        self.capabilities.DeviceWake = device_state.into();
+        self
+    }
+
+    /// Set the lowest system power state the device can wake the system from
+    #[must_use]
+    pub fn system_wake(mut self, system_state: SystemPowerState) -> Self {
+        self.capabilities.SystemWake = system_state.into();
+        self
+    }
+
+    /// Set the time, in units of 100 microseconds, the device takes to return
+    /// to `D0` from `device_state`. The latency of `D0` cannot be set.
+    #[must_use]
+    pub const fn latency(mut self, device_state: DevicePowerState, latency: ULONG) -> Self {
+        match device_state {
+            DevicePowerState::D0 => {}
+            DevicePowerState::D1 => self.capabilities.D1Latency = latency,
+            DevicePowerState::D2 => self.capabilities.D2Latency = latency,
+            DevicePowerState::D3 => self.capabilities.D3Latency = latency,
+        }
+        self
+    }
+
+    /// Set the lowest device power state the device can enter when the system
+    /// goes to sleep
+    #[must_use]
+    pub fn ideal_dx_state_for_sx(mut self, device_state: DevicePowerState) -> Self {
+        self.capabilities.IdealDxStateForSx = device_state.into();
+        self
+    }
+}
+
+impl Default for PowerCapabilities {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Device {
+    /// Report `state` to the plug and play manager, ex. that the device
+    /// failed. Must be called at `IRQL` = `PASSIVE_LEVEL`.
+    pub fn set_device_state(&self, state: DeviceState) {
+        let mut state = state.state;
+        // SAFETY: The device is valid for as long as `self` is, as guaranteed to
+        // `Device::from_raw`. `state` is a fully initialized state structure that
+        // outlives the call.
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfDeviceSetDeviceState,
+                self.as_raw(),
+                &raw mut state,
+            );
+        }
+    }
+
+    /// Override the plug and play capabilities of the device reported by its
+    /// bus driver with `capabilities`, ex. to declare that it can be removed
+    /// without warning. Usually called in `EvtDriverDeviceAdd`, at `IRQL` =
+    /// `PASSIVE_LEVEL`.
+    pub fn set_pnp_capabilities(&self, capabilities: PnpCapabilities) {
+        let mut capabilities = capabilities.capabilities;
+        // SAFETY: The device is valid for as long as `self` is, as guaranteed to
+        // `Device::from_raw`. `capabilities` is a fully initialized capabilities
+        // structure that outlives the call.
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfDeviceSetPnpCapabilities,
+                self.as_raw(),
+                &raw mut capabilities,
+            );
+        }
+    }
+
+    /// Override the power capabilities of the device reported by its bus
+    /// driver with `capabilities`. Usually called in `EvtDriverDeviceAdd`, at
+    /// `IRQL` = `PASSIVE_LEVEL`.
+    pub fn set_power_capabilities(&self, capabilities: PowerCapabilities) {
+        let mut capabilities = capabilities.capabilities;
+        // SAFETY: The device is valid for as long as `self` is, as guaranteed to
+        // `Device::from_raw`. `capabilities` is a fully initialized capabilities
+        // structure that outlives the call.
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfDeviceSetPowerCapabilities,
+                self.as_raw(),
+                &raw mut capabilities,
+            );
+        }
+    }
+}
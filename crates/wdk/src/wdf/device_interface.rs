@@ -0,0 +1,161 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use wdk_sys::{GUID, NTSTATUS, UNICODE_STRING, call_unsafe_wdf_function_binding};
+
+#[cfg(feature = "alloc")]
+use crate::string::UnicodeString;
+use crate::{
+    InterfaceGuid,
+    nt_success,
+    string::UnicodeStr,
+    wdf::{Device, WdfString},
+};
+
+/// Device interface of a [`Device`], created with
+/// [`Device::create_interface()`].
+///
+/// Applications and other drivers find the device through the interfaces it
+/// exposes, and can register to be notified when an interface arrives, i.e.
+/// is enabled, or is removed, i.e. is disabled. The framework enables the
+/// interfaces of a device when the device starts, and disables them when it is
+/// removed. Drivers can also disable an interface while the device runs, ex.
+/// while the functionality it exposes is unavailable, and enable it again with
+/// [`DeviceInterface::set_state()`].
+#[derive(Clone, Copy)]
+pub struct DeviceInterface<'a> {
+    device: Device,
+    interface_class: InterfaceGuid,
+    reference_string: Option<UnicodeStr<'a>>,
+}
+impl DeviceInterface<'_> {
+    /// Get the device the interface belongs to
+    #[must_use]
+    pub const fn device(&self) -> Device {
+        self.device
+    }
+
+    /// Get the device interface class of the interface
+    #[must_use]
+    pub const fn interface_class(&self) -> InterfaceGuid {
+        self.interface_class
+    }
+
+    /// Enable or disable the interface. Applications and drivers that
+    /// registered for notifications of the interface class are notified of
+    /// the arrival or removal of the interface. Must be called at `IRQL` =
+    /// `PASSIVE_LEVEL`, after the device started.
+    pub fn set_state(&self, enabled: bool) {
+        let interface_class = GUID::from(self.interface_class);
+        let reference_string = self.reference_string.map(|string| string.as_raw());
+
+        // SAFETY: The device is valid for as long as `self` is, as guaranteed to
+        // `Device::from_raw`. `interface_class` and `reference_string` outlive the
+        // call.
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfDeviceSetDeviceInterfaceState,
+                self.device.as_raw(),
+                &raw const interface_class,
+                raw_reference_string(reference_string.as_ref()),
+                u8::from(enabled),
+            );
+        }
+    }
+
+    /// Get the symbolic link name the system generated for the interface, ex.
+    /// to log it. Applications open the device by this name. Must be called at
+    /// `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the name could not be retrieved,
+    /// ex. `STATUS_INVALID_DEVICE_STATE` if the interface is not registered
+    /// yet, which happens when the device starts.
+    #[cfg(feature = "alloc")]
+    pub fn symbolic_link_name(&self) -> Result<UnicodeString, NTSTATUS> {
+        let string = WdfString::try_new()?;
+        self.retrieve_symbolic_link_name(&string)?;
+        string.to_unicode_string()
+    }
+
+    /// Fill `string` with the symbolic link name the system generated for the
+    /// interface, and borrow it, without allocating. Must be called at `IRQL`
+    /// = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the name could not be retrieved,
+    /// ex. `STATUS_INVALID_DEVICE_STATE` if the interface is not registered
+    /// yet, which happens when the device starts.
+    pub fn retrieve_symbolic_link_name<'s>(
+        &self,
+        string: &'s WdfString,
+    ) -> Result<UnicodeStr<'s>, NTSTATUS> {
+        let interface_class = GUID::from(self.interface_class);
+        let reference_string = self.reference_string.map(|string| string.as_raw());
+
+        let nt_status;
+        // SAFETY: The device is valid for as long as `self` is, as guaranteed to
+        // `Device::from_raw`. `interface_class`, `reference_string` and `string`
+        // outlive the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfDeviceRetrieveDeviceInterfaceString,
+                self.device.as_raw(),
+                &raw const interface_class,
+                raw_reference_string(reference_string.as_ref()),
+                string.as_raw(),
+            );
+        }
+        nt_success(nt_status)
+            .then(|| string.as_unicode_str())
+            .ok_or(nt_status)
+    }
+}
+
+impl Device {
+    /// Create a device interface of `interface_class` for the device, with an
+    /// optional `reference_string` that distinguishes several interfaces of
+    /// the same class. The framework enables the interface when the device
+    /// starts. Must be called at `IRQL` = `PASSIVE_LEVEL`, typically from
+    /// `EvtDriverDeviceAdd`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the interface could not be
+    /// created. The error variant will contain a [`NTSTATUS`] of the failure.
+    /// Full error documentation is available in the [WdfDeviceCreateDeviceInterface Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdevicecreatedeviceinterface#return-value)
+    pub fn create_interface<'a>(
+        &self,
+        interface_class: InterfaceGuid,
+        reference_string: Option<UnicodeStr<'a>>,
+    ) -> Result<DeviceInterface<'a>, NTSTATUS> {
+        let interface = DeviceInterface {
+            device: *self,
+            interface_class,
+            reference_string,
+        };
+        let raw_interface_class = GUID::from(interface_class);
+        let raw_reference = reference_string.map(|string| string.as_raw());
+
+        let nt_status;
+        // SAFETY: `wdf_device` is a private member of `Device`, which the caller of
+        // `from_raw` guaranteed to be valid. The framework copies the interface
+        // class and the reference string, which outlive the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfDeviceCreateDeviceInterface,
+                self.as_raw(),
+                &raw const raw_interface_class,
+                raw_reference_string(raw_reference.as_ref()),
+            );
+        }
+        nt_success(nt_status).then_some(interface).ok_or(nt_status)
+    }
+}
+
+/// Get the pointer to pass for an optional reference string
+fn raw_reference_string(reference_string: Option<&UNICODE_STRING>) -> *const UNICODE_STRING {
+    reference_string.map_or(core::ptr::null(), core::ptr::from_ref)
+}
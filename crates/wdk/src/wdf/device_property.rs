@@ -0,0 +1,433 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use alloc::vec::Vec;
+
+use wdk_sys::{
+    DEVPROP_TYPE_BINARY,
+    DEVPROP_TYPE_BOOLEAN,
+    DEVPROP_TYPE_EMPTY,
+    DEVPROP_TYPE_GUID,
+    DEVPROP_TYPE_STRING,
+    DEVPROP_TYPE_STRING_LIST,
+    DEVPROP_TYPE_UINT32,
+    DEVPROPKEY,
+    DEVPROPTYPE,
+    GUID,
+    NTSTATUS,
+    STATUS_BUFFER_TOO_SMALL,
+    STATUS_INVALID_PARAMETER,
+    STATUS_NOT_SUPPORTED,
+    STATUS_OBJECT_NAME_NOT_FOUND,
+    ULONG,
+    WDF_DEVICE_PROPERTY_DATA,
+    call_unsafe_wdf_function_binding,
+};
+
+use super::registry::{split_multi_string, to_unicode_string, trim_string};
+use crate::{Guid, alloc_failure::alloc_failed, nt_success, string::UnicodeString, wdf::Device};
+
+/// `DEVPROP_TRUE`, the value of a `DEVPROP_TYPE_BOOLEAN` property that is set
+const DEVPROP_TRUE: u8 = 0xFF;
+
+/// Key of a device property, i.e. a `DEVPROPKEY`: the GUID of its property
+/// set and its identifier within the set.
+///
+/// The keys of the common properties the system sets are provided as
+/// constants, ex. [`DevicePropertyKey::FRIENDLY_NAME`]. Drivers define the
+/// keys of their custom properties with [`DevicePropertyKey::new()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DevicePropertyKey {
+    fmtid: Guid,
+    pid: u32,
+}
+impl DevicePropertyKey {
+    /// `DEVPKEY_Device_CompatibleIds`, the compatible IDs of the device, a
+    /// list of strings
+    pub const COMPATIBLE_IDS: Self = Self::new(Self::DEVICE_FMTID, 4);
+    /// `DEVPKEY_Device_DeviceDesc`, the description of the device, a string
+    pub const DEVICE_DESC: Self = Self::new(Self::DEVICE_FMTID, 2);
+    /// The GUID of the property set of `DEVPKEY_Device_*` properties
+    const DEVICE_FMTID: Guid = Guid::from_u128(0xA45C_254E_DF1C_4EFD_8020_67D1_46A8_50E0);
+    /// `DEVPKEY_Device_FriendlyName`, the name of the device shown to users, a
+    /// string
+    pub const FRIENDLY_NAME: Self = Self::new(Self::DEVICE_FMTID, 14);
+    /// `DEVPKEY_Device_HardwareIds`, the hardware IDs of the device, a list
+    /// of strings
+    pub const HARDWARE_IDS: Self = Self::new(Self::DEVICE_FMTID, 3);
+    /// `DEVPKEY_Device_LocationInfo`, the location of the device on its bus, a
+    /// string
+    pub const LOCATION_INFO: Self = Self::new(Self::DEVICE_FMTID, 15);
+    /// `DEVPKEY_Device_Manufacturer`, the manufacturer of the device, a string
+    pub const MANUFACTURER: Self = Self::new(Self::DEVICE_FMTID, 13);
+
+    /// Construct the key of the property `pid` of the property set `fmtid`.
+    /// Identifiers below 2 are reserved by the system.
+    #[must_use]
+    pub const fn new(fmtid: Guid, pid: u32) -> Self {
+        Self { fmtid, pid }
+    }
+
+    /// Get the GUID of the property set of the key
+    #[must_use]
+    pub const fn fmtid(&self) -> Guid {
+        self.fmtid
+    }
+
+    /// Get the identifier of the property within its property set
+    #[must_use]
+    pub const fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// Get the `DEVPROPKEY` of the key, to pass to WDF APIs
+    #[must_use]
+    pub fn as_raw(&self) -> DEVPROPKEY {
+        DEVPROPKEY {
+            fmtid: GUID::from(self.fmtid),
+            pid: self.pid,
+        }
+    }
+}
+
+/// Value of a device property, by its `DEVPROP_TYPE_*` type.
+///
+/// Returned by [`Device::query_property()`] and passed to
+/// [`Device::assign_property()`]. Properties of other types cannot be read or
+/// written through these methods.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DevicePropertyValue {
+    /// `DEVPROP_TYPE_STRING`
+    String(UnicodeString),
+    /// `DEVPROP_TYPE_STRING_LIST`, ex. the hardware IDs of the device
+    StringList(Vec<UnicodeString>),
+    /// `DEVPROP_TYPE_UINT32`
+    U32(u32),
+    /// `DEVPROP_TYPE_BOOLEAN`
+    Bool(bool),
+    /// `DEVPROP_TYPE_GUID`
+    Guid(Guid),
+    /// `DEVPROP_TYPE_BINARY`
+    Binary(Vec<u8>),
+}
+impl DevicePropertyValue {
+    /// Decode the data of a property of type `property_type`
+    fn decode(property_type: DEVPROPTYPE, data: &[u8]) -> Result<Self, NTSTATUS> {
+        match property_type {
+            DEVPROP_TYPE_EMPTY => Err(STATUS_OBJECT_NAME_NOT_FOUND),
+            DEVPROP_TYPE_STRING => {
+                let string = utf16_units(data)?;
+                Ok(Self::String(to_unicode_string(trim_string(&string))?))
+            }
+            DEVPROP_TYPE_STRING_LIST => {
+                let strings = utf16_units(data)?;
+                let mut list = Vec::new();
+                for string in split_multi_string(&strings) {
+                    list.try_reserve(1)
+                        .map_err(|_| alloc_failed(core::mem::size_of::<UnicodeString>()))?;
+                    list.push(to_unicode_string(string)?);
+                }
+                Ok(Self::StringList(list))
+            }
+            DEVPROP_TYPE_UINT32 => data
+                .try_into()
+                .map(|bytes| Self::U32(u32::from_le_bytes(bytes)))
+                .map_err(|_| STATUS_INVALID_PARAMETER),
+            DEVPROP_TYPE_BOOLEAN => match data {
+                [value] => Ok(Self::Bool(*value != 0)),
+                _ => Err(STATUS_INVALID_PARAMETER),
+            },
+            DEVPROP_TYPE_GUID => {
+                let [a0, a1, a2, a3, b0, b1, c0, c1, d @ ..]: [u8; 16] =
+                    data.try_into().map_err(|_| STATUS_INVALID_PARAMETER)?;
+                Ok(Self::Guid(Guid::from_fields(
+                    u32::from_le_bytes([a0, a1, a2, a3]),
+                    u16::from_le_bytes([b0, b1]),
+                    u16::from_le_bytes([c0, c1]),
+                    d,
+                )))
+            }
+            DEVPROP_TYPE_BINARY => {
+                let mut binary = Vec::new();
+                binary
+                    .try_reserve_exact(data.len())
+                    .map_err(|_| alloc_failed(data.len()))?;
+                binary.extend_from_slice(data);
+                Ok(Self::Binary(binary))
+            }
+            _ => Err(STATUS_NOT_SUPPORTED),
+        }
+    }
+
+    /// Encode the value as the data of a property, along with its type
+    fn encode(&self) -> Result<(DEVPROPTYPE, Vec<u8>), NTSTATUS> {
+        let mut data = Vec::new();
+        let reserve = |data: &mut Vec<u8>, length: usize| {
+            data.try_reserve_exact(length)
+                .map_err(|_| alloc_failed(length))
+        };
+        let property_type = match self {
+            Self::String(string) => {
+                let string = string.as_unicode_str();
+                reserve(&mut data, (string.len() + 1) * core::mem::size_of::<u16>())?;
+                extend_utf16(&mut data, string.as_slice());
+                DEVPROP_TYPE_STRING
+            }
+            Self::StringList(list) => {
+                let length = list
+                    .iter()
+                    .map(|string| string.as_unicode_str().len() + 1)
+                    .sum::<usize>()
+                    + 1;
+                reserve(&mut data, length * core::mem::size_of::<u16>())?;
+                for string in list {
+                    extend_utf16(&mut data, string.as_unicode_str().as_slice());
+                }
+                data.extend_from_slice(&0u16.to_le_bytes());
+                DEVPROP_TYPE_STRING_LIST
+            }
+            Self::U32(value) => {
+                reserve(&mut data, core::mem::size_of::<u32>())?;
+                data.extend_from_slice(&value.to_le_bytes());
+                DEVPROP_TYPE_UINT32
+            }
+            Self::Bool(value) => {
+                reserve(&mut data, 1)?;
+                data.push(if *value { DEVPROP_TRUE } else { 0 });
+                DEVPROP_TYPE_BOOLEAN
+            }
+            Self::Guid(guid) => {
+                let guid = GUID::from(*guid);
+                reserve(&mut data, core::mem::size_of::<GUID>())?;
+                data.extend_from_slice(&guid.Data1.to_le_bytes());
+                data.extend_from_slice(&guid.Data2.to_le_bytes());
+                data.extend_from_slice(&guid.Data3.to_le_bytes());
+                data.extend_from_slice(&guid.Data4);
+                DEVPROP_TYPE_GUID
+            }
+            Self::Binary(binary) => {
+                reserve(&mut data, binary.len())?;
+                data.extend_from_slice(binary);
+                DEVPROP_TYPE_BINARY
+            }
+        };
+        Ok((property_type, data))
+    }
+}
+
+impl Device {
+    /// Read the device property `key`, ex.
+    /// [`DevicePropertyKey::FRIENDLY_NAME`]. Must be called at `IRQL` =
+    /// `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the property could not be read:
+    /// * `STATUS_OBJECT_NAME_NOT_FOUND` if the property is not set
+    /// * `STATUS_NOT_SUPPORTED` if the property is not of a type
+    ///   [`DevicePropertyValue`] represents
+    /// * `STATUS_INSUFFICIENT_RESOURCES` if the value could not be allocated
+    ///
+    /// Full error documentation is available in the [WdfDeviceQueryPropertyEx Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdevicequerypropertyex#return-value)
+    pub fn query_property(&self, key: DevicePropertyKey) -> Result<DevicePropertyValue, NTSTATUS> {
+        let property_key = key.as_raw();
+        let mut property_data = property_data(&property_key);
+        let mut buffer: Vec<u8> = Vec::new();
+        loop {
+            let buffer_length = ULONG::try_from(buffer.len()).unwrap_or(ULONG::MAX);
+            let mut result_length: ULONG = 0;
+            let mut property_type: DEVPROPTYPE = DEVPROP_TYPE_EMPTY;
+
+            let nt_status;
+            // SAFETY: The device is valid for as long as `self` is, as guaranteed to
+            // `Device::from_raw`. `property_data` points to `property_key`, which
+            // outlives the call, and `buffer` is writable for `buffer_length` bytes.
+            unsafe {
+                nt_status = call_unsafe_wdf_function_binding!(
+                    WdfDeviceQueryPropertyEx,
+                    self.as_raw(),
+                    &raw mut property_data,
+                    buffer_length,
+                    buffer.as_mut_ptr().cast(),
+                    &raw mut result_length,
+                    &raw mut property_type,
+                );
+            }
+            if nt_status == STATUS_BUFFER_TOO_SMALL {
+                let length = result_length as usize;
+                buffer
+                    .try_reserve_exact(length.saturating_sub(buffer.len()))
+                    .map_err(|_| alloc_failed(length))?;
+                buffer.resize(length, 0);
+                continue;
+            }
+            if !nt_success(nt_status) {
+                return Err(nt_status);
+            }
+            buffer.truncate(result_length as usize);
+            return DevicePropertyValue::decode(property_type, &buffer);
+        }
+    }
+
+    /// Set the device property `key` to `value`, ex. a custom property of the
+    /// driver, which applications can read with `CM_Get_DevNode_Property`. The
+    /// property is not kept across restarts of the system. Must be called at
+    /// `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the property could not be set,
+    /// ex. `STATUS_INSUFFICIENT_RESOURCES` if the data of `value` could not be
+    /// allocated. Full error documentation is available in the [WdfDeviceAssignProperty Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdeviceassignproperty#return-value)
+    pub fn assign_property(
+        &self,
+        key: DevicePropertyKey,
+        value: &DevicePropertyValue,
+    ) -> Result<(), NTSTATUS> {
+        let property_key = key.as_raw();
+        let mut property_data = property_data(&property_key);
+        let (property_type, mut data) = value.encode()?;
+        let size = ULONG::try_from(data.len()).map_err(|_| STATUS_INVALID_PARAMETER)?;
+
+        let nt_status;
+        // SAFETY: The device is valid for as long as `self` is, as guaranteed to
+        // `Device::from_raw`. `property_data` points to `property_key`, and `data` is
+        // readable for `size` bytes, which all outlive the call. The framework copies
+        // the data.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfDeviceAssignProperty,
+                self.as_raw(),
+                &raw mut property_data,
+                property_type,
+                size,
+                data.as_mut_ptr().cast(),
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+}
+
+/// Initialize a [`WDF_DEVICE_PROPERTY_DATA`] for `property_key`, like
+/// `WDF_DEVICE_PROPERTY_DATA_INIT`
+fn property_data(property_key: &DEVPROPKEY) -> WDF_DEVICE_PROPERTY_DATA {
+    let wdf_device_property_data_size;
+    // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+    #[allow(clippy::cast_possible_truncation)]
+    {
+        const WDF_DEVICE_PROPERTY_DATA_SIZE: usize =
+            core::mem::size_of::<WDF_DEVICE_PROPERTY_DATA>();
+
+        // Manually assert there is not truncation since clippy doesn't work for
+        // compile-time constants
+        const { assert!(WDF_DEVICE_PROPERTY_DATA_SIZE <= ULONG::MAX as usize) }
+
+        wdf_device_property_data_size = WDF_DEVICE_PROPERTY_DATA_SIZE as ULONG;
+    }
+
+    WDF_DEVICE_PROPERTY_DATA {
+        Size: wdf_device_property_data_size,
+        PropertyKey: property_key,
+        // LOCALE_NEUTRAL
+        Lcid: 0,
+        Flags: 0,
+    }
+}
+
+/// Reinterpret the data of a string property as its UTF-16 code units
+fn utf16_units(data: &[u8]) -> Result<Vec<u16>, NTSTATUS> {
+    let length = data.len() / core::mem::size_of::<u16>();
+    let mut units = Vec::new();
+    units
+        .try_reserve_exact(length)
+        .map_err(|_| alloc_failed(data.len()))?;
+    units.extend(
+        data.chunks_exact(core::mem::size_of::<u16>())
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]])),
+    );
+    Ok(units)
+}
+
+/// Append the NUL terminated UTF-16 string `string` to `data`
+fn extend_utf16(data: &mut Vec<u8>, string: &[u16]) {
+    for unit in string.iter().chain(&[0]) {
+        data.extend_from_slice(&unit.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unicode_string(string: &str) -> UnicodeString {
+        UnicodeString::try_from(string).unwrap()
+    }
+
+    #[test]
+    fn values_round_trip_through_property_data() {
+        let values = [
+            DevicePropertyValue::String(unicode_string("Sample Device")),
+            DevicePropertyValue::StringList(alloc::vec![
+                unicode_string("ACPI\\SMPL0001"),
+                unicode_string("*SMPL0001"),
+            ]),
+            DevicePropertyValue::U32(0x1234_5678),
+            DevicePropertyValue::Bool(true),
+            DevicePropertyValue::Bool(false),
+            DevicePropertyValue::Guid(Guid::from_u128(0x0123_4567_89AB_CDEF_0123_4567_89AB_CDEF)),
+            DevicePropertyValue::Binary(alloc::vec![1, 2, 3]),
+        ];
+        for value in values {
+            let (property_type, data) = value.encode().unwrap();
+            assert_eq!(
+                DevicePropertyValue::decode(property_type, &data).unwrap(),
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn property_data_is_encoded_like_the_system_does() {
+        let (property_type, data) = DevicePropertyValue::Bool(true).encode().unwrap();
+        assert_eq!(property_type, DEVPROP_TYPE_BOOLEAN);
+        assert_eq!(data, [0xFF]);
+
+        let (property_type, data) =
+            DevicePropertyValue::StringList(alloc::vec![unicode_string("a"), unicode_string("b"),])
+                .encode()
+                .unwrap();
+        assert_eq!(property_type, DEVPROP_TYPE_STRING_LIST);
+        assert_eq!(data, [b'a', 0, 0, 0, b'b', 0, 0, 0, 0, 0]);
+
+        let guid = Guid::parse("A45C254E-DF1C-4EFD-8020-67D146A850E0").unwrap();
+        let (_, data) = DevicePropertyValue::Guid(guid).encode().unwrap();
+        assert_eq!(
+            data,
+            [
+                0x4E, 0x25, 0x5C, 0xA4, 0x1C, 0xDF, 0xFD, 0x4E, 0x80, 0x20, 0x67, 0xD1, 0x46, 0xA8,
+                0x50, 0xE0
+            ]
+        );
+    }
+
+    #[test]
+    fn unexpected_property_data_is_rejected() {
+        assert_eq!(
+            DevicePropertyValue::decode(DEVPROP_TYPE_EMPTY, &[]),
+            Err(STATUS_OBJECT_NAME_NOT_FOUND)
+        );
+        assert_eq!(
+            DevicePropertyValue::decode(DEVPROP_TYPE_UINT32, &[1, 2]),
+            Err(STATUS_INVALID_PARAMETER)
+        );
+        assert_eq!(
+            DevicePropertyValue::decode(DEVPROP_TYPE_GUID, &[0; 8]),
+            Err(STATUS_INVALID_PARAMETER)
+        );
+        // DEVPROP_TYPE_FILETIME
+        assert_eq!(
+            DevicePropertyValue::decode(0x10, &[0; 8]),
+            Err(STATUS_NOT_SUPPORTED)
+        );
+    }
+}
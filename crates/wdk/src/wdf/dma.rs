@@ -0,0 +1,727 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{ffi::c_void, iter::FusedIterator};
+
+use wdk_sys::{
+    _WDF_DMA_DIRECTION,
+    _WDF_DMA_PROFILE,
+    BOOLEAN,
+    NTSTATUS,
+    PMDL,
+    PSCATTER_GATHER_LIST,
+    SCATTER_GATHER_ELEMENT,
+    SCATTER_GATHER_LIST,
+    ULONG,
+    WDF_DMA_DIRECTION,
+    WDF_DMA_ENABLER_CONFIG,
+    WDF_DMA_PROFILE,
+    WDF_NO_OBJECT_ATTRIBUTES,
+    WDFCONTEXT,
+    WDFDEVICE,
+    WDFDMAENABLER,
+    WDFDMATRANSACTION,
+    WDFREQUEST,
+    call_unsafe_wdf_function_binding,
+};
+
+use crate::{
+    nt_success,
+    wdf::{Device, Request},
+};
+
+/// DMA capabilities of a device, i.e. whether it transfers a single buffer at
+/// a time or a list of buffers, and how many address bits it supports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DmaProfile {
+    /// The device transfers a single buffer at a time, with 32-bit addresses
+    Packet,
+    /// The device transfers a list of buffers, with 32-bit addresses
+    ScatterGather,
+    /// The device transfers a single buffer at a time, with 64-bit addresses
+    Packet64,
+    /// The device transfers a list of buffers, with 64-bit addresses
+    ScatterGather64,
+    /// The device transfers a list of buffers, with 32-bit addresses, and can
+    /// read and write at the same time
+    ScatterGatherDuplex,
+    /// The device transfers a list of buffers, with 64-bit addresses, and can
+    /// read and write at the same time
+    ScatterGather64Duplex,
+}
+
+impl From<DmaProfile> for WDF_DMA_PROFILE {
+    fn from(profile: DmaProfile) -> Self {
+        match profile {
+            DmaProfile::Packet => _WDF_DMA_PROFILE::WdfDmaProfilePacket,
+            DmaProfile::ScatterGather => _WDF_DMA_PROFILE::WdfDmaProfileScatterGather,
+            DmaProfile::Packet64 => _WDF_DMA_PROFILE::WdfDmaProfilePacket64,
+            DmaProfile::ScatterGather64 => _WDF_DMA_PROFILE::WdfDmaProfileScatterGather64,
+            DmaProfile::ScatterGatherDuplex => _WDF_DMA_PROFILE::WdfDmaProfileScatterGatherDuplex,
+            DmaProfile::ScatterGather64Duplex => {
+                _WDF_DMA_PROFILE::WdfDmaProfileScatterGather64Duplex
+            }
+        }
+    }
+}
+
+/// Direction of a [`DmaTransaction`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DmaDirection {
+    /// The device writes to memory, ex. for a read request
+    ReadFromDevice,
+    /// The device reads from memory, ex. for a write request
+    WriteToDevice,
+}
+
+impl From<DmaDirection> for WDF_DMA_DIRECTION {
+    fn from(direction: DmaDirection) -> Self {
+        match direction {
+            DmaDirection::ReadFromDevice => _WDF_DMA_DIRECTION::WdfDmaDirectionReadFromDevice,
+            DmaDirection::WriteToDevice => _WDF_DMA_DIRECTION::WdfDmaDirectionWriteToDevice,
+        }
+    }
+}
+
+impl From<WDF_DMA_DIRECTION> for DmaDirection {
+    fn from(direction: WDF_DMA_DIRECTION) -> Self {
+        if direction == _WDF_DMA_DIRECTION::WdfDmaDirectionWriteToDevice {
+            Self::WriteToDevice
+        } else {
+            Self::ReadFromDevice
+        }
+    }
+}
+
+/// Driver-defined callback that programs the device to transfer a part of a
+/// [`DmaTransaction`].
+///
+/// Set with [`DmaTransaction::initialize_using_request()`] or
+/// [`DmaTransaction::initialize()`].
+pub trait ProgramDmaCallback {
+    /// Programs the device of `transaction` to transfer the buffers of
+    /// `sg_list` in `direction`, and starts the transfer. Returns `true` if
+    /// the device was programmed. When the device signals that the transfer
+    /// is done, typically from the DPC of its interrupt, the driver reports it
+    /// with [`DmaTransaction::dma_completed()`]. This is invoked from the
+    /// framework's `EvtProgramDma` callback, at `IRQL` = `DISPATCH_LEVEL`.
+    /// Full documentation is available in the [EvtProgramDma Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdmatransaction/nc-wdfdmatransaction-evt_wdf_program_dma)
+    fn program_dma(
+        transaction: &DmaTransaction,
+        device: &Device,
+        direction: DmaDirection,
+        sg_list: ScatterGatherList<'_>,
+    ) -> bool;
+}
+
+/// Scatter/gather list of a part of a [`DmaTransaction`], i.e. the device
+/// addresses and lengths of the buffers the device transfers.
+///
+/// Passed to [`ProgramDmaCallback::program_dma()`]. Drivers of devices with a
+/// packet [`DmaProfile`] always receive a single element.
+#[derive(Clone, Copy)]
+pub struct ScatterGatherList<'a> {
+    elements: &'a [SCATTER_GATHER_ELEMENT],
+}
+impl<'a> ScatterGatherList<'a> {
+    /// Wrap a scatter/gather list built by the framework
+    ///
+    /// # Safety
+    ///
+    /// `sg_list` must point to a valid `SCATTER_GATHER_LIST`, followed by
+    /// `NumberOfElements` elements, that is not modified for `'a`.
+    unsafe fn from_raw(sg_list: *const SCATTER_GATHER_LIST) -> Self {
+        // SAFETY: The caller guarantees that `sg_list` is valid
+        let len = unsafe { (*sg_list).NumberOfElements } as usize;
+        // SAFETY: The caller guarantees that `sg_list` is valid
+        let elements = unsafe { &raw const (*sg_list).Elements };
+        // SAFETY: The caller guarantees that `len` elements follow the header of the
+        // list, and that they are not modified for `'a`
+        let elements =
+            unsafe { core::slice::from_raw_parts(elements.cast::<SCATTER_GATHER_ELEMENT>(), len) };
+        Self { elements }
+    }
+
+    /// Get the number of elements of the list
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    /// Returns `true` if the list has no elements
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Get an iterator over the elements of the list
+    #[must_use]
+    pub fn iter(&self) -> ScatterGatherElements<'a> {
+        ScatterGatherElements {
+            elements: self.elements.iter(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for ScatterGatherList<'a> {
+    type IntoIter = ScatterGatherElements<'a>;
+    type Item = ScatterGatherElement;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Buffer of a [`ScatterGatherList`], as seen by the device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScatterGatherElement {
+    address: u64,
+    length: ULONG,
+}
+impl ScatterGatherElement {
+    /// Get the logical address of the buffer, which the device is programmed
+    /// with
+    #[must_use]
+    pub const fn address(&self) -> u64 {
+        self.address
+    }
+
+    /// Get the length of the buffer, in bytes
+    #[must_use]
+    pub const fn length(&self) -> ULONG {
+        self.length
+    }
+}
+
+/// Iterator over the elements of a [`ScatterGatherList`]
+#[derive(Clone)]
+pub struct ScatterGatherElements<'a> {
+    elements: core::slice::Iter<'a, SCATTER_GATHER_ELEMENT>,
+}
+
+impl Iterator for ScatterGatherElements<'_> {
+    type Item = ScatterGatherElement;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let element = self.elements.next()?;
+        // SAFETY: Every variant of `PHYSICAL_ADDRESS` covers its 64 bits, so
+        // `QuadPart` is always initialized
+        let address = unsafe { element.Address.QuadPart };
+
+        // The address is a bit pattern, which is reinterpreted as unsigned
+        #[allow(clippy::cast_sign_loss)]
+        let address = address as u64;
+
+        Some(ScatterGatherElement {
+            address,
+            length: element.Length,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.elements.size_hint()
+    }
+}
+
+impl ExactSizeIterator for ScatterGatherElements<'_> {}
+
+impl FusedIterator for ScatterGatherElements<'_> {}
+
+/// WDF DMA Enabler.
+///
+/// Describes the DMA capabilities of a device to the framework, which then
+/// maps the buffers of each [`DmaTransaction`] of the device to addresses the
+/// device can access. The DMA enabler is a child of its device, which deletes
+/// it when the device is removed.
+///
+/// A driver typically creates the DMA enabler of its device in
+/// `EvtDriverDeviceAdd`, after setting the alignment the device requires for
+/// its buffers with `WdfDeviceSetAlignmentRequirement`.
+#[derive(Clone, Copy)]
+pub struct DmaEnabler {
+    wdf_dma_enabler: WDFDMAENABLER,
+}
+impl DmaEnabler {
+    /// Try to construct a WDF DMA Enabler object for `device`, which supports
+    /// `profile` and transfers at most `maximum_length` bytes at a time. Must
+    /// be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a DMA
+    /// enabler. The error variant will contain a [`NTSTATUS`] of the failure.
+    /// Full error documentation is available in the [WDFDmaEnabler Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdmaenabler/nf-wdfdmaenabler-wdfdmaenablercreate#return-value)
+    pub fn try_new(
+        device: &Device,
+        profile: DmaProfile,
+        maximum_length: usize,
+    ) -> Result<Self, NTSTATUS> {
+        let config_size: ULONG;
+
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            const CONFIG_SIZE: usize = core::mem::size_of::<WDF_DMA_ENABLER_CONFIG>();
+
+            // Manually assert there is not truncation since clippy doesn't work for
+            // compile-time constants
+            const { assert!(CONFIG_SIZE <= ULONG::MAX as usize) }
+
+            config_size = CONFIG_SIZE as ULONG;
+        }
+
+        let mut config = WDF_DMA_ENABLER_CONFIG {
+            Size: config_size,
+            Profile: profile.into(),
+            MaximumLength: maximum_length,
+            ..WDF_DMA_ENABLER_CONFIG::default()
+        };
+
+        let mut dma_enabler = Self {
+            wdf_dma_enabler: core::ptr::null_mut(),
+        };
+
+        let nt_status;
+        // SAFETY: `device` is a valid device handle, and the resulting ffi object is
+        // stored in a private member and not accessible outside of this module, and
+        // this module guarantees that it is always in a valid state.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfDmaEnablerCreate,
+                device.as_raw(),
+                &raw mut config,
+                WDF_NO_OBJECT_ATTRIBUTES,
+                &mut dma_enabler.wdf_dma_enabler as *mut WDFDMAENABLER,
+            );
+        }
+        nt_success(nt_status)
+            .then_some(dma_enabler)
+            .ok_or(nt_status)
+    }
+
+    /// Try to construct a WDF DMA Enabler object for `device`. This is an
+    /// alias for [`DmaEnabler::try_new()`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a DMA
+    /// enabler. The error variant will contain a [`NTSTATUS`] of the failure.
+    /// Full error documentation is available in the [WDFDmaEnabler Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdmaenabler/nf-wdfdmaenabler-wdfdmaenablercreate#return-value)
+    pub fn create(
+        device: &Device,
+        profile: DmaProfile,
+        maximum_length: usize,
+    ) -> Result<Self, NTSTATUS> {
+        Self::try_new(device, profile, maximum_length)
+    }
+
+    /// Get the raw handle of the framework DMA enabler object
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFDMAENABLER {
+        self.wdf_dma_enabler
+    }
+
+    /// Get the largest number of bytes the device transfers at a time, as set
+    /// when the DMA enabler was created
+    #[must_use]
+    pub fn maximum_length(&self) -> usize {
+        let maximum_length;
+        // SAFETY: `wdf_dma_enabler` is a private member of `DmaEnabler`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            maximum_length = call_unsafe_wdf_function_binding!(
+                WdfDmaEnablerGetMaximumLength,
+                self.wdf_dma_enabler
+            );
+        }
+        maximum_length
+    }
+
+    /// Get the largest number of elements of the scatter/gather lists passed
+    /// to [`ProgramDmaCallback::program_dma()`]
+    #[must_use]
+    pub fn maximum_scatter_gather_elements(&self) -> usize {
+        let maximum_elements;
+        // SAFETY: `wdf_dma_enabler` is a private member of `DmaEnabler`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            maximum_elements = call_unsafe_wdf_function_binding!(
+                WdfDmaEnablerGetMaximumScatterGatherElements,
+                self.wdf_dma_enabler
+            );
+        }
+        maximum_elements
+    }
+
+    /// Set the largest number of elements of the scatter/gather lists the
+    /// device supports. Must be called before the first [`DmaTransaction`] of
+    /// the device is initialized, ex. from `EvtDevicePrepareHardware`.
+    pub fn set_maximum_scatter_gather_elements(&self, maximum_elements: usize) {
+        // SAFETY: `wdf_dma_enabler` is a private member of `DmaEnabler`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfDmaEnablerSetMaximumScatterGatherElements,
+                self.wdf_dma_enabler,
+                maximum_elements,
+            );
+        }
+    }
+}
+
+/// WDF DMA Transaction.
+///
+/// A DMA transfer between the device of a [`DmaEnabler`] and a buffer, ex. the
+/// buffer of a read or write request. The framework splits the transaction in
+/// transfers the device supports, and calls the [`ProgramDmaCallback`] of the
+/// transaction for each of them. The DMA transaction is a child of its DMA
+/// enabler, which deletes it when the device is removed.
+///
+/// Transactions can be reused: a driver typically creates one per transfer
+/// the device can run at a time, initializes it for each request with
+/// [`DmaTransaction::initialize_using_request()`], and releases it with
+/// [`DmaTransaction::release()`] once the request is completed.
+#[derive(Clone, Copy)]
+pub struct DmaTransaction {
+    wdf_dma_transaction: WDFDMATRANSACTION,
+}
+impl DmaTransaction {
+    /// Try to construct a WDF DMA Transaction object for the device of
+    /// `dma_enabler`. Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a DMA
+    /// transaction. The error variant will contain a [`NTSTATUS`] of the
+    /// failure. Full error documentation is available in the [WDFDmaTransaction Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdmatransaction/nf-wdfdmatransaction-wdfdmatransactioncreate#return-value)
+    pub fn try_new(dma_enabler: &DmaEnabler) -> Result<Self, NTSTATUS> {
+        let mut dma_transaction = Self {
+            wdf_dma_transaction: core::ptr::null_mut(),
+        };
+
+        let nt_status;
+        // SAFETY: The resulting ffi object is stored in a private member and not
+        // accessible outside of this module, and this module guarantees that it is
+        // always in a valid state.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfDmaTransactionCreate,
+                dma_enabler.wdf_dma_enabler,
+                WDF_NO_OBJECT_ATTRIBUTES,
+                &mut dma_transaction.wdf_dma_transaction as *mut WDFDMATRANSACTION,
+            );
+        }
+        nt_success(nt_status)
+            .then_some(dma_transaction)
+            .ok_or(nt_status)
+    }
+
+    /// Try to construct a WDF DMA Transaction object for the device of
+    /// `dma_enabler`. This is an alias for [`DmaTransaction::try_new()`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a DMA
+    /// transaction. The error variant will contain a [`NTSTATUS`] of the
+    /// failure. Full error documentation is available in the [WDFDmaTransaction Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdmatransaction/nf-wdfdmatransaction-wdfdmatransactioncreate#return-value)
+    pub fn create(dma_enabler: &DmaEnabler) -> Result<Self, NTSTATUS> {
+        Self::try_new(dma_enabler)
+    }
+
+    /// Get the raw handle of the framework DMA transaction object
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFDMATRANSACTION {
+        self.wdf_dma_transaction
+    }
+
+    /// Initialize the transaction to transfer the buffer of `request`, a read
+    /// or write request, or a device control request with a direct
+    /// [`crate::TransferMethod`]. The device is programmed through `T`. Must
+    /// be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to initialize the
+    /// transaction. The error variant will contain a [`NTSTATUS`] of the
+    /// failure. Full error documentation is available in the [WDFDmaTransaction Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdmatransaction/nf-wdfdmatransaction-wdfdmatransactioninitializeusingrequest#return-value)
+    pub fn initialize_using_request<T: ProgramDmaCallback>(
+        &self,
+        request: &Request,
+        direction: DmaDirection,
+    ) -> Result<(), NTSTATUS> {
+        let nt_status;
+        // SAFETY: `wdf_dma_transaction` is a private member of `DmaTransaction`,
+        // originally created by WDF, and this module guarantees that it is always in
+        // a valid state. `request` is a valid request handle.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfDmaTransactionInitializeUsingRequest,
+                self.wdf_dma_transaction,
+                request.as_raw(),
+                Some(evt_program_dma::<T>),
+                direction.into(),
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Initialize the transaction to transfer `length` bytes of the buffer
+    /// described by `mdl`, starting at `virtual_address`. The device is
+    /// programmed through `T`. Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to initialize the
+    /// transaction. The error variant will contain a [`NTSTATUS`] of the
+    /// failure. Full error documentation is available in the [WDFDmaTransaction Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdmatransaction/nf-wdfdmatransaction-wdfdmatransactioninitialize#return-value)
+    ///
+    /// # Safety
+    ///
+    /// `mdl` must describe a locked buffer that contains the `length` bytes at
+    /// `virtual_address`, and that stays valid until the transaction is
+    /// released.
+    pub unsafe fn initialize<T: ProgramDmaCallback>(
+        &self,
+        mdl: PMDL,
+        virtual_address: *mut c_void,
+        length: usize,
+        direction: DmaDirection,
+    ) -> Result<(), NTSTATUS> {
+        let nt_status;
+        // SAFETY: `wdf_dma_transaction` is a private member of `DmaTransaction`,
+        // originally created by WDF, and this module guarantees that it is always in
+        // a valid state. The caller guarantees that the buffer is valid.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfDmaTransactionInitialize,
+                self.wdf_dma_transaction,
+                Some(evt_program_dma::<T>),
+                direction.into(),
+                mdl,
+                virtual_address,
+                length,
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Start the transaction. The framework maps the first part of the buffer
+    /// and calls [`ProgramDmaCallback::program_dma()`] with it. Must be called
+    /// at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to start the
+    /// transaction, ex. if the callback returned `false`. The error variant
+    /// will contain a [`NTSTATUS`] of the failure. Full error documentation is
+    /// available in the [WDFDmaTransaction Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdmatransaction/nf-wdfdmatransaction-wdfdmatransactionexecute#return-value)
+    pub fn execute(&self) -> Result<(), NTSTATUS> {
+        let nt_status;
+        // SAFETY: `wdf_dma_transaction` is a private member of `DmaTransaction`,
+        // originally created by WDF, and this module guarantees that it is always in
+        // a valid state.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfDmaTransactionExecute,
+                self.wdf_dma_transaction,
+                core::ptr::null_mut(),
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Report that the device finished the transfer it was last programmed
+    /// with. Returns the status of the transaction if it is done, or `None`
+    /// if the framework programs the device with the next part of the buffer.
+    /// Must be called at `IRQL` <= `DISPATCH_LEVEL`, typically from the DPC of
+    /// the interrupt of the device.
+    #[must_use]
+    pub fn dma_completed(&self) -> Option<NTSTATUS> {
+        let mut nt_status: NTSTATUS = 0;
+        let done: BOOLEAN;
+        // SAFETY: `wdf_dma_transaction` is a private member of `DmaTransaction`,
+        // originally created by WDF, and this module guarantees that it is always in
+        // a valid state.
+        unsafe {
+            done = call_unsafe_wdf_function_binding!(
+                WdfDmaTransactionDmaCompleted,
+                self.wdf_dma_transaction,
+                &raw mut nt_status,
+            );
+        }
+        (done != 0).then_some(nt_status)
+    }
+
+    /// Report that the device finished the transfer it was last programmed
+    /// with, after transferring only `transferred_length` bytes of it. Returns
+    /// the status of the transaction if it is done, or `None` if the framework
+    /// programs the device with the rest of the buffer. Must be called at
+    /// `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn dma_completed_with_length(&self, transferred_length: usize) -> Option<NTSTATUS> {
+        let mut nt_status: NTSTATUS = 0;
+        let done: BOOLEAN;
+        // SAFETY: `wdf_dma_transaction` is a private member of `DmaTransaction`,
+        // originally created by WDF, and this module guarantees that it is always in
+        // a valid state.
+        unsafe {
+            done = call_unsafe_wdf_function_binding!(
+                WdfDmaTransactionDmaCompletedWithLength,
+                self.wdf_dma_transaction,
+                transferred_length,
+                &raw mut nt_status,
+            );
+        }
+        (done != 0).then_some(nt_status)
+    }
+
+    /// Get the number of bytes transferred by the transaction so far
+    #[must_use]
+    pub fn bytes_transferred(&self) -> usize {
+        let bytes_transferred;
+        // SAFETY: `wdf_dma_transaction` is a private member of `DmaTransaction`,
+        // originally created by WDF, and this module guarantees that it is always in
+        // a valid state.
+        unsafe {
+            bytes_transferred = call_unsafe_wdf_function_binding!(
+                WdfDmaTransactionGetBytesTransferred,
+                self.wdf_dma_transaction
+            );
+        }
+        bytes_transferred
+    }
+
+    /// Get the request the transaction was initialized with through
+    /// [`DmaTransaction::initialize_using_request()`], or `None` if it was
+    /// initialized with a buffer
+    ///
+    /// # Safety
+    ///
+    /// The returned [`Request`] is a second handle to a request the driver
+    /// already owns, so the caller must not use the [`Request`] it initialized
+    /// the transaction with while it uses the returned one, ex. by dropping it
+    /// after initializing the transaction.
+    #[must_use]
+    pub unsafe fn get_request(&self) -> Option<Request> {
+        let wdf_request: WDFREQUEST;
+        // SAFETY: `wdf_dma_transaction` is a private member of `DmaTransaction`,
+        // originally created by WDF, and this module guarantees that it is always in
+        // a valid state.
+        unsafe {
+            wdf_request = call_unsafe_wdf_function_binding!(
+                WdfDmaTransactionGetRequest,
+                self.wdf_dma_transaction
+            );
+        }
+        if wdf_request.is_null() {
+            return None;
+        }
+        // SAFETY: The request stays valid until the driver completes it, which it
+        // does after the transaction is done. The caller guarantees that it does not
+        // use another `Request` of it meanwhile.
+        Some(unsafe { Request::from_raw(wdf_request) })
+    }
+
+    /// Release the resources of the transaction once it is done, so that it
+    /// can be initialized again. Must be called at `IRQL` <= `DISPATCH_LEVEL`,
+    /// before the request of the transaction is completed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to release the
+    /// transaction. The error variant will contain a [`NTSTATUS`] of the
+    /// failure. Full error documentation is available in the [WDFDmaTransaction Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdmatransaction/nf-wdfdmatransaction-wdfdmatransactionrelease#return-value)
+    pub fn release(&self) -> Result<(), NTSTATUS> {
+        let nt_status;
+        // SAFETY: `wdf_dma_transaction` is a private member of `DmaTransaction`,
+        // originally created by WDF, and this module guarantees that it is always in
+        // a valid state.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfDmaTransactionRelease,
+                self.wdf_dma_transaction
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+}
+
+/// `EvtProgramDma` callback that forwards to
+/// [`ProgramDmaCallback::program_dma()`]
+unsafe extern "C" fn evt_program_dma<T: ProgramDmaCallback>(
+    wdf_dma_transaction: WDFDMATRANSACTION,
+    wdf_device: WDFDEVICE,
+    _context: WDFCONTEXT,
+    direction: WDF_DMA_DIRECTION,
+    sg_list: PSCATTER_GATHER_LIST,
+) -> BOOLEAN {
+    let transaction = DmaTransaction {
+        wdf_dma_transaction,
+    };
+    // SAFETY: The framework passes the device of the transaction, which is valid
+    // until the callback returns
+    let device = unsafe { Device::from_raw(wdf_device) };
+    // SAFETY: The framework passes the scatter/gather list of the transfer, which
+    // is not modified until the callback returns
+    let sg_list = unsafe { ScatterGatherList::from_raw(sg_list) };
+    u8::from(T::program_dma(
+        &transaction,
+        &device,
+        direction.into(),
+        sg_list,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use wdk_sys::LARGE_INTEGER;
+
+    use super::*;
+
+    /// Scatter/gather list with its elements, laid out like the framework
+    /// builds it
+    #[repr(C)]
+    struct ListWithElements {
+        header: SCATTER_GATHER_LIST,
+        elements: [SCATTER_GATHER_ELEMENT; 2],
+    }
+
+    #[test]
+    fn scatter_gather_list_iterates_elements() {
+        let list = ListWithElements {
+            header: SCATTER_GATHER_LIST {
+                NumberOfElements: 2,
+                ..SCATTER_GATHER_LIST::default()
+            },
+            elements: [
+                SCATTER_GATHER_ELEMENT {
+                    Address: LARGE_INTEGER { QuadPart: 0x1000 },
+                    Length: 0x200,
+                    Reserved: 0,
+                },
+                SCATTER_GATHER_ELEMENT {
+                    Address: LARGE_INTEGER {
+                        QuadPart: 0x1_0000_3000,
+                    },
+                    Length: 0x80,
+                    Reserved: 0,
+                },
+            ],
+        };
+
+        // SAFETY: `list` is followed by the number of elements it holds
+        let sg_list = unsafe { ScatterGatherList::from_raw((&raw const list).cast()) };
+        assert_eq!(sg_list.len(), 2);
+
+        let mut elements = sg_list.into_iter();
+        assert_eq!(elements.len(), 2);
+        let element = elements.next().unwrap();
+        assert_eq!((element.address(), element.length()), (0x1000, 0x200));
+        let element = elements.next().unwrap();
+        assert_eq!((element.address(), element.length()), (0x1_0000_3000, 0x80));
+        assert!(elements.next().is_none());
+    }
+}
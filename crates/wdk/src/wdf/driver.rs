@@ -0,0 +1,353 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{
+    ffi::c_void,
+    ops::Deref,
+    sync::atomic::{AtomicPtr, AtomicUsize, Ordering},
+};
+
+use wdk_sys::{
+    NTSTATUS,
+    PWSTR,
+    STATUS_INSUFFICIENT_RESOURCES,
+    STATUS_OBJECT_NAME_COLLISION,
+    WDFDRIVER,
+    WDFOBJECT,
+    call_unsafe_wdf_function_binding,
+};
+
+use crate::{
+    nt_success,
+    string::UnicodeStr,
+    wdf::{
+        Device,
+        RefCountedHandle,
+        WdfString,
+        context::{
+            allocate_context_with_attributes,
+            declare_context_type,
+            get_context,
+            object_attributes,
+        },
+    },
+};
+
+/// Maximum number of callbacks that can be registered through
+/// [`Driver::on_evt_driver_unload()`]
+pub const MAX_DRIVER_UNLOAD_CALLBACKS: usize = 8;
+
+/// Number of slots of `DRIVER_UNLOAD_CALLBACKS` that were claimed
+static DRIVER_UNLOAD_CALLBACKS_LEN: AtomicUsize = AtomicUsize::new(0);
+/// Callbacks registered through [`Driver::on_evt_driver_unload()`], as type
+/// erased `fn(&Driver)` pointers. Slots that are claimed but not published yet
+/// are null.
+static DRIVER_UNLOAD_CALLBACKS: [AtomicPtr<()>; MAX_DRIVER_UNLOAD_CALLBACKS] =
+    [const { AtomicPtr::new(core::ptr::null_mut()) }; MAX_DRIVER_UNLOAD_CALLBACKS];
+
+/// Maximum number of devices that can be registered at a time through
+/// [`Driver::register_device()`]
+pub const MAX_DRIVER_DEVICES: usize = 32;
+
+/// Devices registered through [`Driver::register_device()`]. Free slots are
+/// null.
+static DRIVER_DEVICES: [AtomicPtr<c_void>; MAX_DRIVER_DEVICES] =
+    [const { AtomicPtr::new(core::ptr::null_mut()) }; MAX_DRIVER_DEVICES];
+/// Number of [`Devices`] iterators reading each slot of `DRIVER_DEVICES`, which
+/// a device waits for before it leaves its slot
+static DRIVER_DEVICE_READERS: [AtomicUsize; MAX_DRIVER_DEVICES] =
+    [const { AtomicUsize::new(0) }; MAX_DRIVER_DEVICES];
+
+/// WDF Driver.
+///
+/// The framework driver object of the calling driver, which is created by
+/// `WdfDriverCreate` in `DriverEntry`. [`Driver::get()`] retrieves it from any
+/// callback, so driver code does not need to stash the handle returned by
+/// `WdfDriverCreate` in a static.
+///
+/// Drivers that need to free global resources, flush logs or notify user mode
+/// when they are unloaded register callbacks with
+/// [`Driver::on_evt_driver_unload()`], and set [`evt_driver_unload`] as the
+/// `EvtDriverUnload` of the `WDF_DRIVER_CONFIG` passed to `WdfDriverCreate`.
+///
+/// A driver is loaded once for all the devices it serves. Drivers that act on
+/// all of their devices, ex. to report driver-wide statistics or to apply a
+/// configuration change, register each device with
+/// [`Driver::register_device()`] when it is added, and enumerate them with
+/// [`Driver::devices()`].
+#[derive(Clone, Copy)]
+pub struct Driver {
+    wdf_driver: WDFDRIVER,
+}
+impl Driver {
+    /// Get the framework driver object of the calling driver, or `None` if
+    /// `WdfDriverCreate` has not been called yet
+    #[must_use]
+    pub fn get() -> Option<Self> {
+        let wdf_driver: WDFDRIVER;
+        // SAFETY: `WdfGetDriver` only reads the driver handle stored in the driver's
+        // WDF globals
+        unsafe {
+            wdf_driver = call_unsafe_wdf_function_binding!(WdfGetDriver);
+        }
+        (!wdf_driver.is_null()).then_some(Self { wdf_driver })
+    }
+
+    /// Get the raw handle of the framework driver object
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFDRIVER {
+        self.wdf_driver
+    }
+
+    /// Get the path of the driver's service key in the registry, as a UTF-16
+    /// string without its NUL terminator
+    #[must_use]
+    pub fn registry_path(&self) -> &[u16] {
+        let registry_path: PWSTR;
+        // SAFETY: `wdf_driver` is a private member of `Driver`, originally returned by
+        // WDF, and is valid until the driver is unloaded.
+        unsafe {
+            registry_path =
+                call_unsafe_wdf_function_binding!(WdfDriverGetRegistryPath, self.wdf_driver);
+        }
+        if registry_path.is_null() {
+            return &[];
+        }
+
+        let mut len = 0;
+        // SAFETY: The framework returns a NUL terminated string, so every character
+        // up to and including the terminator is readable
+        while unsafe { *registry_path.add(len) } != 0 {
+            len += 1;
+        }
+        // SAFETY: The `len` characters before the terminator are initialized, and the
+        // string is owned by the framework driver object, which outlives `self`
+        unsafe { core::slice::from_raw_parts(registry_path, len) }
+    }
+
+    /// Fill `string` with the version of the framework the driver runs on, ex.
+    /// to log it, and borrow it, without allocating. Must be called at `IRQL`
+    /// = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the version could not be
+    /// retrieved. The error variant will contain a [`NTSTATUS`] of the failure.
+    /// Full error documentation is available in the [WdfDriverRetrieveVersionString Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdriver/nf-wdfdriver-wdfdriverretrieveversionstring#return-value)
+    pub fn retrieve_version_string<'s>(
+        &self,
+        string: &'s WdfString,
+    ) -> Result<UnicodeStr<'s>, NTSTATUS> {
+        let nt_status;
+        // SAFETY: `wdf_driver` is a private member of `Driver`, originally returned by
+        // WDF, and is valid until the driver is unloaded. `string` outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfDriverRetrieveVersionString,
+                self.wdf_driver,
+                string.as_raw(),
+            );
+        }
+        nt_success(nt_status)
+            .then(|| string.as_unicode_str())
+            .ok_or(nt_status)
+    }
+
+    /// Register `callback` to be invoked when the driver is unloaded. Callbacks
+    /// are invoked by [`evt_driver_unload`], which must be set as the
+    /// `EvtDriverUnload` of the driver, in the reverse order of their
+    /// registration, so that resources are released in the reverse order they
+    /// were acquired in. All callbacks run before the framework driver object
+    /// is deleted, at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_INSUFFICIENT_RESOURCES` if
+    /// [`MAX_DRIVER_UNLOAD_CALLBACKS`] callbacks are already registered.
+    pub fn on_evt_driver_unload(&self, callback: fn(&Self)) -> Result<(), NTSTATUS> {
+        let index = DRIVER_UNLOAD_CALLBACKS_LEN.fetch_add(1, Ordering::Relaxed);
+        if index >= MAX_DRIVER_UNLOAD_CALLBACKS {
+            DRIVER_UNLOAD_CALLBACKS_LEN.fetch_sub(1, Ordering::Relaxed);
+            return Err(STATUS_INSUFFICIENT_RESOURCES);
+        }
+        DRIVER_UNLOAD_CALLBACKS[index].store(callback as *mut (), Ordering::Release);
+        Ok(())
+    }
+
+    /// Register `device` with the driver, so that it is returned by
+    /// [`Driver::devices()`] until it is removed. The device leaves the
+    /// registry on its own when the framework cleans it up. Must be called at
+    /// `IRQL` <= `DISPATCH_LEVEL`, typically from `EvtDriverDeviceAdd`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the device could not be
+    /// registered:
+    /// * `STATUS_OBJECT_NAME_COLLISION` if the device is already registered
+    /// * `STATUS_INSUFFICIENT_RESOURCES` if [`MAX_DRIVER_DEVICES`] devices are
+    ///   already registered, or the registration could not be allocated
+    pub fn register_device(&self, device: &Device) -> Result<(), NTSTATUS> {
+        let mut attributes = object_attributes(&DEVICE_REGISTRATION_TYPE_INFO);
+        attributes.EvtCleanupCallback = Some(evt_device_registration_cleanup);
+        // SAFETY: The caller of `Device::from_raw` guaranteed that the device is
+        // valid. `attributes` describes `DeviceRegistration`, which is valid when
+        // all-zero, i.e. not registered.
+        let registration: &DeviceRegistration =
+            unsafe { allocate_context_with_attributes(device.as_raw().cast(), &mut attributes) }?;
+        if registration.slot.load(Ordering::Acquire) != 0 {
+            return Err(STATUS_OBJECT_NAME_COLLISION);
+        }
+
+        let wdf_device: *mut c_void = device.as_raw().cast();
+        let index = DRIVER_DEVICES
+            .iter()
+            .position(|slot| {
+                slot.compare_exchange(
+                    core::ptr::null_mut(),
+                    wdf_device,
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+            })
+            .ok_or(STATUS_INSUFFICIENT_RESOURCES)?;
+        if registration
+            .slot
+            .compare_exchange(0, index + 1, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            // The device was registered concurrently
+            DRIVER_DEVICES[index].store(core::ptr::null_mut(), Ordering::Release);
+            return Err(STATUS_OBJECT_NAME_COLLISION);
+        }
+        Ok(())
+    }
+
+    /// Get an iterator over the devices registered with
+    /// [`Driver::register_device()`] that are not removed yet. Each device is
+    /// returned as a [`DeviceRef`], which keeps the device object from being
+    /// deleted until it is dropped, so the device can be used even if it is
+    /// removed meanwhile. Devices registered or removed while iterating may or
+    /// may not be returned. Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn devices(&self) -> Devices {
+        Devices { index: 0 }
+    }
+}
+
+/// Iterator over the devices of the driver, returned by [`Driver::devices()`]
+pub struct Devices {
+    index: usize,
+}
+impl Iterator for Devices {
+    type Item = DeviceRef;
+
+    fn next(&mut self) -> Option<DeviceRef> {
+        while self.index < MAX_DRIVER_DEVICES {
+            let index = self.index;
+            self.index += 1;
+
+            // The device waits for the readers of its slot after leaving it, so it
+            // is not deleted before it is referenced below
+            DRIVER_DEVICE_READERS[index].fetch_add(1, Ordering::SeqCst);
+            let wdf_device = DRIVER_DEVICES[index].load(Ordering::SeqCst);
+            let reference = (!wdf_device.is_null()).then(|| {
+                // SAFETY: The device is still registered, and cannot leave its slot
+                // and be deleted while it is read
+                unsafe { RefCountedHandle::new(wdf_device) }
+            });
+            DRIVER_DEVICE_READERS[index].fetch_sub(1, Ordering::SeqCst);
+
+            if let Some(reference) = reference {
+                // SAFETY: The reference taken above keeps the device object valid until
+                // the `DeviceRef` is dropped
+                let device = unsafe { Device::from_raw(wdf_device.cast()) };
+                return Some(DeviceRef {
+                    device,
+                    _reference: reference,
+                });
+            }
+        }
+        None
+    }
+}
+
+/// Reference to a device of the driver, returned by [`Driver::devices()`].
+///
+/// Holds a framework reference on the device object, so that it stays valid
+/// until the [`DeviceRef`] is dropped, even if the device is removed meanwhile.
+/// Drop it as soon as possible, as the framework waits for the references of a
+/// removed device before it deletes the device and its context.
+pub struct DeviceRef {
+    device: Device,
+    _reference: RefCountedHandle,
+}
+
+impl Deref for DeviceRef {
+    type Target = Device;
+
+    fn deref(&self) -> &Device {
+        &self.device
+    }
+}
+
+/// Registration of a device with the driver, stored in a context of the device
+/// object
+#[repr(C)]
+struct DeviceRegistration {
+    /// Index of the slot of the device in `DRIVER_DEVICES` plus one, or zero if
+    /// the device is not registered
+    slot: AtomicUsize,
+}
+
+declare_context_type!(DEVICE_REGISTRATION_TYPE_INFO, DeviceRegistration);
+
+/// `EvtCleanupCallback` of the registration of a device, that removes the
+/// device from the registry when the framework cleans it up
+unsafe extern "C" fn evt_device_registration_cleanup(wdf_object: WDFOBJECT) {
+    // SAFETY: The framework passes the device object the registration belongs to,
+    // which is valid until the callback returns. `DEVICE_REGISTRATION_TYPE_INFO`
+    // describes `DeviceRegistration`.
+    let Some(registration) =
+        (unsafe { get_context::<DeviceRegistration>(wdf_object, &DEVICE_REGISTRATION_TYPE_INFO) })
+    else {
+        return;
+    };
+    let slot = registration.slot.swap(0, Ordering::AcqRel);
+    if slot == 0 {
+        return;
+    }
+    let index = slot - 1;
+    DRIVER_DEVICES[index].store(core::ptr::null_mut(), Ordering::SeqCst);
+    // Iterators that read the device before it left its slot are about to
+    // reference it
+    while DRIVER_DEVICE_READERS[index].load(Ordering::SeqCst) != 0 {
+        core::hint::spin_loop();
+    }
+}
+
+/// `EvtDriverUnload` callback that invokes the callbacks registered through
+/// [`Driver::on_evt_driver_unload()`], in the reverse order of their
+/// registration. Set it as the `EvtDriverUnload` of the `WDF_DRIVER_CONFIG`
+/// passed to `WdfDriverCreate`.
+///
+/// # Safety
+///
+/// `wdf_driver` must be the framework driver object of the calling driver. This
+/// is only meant to be called by the framework.
+pub unsafe extern "C" fn evt_driver_unload(wdf_driver: WDFDRIVER) {
+    let driver = Driver { wdf_driver };
+    let len = DRIVER_UNLOAD_CALLBACKS_LEN
+        .load(Ordering::Relaxed)
+        .min(MAX_DRIVER_UNLOAD_CALLBACKS);
+    for callback in DRIVER_UNLOAD_CALLBACKS[..len].iter().rev() {
+        let callback = callback.swap(core::ptr::null_mut(), Ordering::Acquire);
+        if callback.is_null() {
+            continue;
+        }
+        // SAFETY: Non-null slots only ever hold `fn(&Driver)` pointers stored by
+        // `Driver::on_evt_driver_unload`
+        let callback = unsafe { core::mem::transmute::<*mut (), fn(&Driver)>(callback) };
+        callback(&driver);
+    }
+}
@@ -0,0 +1,327 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use wdk_sys::{
+    _WDF_FILEOBJECT_CLASS,
+    _WDF_TRI_STATE,
+    NTSTATUS,
+    ULONG,
+    UNICODE_STRING,
+    WDF_FILEOBJECT_CONFIG,
+    WDF_NO_OBJECT_ATTRIBUTES,
+    WDFDEVICE,
+    WDFDEVICE_INIT,
+    WDFFILEOBJECT,
+    WDFREQUEST,
+    call_unsafe_wdf_function_binding,
+};
+
+use crate::{
+    string::UnicodeStr,
+    wdf::{
+        Device,
+        Request,
+        context::{
+            VALUE_CONTEXT_MAX_ALIGN,
+            set_value_context,
+            value_context,
+            value_context_or_init,
+        },
+    },
+};
+
+/// Largest alignment of the contexts a [`FileObject`] can hold. The framework
+/// aligns the context space of its objects to at least this.
+pub const FILE_OBJECT_CONTEXT_MAX_ALIGN: usize = VALUE_CONTEXT_MAX_ALIGN;
+
+/// Driver-defined callback invoked when an application opens a handle to a
+/// device, ex. with `CreateFile`.
+///
+/// Set with [`FileObjectConfig::evt_device_file_create()`].
+pub trait FileCreateCallback {
+    /// Handles the opening of `file_object` on `device`. The callback must
+    /// complete `request`, with a success status to let the application open
+    /// the handle, or with an error status to fail it. This is typically where
+    /// the state of the handle is set with [`FileObject::set_context()`]. This
+    /// is invoked from the framework's `EvtDeviceFileCreate` callback, at
+    /// `IRQL` = `PASSIVE_LEVEL`. Full documentation is available in the [EvtDeviceFileCreate Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nc-wdfdevice-evt_wdf_device_file_create)
+    fn create(device: &Device, request: Request, file_object: &FileObject);
+}
+
+/// Driver-defined callback invoked when the last handle to a [`FileObject`] is
+/// closed, ex. with `CloseHandle` or because the application exited.
+///
+/// Set with [`FileObjectConfig::evt_file_cleanup()`]. Requests sent through
+/// the file object can still be in flight, so this is where the driver
+/// completes the requests of the handle it holds on to.
+pub trait FileCleanupCallback {
+    /// Handles the cleanup of `file_object`. This is invoked from the
+    /// framework's `EvtFileCleanup` callback, at `IRQL` = `PASSIVE_LEVEL`.
+    /// Full documentation is available in the [EvtFileCleanup Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nc-wdfdevice-evt_wdf_file_cleanup)
+    fn cleanup(file_object: &FileObject);
+}
+
+/// Driver-defined callback invoked when a [`FileObject`] is released, once
+/// all the requests sent through it were completed.
+///
+/// Set with [`FileObjectConfig::evt_file_close()`].
+pub trait FileCloseCallback {
+    /// Handles the close of `file_object`. The context of the file object is
+    /// dropped after this returns. This is invoked from the framework's
+    /// `EvtFileClose` callback, at `IRQL` = `PASSIVE_LEVEL`. Full
+    /// documentation is available in the [EvtFileClose Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nc-wdfdevice-evt_wdf_file_close)
+    fn close(file_object: &FileObject);
+}
+
+/// Configuration of the file objects of a device, i.e. the callbacks invoked
+/// when applications open and close handles to the device.
+///
+/// Built with [`FileObjectConfig::new()`] and the builder methods, and applied
+/// to a device before it is created with [`set_file_object_config()`]. Unset
+/// callbacks keep the framework defaults of `WDF_FILEOBJECT_CONFIG_INIT`,
+/// which let every handle be opened.
+#[derive(Clone, Copy)]
+pub struct FileObjectConfig {
+    config: WDF_FILEOBJECT_CONFIG,
+}
+impl FileObjectConfig {
+    /// Construct a file object configuration without callbacks
+    #[must_use]
+    pub fn new() -> Self {
+        let config_size: ULONG;
+
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            const CONFIG_SIZE: usize = core::mem::size_of::<WDF_FILEOBJECT_CONFIG>();
+
+            // Manually assert there is not truncation since clippy doesn't work for
+            // compile-time constants
+            const { assert!(CONFIG_SIZE <= ULONG::MAX as usize) }
+
+            config_size = CONFIG_SIZE as ULONG;
+        }
+
+        Self {
+            config: WDF_FILEOBJECT_CONFIG {
+                Size: config_size,
+                AutoForwardCleanupClose: _WDF_TRI_STATE::WdfUseDefault,
+                FileObjectClass: _WDF_FILEOBJECT_CLASS::WdfFileObjectWdfCannotUseFsContexts,
+                ..WDF_FILEOBJECT_CONFIG::default()
+            },
+        }
+    }
+
+    /// Set the callback invoked when an application opens a handle to the
+    /// device
+    #[must_use]
+    pub const fn evt_device_file_create<T: FileCreateCallback>(mut self) -> Self {
+        self.config.EvtDeviceFileCreate = Some(evt_device_file_create::<T>);
+        self
+    }
+
+    /// Set the callback invoked when the last handle to a file object is
+    /// closed
+    #[must_use]
+    pub const fn evt_file_cleanup<T: FileCleanupCallback>(mut self) -> Self {
+        self.config.EvtFileCleanup = Some(evt_file_cleanup::<T>);
+        self
+    }
+
+    /// Set the callback invoked when a file object is released
+    #[must_use]
+    pub const fn evt_file_close<T: FileCloseCallback>(mut self) -> Self {
+        self.config.EvtFileClose = Some(evt_file_close::<T>);
+        self
+    }
+}
+impl Default for FileObjectConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Apply `config` to the device that is created with `device_init`. Must be
+/// called before the device is created with `WdfDeviceCreate`, at `IRQL` =
+/// `PASSIVE_LEVEL`.
+///
+/// # Safety
+///
+/// `device_init` must point to a valid `WDFDEVICE_INIT` structure, that was
+/// not passed to `WdfDeviceCreate` yet.
+pub unsafe fn set_file_object_config(device_init: *mut WDFDEVICE_INIT, config: FileObjectConfig) {
+    let mut config = config.config;
+    // SAFETY: The caller guarantees that `device_init` is valid, and `config` is a
+    // fully initialized configuration that outlives the call
+    unsafe {
+        call_unsafe_wdf_function_binding!(
+            WdfDeviceInitSetFileObjectConfig,
+            device_init,
+            &raw mut config,
+            WDF_NO_OBJECT_ATTRIBUTES,
+        );
+    }
+}
+
+/// WDF File Object.
+///
+/// A handle to a framework file object, which represents a handle an
+/// application opened to a device. The file object is owned by the framework,
+/// which deletes it after the handle is closed and all the requests sent
+/// through it are completed.
+///
+/// The state of each handle is kept with its file object:
+/// [`FileObject::set_context()`] moves it into the context space of the file
+/// object in [`FileCreateCallback::create()`], and the request handlers of the
+/// device get it back with [`FileObject::context()`] on
+/// [`Request::file_object()`].
+#[derive(Clone, Copy)]
+pub struct FileObject {
+    wdf_file_object: WDFFILEOBJECT,
+}
+impl FileObject {
+    /// Wrap a handle to a framework file object
+    ///
+    /// # Safety
+    ///
+    /// `file_object` must be a valid handle to a framework file object, that
+    /// stays valid for as long as the returned [`FileObject`] is used.
+    #[must_use]
+    pub const unsafe fn from_raw(file_object: WDFFILEOBJECT) -> Self {
+        Self {
+            wdf_file_object: file_object,
+        }
+    }
+
+    /// Get the raw handle of the framework file object
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFFILEOBJECT {
+        self.wdf_file_object
+    }
+
+    /// Get the device the file object was opened on. Must be called at `IRQL`
+    /// <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn get_device(&self) -> Device {
+        let wdf_device: WDFDEVICE;
+        // SAFETY: `wdf_file_object` is a private member of `FileObject`, which the
+        // caller of `from_raw` guaranteed to be valid
+        unsafe {
+            wdf_device =
+                call_unsafe_wdf_function_binding!(WdfFileObjectGetDevice, self.wdf_file_object);
+        }
+        // SAFETY: The device outlives the file objects opened on it
+        unsafe { Device::from_raw(wdf_device) }
+    }
+
+    /// Get the name of the file the application opened on the device, i.e.
+    /// the part of the path that follows the name of the device, or `None` if
+    /// the application opened the device itself. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn file_name(&self) -> Option<UnicodeStr<'_>> {
+        let file_name: *mut UNICODE_STRING;
+        // SAFETY: `wdf_file_object` is a private member of `FileObject`, which the
+        // caller of `from_raw` guaranteed to be valid
+        unsafe {
+            file_name =
+                call_unsafe_wdf_function_binding!(WdfFileObjectGetFileName, self.wdf_file_object);
+        }
+        if file_name.is_null() {
+            return None;
+        }
+        // SAFETY: The framework returns the name of the file object, which is not
+        // modified and stays valid for as long as the file object is
+        let file_name = unsafe { UnicodeStr::from_raw(file_name) };
+        (!file_name.as_slice().is_empty()).then_some(file_name)
+    }
+
+    /// Move `context` into the context space of the file object, so that it
+    /// can be retrieved with [`FileObject::context()`] by the request handlers
+    /// of the device, and return a reference to it. The context is dropped
+    /// when the framework deletes the file object, after
+    /// [`FileCloseCallback::close()`] returned. A file object holds a single
+    /// context. Must be called at `IRQL` <= `DISPATCH_LEVEL`, typically from
+    /// [`FileCreateCallback::create()`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the context could not be set.
+    /// `context` is dropped in that case:
+    /// * `STATUS_OBJECT_NAME_COLLISION` if the file object already holds a
+    ///   context
+    /// * `STATUS_INSUFFICIENT_RESOURCES` if the context space could not be
+    ///   allocated
+    pub fn set_context<T: Send + Sync + 'static>(&self, context: T) -> Result<&T, NTSTATUS> {
+        // SAFETY: `wdf_file_object` is a private member of `FileObject`, which the
+        // caller of `from_raw` guaranteed to be valid
+        unsafe { set_value_context(self.wdf_file_object.cast(), context) }
+    }
+
+    /// Get the context of the file object set with
+    /// [`FileObject::set_context()`], or `None` if the file object holds no
+    /// context of type `T`
+    #[must_use]
+    pub fn context<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        // SAFETY: `wdf_file_object` is a private member of `FileObject`, which the
+        // caller of `from_raw` guaranteed to be valid
+        unsafe { value_context(self.wdf_file_object.cast()) }
+    }
+
+    /// Get the context of the file object set with
+    /// [`FileObject::set_context()`], or move the context returned by `init`
+    /// into the file object if it holds none yet. `init` is invoked at most
+    /// once, so that concurrent callers all get the same context.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the context could not be set:
+    /// * `STATUS_OBJECT_NAME_COLLISION` if the file object already holds a
+    ///   context of another type than `T`
+    /// * `STATUS_INSUFFICIENT_RESOURCES` if the context space could not be
+    ///   allocated
+    pub fn context_or_init<T: Send + Sync + 'static>(
+        &self,
+        init: impl FnOnce() -> T,
+    ) -> Result<&T, NTSTATUS> {
+        // SAFETY: `wdf_file_object` is a private member of `FileObject`, which the
+        // caller of `from_raw` guaranteed to be valid
+        unsafe { value_context_or_init(self.wdf_file_object.cast(), init) }
+    }
+}
+
+/// `EvtDeviceFileCreate` callback that forwards to
+/// [`FileCreateCallback::create()`]
+unsafe extern "C" fn evt_device_file_create<T: FileCreateCallback>(
+    wdf_device: WDFDEVICE,
+    wdf_request: WDFREQUEST,
+    wdf_file_object: WDFFILEOBJECT,
+) {
+    // SAFETY: The framework passes the device the file object is opened on, which
+    // is valid until the callback returns
+    let device = unsafe { Device::from_raw(wdf_device) };
+    // SAFETY: The framework passes the create request, which the driver owns until
+    // it completes it
+    let request = unsafe { Request::from_raw(wdf_request) };
+    // SAFETY: The framework passes the file object being opened, which is valid
+    // until the callback returns
+    let file_object = unsafe { FileObject::from_raw(wdf_file_object) };
+    T::create(&device, request, &file_object);
+}
+
+/// `EvtFileCleanup` callback that forwards to
+/// [`FileCleanupCallback::cleanup()`]
+unsafe extern "C" fn evt_file_cleanup<T: FileCleanupCallback>(wdf_file_object: WDFFILEOBJECT) {
+    // SAFETY: The framework passes the file object whose last handle was closed,
+    // which is valid until the callback returns
+    let file_object = unsafe { FileObject::from_raw(wdf_file_object) };
+    T::cleanup(&file_object);
+}
+
+/// `EvtFileClose` callback that forwards to [`FileCloseCallback::close()`]
+unsafe extern "C" fn evt_file_close<T: FileCloseCallback>(wdf_file_object: WDFFILEOBJECT) {
+    // SAFETY: The framework passes the file object being released, which is valid
+    // until the callback returns
+    let file_object = unsafe { FileObject::from_raw(wdf_file_object) };
+    T::close(&file_object);
+}
@@ -0,0 +1,318 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Support for HID minidrivers that sit below the `mshidkmdf` pass-through
+//! driver, like the `vhidmini2` sample.
+//!
+//! The HID class driver sends the standard HID requests as internal device
+//! control requests. [`HidMinidriver`] answers the descriptor requests from
+//! the descriptors of the device, and pends the read-report requests in a
+//! manual queue until the driver has input data to complete them with:
+//!
+//! ```rust, ignore
+//! fn evt_io_internal_device_control(&self, request: Request, code: ControlCode) {
+//!     if let Err(request) = self.hid.handle_internal_device_control(request, code) {
+//!         // `IOCTL_HID_WRITE_REPORT` and the other requests of the device
+//!         request.complete(STATUS_NOT_SUPPORTED);
+//!     }
+//! }
+//!
+//! fn evt_interrupt_dpc(&self) {
+//!     let _ = self.hid.complete_read_report(&self.read_input_report());
+//! }
+//! ```
+
+use wdk_sys::{
+    FILE_DEVICE_KEYBOARD,
+    NTSTATUS,
+    STATUS_INVALID_BUFFER_SIZE,
+    STATUS_SUCCESS,
+    WDFDEVICE,
+};
+
+use crate::{
+    ControlCode,
+    RequiredAccess,
+    TransferMethod,
+    wdf::{NotificationChannel, Request},
+};
+
+/// `HID_CTL_CODE` of `hidport.h`
+const fn hid_ctl_code(function: u32) -> ControlCode {
+    ControlCode::new(
+        FILE_DEVICE_KEYBOARD,
+        function,
+        TransferMethod::Neither,
+        RequiredAccess::Any,
+    )
+}
+
+/// Request for the `HID_DESCRIPTOR` of the device
+pub const IOCTL_HID_GET_DEVICE_DESCRIPTOR: ControlCode = hid_ctl_code(0);
+/// Request for the report descriptor of the device
+pub const IOCTL_HID_GET_REPORT_DESCRIPTOR: ControlCode = hid_ctl_code(1);
+/// Request for the next input report of the device
+pub const IOCTL_HID_READ_REPORT: ControlCode = hid_ctl_code(2);
+/// Request to send an output report to the device
+pub const IOCTL_HID_WRITE_REPORT: ControlCode = hid_ctl_code(3);
+/// Request for the `HID_DEVICE_ATTRIBUTES` of the device
+pub const IOCTL_HID_GET_DEVICE_ATTRIBUTES: ControlCode = hid_ctl_code(9);
+
+/// Length of a `HID_DESCRIPTOR` with a single report descriptor
+const HID_DESCRIPTOR_LENGTH: usize = 9;
+/// Length of `HID_DEVICE_ATTRIBUTES`
+const HID_DEVICE_ATTRIBUTES_LENGTH: usize = 32;
+
+/// Identity of a HID device, as returned for
+/// [`IOCTL_HID_GET_DEVICE_ATTRIBUTES`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HidDeviceAttributes {
+    /// Vendor ID of the device
+    pub vendor_id: u16,
+    /// Product ID of the device
+    pub product_id: u16,
+    /// Version number of the device
+    pub version_number: u16,
+}
+impl HidDeviceAttributes {
+    /// Encode the attributes as a `HID_DEVICE_ATTRIBUTES`
+    #[must_use]
+    pub const fn to_bytes(self) -> [u8; HID_DEVICE_ATTRIBUTES_LENGTH] {
+        let mut bytes = [0; HID_DEVICE_ATTRIBUTES_LENGTH];
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            bytes[0] = HID_DEVICE_ATTRIBUTES_LENGTH as u8;
+        }
+        let vendor_id = self.vendor_id.to_le_bytes();
+        bytes[4] = vendor_id[0];
+        bytes[5] = vendor_id[1];
+        let product_id = self.product_id.to_le_bytes();
+        bytes[6] = product_id[0];
+        bytes[7] = product_id[1];
+        let version_number = self.version_number.to_le_bytes();
+        bytes[8] = version_number[0];
+        bytes[9] = version_number[1];
+        bytes
+    }
+}
+
+/// Report descriptor of a HID device, which describes the reports the device
+/// sends and receives
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HidReportDescriptor<'a> {
+    bytes: &'a [u8],
+}
+impl<'a> HidReportDescriptor<'a> {
+    /// Wrap the bytes of a report descriptor
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_INVALID_BUFFER_SIZE` if `bytes` is
+    /// longer than a `HID_DESCRIPTOR` can describe.
+    pub const fn try_new(bytes: &'a [u8]) -> Result<Self, NTSTATUS> {
+        if bytes.len() > u16::MAX as usize {
+            return Err(STATUS_INVALID_BUFFER_SIZE);
+        }
+        Ok(Self { bytes })
+    }
+
+    /// Get the bytes of the report descriptor
+    #[must_use]
+    pub const fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Encode the `HID_DESCRIPTOR` that points the HID class driver to the
+    /// report descriptor, as returned for [`IOCTL_HID_GET_DEVICE_DESCRIPTOR`]
+    #[must_use]
+    pub const fn hid_descriptor(&self) -> [u8; HID_DESCRIPTOR_LENGTH] {
+        let descriptor_length: u8;
+        let report_descriptor_length: [u8; 2];
+        // The length of the report descriptor was checked to fit in 16 bits by
+        // `try_new`, and clippy::cast_possible_truncation cannot currently check
+        // compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            descriptor_length = HID_DESCRIPTOR_LENGTH as u8;
+            report_descriptor_length = (self.bytes.len() as u16).to_le_bytes();
+        }
+        [
+            descriptor_length,
+            0x21, // bDescriptorType: HID
+            0x00, // bcdHID: 1.00
+            0x01,
+            0x00, // bCountry
+            0x01, // bNumDescriptors
+            0x22, // bReportType: report
+            report_descriptor_length[0],
+            report_descriptor_length[1],
+        ]
+    }
+}
+
+/// Handler of the standard requests the HID class driver sends to a HID
+/// minidriver.
+///
+/// The descriptor and attribute requests are completed from the descriptors
+/// the handler is constructed with. The read-report requests are pended in a
+/// [`NotificationChannel`], whose manual queue is not power-managed, until
+/// [`HidMinidriver::complete_read_report()`] completes the oldest one with an
+/// input report, typically from the interrupt handling of the driver.
+pub struct HidMinidriver {
+    report_descriptor: HidReportDescriptor<'static>,
+    attributes: HidDeviceAttributes,
+    read_reports: NotificationChannel,
+}
+impl HidMinidriver {
+    /// Try to construct a HID minidriver handler for `device`, with the
+    /// report descriptor and attributes of the device. The manual queue of
+    /// the read-report requests is deleted along with `device`. Must be called
+    /// at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct the queue
+    /// of the read-report requests. The error variant will contain a
+    /// [`NTSTATUS`] of the failure. Full error documentation is available in
+    /// the [WDFIoQueue Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfio/nf-wdfio-wdfioqueuecreate#return-value)
+    ///
+    /// # Safety
+    ///
+    /// `device` must be a valid handle to a framework device object.
+    pub unsafe fn try_new(
+        device: WDFDEVICE,
+        report_descriptor: HidReportDescriptor<'static>,
+        attributes: HidDeviceAttributes,
+    ) -> Result<Self, NTSTATUS> {
+        Ok(Self {
+            report_descriptor,
+            attributes,
+            // SAFETY: The caller guarantees that `device` is a valid device handle
+            read_reports: unsafe { NotificationChannel::try_new(device) }?,
+        })
+    }
+
+    /// Try to construct a HID minidriver handler for `device`. This is an
+    /// alias for [`HidMinidriver::try_new()`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct the queue
+    /// of the read-report requests. The error variant will contain a
+    /// [`NTSTATUS`] of the failure. Full error documentation is available in
+    /// the [WDFIoQueue Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfio/nf-wdfio-wdfioqueuecreate#return-value)
+    ///
+    /// # Safety
+    ///
+    /// `device` must be a valid handle to a framework device object.
+    pub unsafe fn create(
+        device: WDFDEVICE,
+        report_descriptor: HidReportDescriptor<'static>,
+        attributes: HidDeviceAttributes,
+    ) -> Result<Self, NTSTATUS> {
+        // SAFETY: The caller upholds the safety contract of `try_new`
+        unsafe { Self::try_new(device, report_descriptor, attributes) }
+    }
+
+    /// Handle `request`, an internal device control request with `code`, if
+    /// it is one of [`IOCTL_HID_GET_DEVICE_DESCRIPTOR`],
+    /// [`IOCTL_HID_GET_REPORT_DESCRIPTOR`],
+    /// [`IOCTL_HID_GET_DEVICE_ATTRIBUTES`] or [`IOCTL_HID_READ_REPORT`]. The
+    /// descriptor requests are completed, while read-report requests are
+    /// pended until [`HidMinidriver::complete_read_report()`]. This is
+    /// typically called from `EvtIoInternalDeviceControl`. Must be called at
+    /// `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `request` if it is another request, ex.
+    /// [`IOCTL_HID_WRITE_REPORT`], so that the driver handles it. The driver
+    /// still owns the request in that case.
+    pub fn handle_internal_device_control(
+        &self,
+        request: Request,
+        code: ControlCode,
+    ) -> Result<(), Request> {
+        match code {
+            IOCTL_HID_GET_DEVICE_DESCRIPTOR => {
+                complete_with_data(request, &self.report_descriptor.hid_descriptor());
+            }
+            IOCTL_HID_GET_REPORT_DESCRIPTOR => {
+                complete_with_data(request, self.report_descriptor.as_bytes());
+            }
+            IOCTL_HID_GET_DEVICE_ATTRIBUTES => {
+                complete_with_data(request, &self.attributes.to_bytes());
+            }
+            IOCTL_HID_READ_REPORT => {
+                if let Err((request, nt_status)) = self.read_reports.pend(request) {
+                    request.complete(nt_status);
+                }
+            }
+            _ => return Err(request),
+        }
+        Ok(())
+    }
+
+    /// Complete the oldest pended read-report request with the input report
+    /// `report`, which is copied to the output buffer of the request. This is
+    /// typically called when the device signals new input data, ex. from the
+    /// DPC of its interrupt. Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the report could not be
+    /// delivered:
+    /// * `STATUS_NO_MORE_ENTRIES` if no read-report request is pended. The
+    ///   driver can keep the report until the HID class driver sends a new
+    ///   request.
+    /// * The error of retrieving the output buffer of the request, ex.
+    ///   `STATUS_BUFFER_TOO_SMALL` if it cannot hold `report`. The request is
+    ///   completed with this error.
+    pub fn complete_read_report(&self, report: &[u8]) -> Result<(), NTSTATUS> {
+        self.read_reports.notify(report)
+    }
+}
+
+/// Complete `request` with `data` copied to its output buffer, or with the
+/// error of retrieving the buffer
+fn complete_with_data(mut request: Request, data: &[u8]) {
+    match request.retrieve_output_buffer(data.len()) {
+        Ok(buffer) => buffer[..data.len()].copy_from_slice(data),
+        Err(nt_status) => {
+            request.complete(nt_status);
+            return;
+        }
+    }
+    request.complete_with_information(STATUS_SUCCESS, data.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_codes_match_hidport() {
+        assert_eq!(IOCTL_HID_GET_DEVICE_DESCRIPTOR.as_raw(), 0x000B_0003);
+        assert_eq!(IOCTL_HID_READ_REPORT.as_raw(), 0x000B_000B);
+        assert_eq!(IOCTL_HID_GET_DEVICE_ATTRIBUTES.as_raw(), 0x000B_0027);
+    }
+
+    #[test]
+    fn descriptors_are_encoded() {
+        let report_descriptor = HidReportDescriptor::try_new(&[0xA1, 0x01, 0xC0]).unwrap();
+        assert_eq!(
+            report_descriptor.hid_descriptor(),
+            [0x09, 0x21, 0x00, 0x01, 0x00, 0x01, 0x22, 0x03, 0x00]
+        );
+
+        let attributes = HidDeviceAttributes {
+            vendor_id: 0xDEED,
+            product_id: 0xFEED,
+            version_number: 0x0101,
+        }
+        .to_bytes();
+        assert_eq!(attributes[0], 32);
+        assert_eq!(attributes[4..10], [0xED, 0xDE, 0xED, 0xFE, 0x01, 0x01]);
+    }
+}
@@ -0,0 +1,352 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use wdk_sys::{
+    _WDF_RELEASE_HARDWARE_ORDER_ON_FAILURE,
+    _WDF_TRI_STATE,
+    BOOLEAN,
+    NTSTATUS,
+    ULONG,
+    WDF_INTERRUPT_CONFIG,
+    WDF_NO_OBJECT_ATTRIBUTES,
+    WDFDEVICE,
+    WDFINTERRUPT,
+    WDFOBJECT,
+    call_unsafe_wdf_function_binding,
+};
+
+use crate::{nt_success, wdf::Device};
+
+/// Driver-defined interrupt service routine of an [`Interrupt`].
+///
+/// Set with [`InterruptConfig::new()`].
+pub trait InterruptIsr {
+    /// Handles an interrupt, and returns `true` if it was raised by the device
+    /// of `interrupt`. `message_id` is the index of the message that raised the
+    /// interrupt for message-signaled interrupts, and 0 otherwise.
+    ///
+    /// The routine runs at the `IRQL` of the device, with the lock of
+    /// `interrupt` held, so it must only acknowledge the interrupt on the
+    /// device, save what the rest of the handling needs and defer it with
+    /// [`Interrupt::queue_dpc_for_isr()`]. This is invoked from the framework's
+    /// `EvtInterruptIsr` callback. Full documentation is available in the [EvtInterruptIsr Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfinterrupt/nc-wdfinterrupt-evt_wdf_interrupt_isr)
+    fn isr(interrupt: &Interrupt, message_id: ULONG) -> bool;
+}
+
+/// Driver-defined deferred procedure call of an [`Interrupt`], which handles
+/// the interrupts that the [`InterruptIsr`] deferred.
+///
+/// Set with [`InterruptConfig::evt_interrupt_dpc()`].
+pub trait InterruptDpc {
+    /// Handles the interrupts deferred with [`Interrupt::queue_dpc_for_isr()`]
+    /// since the last call, ex. by completing the requests the device
+    /// finished. The state shared with the [`InterruptIsr`] must be accessed
+    /// with the lock of [`Interrupt::acquire_lock()`] held. This is invoked
+    /// from the framework's `EvtInterruptDpc` callback, at `IRQL` =
+    /// `DISPATCH_LEVEL`. Full documentation is available in the [EvtInterruptDpc Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfinterrupt/nc-wdfinterrupt-evt_wdf_interrupt_dpc)
+    fn dpc(interrupt: &Interrupt, device: &Device);
+}
+
+/// Driver-defined callback that enables an [`Interrupt`] on its device.
+///
+/// Set with [`InterruptConfig::evt_interrupt_enable()`].
+pub trait InterruptEnable {
+    /// Enables the interrupts of the device, ex. by setting its interrupt
+    /// mask, and returns `STATUS_SUCCESS` if it succeeded. This is invoked from
+    /// the framework's `EvtInterruptEnable` callback when the device enters
+    /// its working state, at the `IRQL` of the device with the lock of
+    /// `interrupt` held. Full documentation is available in the [EvtInterruptEnable Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfinterrupt/nc-wdfinterrupt-evt_wdf_interrupt_enable)
+    fn enable(interrupt: &Interrupt, device: &Device) -> NTSTATUS;
+}
+
+/// Driver-defined callback that disables an [`Interrupt`] on its device.
+///
+/// Set with [`InterruptConfig::evt_interrupt_disable()`].
+pub trait InterruptDisable {
+    /// Disables the interrupts of the device, and returns `STATUS_SUCCESS` if
+    /// it succeeded. This is invoked from the framework's
+    /// `EvtInterruptDisable` callback when the device leaves its working
+    /// state, at the `IRQL` of the device with the lock of `interrupt` held.
+    /// Full documentation is available in the [EvtInterruptDisable Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfinterrupt/nc-wdfinterrupt-evt_wdf_interrupt_disable)
+    fn disable(interrupt: &Interrupt, device: &Device) -> NTSTATUS;
+}
+
+/// Configuration of an [`Interrupt`], i.e. its callbacks.
+///
+/// Built with [`InterruptConfig::new()`], which sets the interrupt service
+/// routine, and the builder methods for the optional callbacks. Settings that
+/// are not set keep the framework defaults of `WDF_INTERRUPT_CONFIG_INIT`.
+#[derive(Clone, Copy)]
+pub struct InterruptConfig {
+    config: WDF_INTERRUPT_CONFIG,
+}
+impl InterruptConfig {
+    /// Construct an interrupt configuration whose interrupt service routine is
+    /// `T`
+    #[must_use]
+    pub fn new<T: InterruptIsr>() -> Self {
+        let config_size: ULONG;
+
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            const CONFIG_SIZE: usize = core::mem::size_of::<WDF_INTERRUPT_CONFIG>();
+
+            // Manually assert there is not truncation since clippy doesn't work for
+            // compile-time constants
+            const { assert!(CONFIG_SIZE <= ULONG::MAX as usize) }
+
+            config_size = CONFIG_SIZE as ULONG;
+        }
+
+        Self {
+            config: WDF_INTERRUPT_CONFIG {
+                Size: config_size,
+                ShareVector: _WDF_TRI_STATE::WdfUseDefault,
+                EvtInterruptIsr: Some(evt_interrupt_isr::<T>),
+                ReportInactiveOnPowerDown: _WDF_TRI_STATE::WdfUseDefault,
+                ReleaseHardwareOrderOnFailure:
+                    _WDF_RELEASE_HARDWARE_ORDER_ON_FAILURE::WdfReleaseHardwareOrderOnFailureEarly,
+                ..WDF_INTERRUPT_CONFIG::default()
+            },
+        }
+    }
+
+    /// Set the deferred procedure call that handles the interrupts the
+    /// interrupt service routine deferred
+    #[must_use]
+    pub const fn evt_interrupt_dpc<T: InterruptDpc>(mut self) -> Self {
+        self.config.EvtInterruptDpc = Some(evt_interrupt_dpc::<T>);
+        self
+    }
+
+    /// Set the callback that enables the interrupts of the device
+    #[must_use]
+    pub const fn evt_interrupt_enable<T: InterruptEnable>(mut self) -> Self {
+        self.config.EvtInterruptEnable = Some(evt_interrupt_enable::<T>);
+        self
+    }
+
+    /// Set the callback that disables the interrupts of the device
+    #[must_use]
+    pub const fn evt_interrupt_disable<T: InterruptDisable>(mut self) -> Self {
+        self.config.EvtInterruptDisable = Some(evt_interrupt_disable::<T>);
+        self
+    }
+
+    /// Set whether the deferred procedure call of the interrupt is serialized
+    /// with the callbacks of the device and its queues, as configured by their
+    /// [`crate::wdf::SynchronizationScope`]
+    #[must_use]
+    pub fn automatic_serialization(mut self, serialize: bool) -> Self {
+        self.config.AutomaticSerialization = u8::from(serialize);
+        self
+    }
+}
+
+/// WDF Interrupt.
+///
+/// An interrupt of a device, whose hardware resource the framework assigns
+/// when the device is started. The framework connects the interrupt when the
+/// device enters its working state, and disconnects it when the device leaves
+/// it. The interrupt is a child of its device, which deletes it when the
+/// device is removed.
+///
+/// Interrupts are handled in two steps: the [`InterruptIsr`] runs at the
+/// `IRQL` of the device, acknowledges the interrupt and defers the rest of
+/// the handling to the [`InterruptDpc`], which runs at `DISPATCH_LEVEL`. The
+/// state they share is protected by the lock of the interrupt, which the
+/// framework holds while the interrupt service routine runs, and which the
+/// other callbacks take with [`Interrupt::acquire_lock()`].
+#[derive(Clone, Copy)]
+pub struct Interrupt {
+    wdf_interrupt: WDFINTERRUPT,
+}
+impl Interrupt {
+    /// Try to construct a WDF Interrupt object for `device`. Must be called at
+    /// `IRQL` = `PASSIVE_LEVEL`, from `EvtDriverDeviceAdd` or
+    /// `EvtDevicePrepareHardware`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct an
+    /// interrupt. The error variant will contain a [`NTSTATUS`] of the
+    /// failure. Full error documentation is available in the [WDFInterrupt Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfinterrupt/nf-wdfinterrupt-wdfinterruptcreate#return-value)
+    pub fn try_new(device: &Device, config: InterruptConfig) -> Result<Self, NTSTATUS> {
+        let mut config = config.config;
+        let mut interrupt = Self {
+            wdf_interrupt: core::ptr::null_mut(),
+        };
+
+        let nt_status;
+        // SAFETY: `device` is a valid device handle, and the resulting ffi object is
+        // stored in a private member and not accessible outside of this module, and
+        // this module guarantees that it is always in a valid state.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfInterruptCreate,
+                device.as_raw(),
+                &raw mut config,
+                WDF_NO_OBJECT_ATTRIBUTES,
+                &mut interrupt.wdf_interrupt as *mut WDFINTERRUPT,
+            );
+        }
+        nt_success(nt_status).then_some(interrupt).ok_or(nt_status)
+    }
+
+    /// Try to construct a WDF Interrupt object for `device`. This is an alias
+    /// for [`Interrupt::try_new()`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct an
+    /// interrupt. The error variant will contain a [`NTSTATUS`] of the
+    /// failure. Full error documentation is available in the [WDFInterrupt Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfinterrupt/nf-wdfinterrupt-wdfinterruptcreate#return-value)
+    pub fn create(device: &Device, config: InterruptConfig) -> Result<Self, NTSTATUS> {
+        Self::try_new(device, config)
+    }
+
+    /// Get the raw handle of the framework interrupt object
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFINTERRUPT {
+        self.wdf_interrupt
+    }
+
+    /// Get the device of the interrupt
+    #[must_use]
+    pub fn get_device(&self) -> Device {
+        let wdf_device: WDFDEVICE;
+        // SAFETY: `wdf_interrupt` is a private member of `Interrupt`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            wdf_device =
+                call_unsafe_wdf_function_binding!(WdfInterruptGetDevice, self.wdf_interrupt);
+        }
+        // SAFETY: The device outlives its interrupts
+        unsafe { Device::from_raw(wdf_device) }
+    }
+
+    /// Queue the [`InterruptDpc`] of the interrupt, typically from the
+    /// [`InterruptIsr`]. Returns `true` if it was queued, and `false` if it was
+    /// already queued and has not run yet.
+    #[must_use]
+    pub fn queue_dpc_for_isr(&self) -> bool {
+        let queued;
+        // SAFETY: `wdf_interrupt` is a private member of `Interrupt`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            queued =
+                call_unsafe_wdf_function_binding!(WdfInterruptQueueDpcForIsr, self.wdf_interrupt);
+        }
+        queued != 0
+    }
+
+    /// Acquire the lock of the interrupt, which raises the `IRQL` to the one of
+    /// the device so that the [`InterruptIsr`] cannot run until the returned
+    /// guard is dropped. Must be called at `IRQL` <= `DISPATCH_LEVEL`, and not
+    /// from the callbacks that run with the lock held, ex. the
+    /// [`InterruptIsr`].
+    #[must_use]
+    pub fn acquire_lock(&self) -> InterruptLockGuard<'_> {
+        // SAFETY: `wdf_interrupt` is a private member of `Interrupt`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfInterruptAcquireLock, self.wdf_interrupt);
+        }
+        InterruptLockGuard { interrupt: self }
+    }
+
+    /// Enable the interrupt, i.e. invoke its [`InterruptEnable`] callback.
+    /// Must be called at `IRQL` = `PASSIVE_LEVEL`, while the device is in its
+    /// working state.
+    pub fn enable(&self) {
+        // SAFETY: `wdf_interrupt` is a private member of `Interrupt`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfInterruptEnable, self.wdf_interrupt);
+        }
+    }
+
+    /// Disable the interrupt, i.e. invoke its [`InterruptDisable`] callback.
+    /// Must be called at `IRQL` = `PASSIVE_LEVEL`, while the device is in its
+    /// working state.
+    pub fn disable(&self) {
+        // SAFETY: `wdf_interrupt` is a private member of `Interrupt`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfInterruptDisable, self.wdf_interrupt);
+        }
+    }
+}
+
+/// Guard of the lock of an [`Interrupt`], returned by
+/// [`Interrupt::acquire_lock()`]. The lock is released when the guard is
+/// dropped.
+#[must_use = "if unused the lock is released immediately"]
+pub struct InterruptLockGuard<'a> {
+    interrupt: &'a Interrupt,
+}
+impl Drop for InterruptLockGuard<'_> {
+    fn drop(&mut self) {
+        // SAFETY: The lock was acquired in `Interrupt::acquire_lock()`, and the
+        // guard is its only owner
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfInterruptReleaseLock,
+                self.interrupt.wdf_interrupt
+            );
+        }
+    }
+}
+
+/// `EvtInterruptIsr` callback that forwards to [`InterruptIsr::isr()`]
+unsafe extern "C" fn evt_interrupt_isr<T: InterruptIsr>(
+    wdf_interrupt: WDFINTERRUPT,
+    message_id: ULONG,
+) -> BOOLEAN {
+    let interrupt = Interrupt { wdf_interrupt };
+    u8::from(T::isr(&interrupt, message_id))
+}
+
+/// `EvtInterruptDpc` callback that forwards to [`InterruptDpc::dpc()`]
+unsafe extern "C" fn evt_interrupt_dpc<T: InterruptDpc>(
+    wdf_interrupt: WDFINTERRUPT,
+    associated_object: WDFOBJECT,
+) {
+    let interrupt = Interrupt { wdf_interrupt };
+    // SAFETY: Interrupts are created with their device as parent, which the
+    // framework passes as the associated object
+    let device = unsafe { Device::from_raw(associated_object.cast()) };
+    T::dpc(&interrupt, &device);
+}
+
+/// `EvtInterruptEnable` callback that forwards to
+/// [`InterruptEnable::enable()`]
+unsafe extern "C" fn evt_interrupt_enable<T: InterruptEnable>(
+    wdf_interrupt: WDFINTERRUPT,
+    wdf_device: WDFDEVICE,
+) -> NTSTATUS {
+    let interrupt = Interrupt { wdf_interrupt };
+    // SAFETY: The framework passes the device of the interrupt, which is valid
+    // until the callback returns
+    let device = unsafe { Device::from_raw(wdf_device) };
+    T::enable(&interrupt, &device)
+}
+
+/// `EvtInterruptDisable` callback that forwards to
+/// [`InterruptDisable::disable()`]
+unsafe extern "C" fn evt_interrupt_disable<T: InterruptDisable>(
+    wdf_interrupt: WDFINTERRUPT,
+    wdf_device: WDFDEVICE,
+) -> NTSTATUS {
+    let interrupt = Interrupt { wdf_interrupt };
+    // SAFETY: The framework passes the device of the interrupt, which is valid
+    // until the callback returns
+    let device = unsafe { Device::from_raw(wdf_device) };
+    T::disable(&interrupt, &device)
+}
@@ -0,0 +1,693 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use wdk_sys::{
+    _WDF_IO_QUEUE_DISPATCH_TYPE,
+    _WDF_IO_QUEUE_STATE,
+    _WDF_REQUEST_STOP_ACTION_FLAGS,
+    NTSTATUS,
+    PFN_WDF_IO_QUEUE_STATE,
+    ULONG,
+    WDF_IO_QUEUE_CONFIG,
+    WDF_IO_QUEUE_STATE,
+    WDF_OBJECT_ATTRIBUTES,
+    WDFCONTEXT,
+    WDFDEVICE,
+    WDFQUEUE,
+    WDFREQUEST,
+    call_unsafe_wdf_function_binding,
+};
+
+use crate::{
+    PassiveLevel,
+    nt_success,
+    wdf::{
+        Device,
+        FileObject,
+        Request,
+        context::{initialize_value_context, value_context, value_context_attributes},
+    },
+};
+
+/// Driver-defined callback invoked when an [`IoQueue`] state change completes.
+///
+/// State changes are requested with [`IoQueue::stop_with_callback()`],
+/// [`IoQueue::purge_with_callback()`], [`IoQueue::drain_with_callback()`] or
+/// [`IoQueue::stop_and_purge_with_callback()`].
+pub trait IoQueueStateCallback {
+    /// Handles the completion of the state change of `queue`. This is invoked
+    /// from the framework's `EvtIoQueueState` callback, at `IRQL` <=
+    /// `DISPATCH_LEVEL`. Full documentation is available in the [EvtIoQueueState Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfio/nc-wdfio-evt_wdf_io_queue_state)
+    fn state_changed(queue: &IoQueue);
+}
+
+/// Driver-defined callback invoked for each request the driver owns when a
+/// power-managed [`IoQueue`] stops, ex. because the device leaves its working
+/// state.
+///
+/// Set with [`IoQueue::set_evt_io_stop()`]. Drivers that hold on to requests
+/// must handle this, or the power transition waits for the requests forever.
+pub trait IoQueueStopCallback {
+    /// Handles the stop of `queue` for `request`. The driver still owns the
+    /// request wherever it holds on to it, so `request` is only borrowed: the
+    /// callback must either complete the request the driver owns, ex. found by
+    /// [`Request::as_raw()`], acknowledge the stop with
+    /// [`Request::stop_acknowledge()`], or acknowledge it and return the
+    /// request to `queue` with [`Request::stop_acknowledge_and_requeue()`].
+    /// This is invoked from the framework's `EvtIoStop` callback, at `IRQL` <=
+    /// `DISPATCH_LEVEL`. Full documentation is available in the [EvtIoStop Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfio/nc-wdfio-evt_wdf_io_queue_io_stop)
+    fn stop(queue: &IoQueue, request: &Request, flags: RequestStopActionFlags);
+}
+
+/// Driver-defined callback invoked for each request the driver acknowledged
+/// the stop of without requeuing it, when a power-managed [`IoQueue`] resumes.
+///
+/// Set with [`IoQueue::set_evt_io_resume()`].
+pub trait IoQueueResumeCallback {
+    /// Handles the resume of `queue` for `request`, which the driver still owns
+    /// wherever it holds on to it, so it is only borrowed. This is invoked from
+    /// the framework's `EvtIoResume` callback, at `IRQL` <= `DISPATCH_LEVEL`. Full documentation is available in the [EvtIoResume Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfio/nc-wdfio-evt_wdf_io_queue_io_resume)
+    fn resume(queue: &IoQueue, request: &Request);
+}
+
+// The flags are positive, but bindgen types them as the signed C enum type
+#[allow(clippy::cast_sign_loss)]
+const STOP_ACTION_PURGE: ULONG = _WDF_REQUEST_STOP_ACTION_FLAGS::WdfRequestStopActionPurge as ULONG;
+#[allow(clippy::cast_sign_loss)]
+const STOP_REQUEST_CANCELABLE: ULONG =
+    _WDF_REQUEST_STOP_ACTION_FLAGS::WdfRequestStopRequestCancelable as ULONG;
+
+/// Reason an [`IoQueue`] stops, as passed to [`IoQueueStopCallback::stop`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestStopAction {
+    /// The device is leaving its working state and the queue will resume
+    /// later. The driver can keep the request after acknowledging the stop.
+    Suspend,
+    /// The device is being removed. The driver must complete the request or
+    /// requeue it.
+    Purge,
+}
+
+/// Flags of an `EvtIoStop` callback, as passed to
+/// [`IoQueueStopCallback::stop`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RequestStopActionFlags {
+    flags: ULONG,
+}
+impl RequestStopActionFlags {
+    /// Get the reason the queue stops
+    #[must_use]
+    pub const fn action(&self) -> RequestStopAction {
+        if self.flags & STOP_ACTION_PURGE != 0 {
+            RequestStopAction::Purge
+        } else {
+            RequestStopAction::Suspend
+        }
+    }
+
+    /// Returns `true` if the request is cancelable, in which case the driver
+    /// must make it non-cancelable before completing it or acknowledging the
+    /// stop
+    #[must_use]
+    pub const fn is_request_cancelable(&self) -> bool {
+        self.flags & STOP_REQUEST_CANCELABLE != 0
+    }
+}
+
+/// Dispatching of the requests of an [`IoQueue`] to the request handlers of
+/// the driver.
+///
+/// Set on the [`WDF_IO_QUEUE_CONFIG`] a queue is created with, using
+/// [`IoQueueDispatchType::apply()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoQueueDispatchType {
+    /// Deliver requests one at a time, once the driver completed or requeued
+    /// the previous one
+    Sequential,
+    /// Deliver requests as soon as they arrive, so that the driver processes
+    /// several of them at a time
+    Parallel {
+        /// Maximum number of requests of the queue the driver owns at a time,
+        /// or `None` for no limit. Requests that are pended by the driver,
+        /// ex. to complete them later, count towards the limit.
+        presented_requests_limit: Option<ULONG>,
+    },
+    /// Do not deliver requests. The driver retrieves them from the queue.
+    Manual,
+}
+
+impl IoQueueDispatchType {
+    /// Set the dispatch type in `queue_config`, which the queue is then
+    /// created with
+    pub fn apply(self, queue_config: &mut WDF_IO_QUEUE_CONFIG) {
+        queue_config.DispatchType = match self {
+            Self::Sequential => _WDF_IO_QUEUE_DISPATCH_TYPE::WdfIoQueueDispatchSequential,
+            Self::Parallel {
+                presented_requests_limit,
+            } => {
+                // `ULONG::MAX`, i.e. `(ULONG)-1`, is how the framework spells no limit
+                queue_config.Settings.Parallel.NumberOfPresentedRequests =
+                    presented_requests_limit.unwrap_or(ULONG::MAX);
+                _WDF_IO_QUEUE_DISPATCH_TYPE::WdfIoQueueDispatchParallel
+            }
+            Self::Manual => _WDF_IO_QUEUE_DISPATCH_TYPE::WdfIoQueueDispatchManual,
+        };
+    }
+}
+
+/// WDF I/O Queue.
+///
+/// I/O queues deliver the requests a device receives to the driver's request
+/// handlers, which are set through the [`WDF_IO_QUEUE_CONFIG`] the queue is
+/// created with, along with its [`IoQueueDispatchType`]. The queue is deleted
+/// along with its device. The framework can serialize the request handlers of
+/// the queue, when its attributes are set up with
+/// [`crate::wdf::SynchronizationScope::apply()`] and
+/// [`crate::wdf::ExecutionLevel::apply()`].
+///
+/// When a device is surprise removed, requests that are still queued or in
+/// flight have to be flushed before the device goes away. [`IoQueue::purge()`]
+/// cancels the queued requests and stops the queue from accepting new ones,
+/// while [`IoQueue::drain()`] lets the queued requests be delivered but stops
+/// the queue from accepting new ones.
+pub struct IoQueue {
+    wdf_queue: WDFQUEUE,
+}
+impl IoQueue {
+    /// Wrap a handle to a framework queue object
+    ///
+    /// # Safety
+    ///
+    /// `queue` must be a valid handle to a framework queue object, that stays
+    /// valid for as long as the returned [`IoQueue`] is used.
+    #[must_use]
+    pub const unsafe fn from_raw(queue: WDFQUEUE) -> Self {
+        Self { wdf_queue: queue }
+    }
+
+    /// Get the raw handle of the framework queue object
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFQUEUE {
+        self.wdf_queue
+    }
+
+    /// Get the device the queue belongs to, ex. to get the context of the
+    /// device with [`Device::context()`] in the request handlers of the queue.
+    /// Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn get_device(&self) -> Device {
+        let wdf_device: WDFDEVICE;
+        // SAFETY: `wdf_queue` is a private member of `IoQueue`, originally created by
+        // WDF, and this module guarantees that it is always in a valid state.
+        unsafe {
+            wdf_device = call_unsafe_wdf_function_binding!(WdfIoQueueGetDevice, self.wdf_queue);
+        }
+        // SAFETY: A queue is a child of its device, so the device outlives the queue
+        unsafe { Device::from_raw(wdf_device) }
+    }
+
+    /// Try to construct a WDF I/O Queue object for `device`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a queue.
+    /// The error variant will contain a [`NTSTATUS`] of the failure. Full error
+    /// documentation is available in the [WDFIoQueue Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfio/nf-wdfio-wdfioqueuecreate#return-value)
+    ///
+    /// # Safety
+    ///
+    /// `device` must be a valid handle to a framework device object.
+    pub unsafe fn try_new(
+        device: WDFDEVICE,
+        queue_config: &mut WDF_IO_QUEUE_CONFIG,
+        attributes: &mut WDF_OBJECT_ATTRIBUTES,
+    ) -> Result<Self, NTSTATUS> {
+        let mut queue = Self {
+            wdf_queue: core::ptr::null_mut(),
+        };
+
+        let nt_status;
+        // SAFETY: The caller guarantees that `device` is a valid device handle. The
+        // resulting ffi object is stored in a private member and not accessible
+        // outside of this module, and this module guarantees that it is always in a
+        // valid state.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfIoQueueCreate,
+                device,
+                queue_config,
+                attributes,
+                &mut queue.wdf_queue as *mut WDFQUEUE,
+            );
+        }
+        nt_success(nt_status).then_some(queue).ok_or(nt_status)
+    }
+
+    /// Try to construct a WDF I/O Queue object for `device`. This is an alias
+    /// for [`IoQueue::try_new()`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a queue.
+    /// The error variant will contain a [`NTSTATUS`] of the failure. Full error
+    /// documentation is available in the [WDFIoQueue Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfio/nf-wdfio-wdfioqueuecreate#return-value)
+    ///
+    /// # Safety
+    ///
+    /// `device` must be a valid handle to a framework device object.
+    pub unsafe fn create(
+        device: WDFDEVICE,
+        queue_config: &mut WDF_IO_QUEUE_CONFIG,
+        attributes: &mut WDF_OBJECT_ATTRIBUTES,
+    ) -> Result<Self, NTSTATUS> {
+        // SAFETY: The caller upholds the safety contract of `try_new`
+        unsafe { Self::try_new(device, queue_config, attributes) }
+    }
+
+    /// Try to construct a WDF I/O Queue object for `device` that holds
+    /// `context` from its creation on, so that its callbacks can retrieve it
+    /// with [`IoQueue::context()`]. The context space is allocated together
+    /// with the queue, and `context` is stored before the queue is returned,
+    /// so before the driver can start the queue or dispatch requests to it.
+    /// The context is dropped when the queue is deleted.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a queue.
+    /// `context` is dropped in that case. The error variant will contain a
+    /// [`NTSTATUS`] of the failure. Full error documentation is available in
+    /// the [WDFIoQueue Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfio/nf-wdfio-wdfioqueuecreate#return-value)
+    ///
+    /// # Safety
+    ///
+    /// `device` must be a valid handle to a framework device object.
+    pub unsafe fn create_with_context<T: Send + Sync + 'static>(
+        device: WDFDEVICE,
+        queue_config: &mut WDF_IO_QUEUE_CONFIG,
+        context: T,
+    ) -> Result<Self, NTSTATUS> {
+        // SAFETY: The caller upholds the safety contract of `try_new`
+        let queue =
+            unsafe { Self::try_new(device, queue_config, &mut value_context_attributes::<T>()) }?;
+        // SAFETY: The queue was just created with the value context space of `T`, and
+        // was never returned to the caller, so nothing else uses it
+        if let Err(nt_status) = unsafe { initialize_value_context(queue.wdf_queue.cast(), context) }
+        {
+            // SAFETY: The queue was never returned to the caller, so it is only used
+            // here
+            unsafe {
+                call_unsafe_wdf_function_binding!(WdfObjectDelete, queue.wdf_queue.cast());
+            }
+            return Err(nt_status);
+        }
+        Ok(queue)
+    }
+
+    /// Get the context of the queue given to
+    /// [`IoQueue::create_with_context()`], or `None` if the queue holds no
+    /// context of type `T`
+    #[must_use]
+    pub fn context<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        // SAFETY: `wdf_queue` is a private member of `IoQueue`, originally created by
+        // WDF, and this module guarantees that it is always in a valid state.
+        unsafe { value_context(self.wdf_queue.cast()) }
+    }
+
+    /// Set `EvtIoStop` in `queue_config`, so that
+    /// [`IoQueueStopCallback::stop`] of `T` is invoked for each request the
+    /// driver owns when the queue stops
+    pub fn set_evt_io_stop<T: IoQueueStopCallback>(queue_config: &mut WDF_IO_QUEUE_CONFIG) {
+        queue_config.EvtIoStop = Some(evt_io_stop::<T>);
+    }
+
+    /// Set `EvtIoResume` in `queue_config`, so that
+    /// [`IoQueueResumeCallback::resume`] of `T` is invoked for each request
+    /// the driver kept when the queue resumes
+    pub fn set_evt_io_resume<T: IoQueueResumeCallback>(queue_config: &mut WDF_IO_QUEUE_CONFIG) {
+        queue_config.EvtIoResume = Some(evt_io_resume::<T>);
+    }
+
+    /// Start the queue, so that it accepts new requests and delivers them to
+    /// the driver
+    pub fn start(&self) {
+        // SAFETY: `wdf_queue` is a private member of `IoQueue`, originally created by
+        // WDF, and this module guarantees that it is always in a valid state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfIoQueueStart, self.wdf_queue);
+        }
+    }
+
+    /// Stop the queue from delivering requests to the driver. The queue keeps
+    /// accepting new requests. This returns immediately, without waiting for
+    /// the requests delivered to the driver to complete.
+    pub fn stop(&self) {
+        self.stop_with(None);
+    }
+
+    /// Stop the queue from delivering requests to the driver, and invoke
+    /// [`IoQueueStateCallback::state_changed`] once all requests delivered to
+    /// the driver have completed
+    pub fn stop_with_callback<T: IoQueueStateCallback>(&self) {
+        self.stop_with(Some(evt_io_queue_state::<T>));
+    }
+
+    /// Stop the queue from delivering requests to the driver, and wait for all
+    /// requests delivered to the driver to complete.
+    pub fn stop_synchronously(&self, _irql: PassiveLevel) {
+        // SAFETY: `wdf_queue` is a private member of `IoQueue`, originally created by
+        // WDF, and this module guarantees that it is always in a valid state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfIoQueueStopSynchronously, self.wdf_queue);
+        }
+    }
+
+    /// Purge the queue: it stops accepting new requests and cancels the
+    /// requests that are queued. This returns immediately, without waiting for
+    /// the requests delivered to the driver to complete.
+    pub fn purge(&self) {
+        self.purge_with(None);
+    }
+
+    /// Purge the queue, and invoke [`IoQueueStateCallback::state_changed`]
+    /// once all requests delivered to the driver have completed
+    pub fn purge_with_callback<T: IoQueueStateCallback>(&self) {
+        self.purge_with(Some(evt_io_queue_state::<T>));
+    }
+
+    /// Purge the queue, and wait for all requests delivered to the driver to
+    /// complete.
+    pub fn purge_synchronously(&self, _irql: PassiveLevel) {
+        // SAFETY: `wdf_queue` is a private member of `IoQueue`, originally created by
+        // WDF, and this module guarantees that it is always in a valid state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfIoQueuePurgeSynchronously, self.wdf_queue);
+        }
+    }
+
+    /// Stop and purge the queue: it stops delivering requests to the driver
+    /// and cancels the requests that are queued, as well as the requests
+    /// delivered to the driver that are cancelable, but keeps accepting new
+    /// requests, so that it can be restarted with [`IoQueue::start()`]. This
+    /// returns immediately, without waiting for the requests delivered to the
+    /// driver to complete.
+    pub fn stop_and_purge(&self) {
+        self.stop_and_purge_with(None);
+    }
+
+    /// Stop and purge the queue, and invoke
+    /// [`IoQueueStateCallback::state_changed`] once all requests delivered to
+    /// the driver have completed
+    pub fn stop_and_purge_with_callback<T: IoQueueStateCallback>(&self) {
+        self.stop_and_purge_with(Some(evt_io_queue_state::<T>));
+    }
+
+    /// Stop and purge the queue, and wait for all requests delivered to the
+    /// driver to complete.
+    pub fn stop_and_purge_synchronously(&self, _irql: PassiveLevel) {
+        // SAFETY: `wdf_queue` is a private member of `IoQueue`, originally created by
+        // WDF, and this module guarantees that it is always in a valid state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfIoQueueStopAndPurgeSynchronously, self.wdf_queue);
+        }
+    }
+
+    /// Drain the queue: it stops accepting new requests, but keeps delivering
+    /// the requests that are queued. This returns immediately, without waiting
+    /// for the queued requests to complete.
+    pub fn drain(&self) {
+        self.drain_with(None);
+    }
+
+    /// Drain the queue, and invoke [`IoQueueStateCallback::state_changed`]
+    /// once all queued requests and requests delivered to the driver have
+    /// completed
+    pub fn drain_with_callback<T: IoQueueStateCallback>(&self) {
+        self.drain_with(Some(evt_io_queue_state::<T>));
+    }
+
+    /// Drain the queue, and wait for all queued requests and requests delivered
+    /// to the driver to complete.
+    pub fn drain_synchronously(&self, _irql: PassiveLevel) {
+        // SAFETY: `wdf_queue` is a private member of `IoQueue`, originally created by
+        // WDF, and this module guarantees that it is always in a valid state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfIoQueueDrainSynchronously, self.wdf_queue);
+        }
+    }
+
+    /// Get the current state of the queue, along with the number of requests
+    /// that are queued and delivered to the driver
+    #[must_use]
+    pub fn get_state(&self) -> IoQueueState {
+        let mut queue_requests: ULONG = 0;
+        let mut driver_requests: ULONG = 0;
+
+        let state;
+        // SAFETY: `wdf_queue` is a private member of `IoQueue`, originally created by
+        // WDF, and this module guarantees that it is always in a valid state.
+        unsafe {
+            state = call_unsafe_wdf_function_binding!(
+                WdfIoQueueGetState,
+                self.wdf_queue,
+                &mut queue_requests as *mut ULONG,
+                &mut driver_requests as *mut ULONG,
+            );
+        }
+        IoQueueState {
+            state,
+            queue_requests,
+            driver_requests,
+        }
+    }
+
+    /// Retrieve the request at the head of the queue, so that the driver owns
+    /// it. This is how drivers get the requests of queues with
+    /// [`IoQueueDispatchType::Manual`] dispatching, ex. to drain requests that
+    /// were parked in the queue until the device could handle them. Must be
+    /// called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if no request could be retrieved:
+    /// * `STATUS_NO_MORE_ENTRIES` if the queue is empty
+    /// * `STATUS_WDF_PAUSED` if the queue is stopped
+    /// * `STATUS_INVALID_DEVICE_REQUEST` if the queue does not use manual
+    ///   dispatching
+    ///
+    /// Full error documentation is available in the [WdfIoQueueRetrieveNextRequest Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfio/nf-wdfio-wdfioqueueretrievenextrequest#return-value)
+    pub fn retrieve_next_request(&self) -> Result<Request, NTSTATUS> {
+        let mut wdf_request: WDFREQUEST = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `wdf_queue` is a private member of `IoQueue`, originally created by
+        // WDF, and this module guarantees that it is always in a valid state.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfIoQueueRetrieveNextRequest,
+                self.wdf_queue,
+                &raw mut wdf_request,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        // SAFETY: The request was just retrieved from the queue, so it is owned by the
+        // driver until it completes or requeues it
+        Ok(unsafe { Request::from_raw(wdf_request) })
+    }
+
+    /// Retrieve the first request of the queue that was sent through
+    /// `file_object`, so that the driver owns it, ex. to complete the
+    /// requests of an application that closes its handle. Must be called at
+    /// `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if no request could be retrieved:
+    /// * `STATUS_NO_MORE_ENTRIES` if the queue holds no request of
+    ///   `file_object`
+    /// * `STATUS_WDF_PAUSED` if the queue is stopped
+    /// * `STATUS_INVALID_DEVICE_REQUEST` if the queue does not use manual
+    ///   dispatching
+    ///
+    /// Full error documentation is available in the [WdfIoQueueRetrieveRequestByFileObject Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfio/nf-wdfio-wdfioqueueretrieverequestbyfileobject#return-value)
+    pub fn retrieve_request_by_file_object(
+        &self,
+        file_object: &FileObject,
+    ) -> Result<Request, NTSTATUS> {
+        let mut wdf_request: WDFREQUEST = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `wdf_queue` is a private member of `IoQueue`, originally created by
+        // WDF, and this module guarantees that it is always in a valid state. The
+        // caller of `FileObject::from_raw` guaranteed that `file_object` is valid.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfIoQueueRetrieveRequestByFileObject,
+                self.wdf_queue,
+                file_object.as_raw(),
+                &raw mut wdf_request,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        // SAFETY: The request was just retrieved from the queue, so it is owned by the
+        // driver until it completes or requeues it
+        Ok(unsafe { Request::from_raw(wdf_request) })
+    }
+
+    fn stop_with(&self, stop_complete: PFN_WDF_IO_QUEUE_STATE) {
+        // SAFETY: `wdf_queue` is a private member of `IoQueue`, originally created by
+        // WDF, and this module guarantees that it is always in a valid state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfIoQueueStop,
+                self.wdf_queue,
+                stop_complete,
+                core::ptr::null_mut(),
+            );
+        }
+    }
+
+    fn purge_with(&self, purge_complete: PFN_WDF_IO_QUEUE_STATE) {
+        // SAFETY: `wdf_queue` is a private member of `IoQueue`, originally created by
+        // WDF, and this module guarantees that it is always in a valid state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfIoQueuePurge,
+                self.wdf_queue,
+                purge_complete,
+                core::ptr::null_mut(),
+            );
+        }
+    }
+
+    fn stop_and_purge_with(&self, stop_and_purge_complete: PFN_WDF_IO_QUEUE_STATE) {
+        // SAFETY: `wdf_queue` is a private member of `IoQueue`, originally created by
+        // WDF, and this module guarantees that it is always in a valid state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfIoQueueStopAndPurge,
+                self.wdf_queue,
+                stop_and_purge_complete,
+                core::ptr::null_mut(),
+            );
+        }
+    }
+
+    fn drain_with(&self, drain_complete: PFN_WDF_IO_QUEUE_STATE) {
+        // SAFETY: `wdf_queue` is a private member of `IoQueue`, originally created by
+        // WDF, and this module guarantees that it is always in a valid state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfIoQueueDrain,
+                self.wdf_queue,
+                drain_complete,
+                core::ptr::null_mut(),
+            );
+        }
+    }
+}
+
+/// State of an [`IoQueue`], as returned by [`IoQueue::get_state()`]
+#[derive(Clone, Copy, Debug)]
+pub struct IoQueueState {
+    state: WDF_IO_QUEUE_STATE,
+    queue_requests: ULONG,
+    driver_requests: ULONG,
+}
+impl IoQueueState {
+    /// Returns `true` if the queue accepts new requests
+    #[must_use]
+    pub const fn accepts_requests(&self) -> bool {
+        self.has(_WDF_IO_QUEUE_STATE::WdfIoQueueAcceptRequests)
+    }
+
+    /// Returns `true` if the queue delivers requests to the driver
+    #[must_use]
+    pub const fn dispatches_requests(&self) -> bool {
+        self.has(_WDF_IO_QUEUE_STATE::WdfIoQueueDispatchRequests)
+    }
+
+    /// Returns `true` if the queue both accepts new requests and delivers them
+    /// to the driver
+    #[must_use]
+    pub const fn is_ready(&self) -> bool {
+        self.accepts_requests() && self.dispatches_requests()
+    }
+
+    /// Returns `true` if the queue has no queued requests and no requests
+    /// delivered to the driver
+    #[must_use]
+    pub const fn is_idle(&self) -> bool {
+        self.has(_WDF_IO_QUEUE_STATE::WdfIoQueueNoRequests)
+            && self.has(_WDF_IO_QUEUE_STATE::WdfIoQueueDriverNoRequests)
+    }
+
+    /// Returns `true` if the framework holds the queue, ex. because the device
+    /// left its working state, so that it does not deliver requests even when
+    /// started
+    #[must_use]
+    pub const fn is_pnp_held(&self) -> bool {
+        self.has(_WDF_IO_QUEUE_STATE::WdfIoQueuePnpHeld)
+    }
+
+    /// Get the number of requests that are queued, at the time the state was
+    /// retrieved
+    #[must_use]
+    pub const fn queue_requests(&self) -> ULONG {
+        self.queue_requests
+    }
+
+    /// Get the number of requests that were delivered to the driver and are
+    /// not completed yet, at the time the state was retrieved
+    #[must_use]
+    pub const fn driver_requests(&self) -> ULONG {
+        self.driver_requests
+    }
+
+    const fn has(&self, flag: WDF_IO_QUEUE_STATE) -> bool {
+        self.state & flag != 0
+    }
+}
+
+/// `EvtIoQueueState` callback that forwards to
+/// [`IoQueueStateCallback::state_changed`]
+unsafe extern "C" fn evt_io_queue_state<T: IoQueueStateCallback>(
+    wdf_queue: WDFQUEUE,
+    _context: WDFCONTEXT,
+) {
+    T::state_changed(&IoQueue { wdf_queue });
+}
+
+/// `EvtIoStop` callback that forwards to [`IoQueueStopCallback::stop`]
+unsafe extern "C" fn evt_io_stop<T: IoQueueStopCallback>(
+    wdf_queue: WDFQUEUE,
+    wdf_request: WDFREQUEST,
+    action_flags: ULONG,
+) {
+    // SAFETY: The framework passes a request the driver owns, which stays valid
+    // until the driver completes it. It is only lent to the callback, which
+    // cannot complete it through this `Request`.
+    let request = unsafe { Request::from_raw(wdf_request) };
+    T::stop(
+        &IoQueue { wdf_queue },
+        &request,
+        RequestStopActionFlags {
+            flags: action_flags,
+        },
+    );
+}
+
+/// `EvtIoResume` callback that forwards to [`IoQueueResumeCallback::resume`]
+unsafe extern "C" fn evt_io_resume<T: IoQueueResumeCallback>(
+    wdf_queue: WDFQUEUE,
+    wdf_request: WDFREQUEST,
+) {
+    // SAFETY: The framework passes a request the driver owns, which stays valid
+    // until the driver completes it. It is only lent to the callback, which
+    // cannot complete it through this `Request`.
+    let request = unsafe { Request::from_raw(wdf_request) };
+    T::resume(&IoQueue { wdf_queue }, &request);
+}
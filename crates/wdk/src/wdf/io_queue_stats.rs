@@ -0,0 +1,515 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{
+    cell::UnsafeCell,
+    ffi::c_void,
+    marker::PhantomData,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use wdk_sys::{
+    NTSTATUS,
+    STATUS_CANCELLED,
+    ULONG64,
+    WDFOBJECT,
+    WDFQUEUE,
+    WDFREQUEST,
+    WDFSPINLOCK,
+    call_unsafe_wdf_function_binding,
+};
+
+use crate::{
+    nt_success,
+    time::interrupt_time,
+    wdf::{
+        IoQueue,
+        Request,
+        RequestParameters,
+        context::{
+            allocate_context,
+            allocate_context_with_attributes,
+            declare_context_type,
+            get_context,
+            inherited_object_attributes,
+            object_attributes,
+        },
+    },
+};
+
+/// Request received by an [`IoQueue`] that the driver did not complete yet, as
+/// returned by [`IoQueue::in_flight_requests()`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InFlightRequest {
+    id: u64,
+    age: Duration,
+    params: RequestParameters,
+}
+impl InFlightRequest {
+    /// Get the identifier of the request, i.e. the number of requests the
+    /// queue received before it
+    #[must_use]
+    pub const fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Get the time since the request was received by the queue
+    #[must_use]
+    pub const fn age(&self) -> Duration {
+        self.age
+    }
+
+    /// Get the parameters of the request, as when it was received
+    #[must_use]
+    pub const fn params(&self) -> RequestParameters {
+        self.params
+    }
+}
+
+/// Iterator over the [`InFlightRequest`]s of an [`IoQueue`], from the most
+/// recently received, as returned by [`IoQueue::in_flight_requests()`].
+///
+/// The iterator holds the spin lock of the in-flight requests of the queue, so
+/// the requests are not completed while it is in use, and the thread using it
+/// runs at `IRQL` = `DISPATCH_LEVEL`. Drop it as soon as possible, and do not
+/// complete requests received by the queue while it is in use, as completing
+/// them waits for the lock.
+pub struct InFlightRequests<'a> {
+    lock: WDFSPINLOCK,
+    next: *const RequestTracking,
+    now: ULONG64,
+    _queue: PhantomData<&'a IoQueue>,
+}
+impl Iterator for InFlightRequests<'_> {
+    type Item = InFlightRequest;
+
+    fn next(&mut self) -> Option<InFlightRequest> {
+        // SAFETY: The lock of the in-flight requests is held, so the requests in the
+        // list, and their tracking contexts, are not deleted
+        let tracking = unsafe { self.next.as_ref() }?;
+        self.next = tracking.next.load(Ordering::Relaxed);
+        // SAFETY: The parameters are written before the request is added to the list
+        let params = unsafe { tracking.params.get().read() };
+        Some(InFlightRequest {
+            id: tracking.id.load(Ordering::Relaxed),
+            age: interrupt_time_to_duration(
+                self.now
+                    .saturating_sub(tracking.received_at.load(Ordering::Relaxed)),
+            ),
+            // SAFETY: The parameters are written before the request is added to the list
+            params: unsafe { params.assume_init() },
+        })
+    }
+}
+impl Drop for InFlightRequests<'_> {
+    fn drop(&mut self) {
+        if self.lock.is_null() {
+            return;
+        }
+        // SAFETY: The lock was acquired by `IoQueue::in_flight_requests`, and is a
+        // child of the queue, which the iterator borrows
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfSpinLockRelease, self.lock);
+        }
+    }
+}
+
+/// Request statistics of an [`IoQueue`], as returned by [`IoQueue::stats()`].
+///
+/// Only requests the driver passed to [`IoQueue::record_received()`] are
+/// counted. Their latency is the time from that call until the request is
+/// completed with [`Request::complete()`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IoQueueStats {
+    received: u64,
+    completed: u64,
+    cancelled: u64,
+    total_latency: Duration,
+    max_latency: Duration,
+}
+impl IoQueueStats {
+    /// Get the number of requests received by the queue
+    #[must_use]
+    pub const fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// Get the number of received requests that were completed, including the
+    /// cancelled ones
+    #[must_use]
+    pub const fn completed(&self) -> u64 {
+        self.completed
+    }
+
+    /// Get the number of received requests that were completed with
+    /// `STATUS_CANCELLED`
+    #[must_use]
+    pub const fn cancelled(&self) -> u64 {
+        self.cancelled
+    }
+
+    /// Get the number of received requests that are not completed yet
+    #[must_use]
+    pub const fn in_flight(&self) -> u64 {
+        self.received.saturating_sub(self.completed)
+    }
+
+    /// Get the average latency of the completed requests, or `None` if no
+    /// request was completed yet
+    #[must_use]
+    pub fn average_latency(&self) -> Option<Duration> {
+        let completed = u32::try_from(self.completed).unwrap_or(u32::MAX);
+        (completed != 0).then(|| self.total_latency / completed)
+    }
+
+    /// Get the highest latency of the completed requests
+    #[must_use]
+    pub const fn max_latency(&self) -> Duration {
+        self.max_latency
+    }
+}
+
+impl IoQueue {
+    /// Record that `request` was received by the queue, so that it is counted
+    /// in the [`IoQueue::stats()`] of the queue. Call this at the start of the
+    /// request handlers of the queue. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    ///
+    /// The request is listed by [`IoQueue::in_flight_requests()`] until it is
+    /// completed. Statistics are best effort: the request is not counted if the
+    /// framework fails to allocate the memory to track it.
+    pub fn record_received(&self, request: &Request) {
+        // SAFETY: `wdf_queue` is a private member of `IoQueue`, originally created by
+        // WDF. `QUEUE_STATS_TYPE_INFO` describes `QueueStats`, which is valid when
+        // all-zero.
+        let Ok(stats) = (unsafe {
+            allocate_context::<QueueStats>(self.as_raw().cast(), &QUEUE_STATS_TYPE_INFO)
+        }) else {
+            return;
+        };
+        let mut attributes = object_attributes(&REQUEST_TRACKING_TYPE_INFO);
+        attributes.EvtCleanupCallback = Some(evt_request_tracking_cleanup);
+        // SAFETY: `request` is a valid request owned by the driver. `attributes`
+        // describes `RequestTracking`, which is valid when all-zero.
+        let Ok(tracking) = (unsafe {
+            allocate_context_with_attributes::<RequestTracking>(
+                request.as_raw().cast(),
+                &mut attributes,
+            )
+        }) else {
+            return;
+        };
+
+        // A request forwarded from another queue is only in flight on this one
+        let previous_queue: WDFQUEUE = tracking.queue.load(Ordering::Acquire).cast();
+        if !previous_queue.is_null() {
+            // SAFETY: The queue the request was received by outlives the request
+            unsafe {
+                untrack(previous_queue, tracking);
+            }
+        }
+        tracking
+            .received_at
+            .store(interrupt_time(), Ordering::Relaxed);
+        tracking.id.store(
+            stats.received.fetch_add(1, Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+        tracking
+            .queue
+            .store(self.as_raw().cast(), Ordering::Release);
+
+        // SAFETY: `wdf_queue` is a private member of `IoQueue`, originally created by
+        // WDF
+        let Some(lock) = (unsafe { in_flight_lock(stats, self.as_raw()) }) else {
+            return;
+        };
+        let params = request.params();
+        // SAFETY: The lock is a child of the queue
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfSpinLockAcquire, lock);
+        }
+        // SAFETY: The request is not in the list yet, so the parameters are not read
+        // concurrently
+        unsafe {
+            tracking.params.get().write(MaybeUninit::new(params));
+        }
+        link(stats, tracking);
+        // SAFETY: The lock was acquired above
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfSpinLockRelease, lock);
+        }
+    }
+
+    /// Iterate over the requests received by the queue that the driver did not
+    /// complete yet, ex. to dump them for diagnostics, or to flag the requests
+    /// older than a threshold from a watchdog timer. Only requests the driver
+    /// passed to [`IoQueue::record_received()`] are listed. Must be called at
+    /// `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// The requests are not completed while the returned iterator is in use,
+    /// as it holds a spin lock, see [`InFlightRequests`].
+    #[must_use]
+    pub fn in_flight_requests(&self) -> InFlightRequests<'_> {
+        let mut requests = InFlightRequests {
+            lock: core::ptr::null_mut(),
+            next: core::ptr::null(),
+            now: interrupt_time(),
+            _queue: PhantomData,
+        };
+        // SAFETY: `wdf_queue` is a private member of `IoQueue`, originally created by
+        // WDF. `QUEUE_STATS_TYPE_INFO` describes `QueueStats`.
+        let Some(stats) =
+            (unsafe { get_context::<QueueStats>(self.as_raw().cast(), &QUEUE_STATS_TYPE_INFO) })
+        else {
+            return requests;
+        };
+        // SAFETY: `wdf_queue` is a private member of `IoQueue`, originally created by
+        // WDF
+        let Some(lock) = (unsafe { in_flight_lock(stats, self.as_raw()) }) else {
+            return requests;
+        };
+        // SAFETY: The lock is a child of the queue, and is released when the iterator
+        // is dropped
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfSpinLockAcquire, lock);
+        }
+        requests.lock = lock;
+        requests.next = stats.in_flight_head.load(Ordering::Relaxed);
+        requests
+    }
+
+    /// Get the request statistics of the queue. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn stats(&self) -> IoQueueStats {
+        // SAFETY: `wdf_queue` is a private member of `IoQueue`, originally created by
+        // WDF. `QUEUE_STATS_TYPE_INFO` describes `QueueStats`.
+        let Some(stats) =
+            (unsafe { get_context::<QueueStats>(self.as_raw().cast(), &QUEUE_STATS_TYPE_INFO) })
+        else {
+            return IoQueueStats::default();
+        };
+        IoQueueStats {
+            received: stats.received.load(Ordering::Relaxed),
+            completed: stats.completed.load(Ordering::Relaxed),
+            cancelled: stats.cancelled.load(Ordering::Relaxed),
+            total_latency: interrupt_time_to_duration(stats.total_latency.load(Ordering::Relaxed)),
+            max_latency: interrupt_time_to_duration(stats.max_latency.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Record the completion of `wdf_request` with `status` in the statistics of
+/// the queue it was received by, if it was passed to
+/// [`IoQueue::record_received()`]
+///
+/// # Safety
+///
+/// `wdf_request` must be a valid request owned by the driver, that is not
+/// completed yet
+pub(super) unsafe fn record_completed(wdf_request: WDFREQUEST, status: NTSTATUS) {
+    // SAFETY: The caller guarantees that `wdf_request` is valid.
+    // `REQUEST_TRACKING_TYPE_INFO` describes `RequestTracking`.
+    let Some(tracking) = (unsafe {
+        get_context::<RequestTracking>(wdf_request.cast(), &REQUEST_TRACKING_TYPE_INFO)
+    }) else {
+        return;
+    };
+    let wdf_queue: WDFQUEUE = tracking.queue.load(Ordering::Acquire).cast();
+    if wdf_queue.is_null() {
+        return;
+    }
+    // SAFETY: The queue the request was received by outlives the request
+    unsafe {
+        untrack(wdf_queue, tracking);
+    }
+    // SAFETY: The queue the request was received by outlives the request.
+    // `QUEUE_STATS_TYPE_INFO` describes `QueueStats`.
+    let Some(stats) =
+        (unsafe { get_context::<QueueStats>(wdf_queue.cast(), &QUEUE_STATS_TYPE_INFO) })
+    else {
+        return;
+    };
+    let latency = interrupt_time().saturating_sub(tracking.received_at.load(Ordering::Relaxed));
+    stats.completed.fetch_add(1, Ordering::Relaxed);
+    if status == STATUS_CANCELLED {
+        stats.cancelled.fetch_add(1, Ordering::Relaxed);
+    }
+    stats.total_latency.fetch_add(latency, Ordering::Relaxed);
+    stats.max_latency.fetch_max(latency, Ordering::Relaxed);
+}
+
+/// Request statistics of a queue, stored in a context of the queue object.
+/// Latencies are in 100-nanosecond intervals.
+#[repr(C)]
+struct QueueStats {
+    received: AtomicU64,
+    completed: AtomicU64,
+    cancelled: AtomicU64,
+    total_latency: AtomicU64,
+    max_latency: AtomicU64,
+    /// Spin lock of the list of in-flight requests, created on first use
+    in_flight_lock: AtomicPtr<c_void>,
+    /// Most recently received request of the list of in-flight requests. Only
+    /// accessed with `in_flight_lock` held.
+    in_flight_head: AtomicPtr<RequestTracking>,
+}
+
+/// Tracking of a received request, stored in a context of the request object
+#[repr(C)]
+struct RequestTracking {
+    /// Interrupt time the request was received at
+    received_at: AtomicU64,
+    /// Identifier of the request in the queue it was received by
+    id: AtomicU64,
+    /// Queue the request was received by
+    queue: AtomicPtr<c_void>,
+    /// Links of the list of in-flight requests of the queue. Only accessed with
+    /// the lock of the list held.
+    linked: AtomicBool,
+    previous: AtomicPtr<RequestTracking>,
+    next: AtomicPtr<RequestTracking>,
+    /// Parameters of the request when it was received. Written before the
+    /// request is added to the list of in-flight requests.
+    params: UnsafeCell<MaybeUninit<RequestParameters>>,
+}
+
+declare_context_type!(QUEUE_STATS_TYPE_INFO, QueueStats);
+declare_context_type!(REQUEST_TRACKING_TYPE_INFO, RequestTracking);
+
+/// Get the spin lock of the in-flight requests of `wdf_queue`, whose
+/// statistics are `stats`, creating it on first use. Returns `None` if the
+/// framework fails to create it.
+///
+/// # Safety
+///
+/// `wdf_queue` must be a valid queue, whose statistics context is `stats`
+unsafe fn in_flight_lock(stats: &QueueStats, wdf_queue: WDFQUEUE) -> Option<WDFSPINLOCK> {
+    let lock: WDFSPINLOCK = stats.in_flight_lock.load(Ordering::Acquire).cast();
+    if !lock.is_null() {
+        return Some(lock);
+    }
+
+    let mut attributes = inherited_object_attributes();
+    attributes.ParentObject = wdf_queue.cast();
+    let mut new_lock: WDFSPINLOCK = core::ptr::null_mut();
+    let nt_status;
+    // SAFETY: `attributes` outlives the call. The lock is a child of the queue,
+    // which the caller guarantees to be valid, so it lives as long as `stats`.
+    unsafe {
+        nt_status = call_unsafe_wdf_function_binding!(
+            WdfSpinLockCreate,
+            &raw mut attributes,
+            &raw mut new_lock
+        );
+    }
+    if !nt_success(nt_status) {
+        return None;
+    }
+    match stats.in_flight_lock.compare_exchange(
+        core::ptr::null_mut(),
+        new_lock.cast(),
+        Ordering::AcqRel,
+        Ordering::Acquire,
+    ) {
+        Ok(_) => Some(new_lock),
+        Err(lock) => {
+            // SAFETY: Another thread created the lock first, so `new_lock` was never
+            // shared
+            unsafe {
+                call_unsafe_wdf_function_binding!(WdfObjectDelete, new_lock.cast());
+            }
+            Some(lock.cast())
+        }
+    }
+}
+
+/// Add `tracking` to the front of the in-flight requests of the queue whose
+/// statistics are `stats`. The lock of the list must be held.
+fn link(stats: &QueueStats, tracking: &RequestTracking) {
+    let tracking_ptr = core::ptr::from_ref(tracking).cast_mut();
+    let head = stats.in_flight_head.load(Ordering::Relaxed);
+    tracking
+        .previous
+        .store(core::ptr::null_mut(), Ordering::Relaxed);
+    tracking.next.store(head, Ordering::Relaxed);
+    // SAFETY: The lock of the list is held, so the requests in it, and their
+    // tracking contexts, are not deleted
+    if let Some(head) = unsafe { head.as_ref() } {
+        head.previous.store(tracking_ptr, Ordering::Relaxed);
+    }
+    stats.in_flight_head.store(tracking_ptr, Ordering::Relaxed);
+    tracking.linked.store(true, Ordering::Relaxed);
+}
+
+/// Remove `tracking` from the in-flight requests of `wdf_queue`, if it is in
+/// the list
+///
+/// # Safety
+///
+/// `wdf_queue` must be a valid queue, that `tracking` was received by
+unsafe fn untrack(wdf_queue: WDFQUEUE, tracking: &RequestTracking) {
+    // SAFETY: The caller guarantees that `wdf_queue` is valid.
+    // `QUEUE_STATS_TYPE_INFO` describes `QueueStats`.
+    let Some(stats) =
+        (unsafe { get_context::<QueueStats>(wdf_queue.cast(), &QUEUE_STATS_TYPE_INFO) })
+    else {
+        return;
+    };
+    let lock: WDFSPINLOCK = stats.in_flight_lock.load(Ordering::Acquire).cast();
+    if lock.is_null() {
+        return;
+    }
+    // SAFETY: The lock is a child of the queue, which the caller guarantees to be
+    // valid
+    unsafe {
+        call_unsafe_wdf_function_binding!(WdfSpinLockAcquire, lock);
+    }
+    if tracking.linked.swap(false, Ordering::Relaxed) {
+        let previous = tracking.previous.load(Ordering::Relaxed);
+        let next = tracking.next.load(Ordering::Relaxed);
+        // SAFETY: The lock of the list is held, so the requests in it, and their
+        // tracking contexts, are not deleted
+        match unsafe { previous.as_ref() } {
+            Some(previous) => previous.next.store(next, Ordering::Relaxed),
+            None => stats.in_flight_head.store(next, Ordering::Relaxed),
+        }
+        // SAFETY: The lock of the list is held, so the requests in it, and their
+        // tracking contexts, are not deleted
+        if let Some(next) = unsafe { next.as_ref() } {
+            next.previous.store(previous, Ordering::Relaxed);
+        }
+    }
+    // SAFETY: The lock was acquired above
+    unsafe {
+        call_unsafe_wdf_function_binding!(WdfSpinLockRelease, lock);
+    }
+}
+
+/// `EvtCleanupCallback` of the tracking context of a request, that removes the
+/// request from the in-flight requests of its queue if it was completed
+/// without [`Request::complete()`], ex. by the framework
+unsafe extern "C" fn evt_request_tracking_cleanup(wdf_object: WDFOBJECT) {
+    // SAFETY: The framework passes the request, which is valid until the callback
+    // returns. `REQUEST_TRACKING_TYPE_INFO` describes `RequestTracking`.
+    let Some(tracking) =
+        (unsafe { get_context::<RequestTracking>(wdf_object, &REQUEST_TRACKING_TYPE_INFO) })
+    else {
+        return;
+    };
+    let wdf_queue: WDFQUEUE = tracking.queue.load(Ordering::Acquire).cast();
+    if wdf_queue.is_null() {
+        return;
+    }
+    // SAFETY: The queue the request was received by outlives the request
+    unsafe {
+        untrack(wdf_queue, tracking);
+    }
+}
+
+const fn interrupt_time_to_duration(intervals: ULONG64) -> Duration {
+    Duration::from_nanos(intervals.saturating_mul(100))
+}
@@ -0,0 +1,708 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::time::Duration;
+
+use wdk_sys::{
+    _WDF_IO_TARGET_OPEN_TYPE,
+    _WDF_REQUEST_SEND_OPTIONS_FLAGS,
+    ACCESS_MASK,
+    FILE_NON_DIRECTORY_FILE,
+    FILE_OPEN,
+    LONGLONG,
+    NTSTATUS,
+    PWDF_REQUEST_COMPLETION_PARAMS,
+    ULONG,
+    ULONG_PTR,
+    WDF_IO_TARGET_OPEN_PARAMS,
+    WDF_IO_TARGET_SENT_IO_ACTION,
+    WDF_REQUEST_COMPLETION_PARAMS,
+    WDF_REQUEST_SEND_OPTIONS,
+    WDF_REQUEST_TYPE,
+    WDFCONTEXT,
+    WDFIOTARGET,
+    WDFMEMORY,
+    WDFREQUEST,
+    call_unsafe_wdf_function_binding,
+};
+
+use crate::{
+    ControlCode,
+    nt_success,
+    string::UnicodeStr,
+    wdf::{
+        Device,
+        Memory,
+        Request,
+        context::inherited_object_attributes,
+        memory::{buffer_descriptor, descriptor_ptr},
+        request::send_options,
+    },
+};
+
+// The flags are positive, but bindgen types them as the signed C enum type
+#[allow(clippy::cast_sign_loss)]
+const SEND_OPTION_SYNCHRONOUS: ULONG =
+    _WDF_REQUEST_SEND_OPTIONS_FLAGS::WDF_REQUEST_SEND_OPTION_SYNCHRONOUS as ULONG;
+#[allow(clippy::cast_sign_loss)]
+const SEND_OPTION_SEND_AND_FORGET: ULONG =
+    _WDF_REQUEST_SEND_OPTIONS_FLAGS::WDF_REQUEST_SEND_OPTION_SEND_AND_FORGET as ULONG;
+
+/// Driver-defined callback invoked when a [`Request`] sent to an [`IoTarget`]
+/// is completed by the target.
+///
+/// Set with [`Request::set_completion_routine()`], or by
+/// [`IoTarget::send()`].
+pub trait RequestCompletionCallback {
+    /// Handles the completion of `request` by `target`, as described by
+    /// `params`. The driver owns the request again, and must complete it, ex.
+    /// with [`Request::complete_with_information()`] and the status and
+    /// information of `params` when it forwarded the request of one of its
+    /// queues. This is invoked from the framework's `CompletionRoutine`
+    /// callback, at `IRQL` <= `DISPATCH_LEVEL`. Full documentation is available in the [EvtRequestCompletionRoutine Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfrequest/nc-wdfrequest-evt_wdf_request_completion_routine)
+    fn completed(request: Request, target: IoTarget, params: &CompletionParams);
+}
+
+/// Completion parameters of a [`Request`] sent to an [`IoTarget`], as passed
+/// to [`RequestCompletionCallback::completed`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompletionParams {
+    request_type: WDF_REQUEST_TYPE,
+    status: NTSTATUS,
+    information: usize,
+}
+impl CompletionParams {
+    /// Decode the completion parameters passed by the framework
+    fn from_raw(params: &WDF_REQUEST_COMPLETION_PARAMS) -> Self {
+        // SAFETY: The driver that completed the request set the status of its I/O
+        // status block, so `Status` is the initialized member of the union
+        let status = unsafe { params.IoStatus.__bindgen_anon_1.Status };
+        Self {
+            request_type: params.Type,
+            status,
+            information: usize::try_from(params.IoStatus.Information).unwrap_or(usize::MAX),
+        }
+    }
+
+    /// Get the type of the completed request, ex. `WdfRequestTypeRead`
+    #[must_use]
+    pub const fn request_type(&self) -> WDF_REQUEST_TYPE {
+        self.request_type
+    }
+
+    /// Get the status the target completed the request with
+    #[must_use]
+    pub const fn status(&self) -> NTSTATUS {
+        self.status
+    }
+
+    /// Get the information the target completed the request with, ex. the
+    /// number of bytes transferred by a read, a write or a device control
+    /// request
+    #[must_use]
+    pub const fn information(&self) -> usize {
+        self.information
+    }
+}
+
+/// WDF I/O Target.
+///
+/// An I/O target is the driver a request is sent to, ex. the next lower driver
+/// of the device stack, which filter and bus drivers forward requests to. The
+/// local I/O target of a device, i.e. the next lower driver, is returned by
+/// [`Device::get_io_target()`]. Other drivers are opened as remote I/O targets
+/// with [`IoTarget::try_new()`] and [`IoTarget::open_by_name()`].
+///
+/// A request is sent either asynchronously, with [`IoTarget::send()`], which
+/// invokes a [`RequestCompletionCallback`] once the target completes it, or
+/// synchronously, with [`IoTarget::send_synchronously()`] and the
+/// `send_*_synchronously` helpers, which wait for the target for at most a
+/// timeout. Requests that are not forwarded as they are, ex. requests created
+/// by the driver, are formatted with [`IoTarget::format_request_for_read()`],
+/// [`IoTarget::format_request_for_write()`] and
+/// [`IoTarget::format_request_for_ioctl()`] before they are sent.
+#[derive(Clone, Copy)]
+pub struct IoTarget {
+    wdf_io_target: WDFIOTARGET,
+}
+impl IoTarget {
+    /// Wrap a handle to a framework I/O target object
+    ///
+    /// # Safety
+    ///
+    /// `io_target` must be a valid handle to a framework I/O target object,
+    /// that stays valid for as long as the returned [`IoTarget`] is used.
+    #[must_use]
+    pub const unsafe fn from_raw(io_target: WDFIOTARGET) -> Self {
+        Self {
+            wdf_io_target: io_target,
+        }
+    }
+
+    /// Get the raw handle of the framework I/O target object
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFIOTARGET {
+        self.wdf_io_target
+    }
+
+    /// Try to construct a remote WDF I/O Target object for `device`, which is
+    /// opened with [`IoTarget::open_by_name()`]. The I/O target is deleted
+    /// along with `device`. Must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct an I/O
+    /// target. The error variant will contain a [`NTSTATUS`] of the failure.
+    /// Full error documentation is available in the [WDFIoTarget Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfiotarget/nf-wdfiotarget-wdfiotargetcreate#return-value)
+    pub fn try_new(device: &Device) -> Result<Self, NTSTATUS> {
+        let mut attributes = inherited_object_attributes();
+        attributes.ParentObject = device.as_raw().cast();
+
+        let mut io_target = Self {
+            wdf_io_target: core::ptr::null_mut(),
+        };
+
+        let nt_status;
+        // SAFETY: `device` is a valid framework device, and `attributes` outlives the
+        // call. The resulting ffi object is stored in a private member and not
+        // accessible outside of this module, and this module guarantees that it is
+        // always in a valid state.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfIoTargetCreate,
+                device.as_raw(),
+                &raw mut attributes,
+                &raw mut io_target.wdf_io_target,
+            );
+        }
+        nt_success(nt_status).then_some(io_target).ok_or(nt_status)
+    }
+
+    /// Try to construct a remote WDF I/O Target object for `device`. This is
+    /// an alias for [`IoTarget::try_new()`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct an I/O
+    /// target. The error variant will contain a [`NTSTATUS`] of the failure.
+    /// Full error documentation is available in the [WDFIoTarget Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfiotarget/nf-wdfiotarget-wdfiotargetcreate#return-value)
+    pub fn create(device: &Device) -> Result<Self, NTSTATUS> {
+        Self::try_new(device)
+    }
+
+    /// Open the remote I/O target on the device named `name`, ex.
+    /// `\Device\MyLowerDevice`, with `desired_access`, ex.
+    /// `GENERIC_READ | GENERIC_WRITE`. Must be called at `IRQL` =
+    /// `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the device could not be opened,
+    /// ex. `STATUS_OBJECT_NAME_NOT_FOUND` if no device is named `name`. Full
+    /// error documentation is available in the [WdfIoTargetOpen Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfiotarget/nf-wdfiotarget-wdfiotargetopen#return-value)
+    pub fn open_by_name(
+        &self,
+        name: UnicodeStr<'_>,
+        desired_access: ACCESS_MASK,
+    ) -> Result<(), NTSTATUS> {
+        let params_size: ULONG;
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            const PARAMS_SIZE: usize = core::mem::size_of::<WDF_IO_TARGET_OPEN_PARAMS>();
+
+            // Manually assert there is not truncation since clippy doesn't work for
+            // compile-time constants
+            const { assert!(PARAMS_SIZE <= ULONG::MAX as usize) }
+
+            params_size = PARAMS_SIZE as ULONG;
+        }
+        let mut params = WDF_IO_TARGET_OPEN_PARAMS {
+            Size: params_size,
+            Type: _WDF_IO_TARGET_OPEN_TYPE::WdfIoTargetOpenByName,
+            TargetDeviceName: name.as_raw(),
+            DesiredAccess: desired_access,
+            CreateDisposition: FILE_OPEN,
+            CreateOptions: FILE_NON_DIRECTORY_FILE,
+            ..WDF_IO_TARGET_OPEN_PARAMS::default()
+        };
+
+        let nt_status;
+        // SAFETY: `wdf_io_target` is a private member of `IoTarget`, which the caller
+        // of `from_raw` guaranteed to be valid. `params` is initialized with its size,
+        // and outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfIoTargetOpen,
+                self.wdf_io_target,
+                &raw mut params,
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Close a remote I/O target opened with [`IoTarget::open_by_name()`],
+    /// after the requests sent to it are completed. The I/O target can be
+    /// opened again. Must be called at `IRQL` = `PASSIVE_LEVEL`.
+    pub fn close(&self) {
+        // SAFETY: `wdf_io_target` is a private member of `IoTarget`, which the caller
+        // of `from_raw` guaranteed to be valid
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfIoTargetClose, self.wdf_io_target);
+        }
+    }
+
+    /// Start the I/O target, so that it accepts requests again after it was
+    /// stopped with [`IoTarget::stop()`]. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to start the I/O
+    /// target. The error variant will contain a [`NTSTATUS`] of the failure.
+    /// Full error documentation is available in the [WDFIoTarget Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfiotarget/nf-wdfiotarget-wdfiotargetstart#return-value)
+    pub fn start(&self) -> Result<(), NTSTATUS> {
+        let nt_status;
+        // SAFETY: `wdf_io_target` is a private member of `IoTarget`, which the caller
+        // of `from_raw` guaranteed to be valid
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(WdfIoTargetStart, self.wdf_io_target);
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Stop the I/O target, so that it queues the requests sent to it until it
+    /// is started again. `action` selects what happens to the requests that
+    /// were already sent to the target. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`, or `PASSIVE_LEVEL` if `action` waits for the requests.
+    pub fn stop(&self, action: WDF_IO_TARGET_SENT_IO_ACTION) {
+        // SAFETY: `wdf_io_target` is a private member of `IoTarget`, which the caller
+        // of `from_raw` guaranteed to be valid
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfIoTargetStop, self.wdf_io_target, action);
+        }
+    }
+
+    /// Format `request` as a read request for the I/O target, that reads into
+    /// `output`, or into the output buffer of `request` if `output` is `None`,
+    /// from `device_offset` in the target. The request keeps a reference to
+    /// `output` until it is completed. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request could not be
+    /// formatted, ex. `STATUS_INVALID_DEVICE_REQUEST` if the request is not a
+    /// read request and `output` is `None`. Full error documentation is
+    /// available in the [WdfIoTargetFormatRequestForRead Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfiotarget/nf-wdfiotarget-wdfiotargetformatrequestforread#return-value)
+    pub fn format_request_for_read(
+        &self,
+        request: &Request,
+        output: Option<&Memory<'_>>,
+        device_offset: Option<LONGLONG>,
+    ) -> Result<(), NTSTATUS> {
+        let mut device_offset = device_offset;
+        let nt_status;
+        // SAFETY: `wdf_io_target` is a private member of `IoTarget`, which the caller
+        // of `from_raw` guaranteed to be valid, and `request` is a request the driver
+        // owns. The framework references the memory object for as long as the request
+        // uses it. The offset pointer is either null or points to a value that
+        // outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfIoTargetFormatRequestForRead,
+                self.wdf_io_target,
+                request.as_raw(),
+                memory_handle(output),
+                core::ptr::null_mut(),
+                device_offset
+                    .as_mut()
+                    .map_or(core::ptr::null_mut(), core::ptr::from_mut),
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Format `request` as a write request for the I/O target, that writes
+    /// `input`, or the input buffer of `request` if `input` is `None`, at
+    /// `device_offset` in the target. The request keeps a reference to `input`
+    /// until it is completed. Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request could not be
+    /// formatted, ex. `STATUS_INVALID_DEVICE_REQUEST` if the request is not a
+    /// write request and `input` is `None`. Full error documentation is
+    /// available in the [WdfIoTargetFormatRequestForWrite Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfiotarget/nf-wdfiotarget-wdfiotargetformatrequestforwrite#return-value)
+    pub fn format_request_for_write(
+        &self,
+        request: &Request,
+        input: Option<&Memory<'_>>,
+        device_offset: Option<LONGLONG>,
+    ) -> Result<(), NTSTATUS> {
+        let mut device_offset = device_offset;
+        let nt_status;
+        // SAFETY: `wdf_io_target` is a private member of `IoTarget`, which the caller
+        // of `from_raw` guaranteed to be valid, and `request` is a request the driver
+        // owns. The framework references the memory object for as long as the request
+        // uses it. The offset pointer is either null or points to a value that
+        // outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfIoTargetFormatRequestForWrite,
+                self.wdf_io_target,
+                request.as_raw(),
+                memory_handle(input),
+                core::ptr::null_mut(),
+                device_offset
+                    .as_mut()
+                    .map_or(core::ptr::null_mut(), core::ptr::from_mut),
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Format `request` as a device control request for the I/O target, with
+    /// the control code `code`, that sends `input` and receives into `output`.
+    /// The buffers of `request` are used instead of the ones that are `None`.
+    /// The request keeps a reference to `input` and `output` until it is
+    /// completed. Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request could not be
+    /// formatted. Full error documentation is available in the [WdfIoTargetFormatRequestForIoctl Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfiotarget/nf-wdfiotarget-wdfiotargetformatrequestforioctl#return-value)
+    pub fn format_request_for_ioctl(
+        &self,
+        request: &Request,
+        code: ControlCode,
+        input: Option<&Memory<'_>>,
+        output: Option<&Memory<'_>>,
+    ) -> Result<(), NTSTATUS> {
+        let nt_status;
+        // SAFETY: `wdf_io_target` is a private member of `IoTarget`, which the caller
+        // of `from_raw` guaranteed to be valid, and `request` is a request the driver
+        // owns. The framework references the memory objects for as long as the
+        // request uses them.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfIoTargetFormatRequestForIoctl,
+                self.wdf_io_target,
+                request.as_raw(),
+                code.as_raw(),
+                memory_handle(input),
+                core::ptr::null_mut(),
+                memory_handle(output),
+                core::ptr::null_mut(),
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Send `request` to the I/O target, which invokes
+    /// [`RequestCompletionCallback::completed`] of `T` once it completes the
+    /// request. The request must be formatted first, ex. with
+    /// [`Request::format_using_current_type()`] to forward it as it is. The
+    /// driver does not own the request while it is sent, so it is consumed,
+    /// and handed back to the callback. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request could not be sent,
+    /// ex. `STATUS_INVALID_DEVICE_STATE` if the target is stopped. The
+    /// callback is not invoked in that case, so the driver still owns the
+    /// request, which is returned along with the error, and must complete it.
+    pub fn send<T: RequestCompletionCallback>(
+        &self,
+        request: Request,
+    ) -> Result<(), (Request, NTSTATUS)> {
+        request.set_completion_routine::<T>();
+        match self.send_with_options(&request, core::ptr::null_mut()) {
+            Ok(()) => Ok(()),
+            Err(nt_status) => Err((request, nt_status)),
+        }
+    }
+
+    /// Send `request` to the I/O target as it is, without being notified of
+    /// its completion, ex. to pass the requests a filter driver does not
+    /// handle down the device stack. The driver no longer owns the request
+    /// once it is sent, so it is consumed. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request could not be sent.
+    /// The driver still owns the request in that case, which is returned along
+    /// with the error, and must complete it.
+    pub fn send_and_forget(&self, request: Request) -> Result<(), (Request, NTSTATUS)> {
+        let mut options = send_options(SEND_OPTION_SEND_AND_FORGET, None);
+        match self.send_with_options(&request, &raw mut options) {
+            Ok(()) => Ok(()),
+            Err(nt_status) => Err((request, nt_status)),
+        }
+    }
+
+    /// Send `request` to the I/O target and wait for at most `timeout`, or
+    /// indefinitely if `timeout` is `None`, for the target to complete it. The
+    /// request must be formatted first. On success, returns the information
+    /// the target completed the request with, ex. the number of bytes
+    /// transferred. The driver owns the request again when this returns. Must
+    /// be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request could not be sent,
+    /// or the status the target completed the request with if it failed, ex.
+    /// `STATUS_IO_TIMEOUT` if the target did not complete it before `timeout`
+    /// elapsed.
+    pub fn send_synchronously(
+        &self,
+        request: &Request,
+        timeout: Option<Duration>,
+    ) -> Result<usize, NTSTATUS> {
+        let mut options = send_options(SEND_OPTION_SYNCHRONOUS, timeout);
+        self.send_with_options(request, &raw mut options)?;
+
+        let nt_status;
+        let information: ULONG_PTR;
+        // SAFETY: The target completed `request`, which the driver owns again
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(WdfRequestGetStatus, request.as_raw());
+        }
+        // SAFETY: The target completed `request`, which the driver owns again
+        unsafe {
+            information =
+                call_unsafe_wdf_function_binding!(WdfRequestGetInformation, request.as_raw());
+        }
+        nt_success(nt_status)
+            .then(|| usize::try_from(information).unwrap_or(usize::MAX))
+            .ok_or(nt_status)
+    }
+
+    /// Read from the I/O target into `buffer`, from `device_offset` in the
+    /// target, and wait for at most `timeout`, or indefinitely if `timeout` is
+    /// `None`, for the read to complete. On success, returns the number of
+    /// bytes read. Must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the read failed, ex.
+    /// `STATUS_IO_TIMEOUT` if it did not complete before `timeout` elapsed.
+    /// Full error documentation is available in the [WdfIoTargetSendReadSynchronously Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfiotarget/nf-wdfiotarget-wdfiotargetsendreadsynchronously#return-value)
+    pub fn send_read_synchronously(
+        &self,
+        buffer: &mut [u8],
+        device_offset: Option<LONGLONG>,
+        timeout: Option<Duration>,
+    ) -> Result<usize, NTSTATUS> {
+        let mut descriptor = buffer_descriptor(buffer.as_mut_ptr().cast(), buffer.len())?;
+        let mut device_offset = device_offset;
+        let mut options = send_options(0, timeout);
+        let mut bytes_read: ULONG_PTR = 0;
+
+        let nt_status;
+        // SAFETY: `wdf_io_target` is a private member of `IoTarget`, which the caller
+        // of `from_raw` guaranteed to be valid. The descriptor describes `buffer`,
+        // which is borrowed mutably until the read completes, and every pointer
+        // outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfIoTargetSendReadSynchronously,
+                self.wdf_io_target,
+                core::ptr::null_mut(),
+                &raw mut descriptor,
+                device_offset
+                    .as_mut()
+                    .map_or(core::ptr::null_mut(), core::ptr::from_mut),
+                &raw mut options,
+                &raw mut bytes_read,
+            );
+        }
+        nt_success(nt_status)
+            .then(|| usize::try_from(bytes_read).unwrap_or(usize::MAX))
+            .ok_or(nt_status)
+    }
+
+    /// Write `buffer` to the I/O target, at `device_offset` in the target, and
+    /// wait for at most `timeout`, or indefinitely if `timeout` is `None`, for
+    /// the write to complete. On success, returns the number of bytes
+    /// written. Must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the write failed, ex.
+    /// `STATUS_IO_TIMEOUT` if it did not complete before `timeout` elapsed.
+    /// Full error documentation is available in the [WdfIoTargetSendWriteSynchronously Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfiotarget/nf-wdfiotarget-wdfiotargetsendwritesynchronously#return-value)
+    pub fn send_write_synchronously(
+        &self,
+        buffer: &[u8],
+        device_offset: Option<LONGLONG>,
+        timeout: Option<Duration>,
+    ) -> Result<usize, NTSTATUS> {
+        let mut descriptor = buffer_descriptor(buffer.as_ptr().cast_mut().cast(), buffer.len())?;
+        let mut device_offset = device_offset;
+        let mut options = send_options(0, timeout);
+        let mut bytes_written: ULONG_PTR = 0;
+
+        let nt_status;
+        // SAFETY: `wdf_io_target` is a private member of `IoTarget`, which the caller
+        // of `from_raw` guaranteed to be valid. The descriptor describes `buffer`,
+        // which the framework only reads, and every pointer outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfIoTargetSendWriteSynchronously,
+                self.wdf_io_target,
+                core::ptr::null_mut(),
+                &raw mut descriptor,
+                device_offset
+                    .as_mut()
+                    .map_or(core::ptr::null_mut(), core::ptr::from_mut),
+                &raw mut options,
+                &raw mut bytes_written,
+            );
+        }
+        nt_success(nt_status)
+            .then(|| usize::try_from(bytes_written).unwrap_or(usize::MAX))
+            .ok_or(nt_status)
+    }
+
+    /// Send the device control code `code` to the I/O target, with `input` as
+    /// input buffer and `output` as output buffer, and wait for at most
+    /// `timeout`, or indefinitely if `timeout` is `None`, for the target to
+    /// complete it. On success, returns the number of bytes written to
+    /// `output`. Must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the control code failed, ex.
+    /// `STATUS_IO_TIMEOUT` if it did not complete before `timeout` elapsed.
+    /// Full error documentation is available in the [WdfIoTargetSendIoctlSynchronously Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfiotarget/nf-wdfiotarget-wdfiotargetsendioctlsynchronously#return-value)
+    pub fn send_ioctl_synchronously(
+        &self,
+        code: ControlCode,
+        input: Option<&[u8]>,
+        output: Option<&mut [u8]>,
+        timeout: Option<Duration>,
+    ) -> Result<usize, NTSTATUS> {
+        let mut input = input
+            .map(|input| buffer_descriptor(input.as_ptr().cast_mut().cast(), input.len()))
+            .transpose()?;
+        let mut output = output
+            .map(|output| buffer_descriptor(output.as_mut_ptr().cast(), output.len()))
+            .transpose()?;
+        let mut options = send_options(0, timeout);
+        let mut bytes_returned: ULONG_PTR = 0;
+
+        let nt_status;
+        // SAFETY: `wdf_io_target` is a private member of `IoTarget`, which the caller
+        // of `from_raw` guaranteed to be valid. The descriptors describe the buffers,
+        // of which `output` is borrowed mutably until the control code completes, and
+        // every pointer is either null or outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfIoTargetSendIoctlSynchronously,
+                self.wdf_io_target,
+                core::ptr::null_mut(),
+                code.as_raw(),
+                descriptor_ptr(input.as_mut()),
+                descriptor_ptr(output.as_mut()),
+                &raw mut options,
+                &raw mut bytes_returned,
+            );
+        }
+        nt_success(nt_status)
+            .then(|| usize::try_from(bytes_returned).unwrap_or(usize::MAX))
+            .ok_or(nt_status)
+    }
+
+    /// Send `request` with `options`, which is null to send it asynchronously
+    fn send_with_options(
+        &self,
+        request: &Request,
+        options: *mut WDF_REQUEST_SEND_OPTIONS,
+    ) -> Result<(), NTSTATUS> {
+        let sent;
+        // SAFETY: `wdf_io_target` is a private member of `IoTarget`, which the caller
+        // of `from_raw` guaranteed to be valid, and `request` is a request the driver
+        // owns. `options` is either null or points to options that outlive the call.
+        unsafe {
+            sent = call_unsafe_wdf_function_binding!(
+                WdfRequestSend,
+                request.as_raw(),
+                self.wdf_io_target,
+                options,
+            );
+        }
+        if sent != 0 {
+            return Ok(());
+        }
+
+        let nt_status;
+        // SAFETY: The request was not sent, so the driver still owns it
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(WdfRequestGetStatus, request.as_raw());
+        }
+        Err(nt_status)
+    }
+}
+
+impl Device {
+    /// Get the local I/O target of the device, i.e. the next lower driver of
+    /// its device stack, which filter drivers forward requests to. Must be
+    /// called at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn get_io_target(&self) -> IoTarget {
+        let wdf_io_target: WDFIOTARGET;
+        // SAFETY: `Device` guarantees that its handle is valid
+        unsafe {
+            wdf_io_target = call_unsafe_wdf_function_binding!(WdfDeviceGetIoTarget, self.as_raw());
+        }
+        // SAFETY: The local I/O target of a device is valid for as long as the device
+        unsafe { IoTarget::from_raw(wdf_io_target) }
+    }
+}
+
+impl Request {
+    /// Set [`RequestCompletionCallback::completed`] of `T` as the completion
+    /// routine of the request, so that it is invoked once the I/O target the
+    /// request is sent to completes it, ex. for a filter driver to observe the
+    /// completion of a request it forwards. The completion routine replaces
+    /// any previous one, and applies to the next send of the request. Must be
+    /// called at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn set_completion_routine<T: RequestCompletionCallback>(&self) {
+        // SAFETY: `Request` guarantees that its handle is valid and not completed yet
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfRequestSetCompletionRoutine,
+                self.as_raw(),
+                Some(evt_request_completion::<T>),
+                core::ptr::null_mut(),
+            );
+        }
+    }
+}
+
+/// `CompletionRoutine` callback that forwards to
+/// [`RequestCompletionCallback::completed`]
+unsafe extern "C" fn evt_request_completion<T: RequestCompletionCallback>(
+    wdf_request: WDFREQUEST,
+    wdf_io_target: WDFIOTARGET,
+    params: PWDF_REQUEST_COMPLETION_PARAMS,
+    _context: WDFCONTEXT,
+) {
+    // SAFETY: The framework passes the completed request, which the driver owns
+    // again
+    let request = unsafe { Request::from_raw(wdf_request) };
+    // SAFETY: The framework passes the target the request was sent to
+    let target = unsafe { IoTarget::from_raw(wdf_io_target) };
+    // SAFETY: The framework passes the completion parameters of the request, which
+    // are valid until the callback returns
+    let params = CompletionParams::from_raw(unsafe { &*params });
+    T::completed(request, target, &params);
+}
+
+/// Get the handle of `memory`, or null to use the buffer of the request
+fn memory_handle(memory: Option<&Memory<'_>>) -> WDFMEMORY {
+    memory.map_or(core::ptr::null_mut(), Memory::as_raw)
+}
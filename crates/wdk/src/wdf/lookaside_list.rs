@@ -0,0 +1,167 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use wdk_sys::{
+    NTSTATUS,
+    POOL_TYPE,
+    ULONG,
+    WDF_OBJECT_ATTRIBUTES,
+    WDFLOOKASIDE,
+    WDFMEMORY,
+    call_unsafe_wdf_function_binding,
+};
+
+use crate::nt_success;
+
+/// WDF Lookaside List.
+///
+/// A lookaside list is a pool of fixed-size buffers that the system recycles,
+/// so that drivers allocating buffers of the same size at a high frequency, ex.
+/// for every request, avoid the cost of a pool allocation each time. The
+/// lookaside list is deleted along with its parent object, which is set
+/// through the `ParentObject` of the attributes passed to
+/// [`LookasideList::try_new()`], ex. a device or a queue.
+///
+/// Buffers are allocated with [`LookasideList::allocate()`] and returned to
+/// the list when the [`LookasideBuffer`] is dropped.
+pub struct LookasideList {
+    wdf_lookaside: WDFLOOKASIDE,
+}
+impl LookasideList {
+    /// Try to construct a WDF Lookaside List object of buffers of
+    /// `buffer_size` bytes, allocated from `pool_type` with the tag `pool_tag`.
+    /// `memory_attributes` are applied to the memory objects of the allocated
+    /// buffers.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a
+    /// lookaside list. The error variant will contain a [`NTSTATUS`] of the
+    /// failure. Full error documentation is available in the [WDFLookasideList Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfmemory/nf-wdfmemory-wdflookasidelistcreate#return-value)
+    pub fn try_new(
+        attributes: &mut WDF_OBJECT_ATTRIBUTES,
+        buffer_size: usize,
+        pool_type: POOL_TYPE,
+        memory_attributes: Option<&mut WDF_OBJECT_ATTRIBUTES>,
+        pool_tag: ULONG,
+    ) -> Result<Self, NTSTATUS> {
+        let mut lookaside_list = Self {
+            wdf_lookaside: core::ptr::null_mut(),
+        };
+
+        let nt_status;
+        // SAFETY: The resulting ffi object is stored in a private member and not
+        // accessible outside of this module, and this module guarantees that it is
+        // always in a valid state. The memory attributes pointer is either null or
+        // points to attributes that outlive the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfLookasideListCreate,
+                attributes,
+                buffer_size,
+                pool_type,
+                memory_attributes.map_or(core::ptr::null_mut(), core::ptr::from_mut),
+                pool_tag,
+                &mut lookaside_list.wdf_lookaside as *mut WDFLOOKASIDE,
+            );
+        }
+        nt_success(nt_status)
+            .then_some(lookaside_list)
+            .ok_or(nt_status)
+    }
+
+    /// Allocate a buffer from the lookaside list. The buffer is zeroed, and is
+    /// returned to the list when the [`LookasideBuffer`] is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to allocate the buffer.
+    /// The error variant will contain a [`NTSTATUS`] of the failure. Full error
+    /// documentation is available in the [WDFMemory Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfmemory/nf-wdfmemory-wdfmemorycreatefromlookaside#return-value)
+    pub fn allocate(&self) -> Result<LookasideBuffer<'_>, NTSTATUS> {
+        let mut wdf_memory: WDFMEMORY = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `wdf_lookaside` is a private member of `LookasideList`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfMemoryCreateFromLookaside,
+                self.wdf_lookaside,
+                &mut wdf_memory as *mut WDFMEMORY,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        let mut len = 0;
+        let buffer: *mut u8;
+        // SAFETY: `wdf_memory` was just created by WDF from the lookaside list
+        unsafe {
+            buffer = call_unsafe_wdf_function_binding!(
+                WdfMemoryGetBuffer,
+                wdf_memory,
+                &mut len as *mut usize,
+            )
+            .cast();
+        }
+        // SAFETY: `buffer` points to `len` writable bytes owned by `wdf_memory`.
+        // Zeroing them makes the buffer safe to expose as a slice.
+        unsafe {
+            core::ptr::write_bytes(buffer, 0, len);
+        }
+
+        Ok(LookasideBuffer {
+            wdf_memory,
+            buffer,
+            len,
+            _lookaside_list: PhantomData,
+        })
+    }
+}
+
+/// Buffer allocated from a [`LookasideList`]. It dereferences to the bytes of
+/// the buffer, and is returned to the list when dropped.
+pub struct LookasideBuffer<'a> {
+    wdf_memory: WDFMEMORY,
+    buffer: *mut u8,
+    len: usize,
+    _lookaside_list: PhantomData<&'a LookasideList>,
+}
+
+impl Deref for LookasideBuffer<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: `buffer` points to `len` initialized bytes that are owned by
+        // `wdf_memory`, which is alive until this buffer is dropped
+        unsafe { core::slice::from_raw_parts(self.buffer, self.len) }
+    }
+}
+
+impl DerefMut for LookasideBuffer<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: `buffer` points to `len` initialized bytes that are owned by
+        // `wdf_memory`, which is alive until this buffer is dropped. `&mut self`
+        // guarantees the access is exclusive.
+        unsafe { core::slice::from_raw_parts_mut(self.buffer, self.len) }
+    }
+}
+
+impl Drop for LookasideBuffer<'_> {
+    fn drop(&mut self) {
+        // SAFETY: `wdf_memory` is a private member of `LookasideBuffer`, originally
+        // created by WDF, and is deleted exactly once here, which returns the buffer
+        // to the lookaside list.
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfObjectDelete, self.wdf_memory.cast());
+        }
+    }
+}
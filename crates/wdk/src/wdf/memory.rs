@@ -0,0 +1,262 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{ffi::c_void, marker::PhantomData};
+
+use wdk_sys::{
+    _WDF_MEMORY_DESCRIPTOR_TYPE,
+    NTSTATUS,
+    POOL_TYPE,
+    PWDF_MEMORY_DESCRIPTOR,
+    STATUS_INVALID_BUFFER_SIZE,
+    ULONG,
+    WDF_MEMORY_DESCRIPTOR,
+    WDFMEMORY,
+    call_unsafe_wdf_function_binding,
+};
+
+use crate::nt_success;
+
+/// WDF Memory.
+///
+/// A buffer described by a framework memory object, either allocated by the
+/// driver with [`Memory::try_new()`], or the buffer of a request retrieved
+/// with [`crate::wdf::Request::retrieve_input_memory()`] or
+/// [`crate::wdf::Request::retrieve_output_memory()`].
+///
+/// The bytes of the buffer are accessed through the slices returned by
+/// [`Memory::as_slice()`] and [`Memory::as_mut_slice()`], or copied with
+/// [`Memory::copy_from_slice()`] and [`Memory::copy_to_slice()`], which check
+/// that the copied range is within the buffer. Memory allocated by the driver
+/// is deleted when the [`Memory`] is dropped, while the memory of a request is
+/// owned by the request, and borrows it.
+pub struct Memory<'a> {
+    wdf_memory: WDFMEMORY,
+    buffer: *mut u8,
+    len: usize,
+    owned: bool,
+    _owner: PhantomData<&'a mut ()>,
+}
+// SAFETY: `Memory` has exclusive access to its buffer, whose framework memory
+// object can be used and deleted from any thread
+unsafe impl Send for Memory<'_> {}
+
+impl Memory<'static> {
+    /// Try to allocate a buffer of `size` bytes from `pool_type`, with the tag
+    /// `pool_tag`. The buffer is zeroed. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`, or `APC_LEVEL` for paged pool.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to allocate the buffer.
+    /// The error variant will contain a [`NTSTATUS`] of the failure. Full error
+    /// documentation is available in the [WDFMemory Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfmemory/nf-wdfmemory-wdfmemorycreate#return-value)
+    pub fn try_new(pool_type: POOL_TYPE, pool_tag: ULONG, size: usize) -> Result<Self, NTSTATUS> {
+        let mut wdf_memory: WDFMEMORY = core::ptr::null_mut();
+        let mut buffer: *mut c_void = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: The resulting ffi object is stored in a private member and not
+        // accessible outside of this module, and this module guarantees that it is
+        // always in a valid state. The parent of the memory object defaults to the
+        // driver object.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfMemoryCreate,
+                core::ptr::null_mut(),
+                pool_type,
+                pool_tag,
+                size,
+                &raw mut wdf_memory,
+                &raw mut buffer,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        let buffer = buffer.cast::<u8>();
+        // SAFETY: `buffer` points to `size` writable bytes owned by `wdf_memory`.
+        // Zeroing them makes the buffer safe to expose as a slice.
+        unsafe {
+            core::ptr::write_bytes(buffer, 0, size);
+        }
+
+        Ok(Self {
+            wdf_memory,
+            buffer,
+            len: size,
+            owned: true,
+            _owner: PhantomData,
+        })
+    }
+
+    /// Try to allocate a buffer of `size` bytes from `pool_type`, with the tag
+    /// `pool_tag`. This is an alias for [`Memory::try_new()`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to allocate the buffer.
+    /// The error variant will contain a [`NTSTATUS`] of the failure. Full error
+    /// documentation is available in the [WDFMemory Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfmemory/nf-wdfmemory-wdfmemorycreate#return-value)
+    pub fn create(pool_type: POOL_TYPE, pool_tag: ULONG, size: usize) -> Result<Self, NTSTATUS> {
+        Self::try_new(pool_type, pool_tag, size)
+    }
+}
+impl Memory<'_> {
+    /// Wrap a memory object owned by another framework object, ex. the memory
+    /// of a request
+    ///
+    /// # Safety
+    ///
+    /// `memory` must be a valid handle to a framework memory object, whose
+    /// buffer is not accessed through another handle, for the lifetime of the
+    /// returned [`Memory`].
+    pub(crate) unsafe fn from_borrowed(memory: WDFMEMORY) -> Self {
+        let mut len = 0;
+        let buffer: *mut u8;
+        // SAFETY: The caller guarantees that `memory` is valid
+        unsafe {
+            buffer =
+                call_unsafe_wdf_function_binding!(WdfMemoryGetBuffer, memory, &raw mut len).cast();
+        }
+        Self {
+            wdf_memory: memory,
+            buffer,
+            len,
+            owned: false,
+            _owner: PhantomData,
+        }
+    }
+
+    /// Get the raw handle of the framework memory object
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFMEMORY {
+        self.wdf_memory
+    }
+
+    /// Get the length of the buffer, in bytes
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the buffer holds no bytes
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the bytes of the buffer
+    #[must_use]
+    pub const fn as_slice(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        // SAFETY: `buffer` points to `len` initialized bytes that are owned by
+        // `wdf_memory`, which is alive for as long as `self` is
+        unsafe { core::slice::from_raw_parts(self.buffer, self.len) }
+    }
+
+    /// Get the bytes of the buffer, mutably
+    #[must_use]
+    pub const fn as_mut_slice(&mut self) -> &mut [u8] {
+        if self.len == 0 {
+            return &mut [];
+        }
+        // SAFETY: `buffer` points to `len` initialized bytes that are owned by
+        // `wdf_memory`, which is alive for as long as `self` is. `&mut self`
+        // guarantees the access is exclusive.
+        unsafe { core::slice::from_raw_parts_mut(self.buffer, self.len) }
+    }
+
+    /// Copy `source` into the buffer, starting `offset` bytes into it. Must be
+    /// called at `IRQL` <= `DISPATCH_LEVEL`, or `APC_LEVEL` for paged pool.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the memory could not be copied:
+    /// * `STATUS_INVALID_BUFFER_SIZE` if `source` does not fit in the buffer at
+    ///   `offset`
+    ///
+    /// Full error documentation is available in the [WdfMemoryCopyFromBuffer Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfmemory/nf-wdfmemory-wdfmemorycopyfrombuffer#return-value)
+    pub fn copy_from_slice(&mut self, offset: usize, source: &[u8]) -> Result<(), NTSTATUS> {
+        let nt_status;
+        // SAFETY: `wdf_memory` is valid for as long as `self` is, and `source` is a
+        // readable buffer of `source.len()` bytes. The framework checks that the
+        // copied range is within the buffer of the memory object.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfMemoryCopyFromBuffer,
+                self.wdf_memory,
+                offset,
+                source.as_ptr().cast_mut().cast(),
+                source.len(),
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Copy the bytes of the buffer, starting `offset` bytes into it, to
+    /// `destination`, filling it. Must be called at `IRQL` <= `DISPATCH_LEVEL`,
+    /// or `APC_LEVEL` for paged pool.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the memory could not be copied:
+    /// * `STATUS_INVALID_BUFFER_SIZE` if the buffer holds fewer than
+    ///   `destination.len()` bytes after `offset`
+    ///
+    /// Full error documentation is available in the [WdfMemoryCopyToBuffer Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfmemory/nf-wdfmemory-wdfmemorycopytobuffer#return-value)
+    pub fn copy_to_slice(&self, offset: usize, destination: &mut [u8]) -> Result<(), NTSTATUS> {
+        let nt_status;
+        // SAFETY: `wdf_memory` is valid for as long as `self` is, and `destination`
+        // is a writable buffer of `destination.len()` bytes. The framework checks
+        // that the copied range is within the buffer of the memory object.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfMemoryCopyToBuffer,
+                self.wdf_memory,
+                offset,
+                destination.as_mut_ptr().cast(),
+                destination.len(),
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+}
+
+impl Drop for Memory<'_> {
+    fn drop(&mut self) {
+        if !self.owned {
+            return;
+        }
+        // SAFETY: `wdf_memory` was allocated by `Memory::try_new`, is owned by
+        // `self`, and is deleted exactly once here
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfObjectDelete, self.wdf_memory.cast());
+        }
+    }
+}
+
+/// Get a pointer to `descriptor`, or null if there is none
+pub(super) fn descriptor_ptr(
+    descriptor: Option<&mut WDF_MEMORY_DESCRIPTOR>,
+) -> PWDF_MEMORY_DESCRIPTOR {
+    descriptor.map_or(core::ptr::null_mut(), core::ptr::from_mut)
+}
+
+/// Describe the buffer of `length` bytes at `buffer`
+pub(super) fn buffer_descriptor(
+    buffer: *mut c_void,
+    length: usize,
+) -> Result<WDF_MEMORY_DESCRIPTOR, NTSTATUS> {
+    let length = ULONG::try_from(length).map_err(|_| STATUS_INVALID_BUFFER_SIZE)?;
+    let mut descriptor = WDF_MEMORY_DESCRIPTOR {
+        Type: _WDF_MEMORY_DESCRIPTOR_TYPE::WdfMemoryDescriptorTypeBuffer,
+        ..WDF_MEMORY_DESCRIPTOR::default()
+    };
+    descriptor.u.BufferType.Buffer = buffer;
+    descriptor.u.BufferType.Length = length;
+    Ok(descriptor)
+}
@@ -0,0 +1,2033 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! In-memory fakes of the WDF wrappers, to test driver logic on the host.
+//!
+//! The fakes have the same methods as the wrappers they stand in for, but keep
+//! their state in memory instead of calling into WDF, so driver code that is
+//! written against the wrappers can be exercised with `cargo test`. Each fake
+//! also has methods to set up and inspect its state, and to simulate the
+//! framework, ex. [`Timer::expire()`], [`WorkItem::run()`] or
+//! [`Driver::unload()`].
+//!
+//! Drivers swap the wrappers for the fakes in their tests:
+//!
+//! ```rust, ignore
+//! #[cfg(not(test))]
+//! use wdk::wdf::{IoQueue, Request, Timer};
+//! #[cfg(test)]
+//! use wdk::wdf::mock::{IoQueue, Request, Timer};
+//! ```
+//!
+//! Fakes are handles to shared state, like the framework objects they stand in
+//! for: clones of a fake observe the same state. They are only meant to be used
+//! from a single test thread.
+
+use alloc::{boxed::Box, collections::VecDeque, rc::Rc, vec::Vec};
+use core::{
+    any::Any,
+    cell::{OnceCell, Ref, RefCell, RefMut},
+    time::Duration,
+};
+
+use wdk_sys::{
+    NTSTATUS,
+    STATUS_BUFFER_TOO_SMALL,
+    STATUS_CANCELLED,
+    STATUS_INSUFFICIENT_RESOURCES,
+    STATUS_INVALID_BUFFER_SIZE,
+    STATUS_INVALID_DEVICE_REQUEST,
+    STATUS_NO_MORE_ENTRIES,
+    STATUS_OBJECT_NAME_COLLISION,
+    ULONG,
+    WDF_IO_QUEUE_CONFIG,
+    WDF_OBJECT_ATTRIBUTES,
+    WDF_TIMER_CONFIG,
+    WDFDEVICE,
+};
+
+use super::{
+    MAX_DRIVER_DEVICES,
+    MAX_DRIVER_UNLOAD_CALLBACKS,
+    MAX_REGISTERED_QUEUES,
+    RequestParameters,
+    SystemTime,
+    timer::relative_due_time,
+};
+use crate::{
+    PassiveLevel,
+    TransferMethod,
+    string::{UnicodeStr, UnicodeString},
+};
+
+/// Fake of [`super::Driver`]
+#[derive(Clone, Default)]
+pub struct Driver {
+    state: Rc<RefCell<DriverState>>,
+}
+
+#[derive(Default)]
+struct DriverState {
+    registry_path: Vec<u16>,
+    unload_callbacks: Vec<fn(&Driver)>,
+    devices: Vec<Device>,
+}
+
+impl Driver {
+    /// Create a fake driver with an empty registry path
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the path returned by [`Driver::registry_path()`]
+    pub fn set_registry_path(&self, registry_path: &str) {
+        self.state.borrow_mut().registry_path = registry_path.encode_utf16().collect();
+    }
+
+    /// Get the path of the driver's service key in the registry, as a UTF-16
+    /// string without its NUL terminator
+    #[must_use]
+    pub fn registry_path(&self) -> Vec<u16> {
+        self.state.borrow().registry_path.clone()
+    }
+
+    /// Register `callback` to be invoked by [`Driver::unload()`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_INSUFFICIENT_RESOURCES` if
+    /// [`MAX_DRIVER_UNLOAD_CALLBACKS`] callbacks are already registered.
+    pub fn on_evt_driver_unload(&self, callback: fn(&Self)) -> Result<(), NTSTATUS> {
+        let mut state = self.state.borrow_mut();
+        if state.unload_callbacks.len() >= MAX_DRIVER_UNLOAD_CALLBACKS {
+            return Err(STATUS_INSUFFICIENT_RESOURCES);
+        }
+        state.unload_callbacks.push(callback);
+        Ok(())
+    }
+
+    /// Register `device` with the driver, so that it is returned by
+    /// [`Driver::devices()`] until it is removed with
+    /// [`Driver::remove_device()`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the device could not be
+    /// registered:
+    /// * `STATUS_OBJECT_NAME_COLLISION` if the device is already registered
+    /// * `STATUS_INSUFFICIENT_RESOURCES` if [`MAX_DRIVER_DEVICES`] devices are
+    ///   already registered
+    pub fn register_device(&self, device: &Device) -> Result<(), NTSTATUS> {
+        let mut state = self.state.borrow_mut();
+        if state.devices.iter().any(|d| d.ptr_eq(device)) {
+            return Err(STATUS_OBJECT_NAME_COLLISION);
+        }
+        if state.devices.len() >= MAX_DRIVER_DEVICES {
+            return Err(STATUS_INSUFFICIENT_RESOURCES);
+        }
+        state.devices.push(device.clone());
+        Ok(())
+    }
+
+    /// Get an iterator over the registered devices, in the order of their
+    /// registration
+    #[must_use]
+    pub fn devices(&self) -> alloc::vec::IntoIter<Device> {
+        self.state.borrow().devices.clone().into_iter()
+    }
+
+    /// Simulate the removal of `device`, which leaves the devices of the
+    /// driver like it does when the framework cleans it up
+    pub fn remove_device(&self, device: &Device) {
+        self.state
+            .borrow_mut()
+            .devices
+            .retain(|d| !d.ptr_eq(device));
+    }
+
+    /// Simulate the unload of the driver: invoke the registered unload
+    /// callbacks in the reverse order of their registration, like
+    /// [`super::evt_driver_unload`] does
+    pub fn unload(&self) {
+        let callbacks = core::mem::take(&mut self.state.borrow_mut().unload_callbacks);
+        for callback in callbacks.iter().rev() {
+            callback(self);
+        }
+    }
+}
+
+/// Fake of [`super::Device`]
+#[derive(Clone, Default)]
+pub struct Device {
+    state: Rc<RefCell<DeviceState>>,
+    context: Rc<OnceCell<Box<dyn Any>>>,
+}
+
+#[derive(Default)]
+struct DeviceState {
+    default_queue: Option<IoQueue>,
+    registered_queues: Vec<(u32, IoQueue)>,
+    symbolic_links: Vec<UnicodeString>,
+}
+
+impl Device {
+    /// Create a fake device without queues
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the queue returned by [`Device::get_default_queue()`]
+    pub fn set_default_queue(&self, queue: &IoQueue) {
+        self.state.borrow_mut().default_queue = Some(queue.clone());
+    }
+
+    /// Create a symbolic link to the device
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_OBJECT_NAME_COLLISION` if
+    /// `symbolic_link_name` is already in use.
+    pub fn create_symbolic_link(
+        &self,
+        symbolic_link_name: UnicodeStr<'_>,
+        _irql: PassiveLevel,
+    ) -> Result<(), NTSTATUS> {
+        let mut state = self.state.borrow_mut();
+        if state
+            .symbolic_links
+            .iter()
+            .any(|link| link.as_unicode_str().as_slice() == symbolic_link_name.as_slice())
+        {
+            return Err(STATUS_OBJECT_NAME_COLLISION);
+        }
+        state.symbolic_links.push(symbolic_link_name.into());
+        Ok(())
+    }
+
+    /// Get the symbolic links created with [`Device::create_symbolic_link()`]
+    #[must_use]
+    pub fn symbolic_links(&self) -> Vec<UnicodeString> {
+        self.state.borrow().symbolic_links.clone()
+    }
+
+    /// Get the default queue of the device
+    #[must_use]
+    pub fn get_default_queue(&self) -> Option<IoQueue> {
+        self.state.borrow().default_queue.clone()
+    }
+
+    /// Set the context of the device, and return a reference to it
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_OBJECT_NAME_COLLISION` if the device
+    /// already holds a context.
+    pub fn set_context<T: Send + Sync + 'static>(&self, context: T) -> Result<&T, NTSTATUS> {
+        self.context
+            .set(Box::new(context))
+            .map_err(|_| STATUS_OBJECT_NAME_COLLISION)?;
+        self.context().ok_or(STATUS_OBJECT_NAME_COLLISION)
+    }
+
+    /// Get the context of the device set with [`Device::set_context()`], or
+    /// `None` if the device holds no context of type `T`
+    #[must_use]
+    pub fn context<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.context.get()?.downcast_ref()
+    }
+
+    /// Get the context of the device, or set it to the context returned by
+    /// `init` if it holds none yet
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_OBJECT_NAME_COLLISION` if the device
+    /// already holds a context of another type than `T`.
+    pub fn context_or_init<T: Send + Sync + 'static>(
+        &self,
+        init: impl FnOnce() -> T,
+    ) -> Result<&T, NTSTATUS> {
+        self.context
+            .get_or_init(|| Box::new(init()))
+            .downcast_ref()
+            .ok_or(STATUS_OBJECT_NAME_COLLISION)
+    }
+
+    /// Register `queue` with the device under `tag`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the queue could not be registered:
+    /// * `STATUS_OBJECT_NAME_COLLISION` if a queue is already registered under
+    ///   `tag`
+    /// * `STATUS_INSUFFICIENT_RESOURCES` if [`MAX_REGISTERED_QUEUES`] queues
+    ///   are already registered
+    pub fn register_queue(&self, tag: u32, queue: &IoQueue) -> Result<(), NTSTATUS> {
+        let mut state = self.state.borrow_mut();
+        if state.registered_queues.iter().any(|(t, _)| *t == tag) {
+            return Err(STATUS_OBJECT_NAME_COLLISION);
+        }
+        if state.registered_queues.len() >= MAX_REGISTERED_QUEUES {
+            return Err(STATUS_INSUFFICIENT_RESOURCES);
+        }
+        state.registered_queues.push((tag, queue.clone()));
+        Ok(())
+    }
+
+    /// Look up the queue registered with the device under `tag`
+    #[must_use]
+    pub fn queue_by_tag(&self, tag: u32) -> Option<IoQueue> {
+        self.state
+            .borrow()
+            .registered_queues
+            .iter()
+            .find_map(|(t, queue)| (*t == tag).then(|| queue.clone()))
+    }
+
+    /// Returns `true` if `self` and `other` are handles to the same device
+    #[must_use]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.state, &other.state)
+    }
+}
+
+/// Fake of [`super::FileObject`]
+#[derive(Clone, Default)]
+pub struct FileObject {
+    device: Rc<RefCell<Option<Device>>>,
+    file_name: Rc<OnceCell<Vec<u16>>>,
+    context: Rc<OnceCell<Box<dyn Any>>>,
+}
+
+impl FileObject {
+    /// Create a fake file object for a handle to the device itself
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a fake file object for a handle to `file_name` on the device
+    #[must_use]
+    pub fn with_file_name(file_name: &str) -> Self {
+        let file_object = Self::default();
+        file_object
+            .file_name
+            .get_or_init(|| file_name.encode_utf16().collect());
+        file_object
+    }
+
+    /// Set the device returned by [`FileObject::get_device()`]
+    pub fn set_device(&self, device: &Device) {
+        *self.device.borrow_mut() = Some(device.clone());
+    }
+
+    /// Get the device the file object was opened on
+    ///
+    /// # Panics
+    ///
+    /// Panics if the device of the file object was never set with
+    /// [`FileObject::set_device()`]
+    #[must_use]
+    pub fn get_device(&self) -> Device {
+        self.device
+            .borrow()
+            .clone()
+            .expect("Device of the file object was never set")
+    }
+
+    /// Get the name of the file opened on the device, or `None` for a handle
+    /// to the device itself
+    #[must_use]
+    pub fn file_name(&self) -> Option<UnicodeStr<'_>> {
+        self.file_name
+            .get()
+            .filter(|file_name| !file_name.is_empty())
+            .and_then(|file_name| UnicodeStr::from_slice(file_name).ok())
+    }
+
+    /// Set the context of the file object, and return a reference to it
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_OBJECT_NAME_COLLISION` if the file
+    /// object already holds a context.
+    pub fn set_context<T: Send + Sync + 'static>(&self, context: T) -> Result<&T, NTSTATUS> {
+        self.context
+            .set(Box::new(context))
+            .map_err(|_| STATUS_OBJECT_NAME_COLLISION)?;
+        self.context().ok_or(STATUS_OBJECT_NAME_COLLISION)
+    }
+
+    /// Get the context of the file object set with
+    /// [`FileObject::set_context()`], or `None` if the file object holds no
+    /// context of type `T`
+    #[must_use]
+    pub fn context<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.context.get()?.downcast_ref()
+    }
+
+    /// Get the context of the file object, or set it to the context returned by
+    /// `init` if it holds none yet
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_OBJECT_NAME_COLLISION` if the file
+    /// object already holds a context of another type than `T`.
+    pub fn context_or_init<T: Send + Sync + 'static>(
+        &self,
+        init: impl FnOnce() -> T,
+    ) -> Result<&T, NTSTATUS> {
+        self.context
+            .get_or_init(|| Box::new(init()))
+            .downcast_ref()
+            .ok_or(STATUS_OBJECT_NAME_COLLISION)
+    }
+
+    /// Returns `true` if `self` and `other` are handles to the same file object
+    #[must_use]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.context, &other.context)
+    }
+}
+
+/// Fake of [`super::IoQueue`].
+///
+/// Requests are added with [`IoQueue::enqueue()`] and taken in order with
+/// [`IoQueue::dequeue()`], which stand in for the framework delivering
+/// requests to the driver.
+#[derive(Clone, Default)]
+pub struct IoQueue {
+    state: Rc<RefCell<IoQueueFakeState>>,
+    context: Rc<OnceCell<Box<dyn Any>>>,
+}
+
+#[derive(Default)]
+struct IoQueueFakeState {
+    accepts_requests: bool,
+    dispatches_requests: bool,
+    requests: VecDeque<Request>,
+    driver_requests: ULONG,
+    device: Option<Device>,
+}
+
+impl IoQueue {
+    /// Create a fake queue that accepts and delivers requests
+    #[must_use]
+    pub fn new() -> Self {
+        let queue = Self::default();
+        queue.start();
+        queue
+    }
+
+    /// Create a fake queue that accepts and delivers requests. The arguments
+    /// are ignored, they are only there to match [`super::IoQueue::try_new()`]
+    ///
+    /// # Errors
+    ///
+    /// This function does not fail, the result is only there to match
+    /// [`super::IoQueue::try_new()`]
+    ///
+    /// # Safety
+    ///
+    /// This function is always safe to call, it is only `unsafe` to match
+    /// [`super::IoQueue::try_new()`]
+    pub unsafe fn try_new(
+        _device: WDFDEVICE,
+        _queue_config: &mut WDF_IO_QUEUE_CONFIG,
+        _attributes: &mut WDF_OBJECT_ATTRIBUTES,
+    ) -> Result<Self, NTSTATUS> {
+        Ok(Self::new())
+    }
+
+    /// Create a fake queue that accepts and delivers requests, and holds
+    /// `context`. The device and configuration are ignored, they are only
+    /// there to match [`super::IoQueue::create_with_context()`]
+    ///
+    /// # Errors
+    ///
+    /// This function does not fail, the result is only there to match
+    /// [`super::IoQueue::create_with_context()`]
+    ///
+    /// # Safety
+    ///
+    /// This function is always safe to call, it is only `unsafe` to match
+    /// [`super::IoQueue::create_with_context()`]
+    pub unsafe fn create_with_context<T: Send + Sync + 'static>(
+        _device: WDFDEVICE,
+        _queue_config: &mut WDF_IO_QUEUE_CONFIG,
+        context: T,
+    ) -> Result<Self, NTSTATUS> {
+        let queue = Self::new();
+        queue
+            .context
+            .set(Box::new(context))
+            .map_err(|_| STATUS_OBJECT_NAME_COLLISION)?;
+        Ok(queue)
+    }
+
+    /// Get the context of the queue given to
+    /// [`IoQueue::create_with_context()`], or `None` if the queue holds no
+    /// context of type `T`
+    #[must_use]
+    pub fn context<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.context.get()?.downcast_ref()
+    }
+
+    /// Set the device returned by [`IoQueue::get_device()`]
+    pub fn set_device(&self, device: &Device) {
+        self.state.borrow_mut().device = Some(device.clone());
+    }
+
+    /// Get the device the queue belongs to
+    ///
+    /// # Panics
+    ///
+    /// Panics if the device of the queue was never set with
+    /// [`IoQueue::set_device()`]
+    #[must_use]
+    pub fn get_device(&self) -> Device {
+        self.state
+            .borrow()
+            .device
+            .clone()
+            .expect("Device of the queue was never set")
+    }
+
+    /// Add `request` to the tail of the queue, like the framework does when
+    /// the device receives a request.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_INVALID_DEVICE_REQUEST` if the queue
+    /// does not accept requests.
+    pub fn enqueue(&self, request: &Request) -> Result<(), NTSTATUS> {
+        let mut state = self.state.borrow_mut();
+        if !state.accepts_requests {
+            return Err(STATUS_INVALID_DEVICE_REQUEST);
+        }
+        request.state.borrow_mut().queue = Some(self.clone());
+        state.requests.push_back(request.clone());
+        Ok(())
+    }
+
+    /// Take the request at the head of the queue, like the framework does when
+    /// it delivers a request to the driver. Returns `None` if the queue is
+    /// empty or does not deliver requests.
+    #[must_use]
+    pub fn dequeue(&self) -> Option<Request> {
+        let mut state = self.state.borrow_mut();
+        if !state.dispatches_requests {
+            return None;
+        }
+        let request = state.requests.pop_front()?;
+        state.driver_requests += 1;
+        Some(request)
+    }
+
+    /// Retrieve the request at the head of the queue, like a driver does with
+    /// a queue with manual dispatching
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_NO_MORE_ENTRIES` if the queue is
+    /// empty or does not deliver requests.
+    pub fn retrieve_next_request(&self) -> Result<Request, NTSTATUS> {
+        self.dequeue().ok_or(STATUS_NO_MORE_ENTRIES)
+    }
+
+    /// Retrieve the first request of the queue that was sent through
+    /// `file_object`, like a driver does with a queue with manual dispatching
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_NO_MORE_ENTRIES` if the queue holds
+    /// no request of `file_object` or does not deliver requests.
+    pub fn retrieve_request_by_file_object(
+        &self,
+        file_object: &FileObject,
+    ) -> Result<Request, NTSTATUS> {
+        let mut state = self.state.borrow_mut();
+        if !state.dispatches_requests {
+            return Err(STATUS_NO_MORE_ENTRIES);
+        }
+        let index = state
+            .requests
+            .iter()
+            .position(|request| {
+                request
+                    .file_object()
+                    .is_some_and(|request_file_object| request_file_object.ptr_eq(file_object))
+            })
+            .ok_or(STATUS_NO_MORE_ENTRIES)?;
+        let request = state.requests.remove(index).ok_or(STATUS_NO_MORE_ENTRIES)?;
+        state.driver_requests += 1;
+        Ok(request)
+    }
+
+    /// Start the queue, so that it accepts new requests and delivers them
+    pub fn start(&self) {
+        let mut state = self.state.borrow_mut();
+        state.accepts_requests = true;
+        state.dispatches_requests = true;
+    }
+
+    /// Stop the queue from delivering requests
+    pub fn stop(&self) {
+        self.state.borrow_mut().dispatches_requests = false;
+    }
+
+    /// Stop the queue from delivering requests. Requests delivered to the
+    /// driver are not tracked, so this does not wait.
+    pub fn stop_synchronously(&self, _irql: PassiveLevel) {
+        self.stop();
+    }
+
+    /// Purge the queue: it stops accepting new requests and the queued
+    /// requests are cancelled, i.e. dropped
+    pub fn purge(&self) {
+        let mut state = self.state.borrow_mut();
+        state.accepts_requests = false;
+        state.requests.clear();
+    }
+
+    /// Purge the queue. Requests delivered to the driver are not tracked, so
+    /// this does not wait.
+    pub fn purge_synchronously(&self, _irql: PassiveLevel) {
+        self.purge();
+    }
+
+    /// Stop and purge the queue: it stops delivering requests and the queued
+    /// requests are cancelled, i.e. dropped, but it keeps accepting new ones
+    pub fn stop_and_purge(&self) {
+        let mut state = self.state.borrow_mut();
+        state.dispatches_requests = false;
+        state.requests.clear();
+    }
+
+    /// Stop and purge the queue. Requests delivered to the driver are not
+    /// tracked, so this does not wait.
+    pub fn stop_and_purge_synchronously(&self, _irql: PassiveLevel) {
+        self.stop_and_purge();
+    }
+
+    /// Drain the queue: it stops accepting new requests, but keeps delivering
+    /// the queued ones
+    pub fn drain(&self) {
+        self.state.borrow_mut().accepts_requests = false;
+    }
+
+    /// Drain the queue. Requests delivered to the driver are not tracked, so
+    /// this does not wait.
+    pub fn drain_synchronously(&self, _irql: PassiveLevel) {
+        self.drain();
+    }
+
+    /// Get the current state of the queue
+    #[must_use]
+    pub fn get_state(&self) -> IoQueueState {
+        let state = self.state.borrow();
+        IoQueueState {
+            accepts_requests: state.accepts_requests,
+            dispatches_requests: state.dispatches_requests,
+            queue_requests: ULONG::try_from(state.requests.len()).unwrap_or(ULONG::MAX),
+            driver_requests: state.driver_requests,
+        }
+    }
+
+    /// Returns `true` if `self` and `other` are handles to the same queue
+    #[must_use]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.state, &other.state)
+    }
+}
+
+/// Fake of [`super::IoQueueState`]
+#[derive(Clone, Copy, Debug)]
+pub struct IoQueueState {
+    accepts_requests: bool,
+    dispatches_requests: bool,
+    queue_requests: ULONG,
+    driver_requests: ULONG,
+}
+impl IoQueueState {
+    /// Returns `true` if the queue accepts new requests
+    #[must_use]
+    pub const fn accepts_requests(&self) -> bool {
+        self.accepts_requests
+    }
+
+    /// Returns `true` if the queue delivers requests to the driver
+    #[must_use]
+    pub const fn dispatches_requests(&self) -> bool {
+        self.dispatches_requests
+    }
+
+    /// Returns `true` if the queue both accepts new requests and delivers them
+    /// to the driver
+    #[must_use]
+    pub const fn is_ready(&self) -> bool {
+        self.accepts_requests && self.dispatches_requests
+    }
+
+    /// Returns `true` if the queue has no queued requests and no requests
+    /// delivered to the driver
+    #[must_use]
+    pub const fn is_idle(&self) -> bool {
+        self.queue_requests == 0 && self.driver_requests == 0
+    }
+
+    /// Get the number of requests that are queued
+    #[must_use]
+    pub const fn queue_requests(&self) -> ULONG {
+        self.queue_requests
+    }
+
+    /// Get the number of requests that were delivered to the driver and were
+    /// not requeued
+    #[must_use]
+    pub const fn driver_requests(&self) -> ULONG {
+        self.driver_requests
+    }
+}
+
+/// Fake of [`super::RequestCancelCallback`], invoked with the fake
+/// [`Request`] by [`Request::cancel()`]
+pub trait RequestCancelCallback {
+    /// Handles the cancellation of `request`
+    fn cancel(request: Request);
+}
+
+/// Fake of [`super::Request`]
+#[derive(Clone)]
+pub struct Request {
+    state: Rc<RefCell<RequestState>>,
+}
+
+struct RequestState {
+    params: RequestParameters,
+    file_object: Option<FileObject>,
+    queue: Option<IoQueue>,
+    completion_status: Option<NTSTATUS>,
+    information: Option<usize>,
+    input_buffer: Vec<u8>,
+    output_buffer: Vec<u8>,
+    cancelled: bool,
+    cancel_callback: Option<fn(Request)>,
+}
+
+impl Request {
+    /// Create a fake request with the given parameters, that is not associated
+    /// with a file object
+    #[must_use]
+    pub fn new(params: RequestParameters) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(RequestState {
+                params,
+                file_object: None,
+                queue: None,
+                completion_status: None,
+                information: None,
+                input_buffer: Vec::new(),
+                output_buffer: Vec::new(),
+                cancelled: false,
+                cancel_callback: None,
+            })),
+        }
+    }
+
+    /// Set the file object returned by [`Request::file_object()`]
+    pub fn set_file_object(&self, file_object: &FileObject) {
+        self.state.borrow_mut().file_object = Some(file_object.clone());
+    }
+
+    /// Set the input buffer returned by [`Request::retrieve_input_buffer()`]
+    pub fn set_input_buffer(&self, input_buffer: &[u8]) {
+        self.state.borrow_mut().input_buffer = input_buffer.to_vec();
+    }
+
+    /// Set the length of the zero-filled output buffer returned by
+    /// [`Request::retrieve_output_buffer()`]
+    pub fn set_output_buffer_length(&self, length: usize) {
+        self.state.borrow_mut().output_buffer = alloc::vec![0; length];
+    }
+
+    /// Get the contents of the output buffer, as written by the driver
+    #[must_use]
+    pub fn output_buffer(&self) -> Vec<u8> {
+        self.state.borrow().output_buffer.clone()
+    }
+
+    /// Get the parameters of the request
+    #[must_use]
+    pub fn params(&self) -> RequestParameters {
+        self.state.borrow().params
+    }
+
+    /// Get the file object the request was sent through
+    #[must_use]
+    pub fn file_object(&self) -> Option<FileObject> {
+        self.state.borrow().file_object.clone()
+    }
+
+    /// Get the queue the request was delivered from
+    ///
+    /// # Panics
+    ///
+    /// Panics if the request was never added to a queue with
+    /// [`IoQueue::enqueue()`]
+    #[must_use]
+    pub fn get_io_queue(&self) -> IoQueue {
+        self.state
+            .borrow()
+            .queue
+            .clone()
+            .expect("Request was never added to a queue")
+    }
+
+    /// Get the input buffer of a buffered request, set with
+    /// [`Request::set_input_buffer()`]. Read and write requests are buffered
+    /// in the fake.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the buffer could not be retrieved:
+    /// * `STATUS_BUFFER_TOO_SMALL` if the buffer is shorter than
+    ///   `minimum_length`
+    /// * `STATUS_INVALID_DEVICE_REQUEST` if the request is a
+    ///   [`TransferMethod::InDirect`] or [`TransferMethod::OutDirect`] device
+    ///   control request
+    pub fn retrieve_input_buffer(
+        &mut self,
+        minimum_length: usize,
+    ) -> Result<Ref<'_, [u8]>, NTSTATUS> {
+        if self.uses_direct_buffers() {
+            return Err(STATUS_INVALID_DEVICE_REQUEST);
+        }
+        self.input_buffer(minimum_length)
+    }
+
+    /// Get the input buffer of the request, set with
+    /// [`Request::set_input_buffer()`], whatever its transfer method
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_BUFFER_TOO_SMALL` if the buffer is
+    /// shorter than `minimum_length`.
+    pub fn retrieve_direct_input_buffer(
+        &mut self,
+        minimum_length: usize,
+    ) -> Result<UserInputBuffer<'_>, NTSTATUS> {
+        self.input_buffer(minimum_length)
+            .map(|buffer| UserInputBuffer { buffer })
+    }
+
+    /// Get the output buffer of a buffered request, of the length set with
+    /// [`Request::set_output_buffer_length()`]. Read and write requests are
+    /// buffered in the fake.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the buffer could not be retrieved:
+    /// * `STATUS_BUFFER_TOO_SMALL` if the buffer is shorter than
+    ///   `minimum_length`
+    /// * `STATUS_INVALID_DEVICE_REQUEST` if the request is a
+    ///   [`TransferMethod::InDirect`] or [`TransferMethod::OutDirect`] device
+    ///   control request
+    pub fn retrieve_output_buffer(
+        &mut self,
+        minimum_length: usize,
+    ) -> Result<RefMut<'_, [u8]>, NTSTATUS> {
+        if self.uses_direct_buffers() {
+            return Err(STATUS_INVALID_DEVICE_REQUEST);
+        }
+        self.output_buffer_mut(minimum_length)
+    }
+
+    /// Get the output buffer of the request, of the length set with
+    /// [`Request::set_output_buffer_length()`], whatever its transfer method
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_BUFFER_TOO_SMALL` if the buffer is
+    /// shorter than `minimum_length`.
+    pub fn retrieve_direct_output_buffer(
+        &mut self,
+        minimum_length: usize,
+    ) -> Result<UserOutputBuffer<'_>, NTSTATUS> {
+        self.output_buffer_mut(minimum_length)
+            .map(|buffer| UserOutputBuffer { buffer })
+    }
+
+    /// Get the input buffer of a `METHOD_NEITHER` request, set with
+    /// [`Request::set_input_buffer()`]. The fake has no user address space, so
+    /// this behaves like [`Request::retrieve_direct_input_buffer()`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_BUFFER_TOO_SMALL` if the buffer is
+    /// shorter than `minimum_length`.
+    pub fn probe_and_lock_user_buffer_for_read(
+        &mut self,
+        minimum_length: usize,
+    ) -> Result<UserInputBuffer<'_>, NTSTATUS> {
+        self.retrieve_direct_input_buffer(minimum_length)
+    }
+
+    /// Get the output buffer of a `METHOD_NEITHER` request, of the length set
+    /// with [`Request::set_output_buffer_length()`]. The fake has no user
+    /// address space, so this behaves like
+    /// [`Request::retrieve_direct_output_buffer()`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_BUFFER_TOO_SMALL` if the buffer is
+    /// shorter than `minimum_length`.
+    pub fn probe_and_lock_user_buffer_for_write(
+        &mut self,
+        minimum_length: usize,
+    ) -> Result<UserOutputBuffer<'_>, NTSTATUS> {
+        self.retrieve_direct_output_buffer(minimum_length)
+    }
+
+    /// Returns `true` if the request is a [`TransferMethod::InDirect`] or
+    /// [`TransferMethod::OutDirect`] device control request
+    fn uses_direct_buffers(&self) -> bool {
+        match self.state.borrow().params {
+            RequestParameters::DeviceControl { code, .. }
+            | RequestParameters::InternalDeviceControl { code, .. } => matches!(
+                code.method(),
+                TransferMethod::InDirect | TransferMethod::OutDirect
+            ),
+            _ => false,
+        }
+    }
+
+    /// Get the input buffer, if it holds at least `minimum_length` bytes
+    fn input_buffer(&self, minimum_length: usize) -> Result<Ref<'_, [u8]>, NTSTATUS> {
+        let state = self.state.borrow();
+        if state.input_buffer.len() < minimum_length {
+            return Err(STATUS_BUFFER_TOO_SMALL);
+        }
+        Ok(Ref::map(state, |state| state.input_buffer.as_slice()))
+    }
+
+    /// Get the output buffer, if it holds at least `minimum_length` bytes
+    fn output_buffer_mut(&self, minimum_length: usize) -> Result<RefMut<'_, [u8]>, NTSTATUS> {
+        let state = self.state.borrow_mut();
+        if state.output_buffer.len() < minimum_length {
+            return Err(STATUS_BUFFER_TOO_SMALL);
+        }
+        Ok(RefMut::map(state, |state| {
+            state.output_buffer.as_mut_slice()
+        }))
+    }
+
+    /// Check whether the request was cancelled with [`Request::cancel()`]
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.state.borrow().cancelled
+    }
+
+    /// Mark the request cancelable, so that [`RequestCancelCallback::cancel`]
+    /// of `T` is invoked by [`Request::cancel()`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_CANCELLED` if the request was already
+    /// cancelled.
+    ///
+    /// # Safety
+    ///
+    /// This function is always safe to call, it is only `unsafe` to match
+    /// [`super::Request::mark_cancelable()`]
+    pub unsafe fn mark_cancelable<T: RequestCancelCallback>(&self) -> Result<(), NTSTATUS> {
+        let mut state = self.state.borrow_mut();
+        if state.cancelled {
+            return Err(STATUS_CANCELLED);
+        }
+        state.cancel_callback = Some(T::cancel);
+        Ok(())
+    }
+
+    /// Make a request marked with [`Request::mark_cancelable()`] non-cancelable
+    /// again
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_CANCELLED` if the request was
+    /// cancelled.
+    ///
+    /// # Safety
+    ///
+    /// This function is always safe to call, it is only `unsafe` to match
+    /// [`super::Request::unmark_cancelable()`]
+    pub unsafe fn unmark_cancelable(&self) -> Result<(), NTSTATUS> {
+        let mut state = self.state.borrow_mut();
+        if state.cancelled {
+            return Err(STATUS_CANCELLED);
+        }
+        state.cancel_callback = None;
+        Ok(())
+    }
+
+    /// Mark the request cancelable like [`Request::mark_cancelable()`], and
+    /// take ownership of it as a [`CancellableMarkedRequest`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return the request back if it was already cancelled.
+    pub fn into_cancelable<T: RequestCancelCallback>(
+        self,
+    ) -> Result<CancellableMarkedRequest, Self> {
+        // SAFETY: The fake is always safe to mark
+        match unsafe { self.mark_cancelable::<T>() } {
+            Ok(()) => Ok(CancellableMarkedRequest { request: self }),
+            Err(_) => Err(self),
+        }
+    }
+
+    /// Simulate the sender of the request cancelling it. If the request is
+    /// marked cancelable, it is made non-cancelable and its cancel callback is
+    /// invoked. Returns whether the callback was invoked.
+    pub fn cancel(&self) -> bool {
+        let cancel_callback = {
+            let mut state = self.state.borrow_mut();
+            state.cancelled = true;
+            state.cancel_callback.take()
+        };
+        cancel_callback
+            .map(|callback| callback(self.clone()))
+            .is_some()
+    }
+
+    /// Return the request to the head of the queue it was delivered from
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_INVALID_DEVICE_REQUEST` if the request
+    /// was never added to a queue, along with the request.
+    pub fn requeue(self) -> Result<(), (Self, NTSTATUS)> {
+        let Some(queue) = self.state.borrow().queue.clone() else {
+            return Err((self, STATUS_INVALID_DEVICE_REQUEST));
+        };
+        let mut queue_state = queue.state.borrow_mut();
+        queue_state.driver_requests = queue_state.driver_requests.saturating_sub(1);
+        queue_state.requests.push_front(self);
+        Ok(())
+    }
+
+    /// Add the request to the tail of `queue`
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_INVALID_DEVICE_REQUEST` if `queue`
+    /// does not accept requests, along with the request.
+    pub fn forward_to_io_queue(self, queue: &IoQueue) -> Result<(), (Self, NTSTATUS)> {
+        let previous_queue = self.state.borrow().queue.clone();
+        if let Err(nt_status) = queue.enqueue(&self) {
+            return Err((self, nt_status));
+        }
+        if let Some(previous_queue) = previous_queue {
+            let mut queue_state = previous_queue.state.borrow_mut();
+            queue_state.driver_requests = queue_state.driver_requests.saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    /// Set the information reported to the sender of the request when it is
+    /// completed
+    pub fn set_information(&self, information: usize) {
+        self.state.borrow_mut().information = Some(information);
+    }
+
+    /// Get the information reported to the sender of the request, as set with
+    /// [`Request::set_information()`], or 0 if it was not set
+    #[must_use]
+    pub fn get_information(&self) -> usize {
+        self.state.borrow().information.unwrap_or(0)
+    }
+
+    /// Complete the request with `status`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the request was already completed
+    pub fn complete(self, status: NTSTATUS) {
+        let mut state = self.state.borrow_mut();
+        assert!(
+            state.completion_status.is_none(),
+            "Request was already completed"
+        );
+        state.completion_status = Some(status);
+        if let Some(queue) = &state.queue {
+            let mut queue_state = queue.state.borrow_mut();
+            queue_state.driver_requests = queue_state.driver_requests.saturating_sub(1);
+        }
+    }
+
+    /// Complete the request with `status`, and report `information` to the
+    /// sender of the request
+    ///
+    /// # Panics
+    ///
+    /// Panics if the request was already completed
+    pub fn complete_with_information(self, status: NTSTATUS, information: usize) {
+        self.state.borrow_mut().information = Some(information);
+        self.complete(status);
+    }
+
+    /// Acknowledge the stop of the queue the request was delivered from. The
+    /// fake does not track stops, so this does nothing.
+    pub const fn stop_acknowledge(&self) {}
+
+    /// Acknowledge the stop of the queue the request was delivered from, and
+    /// return the request to the head of the queue
+    ///
+    /// # Panics
+    ///
+    /// Panics if the request was never added to a queue
+    pub fn stop_acknowledge_and_requeue(self) {
+        assert!(self.requeue().is_ok(), "Request was never added to a queue");
+    }
+
+    /// Get the status the request was completed with, or `None` if it was not
+    /// completed
+    #[must_use]
+    pub fn completion_status(&self) -> Option<NTSTATUS> {
+        self.state.borrow().completion_status
+    }
+
+    /// Get the information the request was completed with, set through
+    /// [`Request::complete_with_information()`] or
+    /// [`Request::set_information()`], or `None` if it was not
+    #[must_use]
+    pub fn information(&self) -> Option<usize> {
+        self.state.borrow().information
+    }
+}
+
+/// Fake of [`super::UserInputBuffer`], returned by
+/// [`Request::retrieve_direct_input_buffer()`] and
+/// [`Request::probe_and_lock_user_buffer_for_read()`]
+pub struct UserInputBuffer<'a> {
+    buffer: Ref<'a, [u8]>,
+}
+
+impl UserInputBuffer<'_> {
+    /// Get the length of the buffer, in bytes
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns `true` if the buffer holds no bytes
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Copy the bytes of the buffer, starting `offset` bytes into it, to
+    /// `destination`, filling it
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_INVALID_BUFFER_SIZE` if the buffer
+    /// holds fewer than `destination.len()` bytes after `offset`.
+    pub fn copy_to(&self, offset: usize, destination: &mut [u8]) -> Result<(), NTSTATUS> {
+        let source = offset
+            .checked_add(destination.len())
+            .and_then(|end| self.buffer.get(offset..end))
+            .ok_or(STATUS_INVALID_BUFFER_SIZE)?;
+        destination.copy_from_slice(source);
+        Ok(())
+    }
+}
+
+/// Fake of [`super::UserOutputBuffer`], returned by
+/// [`Request::retrieve_direct_output_buffer()`] and
+/// [`Request::probe_and_lock_user_buffer_for_write()`]
+pub struct UserOutputBuffer<'a> {
+    buffer: RefMut<'a, [u8]>,
+}
+
+impl UserOutputBuffer<'_> {
+    /// Get the length of the buffer, in bytes
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns `true` if the buffer holds no bytes
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Copy `source` into the buffer, starting `offset` bytes into it
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_INVALID_BUFFER_SIZE` if `source` does
+    /// not fit in the buffer at `offset`.
+    pub fn copy_from(&mut self, offset: usize, source: &[u8]) -> Result<(), NTSTATUS> {
+        let destination = offset
+            .checked_add(source.len())
+            .and_then(|end| self.buffer.get_mut(offset..end))
+            .ok_or(STATUS_INVALID_BUFFER_SIZE)?;
+        destination.copy_from_slice(source);
+        Ok(())
+    }
+}
+
+/// Fake of [`super::CancellableMarkedRequest`]
+#[must_use = "the request must be unmarked or completed unless it is cancelled"]
+pub struct CancellableMarkedRequest {
+    request: Request,
+}
+
+impl CancellableMarkedRequest {
+    /// Make the request non-cancelable again, and return it
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_CANCELLED` if the request was
+    /// cancelled.
+    pub fn unmark(self) -> Result<Request, NTSTATUS> {
+        // SAFETY: The fake is always safe to unmark
+        unsafe { self.request.unmark_cancelable() }.map(|()| self.request)
+    }
+
+    /// Complete the request with `status`, unless it was cancelled
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_CANCELLED` if the request was
+    /// cancelled.
+    pub fn complete_if_not_cancelled(self, status: NTSTATUS) -> Result<(), NTSTATUS> {
+        self.unmark().map(|request| request.complete(status))
+    }
+
+    /// Complete the request with `status` and `information`, unless it was
+    /// cancelled
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_CANCELLED` if the request was
+    /// cancelled.
+    pub fn complete_with_information_if_not_cancelled(
+        self,
+        status: NTSTATUS,
+        information: usize,
+    ) -> Result<(), NTSTATUS> {
+        self.unmark()
+            .map(|request| request.complete_with_information(status, information))
+    }
+}
+
+/// Fake of [`super::Timer`].
+///
+/// The timer never expires on its own. Tests call [`Timer::expire()`] to
+/// simulate the due time passing.
+#[derive(Clone, Default)]
+pub struct Timer {
+    state: Rc<RefCell<TimerState>>,
+}
+
+#[derive(Default)]
+struct TimerState {
+    period: ULONG,
+    due_time: Option<i64>,
+    expirations: usize,
+}
+
+impl Timer {
+    /// Create a fake timer that is not started. Only the `Period` of
+    /// `timer_config` is used, to tell if the timer is periodic.
+    ///
+    /// # Errors
+    ///
+    /// This function does not fail, the result is only there to match
+    /// [`super::Timer::try_new()`]
+    pub fn try_new(
+        timer_config: &mut WDF_TIMER_CONFIG,
+        _attributes: &mut WDF_OBJECT_ATTRIBUTES,
+    ) -> Result<Self, NTSTATUS> {
+        let timer = Self::default();
+        timer.state.borrow_mut().period = timer_config.Period;
+        Ok(timer)
+    }
+
+    /// Create a fake timer that is not started. This is an alias for
+    /// [`Timer::try_new()`]
+    ///
+    /// # Errors
+    ///
+    /// This function does not fail, the result is only there to match
+    /// [`super::Timer::create()`]
+    pub fn create(
+        timer_config: &mut WDF_TIMER_CONFIG,
+        attributes: &mut WDF_OBJECT_ATTRIBUTES,
+    ) -> Result<Self, NTSTATUS> {
+        Self::try_new(timer_config, attributes)
+    }
+
+    /// Start the timer with a raw due time
+    ///
+    /// Returns `true` if the timer was already started.
+    #[must_use]
+    pub fn start(&self, due_time: i64) -> bool {
+        self.state.borrow_mut().due_time.replace(due_time).is_some()
+    }
+
+    /// Start the timer, so that it is due after `duration`
+    ///
+    /// Returns `true` if the timer was already started.
+    #[must_use]
+    pub fn start_in(&self, duration: Duration) -> bool {
+        self.start(relative_due_time(duration))
+    }
+
+    /// Start the timer, so that it is due at `time`
+    ///
+    /// Returns `true` if the timer was already started.
+    #[must_use]
+    pub fn start_at(&self, time: SystemTime) -> bool {
+        self.start(time.as_raw())
+    }
+
+    /// Stop the timer
+    ///
+    /// Returns `true` if the timer was started.
+    #[must_use]
+    pub fn stop(&self) -> bool {
+        self.state.borrow_mut().due_time.take().is_some()
+    }
+
+    /// Stop the timer. The fake timer never runs its callback on its own, so
+    /// this does not wait.
+    ///
+    /// Returns `true` if the timer was started.
+    #[must_use]
+    pub fn stop_synchronously(&self, _irql: PassiveLevel) -> bool {
+        self.stop()
+    }
+
+    /// Get the raw due time the timer was last started with, or `None` if it
+    /// is not started. Relative due times are negative.
+    #[must_use]
+    pub fn due_time(&self) -> Option<i64> {
+        self.state.borrow().due_time
+    }
+
+    /// Returns `true` if the timer is started
+    #[must_use]
+    pub fn is_started(&self) -> bool {
+        self.due_time().is_some()
+    }
+
+    /// Simulate the due time of the timer passing. A periodic timer stays
+    /// started, any other timer is stopped. The test then invokes the driver's
+    /// timer callback logic.
+    ///
+    /// Returns `false` if the timer was not started, so it did not expire.
+    pub fn expire(&self) -> bool {
+        let mut state = self.state.borrow_mut();
+        if state.due_time.is_none() {
+            return false;
+        }
+        if state.period == 0 {
+            state.due_time = None;
+        }
+        state.expirations += 1;
+        true
+    }
+
+    /// Get the number of times the timer expired
+    #[must_use]
+    pub fn expirations(&self) -> usize {
+        self.state.borrow().expirations
+    }
+}
+
+/// Fake of [`super::WorkItemCallback`], invoked with the fake [`WorkItem`] by
+/// [`WorkItem::run()`]
+pub trait WorkItemCallback {
+    /// Performs the work deferred with [`WorkItem::enqueue()`]
+    fn run(work_item: &WorkItem, irql: PassiveLevel);
+}
+
+/// Fake of [`super::WorkItem`].
+///
+/// The work item never runs on its own. Tests call [`WorkItem::run()`] to
+/// simulate the framework running the callback of an enqueued work item.
+#[derive(Clone)]
+pub struct WorkItem {
+    state: Rc<RefCell<WorkItemState>>,
+    callback: Rc<dyn Fn(&Self, PassiveLevel)>,
+    context: Rc<OnceCell<Box<dyn Any>>>,
+}
+
+struct WorkItemState {
+    device: Device,
+    enqueued: bool,
+    runs: usize,
+}
+
+impl WorkItem {
+    /// Create a fake work item of `device`, that invokes
+    /// [`WorkItemCallback::run`] of `T` when it runs
+    ///
+    /// # Errors
+    ///
+    /// This function does not fail, the result is only there to match
+    /// [`super::WorkItem::try_new()`]
+    pub fn try_new<T: WorkItemCallback>(device: &Device) -> Result<Self, NTSTATUS> {
+        Ok(Self::with_callback(device, Rc::new(T::run)))
+    }
+
+    /// Create a fake work item of `device`, that invokes
+    /// [`WorkItemCallback::run`] of `T` when it runs. This is an alias for
+    /// [`WorkItem::try_new()`]
+    ///
+    /// # Errors
+    ///
+    /// This function does not fail, the result is only there to match
+    /// [`super::WorkItem::create()`]
+    pub fn create<T: WorkItemCallback>(device: &Device) -> Result<Self, NTSTATUS> {
+        Self::try_new::<T>(device)
+    }
+
+    /// Create a fake work item of `device`, that invokes `callback` when it
+    /// runs
+    ///
+    /// # Errors
+    ///
+    /// This function does not fail, the result is only there to match
+    /// [`super::WorkItem::try_new_with()`]
+    pub fn try_new_with<F>(device: &Device, callback: F) -> Result<Self, NTSTATUS>
+    where
+        F: Fn(&Self, PassiveLevel) + Send + Sync + 'static,
+    {
+        Ok(Self::with_callback(device, Rc::new(callback)))
+    }
+
+    /// Get the device the work item belongs to
+    #[must_use]
+    pub fn get_device(&self) -> Device {
+        self.state.borrow().device.clone()
+    }
+
+    /// Enqueue the work item, so that the next [`WorkItem::run()`] invokes its
+    /// callback
+    pub fn enqueue(&self) {
+        self.state.borrow_mut().enqueued = true;
+    }
+
+    /// Run the callback of the work item if it is enqueued, like
+    /// [`WorkItem::run()`], as the real work item waits for it to run
+    pub fn flush(&self, _irql: PassiveLevel) {
+        self.run();
+    }
+
+    /// Simulate the framework running the enqueued work item: its callback is
+    /// invoked once, and can enqueue the work item again.
+    ///
+    /// Returns `false` if the work item was not enqueued, so it did not run.
+    pub fn run(&self) -> bool {
+        {
+            let mut state = self.state.borrow_mut();
+            if !state.enqueued {
+                return false;
+            }
+            state.enqueued = false;
+            state.runs += 1;
+        }
+        // SAFETY: The fakes run on the host, in user mode, where the `IRQL` is
+        // always `PASSIVE_LEVEL`
+        let irql = unsafe { PassiveLevel::new_unchecked() };
+        (self.callback)(self, irql);
+        true
+    }
+
+    /// Returns `true` if the work item is enqueued and did not run yet
+    #[must_use]
+    pub fn is_enqueued(&self) -> bool {
+        self.state.borrow().enqueued
+    }
+
+    /// Get the number of times the work item ran
+    #[must_use]
+    pub fn runs(&self) -> usize {
+        self.state.borrow().runs
+    }
+
+    /// Set the context of the work item, and return a reference to it
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_OBJECT_NAME_COLLISION` if the work
+    /// item already holds a context.
+    pub fn set_context<T: Send + Sync + 'static>(&self, context: T) -> Result<&T, NTSTATUS> {
+        self.context
+            .set(Box::new(context))
+            .map_err(|_| STATUS_OBJECT_NAME_COLLISION)?;
+        self.context().ok_or(STATUS_OBJECT_NAME_COLLISION)
+    }
+
+    /// Get the context of the work item set with [`WorkItem::set_context()`],
+    /// or `None` if the work item holds no context of type `T`
+    #[must_use]
+    pub fn context<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.context.get()?.downcast_ref()
+    }
+
+    /// Get the context of the work item, or set it to the context returned by
+    /// `init` if it holds none yet
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_OBJECT_NAME_COLLISION` if the work
+    /// item already holds a context of another type than `T`.
+    pub fn context_or_init<T: Send + Sync + 'static>(
+        &self,
+        init: impl FnOnce() -> T,
+    ) -> Result<&T, NTSTATUS> {
+        self.context
+            .get_or_init(|| Box::new(init()))
+            .downcast_ref()
+            .ok_or(STATUS_OBJECT_NAME_COLLISION)
+    }
+
+    fn with_callback(device: &Device, callback: Rc<dyn Fn(&Self, PassiveLevel)>) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(WorkItemState {
+                device: device.clone(),
+                enqueued: false,
+                runs: 0,
+            })),
+            callback,
+            context: Rc::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wdk_sys::STATUS_SUCCESS;
+
+    use super::*;
+    use crate::{ControlCode, RequiredAccess};
+
+    #[test]
+    fn requeued_request_is_delivered_again() {
+        let queue = IoQueue::new();
+        let first = Request::new(RequestParameters::Cleanup);
+        let second = Request::new(RequestParameters::Close);
+        queue.enqueue(&first).unwrap();
+        queue.enqueue(&second).unwrap();
+
+        let request = queue.dequeue().unwrap();
+        assert_eq!(request.params(), RequestParameters::Cleanup);
+        assert!(request.get_io_queue().ptr_eq(&queue));
+        assert_eq!(queue.get_state().driver_requests(), 1);
+
+        assert!(request.requeue().is_ok());
+        assert_eq!(queue.get_state().driver_requests(), 0);
+        assert_eq!(queue.get_state().queue_requests(), 2);
+        assert_eq!(
+            queue.dequeue().unwrap().params(),
+            RequestParameters::Cleanup
+        );
+        assert_eq!(queue.dequeue().unwrap().params(), RequestParameters::Close);
+        assert!(queue.dequeue().is_none());
+    }
+
+    #[test]
+    fn manual_queue_requests_are_retrieved_by_file_object() {
+        let queue = IoQueue::new();
+        let first_file_object = FileObject::new();
+        let second_file_object = FileObject::new();
+        let first = Request::new(RequestParameters::Cleanup);
+        first.set_file_object(&first_file_object);
+        let second = Request::new(RequestParameters::Close);
+        second.set_file_object(&second_file_object);
+        queue.enqueue(&first).unwrap();
+        queue.enqueue(&second).unwrap();
+
+        let request = queue
+            .retrieve_request_by_file_object(&second_file_object)
+            .unwrap();
+        assert_eq!(request.params(), RequestParameters::Close);
+        assert_eq!(
+            queue
+                .retrieve_request_by_file_object(&second_file_object)
+                .err(),
+            Some(STATUS_NO_MORE_ENTRIES)
+        );
+        assert_eq!(
+            queue.retrieve_next_request().unwrap().params(),
+            RequestParameters::Cleanup
+        );
+        assert_eq!(
+            queue.retrieve_next_request().err(),
+            Some(STATUS_NO_MORE_ENTRIES)
+        );
+        assert_eq!(queue.get_state().driver_requests(), 2);
+    }
+
+    #[test]
+    fn queue_state_transitions() {
+        let queue = IoQueue::new();
+        assert!(queue.get_state().is_ready());
+        queue
+            .enqueue(&Request::new(RequestParameters::Cleanup))
+            .unwrap();
+
+        queue.stop();
+        assert!(queue.get_state().accepts_requests());
+        assert!(queue.dequeue().is_none());
+
+        queue.start();
+        queue.drain();
+        assert!(!queue.get_state().accepts_requests());
+        assert_eq!(
+            queue.enqueue(&Request::new(RequestParameters::Close)),
+            Err(STATUS_INVALID_DEVICE_REQUEST)
+        );
+        assert!(queue.dequeue().is_some());
+
+        queue.start();
+        queue
+            .enqueue(&Request::new(RequestParameters::Close))
+            .unwrap();
+        queue.purge();
+        assert_eq!(queue.get_state().queue_requests(), 0);
+
+        queue.start();
+        queue
+            .enqueue(&Request::new(RequestParameters::Close))
+            .unwrap();
+        queue.stop_and_purge();
+        let state = queue.get_state();
+        assert!(state.accepts_requests() && !state.dispatches_requests());
+        assert_eq!(state.queue_requests(), 0);
+    }
+
+    #[test]
+    fn user_buffers_are_copied_within_bounds() {
+        let mut request = Request::new(RequestParameters::Cleanup);
+        request.set_input_buffer(&[1, 2, 3, 4]);
+        request.set_output_buffer_length(4);
+
+        let mut destination = [0; 2];
+        let input = request.probe_and_lock_user_buffer_for_read(4).unwrap();
+        input.copy_to(2, &mut destination).unwrap();
+        assert_eq!(destination, [3, 4]);
+        assert_eq!(
+            input.copy_to(3, &mut destination),
+            Err(STATUS_INVALID_BUFFER_SIZE)
+        );
+        drop(input);
+
+        let mut output = request.probe_and_lock_user_buffer_for_write(4).unwrap();
+        output.copy_from(1, &[5, 6]).unwrap();
+        assert_eq!(
+            output.copy_from(usize::MAX, &[7]),
+            Err(STATUS_INVALID_BUFFER_SIZE)
+        );
+        drop(output);
+        assert_eq!(request.output_buffer(), [0, 5, 6, 0]);
+    }
+
+    #[test]
+    fn cancelled_request_invokes_cancel_callback() {
+        struct CompleteCancelled;
+
+        impl RequestCancelCallback for CompleteCancelled {
+            fn cancel(request: Request) {
+                request.complete(STATUS_CANCELLED);
+            }
+        }
+
+        let request = Request::new(RequestParameters::Cleanup);
+        // SAFETY: The fake is always safe to mark
+        unsafe { request.mark_cancelable::<CompleteCancelled>() }.unwrap();
+        // SAFETY: The fake is always safe to unmark
+        unsafe { request.unmark_cancelable() }.unwrap();
+        assert!(!request.cancel());
+        assert!(request.is_cancelled());
+        assert_eq!(request.completion_status(), None);
+        assert_eq!(
+            // SAFETY: The fake is always safe to mark
+            unsafe { request.mark_cancelable::<CompleteCancelled>() },
+            Err(STATUS_CANCELLED)
+        );
+
+        let request = Request::new(RequestParameters::Close);
+        // SAFETY: The fake is always safe to mark
+        unsafe { request.mark_cancelable::<CompleteCancelled>() }.unwrap();
+        assert!(request.cancel());
+        assert_eq!(request.completion_status(), Some(STATUS_CANCELLED));
+        // SAFETY: The fake is always safe to unmark
+        assert_eq!(
+            unsafe { request.unmark_cancelable() },
+            Err(STATUS_CANCELLED)
+        );
+    }
+
+    #[test]
+    fn cancellable_marked_request_is_completed_once() {
+        struct CompleteCancelled;
+
+        impl RequestCancelCallback for CompleteCancelled {
+            fn cancel(request: Request) {
+                request.complete(STATUS_CANCELLED);
+            }
+        }
+
+        let request = Request::new(RequestParameters::Cleanup);
+        let marked = request
+            .clone()
+            .into_cancelable::<CompleteCancelled>()
+            .ok()
+            .unwrap();
+        assert!(marked.complete_if_not_cancelled(STATUS_SUCCESS).is_ok());
+        assert_eq!(request.completion_status(), Some(STATUS_SUCCESS));
+
+        let request = Request::new(RequestParameters::Close);
+        let marked = request
+            .clone()
+            .into_cancelable::<CompleteCancelled>()
+            .ok()
+            .unwrap();
+        assert!(request.cancel());
+        assert_eq!(
+            marked.complete_with_information_if_not_cancelled(STATUS_SUCCESS, 4),
+            Err(STATUS_CANCELLED)
+        );
+        assert_eq!(request.completion_status(), Some(STATUS_CANCELLED));
+        assert_eq!(request.information(), None);
+        assert!(
+            request
+                .into_cancelable::<CompleteCancelled>()
+                .is_err_and(|request| request.completion_status() == Some(STATUS_CANCELLED))
+        );
+    }
+
+    #[test]
+    fn cancel_racing_completion_completes_request_once() {
+        /// Cancel callback that completes the request later, like a cancel
+        /// routine that is still running on another processor
+        struct CompleteLater;
+
+        impl RequestCancelCallback for CompleteLater {
+            fn cancel(_request: Request) {}
+        }
+
+        for cancel_first in [true, false] {
+            let request = Request::new(RequestParameters::Cleanup);
+            let marked = request
+                .clone()
+                .into_cancelable::<CompleteLater>()
+                .ok()
+                .unwrap();
+
+            if cancel_first {
+                // The cancel routine starts before the driver completes the request,
+                // so the driver must back off and leave the completion to it
+                assert!(request.cancel());
+                assert_eq!(
+                    marked.complete_if_not_cancelled(STATUS_SUCCESS),
+                    Err(STATUS_CANCELLED)
+                );
+                assert_eq!(request.completion_status(), None);
+                request.clone().complete(STATUS_CANCELLED);
+                assert_eq!(request.completion_status(), Some(STATUS_CANCELLED));
+            } else {
+                // The driver completes the request first, so the cancel routine is
+                // never invoked
+                assert!(marked.complete_if_not_cancelled(STATUS_SUCCESS).is_ok());
+                assert!(!request.cancel());
+                assert_eq!(request.completion_status(), Some(STATUS_SUCCESS));
+            }
+        }
+    }
+
+    #[test]
+    fn enqueued_work_item_runs_once() {
+        use core::sync::atomic::{AtomicU32, Ordering};
+
+        struct CountRuns;
+
+        impl WorkItemCallback for CountRuns {
+            fn run(work_item: &WorkItem, _irql: PassiveLevel) {
+                if let Some(runs) = work_item.context::<AtomicU32>() {
+                    runs.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+
+        let device = Device::new();
+        let work_item = WorkItem::try_new::<CountRuns>(&device).unwrap();
+        let runs = work_item.set_context(AtomicU32::new(0)).unwrap();
+        assert!(work_item.get_device().ptr_eq(&device));
+        assert!(!work_item.run());
+
+        work_item.enqueue();
+        work_item.enqueue();
+        assert!(work_item.is_enqueued());
+        assert!(work_item.run());
+        assert!(!work_item.run());
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+        assert_eq!(work_item.runs(), 1);
+
+        let requeuing = WorkItem::try_new_with(&device, |work_item, _| {
+            if work_item.runs() < 2 {
+                work_item.enqueue();
+            }
+        })
+        .unwrap();
+        // SAFETY: Tests run on the host, in user mode, at `PASSIVE_LEVEL`
+        let irql = unsafe { PassiveLevel::new_unchecked() };
+        requeuing.enqueue();
+        requeuing.flush(irql);
+        assert!(requeuing.is_enqueued());
+        requeuing.flush(irql);
+        assert!(!requeuing.is_enqueued());
+        assert_eq!(requeuing.runs(), 2);
+    }
+
+    #[test]
+    fn driver_devices_and_contexts() {
+        let driver = Driver::new();
+        let first = Device::new();
+        let second = Device::new();
+        first.set_context(1_u32).unwrap();
+        second.set_context(2_u32).unwrap();
+        assert_eq!(first.set_context(3_u32), Err(STATUS_OBJECT_NAME_COLLISION));
+        assert_eq!(first.context::<u64>(), None);
+
+        driver.register_device(&first).unwrap();
+        driver.register_device(&second).unwrap();
+        assert_eq!(
+            driver.register_device(&first),
+            Err(STATUS_OBJECT_NAME_COLLISION)
+        );
+        assert_eq!(
+            driver
+                .devices()
+                .filter_map(|device| device.context::<u32>().copied())
+                .sum::<u32>(),
+            3
+        );
+
+        driver.remove_device(&first);
+        let devices = driver.devices().collect::<Vec<_>>();
+        assert_eq!(devices.len(), 1);
+        assert!(devices[0].ptr_eq(&second));
+
+        let queue = IoQueue::new();
+        queue.set_device(&second);
+        assert_eq!(queue.get_device().context::<u32>(), Some(&2));
+    }
+
+    #[test]
+    fn contexts_are_initialized_once() {
+        let device = Device::new();
+        assert_eq!(device.context_or_init(|| 1_u32), Ok(&1));
+        assert_eq!(device.context_or_init(|| -> u32 { unreachable!() }), Ok(&1));
+        assert_eq!(
+            device.context_or_init(|| 2_u64),
+            Err(STATUS_OBJECT_NAME_COLLISION)
+        );
+        assert_eq!(device.set_context(3_u32), Err(STATUS_OBJECT_NAME_COLLISION));
+
+        let file_object = FileObject::new();
+        file_object.set_context(4_u32).unwrap();
+        assert_eq!(file_object.context_or_init(|| 5_u32), Ok(&4));
+
+        // SAFETY: The fake ignores the device, so a null handle is fine
+        let queue = unsafe {
+            IoQueue::create_with_context(
+                core::ptr::null_mut(),
+                &mut WDF_IO_QUEUE_CONFIG::default(),
+                6_u32,
+            )
+        }
+        .unwrap();
+        assert_eq!(queue.context::<u32>(), Some(&6));
+        assert_eq!(IoQueue::new().context::<u32>(), None);
+    }
+
+    #[test]
+    fn request_file_object_context() {
+        let device = Device::new();
+        let file_object = FileObject::with_file_name(r"\stream");
+        file_object.set_device(&device);
+        file_object.set_context(7_u32).unwrap();
+        assert_eq!(
+            file_object.set_context(8_u32),
+            Err(STATUS_OBJECT_NAME_COLLISION)
+        );
+        assert_eq!(
+            file_object.file_name().map(|name| name.as_slice().len()),
+            Some(7)
+        );
+        assert!(FileObject::new().file_name().is_none());
+
+        let request = Request::new(RequestParameters::Cleanup);
+        assert!(request.file_object().is_none());
+        request.set_file_object(&file_object);
+        let handle = request.file_object().unwrap();
+        assert!(handle.ptr_eq(&file_object));
+        assert!(handle.get_device().ptr_eq(&device));
+        assert_eq!(handle.context::<u32>(), Some(&7));
+    }
+
+    #[test]
+    fn read_request_fills_output_buffer() {
+        let mut request = Request::new(RequestParameters::Read {
+            length: 4,
+            offset: 0,
+        });
+        request.set_output_buffer_length(4);
+        assert_eq!(
+            request.retrieve_output_buffer(8).err(),
+            Some(STATUS_BUFFER_TOO_SMALL)
+        );
+        request.retrieve_output_buffer(2).unwrap()[..2].copy_from_slice(b"ok");
+        request.clone().complete_with_information(STATUS_SUCCESS, 2);
+        assert_eq!(request.output_buffer(), b"ok\0\0");
+        assert_eq!(request.information(), Some(2));
+
+        let mut request = Request::new(RequestParameters::Write {
+            length: 3,
+            offset: 0,
+        });
+        request.set_input_buffer(b"abc");
+        assert_eq!(&*request.retrieve_input_buffer(3).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn direct_device_control_buffers_are_not_slices() {
+        let mut request = Request::new(RequestParameters::DeviceControl {
+            code: ControlCode::new(0x22, 0x800, TransferMethod::OutDirect, RequiredAccess::Any),
+            input_length: 2,
+            output_length: 4,
+        });
+        request.set_input_buffer(b"in");
+        request.set_output_buffer_length(4);
+        assert_eq!(
+            request.retrieve_input_buffer(2).err(),
+            Some(STATUS_INVALID_DEVICE_REQUEST)
+        );
+        assert_eq!(
+            request.retrieve_output_buffer(4).err(),
+            Some(STATUS_INVALID_DEVICE_REQUEST)
+        );
+
+        let mut input = [0; 2];
+        assert!(
+            request
+                .retrieve_direct_input_buffer(2)
+                .unwrap()
+                .copy_to(0, &mut input)
+                .is_ok()
+        );
+        assert_eq!(&input, b"in");
+        assert!(
+            request
+                .retrieve_direct_output_buffer(4)
+                .unwrap()
+                .copy_from(1, b"out")
+                .is_ok()
+        );
+        assert_eq!(request.output_buffer(), b"\0out");
+    }
+
+    #[test]
+    fn information_accumulates_before_completion() {
+        let request = Request::new(RequestParameters::Read {
+            length: 8,
+            offset: 0,
+        });
+        assert_eq!(request.get_information(), 0);
+        request.set_information(3);
+        request.set_information(request.get_information() + 5);
+        request.clone().complete(STATUS_SUCCESS);
+        assert_eq!(request.information(), Some(8));
+    }
+
+    #[test]
+    fn timer_expiration() {
+        let mut attributes = WDF_OBJECT_ATTRIBUTES::default();
+        let timer = Timer::try_new(&mut WDF_TIMER_CONFIG::default(), &mut attributes).unwrap();
+        assert!(!timer.expire());
+
+        assert!(!timer.start_in(Duration::from_millis(10)));
+        assert_eq!(timer.due_time(), Some(-100_000));
+        assert!(timer.expire());
+        assert!(!timer.is_started());
+
+        let periodic_timer = Timer::try_new(
+            &mut WDF_TIMER_CONFIG {
+                Period: 10,
+                ..WDF_TIMER_CONFIG::default()
+            },
+            &mut attributes,
+        )
+        .unwrap();
+        assert!(!periodic_timer.start_in(Duration::from_millis(10)));
+        assert!(periodic_timer.expire());
+        assert!(periodic_timer.expire());
+        assert_eq!(periodic_timer.expirations(), 2);
+        // SAFETY: Tests run on the host, in user mode, at `PASSIVE_LEVEL`
+        let irql = unsafe { PassiveLevel::new_unchecked() };
+        assert!(periodic_timer.stop_synchronously(irql));
+    }
+
+    #[test]
+    fn device_queue_registry() {
+        let device = Device::new();
+        let default_queue = IoQueue::new();
+        let queue = IoQueue::new();
+        assert!(device.get_default_queue().is_none());
+
+        device.set_default_queue(&default_queue);
+        device.register_queue(1, &queue).unwrap();
+        assert!(device.get_default_queue().unwrap().ptr_eq(&default_queue));
+        assert!(device.queue_by_tag(1).unwrap().ptr_eq(&queue));
+        assert!(device.queue_by_tag(2).is_none());
+        assert_eq!(
+            device.register_queue(1, &default_queue),
+            Err(STATUS_OBJECT_NAME_COLLISION)
+        );
+    }
+
+    #[test]
+    fn unload_callbacks_run_in_reverse_order() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        static ORDER: AtomicUsize = AtomicUsize::new(0);
+        static FIRST: AtomicUsize = AtomicUsize::new(0);
+        static SECOND: AtomicUsize = AtomicUsize::new(0);
+
+        let driver = Driver::new();
+        driver
+            .on_evt_driver_unload(|_| {
+                FIRST.store(ORDER.fetch_add(1, Ordering::SeqCst), Ordering::SeqCst)
+            })
+            .unwrap();
+        driver
+            .on_evt_driver_unload(|_| {
+                SECOND.store(ORDER.fetch_add(1, Ordering::SeqCst), Ordering::SeqCst)
+            })
+            .unwrap();
+        driver.unload();
+
+        assert_eq!(SECOND.load(Ordering::SeqCst), 0);
+        assert_eq!(FIRST.load(Ordering::SeqCst), 1);
+    }
+}
@@ -3,8 +3,102 @@
 
 //! Safe abstractions over WDF APIs
 
+pub use arc::*;
+#[cfg(driver_model__driver_type = "KMDF")]
+pub use child_list::*;
+pub use collection::*;
+pub use device::*;
+pub use device_capabilities::*;
+pub use device_interface::*;
+#[cfg(feature = "alloc")]
+pub use device_property::*;
+#[cfg(driver_model__driver_type = "KMDF")]
+pub use dma::*;
+pub use driver::*;
+pub use file_object::*;
+#[cfg(driver_model__driver_type = "KMDF")]
+pub use interrupt::*;
+pub use io_queue::*;
+#[cfg(all(feature = "io-queue-stats", driver_model__driver_type = "KMDF"))]
+pub use io_queue_stats::*;
+#[cfg(driver_model__driver_type = "KMDF")]
+pub use io_target::*;
+#[cfg(driver_model__driver_type = "KMDF")]
+pub use lookaside_list::*;
+pub use memory::*;
+pub use notification::*;
+pub use pnp_power::*;
+pub use power_policy::*;
+pub use registry::*;
+pub use request::*;
+pub use resource_list::*;
+#[cfg(all(feature = "spb", driver_model__driver_type = "KMDF"))]
+pub use spb::*;
 pub use spinlock::*;
+pub use string::*;
+pub use synchronization::*;
+pub use task::*;
+#[cfg(driver_model__driver_type = "KMDF")]
+pub use timed_request_store::*;
 pub use timer::*;
+#[cfg(feature = "usb")]
+pub use usb::*;
+pub use wait_lock::*;
+#[cfg(driver_model__driver_type = "KMDF")]
+pub use wmi::*;
+pub use work_item::*;
+#[cfg(feature = "alloc")]
+pub use work_pool::*;
 
+mod arc;
+#[cfg(driver_model__driver_type = "KMDF")]
+mod child_list;
+mod collection;
+pub mod compat;
+mod context;
+mod device;
+mod device_capabilities;
+mod device_interface;
+#[cfg(feature = "alloc")]
+mod device_property;
+#[cfg(driver_model__driver_type = "KMDF")]
+mod dma;
+mod driver;
+mod file_object;
+#[cfg(driver_model__driver_type = "KMDF")]
+pub mod hid;
+#[cfg(driver_model__driver_type = "KMDF")]
+mod interrupt;
+mod io_queue;
+#[cfg(all(feature = "io-queue-stats", driver_model__driver_type = "KMDF"))]
+mod io_queue_stats;
+#[cfg(driver_model__driver_type = "KMDF")]
+mod io_target;
+#[cfg(driver_model__driver_type = "KMDF")]
+mod lookaside_list;
+mod memory;
+#[cfg(feature = "wdf-test")]
+pub mod mock;
+mod notification;
+mod pnp_power;
+mod power_policy;
+mod registry;
+mod request;
+mod resource_list;
+#[cfg(all(feature = "spb", driver_model__driver_type = "KMDF"))]
+mod spb;
 mod spinlock;
+mod string;
+mod synchronization;
+mod task;
+#[cfg(driver_model__driver_type = "KMDF")]
+mod timed_request_store;
 mod timer;
+#[cfg(feature = "usb")]
+mod usb;
+mod wait_lock;
+#[cfg(driver_model__driver_type = "KMDF")]
+mod wmi;
+mod work_item;
+#[cfg(feature = "alloc")]
+mod work_pool;
@@ -0,0 +1,170 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use wdk_sys::{
+    _WDF_IO_QUEUE_DISPATCH_TYPE,
+    _WDF_TRI_STATE,
+    NTSTATUS,
+    STATUS_SUCCESS,
+    ULONG,
+    WDF_IO_QUEUE_CONFIG,
+    WDFDEVICE,
+    WDFQUEUE,
+    call_unsafe_wdf_function_binding,
+};
+
+use crate::{
+    nt_success,
+    wdf::{IoQueue, Request},
+};
+
+/// Channel to send notifications from a driver to an application, using the
+/// "inverted call" pattern.
+///
+/// The application sends I/O control requests to the driver ahead of time,
+/// which the driver pends in the channel with
+/// [`NotificationChannel::pend()`] instead of completing them. When the driver
+/// has an event to report, [`NotificationChannel::notify()`] completes the
+/// oldest pended request with the payload of the event, so that the
+/// application receives it asynchronously. The application should keep
+/// several requests pending, so that no event is lost while it processes the
+/// previous one.
+///
+/// The requests are pended in a manual [`IoQueue`] that is not power-managed,
+/// so that they stay pending while the device is in a low power state. The
+/// framework cancels pended requests when the application cancels them or
+/// closes its handle, and when the device is removed.
+pub struct NotificationChannel {
+    queue: IoQueue,
+}
+impl NotificationChannel {
+    /// Try to construct a notification channel for `device`. The manual queue
+    /// of the channel is deleted along with `device`. Must be called at `IRQL`
+    /// = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct the queue
+    /// of the channel. The error variant will contain a [`NTSTATUS`] of the
+    /// failure. Full error documentation is available in the [WDFIoQueue Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfio/nf-wdfio-wdfioqueuecreate#return-value)
+    ///
+    /// # Safety
+    ///
+    /// `device` must be a valid handle to a framework device object.
+    pub unsafe fn try_new(device: WDFDEVICE) -> Result<Self, NTSTATUS> {
+        let queue_config_size: ULONG;
+
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            const QUEUE_CONFIG_SIZE: usize = core::mem::size_of::<WDF_IO_QUEUE_CONFIG>();
+
+            // Manually assert there is not truncation since clippy doesn't work for
+            // compile-time constants
+            const { assert!(QUEUE_CONFIG_SIZE <= ULONG::MAX as usize) }
+
+            queue_config_size = QUEUE_CONFIG_SIZE as ULONG;
+        }
+
+        let mut queue_config = WDF_IO_QUEUE_CONFIG {
+            Size: queue_config_size,
+            DispatchType: _WDF_IO_QUEUE_DISPATCH_TYPE::WdfIoQueueDispatchManual,
+            PowerManaged: _WDF_TRI_STATE::WdfFalse,
+            ..WDF_IO_QUEUE_CONFIG::default()
+        };
+        let mut wdf_queue: WDFQUEUE = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: The caller guarantees that `device` is a valid device handle.
+        // `queue_config` is initialized with its size and outlives the call. The
+        // queue is created without attributes, so it is parented to `device`.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfIoQueueCreate,
+                device,
+                &raw mut queue_config,
+                core::ptr::null_mut(),
+                &raw mut wdf_queue,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(Self {
+            // SAFETY: The queue was just created and is deleted along with `device`,
+            // which the caller guarantees to be valid
+            queue: unsafe { IoQueue::from_raw(wdf_queue) },
+        })
+    }
+
+    /// Try to construct a notification channel for `device`. This is an alias
+    /// for [`NotificationChannel::try_new()`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct the queue
+    /// of the channel. The error variant will contain a [`NTSTATUS`] of the
+    /// failure. Full error documentation is available in the [WDFIoQueue Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfio/nf-wdfio-wdfioqueuecreate#return-value)
+    ///
+    /// # Safety
+    ///
+    /// `device` must be a valid handle to a framework device object.
+    pub unsafe fn create(device: WDFDEVICE) -> Result<Self, NTSTATUS> {
+        // SAFETY: The caller upholds the safety contract of `try_new`
+        unsafe { Self::try_new(device) }
+    }
+
+    /// Get the manual queue the requests are pended in
+    #[must_use]
+    pub const fn queue(&self) -> &IoQueue {
+        &self.queue
+    }
+
+    /// Pend `request` in the channel, until a notification completes it. This
+    /// is typically called from the `EvtIoDeviceControl` handler for the I/O
+    /// control code the application sends to wait for notifications. The
+    /// driver no longer owns the request once it is pended, so it is consumed.
+    /// Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request could not be pended,
+    /// ex. `STATUS_WDF_BUSY` if the queue does not accept requests because the
+    /// device is being removed. The driver still owns the request in that
+    /// case, which is returned along with the error, and must complete it.
+    pub fn pend(&self, request: Request) -> Result<(), (Request, NTSTATUS)> {
+        request.forward_to_io_queue(&self.queue)
+    }
+
+    /// Complete the oldest pended request with `data`, which is copied to the
+    /// output buffer of the request. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the notification could not be
+    /// delivered:
+    /// * `STATUS_NO_MORE_ENTRIES` if no request is pended. The driver can keep
+    ///   the notification until the application pends a new request.
+    /// * The error of retrieving the output buffer of the request, ex.
+    ///   `STATUS_BUFFER_TOO_SMALL` if it cannot hold `data`. The request is
+    ///   completed with this error, so that the application learns about it.
+    pub fn notify(&self, data: &[u8]) -> Result<(), NTSTATUS> {
+        let mut request = self.queue.retrieve_next_request()?;
+
+        if data.is_empty() {
+            request.complete_with_information(STATUS_SUCCESS, 0);
+            return Ok(());
+        }
+
+        match request.retrieve_output_buffer(data.len()) {
+            Ok(buffer) => buffer[..data.len()].copy_from_slice(data),
+            Err(nt_status) => {
+                request.complete(nt_status);
+                return Err(nt_status);
+            }
+        }
+        request.complete_with_information(STATUS_SUCCESS, data.len());
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use wdk_sys::{
+    NTSTATUS,
+    ULONG,
+    WDF_PNPPOWER_EVENT_CALLBACKS,
+    WDFCMRESLIST,
+    WDFDEVICE,
+    WDFDEVICE_INIT,
+    call_unsafe_wdf_function_binding,
+};
+
+use crate::wdf::{Device, ResourceList};
+
+/// Driver-defined callback invoked when the hardware resources of a device
+/// are assigned, before the device enters its working state.
+///
+/// Set with [`PnpPowerEventCallbacks::evt_device_prepare_hardware()`].
+pub trait PrepareHardwareCallback {
+    /// Makes the hardware of `device` accessible, ex. by mapping the memory
+    /// ranges of `resources_translated`, and returns `STATUS_SUCCESS` if it
+    /// succeeded. `resources_raw` holds the same resources as the bus of the
+    /// device sees them. This is invoked from the framework's
+    /// `EvtDevicePrepareHardware` callback, at `IRQL` = `PASSIVE_LEVEL`. Full documentation is available in the [EvtDevicePrepareHardware Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nc-wdfdevice-evt_wdf_device_prepare_hardware)
+    fn prepare_hardware(
+        device: &Device,
+        resources_raw: &ResourceList,
+        resources_translated: &ResourceList,
+    ) -> NTSTATUS;
+}
+
+/// Driver-defined callback invoked when the hardware resources of a device
+/// are removed, after the device left its working state.
+///
+/// Set with [`PnpPowerEventCallbacks::evt_device_release_hardware()`].
+pub trait ReleaseHardwareCallback {
+    /// Undoes what [`PrepareHardwareCallback::prepare_hardware()`] did, ex. by
+    /// unmapping the memory ranges of the device, and returns `STATUS_SUCCESS`
+    /// if it succeeded. This is invoked from the framework's
+    /// `EvtDeviceReleaseHardware` callback, at `IRQL` = `PASSIVE_LEVEL`. Full
+    /// documentation is available in the [EvtDeviceReleaseHardware Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nc-wdfdevice-evt_wdf_device_release_hardware)
+    fn release_hardware(device: &Device, resources_translated: &ResourceList) -> NTSTATUS;
+}
+
+/// Plug and play and power callbacks of a device.
+///
+/// Built with [`PnpPowerEventCallbacks::new()`] and the builder methods, and
+/// applied to a device before it is created with
+/// [`set_pnp_power_event_callbacks()`]. Unset callbacks keep the framework
+/// defaults.
+#[derive(Clone, Copy)]
+pub struct PnpPowerEventCallbacks {
+    callbacks: WDF_PNPPOWER_EVENT_CALLBACKS,
+}
+impl PnpPowerEventCallbacks {
+    /// Construct a set of plug and play and power callbacks without callbacks
+    #[must_use]
+    pub fn new() -> Self {
+        let callbacks_size: ULONG;
+
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            const CALLBACKS_SIZE: usize = core::mem::size_of::<WDF_PNPPOWER_EVENT_CALLBACKS>();
+
+            // Manually assert there is not truncation since clippy doesn't work for
+            // compile-time constants
+            const { assert!(CALLBACKS_SIZE <= ULONG::MAX as usize) }
+
+            callbacks_size = CALLBACKS_SIZE as ULONG;
+        }
+
+        Self {
+            callbacks: WDF_PNPPOWER_EVENT_CALLBACKS {
+                Size: callbacks_size,
+                ..WDF_PNPPOWER_EVENT_CALLBACKS::default()
+            },
+        }
+    }
+
+    /// Set the callback invoked when the hardware resources of the device are
+    /// assigned
+    #[must_use]
+    pub const fn evt_device_prepare_hardware<T: PrepareHardwareCallback>(mut self) -> Self {
+        self.callbacks.EvtDevicePrepareHardware = Some(evt_device_prepare_hardware::<T>);
+        self
+    }
+
+    /// Set the callback invoked when the hardware resources of the device are
+    /// removed
+    #[must_use]
+    pub const fn evt_device_release_hardware<T: ReleaseHardwareCallback>(mut self) -> Self {
+        self.callbacks.EvtDeviceReleaseHardware = Some(evt_device_release_hardware::<T>);
+        self
+    }
+}
+impl Default for PnpPowerEventCallbacks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Apply `callbacks` to the device that is created with `device_init`. Must be
+/// called before the device is created with `WdfDeviceCreate`, at `IRQL` =
+/// `PASSIVE_LEVEL`.
+///
+/// # Safety
+///
+/// `device_init` must point to a valid `WDFDEVICE_INIT` structure, that was
+/// not passed to `WdfDeviceCreate` yet.
+pub unsafe fn set_pnp_power_event_callbacks(
+    device_init: *mut WDFDEVICE_INIT,
+    callbacks: PnpPowerEventCallbacks,
+) {
+    let mut callbacks = callbacks.callbacks;
+    // SAFETY: The caller guarantees that `device_init` is valid, and `callbacks`
+    // is a fully initialized set of callbacks that outlives the call
+    unsafe {
+        call_unsafe_wdf_function_binding!(
+            WdfDeviceInitSetPnpPowerEventCallbacks,
+            device_init,
+            &raw mut callbacks,
+        );
+    }
+}
+
+/// `EvtDevicePrepareHardware` callback that forwards to
+/// [`PrepareHardwareCallback::prepare_hardware()`]
+unsafe extern "C" fn evt_device_prepare_hardware<T: PrepareHardwareCallback>(
+    wdf_device: WDFDEVICE,
+    resources_raw: WDFCMRESLIST,
+    resources_translated: WDFCMRESLIST,
+) -> NTSTATUS {
+    // SAFETY: The framework passes the device whose hardware is prepared, which is
+    // valid until the callback returns
+    let device = unsafe { Device::from_raw(wdf_device) };
+    // SAFETY: The framework passes the resource lists of the device, which are
+    // valid until the callback returns
+    let resources_raw = unsafe { ResourceList::from_raw(resources_raw) };
+    // SAFETY: The framework passes the resource lists of the device, which are
+    // valid until the callback returns
+    let resources_translated = unsafe { ResourceList::from_raw(resources_translated) };
+    T::prepare_hardware(&device, &resources_raw, &resources_translated)
+}
+
+/// `EvtDeviceReleaseHardware` callback that forwards to
+/// [`ReleaseHardwareCallback::release_hardware()`]
+unsafe extern "C" fn evt_device_release_hardware<T: ReleaseHardwareCallback>(
+    wdf_device: WDFDEVICE,
+    resources_translated: WDFCMRESLIST,
+) -> NTSTATUS {
+    // SAFETY: The framework passes the device whose hardware is released, which is
+    // valid until the callback returns
+    let device = unsafe { Device::from_raw(wdf_device) };
+    // SAFETY: The framework passes the resource list of the device, which is valid
+    // until the callback returns
+    let resources_translated = unsafe { ResourceList::from_raw(resources_translated) };
+    T::release_hardware(&device, &resources_translated)
+}
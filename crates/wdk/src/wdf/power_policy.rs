@@ -0,0 +1,379 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use wdk_sys::{
+    _DEVICE_POWER_STATE,
+    _WDF_POWER_POLICY_IDLE_TIMEOUT_TYPE,
+    _WDF_POWER_POLICY_IDLE_USER_CONTROL,
+    _WDF_POWER_POLICY_S0_IDLE_CAPABILITIES,
+    _WDF_POWER_POLICY_SX_WAKE_USER_CONTROL,
+    _WDF_TRI_STATE,
+    NTSTATUS,
+    ULONG,
+    WDF_DEVICE_POWER_POLICY_IDLE_SETTINGS,
+    WDF_DEVICE_POWER_POLICY_WAKE_SETTINGS,
+    WDF_POWER_POLICY_S0_IDLE_CAPABILITIES,
+    WDF_TRI_STATE,
+    WDFDEVICE,
+    call_unsafe_wdf_function_binding,
+};
+
+use crate::{
+    nt_success,
+    wdf::{Device, DevicePowerState},
+};
+
+/// Ability of a device to wake itself while it is idle in a low-power state and
+/// the system is in its working (S0) state
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum S0IdleCapabilities {
+    /// The device cannot wake itself while it is idle
+    CannotWakeFromS0,
+    /// The device can wake itself while it is idle
+    CanWakeFromS0,
+    /// The device is a USB device that supports selective suspend
+    UsbSelectiveSuspend,
+}
+
+impl From<S0IdleCapabilities> for WDF_POWER_POLICY_S0_IDLE_CAPABILITIES {
+    fn from(capabilities: S0IdleCapabilities) -> Self {
+        match capabilities {
+            S0IdleCapabilities::CannotWakeFromS0 => {
+                _WDF_POWER_POLICY_S0_IDLE_CAPABILITIES::IdleCannotWakeFromS0
+            }
+            S0IdleCapabilities::CanWakeFromS0 => {
+                _WDF_POWER_POLICY_S0_IDLE_CAPABILITIES::IdleCanWakeFromS0
+            }
+            S0IdleCapabilities::UsbSelectiveSuspend => {
+                _WDF_POWER_POLICY_S0_IDLE_CAPABILITIES::IdleUsbSelectiveSuspend
+            }
+        }
+    }
+}
+
+/// Settings for powering down a device when it is idle while the system is in
+/// its working (S0) state.
+///
+/// The settings are built with [`S0IdleSettings::new()`] and the builder
+/// methods, and applied to a device with [`Device::assign_s0_idle_settings()`].
+/// Unset settings keep the framework defaults of
+/// `WDF_DEVICE_POWER_POLICY_IDLE_SETTINGS_INIT`.
+#[derive(Clone, Copy)]
+pub struct S0IdleSettings {
+    settings: WDF_DEVICE_POWER_POLICY_IDLE_SETTINGS,
+}
+impl S0IdleSettings {
+    /// Construct idle settings for a device with `capabilities`
+    #[must_use]
+    pub fn new(capabilities: S0IdleCapabilities) -> Self {
+        let settings_size: ULONG;
+
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            const SETTINGS_SIZE: usize =
+                core::mem::size_of::<WDF_DEVICE_POWER_POLICY_IDLE_SETTINGS>();
+
+            // Manually assert there is not truncation since clippy doesn't work for
+            // compile-time constants
+            const { assert!(SETTINGS_SIZE <= ULONG::MAX as usize) }
+
+            settings_size = SETTINGS_SIZE as ULONG;
+        }
+
+        Self {
+            settings: WDF_DEVICE_POWER_POLICY_IDLE_SETTINGS {
+                Size: settings_size,
+                IdleCaps: capabilities.into(),
+                DxState: _DEVICE_POWER_STATE::PowerDeviceMaximum,
+                UserControlOfIdleSettings:
+                    _WDF_POWER_POLICY_IDLE_USER_CONTROL::IdleAllowUserControl,
+                Enabled: _WDF_TRI_STATE::WdfUseDefault,
+                PowerUpIdleDeviceOnSystemWake: _WDF_TRI_STATE::WdfUseDefault,
+                IdleTimeoutType: _WDF_POWER_POLICY_IDLE_TIMEOUT_TYPE::DriverManagedIdleTimeout,
+                ExcludeD3Cold: _WDF_TRI_STATE::WdfUseDefault,
+                ..WDF_DEVICE_POWER_POLICY_IDLE_SETTINGS::default()
+            },
+        }
+    }
+
+    /// Set the time, in milliseconds, the device must be idle before it is
+    /// powered down. The framework default is 5 seconds.
+    #[must_use]
+    pub const fn idle_timeout(mut self, idle_timeout_ms: ULONG) -> Self {
+        self.settings.IdleTimeout = idle_timeout_ms;
+        self
+    }
+
+    /// Enable or disable powering down the device when it is idle
+    #[must_use]
+    pub const fn enabled(mut self, enabled: bool) -> Self {
+        self.settings.Enabled = to_tri_state(enabled);
+        self
+    }
+
+    /// Allow or prevent users to change the idle settings in the device's
+    /// property sheet
+    #[must_use]
+    pub const fn allow_user_control(mut self, allow_user_control: bool) -> Self {
+        self.settings.UserControlOfIdleSettings = if allow_user_control {
+            _WDF_POWER_POLICY_IDLE_USER_CONTROL::IdleAllowUserControl
+        } else {
+            _WDF_POWER_POLICY_IDLE_USER_CONTROL::IdleDoNotAllowUserControl
+        };
+        self
+    }
+
+    /// Let the power manager choose the idle timeout, instead of the driver
+    #[must_use]
+    pub const fn system_managed_idle_timeout(mut self) -> Self {
+        self.settings.IdleTimeoutType =
+            _WDF_POWER_POLICY_IDLE_TIMEOUT_TYPE::SystemManagedIdleTimeout;
+        self
+    }
+
+    /// Set the device power state the device enters when it is idle. The
+    /// framework default is the lowest state the device can wake itself from,
+    /// or `D3` if it cannot wake itself.
+    #[must_use]
+    pub fn dx_state(mut self, dx_state: DevicePowerState) -> Self {
+        self.settings.DxState = dx_state.into();
+        self
+    }
+
+    /// Power up the device when the system returns to its working state, even
+    /// if the device was idle when the system went to sleep
+    #[must_use]
+    pub const fn power_up_idle_device_on_system_wake(mut self, power_up: bool) -> Self {
+        self.settings.PowerUpIdleDeviceOnSystemWake = to_tri_state(power_up);
+        self
+    }
+}
+
+/// Settings for arming a device to wake the system from a low-power (Sx)
+/// state.
+///
+/// The settings are built with [`SxWakeSettings::new()`] and the builder
+/// methods, and applied to a device with [`Device::assign_sx_wake_settings()`].
+/// Unset settings keep the framework defaults of
+/// `WDF_DEVICE_POWER_POLICY_WAKE_SETTINGS_INIT`.
+#[derive(Clone, Copy)]
+pub struct SxWakeSettings {
+    settings: WDF_DEVICE_POWER_POLICY_WAKE_SETTINGS,
+}
+impl SxWakeSettings {
+    /// Construct wake settings with the framework defaults
+    #[must_use]
+    pub fn new() -> Self {
+        let settings_size: ULONG;
+
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            const SETTINGS_SIZE: usize =
+                core::mem::size_of::<WDF_DEVICE_POWER_POLICY_WAKE_SETTINGS>();
+
+            // Manually assert there is not truncation since clippy doesn't work for
+            // compile-time constants
+            const { assert!(SETTINGS_SIZE <= ULONG::MAX as usize) }
+
+            settings_size = SETTINGS_SIZE as ULONG;
+        }
+
+        Self {
+            settings: WDF_DEVICE_POWER_POLICY_WAKE_SETTINGS {
+                Size: settings_size,
+                DxState: _DEVICE_POWER_STATE::PowerDeviceMaximum,
+                UserControlOfWakeSettings:
+                    _WDF_POWER_POLICY_SX_WAKE_USER_CONTROL::WakeAllowUserControl,
+                Enabled: _WDF_TRI_STATE::WdfUseDefault,
+                ..WDF_DEVICE_POWER_POLICY_WAKE_SETTINGS::default()
+            },
+        }
+    }
+
+    /// Enable or disable arming the device to wake the system
+    #[must_use]
+    pub const fn enabled(mut self, enabled: bool) -> Self {
+        self.settings.Enabled = to_tri_state(enabled);
+        self
+    }
+
+    /// Allow or prevent users to change the wake settings in the device's
+    /// property sheet
+    #[must_use]
+    pub const fn allow_user_control(mut self, allow_user_control: bool) -> Self {
+        self.settings.UserControlOfWakeSettings = if allow_user_control {
+            _WDF_POWER_POLICY_SX_WAKE_USER_CONTROL::WakeAllowUserControl
+        } else {
+            _WDF_POWER_POLICY_SX_WAKE_USER_CONTROL::WakeDoNotAllowUserControl
+        };
+        self
+    }
+
+    /// Set the device power state the device enters when the system goes to
+    /// sleep while it is armed for wake. The framework default is the lowest
+    /// state the device can wake the system from.
+    #[must_use]
+    pub fn dx_state(mut self, dx_state: DevicePowerState) -> Self {
+        self.settings.DxState = dx_state.into();
+        self
+    }
+
+    /// Arm the device to wake the system if any of its child devices are armed
+    /// to wake the system
+    #[must_use]
+    pub fn arm_for_wake_if_children_are_armed_for_wake(mut self, arm: bool) -> Self {
+        self.settings.ArmForWakeIfChildrenAreArmedForWake = u8::from(arm);
+        self
+    }
+}
+
+impl Default for SxWakeSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Apply `settings` to `device`, which must be the power policy owner of its
+/// device stack
+///
+/// # Errors
+///
+/// This function will return an error if WDF fails to apply the settings. The
+/// error variant will contain a [`NTSTATUS`] of the failure. Full error
+/// documentation is available in the [WDFDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdeviceassigns0idlesettings#return-value)
+///
+/// # Safety
+///
+/// `device` must be a valid handle to a framework device object.
+pub unsafe fn assign_s0_idle_settings(
+    device: WDFDEVICE,
+    settings: S0IdleSettings,
+) -> Result<(), NTSTATUS> {
+    let mut settings = settings.settings;
+    let nt_status;
+    // SAFETY: The caller guarantees that `device` is a valid device handle, and
+    // `settings` is a fully initialized settings structure that outlives the call.
+    unsafe {
+        nt_status =
+            call_unsafe_wdf_function_binding!(WdfDeviceAssignS0IdleSettings, device, &mut settings);
+    }
+    nt_success(nt_status).then_some(()).ok_or(nt_status)
+}
+
+/// Apply `settings` to `device`, which must be the power policy owner of its
+/// device stack
+///
+/// # Errors
+///
+/// This function will return an error if WDF fails to apply the settings. The
+/// error variant will contain a [`NTSTATUS`] of the failure. Full error
+/// documentation is available in the [WDFDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdeviceassignsxwakesettings#return-value)
+///
+/// # Safety
+///
+/// `device` must be a valid handle to a framework device object.
+pub unsafe fn assign_sx_wake_settings(
+    device: WDFDEVICE,
+    settings: SxWakeSettings,
+) -> Result<(), NTSTATUS> {
+    let mut settings = settings.settings;
+    let nt_status;
+    // SAFETY: The caller guarantees that `device` is a valid device handle, and
+    // `settings` is a fully initialized settings structure that outlives the call.
+    unsafe {
+        nt_status =
+            call_unsafe_wdf_function_binding!(WdfDeviceAssignSxWakeSettings, device, &mut settings);
+    }
+    nt_success(nt_status).then_some(()).ok_or(nt_status)
+}
+
+impl Device {
+    /// Apply `settings` to the device, which must be the power policy owner of
+    /// its device stack. Usually called in `EvtDriverDeviceAdd`, at `IRQL` =
+    /// `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to apply the settings.
+    /// The error variant will contain a [`NTSTATUS`] of the failure. Full
+    /// error documentation is available in the [WDFDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdeviceassigns0idlesettings#return-value)
+    pub fn assign_s0_idle_settings(&self, settings: S0IdleSettings) -> Result<(), NTSTATUS> {
+        // SAFETY: The device is valid for as long as `self` is, as guaranteed to
+        // `Device::from_raw`
+        unsafe { assign_s0_idle_settings(self.as_raw(), settings) }
+    }
+
+    /// Apply `settings` to the device, which must be the power policy owner of
+    /// its device stack. Usually called in `EvtDriverDeviceAdd`, at `IRQL` =
+    /// `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to apply the settings.
+    /// The error variant will contain a [`NTSTATUS`] of the failure. Full
+    /// error documentation is available in the [WDFDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdeviceassignsxwakesettings#return-value)
+    pub fn assign_sx_wake_settings(&self, settings: SxWakeSettings) -> Result<(), NTSTATUS> {
+        // SAFETY: The device is valid for as long as `self` is, as guaranteed to
+        // `Device::from_raw`
+        unsafe { assign_sx_wake_settings(self.as_raw(), settings) }
+    }
+
+    /// Keep the device in its working (`D0`) state, ex. while it has I/O in
+    /// progress that does not go through a power-managed queue. The device
+    /// can power down when idle again once the returned [`StopIdleGuard`] is
+    /// dropped.
+    ///
+    /// If `wait_for_d0` is set, this waits until the device is in `D0`, and
+    /// must be called at `IRQL` = `PASSIVE_LEVEL`. Otherwise the device is
+    /// powered up asynchronously, and this can be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the device could not be kept in
+    /// `D0`, ex. because it is being removed. The error variant will contain a
+    /// [`NTSTATUS`] of the failure. Full error documentation is available in the [WdfDeviceStopIdle Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/wdf/wdfdevicestopidle#return-value)
+    pub fn stop_idle(&self, wait_for_d0: bool) -> Result<StopIdleGuard<'_>, NTSTATUS> {
+        let nt_status;
+        // SAFETY: The device is valid for as long as `self` is, as guaranteed to
+        // `Device::from_raw`
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfDeviceStopIdleNoTrack,
+                self.as_raw(),
+                u8::from(wait_for_d0),
+            );
+        }
+        nt_success(nt_status)
+            .then_some(StopIdleGuard { device: self })
+            .ok_or(nt_status)
+    }
+}
+
+/// Guard that keeps a device in its working (`D0`) state, returned by
+/// [`Device::stop_idle()`]. The device can power down when idle again once
+/// the guard is dropped, which must happen at `IRQL` <= `DISPATCH_LEVEL`.
+#[must_use = "the device can power down as soon as the guard is dropped"]
+pub struct StopIdleGuard<'a> {
+    device: &'a Device,
+}
+
+impl Drop for StopIdleGuard<'_> {
+    fn drop(&mut self) {
+        // SAFETY: The device outlives the guard, and `WdfDeviceStopIdle` succeeded
+        // for it, so its power reference is released exactly once here
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfDeviceResumeIdleNoTrack, self.device.as_raw());
+        }
+    }
+}
+
+pub(super) const fn to_tri_state(value: bool) -> WDF_TRI_STATE {
+    if value {
+        _WDF_TRI_STATE::WdfTrue
+    } else {
+        _WDF_TRI_STATE::WdfFalse
+    }
+}
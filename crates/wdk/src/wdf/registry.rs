@@ -0,0 +1,536 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+#[cfg(all(feature = "alloc", driver_model__driver_type = "KMDF"))]
+use wdk_sys::{
+    _KEY_INFORMATION_CLASS,
+    KEY_BASIC_INFORMATION,
+    STATUS_BUFFER_TOO_SMALL,
+    STATUS_NO_MORE_ENTRIES,
+    ntddk::ZwEnumerateKey,
+};
+use wdk_sys::{
+    ACCESS_MASK,
+    NTSTATUS,
+    PLUGPLAY_REGKEY_DEVICE,
+    REG_EXPAND_SZ,
+    REG_SZ,
+    STATUS_OBJECT_TYPE_MISMATCH,
+    ULONG,
+    UNICODE_STRING,
+    WDFKEY,
+    call_unsafe_wdf_function_binding,
+};
+#[cfg(feature = "alloc")]
+use wdk_sys::{REG_MULTI_SZ, STATUS_BUFFER_OVERFLOW};
+
+use crate::{
+    PassiveLevel,
+    nt_success,
+    string::UnicodeStr,
+    wdf::{Device, Driver},
+};
+#[cfg(feature = "alloc")]
+use crate::{alloc_failure::alloc_failed, string::UnicodeString};
+
+/// WDF Registry Key.
+///
+/// An open registry key, ex. the `Parameters` key of the driver opened with
+/// [`Driver::open_parameters_key()`], or the hardware key of a device opened
+/// with [`Device::open_parameters_key()`]. Values are read and written with
+/// the typed methods of the key, ex. [`RegistryKey::read_u32()`]. The key is
+/// closed when the [`RegistryKey`] is dropped.
+///
+/// All methods must be called at `IRQL` = `PASSIVE_LEVEL`. A key is opened
+/// with a [`PassiveLevel`], and is neither `Send` nor `Sync`, so it cannot be
+/// reached from code running at a higher `IRQL`.
+pub struct RegistryKey {
+    wdf_key: WDFKEY,
+    _irql: PhantomData<PassiveLevel>,
+}
+impl RegistryKey {
+    /// Take ownership of a handle to a framework registry key object, which is
+    /// closed when the returned [`RegistryKey`] is dropped
+    ///
+    /// # Safety
+    ///
+    /// `key` must be a valid handle to an open framework registry key object,
+    /// that is not closed by anything else. The caller must run at `IRQL` =
+    /// `PASSIVE_LEVEL`.
+    #[must_use]
+    pub const unsafe fn from_raw(key: WDFKEY) -> Self {
+        Self {
+            wdf_key: key,
+            _irql: PhantomData,
+        }
+    }
+
+    /// Get the raw handle of the framework registry key object
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFKEY {
+        self.wdf_key
+    }
+
+    /// Open the subkey `name` of the key with `desired_access`, ex. `KEY_READ`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the subkey could not be opened,
+    /// ex. `STATUS_OBJECT_NAME_NOT_FOUND` if it does not exist. Full error
+    /// documentation is available in the [WdfRegistryOpenKey Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfregistry/nf-wdfregistry-wdfregistryopenkey#return-value)
+    pub fn open_subkey(
+        &self,
+        name: UnicodeStr<'_>,
+        desired_access: ACCESS_MASK,
+    ) -> Result<Self, NTSTATUS> {
+        let name = name.as_raw();
+        let mut wdf_key: WDFKEY = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `wdf_key` is a private member of `RegistryKey`, which the caller of
+        // `from_raw` guaranteed to be open. `name` outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfRegistryOpenKey,
+                self.wdf_key,
+                &raw const name,
+                desired_access,
+                core::ptr::null_mut(),
+                &raw mut wdf_key,
+            );
+        }
+        nt_success(nt_status)
+            .then_some(Self {
+                wdf_key,
+                _irql: PhantomData,
+            })
+            .ok_or(nt_status)
+    }
+
+    /// Read the `REG_DWORD` value `value_name`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the value could not be read:
+    /// * `STATUS_OBJECT_NAME_NOT_FOUND` if the value does not exist
+    /// * `STATUS_OBJECT_TYPE_MISMATCH` if the value is not a `REG_DWORD`
+    ///
+    /// Full error documentation is available in the [WdfRegistryQueryULong Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfregistry/nf-wdfregistry-wdfregistryqueryulong#return-value)
+    pub fn read_u32(&self, value_name: UnicodeStr<'_>) -> Result<ULONG, NTSTATUS> {
+        let value_name = value_name.as_raw();
+        let mut value: ULONG = 0;
+
+        let nt_status;
+        // SAFETY: `wdf_key` is a private member of `RegistryKey`, which the caller of
+        // `from_raw` guaranteed to be open. `value_name` outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfRegistryQueryULong,
+                self.wdf_key,
+                &raw const value_name,
+                &raw mut value,
+            );
+        }
+        nt_success(nt_status).then_some(value).ok_or(nt_status)
+    }
+
+    /// Write `value` to the value `value_name`, as a `REG_DWORD`. The value is
+    /// created if it does not exist.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the value could not be written,
+    /// ex. `STATUS_ACCESS_DENIED` if the key was not opened with
+    /// `KEY_SET_VALUE` access. Full error documentation is available in the [WdfRegistryAssignULong Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfregistry/nf-wdfregistry-wdfregistryassignulong#return-value)
+    pub fn write_u32(&self, value_name: UnicodeStr<'_>, value: ULONG) -> Result<(), NTSTATUS> {
+        let value_name = value_name.as_raw();
+
+        let nt_status;
+        // SAFETY: `wdf_key` is a private member of `RegistryKey`, which the caller of
+        // `from_raw` guaranteed to be open. `value_name` outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfRegistryAssignULong,
+                self.wdf_key,
+                &raw const value_name,
+                value,
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Write `value` to the value `value_name`, as a `REG_SZ`. The value is
+    /// created if it does not exist.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the value could not be written,
+    /// ex. `STATUS_ACCESS_DENIED` if the key was not opened with
+    /// `KEY_SET_VALUE` access. Full error documentation is available in the [WdfRegistryAssignUnicodeString Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfregistry/nf-wdfregistry-wdfregistryassignunicodestring#return-value)
+    pub fn write_string(
+        &self,
+        value_name: UnicodeStr<'_>,
+        value: UnicodeStr<'_>,
+    ) -> Result<(), NTSTATUS> {
+        let value_name = value_name.as_raw();
+        let value = value.as_raw();
+
+        let nt_status;
+        // SAFETY: `wdf_key` is a private member of `RegistryKey`, which the caller of
+        // `from_raw` guaranteed to be open. `value_name` and `value` outlive the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfRegistryAssignUnicodeString,
+                self.wdf_key,
+                &raw const value_name,
+                &raw const value,
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Read the `REG_SZ` or `REG_EXPAND_SZ` value `value_name`. Environment
+    /// variables of `REG_EXPAND_SZ` values are not expanded.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the value could not be read:
+    /// * `STATUS_OBJECT_NAME_NOT_FOUND` if the value does not exist
+    /// * `STATUS_OBJECT_TYPE_MISMATCH` if the value is not a string
+    /// * `STATUS_INSUFFICIENT_RESOURCES` if the string could not be allocated
+    #[cfg(feature = "alloc")]
+    pub fn read_string(&self, value_name: UnicodeStr<'_>) -> Result<UnicodeString, NTSTATUS> {
+        let (value, value_type) = self.query_value(value_name)?;
+        if value_type != REG_SZ && value_type != REG_EXPAND_SZ {
+            return Err(STATUS_OBJECT_TYPE_MISMATCH);
+        }
+        to_unicode_string(trim_string(&value))
+    }
+
+    /// Read the `REG_SZ` or `REG_EXPAND_SZ` value `value_name` into `buffer`,
+    /// without allocating, and get the string it holds. Environment variables
+    /// of `REG_EXPAND_SZ` values are not expanded.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the value could not be read:
+    /// * `STATUS_OBJECT_NAME_NOT_FOUND` if the value does not exist
+    /// * `STATUS_OBJECT_TYPE_MISMATCH` if the value is not a string
+    /// * `STATUS_BUFFER_OVERFLOW` if the value does not fit in `buffer`
+    pub fn read_string_into<'a>(
+        &self,
+        value_name: UnicodeStr<'_>,
+        buffer: &'a mut [u16],
+    ) -> Result<UnicodeStr<'a>, NTSTATUS> {
+        let value_name = value_name.as_raw();
+        let (value_length, value_type) = self
+            .query_value_into(&value_name, buffer)
+            .map_err(|(nt_status, _)| nt_status)?;
+        if value_type != REG_SZ && value_type != REG_EXPAND_SZ {
+            return Err(STATUS_OBJECT_TYPE_MISMATCH);
+        }
+        let buffer: &'a [u16] = buffer;
+        let words = value_words(value_length).min(buffer.len());
+        UnicodeStr::from_slice(trim_string(&buffer[..words]))
+    }
+
+    /// Read the `REG_MULTI_SZ` value `value_name`, as the list of its strings
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the value could not be read:
+    /// * `STATUS_OBJECT_NAME_NOT_FOUND` if the value does not exist
+    /// * `STATUS_OBJECT_TYPE_MISMATCH` if the value is not a `REG_MULTI_SZ`
+    /// * `STATUS_INSUFFICIENT_RESOURCES` if the strings could not be allocated
+    #[cfg(feature = "alloc")]
+    pub fn read_multi_string(
+        &self,
+        value_name: UnicodeStr<'_>,
+    ) -> Result<Vec<UnicodeString>, NTSTATUS> {
+        let (value, value_type) = self.query_value(value_name)?;
+        if value_type != REG_MULTI_SZ {
+            return Err(STATUS_OBJECT_TYPE_MISMATCH);
+        }
+
+        let mut strings = Vec::new();
+        for string in split_multi_string(&value) {
+            strings
+                .try_reserve(1)
+                .map_err(|_| alloc_failed(core::mem::size_of::<UnicodeString>()))?;
+            strings.push(to_unicode_string(string)?);
+        }
+        Ok(strings)
+    }
+
+    /// Get the names of the subkeys of the key, ex. to open each of them with
+    /// [`RegistryKey::open_subkey()`]. The key must be opened with
+    /// `KEY_ENUMERATE_SUB_KEYS` access, which `KEY_READ` includes.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the subkeys could not be
+    /// enumerated, ex. `STATUS_ACCESS_DENIED` if the key was not opened with
+    /// `KEY_ENUMERATE_SUB_KEYS` access, or `STATUS_INSUFFICIENT_RESOURCES` if
+    /// the names could not be allocated.
+    #[cfg(all(feature = "alloc", driver_model__driver_type = "KMDF"))]
+    pub fn subkey_names(&self) -> Result<Vec<UnicodeString>, NTSTATUS> {
+        let handle;
+        // SAFETY: `wdf_key` is a private member of `RegistryKey`, which the caller of
+        // `from_raw` guaranteed to be open
+        unsafe {
+            handle = call_unsafe_wdf_function_binding!(WdfRegistryWdmGetHandle, self.wdf_key);
+        }
+
+        let mut names = Vec::new();
+        // `KEY_BASIC_INFORMATION` is 8-byte aligned
+        let mut buffer: Vec<u64> = Vec::new();
+        let mut index = 0;
+        loop {
+            let buffer_length =
+                ULONG::try_from(core::mem::size_of_val(buffer.as_slice())).unwrap_or(ULONG::MAX);
+            let mut result_length: ULONG = 0;
+            // SAFETY: `handle` is the handle of the open key, and `buffer` is writable
+            // for `buffer_length` bytes
+            let nt_status = unsafe {
+                ZwEnumerateKey(
+                    handle,
+                    index,
+                    _KEY_INFORMATION_CLASS::KeyBasicInformation,
+                    buffer.as_mut_ptr().cast(),
+                    buffer_length,
+                    &raw mut result_length,
+                )
+            };
+            match nt_status {
+                STATUS_NO_MORE_ENTRIES => return Ok(names),
+                STATUS_BUFFER_OVERFLOW | STATUS_BUFFER_TOO_SMALL => {
+                    let words = (result_length as usize).div_ceil(core::mem::size_of::<u64>());
+                    buffer
+                        .try_reserve_exact(words.saturating_sub(buffer.len()))
+                        .map_err(|_| alloc_failed(result_length as usize))?;
+                    buffer.resize(words, 0);
+                    continue;
+                }
+                nt_status if !nt_success(nt_status) => return Err(nt_status),
+                _ => {}
+            }
+
+            let info = buffer.as_ptr().cast::<KEY_BASIC_INFORMATION>();
+            // SAFETY: On success, `ZwEnumerateKey` filled `buffer` with a
+            // `KEY_BASIC_INFORMATION`
+            let name_length = unsafe { (*info).NameLength } as usize / core::mem::size_of::<u16>();
+            // SAFETY: `info` points to the `KEY_BASIC_INFORMATION` in `buffer`
+            let name = unsafe { &raw const (*info).Name }.cast::<u16>();
+            // SAFETY: The name of `NameLength` bytes follows the fixed part of the
+            // information, within the `result_length` bytes written to `buffer`
+            let name = unsafe { core::slice::from_raw_parts(name, name_length) };
+
+            names
+                .try_reserve(1)
+                .map_err(|_| alloc_failed(core::mem::size_of::<UnicodeString>()))?;
+            names.push(to_unicode_string(name)?);
+            index += 1;
+        }
+    }
+
+    /// Read the raw data of the value `value_name`, along with its `REG_*` type
+    #[cfg(feature = "alloc")]
+    fn query_value(&self, value_name: UnicodeStr<'_>) -> Result<(Vec<u16>, ULONG), NTSTATUS> {
+        let value_name = value_name.as_raw();
+        let mut buffer: Vec<u16> = Vec::new();
+        loop {
+            match self.query_value_into(&value_name, &mut buffer) {
+                Ok((value_length, value_type)) => {
+                    buffer.truncate(value_words(value_length));
+                    return Ok((buffer, value_type));
+                }
+                Err((STATUS_BUFFER_OVERFLOW, value_length)) => {
+                    let words = value_words(value_length);
+                    buffer
+                        .try_reserve_exact(words.saturating_sub(buffer.len()))
+                        .map_err(|_| alloc_failed(value_length as usize))?;
+                    buffer.resize(words, 0);
+                }
+                Err((nt_status, _)) => return Err(nt_status),
+            }
+        }
+    }
+
+    /// Read the raw data of the value `value_name` into `buffer`, and get the
+    /// length of the value in bytes along with its `REG_*` type. The error
+    /// variant holds the length of the value too, which is the length `buffer`
+    /// needs when the error is `STATUS_BUFFER_OVERFLOW`.
+    fn query_value_into(
+        &self,
+        value_name: &UNICODE_STRING,
+        buffer: &mut [u16],
+    ) -> Result<(ULONG, ULONG), (NTSTATUS, ULONG)> {
+        let buffer_length = ULONG::try_from(core::mem::size_of_val(buffer)).unwrap_or(ULONG::MAX);
+        let mut value_length: ULONG = 0;
+        let mut value_type: ULONG = 0;
+
+        let nt_status;
+        // SAFETY: `wdf_key` is a private member of `RegistryKey`, which the caller of
+        // `from_raw` guaranteed to be open. `value_name` outlives the call, and
+        // `buffer` is writable for `buffer_length` bytes.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfRegistryQueryValue,
+                self.wdf_key,
+                value_name,
+                buffer_length,
+                buffer.as_mut_ptr().cast(),
+                &raw mut value_length,
+                &raw mut value_type,
+            );
+        }
+        nt_success(nt_status)
+            .then_some((value_length, value_type))
+            .ok_or((nt_status, value_length))
+    }
+}
+
+impl Drop for RegistryKey {
+    fn drop(&mut self) {
+        // SAFETY: `wdf_key` is a private member of `RegistryKey`, which the caller of
+        // `from_raw` guaranteed to be open, and is closed exactly once here
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfRegistryClose, self.wdf_key);
+        }
+    }
+}
+
+impl Driver {
+    /// Open the `Parameters` key of the driver's service key with
+    /// `desired_access`, ex. `KEY_READ`, to read the configuration of the
+    /// driver.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the key could not be opened. The
+    /// error variant will contain a [`NTSTATUS`] of the failure. Full error
+    /// documentation is available in the [WdfDriverOpenParametersRegistryKey Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdriver/nf-wdfdriver-wdfdriveropenparametersregistrykey#return-value)
+    pub fn open_parameters_key(
+        &self,
+        desired_access: ACCESS_MASK,
+        _irql: PassiveLevel,
+    ) -> Result<RegistryKey, NTSTATUS> {
+        let mut wdf_key: WDFKEY = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: The driver object is valid until the driver is unloaded. The key is
+        // created without attributes, so it is parented to the driver object.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfDriverOpenParametersRegistryKey,
+                self.as_raw(),
+                desired_access,
+                core::ptr::null_mut(),
+                &raw mut wdf_key,
+            );
+        }
+        nt_success(nt_status)
+            .then_some(RegistryKey {
+                wdf_key,
+                _irql: PhantomData,
+            })
+            .ok_or(nt_status)
+    }
+}
+
+impl Device {
+    /// Open the hardware key of the device, i.e. its `Device Parameters` key,
+    /// with `desired_access`, ex. `KEY_READ`, to read the configuration the INF
+    /// file of the driver stored for the device.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the key could not be opened. The
+    /// error variant will contain a [`NTSTATUS`] of the failure. Full error
+    /// documentation is available in the [WdfDeviceOpenRegistryKey Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdeviceopenregistrykey#return-value)
+    pub fn open_parameters_key(
+        &self,
+        desired_access: ACCESS_MASK,
+        _irql: PassiveLevel,
+    ) -> Result<RegistryKey, NTSTATUS> {
+        let mut wdf_key: WDFKEY = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: The device is valid for as long as `self` is, as guaranteed to
+        // `Device::from_raw`. The key is created without attributes, so it is
+        // parented to the device object.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfDeviceOpenRegistryKey,
+                self.as_raw(),
+                PLUGPLAY_REGKEY_DEVICE,
+                desired_access,
+                core::ptr::null_mut(),
+                &raw mut wdf_key,
+            );
+        }
+        nt_success(nt_status)
+            .then_some(RegistryKey {
+                wdf_key,
+                _irql: PhantomData,
+            })
+            .ok_or(nt_status)
+    }
+}
+
+/// Copy a UTF-16 string read from the registry into a [`UnicodeString`]
+#[cfg(feature = "alloc")]
+pub(super) fn to_unicode_string(string: &[u16]) -> Result<UnicodeString, NTSTATUS> {
+    UnicodeString::try_from_unicode_str(UnicodeStr::from_slice(string)?)
+}
+
+/// Get the number of UTF-16 code units of a value of `value_length` bytes
+const fn value_words(value_length: ULONG) -> usize {
+    (value_length as usize).div_ceil(core::mem::size_of::<u16>())
+}
+
+/// Get the characters of a `REG_SZ` value before its NUL terminator. Values
+/// written without a terminator are returned whole.
+pub(super) fn trim_string(value: &[u16]) -> &[u16] {
+    value
+        .iter()
+        .position(|&c| c == 0)
+        .map_or(value, |end| &value[..end])
+}
+
+/// Split a `REG_MULTI_SZ` value into its strings. The strings are NUL
+/// terminated, and the list ends at the first empty string.
+#[cfg(feature = "alloc")]
+pub(super) fn split_multi_string(value: &[u16]) -> impl Iterator<Item = &[u16]> {
+    value
+        .split(|&c| c == 0)
+        .take_while(|string| !string.is_empty())
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+
+    fn utf16(string: &str) -> Vec<u16> {
+        string.encode_utf16().collect()
+    }
+
+    #[test]
+    fn trim_string_stops_at_terminator() {
+        assert_eq!(trim_string(&utf16("COM1\0")), utf16("COM1").as_slice());
+        assert_eq!(trim_string(&utf16("COM1")), utf16("COM1").as_slice());
+        assert_eq!(trim_string(&utf16("\0")), &[] as &[u16]);
+    }
+
+    #[test]
+    fn split_multi_string_ends_at_empty_string() {
+        let strings = split_multi_string(&utf16("first\0second\0\0ignored\0")).collect::<Vec<_>>();
+        assert_eq!(strings, [utf16("first"), utf16("second")]);
+        assert_eq!(split_multi_string(&utf16("\0\0")).count(), 0);
+        assert_eq!(split_multi_string(&[]).count(), 0);
+    }
+}
@@ -0,0 +1,1352 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{ffi::c_void, marker::PhantomData, time::Duration};
+
+#[cfg(driver_model__driver_type = "KMDF")]
+use wdk_sys::WDFDEVICE_INIT;
+use wdk_sys::{
+    _WDF_DEVICE_IO_TYPE,
+    _WDF_REQUEST_SEND_OPTIONS_FLAGS,
+    _WDF_REQUEST_TYPE,
+    LONGLONG,
+    NTSTATUS,
+    STATUS_INVALID_BUFFER_SIZE,
+    STATUS_INVALID_DEVICE_REQUEST,
+    ULONG,
+    ULONG_PTR,
+    USHORT,
+    WDF_DEVICE_IO_TYPE,
+    WDF_REQUEST_PARAMETERS,
+    WDF_REQUEST_SEND_OPTIONS,
+    WDF_REQUEST_TYPE,
+    WDFDEVICE,
+    WDFFILEOBJECT,
+    WDFMEMORY,
+    WDFQUEUE,
+    WDFREQUEST,
+    call_unsafe_wdf_function_binding,
+};
+
+use super::timer::relative_due_time;
+#[cfg(driver_model__driver_type = "KMDF")]
+use crate::wdf::Device;
+use crate::{
+    ControlCode,
+    TransferMethod,
+    nt_success,
+    wdf::{FileObject, IoQueue, Memory},
+};
+
+/// Parameters of a [`Request`], by request type.
+///
+/// Returned by [`Request::params()`], so that `EvtIoDefault` handlers can
+/// dispatch on the type of the request without reading the union of
+/// [`WDF_REQUEST_PARAMETERS`] directly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestParameters {
+    /// An application opened the device or a file on it
+    Create {
+        /// Create options and disposition, ex. `FILE_DIRECTORY_FILE`
+        options: ULONG,
+        /// Attributes of the file being created, ex. `FILE_ATTRIBUTE_NORMAL`
+        file_attributes: USHORT,
+        /// Type of share access requested, ex. `FILE_SHARE_READ`
+        share_access: USHORT,
+    },
+    /// The last handle to a file object was closed
+    Cleanup,
+    /// A file object was released
+    Close,
+    /// An application is reading from the device
+    Read {
+        /// Number of bytes to read
+        length: usize,
+        /// Offset in the device to read from
+        offset: LONGLONG,
+    },
+    /// An application is writing to the device
+    Write {
+        /// Number of bytes to write
+        length: usize,
+        /// Offset in the device to write to
+        offset: LONGLONG,
+    },
+    /// An application sent an I/O control code to the device
+    DeviceControl {
+        /// I/O control code of the request
+        code: ControlCode,
+        /// Length of the input buffer, in bytes
+        input_length: usize,
+        /// Length of the output buffer, in bytes
+        output_length: usize,
+    },
+    /// Another driver sent an internal I/O control code to the device
+    InternalDeviceControl {
+        /// I/O control code of the request
+        code: ControlCode,
+        /// Length of the input buffer, in bytes
+        input_length: usize,
+        /// Length of the output buffer, in bytes
+        output_length: usize,
+    },
+    /// A request of another type, whose parameters are not decoded
+    Other {
+        /// Type of the request
+        request_type: WDF_REQUEST_TYPE,
+        /// Minor function code of the request
+        minor_function: u8,
+    },
+}
+
+/// Driver-defined callback invoked when a [`Request`] the driver marked
+/// cancelable with [`Request::mark_cancelable()`] is cancelled, ex. because the
+/// application that sent it exited.
+pub trait RequestCancelCallback {
+    /// Handles the cancellation of `request`. The request is no longer
+    /// cancelable, and the callback must complete it, typically with
+    /// `STATUS_CANCELLED`, after releasing any reference the driver keeps to
+    /// it. This is invoked from the framework's `EvtRequestCancel` callback, at
+    /// `IRQL` <= `DISPATCH_LEVEL`. Full documentation is available in the [EvtRequestCancel Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfrequest/nc-wdfrequest-evt_wdf_request_cancel)
+    fn cancel(request: Request);
+}
+
+/// Driver-defined callback invoked when a [`Request`] arrives at a device,
+/// before it is added to an I/O queue, in the context of the thread that sent
+/// it.
+///
+/// Set with [`set_io_in_caller_context_callback()`]. This is where the buffers
+/// of [`TransferMethod::Neither`] device
+/// control requests are accessed, with
+/// [`Request::probe_and_lock_user_buffer_for_read()`] and
+/// [`Request::probe_and_lock_user_buffer_for_write()`], as they are addresses
+/// in the address space of the application that sent the request.
+#[cfg(driver_model__driver_type = "KMDF")]
+pub trait IoInCallerContextCallback {
+    /// Handles `request` in the context of its sender. The callback must
+    /// either complete `request`, or add it to the I/O queues of `device` with
+    /// [`Device::enqueue_request()`]. This is invoked from the framework's
+    /// `EvtIoInCallerContext` callback, at `IRQL` = `PASSIVE_LEVEL` for the
+    /// requests of applications. Full documentation is available in the [EvtIoInCallerContext Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nc-wdfdevice-evt_wdf_io_in_caller_context)
+    fn in_caller_context(device: &Device, request: Request);
+}
+
+/// Invoke [`IoInCallerContextCallback::in_caller_context()`] of `T` for every
+/// request that arrives at the device that is created with `device_init`.
+/// Must be called before the device is created with `WdfDeviceCreate`, at
+/// `IRQL` = `PASSIVE_LEVEL`.
+///
+/// # Safety
+///
+/// `device_init` must point to a valid `WDFDEVICE_INIT` structure, that was
+/// not passed to `WdfDeviceCreate` yet.
+#[cfg(driver_model__driver_type = "KMDF")]
+pub unsafe fn set_io_in_caller_context_callback<T: IoInCallerContextCallback>(
+    device_init: *mut WDFDEVICE_INIT,
+) {
+    // SAFETY: The caller guarantees that `device_init` is valid
+    unsafe {
+        call_unsafe_wdf_function_binding!(
+            WdfDeviceInitSetIoInCallerContextCallback,
+            device_init,
+            Some(evt_io_in_caller_context::<T>),
+        );
+    }
+}
+
+/// WDF Request.
+///
+/// A handle to a framework request object, ex. the one delivered to the
+/// request handlers of an I/O queue, that the driver owns until it hands the
+/// request back to the framework. A request has a single owner, so [`Request`]
+/// is neither `Clone` nor `Copy`, and the methods that hand it back, ex.
+/// [`Request::complete()`] or [`Request::requeue()`], take it by value.
+pub struct Request {
+    wdf_request: WDFREQUEST,
+}
+impl Request {
+    /// Wrap a handle to a framework request object
+    ///
+    /// # Safety
+    ///
+    /// `request` must be a valid handle to a framework request object, that
+    /// is owned by the driver and has not been completed, for as long as the
+    /// returned [`Request`] is used. No other [`Request`] of the same request
+    /// object may be used meanwhile.
+    #[must_use]
+    pub const unsafe fn from_raw(request: WDFREQUEST) -> Self {
+        Self {
+            wdf_request: request,
+        }
+    }
+
+    /// Get the raw handle of the framework request object
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFREQUEST {
+        self.wdf_request
+    }
+
+    /// Get the parameters of the request, decoded by request type. Must be
+    /// called at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn params(&self) -> RequestParameters {
+        let params_size: ULONG;
+
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            const PARAMS_SIZE: usize = core::mem::size_of::<WDF_REQUEST_PARAMETERS>();
+
+            // Manually assert there is not truncation since clippy doesn't work for
+            // compile-time constants
+            const { assert!(PARAMS_SIZE <= ULONG::MAX as usize) }
+
+            params_size = PARAMS_SIZE as ULONG;
+        }
+
+        let mut params = WDF_REQUEST_PARAMETERS {
+            Size: params_size,
+            ..WDF_REQUEST_PARAMETERS::default()
+        };
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid. `params` is initialized with its size.
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfRequestGetParameters,
+                self.wdf_request,
+                &raw mut params,
+            );
+        }
+
+        match params.Type {
+            _WDF_REQUEST_TYPE::WdfRequestTypeCreate => {
+                // SAFETY: The framework fills the member of `Parameters` that matches
+                // `Type`
+                let create = unsafe { params.Parameters.Create };
+                RequestParameters::Create {
+                    options: create.Options,
+                    file_attributes: create.FileAttributes,
+                    share_access: create.ShareAccess,
+                }
+            }
+            _WDF_REQUEST_TYPE::WdfRequestTypeCleanup => RequestParameters::Cleanup,
+            _WDF_REQUEST_TYPE::WdfRequestTypeClose => RequestParameters::Close,
+            _WDF_REQUEST_TYPE::WdfRequestTypeRead => {
+                // SAFETY: The framework fills the member of `Parameters` that matches
+                // `Type`
+                let read = unsafe { params.Parameters.Read };
+                RequestParameters::Read {
+                    length: read.Length,
+                    offset: read.DeviceOffset,
+                }
+            }
+            _WDF_REQUEST_TYPE::WdfRequestTypeWrite => {
+                // SAFETY: The framework fills the member of `Parameters` that matches
+                // `Type`
+                let write = unsafe { params.Parameters.Write };
+                RequestParameters::Write {
+                    length: write.Length,
+                    offset: write.DeviceOffset,
+                }
+            }
+            _WDF_REQUEST_TYPE::WdfRequestTypeDeviceControl => {
+                // SAFETY: The framework fills the member of `Parameters` that matches
+                // `Type`
+                let device_io_control = unsafe { params.Parameters.DeviceIoControl };
+                RequestParameters::DeviceControl {
+                    code: ControlCode::from_raw(device_io_control.IoControlCode),
+                    input_length: device_io_control.InputBufferLength,
+                    output_length: device_io_control.OutputBufferLength,
+                }
+            }
+            _WDF_REQUEST_TYPE::WdfRequestTypeDeviceControlInternal => {
+                // SAFETY: The framework fills the member of `Parameters` that matches
+                // `Type`
+                let device_io_control = unsafe { params.Parameters.DeviceIoControl };
+                RequestParameters::InternalDeviceControl {
+                    code: ControlCode::from_raw(device_io_control.IoControlCode),
+                    input_length: device_io_control.InputBufferLength,
+                    output_length: device_io_control.OutputBufferLength,
+                }
+            }
+            request_type => RequestParameters::Other {
+                request_type,
+                minor_function: params.MinorFunction,
+            },
+        }
+    }
+
+    /// Get the framework file object the request was sent through, or `None`
+    /// if the request is not associated with a file object. Must be called at
+    /// `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn file_object(&self) -> Option<FileObject> {
+        let file_object: WDFFILEOBJECT;
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid
+        unsafe {
+            file_object =
+                call_unsafe_wdf_function_binding!(WdfRequestGetFileObject, self.wdf_request);
+        }
+        // SAFETY: The file object outlives the requests sent through it
+        (!file_object.is_null()).then(|| unsafe { FileObject::from_raw(file_object) })
+    }
+
+    /// Get the I/O queue the request was delivered from. Must be called at
+    /// `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn get_io_queue(&self) -> IoQueue {
+        let wdf_queue: WDFQUEUE;
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid
+        unsafe {
+            wdf_queue = call_unsafe_wdf_function_binding!(WdfRequestGetIoQueue, self.wdf_request);
+        }
+        // SAFETY: The queue the request was delivered from outlives the request
+        unsafe { IoQueue::from_raw(wdf_queue) }
+    }
+
+    /// Get the input buffer of the request, ex. the data an application writes
+    /// or the input of a device control code. The buffer holds at least
+    /// `minimum_length` bytes. Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// The buffer borrows the request mutably, as the input and output buffers
+    /// of buffered device control requests are the same memory, so the request
+    /// cannot be completed or its output buffer retrieved while the input
+    /// buffer is in use.
+    ///
+    /// Only buffered requests are borrowed as a slice, i.e.
+    /// [`TransferMethod::Buffered`] device control requests and the write
+    /// requests of devices that use buffered I/O, whose buffers are copies in
+    /// system memory. The buffers of direct requests are the pages of the
+    /// application, which it can change at any time, and are retrieved with
+    /// [`Request::retrieve_direct_input_buffer()`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the buffer could not be retrieved:
+    /// * `STATUS_BUFFER_TOO_SMALL` if the buffer is shorter than
+    ///   `minimum_length`
+    /// * `STATUS_INVALID_DEVICE_REQUEST` if the request has no input buffer,
+    ///   ex. a read request or a [`TransferMethod::Neither`] device control
+    ///   request, or if it is not a buffered request
+    ///
+    /// Full error documentation is available in the [WdfRequestRetrieveInputBuffer Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfrequest/nf-wdfrequest-wdfrequestretrieveinputbuffer#return-value)
+    pub fn retrieve_input_buffer(&mut self, minimum_length: usize) -> Result<&[u8], NTSTATUS> {
+        if self.uses_direct_buffers() {
+            return Err(STATUS_INVALID_DEVICE_REQUEST);
+        }
+        let (buffer, length) = self.retrieve_raw_input_buffer(minimum_length)?;
+        // SAFETY: On success, the framework returns a buffer of `length` bytes that
+        // stays valid until the request is completed. The request is buffered, so
+        // the buffer is a copy in system memory that the application cannot access.
+        // `Request` is not `Copy`, and completing or otherwise handing back the
+        // request consumes it, which cannot happen while `self` is borrowed. The
+        // caller of `from_raw` guaranteed that no other `Request` of the request is
+        // used meanwhile.
+        Ok(unsafe { buffer_slice(buffer, length) })
+    }
+
+    /// Get the input buffer of the request, whatever its transfer method, ex.
+    /// the data an application writes to a device that uses direct I/O, or
+    /// the input of a [`TransferMethod::InDirect`] or
+    /// [`TransferMethod::OutDirect`] device control code. The buffer holds at
+    /// least `minimum_length` bytes. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    ///
+    /// The buffer of a direct request is the buffer of the application, locked
+    /// in memory and mapped for the driver, that the application can change
+    /// while the driver reads it, so it is only accessible through copies, see
+    /// [`UserInputBuffer`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the buffer could not be retrieved:
+    /// * `STATUS_BUFFER_TOO_SMALL` if the buffer is shorter than
+    ///   `minimum_length`
+    /// * `STATUS_INVALID_DEVICE_REQUEST` if the request has no input buffer,
+    ///   ex. a read request or a [`TransferMethod::Neither`] device control
+    ///   request
+    ///
+    /// Full error documentation is available in the [WdfRequestRetrieveInputBuffer Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfrequest/nf-wdfrequest-wdfrequestretrieveinputbuffer#return-value)
+    pub fn retrieve_direct_input_buffer(
+        &mut self,
+        minimum_length: usize,
+    ) -> Result<UserInputBuffer<'_>, NTSTATUS> {
+        let (buffer, length) = self.retrieve_raw_input_buffer(minimum_length)?;
+        // The buffer stays valid until the request is completed, which cannot happen
+        // while the request is borrowed
+        Ok(UserInputBuffer {
+            buffer: buffer.cast_const().cast(),
+            len: length,
+            _request: PhantomData,
+        })
+    }
+
+    /// Get the output buffer of the request, ex. to fill the data an
+    /// application reads or the output of a device control code. The buffer
+    /// holds at least `minimum_length` bytes. Report the number of bytes
+    /// written to it when completing the request with
+    /// [`Request::complete_with_information()`]. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    ///
+    /// The buffer borrows the request mutably, so the request cannot be
+    /// completed while the buffer is in use.
+    ///
+    /// Only buffered requests are borrowed as a slice, i.e.
+    /// [`TransferMethod::Buffered`] device control requests and the read
+    /// requests of devices that use buffered I/O, whose buffers are copies in
+    /// system memory. The buffers of direct requests are the pages of the
+    /// application, which it can access at any time, and are retrieved with
+    /// [`Request::retrieve_direct_output_buffer()`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the buffer could not be retrieved:
+    /// * `STATUS_BUFFER_TOO_SMALL` if the buffer is shorter than
+    ///   `minimum_length`
+    /// * `STATUS_INVALID_DEVICE_REQUEST` if the request has no output buffer,
+    ///   ex. a write request or a [`TransferMethod::Neither`] device control
+    ///   request, or if it is not a buffered request
+    ///
+    /// Full error documentation is available in the [WdfRequestRetrieveOutputBuffer Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfrequest/nf-wdfrequest-wdfrequestretrieveoutputbuffer#return-value)
+    pub fn retrieve_output_buffer(&mut self, minimum_length: usize) -> Result<&mut [u8], NTSTATUS> {
+        if self.uses_direct_buffers() {
+            return Err(STATUS_INVALID_DEVICE_REQUEST);
+        }
+        let (buffer, length) = self.retrieve_raw_output_buffer(minimum_length)?;
+        // SAFETY: On success, the framework returns a buffer of `length` bytes that
+        // stays valid until the request is completed. The request is buffered, so
+        // the buffer is a copy in system memory that the application cannot access.
+        // `Request` is not `Copy`, and completing or otherwise handing back the
+        // request consumes it, which cannot happen while `self` is borrowed. The
+        // caller of `from_raw` guaranteed that no other `Request` of the request is
+        // used meanwhile, so nothing else accesses the buffer.
+        Ok(unsafe { buffer_slice_mut(buffer, length) })
+    }
+
+    /// Get the output buffer of the request, whatever its transfer method, ex.
+    /// to fill the data an application reads from a device that uses direct
+    /// I/O, or the output of a [`TransferMethod::InDirect`] or
+    /// [`TransferMethod::OutDirect`] device control code. The buffer holds at
+    /// least `minimum_length` bytes. Report the number of bytes written to it
+    /// when completing the request with
+    /// [`Request::complete_with_information()`]. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    ///
+    /// The buffer of a direct request is the buffer of the application, locked
+    /// in memory and mapped for the driver, that the application can access
+    /// while the driver writes to it, so it is only accessible through copies,
+    /// see [`UserOutputBuffer`]. The driver reads from it with
+    /// [`TransferMethod::InDirect`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the buffer could not be retrieved:
+    /// * `STATUS_BUFFER_TOO_SMALL` if the buffer is shorter than
+    ///   `minimum_length`
+    /// * `STATUS_INVALID_DEVICE_REQUEST` if the request has no output buffer,
+    ///   ex. a write request or a [`TransferMethod::Neither`] device control
+    ///   request
+    ///
+    /// Full error documentation is available in the [WdfRequestRetrieveOutputBuffer Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfrequest/nf-wdfrequest-wdfrequestretrieveoutputbuffer#return-value)
+    pub fn retrieve_direct_output_buffer(
+        &mut self,
+        minimum_length: usize,
+    ) -> Result<UserOutputBuffer<'_>, NTSTATUS> {
+        let (buffer, length) = self.retrieve_raw_output_buffer(minimum_length)?;
+        // The buffer stays valid until the request is completed, which cannot happen
+        // while the request is borrowed
+        Ok(UserOutputBuffer {
+            buffer: buffer.cast(),
+            len: length,
+            _request: PhantomData,
+        })
+    }
+
+    /// Get the address and length of the input buffer of the request, as
+    /// returned by `WdfRequestRetrieveInputBuffer`
+    fn retrieve_raw_input_buffer(
+        &self,
+        minimum_length: usize,
+    ) -> Result<(*mut c_void, usize), NTSTATUS> {
+        let mut buffer: *mut c_void = core::ptr::null_mut();
+        let mut length = 0;
+
+        let nt_status;
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid and not completed yet
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfRequestRetrieveInputBuffer,
+                self.wdf_request,
+                minimum_length,
+                &raw mut buffer,
+                &raw mut length,
+            );
+        }
+        nt_success(nt_status)
+            .then_some((buffer, length))
+            .ok_or(nt_status)
+    }
+
+    /// Get the address and length of the output buffer of the request, as
+    /// returned by `WdfRequestRetrieveOutputBuffer`
+    fn retrieve_raw_output_buffer(
+        &self,
+        minimum_length: usize,
+    ) -> Result<(*mut c_void, usize), NTSTATUS> {
+        let mut buffer: *mut c_void = core::ptr::null_mut();
+        let mut length = 0;
+
+        let nt_status;
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid and not completed yet
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfRequestRetrieveOutputBuffer,
+                self.wdf_request,
+                minimum_length,
+                &raw mut buffer,
+                &raw mut length,
+            );
+        }
+        nt_success(nt_status)
+            .then_some((buffer, length))
+            .ok_or(nt_status)
+    }
+
+    /// Returns `true` if the buffers of the request may be the buffers of the
+    /// application, locked in memory and mapped for the driver, rather than
+    /// copies in system memory. These are the buffers of
+    /// [`TransferMethod::InDirect`] and [`TransferMethod::OutDirect`] device
+    /// control requests, and of the read and write requests of devices that do
+    /// not use buffered I/O. The I/O type of the device is unknown for requests
+    /// that were not delivered from a queue, so their read and write buffers
+    /// are treated as direct.
+    fn uses_direct_buffers(&self) -> bool {
+        match self.params() {
+            RequestParameters::DeviceControl { code, .. }
+            | RequestParameters::InternalDeviceControl { code, .. } => matches!(
+                code.method(),
+                TransferMethod::InDirect | TransferMethod::OutDirect
+            ),
+            RequestParameters::Read { .. } | RequestParameters::Write { .. } => {
+                self.read_write_io_type() != _WDF_DEVICE_IO_TYPE::WdfDeviceIoBuffered
+            }
+            _ => false,
+        }
+    }
+
+    /// Get the I/O type of the read and write requests of the device stack of
+    /// the queue the request was delivered from, or `WdfDeviceIoUndefined` if
+    /// the request was not delivered from a queue
+    fn read_write_io_type(&self) -> WDF_DEVICE_IO_TYPE {
+        let wdf_queue: WDFQUEUE;
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid
+        unsafe {
+            wdf_queue = call_unsafe_wdf_function_binding!(WdfRequestGetIoQueue, self.wdf_request);
+        }
+        if wdf_queue.is_null() {
+            return _WDF_DEVICE_IO_TYPE::WdfDeviceIoUndefined;
+        }
+
+        let wdf_device: WDFDEVICE;
+        // SAFETY: The queue the request was delivered from outlives the request
+        unsafe {
+            wdf_device = call_unsafe_wdf_function_binding!(WdfIoQueueGetDevice, wdf_queue);
+        }
+        let mut read_write_io_type: WDF_DEVICE_IO_TYPE = _WDF_DEVICE_IO_TYPE::WdfDeviceIoUndefined;
+        let mut io_control_io_type: WDF_DEVICE_IO_TYPE = _WDF_DEVICE_IO_TYPE::WdfDeviceIoUndefined;
+        // SAFETY: The device of a queue outlives the queue, and both I/O types are
+        // valid locations to write to
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfDeviceGetDeviceStackIoType,
+                wdf_device,
+                &raw mut read_write_io_type,
+                &raw mut io_control_io_type,
+            );
+        }
+        read_write_io_type
+    }
+
+    /// Get the memory object of the input buffer of the request, ex. to copy
+    /// its contents at an offset with [`Memory::copy_to_slice()`]. The input
+    /// buffer is available for the same requests as with
+    /// [`Request::retrieve_direct_input_buffer()`]. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the memory could not be
+    /// retrieved:
+    /// * `STATUS_INVALID_DEVICE_REQUEST` if the request has no input buffer
+    ///
+    /// Full error documentation is available in the [WdfRequestRetrieveInputMemory Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfrequest/nf-wdfrequest-wdfrequestretrieveinputmemory#return-value)
+    pub fn retrieve_input_memory(&mut self) -> Result<Memory<'_>, NTSTATUS> {
+        let mut wdf_memory: WDFMEMORY = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid and not completed yet
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfRequestRetrieveInputMemory,
+                self.wdf_request,
+                &raw mut wdf_memory,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        // SAFETY: On success, the framework returns a memory object that is owned by
+        // the request, and stays valid until the request is completed. `Request` is
+        // not `Copy`, and completing the request consumes it, which cannot happen
+        // while `self` is borrowed.
+        Ok(unsafe { Memory::from_borrowed(wdf_memory) })
+    }
+
+    /// Get the memory object of the output buffer of the request, ex. to copy
+    /// data into it at an offset with [`Memory::copy_from_slice()`]. The output
+    /// buffer is available for the same requests as with
+    /// [`Request::retrieve_direct_output_buffer()`]. Must be called at `IRQL`
+    /// <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the memory could not be
+    /// retrieved:
+    /// * `STATUS_INVALID_DEVICE_REQUEST` if the request has no output buffer
+    ///
+    /// Full error documentation is available in the [WdfRequestRetrieveOutputMemory Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfrequest/nf-wdfrequest-wdfrequestretrieveoutputmemory#return-value)
+    pub fn retrieve_output_memory(&mut self) -> Result<Memory<'_>, NTSTATUS> {
+        let mut wdf_memory: WDFMEMORY = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid and not completed yet
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfRequestRetrieveOutputMemory,
+                self.wdf_request,
+                &raw mut wdf_memory,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        // SAFETY: On success, the framework returns a memory object that is owned by
+        // the request, and stays valid until the request is completed. `Request` is
+        // not `Copy`, and completing the request consumes it, which cannot happen
+        // while `self` is borrowed.
+        Ok(unsafe { Memory::from_borrowed(wdf_memory) })
+    }
+
+    /// Probe and lock the input buffer of a
+    /// [`TransferMethod::Neither`] device
+    /// control request, so that the driver can read from it. The buffer holds
+    /// at least `minimum_length` bytes. Must be called from
+    /// [`IoInCallerContextCallback::in_caller_context()`], at `IRQL` =
+    /// `PASSIVE_LEVEL`, as the buffer is an address in the address space of
+    /// the application that sent the request.
+    ///
+    /// The framework checks that the application can read the buffer, locks it
+    /// in memory and maps it for the driver. It stays locked until the request
+    /// is completed. The application can still change its contents while the
+    /// driver reads them, so it is only accessible through copies, see
+    /// [`UserInputBuffer`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the buffer could not be probed and
+    /// locked:
+    /// * `STATUS_BUFFER_TOO_SMALL` if the buffer is shorter than
+    ///   `minimum_length`
+    /// * `STATUS_INVALID_DEVICE_REQUEST` if the request is not a
+    ///   [`TransferMethod::Neither`] device control request, or is not handled
+    ///   in the context of its sender
+    /// * `STATUS_ACCESS_VIOLATION` if the application cannot read the buffer
+    ///
+    /// Full error documentation is available in the [WdfRequestProbeAndLockUserBufferForRead Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfrequest/nf-wdfrequest-wdfrequestprobeandlockuserbufferforread#return-value)
+    #[cfg(driver_model__driver_type = "KMDF")]
+    pub fn probe_and_lock_user_buffer_for_read(
+        &mut self,
+        minimum_length: usize,
+    ) -> Result<UserInputBuffer<'_>, NTSTATUS> {
+        let mut user_buffer: *mut c_void = core::ptr::null_mut();
+        let mut length = 0;
+
+        let mut nt_status;
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid and not completed yet. The driver does
+        // not access the unsafe user buffer, it only passes it to the framework to be
+        // probed and locked.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfRequestRetrieveUnsafeUserInputBuffer,
+                self.wdf_request,
+                minimum_length,
+                &raw mut user_buffer,
+                &raw mut length,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        let mut wdf_memory: WDFMEMORY = core::ptr::null_mut();
+        // SAFETY: `user_buffer` and `length` describe the input buffer of the
+        // request, as returned by the framework
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfRequestProbeAndLockUserBufferForRead,
+                self.wdf_request,
+                user_buffer,
+                length,
+                &raw mut wdf_memory,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        // SAFETY: On success, the framework returns a memory object that is owned by
+        // the request, and stays valid until the request is completed. `Request` is
+        // not `Copy`, and completing the request consumes it, which cannot happen
+        // while `self` is borrowed.
+        let (buffer, length) = unsafe { memory_buffer(wdf_memory) };
+        // The buffer of the memory object is the locked buffer of the application,
+        // mapped for the driver, and stays valid while the request is borrowed
+        Ok(UserInputBuffer {
+            buffer: buffer.cast_const().cast(),
+            len: length,
+            _request: PhantomData,
+        })
+    }
+
+    /// Probe and lock the output buffer of a
+    /// [`TransferMethod::Neither`] device
+    /// control request, so that the driver can write to it. The buffer holds
+    /// at least `minimum_length` bytes. Report the number of bytes written to
+    /// it when completing the request with
+    /// [`Request::complete_with_information()`]. Must be called from
+    /// [`IoInCallerContextCallback::in_caller_context()`], at `IRQL` =
+    /// `PASSIVE_LEVEL`, as the buffer is an address in the address space of
+    /// the application that sent the request.
+    ///
+    /// The framework checks that the application can write to the buffer,
+    /// locks it in memory and maps it for the driver. It stays locked until the
+    /// request is completed. The application can still access it while the
+    /// driver writes to it, so it is only accessible through copies, see
+    /// [`UserOutputBuffer`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the buffer could not be probed and
+    /// locked:
+    /// * `STATUS_BUFFER_TOO_SMALL` if the buffer is shorter than
+    ///   `minimum_length`
+    /// * `STATUS_INVALID_DEVICE_REQUEST` if the request is not a
+    ///   [`TransferMethod::Neither`] device control request, or is not handled
+    ///   in the context of its sender
+    /// * `STATUS_ACCESS_VIOLATION` if the application cannot write to the
+    ///   buffer
+    ///
+    /// Full error documentation is available in the [WdfRequestProbeAndLockUserBufferForWrite Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfrequest/nf-wdfrequest-wdfrequestprobeandlockuserbufferforwrite#return-value)
+    #[cfg(driver_model__driver_type = "KMDF")]
+    pub fn probe_and_lock_user_buffer_for_write(
+        &mut self,
+        minimum_length: usize,
+    ) -> Result<UserOutputBuffer<'_>, NTSTATUS> {
+        let mut user_buffer: *mut c_void = core::ptr::null_mut();
+        let mut length = 0;
+
+        let mut nt_status;
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid and not completed yet. The driver does
+        // not access the unsafe user buffer, it only passes it to the framework to be
+        // probed and locked.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfRequestRetrieveUnsafeUserOutputBuffer,
+                self.wdf_request,
+                minimum_length,
+                &raw mut user_buffer,
+                &raw mut length,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        let mut wdf_memory: WDFMEMORY = core::ptr::null_mut();
+        // SAFETY: `user_buffer` and `length` describe the output buffer of the
+        // request, as returned by the framework
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfRequestProbeAndLockUserBufferForWrite,
+                self.wdf_request,
+                user_buffer,
+                length,
+                &raw mut wdf_memory,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        // SAFETY: On success, the framework returns a memory object that is owned by
+        // the request, and stays valid until the request is completed. `Request` is
+        // not `Copy`, and completing the request consumes it, which cannot happen
+        // while `self` is borrowed.
+        let (buffer, length) = unsafe { memory_buffer(wdf_memory) };
+        // The buffer of the memory object is the locked buffer of the application,
+        // mapped for the driver, and stays valid while the request is borrowed
+        Ok(UserOutputBuffer {
+            buffer: buffer.cast(),
+            len: length,
+            _request: PhantomData,
+        })
+    }
+
+    /// Check whether the request was cancelled, so that the driver can stop
+    /// processing it early, ex. between the steps of a long running operation,
+    /// and complete it with `STATUS_CANCELLED`. Requests the driver marked
+    /// cancelable are completed by their [`RequestCancelCallback`] instead.
+    /// Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        let cancelled;
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid
+        unsafe {
+            cancelled = call_unsafe_wdf_function_binding!(WdfRequestIsCanceled, self.wdf_request);
+        }
+        cancelled != 0
+    }
+
+    /// Mark the request cancelable, so that [`RequestCancelCallback::cancel`]
+    /// of `T` is invoked if it is cancelled while the driver holds on to it,
+    /// ex. while it waits for the device. The callback can run as soon as this
+    /// returns, so the driver must make the request reachable by the callback
+    /// before marking it. Prefer [`Request::into_cancelable()`] where the
+    /// driver does not need to share the request with the callback. Must be
+    /// called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_CANCELLED` if the request was already
+    /// cancelled. The request is not marked cancelable and the callback is not
+    /// invoked in that case, so the driver still owns the request and must
+    /// complete it.
+    ///
+    /// # Safety
+    ///
+    /// The callback receives its own [`Request`] of the request, so once the
+    /// request is marked, `self` must not be used except to call
+    /// [`Request::unmark_cancelable()`], and only be used again if that
+    /// succeeds.
+    pub unsafe fn mark_cancelable<T: RequestCancelCallback>(&self) -> Result<(), NTSTATUS> {
+        let nt_status;
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid and not completed yet
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfRequestMarkCancelableEx,
+                self.wdf_request,
+                Some(evt_request_cancel::<T>),
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Make a request marked with [`Request::mark_cancelable()`] non-cancelable
+    /// again, so that the driver owns it and can complete it. Must be called
+    /// at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_CANCELLED` if the request was
+    /// cancelled and its [`RequestCancelCallback`] is running or about to run.
+    /// The callback completes the request in that case, so the driver must not
+    /// use it afterwards.
+    ///
+    /// # Safety
+    ///
+    /// The request must have been marked with [`Request::mark_cancelable()`],
+    /// and not unmarked since.
+    pub unsafe fn unmark_cancelable(&self) -> Result<(), NTSTATUS> {
+        let nt_status;
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid and not completed yet
+        unsafe {
+            nt_status =
+                call_unsafe_wdf_function_binding!(WdfRequestUnmarkCancelable, self.wdf_request);
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Mark the request cancelable like [`Request::mark_cancelable()`], and
+    /// take ownership of it as a [`CancellableMarkedRequest`], which can only
+    /// be completed or unmarked in a way that does not race with
+    /// [`RequestCancelCallback::cancel`] of `T`. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return the request back if it was already cancelled.
+    /// The request is not marked cancelable and the callback is not invoked in
+    /// that case, so the driver still owns the request and must complete it.
+    pub fn into_cancelable<T: RequestCancelCallback>(
+        self,
+    ) -> Result<CancellableMarkedRequest, Self> {
+        // SAFETY: The request is moved into the `CancellableMarkedRequest`, which
+        // only uses it again after unmarking it
+        match unsafe { self.mark_cancelable::<T>() } {
+            Ok(()) => Ok(CancellableMarkedRequest { request: self }),
+            Err(_) => Err(self),
+        }
+    }
+
+    /// Format the request to be sent to the next lower driver as it was
+    /// received, ex. before a filter driver forwards it with
+    /// [`crate::wdf::IoTarget::send()`]. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    pub fn format_using_current_type(&self) {
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid and not completed yet
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfRequestFormatRequestUsingCurrentType,
+                self.wdf_request,
+            );
+        }
+    }
+
+    /// Return the request to the head of the I/O queue it was delivered from,
+    /// ex. when the resources to process it are temporarily unavailable. The
+    /// queue must use manual dispatching. The driver no longer owns the request
+    /// once it is requeued, so it must not be used afterwards. Must be called
+    /// at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request could not be requeued,
+    /// ex. `STATUS_INVALID_DEVICE_REQUEST` if the queue does not use manual
+    /// dispatching. The driver still owns the request in that case, so it is
+    /// returned along with the error.
+    pub fn requeue(self) -> Result<(), (Self, NTSTATUS)> {
+        let nt_status;
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(WdfRequestRequeue, self.wdf_request);
+        }
+        if !nt_success(nt_status) {
+            return Err((self, nt_status));
+        }
+        Ok(())
+    }
+
+    /// Add the request to `queue`, ex. to park it in a queue with manual
+    /// dispatching until the driver can process it. The driver no longer owns
+    /// the request once it is forwarded, so it is consumed. Must be called at
+    /// `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request could not be
+    /// forwarded, ex. `STATUS_WDF_BUSY` if `queue` does not accept requests.
+    /// The driver still owns the request in that case, so it is returned along
+    /// with the error. Full error documentation is available in the [WdfRequestForwardToIoQueue Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfrequest/nf-wdfrequest-wdfrequestforwardtoioqueue#return-value)
+    pub fn forward_to_io_queue(self, queue: &IoQueue) -> Result<(), (Self, NTSTATUS)> {
+        let nt_status;
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid and not completed yet. `IoQueue`
+        // guarantees that its handle is valid.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfRequestForwardToIoQueue,
+                self.wdf_request,
+                queue.as_raw(),
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err((self, nt_status));
+        }
+        Ok(())
+    }
+
+    /// Set the information reported to the sender of the request when it is
+    /// completed, ex. the number of bytes copied to the output buffer so far,
+    /// so that it can be accumulated over several stages of processing before
+    /// the request is completed with [`Request::complete()`] or forwarded.
+    /// Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn set_information(&self, information: usize) {
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid and not completed yet
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfRequestSetInformation,
+                self.wdf_request,
+                information as ULONG_PTR,
+            );
+        }
+    }
+
+    /// Get the information reported to the sender of the request, as set with
+    /// [`Request::set_information()`], or by the I/O target that completed the
+    /// request after it was sent with [`crate::wdf::IoTarget::send()`]. Must
+    /// be called at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn get_information(&self) -> usize {
+        let information: ULONG_PTR;
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid
+        unsafe {
+            information =
+                call_unsafe_wdf_function_binding!(WdfRequestGetInformation, self.wdf_request);
+        }
+        usize::try_from(information).unwrap_or(usize::MAX)
+    }
+
+    /// Complete the request with `status`. The driver no longer owns the
+    /// request once it is completed, so it must not be used afterwards. Must be
+    /// called at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn complete(self, status: NTSTATUS) {
+        #[cfg(all(feature = "io-queue-stats", driver_model__driver_type = "KMDF"))]
+        // SAFETY: The caller of `from_raw` guaranteed that `wdf_request` is valid and
+        // not completed yet
+        unsafe {
+            super::io_queue_stats::record_completed(self.wdf_request, status);
+        }
+
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid and not completed yet
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfRequestComplete, self.wdf_request, status);
+        }
+    }
+
+    /// Complete the request with `status`, and report `information` to the
+    /// sender of the request, ex. the number of bytes written to the output
+    /// buffer. The driver no longer owns the request once it is completed, so
+    /// it must not be used afterwards. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    pub fn complete_with_information(self, status: NTSTATUS, information: usize) {
+        #[cfg(all(feature = "io-queue-stats", driver_model__driver_type = "KMDF"))]
+        // SAFETY: The caller of `from_raw` guaranteed that `wdf_request` is valid and
+        // not completed yet
+        unsafe {
+            super::io_queue_stats::record_completed(self.wdf_request, status);
+        }
+
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid and not completed yet
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfRequestCompleteWithInformation,
+                self.wdf_request,
+                status,
+                information as ULONG_PTR,
+            );
+        }
+    }
+
+    /// Acknowledge the stop of the queue the request was delivered from, from
+    /// [`crate::wdf::IoQueueStopCallback::stop`]. The driver keeps the request
+    /// until it completes it, and is notified when the queue resumes through
+    /// [`crate::wdf::IoQueueResumeCallback::resume`]. Must be called at `IRQL`
+    /// <= `DISPATCH_LEVEL`.
+    pub fn stop_acknowledge(&self) {
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfRequestStopAcknowledge, self.wdf_request, 0);
+        }
+    }
+
+    /// Acknowledge the stop of the queue the request was delivered from like
+    /// [`Request::stop_acknowledge()`], and return the request to the queue.
+    /// The driver no longer owns the request, so it is consumed. Must be called
+    /// at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn stop_acknowledge_and_requeue(self) {
+        // SAFETY: `wdf_request` is a private member of `Request`, which the caller of
+        // `from_raw` guaranteed to be valid
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfRequestStopAcknowledge, self.wdf_request, 1);
+        }
+    }
+}
+
+/// [`Request`] marked cancelable with [`Request::into_cancelable()`].
+///
+/// While the request is marked, it is owned by the framework as much as by the
+/// driver: if it is cancelled, the [`RequestCancelCallback`] it was marked with
+/// completes it. The driver can only take it back with
+/// [`CancellableMarkedRequest::unmark()`], or complete it with
+/// [`CancellableMarkedRequest::complete_if_not_cancelled()`], which both
+/// resolve the race with the cancel callback, so the request is completed
+/// exactly once. [`Request::into_cancelable()`] consumes the [`Request`], which
+/// is not `Copy`, so the driver has no other handle to complete it with
+/// meanwhile.
+#[must_use = "the request must be unmarked or completed unless it is cancelled"]
+pub struct CancellableMarkedRequest {
+    request: Request,
+}
+
+impl CancellableMarkedRequest {
+    /// Get the raw handle of the framework request object, ex. to find the
+    /// request kept by the driver from [`RequestCancelCallback::cancel`]
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFREQUEST {
+        self.request.as_raw()
+    }
+
+    /// Make the request non-cancelable again, and return it so that the driver
+    /// can keep processing it. Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_CANCELLED` if the request was
+    /// cancelled and its [`RequestCancelCallback`] is running or about to run.
+    /// The callback completes the request in that case.
+    pub fn unmark(self) -> Result<Request, NTSTATUS> {
+        // SAFETY: The request was marked by `Request::into_cancelable`, and is only
+        // unmarked here, which consumes `self`
+        unsafe { self.request.unmark_cancelable() }.map(|()| self.request)
+    }
+
+    /// Complete the request with `status`, unless it was cancelled and its
+    /// [`RequestCancelCallback`] completes it instead. Must be called at `IRQL`
+    /// <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_CANCELLED` if the request was
+    /// cancelled, and so was not completed with `status`.
+    pub fn complete_if_not_cancelled(self, status: NTSTATUS) -> Result<(), NTSTATUS> {
+        self.unmark().map(|request| request.complete(status))
+    }
+
+    /// Complete the request with `status` and `information`, unless it was
+    /// cancelled and its [`RequestCancelCallback`] completes it instead. Must
+    /// be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_CANCELLED` if the request was
+    /// cancelled, and so was not completed with `status`.
+    pub fn complete_with_information_if_not_cancelled(
+        self,
+        status: NTSTATUS,
+        information: usize,
+    ) -> Result<(), NTSTATUS> {
+        self.unmark()
+            .map(|request| request.complete_with_information(status, information))
+    }
+}
+
+/// Locked input buffer of a direct or [`TransferMethod::Neither`] request,
+/// returned by [`Request::retrieve_direct_input_buffer()`] and
+/// [`Request::probe_and_lock_user_buffer_for_read()`].
+///
+/// The application that sent the request can change the contents of the buffer
+/// at any time, even while the driver reads it, so it cannot be borrowed as a
+/// slice. The driver copies it with [`UserInputBuffer::copy_to()`], which reads
+/// each byte exactly once, and must validate and use the copy, never read the
+/// buffer twice.
+pub struct UserInputBuffer<'a> {
+    buffer: *const u8,
+    len: usize,
+    _request: PhantomData<&'a mut Request>,
+}
+
+impl UserInputBuffer<'_> {
+    /// Get the length of the buffer, in bytes
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the buffer holds no bytes
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Copy the bytes of the buffer, starting `offset` bytes into it, to
+    /// `destination`, filling it
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_INVALID_BUFFER_SIZE` if the buffer
+    /// holds fewer than `destination.len()` bytes after `offset`.
+    pub fn copy_to(&self, offset: usize, destination: &mut [u8]) -> Result<(), NTSTATUS> {
+        check_user_buffer_range(offset, destination.len(), self.len)?;
+        for (index, byte) in destination.iter_mut().enumerate() {
+            let source = self.buffer.wrapping_add(offset + index);
+            // SAFETY: The range was checked to be within the buffer, which stays locked
+            // and mapped while the request is borrowed. The read is volatile, as the
+            // application can change the byte concurrently.
+            *byte = unsafe { source.read_volatile() };
+        }
+        Ok(())
+    }
+}
+
+/// Locked output buffer of a direct or [`TransferMethod::Neither`] request,
+/// returned by [`Request::retrieve_direct_output_buffer()`] and
+/// [`Request::probe_and_lock_user_buffer_for_write()`].
+///
+/// The application that sent the request can access the buffer at any time,
+/// even while the driver writes to it, so it cannot be borrowed as a slice. The
+/// driver copies data into it with [`UserOutputBuffer::copy_from()`], and must
+/// not read back what it wrote, as the application can have changed it.
+pub struct UserOutputBuffer<'a> {
+    buffer: *mut u8,
+    len: usize,
+    _request: PhantomData<&'a mut Request>,
+}
+
+impl UserOutputBuffer<'_> {
+    /// Get the length of the buffer, in bytes
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the buffer holds no bytes
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Copy `source` into the buffer, starting `offset` bytes into it
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_INVALID_BUFFER_SIZE` if `source` does
+    /// not fit in the buffer at `offset`.
+    pub fn copy_from(&mut self, offset: usize, source: &[u8]) -> Result<(), NTSTATUS> {
+        check_user_buffer_range(offset, source.len(), self.len)?;
+        for (index, byte) in source.iter().enumerate() {
+            let destination = self.buffer.wrapping_add(offset + index);
+            // SAFETY: The range was checked to be within the buffer, which stays locked
+            // and mapped while the request is borrowed. The write is volatile, as the
+            // application can access the byte concurrently.
+            unsafe { destination.write_volatile(*byte) };
+        }
+        Ok(())
+    }
+}
+
+/// Check that `count` bytes at `offset` are within a user buffer of `len`
+/// bytes
+fn check_user_buffer_range(offset: usize, count: usize, len: usize) -> Result<(), NTSTATUS> {
+    offset
+        .checked_add(count)
+        .is_some_and(|end| end <= len)
+        .then_some(())
+        .ok_or(STATUS_INVALID_BUFFER_SIZE)
+}
+
+/// `EvtRequestCancel` callback that forwards to
+/// [`RequestCancelCallback::cancel`]
+unsafe extern "C" fn evt_request_cancel<T: RequestCancelCallback>(wdf_request: WDFREQUEST) {
+    // SAFETY: The framework passes a request the driver owns, which stays valid
+    // until the driver completes it. The driver marked it cancelable, so it does
+    // not use its own `Request` of it unless it fails to unmark it.
+    let request = unsafe { Request::from_raw(wdf_request) };
+    T::cancel(request);
+}
+
+/// `EvtIoInCallerContext` callback that forwards to
+/// [`IoInCallerContextCallback::in_caller_context()`]
+#[cfg(driver_model__driver_type = "KMDF")]
+unsafe extern "C" fn evt_io_in_caller_context<T: IoInCallerContextCallback>(
+    wdf_device: WDFDEVICE,
+    wdf_request: WDFREQUEST,
+) {
+    // SAFETY: The framework passes the device the request arrived at, which is
+    // valid until the callback returns
+    let device = unsafe { Device::from_raw(wdf_device) };
+    // SAFETY: The framework passes a valid request, which the driver owns until it
+    // completes or enqueues it
+    let request = unsafe { Request::from_raw(wdf_request) };
+    T::in_caller_context(&device, request);
+}
+
+/// Get the buffer of the memory object `memory` and its length
+///
+/// # Safety
+///
+/// `memory` must be a valid handle to a framework memory object
+#[cfg(driver_model__driver_type = "KMDF")]
+unsafe fn memory_buffer(memory: WDFMEMORY) -> (*mut c_void, usize) {
+    let mut length = 0;
+    let buffer;
+    // SAFETY: The caller guarantees that `memory` is valid
+    unsafe {
+        buffer = call_unsafe_wdf_function_binding!(WdfMemoryGetBuffer, memory, &raw mut length);
+    }
+    (buffer, length)
+}
+
+/// Get the buffer at `buffer` as a slice of `length` bytes, ex. a request
+/// buffer
+///
+/// # Safety
+///
+/// `buffer` must be valid for reads of `length` bytes for `'a`, unless
+/// `length` is 0
+pub(super) unsafe fn buffer_slice<'a>(buffer: *mut c_void, length: usize) -> &'a [u8] {
+    if length == 0 {
+        return &[];
+    }
+    // SAFETY: The caller guarantees that `buffer` is valid for `length` bytes
+    unsafe { core::slice::from_raw_parts(buffer.cast::<u8>(), length) }
+}
+
+/// Get the buffer at `buffer` as a mutable slice of `length` bytes, ex. a
+/// request buffer
+///
+/// # Safety
+///
+/// `buffer` must be valid for reads and writes of `length` bytes for `'a`, and
+/// not accessed through other references for `'a`, unless `length` is 0
+pub(super) unsafe fn buffer_slice_mut<'a>(buffer: *mut c_void, length: usize) -> &'a mut [u8] {
+    if length == 0 {
+        return &mut [];
+    }
+    // SAFETY: The caller guarantees that `buffer` is valid and unaliased for
+    // `length` bytes
+    unsafe { core::slice::from_raw_parts_mut(buffer.cast::<u8>(), length) }
+}
+
+// The flag is positive, but bindgen types it as the signed C enum type
+#[allow(clippy::cast_sign_loss)]
+const SEND_OPTION_TIMEOUT: ULONG =
+    _WDF_REQUEST_SEND_OPTIONS_FLAGS::WDF_REQUEST_SEND_OPTION_TIMEOUT as ULONG;
+
+/// Build the send options of a request with `flags`, that times out after
+/// `timeout` if it is not `None`
+pub(super) fn send_options(flags: ULONG, timeout: Option<Duration>) -> WDF_REQUEST_SEND_OPTIONS {
+    let options_size: ULONG;
+    // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+    #[allow(clippy::cast_possible_truncation)]
+    {
+        const OPTIONS_SIZE: usize = core::mem::size_of::<WDF_REQUEST_SEND_OPTIONS>();
+
+        // Manually assert there is not truncation since clippy doesn't work for
+        // compile-time constants
+        const { assert!(OPTIONS_SIZE <= ULONG::MAX as usize) }
+
+        options_size = OPTIONS_SIZE as ULONG;
+    }
+    WDF_REQUEST_SEND_OPTIONS {
+        Size: options_size,
+        Flags: flags | timeout.map_or(0, |_| SEND_OPTION_TIMEOUT),
+        Timeout: timeout.map_or(0, relative_due_time),
+    }
+}
@@ -0,0 +1,311 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::iter::FusedIterator;
+
+use wdk_sys::{
+    CM_PARTIAL_RESOURCE_DESCRIPTOR,
+    CM_RESOURCE_INTERRUPT_MESSAGE,
+    CM_RESOURCE_MEMORY_LARGE_40,
+    CM_RESOURCE_MEMORY_LARGE_48,
+    CM_RESOURCE_MEMORY_LARGE_64,
+    CmResourceTypeConnection,
+    CmResourceTypeDma,
+    CmResourceTypeInterrupt,
+    CmResourceTypeMemory,
+    CmResourceTypeMemoryLarge,
+    CmResourceTypePort,
+    KAFFINITY,
+    UCHAR,
+    ULONG,
+    USHORT,
+    WDFCMRESLIST,
+    call_unsafe_wdf_function_binding,
+};
+
+/// Hardware resource assigned to a device, as held by a [`ResourceList`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resource {
+    /// Range of memory-mapped registers, ex. a BAR of a PCI device
+    Memory {
+        /// Physical address of the start of the range
+        start: u64,
+        /// Length of the range, in bytes
+        length: u64,
+    },
+    /// Range of I/O ports
+    Port {
+        /// Address of the first port of the range
+        start: u64,
+        /// Length of the range, in bytes
+        length: ULONG,
+    },
+    /// Interrupt, which the framework connects to the
+    /// [`crate::wdf::Interrupt`] objects of the device
+    Interrupt {
+        /// Vector of the interrupt
+        vector: ULONG,
+        /// Processors the interrupt can be delivered to
+        affinity: KAFFINITY,
+        /// Whether the interrupt is message-signaled
+        message_signaled: bool,
+    },
+    /// Channel of a system DMA controller
+    Dma {
+        /// Number of the channel
+        channel: ULONG,
+        /// Number of the port of the channel
+        port: ULONG,
+    },
+    /// Connection to a resource of another device, ex. a GPIO pin or an
+    /// I2C or SPI controller, which is opened through the resource hub with
+    /// its ID, ex. with [`crate::wdf::SpbTarget::open()`] for SPB connections
+    Connection {
+        /// `CM_RESOURCE_CONNECTION_CLASS_*` value of the connection, ex.
+        /// `CM_RESOURCE_CONNECTION_CLASS_SERIAL` for SPB connections
+        class: UCHAR,
+        /// `CM_RESOURCE_CONNECTION_TYPE_*` value of the connection, ex.
+        /// `CM_RESOURCE_CONNECTION_TYPE_SERIAL_I2C`
+        connection_type: UCHAR,
+        /// ID of the connection at the resource hub
+        id: i64,
+    },
+    /// Resource of another type, ex. a bus number, identified by its
+    /// `CmResourceType` value
+    Other {
+        /// `CmResourceType` value of the resource
+        resource_type: UCHAR,
+    },
+}
+impl Resource {
+    /// Decode a resource descriptor
+    fn from_descriptor(descriptor: &CM_PARTIAL_RESOURCE_DESCRIPTOR) -> Self {
+        let resource_type = descriptor.Type;
+        match u32::from(resource_type) {
+            CmResourceTypeMemory | CmResourceTypeMemoryLarge => {
+                // SAFETY: The layout of memory descriptors, of every size, starts with the
+                // address and the length of the range
+                let memory = unsafe { descriptor.u.Memory };
+                // SAFETY: Every variant of `PHYSICAL_ADDRESS` covers its 64 bits, so
+                // `QuadPart` is always initialized
+                let start = unsafe { memory.Start.QuadPart };
+                Self::Memory {
+                    start: physical_address_bits(start),
+                    length: memory_length(resource_type, descriptor.Flags, memory.Length),
+                }
+            }
+            CmResourceTypePort => {
+                // SAFETY: The descriptor is of a port range
+                let port = unsafe { descriptor.u.Port };
+                // SAFETY: Every variant of `PHYSICAL_ADDRESS` covers its 64 bits, so
+                // `QuadPart` is always initialized
+                let start = unsafe { port.Start.QuadPart };
+                Self::Port {
+                    start: physical_address_bits(start),
+                    length: port.Length,
+                }
+            }
+            CmResourceTypeInterrupt => {
+                // SAFETY: The descriptor is of an interrupt. The vector and the affinity of
+                // message-signaled interrupts are at the same place as for line-based ones.
+                let interrupt = unsafe { descriptor.u.Interrupt };
+                Self::Interrupt {
+                    vector: interrupt.Vector,
+                    affinity: interrupt.Affinity,
+                    message_signaled: u32::from(descriptor.Flags) & CM_RESOURCE_INTERRUPT_MESSAGE
+                        != 0,
+                }
+            }
+            CmResourceTypeDma => {
+                // SAFETY: The descriptor is of a DMA channel
+                let dma = unsafe { descriptor.u.Dma };
+                Self::Dma {
+                    channel: dma.Channel,
+                    port: dma.Port,
+                }
+            }
+            CmResourceTypeConnection => {
+                // SAFETY: The descriptor is of a connection
+                let connection = unsafe { descriptor.u.Connection };
+                Self::Connection {
+                    class: connection.Class,
+                    connection_type: connection.Type,
+                    id: (i64::from(connection.IdHighPart) << 32) | i64::from(connection.IdLowPart),
+                }
+            }
+            _ => Self::Other { resource_type },
+        }
+    }
+}
+
+/// Reinterpret the bits of a physical address as unsigned
+const fn physical_address_bits(address: i64) -> u64 {
+    // The address is a bit pattern, which is reinterpreted as unsigned
+    #[allow(clippy::cast_sign_loss)]
+    let address = address as u64;
+    address
+}
+
+/// Decode the length of a memory range, like `RtlCmDecodeMemIoResource`.
+/// Large memory ranges store their length in units given by their `flags`.
+fn memory_length(resource_type: UCHAR, flags: USHORT, length: ULONG) -> u64 {
+    let length = u64::from(length);
+    if u32::from(resource_type) != CmResourceTypeMemoryLarge {
+        return length;
+    }
+
+    let flags = u32::from(flags);
+    if flags & CM_RESOURCE_MEMORY_LARGE_40 != 0 {
+        length << 8
+    } else if flags & CM_RESOURCE_MEMORY_LARGE_48 != 0 {
+        length << 16
+    } else if flags & CM_RESOURCE_MEMORY_LARGE_64 != 0 {
+        length << 32
+    } else {
+        length
+    }
+}
+
+/// WDF Resource List.
+///
+/// The hardware resources assigned to a device, as passed to
+/// [`crate::wdf::PrepareHardwareCallback::prepare_hardware()`]. The resource
+/// list is owned by the framework, and is only valid during the callback it is
+/// passed to.
+#[derive(Clone, Copy)]
+pub struct ResourceList {
+    wdf_cm_res_list: WDFCMRESLIST,
+}
+impl ResourceList {
+    /// Wrap a handle to a framework resource list object
+    ///
+    /// # Safety
+    ///
+    /// `resource_list` must be a valid handle to a framework resource list
+    /// object, that stays valid for as long as the returned [`ResourceList`] is
+    /// used.
+    #[must_use]
+    pub const unsafe fn from_raw(resource_list: WDFCMRESLIST) -> Self {
+        Self {
+            wdf_cm_res_list: resource_list,
+        }
+    }
+
+    /// Get the raw handle of the framework resource list object
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFCMRESLIST {
+        self.wdf_cm_res_list
+    }
+
+    /// Get the number of resources of the list
+    #[must_use]
+    pub fn len(&self) -> ULONG {
+        let count;
+        // SAFETY: `wdf_cm_res_list` is a private member of `ResourceList`, which the
+        // caller of `from_raw` guaranteed to be valid
+        unsafe {
+            count =
+                call_unsafe_wdf_function_binding!(WdfCmResourceListGetCount, self.wdf_cm_res_list);
+        }
+        count
+    }
+
+    /// Returns `true` if the list has no resources
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the resource at `index`, or `None` if `index` is out of bounds
+    #[must_use]
+    pub fn get(&self, index: ULONG) -> Option<Resource> {
+        let descriptor: *mut CM_PARTIAL_RESOURCE_DESCRIPTOR;
+        // SAFETY: `wdf_cm_res_list` is a private member of `ResourceList`, which the
+        // caller of `from_raw` guaranteed to be valid
+        unsafe {
+            descriptor = call_unsafe_wdf_function_binding!(
+                WdfCmResourceListGetDescriptor,
+                self.wdf_cm_res_list,
+                index,
+            );
+        }
+        // SAFETY: The framework returns a descriptor of the list, which is valid for
+        // as long as the list is, or null if `index` is out of bounds
+        let descriptor = unsafe { descriptor.as_ref() }?;
+        Some(Resource::from_descriptor(descriptor))
+    }
+
+    /// Get an iterator over the resources of the list
+    #[must_use]
+    pub fn iter(&self) -> Resources<'_> {
+        Resources {
+            resource_list: self,
+            index: 0,
+            len: self.len(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a ResourceList {
+    type IntoIter = Resources<'a>;
+    type Item = Resource;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the resources of a [`ResourceList`]
+pub struct Resources<'a> {
+    resource_list: &'a ResourceList,
+    index: ULONG,
+    len: ULONG,
+}
+
+impl Iterator for Resources<'_> {
+    type Item = Resource;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.len {
+            return None;
+        }
+        let resource = self.resource_list.get(self.index);
+        self.index += 1;
+        resource
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.len - self.index) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Resources<'_> {}
+
+impl FusedIterator for Resources<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_length_decodes_large_ranges() {
+        // Truncation is fine for these test values
+        #[allow(clippy::cast_possible_truncation)]
+        let (memory, memory_large) = (
+            CmResourceTypeMemory as UCHAR,
+            CmResourceTypeMemoryLarge as UCHAR,
+        );
+        #[allow(clippy::cast_possible_truncation)]
+        let (large_40, large_48, large_64) = (
+            CM_RESOURCE_MEMORY_LARGE_40 as USHORT,
+            CM_RESOURCE_MEMORY_LARGE_48 as USHORT,
+            CM_RESOURCE_MEMORY_LARGE_64 as USHORT,
+        );
+
+        assert_eq!(memory_length(memory, large_40, 0x1000), 0x1000);
+        assert_eq!(memory_length(memory_large, large_40, 0x10), 0x1000);
+        assert_eq!(memory_length(memory_large, large_48, 0x10), 0x10_0000);
+        assert_eq!(memory_length(memory_large, large_64, 0x10), 0x10_0000_0000);
+    }
+}
@@ -0,0 +1,268 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{ffi::c_void, time::Duration};
+
+use wdk_sys::{
+    _SPB_TRANSFER_BUFFER_FORMAT,
+    _SPB_TRANSFER_DIRECTION,
+    GENERIC_READ,
+    GENERIC_WRITE,
+    NTSTATUS,
+    SPB_TRANSFER_BUFFER_LIST_ENTRY,
+    SPB_TRANSFER_DIRECTION,
+    SPB_TRANSFER_LIST,
+    SPB_TRANSFER_LIST_ENTRY,
+    STATUS_INVALID_BUFFER_SIZE,
+    ULONG,
+    ULONG_PTR,
+    call_unsafe_wdf_function_binding,
+    spb::FILE_DEVICE_SPB,
+};
+
+use crate::{
+    ControlCode,
+    RequiredAccess,
+    TransferMethod,
+    nt_success,
+    string::UnicodeStr,
+    wdf::{Device, IoTarget, memory::buffer_descriptor, request::send_options},
+};
+
+/// `IOCTL_SPB_EXECUTE_SEQUENCE` of `spb.h`
+const IOCTL_SPB_EXECUTE_SEQUENCE: ControlCode = ControlCode::new(
+    FILE_DEVICE_SPB,
+    0x1,
+    TransferMethod::Neither,
+    RequiredAccess::Any,
+);
+
+/// Prefix of the path of a connection at the resource hub, which is followed
+/// by the ID of the connection as 16 hexadecimal digits, like
+/// `RESOURCE_HUB_CREATE_PATH_FROM_ID`
+const RESOURCE_HUB_PATH_PREFIX: &[u8] = br"\Device\RESOURCE_HUB\";
+/// Number of hexadecimal digits of a connection ID in a resource hub path
+const RESOURCE_HUB_ID_DIGITS: usize = 16;
+/// Length of the path of a connection at the resource hub, in UTF-16 units
+const RESOURCE_HUB_PATH_LENGTH: usize = RESOURCE_HUB_PATH_PREFIX.len() + RESOURCE_HUB_ID_DIGITS;
+
+/// Peripheral on a simple peripheral bus (SPB), ex. an I2C or SPI device.
+///
+/// Peripheral drivers get the connection ID of their peripheral from the
+/// [`crate::wdf::Resource::Connection`] resource of the device, in
+/// `EvtDevicePrepareHardware`, and open the SPB controller through the
+/// resource hub with [`SpbTarget::open()`]. Reads and writes are then
+/// addressed to the peripheral by the controller. The target is deleted along
+/// with the device it was opened for.
+pub struct SpbTarget {
+    io_target: IoTarget,
+}
+impl SpbTarget {
+    /// Open the SPB controller of the peripheral with the connection ID
+    /// `connection_id`, as a remote I/O target of `device`. Must be called at
+    /// `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct the I/O
+    /// target, or if the controller could not be opened. The error variant
+    /// will contain a [`NTSTATUS`] of the failure. Full error documentation is
+    /// available in the [WdfIoTargetOpen Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfiotarget/nf-wdfiotarget-wdfiotargetopen#return-value)
+    pub fn open(device: &Device, connection_id: i64) -> Result<Self, NTSTATUS> {
+        let path = resource_hub_path(connection_id);
+        let io_target = IoTarget::try_new(device)?;
+        io_target.open_by_name(UnicodeStr::from_slice(&path)?, GENERIC_READ | GENERIC_WRITE)?;
+        Ok(Self { io_target })
+    }
+
+    /// Get the I/O target of the SPB controller, ex. to send it requests
+    /// asynchronously
+    #[must_use]
+    pub const fn io_target(&self) -> &IoTarget {
+        &self.io_target
+    }
+
+    /// Close the SPB controller, after the requests sent to it are completed.
+    /// This is typically called from `EvtDeviceReleaseHardware`. Must be
+    /// called at `IRQL` = `PASSIVE_LEVEL`.
+    pub fn close(&self) {
+        self.io_target.close();
+    }
+
+    /// Read from the peripheral into `buffer`, and wait for at most
+    /// `timeout`, or indefinitely if `timeout` is `None`, for the read to
+    /// complete. On success, returns the number of bytes read. Must be called
+    /// at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the read failed, ex.
+    /// `STATUS_IO_TIMEOUT` if it did not complete before `timeout` elapsed.
+    pub fn read(&self, buffer: &mut [u8], timeout: Option<Duration>) -> Result<usize, NTSTATUS> {
+        self.io_target
+            .send_read_synchronously(buffer, None, timeout)
+    }
+
+    /// Write `data` to the peripheral, and wait for at most `timeout`, or
+    /// indefinitely if `timeout` is `None`, for the write to complete. On
+    /// success, returns the number of bytes written. Must be called at `IRQL`
+    /// = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the write failed, ex.
+    /// `STATUS_IO_TIMEOUT` if it did not complete before `timeout` elapsed.
+    pub fn write(&self, data: &[u8], timeout: Option<Duration>) -> Result<usize, NTSTATUS> {
+        self.io_target.send_write_synchronously(data, None, timeout)
+    }
+
+    /// Write `data` to the peripheral and then read from it into `buffer`, as
+    /// a single sequence that no other transfer on the bus interrupts, ex. to
+    /// write the address of a register and read its value with a repeated
+    /// start condition on I2C. Waits for at most `timeout`, or indefinitely if
+    /// `timeout` is `None`, for the sequence to complete. On success, returns
+    /// the number of bytes transferred in both directions. Must be called at
+    /// `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the sequence failed, ex.
+    /// `STATUS_IO_TIMEOUT` if it did not complete before `timeout` elapsed, or
+    /// `STATUS_INVALID_BUFFER_SIZE` if a buffer is longer than a transfer can
+    /// be.
+    pub fn write_read_sequence(
+        &self,
+        data: &[u8],
+        buffer: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<usize, NTSTATUS> {
+        let transfer_list = TransferList {
+            size: transfer_list_size(),
+            reserved: 0,
+            transfer_count: 2,
+            transfers: [
+                transfer_entry(
+                    _SPB_TRANSFER_DIRECTION::SpbTransferDirectionToDevice,
+                    data.as_ptr().cast_mut().cast(),
+                    data.len(),
+                )?,
+                transfer_entry(
+                    _SPB_TRANSFER_DIRECTION::SpbTransferDirectionFromDevice,
+                    buffer.as_mut_ptr().cast(),
+                    buffer.len(),
+                )?,
+            ],
+        };
+
+        let mut input = buffer_descriptor(
+            (&raw const transfer_list).cast_mut().cast(),
+            core::mem::size_of_val(&transfer_list),
+        )?;
+        let mut options = send_options(0, timeout);
+        let mut bytes_transferred: ULONG_PTR = 0;
+
+        let nt_status;
+        // SAFETY: The I/O target of the controller is valid for as long as the
+        // device it was opened for. The descriptor describes the transfer list, which
+        // points to `data` and `buffer`. They are all borrowed until the sequence
+        // completes, as it is sent synchronously, and every pointer outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfIoTargetSendIoctlSynchronously,
+                self.io_target.as_raw(),
+                core::ptr::null_mut(),
+                IOCTL_SPB_EXECUTE_SEQUENCE.as_raw(),
+                &raw mut input,
+                core::ptr::null_mut(),
+                &raw mut options,
+                &raw mut bytes_transferred,
+            );
+        }
+        nt_success(nt_status)
+            .then(|| usize::try_from(bytes_transferred).unwrap_or(usize::MAX))
+            .ok_or(nt_status)
+    }
+}
+
+/// `SPB_TRANSFER_LIST` with `N` transfers, like the ones built with
+/// `SPB_TRANSFER_LIST_INIT` over a larger buffer in C
+#[repr(C)]
+struct TransferList<const N: usize> {
+    size: ULONG,
+    reserved: ULONG,
+    transfer_count: ULONG,
+    transfers: [SPB_TRANSFER_LIST_ENTRY; N],
+}
+
+/// Get the size of `SPB_TRANSFER_LIST`, which the controller expects in the
+/// header of every transfer list
+const fn transfer_list_size() -> ULONG {
+    let transfer_list_size: ULONG;
+    // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+    #[allow(clippy::cast_possible_truncation)]
+    {
+        const TRANSFER_LIST_SIZE: usize = core::mem::size_of::<SPB_TRANSFER_LIST>();
+
+        // Manually assert there is not truncation since clippy doesn't work for
+        // compile-time constants
+        const { assert!(TRANSFER_LIST_SIZE <= ULONG::MAX as usize) }
+
+        transfer_list_size = TRANSFER_LIST_SIZE as ULONG;
+    }
+    transfer_list_size
+}
+
+/// Describe the transfer of the buffer of `length` bytes at `buffer` in
+/// `direction`, like `SPB_TRANSFER_LIST_ENTRY_INIT_SIMPLE`
+fn transfer_entry(
+    direction: SPB_TRANSFER_DIRECTION,
+    buffer: *mut c_void,
+    length: usize,
+) -> Result<SPB_TRANSFER_LIST_ENTRY, NTSTATUS> {
+    let length = ULONG::try_from(length).map_err(|_| STATUS_INVALID_BUFFER_SIZE)?;
+    let mut entry = SPB_TRANSFER_LIST_ENTRY {
+        Direction: direction,
+        DelayInUs: 0,
+        ..SPB_TRANSFER_LIST_ENTRY::default()
+    };
+    entry.Buffer.Format = _SPB_TRANSFER_BUFFER_FORMAT::SpbTransferBufferFormatSimple;
+    entry.Buffer.__bindgen_anon_1.Simple = SPB_TRANSFER_BUFFER_LIST_ENTRY {
+        Buffer: buffer,
+        BufferCb: length,
+    };
+    Ok(entry)
+}
+
+/// Build the path of the connection with the ID `connection_id` at the
+/// resource hub
+fn resource_hub_path(connection_id: i64) -> [u16; RESOURCE_HUB_PATH_LENGTH] {
+    const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+    let mut path = [0; RESOURCE_HUB_PATH_LENGTH];
+    for (unit, byte) in path.iter_mut().zip(RESOURCE_HUB_PATH_PREFIX) {
+        *unit = u16::from(*byte);
+    }
+    // The ID is a bit pattern, which is formatted as unsigned
+    #[allow(clippy::cast_sign_loss)]
+    let mut id = connection_id as u64;
+    for unit in path[RESOURCE_HUB_PATH_PREFIX.len()..].iter_mut().rev() {
+        // The index is masked to a hexadecimal digit
+        #[allow(clippy::cast_possible_truncation)]
+        let digit = (id & 0xF) as usize;
+        *unit = u16::from(HEX_DIGITS[digit]);
+        id >>= 4;
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resource_hub_path_has_16_hex_digits() {
+        let path = resource_hub_path(0x1_0000_00AB);
+        let expected = r"\Device\RESOURCE_HUB\00000001000000ab";
+        assert!(path.iter().copied().eq(expected.encode_utf16()));
+    }
+}
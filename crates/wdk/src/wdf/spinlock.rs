@@ -1,9 +1,29 @@
 // Copyright (c) Microsoft Corporation
 // License: MIT OR Apache-2.0
 
-use wdk_sys::{NTSTATUS, WDF_OBJECT_ATTRIBUTES, WDFSPINLOCK, call_unsafe_wdf_function_binding};
+#[cfg(all(debug_assertions, driver_model__driver_type = "KMDF"))]
+use core::sync::atomic::{AtomicUsize, Ordering};
 
-use crate::nt_success;
+#[cfg(all(debug_assertions, driver_model__driver_type = "KMDF"))]
+use wdk_sys::{
+    DISPATCH_LEVEL,
+    ntddk::{KeGetCurrentIrql, KeGetCurrentProcessorNumberEx},
+};
+use wdk_sys::{
+    NTSTATUS,
+    ULONG,
+    WDF_OBJECT_ATTRIBUTES,
+    WDFOBJECT,
+    WDFSPINLOCK,
+    call_unsafe_wdf_function_binding,
+};
+
+use crate::{nt_success, wdf::context::inherited_object_attributes};
+
+/// Bug check code of a recursive acquisition of a [`SpinLock`], detected in
+/// debug builds of KMDF drivers. It reads as `LOCK` when displayed in hex by
+/// the debugger.
+pub const SPIN_LOCK_RECURSION_BUGCHECK_CODE: ULONG = u32::from_be_bytes(*b"LOCK");
 
 /// WDF Spin Lock.
 ///
@@ -16,10 +36,32 @@
 /// the context space is writable and if more than one of the driver's event
 /// callback functions access the space. Before a driver can use a framework
 /// spin lock it must call [`SpinLock::try_new()`] to create a [`SpinLock`]. The
-/// driver can then call [`SpinLock::acquire`] to acquire the lock and
-/// [`SpinLock::release()`] to release it.
+/// driver can then call [`SpinLock::lock()`] to acquire the lock, which is
+/// released when the returned [`SpinLockGuard`] is dropped, or call
+/// [`SpinLock::acquire`] and [`SpinLock::release()`] explicitly.
+///
+/// The framework deletes the spin lock object with its parent object, which is
+/// the driver object unless another parent is set in the attributes passed to
+/// [`SpinLock::try_new()`]. Locks that belong to a device or a queue are
+/// created with [`SpinLock::try_new_with_parent()`], so that they are deleted
+/// when the device is removed rather than when the driver is unloaded.
+///
+/// Acquiring a spin lock that is already held on the same processor, ex. when
+/// a callback re-enters a code path that holds the lock, deadlocks. In debug
+/// builds of KMDF drivers, the processor holding the lock is tracked, and such
+/// recursive acquisitions bug check with [`SPIN_LOCK_RECURSION_BUGCHECK_CODE`]
+/// instead. Release builds do not track the owner, so that acquiring the lock
+/// costs no more than `WdfSpinLockAcquire`.
+///
+/// There is no `try_lock`, as the framework has no way to try to acquire a
+/// spin lock without spinning. Use a [`super::WaitLock`], which can be tried,
+/// when the caller must be able to give up.
 pub struct SpinLock {
     wdf_spin_lock: WDFSPINLOCK,
+    /// Index of the processor holding the lock plus one, or zero if the lock
+    /// is not held
+    #[cfg(all(debug_assertions, driver_model__driver_type = "KMDF"))]
+    owner: AtomicUsize,
 }
 impl SpinLock {
     /// Try to construct a WDF Spin Lock object
@@ -32,6 +74,8 @@ impl SpinLock {
     pub fn try_new(attributes: &mut WDF_OBJECT_ATTRIBUTES) -> Result<Self, NTSTATUS> {
         let mut spin_lock = Self {
             wdf_spin_lock: core::ptr::null_mut(),
+            #[cfg(all(debug_assertions, driver_model__driver_type = "KMDF"))]
+            owner: AtomicUsize::new(0),
         };
 
         let nt_status;
@@ -60,21 +104,140 @@ pub fn create(attributes: &mut WDF_OBJECT_ATTRIBUTES) -> Result<Self, NTSTATUS>
         Self::try_new(attributes)
     }
 
+    /// Try to construct a WDF Spin Lock object that is a child of `parent`, ex.
+    /// of the device or queue whose context holds the lock, so that the
+    /// framework deletes it with `parent`. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a spinlock.
+    /// The error variant will contain a [`NTSTATUS`] of the failure. Full error
+    /// documentation is available in the [WDFSpinLock Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfsync/nf-wdfsync-wdfspinlockcreate#return-value)
+    ///
+    /// # Safety
+    ///
+    /// `parent` must be a valid handle to a framework object, and the returned
+    /// [`SpinLock`] must not be used after `parent` is deleted.
+    pub unsafe fn try_new_with_parent(parent: WDFOBJECT) -> Result<Self, NTSTATUS> {
+        Self::try_new(&mut WDF_OBJECT_ATTRIBUTES {
+            ParentObject: parent,
+            ..inherited_object_attributes()
+        })
+    }
+
+    /// Try to construct a WDF Spin Lock object that is a child of `parent`.
+    /// This is an alias for [`SpinLock::try_new_with_parent()`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a spinlock.
+    /// The error variant will contain a [`NTSTATUS`] of the failure. Full error
+    /// documentation is available in the [WDFSpinLock Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfsync/nf-wdfsync-wdfspinlockcreate#return-value)
+    ///
+    /// # Safety
+    ///
+    /// `parent` must be a valid handle to a framework object, and the returned
+    /// [`SpinLock`] must not be used after `parent` is deleted.
+    pub unsafe fn create_with_parent(parent: WDFOBJECT) -> Result<Self, NTSTATUS> {
+        // SAFETY: The caller upholds the requirements of `try_new_with_parent`
+        unsafe { Self::try_new_with_parent(parent) }
+    }
+
     /// Acquire the spinlock
     pub fn acquire(&self) {
+        #[cfg(all(debug_assertions, driver_model__driver_type = "KMDF"))]
+        if self.is_held_by_current_processor() {
+            crate::fatal_error!(
+                SPIN_LOCK_RECURSION_BUGCHECK_CODE,
+                "spin lock {:p} acquired recursively on the processor holding it",
+                self.wdf_spin_lock
+            );
+        }
         // SAFETY: `wdf_spin_lock` is a private member of `SpinLock`, originally created
         // by WDF, and this module guarantees that it is always in a valid state.
         unsafe {
             call_unsafe_wdf_function_binding!(WdfSpinLockAcquire, self.wdf_spin_lock);
         }
+        #[cfg(all(debug_assertions, driver_model__driver_type = "KMDF"))]
+        self.owner.store(current_owner(), Ordering::Relaxed);
     }
 
     /// Release the spinlock
     pub fn release(&self) {
+        #[cfg(all(debug_assertions, driver_model__driver_type = "KMDF"))]
+        self.owner.store(0, Ordering::Relaxed);
         // SAFETY: `wdf_spin_lock` is a private member of `SpinLock`, originally created
         // by WDF, and this module guarantees that it is always in a valid state.
         unsafe {
             call_unsafe_wdf_function_binding!(WdfSpinLockRelease, self.wdf_spin_lock);
         }
     }
+
+    /// Acquire the spinlock. The lock is released when the returned
+    /// [`SpinLockGuard`] is dropped.
+    pub fn lock(&self) -> SpinLockGuard<'_> {
+        self.acquire();
+        SpinLockGuard { spin_lock: self }
+    }
+
+    /// Acquire the spinlock, unless it is held by a code path that re-entered
+    /// the caller on the current processor. Returns `None` in that case
+    /// instead of deadlocking.
+    ///
+    /// This is not a try-lock: if another processor holds the lock, this
+    /// spins until that processor releases it.
+    ///
+    /// Only available in debug builds, as it relies on the owner tracking that
+    /// release builds leave out. It lets debug code, ex. a consistency check
+    /// that may run both with and without the lock held, inspect the protected
+    /// data. Release code must not re-enter a code path that holds the lock.
+    #[cfg(all(debug_assertions, driver_model__driver_type = "KMDF"))]
+    pub fn lock_unless_reentered(&self) -> Option<SpinLockGuard<'_>> {
+        (!self.is_held_by_current_processor()).then(|| self.lock())
+    }
+
+    /// Returns `true` if the lock is held by the current processor, i.e. by a
+    /// code path that the caller re-entered.
+    ///
+    /// A caller below `DISPATCH_LEVEL` cannot hold a spin lock, and may move
+    /// to another processor at any time, so it is never the owner. At
+    /// `DISPATCH_LEVEL` or above, the caller stays on the current processor,
+    /// and only that processor stores its own index as the owner, so the
+    /// comparison is exact.
+    #[cfg(all(debug_assertions, driver_model__driver_type = "KMDF"))]
+    fn is_held_by_current_processor(&self) -> bool {
+        let irql;
+        // SAFETY: `KeGetCurrentIrql` can be called at any IRQL, and has no other
+        // requirements
+        unsafe {
+            irql = KeGetCurrentIrql();
+        }
+        u32::from(irql) >= DISPATCH_LEVEL && self.owner.load(Ordering::Relaxed) == current_owner()
+    }
+}
+
+/// RAII guard of an acquired [`SpinLock`]. The lock is released when the guard
+/// is dropped.
+#[must_use = "the spin lock is released as soon as the guard is dropped"]
+pub struct SpinLockGuard<'a> {
+    spin_lock: &'a SpinLock,
+}
+
+impl Drop for SpinLockGuard<'_> {
+    fn drop(&mut self) {
+        self.spin_lock.release();
+    }
+}
+
+/// Returns the owner value of the current processor, i.e. its index plus one
+#[cfg(all(debug_assertions, driver_model__driver_type = "KMDF"))]
+fn current_owner() -> usize {
+    let processor_index;
+    // SAFETY: Getting the index of the current processor is allowed at any IRQL,
+    // and the processor number is optional
+    unsafe {
+        processor_index = KeGetCurrentProcessorNumberEx(core::ptr::null_mut());
+    }
+    usize::try_from(processor_index).map_or(usize::MAX, |index| index + 1)
 }
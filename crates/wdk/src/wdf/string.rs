@@ -0,0 +1,134 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::fmt;
+
+use wdk_sys::{NTSTATUS, UNICODE_STRING, WDFSTRING, call_unsafe_wdf_function_binding};
+
+#[cfg(feature = "alloc")]
+use crate::string::UnicodeString;
+use crate::{nt_success, string::UnicodeStr};
+
+/// WDF String.
+///
+/// A framework string object, which WDF APIs that return strings of unknown
+/// length fill, ex. `WdfDeviceRetrieveDeviceInterfaceString`. The string is
+/// read with [`WdfString::as_unicode_str()`], and deleted when the
+/// [`WdfString`] is dropped.
+pub struct WdfString {
+    wdf_string: WDFSTRING,
+}
+impl WdfString {
+    /// Try to construct an empty WDF String object. Must be called at `IRQL` =
+    /// `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a string.
+    /// The error variant will contain a [`NTSTATUS`] of the failure. Full error
+    /// documentation is available in the [WdfStringCreate Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfstring/nf-wdfstring-wdfstringcreate#return-value)
+    pub fn try_new() -> Result<Self, NTSTATUS> {
+        Self::create(core::ptr::null())
+    }
+
+    /// Try to construct a WDF String object that holds a copy of `string`.
+    /// Must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a string.
+    /// The error variant will contain a [`NTSTATUS`] of the failure. Full error
+    /// documentation is available in the [WdfStringCreate Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfstring/nf-wdfstring-wdfstringcreate#return-value)
+    pub fn try_from_unicode_str(string: UnicodeStr<'_>) -> Result<Self, NTSTATUS> {
+        let string = string.as_raw();
+        Self::create(&raw const string)
+    }
+
+    /// Take ownership of a handle to a framework string object, which is
+    /// deleted when the returned [`WdfString`] is dropped
+    ///
+    /// # Safety
+    ///
+    /// `string` must be a valid handle to a framework string object, that is
+    /// not deleted by anything else.
+    #[must_use]
+    pub const unsafe fn from_raw(string: WDFSTRING) -> Self {
+        Self { wdf_string: string }
+    }
+
+    /// Get the raw handle of the framework string object, ex. to pass to WDF
+    /// APIs that fill it
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFSTRING {
+        self.wdf_string
+    }
+
+    /// Borrow the string held by the string object. Must be called at `IRQL`
+    /// <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn as_unicode_str(&self) -> UnicodeStr<'_> {
+        let mut unicode_string = UNICODE_STRING::default();
+        // SAFETY: `wdf_string` is a private member of `WdfString`, which is valid
+        // until it is deleted when `self` is dropped
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfStringGetUnicodeString,
+                self.wdf_string,
+                &raw mut unicode_string,
+            );
+        }
+        // SAFETY: The framework points `unicode_string` to the buffer of the string
+        // object, which stays valid while `self` is borrowed
+        unsafe { UnicodeStr::from_raw(&raw const unicode_string) }
+    }
+
+    /// Copy the string held by the string object into a [`UnicodeString`].
+    /// Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_INSUFFICIENT_RESOURCES` if the copy
+    /// could not be allocated.
+    #[cfg(feature = "alloc")]
+    pub fn to_unicode_string(&self) -> Result<UnicodeString, NTSTATUS> {
+        UnicodeString::try_from_unicode_str(self.as_unicode_str())
+    }
+
+    /// Create a string object holding a copy of `string`, or an empty one if
+    /// `string` is null
+    fn create(string: *const UNICODE_STRING) -> Result<Self, NTSTATUS> {
+        let mut wdf_string: WDFSTRING = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `string` is null or points to a valid `UNICODE_STRING`, which the
+        // framework copies. The string is created without attributes, so it is
+        // parented to the driver object.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfStringCreate,
+                string,
+                core::ptr::null_mut(),
+                &raw mut wdf_string,
+            );
+        }
+        nt_success(nt_status)
+            .then_some(Self { wdf_string })
+            .ok_or(nt_status)
+    }
+}
+
+impl Drop for WdfString {
+    fn drop(&mut self) {
+        // SAFETY: `wdf_string` is a private member of `WdfString`, which is valid and
+        // deleted only here
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfObjectDelete, self.wdf_string.cast());
+        }
+    }
+}
+
+impl fmt::Display for WdfString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.as_unicode_str(), f)
+    }
+}
@@ -0,0 +1,96 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use wdk_sys::{
+    _WDF_EXECUTION_LEVEL,
+    _WDF_SYNCHRONIZATION_SCOPE,
+    WDF_EXECUTION_LEVEL,
+    WDF_OBJECT_ATTRIBUTES,
+    WDF_SYNCHRONIZATION_SCOPE,
+};
+
+/// Scope in which the framework serializes the callbacks of an object, so that
+/// they do not run concurrently and the driver does not need its own locks to
+/// protect the state they share.
+///
+/// Set on the attributes an object is created with, using
+/// [`SynchronizationScope::apply()`]:
+///
+/// * On a device, [`SynchronizationScope::Device`] serializes the callbacks of
+///   all its queues and file objects with each other.
+/// * On a device or a queue, [`SynchronizationScope::Queue`] serializes the
+///   callbacks of each queue separately.
+///
+/// Timers configured with [`crate::wdf::Timer::set_automatic_serialization()`]
+/// join the serialization of their parent queue or device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SynchronizationScope {
+    /// Use the synchronization scope of the parent object. This is the default.
+    InheritFromParent,
+    /// Serialize the callbacks of the queues and file objects of the device
+    Device,
+    /// Serialize the callbacks of each queue separately
+    Queue,
+    /// Do not serialize callbacks
+    None,
+}
+
+impl From<SynchronizationScope> for WDF_SYNCHRONIZATION_SCOPE {
+    fn from(scope: SynchronizationScope) -> Self {
+        match scope {
+            SynchronizationScope::InheritFromParent => {
+                _WDF_SYNCHRONIZATION_SCOPE::WdfSynchronizationScopeInheritFromParent
+            }
+            SynchronizationScope::Device => {
+                _WDF_SYNCHRONIZATION_SCOPE::WdfSynchronizationScopeDevice
+            }
+            SynchronizationScope::Queue => _WDF_SYNCHRONIZATION_SCOPE::WdfSynchronizationScopeQueue,
+            SynchronizationScope::None => _WDF_SYNCHRONIZATION_SCOPE::WdfSynchronizationScopeNone,
+        }
+    }
+}
+
+impl SynchronizationScope {
+    /// Set the synchronization scope in `attributes`, which the object is then
+    /// created with
+    pub fn apply(self, attributes: &mut WDF_OBJECT_ATTRIBUTES) {
+        attributes.SynchronizationScope = self.into();
+    }
+}
+
+/// Maximum `IRQL` at which the framework invokes the callbacks of an object.
+///
+/// Set on the attributes an object is created with, using
+/// [`ExecutionLevel::apply()`]. [`ExecutionLevel::Passive`] lets callbacks
+/// call functions that must run at `PASSIVE_LEVEL`, ex. to access paged
+/// memory or wait, and makes the framework serialize them with a wait lock
+/// instead of a spin lock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecutionLevel {
+    /// Use the execution level of the parent object. This is the default.
+    InheritFromParent,
+    /// Invoke callbacks at `IRQL` = `PASSIVE_LEVEL`
+    Passive,
+    /// Invoke callbacks at `IRQL` <= `DISPATCH_LEVEL`
+    Dispatch,
+}
+
+impl From<ExecutionLevel> for WDF_EXECUTION_LEVEL {
+    fn from(level: ExecutionLevel) -> Self {
+        match level {
+            ExecutionLevel::InheritFromParent => {
+                _WDF_EXECUTION_LEVEL::WdfExecutionLevelInheritFromParent
+            }
+            ExecutionLevel::Passive => _WDF_EXECUTION_LEVEL::WdfExecutionLevelPassive,
+            ExecutionLevel::Dispatch => _WDF_EXECUTION_LEVEL::WdfExecutionLevelDispatch,
+        }
+    }
+}
+
+impl ExecutionLevel {
+    /// Set the execution level in `attributes`, which the object is then
+    /// created with
+    pub fn apply(self, attributes: &mut WDF_OBJECT_ATTRIBUTES) {
+        attributes.ExecutionLevel = self.into();
+    }
+}
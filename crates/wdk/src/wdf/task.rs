@@ -0,0 +1,796 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Tasks that drive futures to completion on framework work items, so that
+//! request handlers can be written as `async` blocks.
+
+use core::{
+    cell::UnsafeCell,
+    ffi::c_void,
+    future::Future,
+    mem::{ManuallyDrop, MaybeUninit},
+    pin::Pin,
+    ptr::NonNull,
+    sync::atomic::{AtomicBool, AtomicPtr, AtomicU8, AtomicUsize, Ordering},
+    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+};
+
+use wdk_sys::{
+    NTSTATUS,
+    STATUS_CANCELLED,
+    STATUS_INSUFFICIENT_RESOURCES,
+    STATUS_SUCCESS,
+    ULONG,
+    WDF_WORKITEM_CONFIG,
+    WDFOBJECT,
+    WDFWORKITEM,
+    call_unsafe_wdf_function_binding,
+};
+
+use crate::{
+    nt_success,
+    wdf::{
+        Device,
+        RefCountedHandle,
+        Request,
+        RequestCancelCallback,
+        context::{
+            allocate_context,
+            declare_context_type,
+            dereference_object,
+            get_context,
+            object_attributes,
+            reference_object,
+        },
+    },
+};
+
+/// Largest alignment of the futures a task can run. The framework aligns the
+/// context space of its objects to at least this.
+pub const TASK_MAX_ALIGN: usize = 8;
+
+/// States of a task
+const TASK_IDLE: u8 = 0;
+const TASK_SCHEDULED: u8 = 1;
+const TASK_RUNNING: u8 = 2;
+const TASK_NOTIFIED: u8 = 3;
+const TASK_DONE: u8 = 4;
+
+/// Spawn a task that runs `future` to completion on framework work items of
+/// `device`. Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+///
+/// The future is first polled from a system worker thread, at `IRQL` =
+/// `PASSIVE_LEVEL`, and polled again from one whenever its waker is woken, ex.
+/// by a [`Signal`] notified from a DPC. It is polled by a single thread at a
+/// time. The future is dropped once it completes, or when the device is
+/// removed if it is still pending then.
+///
+/// # Errors
+///
+/// This function will return an error if the framework fails to create the
+/// work item of the task. `future` is dropped in that case.
+pub fn spawn<F>(device: &Device, future: F) -> Result<(), NTSTATUS>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let wdf_work_item = create_task(device, None, async move {
+        future.await;
+        Ok(0)
+    })?;
+    // SAFETY: The task was just created, and is only deleted once it completes
+    unsafe {
+        schedule(wdf_work_item);
+    }
+    Ok(())
+}
+
+/// Spawn a task that processes `request` by running `future` to completion,
+/// and completes `request` with its output.
+///
+/// The task runs on framework work items of the device of the queue of
+/// `request`. Must be called at `IRQL` <= `DISPATCH_LEVEL`, typically from a
+/// request handler of the queue.
+///
+/// When the future outputs `Ok(information)`, the request is completed with
+/// `STATUS_SUCCESS` and `information`, ex. the number of bytes written to its
+/// output buffer. When it outputs `Err(status)`, the request is completed with
+/// `status`. The request is cancelable while the future runs: if it is
+/// cancelled, or if the device is removed before the future completes, the
+/// future is dropped without being polled again, and the request is completed
+/// with `STATUS_CANCELLED`. The task owns `request` until it completes it, so
+/// the future must take what it needs from the request, ex. the contents of
+/// its input buffer, before it is spawned.
+///
+/// The future is polled like the futures of [`spawn()`].
+///
+/// # Errors
+///
+/// This function will return an error if the task could not be spawned.
+/// `future` is dropped and the request is not completed in that case, so the
+/// driver still owns the request, which is returned along with the error, and
+/// must complete it:
+/// * `STATUS_CANCELLED` if the request was already cancelled
+/// * `STATUS_INSUFFICIENT_RESOURCES` if the framework fails to create the work
+///   item of the task
+pub fn spawn_for_request<F>(request: Request, future: F) -> Result<(), (Request, NTSTATUS)>
+where
+    F: Future<Output = Result<usize, NTSTATUS>> + Send + 'static,
+{
+    let device = request.get_io_queue().get_device();
+    let wdf_work_item = match create_task(&device, Some(&request), future) {
+        Ok(wdf_work_item) => wdf_work_item,
+        Err(nt_status) => return Err((request, nt_status)),
+    };
+
+    // SAFETY: The request is owned by the driver and not completed yet.
+    // `TASK_REQUEST_TYPE_INFO` describes `TaskRequest`, which is valid when
+    // all-zero.
+    let task_request = unsafe {
+        allocate_context::<TaskRequest>(request.as_raw().cast(), &TASK_REQUEST_TYPE_INFO)
+    };
+    let result = task_request.and_then(|task_request| {
+        task_request
+            .work_item
+            .store(wdf_work_item.cast(), Ordering::Release);
+        // SAFETY: `request` is not used anymore once it is marked. The task rebuilds
+        // it from its header, and only uses it after unmarking it or after the
+        // cancel callback scheduled it.
+        unsafe { request.mark_cancelable::<CancelTask>() }
+    });
+    if let Err(nt_status) = result {
+        // SAFETY: The task was never scheduled, so its work item is only used here
+        let header =
+            unsafe { get_context::<TaskHeader>(wdf_work_item.cast(), &TASK_HEADER_TYPE_INFO) };
+        if let Some(header) = header {
+            // The request is returned to the caller, so the cleanup callback of the
+            // work item must not complete it
+            header
+                .request
+                .store(core::ptr::null_mut(), Ordering::Relaxed);
+        }
+        // SAFETY: The task was never scheduled, so its work item is only used here.
+        // Its cleanup callback drops the future.
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfObjectDelete, wdf_work_item.cast());
+        }
+        return Err((request, nt_status));
+    }
+    // SAFETY: The task is only deleted once it completed the request
+    unsafe {
+        schedule(wdf_work_item);
+    }
+    Ok(())
+}
+
+/// Event that a task can wait for, ex. the completion of an operation by the
+/// hardware, notified from a DPC or a timer callback.
+///
+/// [`Signal::wait()`] completes once the signal is notified, and consumes the
+/// notification, so that the next wait waits for the next notification.
+/// Notifications are not counted: notifying the signal several times before
+/// it is waited for completes a single wait. A signal is meant to be waited
+/// for by a single task at a time.
+///
+/// Tasks are `'static`, so a signal is typically shared with them through an
+/// [`Arc`](crate::wdf::Arc).
+#[derive(Default)]
+pub struct Signal {
+    notified: AtomicBool,
+    waker: AtomicWaker,
+}
+impl Signal {
+    /// Construct a signal that is not notified
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            notified: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    /// Notify the signal, waking the task waiting for it, if any. Must be
+    /// called at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn notify(&self) {
+        self.notified.store(true, Ordering::Release);
+        self.waker.wake();
+    }
+
+    /// Wait for the signal to be notified
+    pub const fn wait(&self) -> SignalWait<'_> {
+        SignalWait { signal: self }
+    }
+}
+
+/// Future returned by [`Signal::wait()`]
+#[must_use = "futures do nothing unless polled"]
+pub struct SignalWait<'a> {
+    signal: &'a Signal,
+}
+impl Future for SignalWait<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.signal.notified.swap(false, Ordering::Acquire) {
+            return Poll::Ready(());
+        }
+        self.signal.waker.register(cx.waker());
+        // The signal may have been notified before the waker was registered
+        if self.signal.notified.swap(false, Ordering::Acquire) {
+            return Poll::Ready(());
+        }
+        Poll::Pending
+    }
+}
+
+/// Waker slot that can be woken from any thread while it is being updated.
+///
+/// Registering the waker takes the `REGISTERING` bit, and waking it takes the
+/// `WAKING` bit. Neither spins: a waker registered while the slot is being
+/// woken is woken right away, and a wake while the waker is being registered
+/// is performed by the registering thread once it is done.
+#[derive(Default)]
+struct AtomicWaker {
+    state: AtomicUsize,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+const WAKER_WAITING: usize = 0;
+const WAKER_REGISTERING: usize = 0b01;
+const WAKER_WAKING: usize = 0b10;
+
+// SAFETY: The waker slot is only accessed by the thread that holds the
+// `REGISTERING` or the `WAKING` bit, and `Waker` is `Send + Sync`
+unsafe impl Sync for AtomicWaker {}
+
+impl AtomicWaker {
+    const fn new() -> Self {
+        Self {
+            state: AtomicUsize::new(WAKER_WAITING),
+            waker: UnsafeCell::new(None),
+        }
+    }
+
+    fn register(&self, waker: &Waker) {
+        match self
+            .state
+            .compare_exchange(
+                WAKER_WAITING,
+                WAKER_REGISTERING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            )
+            .unwrap_or_else(|state| state)
+        {
+            WAKER_WAITING => {
+                // SAFETY: This thread holds the `REGISTERING` bit, so it has exclusive
+                // access to the slot
+                let slot = unsafe { &mut *self.waker.get() };
+                if !slot
+                    .as_ref()
+                    .is_some_and(|current| current.will_wake(waker))
+                {
+                    *slot = Some(waker.clone());
+                }
+                if self
+                    .state
+                    .compare_exchange(
+                        WAKER_REGISTERING,
+                        WAKER_WAITING,
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                    )
+                    .is_err()
+                {
+                    // A wake happened while registering, and left it to this thread
+                    let waker = slot.take();
+                    self.state.swap(WAKER_WAITING, Ordering::AcqRel);
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+            }
+            WAKER_WAKING => waker.wake_by_ref(),
+            // Another thread is registering, which a single waiter never does
+            _ => {}
+        }
+    }
+
+    fn wake(&self) {
+        if self.state.fetch_or(WAKER_WAKING, Ordering::AcqRel) != WAKER_WAITING {
+            return;
+        }
+        // SAFETY: This thread holds the `WAKING` bit and no thread is registering,
+        // so it has exclusive access to the slot
+        let waker = unsafe { (*self.waker.get()).take() };
+        self.state.fetch_and(!WAKER_WAKING, Ordering::Release);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// State of a task, at the start of the context space of its work item
+#[repr(C)]
+struct TaskHeader {
+    state: AtomicU8,
+    /// Set when the request of the task is cancelled
+    cancelled: AtomicBool,
+    /// Set once the future is dropped. Only accessed by the thread polling the
+    /// task, or by the cleanup callback of the work item.
+    future_dropped: AtomicBool,
+    /// Request the task completes, or null. Set before the task is scheduled,
+    /// and taken by the code path that completes the request once cancelled.
+    request: AtomicPtr<c_void>,
+}
+impl TaskHeader {
+    /// Take the request of the task, so that the task and the cancel callback
+    /// of the request do not both complete it
+    ///
+    /// # Safety
+    ///
+    /// The request must have been cancelled, and the caller must complete the
+    /// returned request
+    unsafe fn take_cancelled_request(&self) -> Option<Request> {
+        let wdf_request = self.request.swap(core::ptr::null_mut(), Ordering::AcqRel);
+        // SAFETY: The request is only taken once, and the caller completes it
+        (!wdf_request.is_null()).then(|| unsafe { Request::from_raw(wdf_request.cast()) })
+    }
+}
+
+/// Context space of the work item of a task running a future of type `F`
+#[repr(C)]
+struct TaskContext<F> {
+    header: TaskHeader,
+    future: UnsafeCell<MaybeUninit<F>>,
+}
+impl<F> TaskContext<F> {
+    /// Get a pointer to the future of the task whose context starts with
+    /// `header`
+    ///
+    /// # Safety
+    ///
+    /// The context must have been allocated with the size of `TaskContext<F>`,
+    /// and be aligned for it
+    unsafe fn future_ptr(header: &TaskHeader) -> *mut F {
+        let inner = NonNull::from(header).cast::<Self>();
+        // SAFETY: The caller guarantees that the context holds a `TaskContext<F>`,
+        // so the field is in bounds
+        let future = unsafe { &raw const (*inner.as_ptr()).future };
+        UnsafeCell::raw_get(future).cast()
+    }
+}
+
+declare_context_type!(TASK_HEADER_TYPE_INFO, TaskHeader);
+
+/// Context of a request processed by a task, to find the task when the request
+/// is cancelled
+#[repr(C)]
+struct TaskRequest {
+    work_item: AtomicPtr<c_void>,
+}
+
+declare_context_type!(TASK_REQUEST_TYPE_INFO, TaskRequest);
+
+/// Cancellation of the requests processed by tasks
+struct CancelTask;
+
+impl RequestCancelCallback for CancelTask {
+    fn cancel(request: Request) {
+        // SAFETY: The request is not completed yet, and its context was allocated by
+        // `spawn_for_request` before it was marked cancelable
+        let Some(task_request) = (unsafe {
+            get_context::<TaskRequest>(request.as_raw().cast(), &TASK_REQUEST_TYPE_INFO)
+        }) else {
+            request.complete(STATUS_CANCELLED);
+            return;
+        };
+        let wdf_work_item: WDFWORKITEM = task_request.work_item.load(Ordering::Acquire).cast();
+        // SAFETY: The task is only deleted once it completed the request, which it
+        // does not do before seeing `cancelled`, so the work item is valid until
+        // `cancelled` is set. The reference keeps its context valid after that.
+        let _reference = unsafe { RefCountedHandle::new(wdf_work_item.cast()) };
+        // SAFETY: The work item is referenced above
+        let header =
+            unsafe { get_context::<TaskHeader>(wdf_work_item.cast(), &TASK_HEADER_TYPE_INFO) };
+        if let Some(header) = header {
+            header.cancelled.store(true, Ordering::Release);
+            // SAFETY: The work item is referenced above
+            let scheduled = unsafe { schedule(wdf_work_item) };
+            // The work item was deleted before the task saw the cancellation, ex. when
+            // the device was removed, so the cancel callback completes the request,
+            // unless the task took it to complete it
+            if !scheduled
+                && !header
+                    .request
+                    .swap(core::ptr::null_mut(), Ordering::AcqRel)
+                    .is_null()
+            {
+                request.complete(STATUS_CANCELLED);
+            }
+        }
+    }
+}
+
+/// Create the work item of a task running `future`, parented to `device`
+fn create_task<F>(
+    device: &Device,
+    request: Option<&Request>,
+    future: F,
+) -> Result<WDFWORKITEM, NTSTATUS>
+where
+    F: Future<Output = Result<usize, NTSTATUS>> + Send + 'static,
+{
+    const {
+        assert!(
+            core::mem::align_of::<TaskContext<F>>() <= TASK_MAX_ALIGN,
+            "the alignment of the future exceeds TASK_MAX_ALIGN"
+        );
+    }
+    let config_size: ULONG;
+    // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+    #[allow(clippy::cast_possible_truncation)]
+    {
+        const CONFIG_SIZE: usize = core::mem::size_of::<WDF_WORKITEM_CONFIG>();
+        const { assert!(CONFIG_SIZE <= ULONG::MAX as usize) }
+        config_size = CONFIG_SIZE as ULONG;
+    }
+    let mut config = WDF_WORKITEM_CONFIG {
+        Size: config_size,
+        EvtWorkItemFunc: Some(evt_task_work_item::<F>),
+        // The framework does not serialize the task with the callbacks of the device
+        AutomaticSerialization: 0,
+    };
+    let mut attributes = object_attributes(&TASK_HEADER_TYPE_INFO);
+    attributes.ParentObject = device.as_raw().cast();
+    attributes.ContextSizeOverride = core::mem::size_of::<TaskContext<F>>();
+    attributes.EvtCleanupCallback = Some(evt_task_cleanup::<F>);
+    let mut wdf_work_item: WDFWORKITEM = core::ptr::null_mut();
+
+    let nt_status;
+    // SAFETY: `config` and `attributes` describe the work item and outlive the
+    // call. The framework zero-initializes the context, including its
+    // `TaskHeader`.
+    unsafe {
+        nt_status = call_unsafe_wdf_function_binding!(
+            WdfWorkItemCreate,
+            &raw mut config,
+            &raw mut attributes,
+            &raw mut wdf_work_item,
+        );
+    }
+    if !nt_success(nt_status) {
+        return Err(nt_status);
+    }
+
+    // SAFETY: `wdf_work_item` was just created with a context described by
+    // `TASK_HEADER_TYPE_INFO`
+    let Some(header) =
+        (unsafe { get_context::<TaskHeader>(wdf_work_item.cast(), &TASK_HEADER_TYPE_INFO) })
+    else {
+        // SAFETY: `wdf_work_item` was just created and is not shared yet. The future
+        // was not stored, so the cleanup callback does not drop it.
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfObjectDelete, wdf_work_item.cast());
+        }
+        return Err(STATUS_INSUFFICIENT_RESOURCES);
+    };
+    header.request.store(
+        request.map_or(core::ptr::null_mut(), |request| request.as_raw().cast()),
+        Ordering::Relaxed,
+    );
+    // SAFETY: The context was allocated with the size of `TaskContext<F>`, and is
+    // aligned for it as checked above
+    let future_ptr = unsafe { TaskContext::<F>::future_ptr(header) };
+    // SAFETY: The task is not scheduled yet, so nothing else accesses the future
+    unsafe {
+        future_ptr.write(future);
+    }
+    Ok(wdf_work_item)
+}
+
+/// Schedule the task of `wdf_work_item` to be polled, unless it is already
+/// scheduled or done. Returns `false` if the task is done.
+///
+/// # Safety
+///
+/// `wdf_work_item` must be the work item of a task, whose context is valid
+unsafe fn schedule(wdf_work_item: WDFWORKITEM) -> bool {
+    // SAFETY: The caller guarantees that the context of the work item is valid
+    let Some(header) =
+        (unsafe { get_context::<TaskHeader>(wdf_work_item.cast(), &TASK_HEADER_TYPE_INFO) })
+    else {
+        return false;
+    };
+    let mut state = header.state.load(Ordering::Acquire);
+    loop {
+        let next = match state {
+            TASK_IDLE => TASK_SCHEDULED,
+            // The thread polling the task polls it again
+            TASK_RUNNING => TASK_NOTIFIED,
+            TASK_DONE => return false,
+            _ => return true,
+        };
+        match header
+            .state
+            .compare_exchange(state, next, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => break,
+            Err(actual) => state = actual,
+        }
+    }
+    if state == TASK_IDLE {
+        // SAFETY: The task is not done, so its work item is not deleted yet
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfWorkItemEnqueue, wdf_work_item);
+        }
+    }
+    true
+}
+
+/// `EvtWorkItem` callback that polls the task of `wdf_work_item`, and deletes
+/// the work item once the task is done
+unsafe extern "C" fn evt_task_work_item<F>(wdf_work_item: WDFWORKITEM)
+where
+    F: Future<Output = Result<usize, NTSTATUS>> + Send + 'static,
+{
+    // SAFETY: The framework passes the work item, which is valid until the callback
+    // returns. `TASK_HEADER_TYPE_INFO` describes `TaskHeader`.
+    let Some(header) =
+        (unsafe { get_context::<TaskHeader>(wdf_work_item.cast(), &TASK_HEADER_TYPE_INFO) })
+    else {
+        return;
+    };
+    if header
+        .state
+        .compare_exchange(
+            TASK_SCHEDULED,
+            TASK_RUNNING,
+            Ordering::Acquire,
+            Ordering::Relaxed,
+        )
+        .is_err()
+    {
+        return;
+    }
+    loop {
+        // SAFETY: This thread moved the task to running, so it polls it exclusively
+        if unsafe { poll_task::<F>(wdf_work_item, header) }.is_ready() {
+            header.state.store(TASK_DONE, Ordering::Release);
+            // SAFETY: The task is done, so the work item is not enqueued anymore. The
+            // framework lets work items be deleted from their callback, and keeps
+            // their context valid while wakers reference them.
+            unsafe {
+                call_unsafe_wdf_function_binding!(WdfObjectDelete, wdf_work_item.cast());
+            }
+            return;
+        }
+        if header
+            .state
+            .compare_exchange(TASK_RUNNING, TASK_IDLE, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+        {
+            return;
+        }
+        // The task was woken while it was polled
+        header.state.store(TASK_RUNNING, Ordering::Relaxed);
+    }
+}
+
+/// Poll the future of the task, and complete its request once it is done.
+/// Returns [`Poll::Ready`] once the task is done.
+///
+/// # Safety
+///
+/// The caller must have moved the task to running, and `header` must be the
+/// context of `wdf_work_item`
+unsafe fn poll_task<F>(wdf_work_item: WDFWORKITEM, header: &TaskHeader) -> Poll<()>
+where
+    F: Future<Output = Result<usize, NTSTATUS>> + Send + 'static,
+{
+    if header.cancelled.load(Ordering::Acquire) {
+        // SAFETY: The caller polls the task exclusively
+        unsafe {
+            drop_future::<F>(header);
+        }
+        // SAFETY: The request was cancelled, and is completed below
+        if let Some(request) = unsafe { header.take_cancelled_request() } {
+            request.complete(STATUS_CANCELLED);
+        }
+        return Poll::Ready(());
+    }
+    if header.future_dropped.load(Ordering::Relaxed) {
+        // The future completed after the request was cancelled, whose cancel
+        // callback schedules the task again
+        return Poll::Pending;
+    }
+
+    // The waker does not hold a reference, as the work item is valid while it is
+    // polled. Clones of it do.
+    // SAFETY: `TASK_WAKER_VTABLE` takes the work item of a task as data
+    let waker = ManuallyDrop::new(unsafe {
+        Waker::from_raw(RawWaker::new(
+            wdf_work_item.cast_const().cast(),
+            &TASK_WAKER_VTABLE,
+        ))
+    });
+    let mut cx = Context::from_waker(&waker);
+    // SAFETY: The context of the work item holds a `TaskContext<F>`
+    let future_ptr = unsafe { TaskContext::<F>::future_ptr(header) };
+    // SAFETY: The future was stored by `create_task` and is not dropped yet. The
+    // caller polls the task exclusively.
+    let future = unsafe { &mut *future_ptr };
+    // SAFETY: The future is never moved out of the context, which stays at the
+    // same address until the future is dropped
+    let future = unsafe { Pin::new_unchecked(future) };
+    let Poll::Ready(output) = future.poll(&mut cx) else {
+        return Poll::Pending;
+    };
+    // SAFETY: The caller polls the task exclusively
+    unsafe {
+        drop_future::<F>(header);
+    }
+
+    let wdf_request = header.request.load(Ordering::Relaxed);
+    if wdf_request.is_null() {
+        return Poll::Ready(());
+    }
+    // SAFETY: The request of the task is owned by the driver until the task
+    // completes it
+    let request = unsafe { Request::from_raw(wdf_request.cast()) };
+    // SAFETY: `spawn_for_request` marked the request cancelable, and the future was
+    // just dropped, so this is only reached once
+    if unsafe { request.unmark_cancelable() } == Err(STATUS_CANCELLED) {
+        // The cancel callback owns the request, and schedules the task again to
+        // complete it
+        return Poll::Pending;
+    }
+    match output {
+        Ok(information) => request.complete_with_information(STATUS_SUCCESS, information),
+        Err(nt_status) => request.complete(nt_status),
+    }
+    Poll::Ready(())
+}
+
+/// Drop the future of the task in place, unless it was already dropped
+///
+/// # Safety
+///
+/// The caller must have exclusive access to the future, and `header` must be
+/// the context of a task running a future of type `F`
+unsafe fn drop_future<F>(header: &TaskHeader) {
+    if header.future_dropped.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    // SAFETY: The caller guarantees that `header` is the context of a task running
+    // a future of type `F`
+    let future_ptr = unsafe { TaskContext::<F>::future_ptr(header) };
+    // SAFETY: The future was stored by `create_task` and not dropped yet, as
+    // checked above. The caller has exclusive access to it.
+    unsafe {
+        future_ptr.drop_in_place();
+    }
+}
+
+/// `EvtCleanupCallback` of the work item of a task, that drops the future and
+/// completes the request of the task with `STATUS_CANCELLED` if the work item
+/// is deleted before the task is done, ex. when the device is removed
+unsafe extern "C" fn evt_task_cleanup<F>(wdf_object: WDFOBJECT) {
+    // SAFETY: The framework passes the work item, which is valid until the callback
+    // returns. `TASK_HEADER_TYPE_INFO` describes `TaskHeader`.
+    let Some(header) = (unsafe { get_context::<TaskHeader>(wdf_object, &TASK_HEADER_TYPE_INFO) })
+    else {
+        return;
+    };
+    if header.state.swap(TASK_DONE, Ordering::AcqRel) == TASK_DONE {
+        return;
+    }
+    let future_was_dropped = header.future_dropped.load(Ordering::Relaxed);
+    // SAFETY: The framework waits for the callback of a work item to return before
+    // it deletes the work item, so the task is not polled anymore
+    unsafe {
+        drop_future::<F>(header);
+    }
+    if future_was_dropped {
+        // The task already unmarked its request, which was cancelled. The cancel
+        // callback completes it once it sees that the task is done.
+        return;
+    }
+
+    let wdf_request = header.request.load(Ordering::Relaxed);
+    if wdf_request.is_null() {
+        return;
+    }
+    // SAFETY: The request of the task is owned by the driver until the task
+    // completes it, and the task is not polled anymore
+    let request = unsafe { Request::from_raw(wdf_request.cast()) };
+    // SAFETY: `spawn_for_request` marked the request cancelable, and the task did
+    // not unmark it, as its future was not dropped yet
+    if unsafe { request.unmark_cancelable() } == Err(STATUS_CANCELLED) {
+        // The cancel callback owns the request, and completes it once it sees that
+        // the task is done
+        return;
+    }
+    request.complete(STATUS_CANCELLED);
+}
+
+/// Waker of a task, whose data is the work item of the task. Clones of a waker
+/// hold a framework reference on the work item, so that waking a task that is
+/// done is harmless.
+static TASK_WAKER_VTABLE: RawWakerVTable =
+    RawWakerVTable::new(waker_clone, waker_wake, waker_wake_by_ref, waker_drop);
+
+unsafe fn waker_clone(data: *const ()) -> RawWaker {
+    // SAFETY: The waker being cloned keeps the work item valid
+    unsafe {
+        reference_object(data.cast_mut().cast());
+    }
+    RawWaker::new(data, &TASK_WAKER_VTABLE)
+}
+
+unsafe fn waker_wake(data: *const ()) {
+    // SAFETY: The waker keeps the work item valid until it is dropped below
+    unsafe {
+        waker_wake_by_ref(data);
+    }
+    // SAFETY: The waker is consumed by `wake`
+    unsafe {
+        waker_drop(data);
+    }
+}
+
+unsafe fn waker_wake_by_ref(data: *const ()) {
+    // SAFETY: The waker keeps the context of the work item valid
+    unsafe {
+        schedule(data.cast_mut().cast());
+    }
+}
+
+unsafe fn waker_drop(data: *const ()) {
+    // SAFETY: Clones of the waker hold a reference, released here
+    unsafe {
+        dereference_object(data.cast_mut().cast());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static WAKES: AtomicUsize = AtomicUsize::new(0);
+
+    static COUNTING_WAKER_VTABLE: RawWakerVTable = RawWakerVTable::new(
+        |data| RawWaker::new(data, &COUNTING_WAKER_VTABLE),
+        |_| {
+            WAKES.fetch_add(1, Ordering::Relaxed);
+        },
+        |_| {
+            WAKES.fetch_add(1, Ordering::Relaxed);
+        },
+        |_| {},
+    );
+
+    #[test]
+    fn signal_wakes_waiting_task() {
+        // SAFETY: The vtable functions ignore the data
+        let waker =
+            unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &COUNTING_WAKER_VTABLE)) };
+        let mut cx = Context::from_waker(&waker);
+        let signal = Signal::new();
+
+        let mut wait = signal.wait();
+        assert_eq!(Pin::new(&mut wait).poll(&mut cx), Poll::Pending);
+        assert_eq!(WAKES.load(Ordering::Relaxed), 0);
+
+        signal.notify();
+        assert_eq!(WAKES.load(Ordering::Relaxed), 1);
+        assert_eq!(Pin::new(&mut wait).poll(&mut cx), Poll::Ready(()));
+
+        // The notification was consumed, and a notification with no waiting task
+        // completes the next wait right away
+        assert_eq!(Pin::new(&mut signal.wait()).poll(&mut cx), Poll::Pending);
+        signal.notify();
+        signal.notify();
+        assert_eq!(Pin::new(&mut signal.wait()).poll(&mut cx), Poll::Ready(()));
+        assert_eq!(Pin::new(&mut signal.wait()).poll(&mut cx), Poll::Pending);
+    }
+}
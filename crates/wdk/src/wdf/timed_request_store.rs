@@ -0,0 +1,328 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{
+    cell::UnsafeCell,
+    ffi::c_void,
+    sync::atomic::{AtomicPtr, Ordering},
+    time::Duration,
+};
+
+use wdk_sys::{
+    NTSTATUS,
+    STATUS_CANCELLED,
+    STATUS_INSUFFICIENT_RESOURCES,
+    STATUS_IO_TIMEOUT,
+    ULONG64,
+    WDF_OBJECT_ATTRIBUTES,
+    WDFREQUEST,
+    call_unsafe_wdf_function_binding,
+};
+
+use crate::{
+    nt_success,
+    time::interrupt_time,
+    wdf::{
+        Arc,
+        Request,
+        SpinLock,
+        context::{allocate_context, declare_context_type, get_context},
+    },
+};
+
+/// Length of the intervals that interrupt times are counted in
+const INTERRUPT_TIME_INTERVAL_NANOS: u128 = 100;
+
+/// Store of up to `N` pended requests, that completes the requests with
+/// `STATUS_IO_TIMEOUT` when they are not taken out of the store before their
+/// deadline.
+///
+/// Requests are pended with [`TimedRequestStore::pend()`], which makes them
+/// cancelable, and taken out in the order they were pended with
+/// [`TimedRequestStore::take()`], to be processed and completed by the driver.
+/// [`TimedRequestStore::complete_expired()`] completes the requests whose
+/// deadline passed, and is typically called from the callback of a periodic
+/// [`crate::wdf::Timer`]. Requests that are cancelled while they are pended
+/// are completed with `STATUS_CANCELLED` by the store.
+///
+/// The store resolves the races between the cancellation, the timeout and the
+/// driver taking a request, so that each request is completed exactly once.
+/// Each pended request holds a reference to the store, so that the store stays
+/// alive until all its requests are taken out or completed. Drivers must call
+/// [`TimedRequestStore::cancel_all()`] when their device stops, ex. from
+/// [`crate::wdf::IoQueueStopCallback::stop`], as the framework does not cancel
+/// the pended requests by itself.
+pub struct TimedRequestStore<const N: usize> {
+    lock: SpinLock,
+    /// Only accessed with `lock` held
+    state: UnsafeCell<StoreState<N>>,
+}
+
+// SAFETY: The state of the store is only accessed with `lock` held, and the
+// framework request handles it holds can be used from any thread
+unsafe impl<const N: usize> Send for TimedRequestStore<N> {}
+// SAFETY: The state of the store is only accessed with `lock` held, and the
+// framework request handles it holds can be used from any thread
+unsafe impl<const N: usize> Sync for TimedRequestStore<N> {}
+
+impl<const N: usize> TimedRequestStore<N> {
+    /// Try to construct a store of up to `N` pended requests. The spin lock
+    /// of the store is created with `lock_attributes`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct the spin
+    /// lock of the store or the object holding the store. The error variant
+    /// will contain a [`NTSTATUS`] of the failure.
+    pub fn try_new(lock_attributes: &mut WDF_OBJECT_ATTRIBUTES) -> Result<Arc<Self>, NTSTATUS> {
+        Arc::try_new(Self {
+            lock: SpinLock::try_new(lock_attributes)?,
+            state: UnsafeCell::new(StoreState {
+                requests: [const { None }; N],
+                next_sequence: 0,
+            }),
+        })
+    }
+
+    /// Try to construct a store of up to `N` pended requests. This is an alias
+    /// for [`TimedRequestStore::try_new()`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct the spin
+    /// lock of the store or the object holding the store. The error variant
+    /// will contain a [`NTSTATUS`] of the failure.
+    pub fn create(lock_attributes: &mut WDF_OBJECT_ATTRIBUTES) -> Result<Arc<Self>, NTSTATUS> {
+        Self::try_new(lock_attributes)
+    }
+
+    /// Pend `request` in the store, until it is taken out with
+    /// [`TimedRequestStore::take()`], or completed when `timeout` passes or it
+    /// is cancelled. The driver no longer owns the request once it is pended,
+    /// so it is consumed. Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request could not be pended.
+    /// The driver still owns the request in that case, which is returned along
+    /// with the error, and must complete it:
+    /// * `STATUS_INSUFFICIENT_RESOURCES` if the store already holds `N`
+    ///   requests, or the framework fails to allocate the context the store
+    ///   tracks the request with.
+    /// * `STATUS_CANCELLED` if the request was already cancelled.
+    pub fn pend(
+        this: &Arc<Self>,
+        request: Request,
+        timeout: Duration,
+    ) -> Result<(), (Request, NTSTATUS)> {
+        // SAFETY: `request` is a valid request owned by the driver.
+        // `TIMED_REQUEST_TYPE_INFO` describes `TimedRequest`, which is valid when
+        // all-zero.
+        let context = match unsafe {
+            allocate_context::<TimedRequest>(request.as_raw().cast(), &TIMED_REQUEST_TYPE_INFO)
+        } {
+            Ok(context) => context,
+            Err(nt_status) => return Err((request, nt_status)),
+        };
+        context.store.store(
+            core::ptr::from_ref::<Self>(this).cast_mut().cast(),
+            Ordering::Release,
+        );
+        let deadline = interrupt_time().saturating_add(duration_to_intervals(timeout));
+
+        let _guard = this.lock.lock();
+        // SAFETY: `lock` is held
+        let state = unsafe { &mut *this.state.get() };
+        let Some(slot) = state.requests.iter_mut().find(|slot| slot.is_none()) else {
+            return Err((request, STATUS_INSUFFICIENT_RESOURCES));
+        };
+
+        let nt_status;
+        // SAFETY: `request` is owned by the driver. Its cancel callback finds the
+        // store through the context set above, and waits for `lock`, so it cannot run
+        // before the request is stored in `slot`.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfRequestMarkCancelableEx,
+                request.as_raw(),
+                Some(evt_request_cancel::<N>),
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err((request, nt_status));
+        }
+        *slot = Some(PendedRequest {
+            wdf_request: request.as_raw(),
+            deadline,
+            sequence: state.next_sequence,
+            cancelled: false,
+            _store: Arc::clone(this),
+        });
+        state.next_sequence += 1;
+        Ok(())
+    }
+
+    /// Take the oldest pended request out of the store, so that the driver
+    /// owns it again and can process and complete it. Returns `None` if no
+    /// request is pended. Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn take(&self) -> Option<Request> {
+        let pended = {
+            let _guard = self.lock.lock();
+            // SAFETY: `lock` is held
+            let state = unsafe { &mut *self.state.get() };
+            loop {
+                let slot = state
+                    .requests
+                    .iter_mut()
+                    .filter(|slot| slot.as_ref().is_some_and(|pended| !pended.cancelled))
+                    .min_by_key(|slot| slot.as_ref().map(|pended| pended.sequence))?;
+                if let Some(pended) = take_uncancelled(slot) {
+                    break pended;
+                }
+            }
+        };
+        // SAFETY: The request was taken out of the store and made non-cancelable, so
+        // it is owned by the driver until it completes it
+        Some(unsafe { Request::from_raw(pended.wdf_request) })
+    }
+
+    /// Complete the pended requests whose deadline passed with
+    /// `STATUS_IO_TIMEOUT`. Returns the number of completed requests. Must be
+    /// called at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn complete_expired(&self) -> usize {
+        let now = interrupt_time();
+        self.complete_where(STATUS_IO_TIMEOUT, |pended| pended.deadline <= now)
+    }
+
+    /// Complete all pended requests with `STATUS_CANCELLED`. Returns the number
+    /// of completed requests. Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn cancel_all(&self) -> usize {
+        self.complete_where(STATUS_CANCELLED, |_| true)
+    }
+
+    /// Complete the pended requests that match `predicate` with `status`,
+    /// after taking them out of the store
+    fn complete_where(
+        &self,
+        status: NTSTATUS,
+        predicate: impl Fn(&PendedRequest<N>) -> bool,
+    ) -> usize {
+        let mut taken: [Option<PendedRequest<N>>; N] = [const { None }; N];
+        {
+            let _guard = self.lock.lock();
+            // SAFETY: `lock` is held
+            let state = unsafe { &mut *self.state.get() };
+            for (slot, taken_slot) in state.requests.iter_mut().zip(&mut taken) {
+                if slot.as_ref().is_some_and(&predicate) {
+                    *taken_slot = take_uncancelled(slot);
+                }
+            }
+        }
+        let mut completed = 0;
+        for pended in taken.into_iter().flatten() {
+            // SAFETY: The request was taken out of the store and made non-cancelable,
+            // so it is owned by the driver until it is completed here
+            let request = unsafe { Request::from_raw(pended.wdf_request) };
+            request.complete(status);
+            completed += 1;
+        }
+        completed
+    }
+}
+
+/// Requests pended in a [`TimedRequestStore`]
+struct StoreState<const N: usize> {
+    requests: [Option<PendedRequest<N>>; N],
+    /// Sequence number of the next pended request
+    next_sequence: u64,
+}
+
+/// A request pended in a [`TimedRequestStore`]
+struct PendedRequest<const N: usize> {
+    wdf_request: WDFREQUEST,
+    /// Interrupt time at which the request times out
+    deadline: ULONG64,
+    /// Order the request was pended in
+    sequence: u64,
+    /// Whether the cancel callback of the request is waiting to complete it
+    cancelled: bool,
+    /// Keeps the store alive while the request is pended, as its cancel
+    /// callback refers to the store
+    _store: Arc<TimedRequestStore<N>>,
+}
+
+/// Context of a request pended in a [`TimedRequestStore`], through which its
+/// cancel callback finds the store
+#[repr(C)]
+struct TimedRequest {
+    store: AtomicPtr<c_void>,
+}
+
+declare_context_type!(TIMED_REQUEST_TYPE_INFO, TimedRequest);
+
+/// Take the request of `slot` out of the store and make it non-cancelable,
+/// unless its cancel callback is waiting to complete it. The lock of the store
+/// must be held.
+fn take_uncancelled<const N: usize>(
+    slot: &mut Option<PendedRequest<N>>,
+) -> Option<PendedRequest<N>> {
+    let pended = slot.as_mut().filter(|pended| !pended.cancelled)?;
+    let nt_status;
+    // SAFETY: The request is pended in the store, so it is owned by the driver and
+    // not completed yet
+    unsafe {
+        nt_status =
+            call_unsafe_wdf_function_binding!(WdfRequestUnmarkCancelable, pended.wdf_request);
+    }
+    if nt_status == STATUS_CANCELLED {
+        // The cancel callback waits for the lock of the store, then completes the
+        // request
+        pended.cancelled = true;
+        return None;
+    }
+    slot.take()
+}
+
+/// `EvtRequestCancel` callback of the requests pended in a
+/// [`TimedRequestStore`], that completes them with `STATUS_CANCELLED`
+unsafe extern "C" fn evt_request_cancel<const N: usize>(wdf_request: WDFREQUEST) {
+    // SAFETY: The framework passes a request the driver owns.
+    // `TIMED_REQUEST_TYPE_INFO` describes `TimedRequest`.
+    let context =
+        unsafe { get_context::<TimedRequest>(wdf_request.cast(), &TIMED_REQUEST_TYPE_INFO) };
+    let store = context
+        .map(|context| context.store.load(Ordering::Acquire))
+        .filter(|store| !store.is_null())
+        .map(|store| {
+            // SAFETY: The context of a pended request points to its store, which the
+            // slot of the request keeps alive until it is taken out below
+            unsafe { &*store.cast::<TimedRequestStore<N>>() }
+        });
+    let pended = store.and_then(|store| {
+        let _guard = store.lock.lock();
+        // SAFETY: `lock` is held
+        let state = unsafe { &mut *store.state.get() };
+        state
+            .requests
+            .iter_mut()
+            .find(|slot| {
+                slot.as_ref()
+                    .is_some_and(|pended| pended.wdf_request == wdf_request)
+            })
+            .and_then(Option::take)
+    });
+
+    // SAFETY: The request was made cancelable by the store, so the driver owns it
+    // until it is completed here
+    let request = unsafe { Request::from_raw(wdf_request) };
+    request.complete(STATUS_CANCELLED);
+    // The store may be deleted along with the reference of the request
+    drop(pended);
+}
+
+/// Convert `duration` to a number of interrupt time intervals, saturating
+/// durations that do not fit
+fn duration_to_intervals(duration: Duration) -> ULONG64 {
+    ULONG64::try_from(duration.as_nanos() / INTERRUPT_TIME_INTERVAL_NANOS).unwrap_or(ULONG64::MAX)
+}
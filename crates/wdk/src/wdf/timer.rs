@@ -1,7 +1,10 @@
 // Copyright (c) Microsoft Corporation
 // License: MIT OR Apache-2.0
 
+use core::time::Duration;
+
 use wdk_sys::{
+    LONGLONG,
     NTSTATUS,
     WDF_OBJECT_ATTRIBUTES,
     WDF_TIMER_CONFIG,
@@ -9,9 +12,61 @@
     call_unsafe_wdf_function_binding,
 };
 
-use crate::nt_success;
+use crate::{PassiveLevel, nt_success};
+
+/// Length of the intervals that WDF timer due times are counted in
+const DUE_TIME_INTERVAL_NANOS: u128 = 100;
+
+/// Absolute system time, in 100-nanosecond intervals since January 1, 1601
+/// (UTC), ex. as returned by `KeQuerySystemTimePrecise`.
+///
+/// Used as the due time of [`Timer::start_at()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SystemTime(LONGLONG);
+impl SystemTime {
+    /// Construct a [`SystemTime`] from a number of 100-nanosecond intervals
+    /// since January 1, 1601 (UTC). Returns `None` if `intervals` is not
+    /// positive, as WDF treats such due times as relative.
+    #[must_use]
+    pub const fn from_raw(intervals: LONGLONG) -> Option<Self> {
+        if intervals > 0 {
+            Some(Self(intervals))
+        } else {
+            None
+        }
+    }
+
+    /// Get the number of 100-nanosecond intervals since January 1, 1601 (UTC)
+    #[must_use]
+    pub const fn as_raw(&self) -> LONGLONG {
+        self.0
+    }
+
+    /// Get the time `duration` after this time. Returns `None` if the result
+    /// overflows.
+    #[must_use]
+    pub fn checked_add(&self, duration: Duration) -> Option<Self> {
+        let intervals = LONGLONG::try_from(duration.as_nanos() / DUE_TIME_INTERVAL_NANOS).ok()?;
+        self.0.checked_add(intervals).map(Self)
+    }
+}
+
+/// Convert `duration` to a relative WDF due time, i.e. a negative number of
+/// 100-nanosecond intervals. Durations shorter than one interval are rounded
+/// up to it, as a due time of 0 would be an absolute time, and durations that
+/// do not fit are saturated.
+pub(super) fn relative_due_time(duration: Duration) -> LONGLONG {
+    let intervals = duration.as_nanos().div_ceil(DUE_TIME_INTERVAL_NANOS).max(1);
+    -LONGLONG::try_from(intervals).unwrap_or(LONGLONG::MAX)
+}
 
 /// WDF Timer.
+///
+/// The timer is started with a due time that is either relative to the current
+/// time, through [`Timer::start_in()`], or absolute, through
+/// [`Timer::start_at()`]. High resolution timers only support relative due
+/// times, and are available as a separate [`HighResTimer`] type, so that they
+/// cannot be started with an absolute due time.
 pub struct Timer {
     wdf_timer: WDFTIMER,
 }
@@ -60,7 +115,22 @@ pub fn create(
         Self::try_new(timer_config, attributes)
     }
 
-    /// Start the [`Timer`]'s clock
+    /// Set `AutomaticSerialization` in `timer_config`, so that the callback of
+    /// the timer is serialized with the callbacks of its parent device or
+    /// queue, as configured by their [`crate::wdf::SynchronizationScope`].
+    /// Timers whose callbacks run at `PASSIVE_LEVEL` can only be serialized
+    /// with a parent whose [`crate::wdf::ExecutionLevel`] is also
+    /// [`crate::wdf::ExecutionLevel::Passive`].
+    pub fn set_automatic_serialization(timer_config: &mut WDF_TIMER_CONFIG, serialize: bool) {
+        timer_config.AutomaticSerialization = u8::from(serialize);
+    }
+
+    /// Start the [`Timer`]'s clock. A negative `due_time` is relative to the
+    /// current time, a positive one is an absolute system time, both in
+    /// 100-nanosecond intervals. Prefer [`Timer::start_in()`] and
+    /// [`Timer::start_at()`], which cannot mix the two up.
+    ///
+    /// Returns `true` if the timer was already in the system's timer queue.
     #[must_use]
     pub fn start(&self, due_time: i64) -> bool {
         let result;
@@ -72,9 +142,43 @@ pub fn start(&self, due_time: i64) -> bool {
         result != 0
     }
 
-    /// Stop the [`Timer`]'s clock
+    /// Start the [`Timer`]'s clock, so that it expires after `duration`
+    ///
+    /// Returns `true` if the timer was already in the system's timer queue.
+    #[must_use]
+    pub fn start_in(&self, duration: Duration) -> bool {
+        self.start(relative_due_time(duration))
+    }
+
+    /// Start the [`Timer`]'s clock, so that it expires at `time`. The timer
+    /// expires immediately if `time` is in the past.
+    ///
+    /// Returns `true` if the timer was already in the system's timer queue.
+    #[must_use]
+    pub fn start_at(&self, time: SystemTime) -> bool {
+        self.start(time.as_raw())
+    }
+
+    /// Stop the [`Timer`]'s clock, without waiting for a running callback of
+    /// the timer to return. Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// Returns `true` if the timer was in the system's timer queue.
     #[must_use]
-    pub fn stop(&self, wait: bool) -> bool {
+    pub fn stop(&self) -> bool {
+        self.stop_with(false)
+    }
+
+    /// Stop the [`Timer`]'s clock, and wait for the callbacks of the timer,
+    /// including the DPCs queued by the system, to return. Must not be called
+    /// from the callback of the timer.
+    ///
+    /// Returns `true` if the timer was in the system's timer queue.
+    #[must_use]
+    pub fn stop_synchronously(&self, _irql: PassiveLevel) -> bool {
+        self.stop_with(true)
+    }
+
+    fn stop_with(&self, wait: bool) -> bool {
         let result;
         // SAFETY: `wdf_timer` is a private member of `Timer`, originally created by
         // WDF, and this module guarantees that it is always in a valid state.
@@ -85,3 +189,71 @@ pub fn stop(&self, wait: bool) -> bool {
         result != 0
     }
 }
+
+/// WDF High Resolution Timer.
+///
+/// A [`Timer`] created with `UseHighResolutionTimer` set, that expires with a
+/// precision of the system clock rather than of the system's timer tick. WDF
+/// does not support absolute due times for high resolution timers, so this
+/// type can only be started with a relative due time, through
+/// [`HighResTimer::start_in()`].
+#[cfg(driver_model__driver_type = "KMDF")]
+pub struct HighResTimer {
+    timer: Timer,
+}
+#[cfg(driver_model__driver_type = "KMDF")]
+impl HighResTimer {
+    /// Try to construct a WDF High Resolution Timer object.
+    /// `UseHighResolutionTimer` is set in `timer_config` before the timer
+    /// is created.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a timer.
+    /// The error variant will contain a [`NTSTATUS`] of the failure. Full error
+    /// documentation is available in the [WDFTimer Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdftimer/nf-wdftimer-wdftimercreate#return-value)
+    pub fn try_new(
+        timer_config: &mut WDF_TIMER_CONFIG,
+        attributes: &mut WDF_OBJECT_ATTRIBUTES,
+    ) -> Result<Self, NTSTATUS> {
+        timer_config.UseHighResolutionTimer = u8::from(true);
+        Timer::try_new(timer_config, attributes).map(|timer| Self { timer })
+    }
+
+    /// Try to construct a WDF High Resolution Timer object. This is an alias
+    /// for [`HighResTimer::try_new()`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a timer.
+    /// The error variant will contain a [`NTSTATUS`] of the failure. Full error
+    /// documentation is available in the [WDFTimer Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdftimer/nf-wdftimer-wdftimercreate#return-value)
+    pub fn create(
+        timer_config: &mut WDF_TIMER_CONFIG,
+        attributes: &mut WDF_OBJECT_ATTRIBUTES,
+    ) -> Result<Self, NTSTATUS> {
+        Self::try_new(timer_config, attributes)
+    }
+
+    /// Start the [`HighResTimer`]'s clock, so that it expires after `duration`
+    ///
+    /// Returns `true` if the timer was already in the system's timer queue.
+    #[must_use]
+    pub fn start_in(&self, duration: Duration) -> bool {
+        self.timer.start_in(duration)
+    }
+
+    /// Stop the [`HighResTimer`]'s clock, without waiting for a running
+    /// callback of the timer to return. See [`Timer::stop()`].
+    #[must_use]
+    pub fn stop(&self) -> bool {
+        self.timer.stop()
+    }
+
+    /// Stop the [`HighResTimer`]'s clock, and wait for the callbacks of the
+    /// timer to return. See [`Timer::stop_synchronously()`].
+    #[must_use]
+    pub fn stop_synchronously(&self, irql: PassiveLevel) -> bool {
+        self.timer.stop_synchronously(irql)
+    }
+}
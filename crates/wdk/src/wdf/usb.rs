@@ -0,0 +1,793 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::time::Duration;
+
+use wdk_sys::{
+    _WdfUsbTargetDeviceSelectConfigType,
+    BOOLEAN,
+    NTSTATUS,
+    STATUS_INVALID_BUFFER_SIZE,
+    ULONG,
+    USB_DEVICE_DESCRIPTOR,
+    USBD_STATUS,
+    WDF_IO_TARGET_SENT_IO_ACTION,
+    WDF_MEMORY_DESCRIPTOR,
+    WDF_OBJECT_ATTRIBUTES,
+    WDF_USB_CONTINUOUS_READER_CONFIG,
+    WDF_USB_CONTROL_SETUP_PACKET,
+    WDF_USB_DEVICE_SELECT_CONFIG_PARAMS,
+    WDF_USB_PIPE_INFORMATION,
+    WDF_USB_PIPE_TYPE,
+    WDFCONTEXT,
+    WDFDEVICE,
+    WDFIOTARGET,
+    WDFMEMORY,
+    WDFUSBDEVICE,
+    WDFUSBINTERFACE,
+    WDFUSBPIPE,
+    call_unsafe_wdf_function_binding,
+};
+
+use crate::{
+    nt_success,
+    wdf::{
+        memory::{buffer_descriptor, descriptor_ptr},
+        request::send_options,
+    },
+};
+
+/// Type of a USB control transfer, as encoded in its `bmRequestType`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UsbControlRequestType {
+    /// A standard request of the USB specification, ex. `GET_DESCRIPTOR`
+    Standard,
+    /// A request defined by the class of the device or interface
+    Class,
+    /// A request defined by the vendor of the device
+    Vendor,
+}
+
+/// Recipient of a USB control transfer, as encoded in its `bmRequestType`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UsbControlRecipient {
+    /// The device
+    Device,
+    /// An interface of the device, selected by the index of the transfer
+    Interface,
+    /// An endpoint of the device, selected by the index of the transfer
+    Endpoint,
+    /// Another recipient
+    Other,
+}
+
+/// Setup packet of a USB control transfer, sent with
+/// [`UsbDevice::control_transfer_in()`] or
+/// [`UsbDevice::control_transfer_out()`], which fill in its direction and
+/// length from the buffer of the transfer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UsbControlSetupPacket {
+    /// Type of the request
+    pub request_type: UsbControlRequestType,
+    /// Recipient of the request
+    pub recipient: UsbControlRecipient,
+    /// `bRequest` of the request
+    pub request: u8,
+    /// `wValue` of the request
+    pub value: u16,
+    /// `wIndex` of the request
+    pub index: u16,
+}
+impl UsbControlSetupPacket {
+    /// Encode the setup packet for a transfer of `length` bytes, from the
+    /// device to the host if `device_to_host` is `true`
+    fn to_raw(
+        self,
+        device_to_host: bool,
+        length: usize,
+    ) -> Result<WDF_USB_CONTROL_SETUP_PACKET, NTSTATUS> {
+        let length = u16::try_from(length).map_err(|_| STATUS_INVALID_BUFFER_SIZE)?;
+        let direction = if device_to_host { 0x80 } else { 0 };
+        let request_type = match self.request_type {
+            UsbControlRequestType::Standard => 0x00,
+            UsbControlRequestType::Class => 0x20,
+            UsbControlRequestType::Vendor => 0x40,
+        };
+        let recipient = match self.recipient {
+            UsbControlRecipient::Device => 0x00,
+            UsbControlRecipient::Interface => 0x01,
+            UsbControlRecipient::Endpoint => 0x02,
+            UsbControlRecipient::Other => 0x03,
+        };
+
+        let [value_low, value_high] = self.value.to_le_bytes();
+        let [index_low, index_high] = self.index.to_le_bytes();
+        let [length_low, length_high] = length.to_le_bytes();
+        let mut packet = WDF_USB_CONTROL_SETUP_PACKET::default();
+        packet.Generic.Bytes = [
+            direction | request_type | recipient,
+            self.request,
+            value_low,
+            value_high,
+            index_low,
+            index_high,
+            length_low,
+            length_high,
+        ];
+        Ok(packet)
+    }
+}
+
+/// WDF USB Target Device.
+///
+/// A USB device is the I/O target that a USB function driver uses to
+/// communicate with its device. A driver typically creates it with
+/// [`UsbDevice::create()`] in its `EvtDevicePrepareHardware` callback, selects
+/// a configuration with [`UsbDevice::select_single_interface()`] or
+/// [`UsbDevice::select_all_interfaces()`], and then enumerates the pipes of the
+/// configured [`UsbInterface`]s.
+///
+/// The USB device is deleted along with the framework device it was created
+/// for, and so are its interfaces and pipes.
+pub struct UsbDevice {
+    wdf_usb_device: WDFUSBDEVICE,
+}
+impl UsbDevice {
+    /// Try to construct a WDF USB Target Device object for `device`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a USB
+    /// device. The error variant will contain a [`NTSTATUS`] of the failure.
+    /// Full error documentation is available in the [WDFUsbTargetDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfusb/nf-wdfusb-wdfusbtargetdevicecreate#return-value)
+    ///
+    /// # Safety
+    ///
+    /// `device` must be a valid handle to a framework device object.
+    pub unsafe fn create(
+        device: WDFDEVICE,
+        attributes: &mut WDF_OBJECT_ATTRIBUTES,
+    ) -> Result<Self, NTSTATUS> {
+        let mut usb_device = Self {
+            wdf_usb_device: core::ptr::null_mut(),
+        };
+
+        let nt_status;
+        // SAFETY: The caller guarantees that `device` is a valid device handle. The
+        // resulting ffi object is stored in a private member and not accessible
+        // outside of this module, and this module guarantees that it is always in a
+        // valid state.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfUsbTargetDeviceCreate,
+                device,
+                attributes,
+                &mut usb_device.wdf_usb_device as *mut WDFUSBDEVICE,
+            );
+        }
+        nt_success(nt_status).then_some(usb_device).ok_or(nt_status)
+    }
+
+    /// Get the USB device descriptor of the device
+    #[must_use]
+    pub fn device_descriptor(&self) -> USB_DEVICE_DESCRIPTOR {
+        let mut device_descriptor = USB_DEVICE_DESCRIPTOR::default();
+        // SAFETY: `wdf_usb_device` is a private member of `UsbDevice`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfUsbTargetDeviceGetDeviceDescriptor,
+                self.wdf_usb_device,
+                &mut device_descriptor as *mut USB_DEVICE_DESCRIPTOR,
+            );
+        }
+        device_descriptor
+    }
+
+    /// Select the first configuration of a device that has a single interface,
+    /// and return that interface
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to select the
+    /// configuration. The error variant will contain a [`NTSTATUS`] of the
+    /// failure. Full error documentation is available in the [WDFUsbTargetDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfusb/nf-wdfusb-wdfusbtargetdeviceselectconfig#return-value)
+    pub fn select_single_interface(&self) -> Result<UsbInterface, NTSTATUS> {
+        let mut params = Self::select_config_params(
+            _WdfUsbTargetDeviceSelectConfigType::WdfUsbTargetDeviceSelectConfigTypeSingleInterface,
+        );
+        self.select_config(&mut params)?;
+
+        // SAFETY: The configuration was selected with the single interface type, so
+        // the framework filled in the `SingleInterface` member of the union.
+        let wdf_usb_interface = unsafe { params.Types.SingleInterface.ConfiguredUsbInterface };
+        Ok(UsbInterface { wdf_usb_interface })
+    }
+
+    /// Select the first configuration of the device, with the first alternate
+    /// setting of each of its interfaces. The interfaces are then available
+    /// through [`UsbDevice::interface()`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to select the
+    /// configuration. The error variant will contain a [`NTSTATUS`] of the
+    /// failure. Full error documentation is available in the [WDFUsbTargetDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfusb/nf-wdfusb-wdfusbtargetdeviceselectconfig#return-value)
+    pub fn select_all_interfaces(&self) -> Result<(), NTSTATUS> {
+        // With no interface setting pairs, the framework selects the first alternate
+        // setting of every interface
+        let mut params = Self::select_config_params(
+            _WdfUsbTargetDeviceSelectConfigType::WdfUsbTargetDeviceSelectConfigTypeMultiInterface,
+        );
+        params.Types.MultiInterface.NumberInterfaces = 0;
+        params.Types.MultiInterface.Pairs = core::ptr::null_mut();
+        self.select_config(&mut params)
+    }
+
+    /// Get the number of interfaces of the selected configuration
+    #[must_use]
+    pub fn number_of_interfaces(&self) -> u8 {
+        let number_of_interfaces;
+        // SAFETY: `wdf_usb_device` is a private member of `UsbDevice`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            number_of_interfaces = call_unsafe_wdf_function_binding!(
+                WdfUsbTargetDeviceGetNumInterfaces,
+                self.wdf_usb_device
+            );
+        }
+        number_of_interfaces
+    }
+
+    /// Get the interface at `index` of the selected configuration, or `None` if
+    /// `index` is out of bounds
+    #[must_use]
+    pub fn interface(&self, index: u8) -> Option<UsbInterface> {
+        if index >= self.number_of_interfaces() {
+            return None;
+        }
+
+        let wdf_usb_interface: WDFUSBINTERFACE;
+        // SAFETY: `wdf_usb_device` is a private member of `UsbDevice`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            wdf_usb_interface = call_unsafe_wdf_function_binding!(
+                WdfUsbTargetDeviceGetInterface,
+                self.wdf_usb_device,
+                index
+            );
+        }
+        (!wdf_usb_interface.is_null()).then_some(UsbInterface { wdf_usb_interface })
+    }
+
+    /// Iterate over the interfaces of the selected configuration
+    pub fn interfaces(&self) -> impl Iterator<Item = UsbInterface> + '_ {
+        (0..self.number_of_interfaces()).filter_map(|index| self.interface(index))
+    }
+
+    /// Send a control transfer described by `setup_packet` to the device, that
+    /// reads into `buffer`, and wait for at most `timeout`, or indefinitely if
+    /// `timeout` is `None`, for the device to complete it. On success, returns
+    /// the number of bytes read. Must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the transfer failed, ex.
+    /// `STATUS_IO_TIMEOUT` if it did not complete before `timeout` elapsed, or
+    /// `STATUS_INVALID_BUFFER_SIZE` if `buffer` is longer than a control
+    /// transfer can be. Full error documentation is available in the [WDFUsbTargetDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfusb/nf-wdfusb-wdfusbtargetdevicesendcontroltransfersynchronously#return-value)
+    pub fn control_transfer_in(
+        &self,
+        setup_packet: UsbControlSetupPacket,
+        buffer: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<usize, NTSTATUS> {
+        let mut packet = setup_packet.to_raw(true, buffer.len())?;
+        let descriptor = (!buffer.is_empty())
+            .then(|| buffer_descriptor(buffer.as_mut_ptr().cast(), buffer.len()))
+            .transpose()?;
+        self.send_control_transfer(&mut packet, descriptor, timeout)
+    }
+
+    /// Send a control transfer described by `setup_packet` to the device, that
+    /// writes `data`, and wait for at most `timeout`, or indefinitely if
+    /// `timeout` is `None`, for the device to complete it. On success, returns
+    /// the number of bytes written. Must be called at `IRQL` =
+    /// `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the transfer failed, ex.
+    /// `STATUS_IO_TIMEOUT` if it did not complete before `timeout` elapsed, or
+    /// `STATUS_INVALID_BUFFER_SIZE` if `data` is longer than a control
+    /// transfer can be. Full error documentation is available in the [WDFUsbTargetDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfusb/nf-wdfusb-wdfusbtargetdevicesendcontroltransfersynchronously#return-value)
+    pub fn control_transfer_out(
+        &self,
+        setup_packet: UsbControlSetupPacket,
+        data: &[u8],
+        timeout: Option<Duration>,
+    ) -> Result<usize, NTSTATUS> {
+        let mut packet = setup_packet.to_raw(false, data.len())?;
+        let descriptor = (!data.is_empty())
+            .then(|| buffer_descriptor(data.as_ptr().cast_mut().cast(), data.len()))
+            .transpose()?;
+        self.send_control_transfer(&mut packet, descriptor, timeout)
+    }
+
+    fn send_control_transfer(
+        &self,
+        packet: &mut WDF_USB_CONTROL_SETUP_PACKET,
+        mut descriptor: Option<WDF_MEMORY_DESCRIPTOR>,
+        timeout: Option<Duration>,
+    ) -> Result<usize, NTSTATUS> {
+        let mut options = send_options(0, timeout);
+        let mut bytes_transferred: ULONG = 0;
+
+        let nt_status;
+        // SAFETY: `wdf_usb_device` is a private member of `UsbDevice`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state. The descriptor, if any, describes a buffer that is borrowed until
+        // the transfer completes, and every pointer outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfUsbTargetDeviceSendControlTransferSynchronously,
+                self.wdf_usb_device,
+                core::ptr::null_mut(),
+                &raw mut options,
+                core::ptr::from_mut(packet),
+                descriptor_ptr(descriptor.as_mut()),
+                &raw mut bytes_transferred,
+            );
+        }
+        nt_success(nt_status)
+            .then(|| usize::try_from(bytes_transferred).unwrap_or(usize::MAX))
+            .ok_or(nt_status)
+    }
+
+    fn select_config_params(
+        select_config_type: _WdfUsbTargetDeviceSelectConfigType::Type,
+    ) -> WDF_USB_DEVICE_SELECT_CONFIG_PARAMS {
+        let params_size: ULONG;
+
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            const PARAMS_SIZE: usize = core::mem::size_of::<WDF_USB_DEVICE_SELECT_CONFIG_PARAMS>();
+
+            // Manually assert there is not truncation since clippy doesn't work for
+            // compile-time constants
+            const { assert!(PARAMS_SIZE <= ULONG::MAX as usize) }
+
+            params_size = PARAMS_SIZE as ULONG;
+        }
+
+        WDF_USB_DEVICE_SELECT_CONFIG_PARAMS {
+            Size: params_size,
+            Type: select_config_type,
+            ..WDF_USB_DEVICE_SELECT_CONFIG_PARAMS::default()
+        }
+    }
+
+    fn select_config(
+        &self,
+        params: &mut WDF_USB_DEVICE_SELECT_CONFIG_PARAMS,
+    ) -> Result<(), NTSTATUS> {
+        let nt_status;
+        // SAFETY: `wdf_usb_device` is a private member of `UsbDevice`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state. `params` is initialized for its selection type.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfUsbTargetDeviceSelectConfig,
+                self.wdf_usb_device,
+                core::ptr::null_mut(),
+                params,
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+}
+
+/// WDF USB Interface.
+///
+/// An interface of the selected configuration of a [`UsbDevice`]. Interfaces
+/// are owned by the framework and remain valid for as long as their
+/// [`UsbDevice`].
+#[derive(Clone, Copy)]
+pub struct UsbInterface {
+    wdf_usb_interface: WDFUSBINTERFACE,
+}
+impl UsbInterface {
+    /// Get the interface number from the interface descriptor
+    #[must_use]
+    pub fn interface_number(&self) -> u8 {
+        let interface_number;
+        // SAFETY: `wdf_usb_interface` is a private member of `UsbInterface`, originally
+        // returned by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            interface_number = call_unsafe_wdf_function_binding!(
+                WdfUsbInterfaceGetInterfaceNumber,
+                self.wdf_usb_interface
+            );
+        }
+        interface_number
+    }
+
+    /// Get the number of pipes configured for the selected alternate setting of
+    /// the interface
+    #[must_use]
+    pub fn number_of_configured_pipes(&self) -> u8 {
+        let number_of_pipes;
+        // SAFETY: `wdf_usb_interface` is a private member of `UsbInterface`, originally
+        // returned by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            number_of_pipes = call_unsafe_wdf_function_binding!(
+                WdfUsbInterfaceGetNumConfiguredPipes,
+                self.wdf_usb_interface
+            );
+        }
+        number_of_pipes
+    }
+
+    /// Get the configured pipe at `index`, or `None` if `index` is out of
+    /// bounds
+    #[must_use]
+    pub fn configured_pipe(&self, index: u8) -> Option<UsbPipe> {
+        if index >= self.number_of_configured_pipes() {
+            return None;
+        }
+
+        let wdf_usb_pipe: WDFUSBPIPE;
+        // SAFETY: `wdf_usb_interface` is a private member of `UsbInterface`, originally
+        // returned by WDF, and this module guarantees that it is always in a valid
+        // state. The pipe information is optional.
+        unsafe {
+            wdf_usb_pipe = call_unsafe_wdf_function_binding!(
+                WdfUsbInterfaceGetConfiguredPipe,
+                self.wdf_usb_interface,
+                index,
+                core::ptr::null_mut(),
+            );
+        }
+        (!wdf_usb_pipe.is_null()).then_some(UsbPipe { wdf_usb_pipe })
+    }
+
+    /// Iterate over the configured pipes of the interface
+    pub fn configured_pipes(&self) -> impl Iterator<Item = UsbPipe> + '_ {
+        (0..self.number_of_configured_pipes()).filter_map(|index| self.configured_pipe(index))
+    }
+}
+
+/// Driver-defined handler of the reads of a continuous reader configured with
+/// [`UsbPipe::config_continuous_reader()`].
+pub trait ContinuousReader {
+    /// Handles a completed read of `buffer` from `pipe`. This is invoked from
+    /// the framework's `EvtUsbTargetPipeReadComplete` callback, at `IRQL` <=
+    /// `DISPATCH_LEVEL`. Full documentation is available in the [EvtUsbTargetPipeReadComplete Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfusb/nc-wdfusb-evt_wdf_usb_reader_completion_routine)
+    fn read_complete(pipe: UsbPipe, buffer: &[u8]);
+
+    /// Handles a failed read on `pipe`. Returns `true` for the framework to
+    /// reset the pipe and restart the reader. This is invoked from the
+    /// framework's `EvtUsbTargetPipeReadersFailed` callback, at `IRQL` =
+    /// `PASSIVE_LEVEL`. Full documentation is available in the [EvtUsbTargetPipeReadersFailed Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfusb/nc-wdfusb-evt_wdf_usb_readers_failed)
+    #[must_use]
+    fn readers_failed(pipe: UsbPipe, nt_status: NTSTATUS, usbd_status: USBD_STATUS) -> bool {
+        let _ = (pipe, nt_status, usbd_status);
+        true
+    }
+}
+
+/// WDF USB Pipe.
+///
+/// A pipe of a configured [`UsbInterface`]. Pipes are owned by the framework
+/// and remain valid for as long as their [`UsbDevice`].
+#[derive(Clone, Copy)]
+pub struct UsbPipe {
+    wdf_usb_pipe: WDFUSBPIPE,
+}
+impl UsbPipe {
+    /// Get the information of the pipe, ex. its endpoint address and maximum
+    /// packet size
+    #[must_use]
+    pub fn information(&self) -> WDF_USB_PIPE_INFORMATION {
+        let pipe_information_size: ULONG;
+
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            const PIPE_INFORMATION_SIZE: usize = core::mem::size_of::<WDF_USB_PIPE_INFORMATION>();
+
+            // Manually assert there is not truncation since clippy doesn't work for
+            // compile-time constants
+            const { assert!(PIPE_INFORMATION_SIZE <= ULONG::MAX as usize) }
+
+            pipe_information_size = PIPE_INFORMATION_SIZE as ULONG;
+        }
+
+        let mut pipe_information = WDF_USB_PIPE_INFORMATION {
+            Size: pipe_information_size,
+            ..WDF_USB_PIPE_INFORMATION::default()
+        };
+        // SAFETY: `wdf_usb_pipe` is a private member of `UsbPipe`, originally returned
+        // by WDF, and this module guarantees that it is always in a valid state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfUsbTargetPipeGetInformation,
+                self.wdf_usb_pipe,
+                &mut pipe_information as *mut WDF_USB_PIPE_INFORMATION,
+            );
+        }
+        pipe_information
+    }
+
+    /// Get the type of the pipe, ex. bulk or interrupt
+    #[must_use]
+    pub fn pipe_type(&self) -> WDF_USB_PIPE_TYPE {
+        let pipe_type;
+        // SAFETY: `wdf_usb_pipe` is a private member of `UsbPipe`, originally returned
+        // by WDF, and this module guarantees that it is always in a valid state.
+        unsafe {
+            pipe_type =
+                call_unsafe_wdf_function_binding!(WdfUsbTargetPipeGetType, self.wdf_usb_pipe);
+        }
+        pipe_type
+    }
+
+    /// Returns `true` if the pipe reads from an IN endpoint of the device
+    #[must_use]
+    pub fn is_in_endpoint(&self) -> bool {
+        let result: BOOLEAN;
+        // SAFETY: `wdf_usb_pipe` is a private member of `UsbPipe`, originally returned
+        // by WDF, and this module guarantees that it is always in a valid state.
+        unsafe {
+            result =
+                call_unsafe_wdf_function_binding!(WdfUsbTargetPipeIsInEndpoint, self.wdf_usb_pipe);
+        }
+        result != 0
+    }
+
+    /// Returns `true` if the pipe writes to an OUT endpoint of the device
+    #[must_use]
+    pub fn is_out_endpoint(&self) -> bool {
+        let result: BOOLEAN;
+        // SAFETY: `wdf_usb_pipe` is a private member of `UsbPipe`, originally returned
+        // by WDF, and this module guarantees that it is always in a valid state.
+        unsafe {
+            result =
+                call_unsafe_wdf_function_binding!(WdfUsbTargetPipeIsOutEndpoint, self.wdf_usb_pipe);
+        }
+        result != 0
+    }
+
+    /// Disable the framework's check that transfers on the pipe are a multiple
+    /// of its maximum packet size
+    pub fn set_no_maximum_packet_size_check(&self) {
+        // SAFETY: `wdf_usb_pipe` is a private member of `UsbPipe`, originally returned
+        // by WDF, and this module guarantees that it is always in a valid state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(
+                WdfUsbTargetPipeSetNoMaximumPacketSizeCheck,
+                self.wdf_usb_pipe
+            );
+        }
+    }
+
+    /// Read from the pipe into `buffer`, and wait for at most `timeout`, or
+    /// indefinitely if `timeout` is `None`, for the read to complete. On
+    /// success, returns the number of bytes read. The pipe must be a bulk or
+    /// interrupt IN pipe without a continuous reader. Must be called at `IRQL`
+    /// = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the read failed, ex.
+    /// `STATUS_IO_TIMEOUT` if it did not complete before `timeout` elapsed.
+    /// Full error documentation is available in the [WDFUsbTargetPipe Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfusb/nf-wdfusb-wdfusbtargetpipereadsynchronously#return-value)
+    pub fn read_synchronously(
+        &self,
+        buffer: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<usize, NTSTATUS> {
+        let mut descriptor = buffer_descriptor(buffer.as_mut_ptr().cast(), buffer.len())?;
+        let mut options = send_options(0, timeout);
+        let mut bytes_read: ULONG = 0;
+
+        let nt_status;
+        // SAFETY: `wdf_usb_pipe` is a private member of `UsbPipe`, originally returned
+        // by WDF, and this module guarantees that it is always in a valid state. The
+        // descriptor describes `buffer`, which is borrowed mutably until the read
+        // completes, and every pointer outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfUsbTargetPipeReadSynchronously,
+                self.wdf_usb_pipe,
+                core::ptr::null_mut(),
+                &raw mut options,
+                &raw mut descriptor,
+                &raw mut bytes_read,
+            );
+        }
+        nt_success(nt_status)
+            .then(|| usize::try_from(bytes_read).unwrap_or(usize::MAX))
+            .ok_or(nt_status)
+    }
+
+    /// Write `data` to the pipe, and wait for at most `timeout`, or
+    /// indefinitely if `timeout` is `None`, for the write to complete. On
+    /// success, returns the number of bytes written. The pipe must be a bulk
+    /// or interrupt OUT pipe. Must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the write failed, ex.
+    /// `STATUS_IO_TIMEOUT` if it did not complete before `timeout` elapsed.
+    /// Full error documentation is available in the [WDFUsbTargetPipe Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfusb/nf-wdfusb-wdfusbtargetpipewritesynchronously#return-value)
+    pub fn write_synchronously(
+        &self,
+        data: &[u8],
+        timeout: Option<Duration>,
+    ) -> Result<usize, NTSTATUS> {
+        let mut descriptor = buffer_descriptor(data.as_ptr().cast_mut().cast(), data.len())?;
+        let mut options = send_options(0, timeout);
+        let mut bytes_written: ULONG = 0;
+
+        let nt_status;
+        // SAFETY: `wdf_usb_pipe` is a private member of `UsbPipe`, originally returned
+        // by WDF, and this module guarantees that it is always in a valid state. The
+        // descriptor describes `data`, which the framework only reads, and every
+        // pointer outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfUsbTargetPipeWriteSynchronously,
+                self.wdf_usb_pipe,
+                core::ptr::null_mut(),
+                &raw mut options,
+                &raw mut descriptor,
+                &raw mut bytes_written,
+            );
+        }
+        nt_success(nt_status)
+            .then(|| usize::try_from(bytes_written).unwrap_or(usize::MAX))
+            .ok_or(nt_status)
+    }
+
+    /// Configure a continuous reader on the pipe, which keeps
+    /// `number_of_pending_reads` reads of `transfer_length` bytes pending on
+    /// the pipe and passes the data of every completed read to
+    /// [`ContinuousReader::read_complete`]. A `number_of_pending_reads` of 0
+    /// uses the framework default.
+    ///
+    /// The pipe must be a bulk or interrupt IN pipe, and the reader must be
+    /// configured from `EvtDevicePrepareHardware`. The reader starts when the
+    /// pipe is started with [`UsbPipe::start()`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to configure the
+    /// reader. The error variant will contain a [`NTSTATUS`] of the failure.
+    /// Full error documentation is available in the [WDFUsbTargetPipe Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfusb/nf-wdfusb-wdfusbtargetpipeconfigcontinuousreader#return-value)
+    pub fn config_continuous_reader<T: ContinuousReader>(
+        &self,
+        transfer_length: usize,
+        number_of_pending_reads: u8,
+    ) -> Result<(), NTSTATUS> {
+        let config_size: ULONG;
+
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            const CONFIG_SIZE: usize = core::mem::size_of::<WDF_USB_CONTINUOUS_READER_CONFIG>();
+
+            // Manually assert there is not truncation since clippy doesn't work for
+            // compile-time constants
+            const { assert!(CONFIG_SIZE <= ULONG::MAX as usize) }
+
+            config_size = CONFIG_SIZE as ULONG;
+        }
+
+        let mut config = WDF_USB_CONTINUOUS_READER_CONFIG {
+            Size: config_size,
+            TransferLength: transfer_length,
+            NumPendingReads: number_of_pending_reads,
+            EvtUsbTargetPipeReadComplete: Some(evt_usb_target_pipe_read_complete::<T>),
+            EvtUsbTargetPipeReadersFailed: Some(evt_usb_target_pipe_readers_failed::<T>),
+            ..WDF_USB_CONTINUOUS_READER_CONFIG::default()
+        };
+
+        let nt_status;
+        // SAFETY: `wdf_usb_pipe` is a private member of `UsbPipe`, originally returned
+        // by WDF, and this module guarantees that it is always in a valid state.
+        // `config` is fully initialized and outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfUsbTargetPipeConfigContinuousReader,
+                self.wdf_usb_pipe,
+                &mut config as *mut WDF_USB_CONTINUOUS_READER_CONFIG,
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Start the I/O target of the pipe, which starts its continuous reader
+    /// if one is configured. This is typically called from `EvtDeviceD0Entry`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to start the I/O
+    /// target. The error variant will contain a [`NTSTATUS`] of the failure.
+    /// Full error documentation is available in the [WDFIoTarget Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfiotarget/nf-wdfiotarget-wdfiotargetstart#return-value)
+    pub fn start(&self) -> Result<(), NTSTATUS> {
+        let io_target = self.io_target();
+        let nt_status;
+        // SAFETY: `io_target` is the I/O target of the pipe, which is valid for as
+        // long as the pipe.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(WdfIoTargetStart, io_target);
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Stop the I/O target of the pipe, which stops its continuous reader if
+    /// one is configured. `action` selects what happens to the requests that
+    /// were already sent to the pipe. This is typically called from
+    /// `EvtDeviceD0Exit`.
+    pub fn stop(&self, action: WDF_IO_TARGET_SENT_IO_ACTION) {
+        let io_target = self.io_target();
+        // SAFETY: `io_target` is the I/O target of the pipe, which is valid for as
+        // long as the pipe.
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfIoTargetStop, io_target, action);
+        }
+    }
+
+    fn io_target(&self) -> WDFIOTARGET {
+        let io_target;
+        // SAFETY: `wdf_usb_pipe` is a private member of `UsbPipe`, originally returned
+        // by WDF, and this module guarantees that it is always in a valid state.
+        unsafe {
+            io_target =
+                call_unsafe_wdf_function_binding!(WdfUsbTargetPipeGetIoTarget, self.wdf_usb_pipe);
+        }
+        io_target
+    }
+}
+
+/// `EvtUsbTargetPipeReadComplete` callback that forwards to
+/// [`ContinuousReader::read_complete`]
+unsafe extern "C" fn evt_usb_target_pipe_read_complete<T: ContinuousReader>(
+    wdf_usb_pipe: WDFUSBPIPE,
+    buffer: WDFMEMORY,
+    number_of_bytes_transferred: usize,
+    _context: WDFCONTEXT,
+) {
+    let data: *mut u8;
+    // SAFETY: `buffer` is the memory object of the completed read, which the
+    // framework keeps alive for the duration of the callback.
+    unsafe {
+        data = call_unsafe_wdf_function_binding!(WdfMemoryGetBuffer, buffer, core::ptr::null_mut())
+            .cast();
+    }
+    // SAFETY: The framework wrote `number_of_bytes_transferred` bytes to the start
+    // of `buffer`, which stays valid until the callback returns.
+    let data = unsafe { core::slice::from_raw_parts(data, number_of_bytes_transferred) };
+    T::read_complete(UsbPipe { wdf_usb_pipe }, data);
+}
+
+/// `EvtUsbTargetPipeReadersFailed` callback that forwards to
+/// [`ContinuousReader::readers_failed`]
+unsafe extern "C" fn evt_usb_target_pipe_readers_failed<T: ContinuousReader>(
+    wdf_usb_pipe: WDFUSBPIPE,
+    nt_status: NTSTATUS,
+    usbd_status: USBD_STATUS,
+) -> BOOLEAN {
+    BOOLEAN::from(T::readers_failed(
+        UsbPipe { wdf_usb_pipe },
+        nt_status,
+        usbd_status,
+    ))
+}
@@ -0,0 +1,219 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    time::Duration,
+};
+
+use wdk_sys::{
+    LONGLONG,
+    NTSTATUS,
+    STATUS_TIMEOUT,
+    WDF_OBJECT_ATTRIBUTES,
+    WDFWAITLOCK,
+    call_unsafe_wdf_function_binding,
+};
+
+use super::timer::relative_due_time;
+use crate::{PassiveLevel, nt_success};
+
+/// WDF Wait Lock.
+///
+/// Use framework wait locks to synchronize access to driver data from code that
+/// runs at `IRQL` = `PASSIVE_LEVEL`, ex. in work items. Unlike a [`SpinLock`],
+/// a thread waiting for a wait lock is put to sleep instead of spinning at
+/// `DISPATCH_LEVEL`, so the code holding the lock can access paged memory and
+/// call APIs that must run at `PASSIVE_LEVEL`.
+///
+/// The wait lock owns the data it protects, which is only reachable through
+/// the [`WaitLockGuard`] returned when the lock is acquired, so the data cannot
+/// be accessed without holding the lock. Before a driver can use a framework
+/// wait lock it must call [`WaitLock::try_new()`] to create a [`WaitLock`]. The
+/// driver can then call [`WaitLock::lock()`] to acquire the lock, or
+/// [`WaitLock::try_lock()`] and [`WaitLock::try_lock_with_timeout()`] to give
+/// up if it is not available in time. The lock is released when the returned
+/// [`WaitLockGuard`] is dropped.
+///
+/// The framework deletes the wait lock object with its parent object, which is
+/// the driver object unless another parent is set in the attributes passed to
+/// [`WaitLock::try_new()`].
+///
+/// [`SpinLock`]: crate::wdf::SpinLock
+pub struct WaitLock<T> {
+    wdf_wait_lock: WDFWAITLOCK,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: The wait lock object can be acquired and released from any thread,
+// and moving the lock moves the data it owns
+unsafe impl<T: Send> Send for WaitLock<T> {}
+// SAFETY: The data is only reachable through a guard, and the framework
+// guarantees that a single thread holds the lock, and so a guard, at a time
+unsafe impl<T: Send> Sync for WaitLock<T> {}
+
+impl<T> WaitLock<T> {
+    /// Try to construct a WDF Wait Lock object that protects `data`. Must be
+    /// called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a wait
+    /// lock. The error variant will contain a [`NTSTATUS`] of the failure. Full
+    /// error documentation is available in the [WDFWaitLock Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfsync/nf-wdfsync-wdfwaitlockcreate#return-value)
+    pub fn try_new(attributes: &mut WDF_OBJECT_ATTRIBUTES, data: T) -> Result<Self, NTSTATUS> {
+        let mut wait_lock = Self {
+            wdf_wait_lock: core::ptr::null_mut(),
+            data: UnsafeCell::new(data),
+        };
+
+        let nt_status;
+        // SAFETY: The resulting ffi object is stored in a private member and not
+        // accessible outside of this module, and this module guarantees that it is
+        // always in a valid state.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfWaitLockCreate,
+                attributes,
+                &raw mut wait_lock.wdf_wait_lock,
+            );
+        }
+        nt_success(nt_status).then_some(wait_lock).ok_or(nt_status)
+    }
+
+    /// Try to construct a WDF Wait Lock object that protects `data`. This is
+    /// an alias for [`WaitLock::try_new()`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a wait
+    /// lock. The error variant will contain a [`NTSTATUS`] of the failure. Full
+    /// error documentation is available in the [WDFWaitLock Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfsync/nf-wdfsync-wdfwaitlockcreate#return-value)
+    pub fn create(attributes: &mut WDF_OBJECT_ATTRIBUTES, data: T) -> Result<Self, NTSTATUS> {
+        Self::try_new(attributes, data)
+    }
+
+    /// Acquire the wait lock, waiting indefinitely for it to become available.
+    /// The lock is released when the returned [`WaitLockGuard`] is dropped.
+    pub fn lock(&self, _irql: PassiveLevel) -> WaitLockGuard<'_, T> {
+        // Waiting without a timeout only returns once the lock is acquired
+        let nt_status = self.acquire_raw(None);
+        debug_assert!(nt_success(nt_status) && nt_status != STATUS_TIMEOUT);
+        WaitLockGuard { wait_lock: self }
+    }
+
+    /// Try to acquire the wait lock without waiting. Returns `None` if the
+    /// lock is held. Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn try_lock(&self) -> Option<WaitLockGuard<'_, T>> {
+        self.acquire(Some(0)).ok()
+    }
+
+    /// Try to acquire the wait lock, waiting at most `timeout` for it to
+    /// become available. Returns `None` if the lock is still held once
+    /// `timeout` elapsed. Use [`WaitLock::try_lock()`] to give up immediately
+    /// at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn try_lock_with_timeout(
+        &self,
+        timeout: Duration,
+        _irql: PassiveLevel,
+    ) -> Option<WaitLockGuard<'_, T>> {
+        let timeout = if timeout.is_zero() {
+            0
+        } else {
+            relative_due_time(timeout)
+        };
+        self.acquire(Some(timeout)).ok()
+    }
+
+    /// Acquire the wait lock, waiting at most `timeout` for it to become
+    /// available. The timeout is in 100-nanosecond units, and is relative to
+    /// the current time if negative. With a `timeout` of `None` this waits
+    /// indefinitely, and with a `timeout` of `Some(0)` this returns
+    /// immediately if the lock is not available.
+    ///
+    /// Must be called at `IRQL` = `PASSIVE_LEVEL`, unless `timeout` is
+    /// `Some(0)`, in which case it can be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`STATUS_TIMEOUT`] if the lock could not be
+    /// acquired before `timeout` elapsed.
+    pub fn acquire(&self, timeout: Option<LONGLONG>) -> Result<WaitLockGuard<'_, T>, NTSTATUS> {
+        let nt_status = self.acquire_raw(timeout);
+        // STATUS_TIMEOUT is a success status, so it has to be checked explicitly
+        if nt_status == STATUS_TIMEOUT {
+            return Err(nt_status);
+        }
+        nt_success(nt_status)
+            .then_some(WaitLockGuard { wait_lock: self })
+            .ok_or(nt_status)
+    }
+
+    /// Get the protected data mutably, without acquiring the lock. This is
+    /// possible because `&mut self` guarantees that no guard is alive.
+    pub const fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Consume the wait lock, returning the data it protects
+    pub const fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+
+    fn acquire_raw(&self, timeout: Option<LONGLONG>) -> NTSTATUS {
+        let mut timeout = timeout;
+        let nt_status;
+        // SAFETY: `wdf_wait_lock` is a private member of `WaitLock`, originally created
+        // by WDF, and this module guarantees that it is always in a valid state. The
+        // timeout pointer is either null or points to a value that outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfWaitLockAcquire,
+                self.wdf_wait_lock,
+                timeout
+                    .as_mut()
+                    .map_or(core::ptr::null_mut(), core::ptr::from_mut),
+            );
+        }
+        nt_status
+    }
+
+    fn release(&self) {
+        // SAFETY: `wdf_wait_lock` is a private member of `WaitLock`, originally created
+        // by WDF, and this module guarantees that it is always in a valid state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfWaitLockRelease, self.wdf_wait_lock);
+        }
+    }
+}
+
+/// RAII guard of an acquired [`WaitLock`], through which the data protected by
+/// the lock is accessed. The lock is released when the guard is dropped.
+#[must_use = "the wait lock is released as soon as the guard is dropped"]
+pub struct WaitLockGuard<'a, T> {
+    wait_lock: &'a WaitLock<T>,
+}
+
+impl<T> Deref for WaitLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The guard holds the lock, so no other guard can access the data
+        unsafe { &*self.wait_lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for WaitLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: The guard holds the lock, so no other guard can access the data,
+        // and `&mut self` guarantees the access through this guard is exclusive
+        unsafe { &mut *self.wait_lock.data.get() }
+    }
+}
+
+impl<T> Drop for WaitLockGuard<'_, T> {
+    fn drop(&mut self) {
+        self.wait_lock.release();
+    }
+}
@@ -0,0 +1,402 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::marker::PhantomData;
+
+use wdk_sys::{
+    NTSTATUS,
+    PULONG,
+    PVOID,
+    STATUS_BUFFER_TOO_SMALL,
+    STATUS_SUCCESS,
+    STATUS_WMI_ITEMID_NOT_FOUND,
+    STATUS_WMI_READ_ONLY,
+    ULONG,
+    WDF_NO_OBJECT_ATTRIBUTES,
+    WDF_WMI_INSTANCE_CONFIG,
+    WDF_WMI_PROVIDER_CONFIG,
+    WDFDEVICE,
+    WDFWMIINSTANCE,
+    call_unsafe_wdf_function_binding,
+};
+
+use super::request::{buffer_slice, buffer_slice_mut};
+use crate::{Guid, nt_success, string::UnicodeStr, wdf::Device};
+
+/// Driver-defined WMI data block, exposed to WMI clients through a
+/// [`WmiInstance`].
+///
+/// The block is the `#[repr(C)]` layout of the class declared with
+/// [`WmiDataBlock::GUID`] in the MOF resource of the driver, see
+/// [`Device::assign_mof_resource_name()`]. It is copied bytewise to and from
+/// the buffers of WMI clients.
+///
+/// # Safety
+///
+/// Implementors must be `#[repr(C)]` types without padding bytes, so that
+/// their bytes can be copied to WMI clients, and every bit pattern must be a
+/// valid value, so that blocks written by WMI clients can be copied into them,
+/// ex. structs of integers.
+pub unsafe trait WmiDataBlock: Copy + 'static {
+    /// GUID of the data block, as declared in the MOF resource of the driver
+    const GUID: Guid;
+
+    /// Returns the current value of the block, ex. a snapshot of the
+    /// statistics of the device. This is invoked from the framework's
+    /// `EvtWmiInstanceQueryInstance` callback, at `IRQL` = `PASSIVE_LEVEL`.
+    /// Full documentation is available in the [EvtWmiInstanceQueryInstance Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfwmi/nc-wdfwmi-evt_wdf_wmi_instance_query_instance)
+    fn query(instance: &WmiInstance<Self>) -> Self;
+
+    /// Handles a WMI client setting the whole block to `block`, and returns
+    /// `STATUS_SUCCESS` if it succeeded. Blocks are read-only unless this is
+    /// implemented. This is invoked from the framework's
+    /// `EvtWmiInstanceSetInstance` callback, at `IRQL` = `PASSIVE_LEVEL`. Full
+    /// documentation is available in the [EvtWmiInstanceSetInstance Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfwmi/nc-wdfwmi-evt_wdf_wmi_instance_set_instance)
+    fn set(instance: &WmiInstance<Self>, block: Self) -> NTSTATUS {
+        let _ = (instance, block);
+        STATUS_WMI_READ_ONLY
+    }
+
+    /// Handles a WMI client setting the item `data_item_id` of the block, i.e.
+    /// its `WmiDataId` in the MOF resource, to `data`, and returns
+    /// `STATUS_SUCCESS` if it succeeded. Items are read-only unless this is
+    /// implemented. This is invoked from the framework's
+    /// `EvtWmiInstanceSetItem` callback, at `IRQL` = `PASSIVE_LEVEL`. Full
+    /// documentation is available in the [EvtWmiInstanceSetItem Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfwmi/nc-wdfwmi-evt_wdf_wmi_instance_set_item)
+    fn set_item(instance: &WmiInstance<Self>, data_item_id: ULONG, data: &[u8]) -> NTSTATUS {
+        let _ = (instance, data_item_id, data);
+        STATUS_WMI_READ_ONLY
+    }
+
+    /// Handles a WMI client executing the method `method_id` of the block,
+    /// i.e. its `WmiMethodId` in the MOF resource, and returns the number of
+    /// bytes of output written to `buffer`. The block has no methods unless
+    /// this is implemented. This is invoked from the framework's
+    /// `EvtWmiInstanceExecuteMethod` callback, at `IRQL` = `PASSIVE_LEVEL`.
+    /// Full documentation is available in the [EvtWmiInstanceExecuteMethod Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfwmi/nc-wdfwmi-evt_wdf_wmi_instance_execute_method)
+    ///
+    /// # Errors
+    ///
+    /// The implementation returns the status the WMI client gets if the method
+    /// failed, ex. `STATUS_WMI_ITEMID_NOT_FOUND` if the block has no method
+    /// `method_id`, which the default implementation returns.
+    fn execute_method(
+        instance: &WmiInstance<Self>,
+        method_id: ULONG,
+        buffer: WmiMethodBuffer<'_>,
+    ) -> Result<usize, NTSTATUS> {
+        let _ = (instance, method_id, buffer);
+        Err(STATUS_WMI_ITEMID_NOT_FOUND)
+    }
+}
+
+/// Buffer of a WMI method call, passed to [`WmiDataBlock::execute_method()`].
+///
+/// The framework passes the input of the method and receives its output in the
+/// same buffer, so the output overwrites the input.
+pub struct WmiMethodBuffer<'a> {
+    buffer: &'a mut [u8],
+    input_length: usize,
+    output_length: usize,
+}
+impl WmiMethodBuffer<'_> {
+    /// Get the input of the method
+    #[must_use]
+    pub fn input(&self) -> &[u8] {
+        &self.buffer[..self.input_length]
+    }
+
+    /// Get the buffer that receives the output of the method, which starts
+    /// with the input of the method until it is overwritten
+    #[must_use]
+    pub fn output(&mut self) -> &mut [u8] {
+        &mut self.buffer[..self.output_length]
+    }
+}
+
+/// WDF WMI Instance.
+///
+/// An instance of the WMI data block `T` of a device, which WMI clients query
+/// and set through the callbacks of [`WmiDataBlock`]. The framework registers
+/// the provider of the block with WMI when the instance is created, and
+/// deletes the instance with its device.
+pub struct WmiInstance<T: WmiDataBlock> {
+    wdf_wmi_instance: WDFWMIINSTANCE,
+    block: PhantomData<fn() -> T>,
+}
+
+impl<T: WmiDataBlock> Clone for WmiInstance<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: WmiDataBlock> Copy for WmiInstance<T> {}
+
+impl<T: WmiDataBlock> WmiInstance<T> {
+    /// Try to construct a WMI instance of the block `T` for `device`, and
+    /// register it with WMI. Must be called at `IRQL` = `PASSIVE_LEVEL`,
+    /// usually from `EvtDriverDeviceAdd`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct the
+    /// instance, ex. `STATUS_OBJECT_NAME_COLLISION` if `device` already has a
+    /// provider for [`WmiDataBlock::GUID`]. The error variant will contain a
+    /// [`NTSTATUS`] of the failure. Full error documentation is available in the [WdfWmiInstanceCreate Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfwmi/nf-wdfwmi-wdfwmiinstancecreate#return-value)
+    pub fn try_new(device: &Device) -> Result<Self, NTSTATUS> {
+        let provider_config_size: ULONG;
+        let instance_config_size: ULONG;
+        let block_size: ULONG;
+
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            const PROVIDER_CONFIG_SIZE: usize = core::mem::size_of::<WDF_WMI_PROVIDER_CONFIG>();
+            const INSTANCE_CONFIG_SIZE: usize = core::mem::size_of::<WDF_WMI_INSTANCE_CONFIG>();
+
+            // Manually assert there is not truncation since clippy doesn't work for
+            // compile-time constants
+            const { assert!(PROVIDER_CONFIG_SIZE <= ULONG::MAX as usize) }
+            const { assert!(INSTANCE_CONFIG_SIZE <= ULONG::MAX as usize) }
+            const { assert!(core::mem::size_of::<T>() <= ULONG::MAX as usize) }
+
+            provider_config_size = PROVIDER_CONFIG_SIZE as ULONG;
+            instance_config_size = INSTANCE_CONFIG_SIZE as ULONG;
+            block_size = core::mem::size_of::<T>() as ULONG;
+        }
+
+        let mut provider_config = WDF_WMI_PROVIDER_CONFIG {
+            Size: provider_config_size,
+            Guid: T::GUID.into(),
+            MinInstanceBufferSize: block_size,
+            ..WDF_WMI_PROVIDER_CONFIG::default()
+        };
+        let mut instance_config = WDF_WMI_INSTANCE_CONFIG {
+            Size: instance_config_size,
+            ProviderConfig: &raw mut provider_config,
+            Register: u8::from(true),
+            EvtWmiInstanceQueryInstance: Some(evt_wmi_instance_query_instance::<T>),
+            EvtWmiInstanceSetInstance: Some(evt_wmi_instance_set_instance::<T>),
+            EvtWmiInstanceSetItem: Some(evt_wmi_instance_set_item::<T>),
+            EvtWmiInstanceExecuteMethod: Some(evt_wmi_instance_execute_method::<T>),
+            ..WDF_WMI_INSTANCE_CONFIG::default()
+        };
+        let mut wdf_wmi_instance: WDFWMIINSTANCE = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `device` is a valid device handle, and the configurations outlive
+        // the call. The resulting ffi object is stored in a private member and not
+        // accessible outside of this module, and this module guarantees that it is
+        // always in a valid state.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfWmiInstanceCreate,
+                device.as_raw(),
+                &raw mut instance_config,
+                WDF_NO_OBJECT_ATTRIBUTES,
+                &raw mut wdf_wmi_instance,
+            );
+        }
+        nt_success(nt_status)
+            .then_some(Self {
+                wdf_wmi_instance,
+                block: PhantomData,
+            })
+            .ok_or(nt_status)
+    }
+
+    /// Get the raw handle of the framework WMI instance object
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFWMIINSTANCE {
+        self.wdf_wmi_instance
+    }
+
+    /// Get the device of the instance
+    #[must_use]
+    pub fn get_device(&self) -> Device {
+        let wdf_device: WDFDEVICE;
+        // SAFETY: `wdf_wmi_instance` is a private member of `WmiInstance`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            wdf_device =
+                call_unsafe_wdf_function_binding!(WdfWmiInstanceGetDevice, self.wdf_wmi_instance);
+        }
+        // SAFETY: The device outlives its WMI instances
+        unsafe { Device::from_raw(wdf_device) }
+    }
+
+    /// Send `block` to the WMI clients that subscribed to the events of the
+    /// block. Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the event could not be sent,
+    /// ex. `STATUS_UNSUCCESSFUL` if no WMI client enabled the events of the
+    /// block. Full error documentation is available in the [WdfWmiInstanceFireEvent Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfwmi/nf-wdfwmi-wdfwmiinstancefireevent#return-value)
+    pub fn fire_event(&self, block: &T) -> Result<(), NTSTATUS> {
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        let block_size = core::mem::size_of::<T>() as ULONG;
+
+        let nt_status;
+        // SAFETY: `wdf_wmi_instance` is a private member of `WmiInstance`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state. The framework copies `block`, which outlives the call, and does not
+        // write to it.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfWmiInstanceFireEvent,
+                self.wdf_wmi_instance,
+                block_size,
+                core::ptr::from_ref(block).cast_mut().cast(),
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+}
+
+impl Device {
+    /// Set the name of the MOF resource of the driver, which declares the
+    /// classes of its [`WmiDataBlock`]s, ex. `MofResource` for a resource
+    /// compiled into the driver with `MofResource MOFDATA "driver.bmf"`. Must
+    /// be called at `IRQL` = `PASSIVE_LEVEL`, before the first
+    /// [`WmiInstance`] of the device is created.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the name could not be assigned,
+    /// ex. `STATUS_INVALID_DEVICE_REQUEST` if a name was assigned already. Full error documentation is available in the [WdfDeviceAssignMofResourceName Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdeviceassignmofresourcename#return-value)
+    pub fn assign_mof_resource_name(&self, name: UnicodeStr<'_>) -> Result<(), NTSTATUS> {
+        let name = name.as_raw();
+
+        let nt_status;
+        // SAFETY: The device is valid for as long as `self` is, as guaranteed to
+        // `Device::from_raw`. The framework copies the name, which outlives the call.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfDeviceAssignMofResourceName,
+                self.as_raw(),
+                &raw const name,
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+}
+
+/// `EvtWmiInstanceQueryInstance` callback that forwards to
+/// [`WmiDataBlock::query()`]
+unsafe extern "C" fn evt_wmi_instance_query_instance<T: WmiDataBlock>(
+    wdf_wmi_instance: WDFWMIINSTANCE,
+    out_buffer_size: ULONG,
+    out_buffer: PVOID,
+    buffer_used: PULONG,
+) -> NTSTATUS {
+    // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+    #[allow(clippy::cast_possible_truncation)]
+    let block_size = core::mem::size_of::<T>() as ULONG;
+
+    // SAFETY: The framework passes a valid pointer that receives the number of
+    // bytes written, or needed if the buffer is too small
+    unsafe {
+        *buffer_used = block_size;
+    }
+    if out_buffer_size < block_size {
+        return STATUS_BUFFER_TOO_SMALL;
+    }
+
+    let instance = WmiInstance::<T> {
+        wdf_wmi_instance,
+        block: PhantomData,
+    };
+    let block = T::query(&instance);
+    // SAFETY: `out_buffer` is writable for `out_buffer_size` bytes, which is at
+    // least the size of `T`, and may not be aligned for `T`
+    unsafe {
+        out_buffer.cast::<T>().write_unaligned(block);
+    }
+    STATUS_SUCCESS
+}
+
+/// `EvtWmiInstanceSetInstance` callback that forwards to
+/// [`WmiDataBlock::set()`]
+unsafe extern "C" fn evt_wmi_instance_set_instance<T: WmiDataBlock>(
+    wdf_wmi_instance: WDFWMIINSTANCE,
+    in_buffer_size: ULONG,
+    in_buffer: PVOID,
+) -> NTSTATUS {
+    if (in_buffer_size as usize) < core::mem::size_of::<T>() {
+        return STATUS_BUFFER_TOO_SMALL;
+    }
+    // SAFETY: `in_buffer` is readable for `in_buffer_size` bytes, which is at least
+    // the size of `T`, and may not be aligned for `T`. Implementors of
+    // `WmiDataBlock` guarantee that any bytes are a valid `T`.
+    let block = unsafe { in_buffer.cast::<T>().read_unaligned() };
+
+    let instance = WmiInstance::<T> {
+        wdf_wmi_instance,
+        block: PhantomData,
+    };
+    T::set(&instance, block)
+}
+
+/// `EvtWmiInstanceSetItem` callback that forwards to
+/// [`WmiDataBlock::set_item()`]
+unsafe extern "C" fn evt_wmi_instance_set_item<T: WmiDataBlock>(
+    wdf_wmi_instance: WDFWMIINSTANCE,
+    data_item_id: ULONG,
+    in_buffer_size: ULONG,
+    in_buffer: PVOID,
+) -> NTSTATUS {
+    // SAFETY: The framework passes a buffer readable for `in_buffer_size` bytes,
+    // which is not written until the callback returns
+    let data = unsafe { buffer_slice(in_buffer, in_buffer_size as usize) };
+
+    let instance = WmiInstance::<T> {
+        wdf_wmi_instance,
+        block: PhantomData,
+    };
+    T::set_item(&instance, data_item_id, data)
+}
+
+/// `EvtWmiInstanceExecuteMethod` callback that forwards to
+/// [`WmiDataBlock::execute_method()`]
+unsafe extern "C" fn evt_wmi_instance_execute_method<T: WmiDataBlock>(
+    wdf_wmi_instance: WDFWMIINSTANCE,
+    method_id: ULONG,
+    in_buffer_size: ULONG,
+    out_buffer_size: ULONG,
+    buffer: PVOID,
+    buffer_used: PULONG,
+) -> NTSTATUS {
+    let input_length = in_buffer_size as usize;
+    let output_length = out_buffer_size as usize;
+    // SAFETY: The framework passes a buffer that holds the input and receives the
+    // output, so it is writable for the larger of the two sizes. The driver has
+    // exclusive access to it until the callback returns.
+    let buffer = unsafe { buffer_slice_mut(buffer, input_length.max(output_length)) };
+
+    let instance = WmiInstance::<T> {
+        wdf_wmi_instance,
+        block: PhantomData,
+    };
+    let (nt_status, used) = match T::execute_method(
+        &instance,
+        method_id,
+        WmiMethodBuffer {
+            buffer,
+            input_length,
+            output_length,
+        },
+    ) {
+        Ok(used) => (STATUS_SUCCESS, used.min(output_length)),
+        Err(nt_status) => (nt_status, 0),
+    };
+    // SAFETY: The framework passes a valid pointer that receives the number of
+    // bytes of output
+    unsafe {
+        *buffer_used = ULONG::try_from(used).unwrap_or(ULONG::MAX);
+    }
+    nt_status
+}
@@ -0,0 +1,296 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use wdk_sys::{
+    NTSTATUS,
+    PFN_WDF_WORKITEM,
+    ULONG,
+    WDF_WORKITEM_CONFIG,
+    WDFOBJECT,
+    WDFWORKITEM,
+    call_unsafe_wdf_function_binding,
+};
+
+use crate::{
+    PassiveLevel,
+    nt_success,
+    wdf::{
+        Device,
+        context::{
+            inherited_object_attributes,
+            set_value_context,
+            value_context,
+            value_context_or_init,
+        },
+    },
+};
+
+/// Driver-defined callback invoked when a [`WorkItem`] runs.
+///
+/// Set with [`WorkItem::try_new()`].
+pub trait WorkItemCallback {
+    /// Performs the work deferred with [`WorkItem::enqueue()`]. This is invoked
+    /// from the framework's `EvtWorkItem` callback, on a system worker thread,
+    /// at `IRQL` = `PASSIVE_LEVEL`, which `irql` attests. Full documentation is available in the [EvtWorkItem Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfworkitem/nc-wdfworkitem-evt_wdf_workitem)
+    fn run(work_item: &WorkItem, irql: PassiveLevel);
+}
+
+/// WDF Work Item.
+///
+/// A work item defers work to a system worker thread, where it runs at `IRQL`
+/// = `PASSIVE_LEVEL`, so that code running at `DISPATCH_LEVEL`, ex. a timer
+/// callback or a DPC, can hand off work that waits, touches paged memory or
+/// calls APIs restricted to `PASSIVE_LEVEL`. The work item is enqueued with
+/// [`WorkItem::enqueue()`] and runs its callback once per enqueue that is not
+/// already pending.
+///
+/// The callback is either a [`WorkItemCallback`], set with
+/// [`WorkItem::try_new()`], or a closure, set with
+/// [`WorkItem::try_new_with()`]. The work item is deleted along with its
+/// device, after its pending callback ran. The framework does not serialize
+/// the callback with the callbacks of the device.
+#[derive(Clone, Copy)]
+pub struct WorkItem {
+    wdf_work_item: WDFWORKITEM,
+}
+impl WorkItem {
+    /// Wrap a handle to a framework work item object
+    ///
+    /// # Safety
+    ///
+    /// `work_item` must be a valid handle to a framework work item object,
+    /// that stays valid for as long as the returned [`WorkItem`] is used.
+    #[must_use]
+    pub const unsafe fn from_raw(work_item: WDFWORKITEM) -> Self {
+        Self {
+            wdf_work_item: work_item,
+        }
+    }
+
+    /// Get the raw handle of the framework work item object
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFWORKITEM {
+        self.wdf_work_item
+    }
+
+    /// Try to construct a WDF Work Item object for `device`, that invokes
+    /// [`WorkItemCallback::run`] of `T` when it runs. Must be called at `IRQL`
+    /// <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a work
+    /// item. The error variant will contain a [`NTSTATUS`] of the failure. Full
+    /// error documentation is available in the [WDFWorkItem Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfworkitem/nf-wdfworkitem-wdfworkitemcreate#return-value)
+    pub fn try_new<T: WorkItemCallback>(device: &Device) -> Result<Self, NTSTATUS> {
+        Self::create_with_callback(device, Some(evt_work_item::<T>))
+    }
+
+    /// Try to construct a WDF Work Item object for `device`, that invokes
+    /// [`WorkItemCallback::run`] of `T` when it runs. This is an alias for
+    /// [`WorkItem::try_new()`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a work
+    /// item. The error variant will contain a [`NTSTATUS`] of the failure. Full
+    /// error documentation is available in the [WDFWorkItem Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfworkitem/nf-wdfworkitem-wdfworkitemcreate#return-value)
+    pub fn create<T: WorkItemCallback>(device: &Device) -> Result<Self, NTSTATUS> {
+        Self::try_new::<T>(device)
+    }
+
+    /// Try to construct a WDF Work Item object for `device`, that invokes
+    /// `callback` when it runs, with the [`PassiveLevel`] of the system worker
+    /// thread it runs on. `callback` is stored in the context of the work
+    /// item, so the work item cannot hold a context set with
+    /// [`WorkItem::set_context()`]; the closure captures the state it needs
+    /// instead. Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to construct a work
+    /// item, or to allocate its context. `callback` is dropped in that case.
+    /// The error variant will contain a [`NTSTATUS`] of the failure. Full
+    /// error documentation is available in the [WDFWorkItem Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfworkitem/nf-wdfworkitem-wdfworkitemcreate#return-value)
+    pub fn try_new_with<F>(device: &Device, callback: F) -> Result<Self, NTSTATUS>
+    where
+        F: Fn(&Self, PassiveLevel) + Send + Sync + 'static,
+    {
+        let work_item = Self::create_with_callback(device, Some(evt_work_item_closure::<F>))?;
+        // SAFETY: The work item was just created, and is not enqueued yet, so its
+        // callback does not look for the closure before it is stored
+        if let Err(nt_status) = unsafe { set_value_context(work_item.as_object(), callback) } {
+            // SAFETY: The work item was never returned to the caller, so it is only used
+            // here
+            unsafe {
+                call_unsafe_wdf_function_binding!(WdfObjectDelete, work_item.as_object());
+            }
+            return Err(nt_status);
+        }
+        Ok(work_item)
+    }
+
+    /// Get the device the work item belongs to. Must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn get_device(&self) -> Device {
+        let parent: WDFOBJECT;
+        // SAFETY: `wdf_work_item` is a private member of `WorkItem`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            parent =
+                call_unsafe_wdf_function_binding!(WdfWorkItemGetParentObject, self.wdf_work_item);
+        }
+        // SAFETY: Work items are created with their device as parent, which
+        // outlives them
+        unsafe { Device::from_raw(parent.cast()) }
+    }
+
+    /// Enqueue the work item, so that its callback runs on a system worker
+    /// thread. Enqueuing a work item whose callback is pending has no effect,
+    /// while enqueuing it from its running callback runs the callback again.
+    /// Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn enqueue(&self) {
+        // SAFETY: `wdf_work_item` is a private member of `WorkItem`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfWorkItemEnqueue, self.wdf_work_item);
+        }
+    }
+
+    /// Wait for the pending callback of the work item, if any, to run to
+    /// completion, ex. before releasing the resources it uses. Must not be
+    /// called from the callback of the work item.
+    pub fn flush(&self, _irql: PassiveLevel) {
+        // SAFETY: `wdf_work_item` is a private member of `WorkItem`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            call_unsafe_wdf_function_binding!(WdfWorkItemFlush, self.wdf_work_item);
+        }
+    }
+
+    /// Move `context` into the context space of the work item, so that its
+    /// callback can retrieve it with [`WorkItem::context()`]. The context is
+    /// dropped when the work item is deleted. A work item holds a single
+    /// context, which it is typically given right after it is created.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the context could not be stored.
+    /// `context` is dropped in that case:
+    /// * `STATUS_OBJECT_NAME_COLLISION` if the work item already holds a
+    ///   context, or was created with [`WorkItem::try_new_with()`]
+    /// * `STATUS_INSUFFICIENT_RESOURCES` if the context space could not be
+    ///   allocated
+    pub fn set_context<T: Send + Sync + 'static>(&self, context: T) -> Result<&T, NTSTATUS> {
+        // SAFETY: `wdf_work_item` is a private member of `WorkItem`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe { set_value_context(self.as_object(), context) }
+    }
+
+    /// Get the context of the work item set with [`WorkItem::set_context()`],
+    /// or `None` if the work item holds no context of type `T`
+    #[must_use]
+    pub fn context<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        // SAFETY: `wdf_work_item` is a private member of `WorkItem`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe { value_context(self.as_object()) }
+    }
+
+    /// Get the context of the work item set with [`WorkItem::set_context()`],
+    /// or move the context returned by `init` into the work item if it holds
+    /// none yet. `init` is invoked at most once, so that concurrent callers
+    /// all get the same context.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the context could not be set:
+    /// * `STATUS_OBJECT_NAME_COLLISION` if the work item already holds a
+    ///   context of another type than `T`, or was created with
+    ///   [`WorkItem::try_new_with()`]
+    /// * `STATUS_INSUFFICIENT_RESOURCES` if the context space could not be
+    ///   allocated
+    pub fn context_or_init<T: Send + Sync + 'static>(
+        &self,
+        init: impl FnOnce() -> T,
+    ) -> Result<&T, NTSTATUS> {
+        // SAFETY: `wdf_work_item` is a private member of `WorkItem`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe { value_context_or_init(self.as_object(), init) }
+    }
+
+    /// Create a work item parented to `device` that invokes `callback`
+    fn create_with_callback(device: &Device, callback: PFN_WDF_WORKITEM) -> Result<Self, NTSTATUS> {
+        let config_size: ULONG;
+        // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            const CONFIG_SIZE: usize = core::mem::size_of::<WDF_WORKITEM_CONFIG>();
+
+            // Manually assert there is not truncation since clippy doesn't work for
+            // compile-time constants
+            const { assert!(CONFIG_SIZE <= ULONG::MAX as usize) }
+
+            config_size = CONFIG_SIZE as ULONG;
+        }
+        let mut config = WDF_WORKITEM_CONFIG {
+            Size: config_size,
+            EvtWorkItemFunc: callback,
+            AutomaticSerialization: 0,
+        };
+        let mut attributes = inherited_object_attributes();
+        attributes.ParentObject = device.as_raw().cast();
+
+        let mut work_item = Self {
+            wdf_work_item: core::ptr::null_mut(),
+        };
+
+        let nt_status;
+        // SAFETY: `config` and `attributes` describe the work item and outlive the
+        // call. The resulting ffi object is stored in a private member and not
+        // accessible outside of this module, and this module guarantees that it is
+        // always in a valid state.
+        unsafe {
+            nt_status = call_unsafe_wdf_function_binding!(
+                WdfWorkItemCreate,
+                &raw mut config,
+                &raw mut attributes,
+                &raw mut work_item.wdf_work_item,
+            );
+        }
+        nt_success(nt_status).then_some(work_item).ok_or(nt_status)
+    }
+
+    const fn as_object(&self) -> WDFOBJECT {
+        self.wdf_work_item.cast()
+    }
+}
+
+/// `EvtWorkItem` callback that forwards to [`WorkItemCallback::run`]
+unsafe extern "C" fn evt_work_item<T: WorkItemCallback>(wdf_work_item: WDFWORKITEM) {
+    // SAFETY: The framework invokes work item callbacks at `PASSIVE_LEVEL`
+    let irql = unsafe { PassiveLevel::new_unchecked() };
+    T::run(&WorkItem { wdf_work_item }, irql);
+}
+
+/// `EvtWorkItem` callback that invokes the closure stored by
+/// [`WorkItem::try_new_with()`]
+unsafe extern "C" fn evt_work_item_closure<F>(wdf_work_item: WDFWORKITEM)
+where
+    F: Fn(&WorkItem, PassiveLevel) + Send + Sync + 'static,
+{
+    let work_item = WorkItem { wdf_work_item };
+    // SAFETY: The framework invokes work item callbacks at `PASSIVE_LEVEL`
+    let irql = unsafe { PassiveLevel::new_unchecked() };
+    // SAFETY: The framework passes the work item that runs, which is valid until
+    // the callback returns
+    if let Some(callback) = unsafe { value_context::<F>(work_item.as_object()) } {
+        callback(&work_item, irql);
+    }
+}
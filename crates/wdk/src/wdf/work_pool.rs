@@ -0,0 +1,184 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use wdk_sys::{NTSTATUS, STATUS_INVALID_PARAMETER};
+
+use crate::{
+    alloc_failure::alloc_failed,
+    wdf::{Arc, Device, Memory, SpinLock, WorkItem, context::inherited_object_attributes},
+};
+
+/// Job queued on a [`WorkPool`], that runs its closure and then its completion
+/// callback
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Fixed-size pool of framework work items, that runs CPU-bound jobs over
+/// [`Memory`] buffers off the I/O path, ex. computing checksums or compressing
+/// the data of requests.
+///
+/// Jobs are submitted with [`WorkPool::submit()`], at `IRQL` <=
+/// `DISPATCH_LEVEL`, into a queue shared by the workers of the pool. Each
+/// submission enqueues the next worker in turn, and a running worker takes
+/// jobs out of the queue until it is empty, so that jobs submitted together
+/// run in parallel on up to as many system worker threads as the pool has
+/// workers. Jobs and their completion callbacks run at `IRQL` =
+/// `PASSIVE_LEVEL`.
+///
+/// The workers are deleted along with the device of the pool, after their
+/// pending callback ran. Jobs that are still queued then are dropped without
+/// running, along with their buffers, once the pool is dropped.
+pub struct WorkPool {
+    queue: Arc<JobQueue>,
+    workers: Vec<WorkItem>,
+    next_worker: AtomicUsize,
+}
+
+impl WorkPool {
+    /// Try to construct a pool of `workers` work items of `device`. Must be
+    /// called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the pool could not be
+    /// constructed:
+    /// * `STATUS_INVALID_PARAMETER` if `workers` is 0
+    /// * `STATUS_INSUFFICIENT_RESOURCES` if the workers could not be allocated
+    /// * the [`NTSTATUS`] of the failure if WDF fails to construct the spin
+    ///   lock of the queue, the object holding the queue, or a work item
+    pub fn try_new(device: &Device, workers: usize) -> Result<Self, NTSTATUS> {
+        if workers == 0 {
+            return Err(STATUS_INVALID_PARAMETER);
+        }
+        let queue = Arc::try_new(JobQueue {
+            lock: SpinLock::try_new(&mut inherited_object_attributes())?,
+            jobs: UnsafeCell::new(VecDeque::new()),
+        })?;
+
+        let mut work_items = Vec::new();
+        work_items
+            .try_reserve_exact(workers)
+            .map_err(|_| alloc_failed(workers * core::mem::size_of::<WorkItem>()))?;
+        for _ in 0..workers {
+            let queue = queue.clone();
+            work_items.push(WorkItem::try_new_with(device, move |_, _| {
+                queue.run_jobs()
+            })?);
+        }
+        Ok(Self {
+            queue,
+            workers: work_items,
+            next_worker: AtomicUsize::new(0),
+        })
+    }
+
+    /// Try to construct a pool of `workers` work items of `device`. This is an
+    /// alias for [`WorkPool::try_new()`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the pool could not be
+    /// constructed:
+    /// * `STATUS_INVALID_PARAMETER` if `workers` is 0
+    /// * `STATUS_INSUFFICIENT_RESOURCES` if the workers could not be allocated
+    /// * the [`NTSTATUS`] of the failure if WDF fails to construct the spin
+    ///   lock of the queue, the object holding the queue, or a work item
+    pub fn create(device: &Device, workers: usize) -> Result<Self, NTSTATUS> {
+        Self::try_new(device, workers)
+    }
+
+    /// Get the number of workers of the pool
+    #[must_use]
+    pub fn workers(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Queue a job that runs `job` over `memory` on a worker of the pool, and
+    /// then passes `memory` back to `completion` along with the output of
+    /// `job`. Must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `STATUS_INSUFFICIENT_RESOURCES` if the job
+    /// could not be queued. `memory`, `job` and `completion` are dropped in
+    /// that case.
+    pub fn submit<J, C, R>(
+        &self,
+        memory: Memory<'static>,
+        job: J,
+        completion: C,
+    ) -> Result<(), NTSTATUS>
+    where
+        J: FnOnce(&mut Memory<'static>) -> R + Send + 'static,
+        C: FnOnce(Memory<'static>, R) + Send + 'static,
+    {
+        let job: Job = try_box(move || {
+            let mut memory = memory;
+            let output = job(&mut memory);
+            completion(memory, output);
+        })?;
+        {
+            let _guard = self.queue.lock.lock();
+            // SAFETY: `lock` is held
+            let jobs = unsafe { &mut *self.queue.jobs.get() };
+            jobs.try_reserve(1)
+                .map_err(|_| alloc_failed(core::mem::size_of::<Job>()))?;
+            jobs.push_back(job);
+        }
+
+        let worker = self.next_worker.fetch_add(1, Ordering::Relaxed) % self.workers.len();
+        self.workers[worker].enqueue();
+        Ok(())
+    }
+}
+
+/// Queue of the jobs of a [`WorkPool`], shared by its workers
+struct JobQueue {
+    lock: SpinLock,
+    /// Only accessed with `lock` held
+    jobs: UnsafeCell<VecDeque<Job>>,
+}
+
+// SAFETY: The jobs are only accessed with `lock` held, and are `Send`. The
+// framework spin lock handle can be used from any thread.
+unsafe impl Send for JobQueue {}
+// SAFETY: The jobs are only accessed with `lock` held, and are `Send`. The
+// framework spin lock handle can be used from any thread.
+unsafe impl Sync for JobQueue {}
+
+impl JobQueue {
+    /// Run the queued jobs until the queue is empty. The lock is released
+    /// while a job runs, so that other workers take the next jobs.
+    fn run_jobs(&self) {
+        loop {
+            let job = {
+                let _guard = self.lock.lock();
+                // SAFETY: `lock` is held
+                unsafe { &mut *self.jobs.get() }.pop_front()
+            };
+            let Some(job) = job else {
+                return;
+            };
+            job();
+        }
+    }
+}
+
+/// Move `value` into a new [`Box`], without panicking when it cannot be
+/// allocated
+fn try_box<T>(value: T) -> Result<Box<T>, NTSTATUS> {
+    let mut storage = Vec::new();
+    storage
+        .try_reserve_exact(1)
+        .map_err(|_| alloc_failed(core::mem::size_of::<T>()))?;
+    storage.push(value);
+    let slice = Box::into_raw(storage.into_boxed_slice());
+    // SAFETY: A boxed slice of a single `T` is allocated with the layout of `T`,
+    // so it can be owned by a `Box<T>`
+    Ok(unsafe { Box::from_raw(slice.cast::<T>()) })
+}
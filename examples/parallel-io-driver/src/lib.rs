@@ -0,0 +1,563 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! # Parallel I/O Driver
+//!
+//! This is a sample KMDF driver that serves device control requests from a
+//! parallel I/O queue, so that many requests are in flight at a time. It is
+//! used with the `parallel-io-load` load generator of the `sample-test` crate
+//! as a regression test and throughput benchmark of the [`wdk::wdf::Arc`] and
+//! [`wdk::wdf::SpinLock`] primitives, which every request goes through.
+//!
+//! The driver serves any number of devices, each with its own queue, timer and
+//! state, which it keeps in the context of the device. Each device is opened
+//! as `\\.\ParallelIoDriver<index>`, where the index is the lowest one not in
+//! use by another device of the driver. Every device serves four device
+//! control codes:
+//! * [`IOCTL_PARALLEL_IO_ECHO`] completes the request right away.
+//! * [`IOCTL_PARALLEL_IO_PEND`] pends the request, cancelably, in a
+//!   [`TimedRequestStore`] until the timeout in milliseconds in its input
+//!   buffer expires.
+//! * [`IOCTL_PARALLEL_IO_STATS`] returns the [`Counts`] of requests served by
+//!   the device.
+//! * [`IOCTL_PARALLEL_IO_DRIVER_STATS`] returns the [`Counts`] of requests
+//!   served by all the devices of the driver.
+//!
+//! The maximum number of requests the driver owns at a time is read from the
+//! `PresentedRequestsLimit` value of the `Parameters` key of the driver, where
+//! 0 means no limit.
+
+#![no_std]
+
+extern crate alloc;
+
+#[cfg(not(test))]
+extern crate wdk_panic;
+
+use core::{cell::UnsafeCell, time::Duration};
+
+use wdk::{
+    ControlCode,
+    RequiredAccess,
+    TransferMethod,
+    alloc_failure::try_format,
+    PassiveLevel,
+    nt_success,
+    println,
+    string::UnicodeString,
+    wdf::{
+        Arc,
+        Device,
+        Driver,
+        IoQueue,
+        IoQueueDispatchType,
+        IoQueueStopCallback,
+        Request,
+        RequestStopActionFlags,
+        SpinLock,
+        TimedRequestStore,
+        Timer,
+    },
+};
+#[cfg(not(test))]
+use wdk_alloc::WdkAllocator;
+use wdk_sys::{
+    _WDF_EXECUTION_LEVEL,
+    _WDF_SYNCHRONIZATION_SCOPE,
+    _WDF_TRI_STATE,
+    DRIVER_OBJECT,
+    FILE_DEVICE_UNKNOWN,
+    KEY_QUERY_VALUE,
+    NTSTATUS,
+    PCUNICODE_STRING,
+    PDRIVER_OBJECT,
+    STATUS_INVALID_DEVICE_REQUEST,
+    STATUS_INVALID_DEVICE_STATE,
+    STATUS_OBJECT_NAME_COLLISION,
+    STATUS_SUCCESS,
+    ULONG,
+    WDF_DRIVER_CONFIG,
+    WDF_IO_QUEUE_CONFIG,
+    WDF_NO_HANDLE,
+    WDF_NO_OBJECT_ATTRIBUTES,
+    WDF_OBJECT_ATTRIBUTES,
+    WDF_TIMER_CONFIG,
+    WDFDEVICE,
+    WDFDEVICE_INIT,
+    WDFDRIVER,
+    WDFKEY,
+    WDFOBJECT,
+    WDFQUEUE,
+    WDFREQUEST,
+    WDFTIMER,
+    call_unsafe_wdf_function_binding,
+};
+
+#[cfg(not(test))]
+#[global_allocator]
+static GLOBAL_ALLOCATOR: WdkAllocator = WdkAllocator;
+
+/// Device control code that completes the request right away
+pub const IOCTL_PARALLEL_IO_ECHO: ControlCode = ctl_code(0x800);
+/// Device control code that pends the request until the timeout, a `u32` of
+/// milliseconds in the input buffer, expires or the request is cancelled
+pub const IOCTL_PARALLEL_IO_PEND: ControlCode = ctl_code(0x801);
+/// Device control code that returns the [`Counts`] of requests served in the
+/// output buffer
+pub const IOCTL_PARALLEL_IO_STATS: ControlCode = ctl_code(0x802);
+/// Device control code that returns the sum of the [`Counts`] of requests
+/// served by all the devices of the driver in the output buffer
+pub const IOCTL_PARALLEL_IO_DRIVER_STATS: ControlCode = ctl_code(0x803);
+
+/// Prefix of the names applications open the devices by, followed by the index
+/// of the device, ex. `\\.\ParallelIoDriver0`
+const SYMBOLIC_LINK_NAME_PREFIX: &str = r"\DosDevices\ParallelIoDriver";
+
+/// Maximum number of devices the driver serves at a time
+const MAX_DEVICES: u32 = 8;
+
+/// Value of the `Parameters` key of the driver that holds the maximum number
+/// of requests the driver owns at a time
+const PRESENTED_REQUESTS_LIMIT_VALUE_NAME: &str = "PresentedRequestsLimit";
+
+/// Maximum number of requests pended at a time. Further pend requests fail
+/// with `STATUS_INSUFFICIENT_RESOURCES`.
+const MAX_PENDED_REQUESTS: usize = 64;
+
+/// Period of the timer that completes the pended requests whose timeout
+/// expired
+const EXPIRY_TIMER_PERIOD_MS: ULONG = 10;
+
+/// Counts of the requests served by a device, returned by
+/// [`IOCTL_PARALLEL_IO_STATS`], or by all the devices of the driver, returned
+/// by [`IOCTL_PARALLEL_IO_DRIVER_STATS`]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Counts {
+    /// Requests completed by [`IOCTL_PARALLEL_IO_ECHO`]
+    pub echoed: u64,
+    /// Requests pended by [`IOCTL_PARALLEL_IO_PEND`]
+    pub pended: u64,
+    /// Pended requests completed because their timeout expired
+    pub timed_out: u64,
+}
+
+impl Counts {
+    const fn add(&mut self, other: &Self) {
+        self.echoed += other.echoed;
+        self.pended += other.pended;
+        self.timed_out += other.timed_out;
+    }
+}
+
+/// [`Counts`] of the device, updated by all the requests in flight
+struct Stats {
+    lock: SpinLock,
+    /// Only accessed with `lock` held
+    counts: UnsafeCell<Counts>,
+}
+
+// SAFETY: `counts` is only accessed with `lock` held
+unsafe impl Send for Stats {}
+// SAFETY: `counts` is only accessed with `lock` held
+unsafe impl Sync for Stats {}
+
+impl Stats {
+    fn record(&self, update: impl FnOnce(&mut Counts)) {
+        let _guard = self.lock.lock();
+        // SAFETY: `counts` is only accessed with `lock` held, so this is the only
+        // reference to it
+        update(unsafe { &mut *self.counts.get() });
+    }
+
+    fn snapshot(&self) -> Counts {
+        let _guard = self.lock.lock();
+        // SAFETY: `counts` is only accessed with `lock` held, so it is not
+        // modified while it is copied
+        unsafe { *self.counts.get() }
+    }
+}
+
+/// State of a device, shared by its request handlers and its timer. It is
+/// stored in the context of the device when the device is added, and dropped
+/// with the device.
+struct DeviceState {
+    /// Index in the name of the symbolic link of the device
+    index: u32,
+    pended: Arc<TimedRequestStore<MAX_PENDED_REQUESTS>>,
+    stats: Arc<Stats>,
+}
+
+/// `DriverEntry` function required by WDF
+///
+/// # Safety
+/// Function is unsafe since it dereferences raw pointers passed to it from WDF
+// SAFETY: "DriverEntry" is the required symbol name for Windows driver entry points.
+// No other function in this compilation unit exports this name, preventing symbol conflicts.
+#[unsafe(export_name = "DriverEntry")] // WDF expects a symbol with the name DriverEntry
+pub unsafe extern "system" fn driver_entry(
+    driver: &mut DRIVER_OBJECT,
+    registry_path: PCUNICODE_STRING,
+) -> NTSTATUS {
+    driver.DriverUnload = Some(driver_exit);
+
+    let mut driver_config = WDF_DRIVER_CONFIG {
+        Size: wdf_struct_size::<WDF_DRIVER_CONFIG>(),
+        EvtDriverDeviceAdd: Some(evt_driver_device_add),
+        ..WDF_DRIVER_CONFIG::default()
+    };
+
+    let nt_status;
+    // SAFETY: This is safe because:
+    //         1. `driver` and `registry_path` are provided by `DriverEntry` and are
+    //            never null
+    //         2. the driver attributes are allowed to be null
+    //         3. `driver_config` is a valid `WDF_DRIVER_CONFIG`
+    //         4. the driver handle output is allowed to be null
+    unsafe {
+        nt_status = call_unsafe_wdf_function_binding!(
+            WdfDriverCreate,
+            driver as PDRIVER_OBJECT,
+            registry_path,
+            WDF_NO_OBJECT_ATTRIBUTES,
+            &mut driver_config,
+            WDF_NO_HANDLE.cast::<WDFDRIVER>(),
+        );
+    }
+    nt_status
+}
+
+extern "C" fn evt_driver_device_add(
+    driver: WDFDRIVER,
+    mut device_init: *mut WDFDEVICE_INIT,
+) -> NTSTATUS {
+    // SAFETY: The framework invokes `EvtDriverDeviceAdd` at `PASSIVE_LEVEL`
+    let irql = unsafe { PassiveLevel::new_unchecked() };
+    match add_device(driver, &mut device_init, irql) {
+        Ok(()) => STATUS_SUCCESS,
+        Err(nt_status) => {
+            println!("Adding the device failed: {nt_status:#010X}");
+            nt_status
+        }
+    }
+}
+
+fn add_device(
+    driver: WDFDRIVER,
+    device_init: &mut *mut WDFDEVICE_INIT,
+    irql: PassiveLevel,
+) -> Result<(), NTSTATUS> {
+    let mut device_attributes = object_attributes(None);
+    device_attributes.EvtCleanupCallback = Some(evt_device_cleanup);
+    let mut wdf_device: WDFDEVICE = WDF_NO_HANDLE.cast();
+
+    let nt_status;
+    // SAFETY: `device_init` is provided by `EvtDriverDeviceAdd` and is never null,
+    // and `device_attributes` outlives the call
+    unsafe {
+        nt_status = call_unsafe_wdf_function_binding!(
+            WdfDeviceCreate,
+            device_init,
+            &mut device_attributes,
+            &mut wdf_device,
+        );
+    }
+    if !nt_success(nt_status) {
+        return Err(nt_status);
+    }
+    // SAFETY: `wdf_device` was just created, and the framework deletes it only
+    // after the callbacks of its children that use it returned
+    let device = unsafe { Device::from_raw(wdf_device) };
+
+    let index = create_symbolic_link(&device, irql)?;
+    println!("Adding device {index}");
+    device.set_context(DeviceState {
+        index,
+        pended: TimedRequestStore::try_new(&mut object_attributes(None))?,
+        stats: Arc::try_new(Stats {
+            lock: SpinLock::try_new(&mut object_attributes(None))?,
+            counts: UnsafeCell::new(Counts::default()),
+        })?,
+    })?;
+    Driver::get()
+        .ok_or(STATUS_INVALID_DEVICE_STATE)?
+        .register_device(&device)?;
+
+    let presented_requests_limit = read_presented_requests_limit(driver);
+    println!("Presented requests limit: {presented_requests_limit:?}");
+
+    let mut queue_config = WDF_IO_QUEUE_CONFIG {
+        Size: wdf_struct_size::<WDF_IO_QUEUE_CONFIG>(),
+        PowerManaged: _WDF_TRI_STATE::WdfUseDefault,
+        DefaultQueue: u8::from(true),
+        EvtIoDeviceControl: Some(evt_io_device_control),
+        ..WDF_IO_QUEUE_CONFIG::default()
+    };
+    IoQueueDispatchType::Parallel {
+        presented_requests_limit,
+    }
+    .apply(&mut queue_config);
+    IoQueue::set_evt_io_stop::<CancelPendedRequests>(&mut queue_config);
+
+    // SAFETY: `wdf_device` was just created and is not deleted before the queue,
+    // which is its child
+    unsafe {
+        IoQueue::try_new(
+            wdf_device,
+            &mut queue_config,
+            &mut object_attributes(Some(wdf_device.cast())),
+        )?;
+    }
+
+    let mut timer_config = WDF_TIMER_CONFIG {
+        Size: wdf_struct_size::<WDF_TIMER_CONFIG>(),
+        EvtTimerFunc: Some(evt_expiry_timer),
+        Period: EXPIRY_TIMER_PERIOD_MS,
+        ..WDF_TIMER_CONFIG::default()
+    };
+    Timer::set_automatic_serialization(&mut timer_config, false);
+    // The timer is a child of the device, so the framework stops it and waits for
+    // its callback before the device is cleaned up
+    let timer = Timer::try_new(
+        &mut timer_config,
+        &mut object_attributes(Some(wdf_device.cast())),
+    )?;
+    timer.start_in(Duration::from_millis(EXPIRY_TIMER_PERIOD_MS.into()));
+
+    Ok(())
+}
+
+/// Create the symbolic link of `device` with the lowest index that is not in
+/// use by another device of the driver, and return the index
+fn create_symbolic_link(device: &Device, irql: PassiveLevel) -> Result<u32, NTSTATUS> {
+    let driver = Driver::get().ok_or(STATUS_INVALID_DEVICE_STATE)?;
+    for index in 0..MAX_DEVICES {
+        let in_use = driver.devices().any(|other| {
+            other
+                .context::<DeviceState>()
+                .is_some_and(|state| state.index == index)
+        });
+        if in_use {
+            continue;
+        }
+        let symbolic_link_name = UnicodeString::try_from(
+            try_format(format_args!("{SYMBOLIC_LINK_NAME_PREFIX}{index}"))?.as_str(),
+        )?;
+        // A device added concurrently may have taken the index since the devices
+        // were enumerated, or a removed device may still hold its link
+        match device.create_symbolic_link(symbolic_link_name.as_unicode_str(), irql) {
+            Ok(()) => return Ok(index),
+            Err(STATUS_OBJECT_NAME_COLLISION) => {}
+            Err(nt_status) => return Err(nt_status),
+        }
+    }
+    Err(STATUS_OBJECT_NAME_COLLISION)
+}
+
+/// Read the maximum number of requests the driver owns at a time from the
+/// `Parameters` key of the driver. The queue has no limit when the value is
+/// missing or 0.
+fn read_presented_requests_limit(driver: WDFDRIVER) -> Option<ULONG> {
+    let value_name = UnicodeString::try_from(PRESENTED_REQUESTS_LIMIT_VALUE_NAME).ok()?;
+    let mut key: WDFKEY = WDF_NO_HANDLE.cast();
+
+    let nt_status;
+    // SAFETY: `driver` is provided by `EvtDriverDeviceAdd`, and the key attributes
+    // are allowed to be null
+    unsafe {
+        nt_status = call_unsafe_wdf_function_binding!(
+            WdfDriverOpenParametersRegistryKey,
+            driver,
+            KEY_QUERY_VALUE,
+            WDF_NO_OBJECT_ATTRIBUTES,
+            &mut key,
+        );
+    }
+    if !nt_success(nt_status) {
+        return None;
+    }
+
+    let mut limit: ULONG = 0;
+    let value_name = value_name.as_raw();
+    let nt_status;
+    // SAFETY: `key` was just opened, and `value_name` and `limit` outlive the call
+    unsafe {
+        nt_status =
+            call_unsafe_wdf_function_binding!(WdfRegistryQueryULong, key, &value_name, &mut limit);
+    }
+    // SAFETY: `key` was opened above and is not used after it is closed
+    unsafe {
+        call_unsafe_wdf_function_binding!(WdfRegistryClose, key);
+    }
+
+    (nt_success(nt_status) && limit != 0).then_some(limit)
+}
+
+extern "C" fn evt_device_cleanup(wdf_device: WDFOBJECT) {
+    // SAFETY: The framework passes the device being cleaned up, which is valid
+    // until the callback returns
+    let device = unsafe { Device::from_raw(wdf_device.cast()) };
+    if let Some(state) = device.context::<DeviceState>() {
+        println!("Removing device {}", state.index);
+        state.pended.cancel_all();
+    }
+}
+
+extern "C" fn evt_io_device_control(
+    wdf_queue: WDFQUEUE,
+    wdf_request: WDFREQUEST,
+    _output_buffer_length: usize,
+    _input_buffer_length: usize,
+    io_control_code: ULONG,
+) {
+    // SAFETY: `wdf_request` is provided by `EvtIoDeviceControl` and is owned by the
+    // driver until it is completed
+    let mut request = unsafe { Request::from_raw(wdf_request) };
+    // SAFETY: `wdf_queue` is provided by `EvtIoDeviceControl`, and is valid until
+    // the callback returns
+    let device = unsafe { IoQueue::from_raw(wdf_queue) }.get_device();
+    let Some(state) = device.context::<DeviceState>() else {
+        request.complete(STATUS_INVALID_DEVICE_STATE);
+        return;
+    };
+
+    match ControlCode::from_raw(io_control_code) {
+        IOCTL_PARALLEL_IO_ECHO => {
+            state.stats.record(|counts| counts.echoed += 1);
+            request.complete(STATUS_SUCCESS);
+        }
+        IOCTL_PARALLEL_IO_PEND => {
+            let timeout_ms = match read_input::<u32>(&mut request) {
+                Ok(timeout_ms) => timeout_ms,
+                Err(nt_status) => {
+                    request.complete(nt_status);
+                    return;
+                }
+            };
+            if let Err((request, nt_status)) = TimedRequestStore::pend(
+                &state.pended,
+                request,
+                Duration::from_millis(timeout_ms.into()),
+            ) {
+                request.complete(nt_status);
+                return;
+            }
+            state.stats.record(|counts| counts.pended += 1);
+        }
+        code @ (IOCTL_PARALLEL_IO_STATS | IOCTL_PARALLEL_IO_DRIVER_STATS) => {
+            let counts = if code == IOCTL_PARALLEL_IO_STATS {
+                state.stats.snapshot()
+            } else {
+                driver_counts()
+            };
+            match write_output(&mut request, counts) {
+                Ok(()) => request
+                    .complete_with_information(STATUS_SUCCESS, core::mem::size_of::<Counts>()),
+                Err(nt_status) => request.complete(nt_status),
+            }
+        }
+        _ => request.complete(STATUS_INVALID_DEVICE_REQUEST),
+    }
+}
+
+/// Sum the [`Counts`] of all the devices of the driver
+fn driver_counts() -> Counts {
+    let mut counts = Counts::default();
+    for device in Driver::get().iter().flat_map(Driver::devices) {
+        if let Some(state) = device.context::<DeviceState>() {
+            counts.add(&state.stats.snapshot());
+        }
+    }
+    counts
+}
+
+/// Completes the pended requests when the queue stops, as the framework waits
+/// for the requests the driver owns before it powers down or removes the
+/// device
+struct CancelPendedRequests;
+
+impl IoQueueStopCallback for CancelPendedRequests {
+    fn stop(queue: &IoQueue, _request: &Request, _flags: RequestStopActionFlags) {
+        // Only pended requests are still owned by the driver when the queue stops
+        if let Some(state) = queue.get_device().context::<DeviceState>() {
+            state.pended.cancel_all();
+        }
+    }
+}
+
+extern "C" fn evt_expiry_timer(wdf_timer: WDFTIMER) {
+    let wdf_device;
+    // SAFETY: `wdf_timer` is provided by `EvtTimerFunc`, and is valid until the
+    // callback returns
+    unsafe {
+        wdf_device = call_unsafe_wdf_function_binding!(WdfTimerGetParentObject, wdf_timer);
+    }
+    // SAFETY: The timer is a child of its device, so the device outlives the
+    // callback
+    let device = unsafe { Device::from_raw(wdf_device.cast()) };
+    if let Some(state) = device.context::<DeviceState>() {
+        let timed_out = state.pended.complete_expired();
+        if timed_out != 0 {
+            state
+                .stats
+                .record(|counts| counts.timed_out += u64::try_from(timed_out).unwrap_or(u64::MAX));
+        }
+    }
+}
+
+/// Read a `T` from the input buffer of `request`. Any bytes must be a valid
+/// `T`.
+fn read_input<T: Copy>(request: &mut Request) -> Result<T, NTSTATUS> {
+    let buffer = request.retrieve_input_buffer(core::mem::size_of::<T>())?;
+    // SAFETY: The framework checked that the buffer holds at least a `T`, and any
+    // bytes are a valid `T`. Buffered I/O buffers are not necessarily aligned for
+    // `T`.
+    Ok(unsafe { buffer.as_ptr().cast::<T>().read_unaligned() })
+}
+
+/// Write `value` to the output buffer of `request`
+fn write_output<T: Copy>(request: &mut Request, value: T) -> Result<(), NTSTATUS> {
+    let buffer = request.retrieve_output_buffer(core::mem::size_of::<T>())?;
+    // SAFETY: The framework checked that the buffer holds at least a `T`. Buffered
+    // I/O buffers are not necessarily aligned for `T`.
+    unsafe { buffer.as_mut_ptr().cast::<T>().write_unaligned(value) };
+    Ok(())
+}
+
+/// Attributes of an object without context, parented to `parent`, or to the
+/// driver when `None`
+fn object_attributes(parent: Option<WDFOBJECT>) -> WDF_OBJECT_ATTRIBUTES {
+    WDF_OBJECT_ATTRIBUTES {
+        Size: wdf_struct_size::<WDF_OBJECT_ATTRIBUTES>(),
+        ExecutionLevel: _WDF_EXECUTION_LEVEL::WdfExecutionLevelInheritFromParent,
+        SynchronizationScope: _WDF_SYNCHRONIZATION_SCOPE::WdfSynchronizationScopeInheritFromParent,
+        ParentObject: parent.unwrap_or(core::ptr::null_mut()),
+        ..WDF_OBJECT_ATTRIBUTES::default()
+    }
+}
+
+/// Size of the framework structure `T`, as stored in its `Size` field
+const fn wdf_struct_size<T>() -> ULONG {
+    const { assert!(core::mem::size_of::<T>() <= ULONG::MAX as usize) }
+
+    // clippy::cast_possible_truncation cannot currently check compile-time constants: https://github.com/rust-lang/rust-clippy/issues/9613
+    #[allow(clippy::cast_possible_truncation)]
+    {
+        core::mem::size_of::<T>() as ULONG
+    }
+}
+
+/// Build a device control code of the device type of the driver, for buffered
+/// I/O that does not require any access rights
+const fn ctl_code(function: ULONG) -> ControlCode {
+    ControlCode::new(
+        FILE_DEVICE_UNKNOWN,
+        function,
+        TransferMethod::Buffered,
+        RequiredAccess::Any,
+    )
+}
+
+extern "C" fn driver_exit(_driver: *mut DRIVER_OBJECT) {
+    println!("Parallel I/O Driver Exit Complete!");
+}
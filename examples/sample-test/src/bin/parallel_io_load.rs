@@ -0,0 +1,428 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! # Parallel I/O Load Generator
+//!
+//! Sends device control requests to the devices of the `parallel-io-driver`
+//! sample from many threads at a time, while another thread periodically
+//! cancels the requests in flight. Prints the throughput and the outcomes of
+//! the requests, and fails if a request fails unexpectedly or if the counts
+//! reported by the devices, or by the driver for all of them, do not match the
+//! outcomes.
+
+use std::{process::ExitCode, str::FromStr};
+
+/// Options of a run, parsed from the command line
+struct Options {
+    /// Number of devices the threads are spread across, starting with device 0
+    devices: usize,
+    /// Number of threads sending requests, each with its own handle
+    threads: usize,
+    /// Number of requests sent by each thread
+    requests: u64,
+    /// Every `pend_every`th request of a thread is a pend request, or none
+    /// when 0
+    pend_every: u64,
+    /// Timeout of the pend requests
+    pend_timeout_ms: u32,
+    /// Interval at which the requests in flight are cancelled, or never when 0
+    cancel_interval_ms: u64,
+}
+
+const USAGE: &str = "\
+Usage: parallel-io-load [OPTIONS]
+
+Options:
+  --devices <N>             Devices the threads are spread across [default: 1]
+  --threads <N>             Threads sending requests [default: 8]
+  --requests <N>            Requests sent by each thread [default: 10000]
+  --pend-every <N>          Send a pend request every N requests, 0 for none [default: 10]
+  --pend-timeout-ms <MS>    Timeout of the pend requests [default: 50]
+  --cancel-interval-ms <MS> Cancel the requests in flight every MS, 0 for never [default: 20]
+  -h, --help                Print this help";
+
+impl Options {
+    /// Parse the options from `args`, or return `None` if help was requested
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut options = Self {
+            devices: 1,
+            threads: 8,
+            requests: 10_000,
+            pend_every: 10,
+            pend_timeout_ms: 50,
+            cancel_interval_ms: 20,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--devices" => options.devices = parse_value(&arg, args.next())?,
+                "--threads" => options.threads = parse_value(&arg, args.next())?,
+                "--requests" => options.requests = parse_value(&arg, args.next())?,
+                "--pend-every" => options.pend_every = parse_value(&arg, args.next())?,
+                "--pend-timeout-ms" => options.pend_timeout_ms = parse_value(&arg, args.next())?,
+                "--cancel-interval-ms" => {
+                    options.cancel_interval_ms = parse_value(&arg, args.next())?;
+                }
+                "-h" | "--help" => return Ok(None),
+                _ => return Err(format!("unexpected argument: {arg}")),
+            }
+        }
+        if options.devices == 0 {
+            return Err("--devices must be at least 1".to_string());
+        }
+        if options.threads < options.devices {
+            return Err("--threads must be at least --devices".to_string());
+        }
+        Ok(Some(options))
+    }
+}
+
+fn parse_value<T: FromStr>(name: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("missing value for {name}"))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {name}: {value}"))
+}
+
+fn main() -> ExitCode {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("error: {error}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match load::run(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(windows)]
+mod load {
+    use std::{
+        ffi::c_void,
+        fs::{File, OpenOptions},
+        io,
+        os::windows::io::AsRawHandle,
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        },
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::Options;
+
+    /// Prefix of the paths of the devices, as created by the symbolic links of
+    /// the driver, followed by the index of the device
+    const DEVICE_PATH_PREFIX: &str = r"\\.\ParallelIoDriver";
+
+    const FILE_DEVICE_UNKNOWN: u32 = 0x22;
+    const METHOD_BUFFERED: u32 = 0;
+    const FILE_ANY_ACCESS: u32 = 0;
+
+    // Must match the device control codes of the driver
+    const IOCTL_PARALLEL_IO_ECHO: u32 = ctl_code(0x800);
+    const IOCTL_PARALLEL_IO_PEND: u32 = ctl_code(0x801);
+    const IOCTL_PARALLEL_IO_STATS: u32 = ctl_code(0x802);
+    const IOCTL_PARALLEL_IO_DRIVER_STATS: u32 = ctl_code(0x803);
+
+    /// Win32 error of requests completed with `STATUS_IO_TIMEOUT`
+    const ERROR_SEM_TIMEOUT: i32 = 121;
+    /// Win32 error of requests completed with `STATUS_CANCELLED`
+    const ERROR_OPERATION_ABORTED: i32 = 995;
+    /// Win32 error of requests completed with `STATUS_INSUFFICIENT_RESOURCES`,
+    /// i.e. pend requests sent while the store of the driver is full
+    const ERROR_NO_SYSTEM_RESOURCES: i32 = 1450;
+
+    /// Counts of the requests served by the driver. Must match the layout of
+    /// `Counts` of the driver.
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, Default)]
+    struct Counts {
+        echoed: u64,
+        pended: u64,
+        timed_out: u64,
+    }
+
+    /// Outcomes of the requests sent by the load generator
+    #[derive(Clone, Copy, Debug, Default)]
+    struct Outcomes {
+        echoed: u64,
+        timed_out: u64,
+        cancelled: u64,
+        rejected: u64,
+        failed: u64,
+    }
+
+    impl Outcomes {
+        const fn total(&self) -> u64 {
+            self.echoed + self.timed_out + self.cancelled + self.rejected + self.failed
+        }
+
+        const fn add(&mut self, other: &Self) {
+            self.echoed += other.echoed;
+            self.timed_out += other.timed_out;
+            self.cancelled += other.cancelled;
+            self.rejected += other.rejected;
+            self.failed += other.failed;
+        }
+    }
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn DeviceIoControl(
+            device: *mut c_void,
+            io_control_code: u32,
+            in_buffer: *const c_void,
+            in_buffer_size: u32,
+            out_buffer: *mut c_void,
+            out_buffer_size: u32,
+            bytes_returned: *mut u32,
+            overlapped: *mut c_void,
+        ) -> i32;
+
+        fn CancelIoEx(file: *mut c_void, overlapped: *mut c_void) -> i32;
+    }
+
+    pub fn run(options: &Options) -> Result<(), String> {
+        // Requests sent through the same synchronous handle are serialized by the
+        // I/O manager, so each thread gets its own handle. Thread `index` uses
+        // device `index % devices`, so handle `device` is the first one of each
+        // device.
+        let handles = (0..options.threads)
+            .map(|index| open_device(index % options.devices))
+            .collect::<Result<Vec<_>, _>>()?;
+        let handles = Arc::new(handles);
+
+        let before = (0..options.devices)
+            .map(|device| query_counts(&handles[device], IOCTL_PARALLEL_IO_STATS))
+            .collect::<Result<Vec<_>, _>>()?;
+        let driver_before = query_counts(&handles[0], IOCTL_PARALLEL_IO_DRIVER_STATS)?;
+        let start = Instant::now();
+
+        let done = Arc::new(AtomicBool::new(false));
+        let canceller = (options.cancel_interval_ms != 0).then(|| {
+            let handles = Arc::clone(&handles);
+            let done = Arc::clone(&done);
+            let interval = Duration::from_millis(options.cancel_interval_ms);
+            thread::spawn(move || {
+                while !done.load(Ordering::Relaxed) {
+                    thread::sleep(interval);
+                    for handle in handles.iter() {
+                        cancel_io(handle);
+                    }
+                }
+            })
+        });
+
+        let workers = (0..options.threads)
+            .map(|index| {
+                let handles = Arc::clone(&handles);
+                let requests = options.requests;
+                let pend_every = options.pend_every;
+                let pend_timeout_ms = options.pend_timeout_ms;
+                thread::spawn(move || {
+                    send_requests(&handles[index], requests, pend_every, pend_timeout_ms)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut outcomes = vec![Outcomes::default(); options.devices];
+        for (index, worker) in workers.into_iter().enumerate() {
+            let worker_outcomes = worker
+                .join()
+                .map_err(|_| "a worker thread panicked".to_string())?;
+            outcomes[index % options.devices].add(&worker_outcomes);
+        }
+        let elapsed = start.elapsed();
+        done.store(true, Ordering::Relaxed);
+        if let Some(canceller) = canceller {
+            canceller
+                .join()
+                .map_err(|_| "the canceller thread panicked".to_string())?;
+        }
+
+        let after = (0..options.devices)
+            .map(|device| query_counts(&handles[device], IOCTL_PARALLEL_IO_STATS))
+            .collect::<Result<Vec<_>, _>>()?;
+        let driver_after = query_counts(&handles[0], IOCTL_PARALLEL_IO_DRIVER_STATS)?;
+
+        let mut total = Outcomes::default();
+        for device_outcomes in &outcomes {
+            total.add(device_outcomes);
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let throughput = total.total() as f64 / elapsed.as_secs_f64();
+        println!(
+            "{} requests in {elapsed:.2?} ({throughput:.0} requests/s)",
+            total.total()
+        );
+        println!("{total:#?}");
+        println!(
+            "Driver counts delta: {:#?}",
+            counts_delta(&driver_before, &driver_after)
+        );
+
+        if total.failed != 0 {
+            return Err(format!("{} requests failed unexpectedly", total.failed));
+        }
+        // Echo requests that reach the driver always succeed, and pend requests
+        // that time out are completed by the driver, so these must match unless
+        // another application uses the devices
+        for (device, device_outcomes) in outcomes.iter().enumerate() {
+            check_counts(
+                &format!("device {device}"),
+                &counts_delta(&before[device], &after[device]),
+                device_outcomes,
+            )?;
+        }
+        check_counts(
+            "the driver",
+            &counts_delta(&driver_before, &driver_after),
+            &total,
+        )
+    }
+
+    /// Check that the counts reported by `source` match the outcomes of the
+    /// requests sent to it
+    fn check_counts(source: &str, delta: &Counts, outcomes: &Outcomes) -> Result<(), String> {
+        if delta.echoed != outcomes.echoed {
+            return Err(format!(
+                "{source} counted a different number of echo requests"
+            ));
+        }
+        if delta.timed_out != outcomes.timed_out {
+            return Err(format!(
+                "{source} counted a different number of timed out requests"
+            ));
+        }
+        Ok(())
+    }
+
+    fn send_requests(
+        device: &File,
+        requests: u64,
+        pend_every: u64,
+        pend_timeout_ms: u32,
+    ) -> Outcomes {
+        let mut outcomes = Outcomes::default();
+        for index in 1..=requests {
+            let is_pend = pend_every != 0 && index % pend_every == 0;
+            let result = if is_pend {
+                device_io_control::<u32, ()>(
+                    device,
+                    IOCTL_PARALLEL_IO_PEND,
+                    Some(&pend_timeout_ms),
+                    None,
+                )
+            } else {
+                device_io_control::<(), ()>(device, IOCTL_PARALLEL_IO_ECHO, None, None)
+            };
+            match (result.map_err(|error| error.raw_os_error()), is_pend) {
+                (Ok(()), false) => outcomes.echoed += 1,
+                (Err(Some(ERROR_SEM_TIMEOUT)), true) => outcomes.timed_out += 1,
+                (Err(Some(ERROR_OPERATION_ABORTED)), _) => outcomes.cancelled += 1,
+                (Err(Some(ERROR_NO_SYSTEM_RESOURCES)), true) => outcomes.rejected += 1,
+                _ => outcomes.failed += 1,
+            }
+        }
+        outcomes
+    }
+
+    fn open_device(device: usize) -> Result<File, String> {
+        let path = format!("{DEVICE_PATH_PREFIX}{device}");
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .map_err(|error| format!("failed to open {path}: {error}"))
+    }
+
+    /// Query the counts of the device of `handle`, or of all the devices of the
+    /// driver, with `io_control_code`
+    fn query_counts(handle: &File, io_control_code: u32) -> Result<Counts, String> {
+        let mut counts = Counts::default();
+        device_io_control::<(), Counts>(handle, io_control_code, None, Some(&mut counts))
+            .map_err(|error| format!("failed to query the counts of the driver: {error}"))?;
+        Ok(counts)
+    }
+
+    const fn counts_delta(before: &Counts, after: &Counts) -> Counts {
+        Counts {
+            echoed: after.echoed - before.echoed,
+            pended: after.pended - before.pended,
+            timed_out: after.timed_out - before.timed_out,
+        }
+    }
+
+    fn device_io_control<I, O>(
+        device: &File,
+        io_control_code: u32,
+        input: Option<&I>,
+        output: Option<&mut O>,
+    ) -> io::Result<()> {
+        let (in_buffer, in_buffer_size) = input.map_or((std::ptr::null(), 0), |input| {
+            (std::ptr::from_ref(input).cast(), buffer_size::<I>())
+        });
+        let (out_buffer, out_buffer_size) = output.map_or((std::ptr::null_mut(), 0), |output| {
+            (std::ptr::from_mut(output).cast(), buffer_size::<O>())
+        });
+        let mut bytes_returned = 0;
+
+        // SAFETY: The handle of `device` is open, the buffers are valid for their
+        // sizes and outlive the call, which is synchronous as the handle is not
+        // opened for overlapped I/O
+        let succeeded = unsafe {
+            DeviceIoControl(
+                device.as_raw_handle(),
+                io_control_code,
+                in_buffer,
+                in_buffer_size,
+                out_buffer,
+                out_buffer_size,
+                &raw mut bytes_returned,
+                std::ptr::null_mut(),
+            )
+        };
+        if succeeded == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Cancel the requests in flight on `device`, whichever thread sent them
+    fn cancel_io(device: &File) {
+        // SAFETY: The handle of `device` is open. Failures, ex. when no request is
+        // in flight, are expected and ignored.
+        unsafe {
+            CancelIoEx(device.as_raw_handle(), std::ptr::null_mut());
+        }
+    }
+
+    fn buffer_size<T>() -> u32 {
+        u32::try_from(size_of::<T>()).expect("buffers of the device control codes fit in a u32")
+    }
+
+    const fn ctl_code(function: u32) -> u32 {
+        (FILE_DEVICE_UNKNOWN << 16) | (FILE_ANY_ACCESS << 14) | (function << 2) | METHOD_BUFFERED
+    }
+}
+
+#[cfg(not(windows))]
+mod load {
+    use super::Options;
+
+    pub fn run(_options: &Options) -> Result<(), String> {
+        Err("parallel-io-load only runs on Windows".to_string())
+    }
+}